@@ -0,0 +1,93 @@
+use api::auth::binding::TokenBinding;
+use api::auth::keyring::Keyring;
+use api::auth::{sign_token_direct, verify_token_direct, TokenData, TokenValidator};
+use api::models::TierType;
+use api::redis_util::RedisConnection;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ed25519_dalek::SigningKey;
+use std::time::Duration;
+use uuid::Uuid;
+
+// Every request verifies its API key, so these guard the latency budget
+// against dependency bumps and claim additions. Compare runs with
+// `cargo bench --bench auth_bench -- --save-baseline main` before a change
+// and `-- --baseline main` after it.
+
+fn token_data() -> TokenData {
+    TokenData {
+        org_id: Uuid::now_v7(),
+        key_id: Uuid::now_v7(),
+        tier: TierType::Free,
+        max_tokens: 128,
+        monthly_quota: 20000,
+        require_signing: false,
+        max_concurrency: None,
+        scopes: Some(vec!["embed".to_string()]),
+        models: None,
+        api_version: None,
+        strict_validation: false,
+        generation: 0,
+    }
+}
+
+/// A signed token with the keyring and binding that verify it
+fn signed_token() -> (String, Keyring, TokenBinding) {
+    let signing_key = SigningKey::from_bytes(&[7; 32]);
+    let keyring = Keyring::new(vec![signing_key.verifying_key()]);
+    let binding = TokenBinding {
+        issuer: "smally".to_string(),
+        audience: "smally-api".to_string(),
+        legacy_until: None,
+    };
+    let token = sign_token_direct(&token_data(), &signing_key, &binding).unwrap();
+    (token, keyring, binding)
+}
+
+fn bench_verify_token_direct(c: &mut Criterion) {
+    let (token, keyring, binding) = signed_token();
+
+    c.bench_function("verify_token_direct", |b| {
+        b.iter(|| verify_token_direct(black_box(&token), &keyring, &binding).unwrap())
+    });
+}
+
+fn bench_validate_cache_hit(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    // The validator needs a Redis connection, though a cache hit never uses it
+    let connect = async {
+        let settings = api::config::get_settings();
+        tokio::time::timeout(Duration::from_secs(2), RedisConnection::connect(settings)).await
+    };
+    let redis = match runtime.block_on(connect) {
+        Ok(Ok(redis)) => redis,
+        Ok(Err(e)) => {
+            eprintln!("Failed to connect to Redis: {}.", e);
+            eprintln!("Skipping validate benchmark (set REDIS_URL).");
+            return;
+        }
+        Err(_) => {
+            eprintln!("Timed out connecting to Redis.");
+            eprintln!("Skipping validate benchmark (set REDIS_URL).");
+            return;
+        }
+    };
+
+    let (token, keyring, binding) = signed_token();
+    let validator = runtime
+        .block_on(TokenValidator::new(keyring, redis, 300, 3600, binding))
+        .unwrap();
+    // First call looks the key up in Redis and caches its status
+    runtime.block_on(validator.validate(&token)).unwrap();
+
+    c.bench_function("validate_cache_hit", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(validator.validate(black_box(&token)))
+                .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, bench_verify_token_direct, bench_validate_cache_hit);
+criterion_main!(benches);
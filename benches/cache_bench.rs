@@ -1,5 +1,7 @@
+use api::cache::admission::{Admission, AdmissionPolicy};
 use api::cache::lru::LruCache;
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 fn bench_lru_put(c: &mut Criterion) {
     let mut group = c.benchmark_group("lru_put");
@@ -96,11 +98,84 @@ fn bench_lru_mixed_workload(c: &mut Criterion) {
     group.finish();
 }
 
+/// Cache keys of a request stream: texts drawn from a Zipf(1.0)
+/// distribution over `texts` ranks, with `one_off` of the requests texts
+/// that never repeat (ids, one-time queries)
+fn zipf_trace(requests: usize, texts: usize, one_off: f64) -> Vec<String> {
+    let weights: Vec<f64> = (1..=texts).map(|rank| 1.0 / rank as f64).collect();
+    let total: f64 = weights.iter().sum();
+    let mut cdf = Vec::with_capacity(texts);
+    let mut sum = 0.0;
+    for weight in weights {
+        sum += weight / total;
+        cdf.push(sum);
+    }
+
+    let mut rng = StdRng::seed_from_u64(42);
+    (0..requests)
+        .map(|i| {
+            let text = if rng.gen_bool(one_off) {
+                format!("one-off {} {}", i, rng.gen::<u64>())
+            } else {
+                let rank = cdf.partition_point(|&p| p < rng.gen::<f64>());
+                format!("text {}", rank)
+            };
+            api::cache::content_hash(&text, "bench-model", true)
+        })
+        .collect()
+}
+
+/// Hit rate of an L1 cache of `capacity` replaying `trace`
+fn replay(trace: &[String], capacity: usize, policy: AdmissionPolicy) -> f64 {
+    let mut cache = LruCache::new(capacity);
+    let admission = Admission::new(policy, capacity);
+    let mut hits = 0;
+    for key in trace {
+        admission.record(key);
+        if cache.get(key).is_some() {
+            hits += 1;
+        } else if admission.admits(&cache, key) {
+            cache.put(key.clone(), ());
+        }
+    }
+    hits as f64 / trace.len() as f64
+}
+
+fn bench_admission_replay(c: &mut Criterion) {
+    let trace = zipf_trace(200_000, 100_000, 0.3);
+    let capacity = 2_000;
+    for (name, policy) in [
+        ("always", AdmissionPolicy::Always),
+        ("tinylfu", AdmissionPolicy::TinyLfu),
+    ] {
+        println!(
+            "admission_replay/{}: hit rate {:.1}% (L1 of {}, {} requests)",
+            name,
+            replay(&trace, capacity, policy) * 100.0,
+            capacity,
+            trace.len()
+        );
+    }
+
+    let mut group = c.benchmark_group("admission_replay");
+    group.sample_size(10);
+    for (name, policy) in [
+        ("always", AdmissionPolicy::Always),
+        ("tinylfu", AdmissionPolicy::TinyLfu),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| replay(black_box(&trace), capacity, policy))
+        });
+    }
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_lru_put,
     bench_lru_get_hit,
     bench_lru_get_miss,
-    bench_lru_mixed_workload
+    bench_lru_mixed_workload,
+    bench_admission_replay
 );
 criterion_main!(benches);
@@ -81,5 +81,47 @@ fn bench_normalize_impact(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_embedding_generation, bench_normalize_impact);
+fn bench_batch_vs_sequential(c: &mut Criterion) {
+    let model_path = std::path::Path::new("models/all-MiniLM-L6-v2-onnx");
+    if !model_path.exists() {
+        eprintln!("Model not found. Skipping benchmark.");
+        return;
+    }
+
+    let mut model = match api::inference::EmbeddingModel::new() {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("Failed to load model: {}. Skipping benchmark.", e);
+            return;
+        }
+    };
+
+    let mut group = c.benchmark_group("batch_32");
+
+    let texts: Vec<String> = (0..32)
+        .map(|i| format!("product description number {} with a few more words", i))
+        .collect();
+    let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            for text in &texts {
+                let _ = model.encode(black_box(text), black_box(true));
+            }
+        })
+    });
+
+    group.bench_function("batched", |b| {
+        b.iter(|| model.encode_batch(black_box(&texts), black_box(true)))
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_embedding_generation,
+    bench_normalize_impact,
+    bench_batch_vs_sequential
+);
 criterion_main!(benches);
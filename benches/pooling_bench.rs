@@ -0,0 +1,76 @@
+use api::inference::kernels::{self, scalar};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+// Compares the portable pooling/normalization loops with the SIMD kernels
+// selected for this CPU (`kernels::active()`). No model needed.
+
+const SEQ_LEN: usize = 128;
+
+fn hidden_states(dim: usize) -> Vec<f32> {
+    (0..SEQ_LEN * dim)
+        .map(|i| ((i * 7919) % 1000) as f32 / 500.0 - 1.0)
+        .collect()
+}
+
+fn bench_mean_pool(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mean_pool");
+    let active = kernels::active().as_str();
+
+    for dim in [384, 768] {
+        let states = hidden_states(dim);
+
+        group.bench_with_input(BenchmarkId::new("scalar", dim), &states, |b, states| {
+            b.iter(|| {
+                let mut acc = vec![0.0f32; dim];
+                for row in states.chunks_exact(dim) {
+                    scalar::add_scaled(&mut acc, black_box(row), 1.0);
+                }
+                scalar::scale(&mut acc, 1.0 / SEQ_LEN as f32);
+                acc
+            })
+        });
+        group.bench_with_input(BenchmarkId::new(active, dim), &states, |b, states| {
+            b.iter(|| {
+                let mut acc = vec![0.0f32; dim];
+                for row in states.chunks_exact(dim) {
+                    kernels::add_scaled(&mut acc, black_box(row), 1.0);
+                }
+                kernels::scale(&mut acc, 1.0 / SEQ_LEN as f32);
+                acc
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_l2_normalize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("l2_normalize");
+    let active = kernels::active().as_str();
+
+    for dim in [384, 768] {
+        let v = hidden_states(dim)[..dim].to_vec();
+
+        group.bench_with_input(BenchmarkId::new("scalar", dim), &v, |b, v| {
+            b.iter(|| {
+                let mut v = v.clone();
+                let norm = scalar::dot(&v, &v).sqrt();
+                scalar::scale(&mut v, 1.0 / norm);
+                v
+            })
+        });
+        group.bench_with_input(BenchmarkId::new(active, dim), &v, |b, v| {
+            b.iter(|| {
+                let mut v = v.clone();
+                let norm = kernels::sum_squares(&v).sqrt();
+                kernels::scale(&mut v, 1.0 / norm);
+                v
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_mean_pool, bench_l2_normalize);
+criterion_main!(benches);
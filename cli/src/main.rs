@@ -0,0 +1,297 @@
+//! `smally`: operator commands against a running server, a self-check of
+//! the local environment, and migrations
+//!
+//! ```text
+//! cargo run -p smally-cli -- compare-models --file texts.txt --candidate all-MiniLM-L12-v2
+//! cargo run -p smally-cli -- check
+//! cargo run -p smally-cli -- migrate
+//! ```
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use serde_json::Value;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "smally", about = "Operator commands for a Smally server")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compare the served model with a candidate on a file of texts
+    /// (`POST /v1/admin/models/compare`)
+    CompareModels(CompareArgs),
+    /// Check this environment could serve traffic (configuration, Postgres
+    /// and migrations, Redis, the cache, the model, the token keys) without
+    /// starting the server. Prints a JSON report and exits non-zero if any
+    /// check fails.
+    Check,
+    /// Apply the pending migrations to DATABASE_URL, long-running ones
+    /// included. For deploys with MIGRATIONS_MODE=check or skip, run it
+    /// before the new release starts.
+    Migrate,
+}
+
+#[derive(clap::Args, Debug)]
+struct CompareArgs {
+    /// Texts to compare, one per line (blank lines are skipped)
+    #[arg(long)]
+    file: PathBuf,
+
+    /// Candidate model (a subdirectory of the server's CANDIDATE_MODELS_DIR)
+    #[arg(long)]
+    candidate: String,
+
+    /// Served model to compare against (defaults to the server default)
+    #[arg(long)]
+    baseline: Option<String>,
+
+    /// Least similar texts to list
+    #[arg(long, default_value_t = 10)]
+    worst: usize,
+
+    /// Base URL of the server
+    #[arg(long, env = "SMALLY_URL", default_value = "http://localhost:8000")]
+    url: String,
+
+    /// Admin token (`admin_...`, see `create_admin_token`)
+    #[arg(long, env = "SMALLY_ADMIN_TOKEN")]
+    token: String,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::CompareModels(args) => compare_models(args).await,
+        Command::Check => check().await,
+        Command::Migrate => migrate().await,
+    }
+}
+
+async fn check() -> Result<()> {
+    // Same environment as the server
+    api::config::provenance::load_dotenv().ok();
+
+    let report = api::monitoring::selfcheck::run().await;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if !report.ok {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn migrate() -> Result<()> {
+    api::config::provenance::load_dotenv().ok();
+
+    let applied = api::database::migrations::migrate()
+        .await
+        .context("migrating the database")?;
+    if applied.is_empty() {
+        println!("Database is up to date");
+    }
+    for version in applied {
+        println!("Applied {}", version);
+    }
+    Ok(())
+}
+
+/// Outcome of a comparison, from either response shape
+#[derive(Debug, Default)]
+struct Comparison {
+    /// (index, similarity) per text
+    results: Vec<(usize, f64)>,
+    /// The summary: `baseline`, `candidate` and `stats`
+    summary: Value,
+}
+
+impl Comparison {
+    /// Fold in one NDJSON line of a streamed comparison
+    fn line(&mut self, line: &str) -> Result<()> {
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        let value: Value = serde_json::from_str(line).context("invalid response line")?;
+        if let Some(error) = value.get("error") {
+            bail!(
+                "comparison failed at text {}: {}",
+                value["index"],
+                error.as_str().unwrap_or_default()
+            );
+        }
+        if value.get("stats").is_some() {
+            self.summary = value;
+        } else {
+            self.results.push(result(&value)?);
+        }
+        Ok(())
+    }
+
+    fn from_json(mut value: Value) -> Result<Self> {
+        let results = value["results"]
+            .as_array()
+            .context("response without results")?
+            .iter()
+            .map(result)
+            .collect::<Result<_>>()?;
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("results");
+        }
+        Ok(Comparison {
+            results,
+            summary: value,
+        })
+    }
+}
+
+fn result(value: &Value) -> Result<(usize, f64)> {
+    Ok((
+        value["index"].as_u64().context("result without index")? as usize,
+        value["similarity"]
+            .as_f64()
+            .context("result without similarity")?,
+    ))
+}
+
+/// Non-blank lines of the file, in order
+fn read_texts(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+async fn compare_models(args: CompareArgs) -> Result<()> {
+    let contents = std::fs::read_to_string(&args.file)
+        .with_context(|| format!("failed to read {}", args.file.display()))?;
+    let texts = read_texts(&contents);
+    if texts.is_empty() {
+        bail!("{} has no texts", args.file.display());
+    }
+
+    let url = format!("{}/v1/admin/models/compare", args.url.trim_end_matches('/'));
+    let body = serde_json::json!({
+        "texts": texts,
+        "baseline": args.baseline,
+        "candidate": args.candidate,
+    });
+    let mut response = reqwest::Client::new()
+        .post(&url)
+        .bearer_auth(&args.token)
+        .json(&body)
+        .send()
+        .await
+        .with_context(|| format!("failed to reach {}", url))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        bail!("{}: {}", status, response.text().await.unwrap_or_default());
+    }
+
+    let streamed = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/x-ndjson"));
+    let comparison = if streamed {
+        let mut comparison = Comparison::default();
+        let mut buffer = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                comparison.line(&String::from_utf8_lossy(&line))?;
+            }
+            eprint!(
+                "\r{}/{} texts compared",
+                comparison.results.len(),
+                texts.len()
+            );
+        }
+        eprintln!();
+        comparison.line(&String::from_utf8_lossy(&buffer))?;
+        if comparison.summary.is_null() {
+            bail!("the comparison ended early");
+        }
+        comparison
+    } else {
+        Comparison::from_json(response.json().await?)?
+    };
+
+    print!("{}", render(&comparison, &texts, args.worst));
+    Ok(())
+}
+
+fn render(comparison: &Comparison, texts: &[String], worst: usize) -> String {
+    let summary = &comparison.summary;
+    let stats = &summary["stats"];
+    let mut out = format!(
+        "Baseline   {}\nCandidate  {}\nTexts      {}\nMean       {:.4}\nP5         {:.4}\nMin        {:.4}\n",
+        summary["baseline"].as_str().unwrap_or_default(),
+        summary["candidate"].as_str().unwrap_or_default(),
+        stats["count"],
+        stats["mean"].as_f64().unwrap_or_default(),
+        stats["p5"].as_f64().unwrap_or_default(),
+        stats["min"].as_f64().unwrap_or_default(),
+    );
+
+    let mut results = comparison.results.clone();
+    results.sort_by(|a, b| a.1.total_cmp(&b.1));
+    if worst > 0 && !results.is_empty() {
+        out.push_str("\nLeast similar:\n");
+        for (index, similarity) in results.iter().take(worst) {
+            let text: String = texts
+                .get(*index)
+                .map(|text| text.chars().take(72).collect())
+                .unwrap_or_default();
+            out.push_str(&format!("  {:.4}  #{:<5} {}\n", similarity, index, text));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_streamed_and_single_responses_agree() {
+        let mut streamed = Comparison::default();
+        for line in [
+            r#"{"index":0,"similarity":0.99}"#,
+            r#"{"index":1,"similarity":0.5}"#,
+            r#"{"baseline":"a","candidate":"b","stats":{"count":2,"mean":0.745,"p5":0.5,"min":0.5}}"#,
+            "",
+        ] {
+            streamed.line(line).unwrap();
+        }
+
+        let single = Comparison::from_json(json!({
+            "baseline": "a",
+            "candidate": "b",
+            "results": [{"index": 0, "similarity": 0.99}, {"index": 1, "similarity": 0.5}],
+            "stats": {"count": 2, "mean": 0.745, "p5": 0.5, "min": 0.5},
+        }))
+        .unwrap();
+
+        assert_eq!(streamed.results, single.results);
+        assert_eq!(streamed.summary, single.summary);
+
+        let texts = read_texts("first\n\n  \nsecond text\n");
+        assert_eq!(texts, vec!["first", "second text"]);
+        let rendered = render(&single, &texts, 1);
+        assert!(rendered.contains("Mean       0.7450"));
+        assert!(rendered.contains("0.5000  #1     second text"));
+    }
+
+    #[test]
+    fn test_error_line_fails() {
+        let mut comparison = Comparison::default();
+        let err = comparison
+            .line(r#"{"error":"Inference queue is full","index":7}"#)
+            .unwrap_err();
+        assert!(err.to_string().contains("text 7"));
+    }
+}
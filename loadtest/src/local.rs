@@ -0,0 +1,51 @@
+//! `--local`: the embedding endpoint served from this process
+//!
+//! Bootstraps the same `AppState` as the server (database, model, Redis,
+//! token validator, usage buffer), reading `.env` like it, and serves
+//! `/v1/embed` and `/metrics` on a free local port. The API key still has
+//! to be valid for that database and signing key.
+
+use ::api::api;
+use ::api::state::{self, Components};
+use anyhow::Result;
+use axum::{
+    routing::{get, post},
+    Router,
+};
+use prometheus::{Encoder, TextEncoder};
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+
+/// Start the server; returns its base URL
+pub async fn spawn() -> Result<String> {
+    dotenvy::dotenv().ok();
+
+    let state = state::bootstrap(Components::SERVER).await?;
+
+    let app = Router::new()
+        .route("/v1/embed", post(api::create_embedding_handler))
+        .route("/metrics", get(metrics_handler))
+        .layer(axum::middleware::from_fn(
+            api::key_prefix::key_prefix_middleware,
+        ))
+        .layer(axum::middleware::from_fn(api::versions::version_middleware))
+        .with_state(state);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr: SocketAddr = listener.local_addr()?;
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            eprintln!("Local server stopped: {}", e);
+        }
+    });
+
+    Ok(format!("http://{}", addr))
+}
+
+async fn metrics_handler() -> String {
+    let mut buffer = vec![];
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buffer)
+        .unwrap_or_default();
+    String::from_utf8(buffer).unwrap_or_default()
+}
@@ -0,0 +1,199 @@
+//! `smally-loadtest`: find the QPS an instance sustains
+//!
+//! Drives `/v1/embed` of a target server with a fixed number of concurrent
+//! workers for a fixed time, then prints achieved QPS, latency percentiles,
+//! outcome counts (429 rate limited, 503 shed) and how the server's
+//! `smally_*` metrics moved during the run.
+//!
+//! ```text
+//! cargo run --release -p smally-loadtest -- --key sk_... --concurrency 32 --duration 60
+//! cargo run --release -p smally-loadtest -- --key sk_... --local
+//! ```
+
+mod local;
+mod report;
+mod texts;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use report::{Outcome, Report, Samples};
+use texts::{LengthDistribution, TextSource};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "smally-loadtest",
+    about = "Load test /v1/embed and report saturation"
+)]
+struct Args {
+    /// Base URL of the server under test
+    #[arg(long, default_value = "http://localhost:8000")]
+    target: String,
+
+    /// API key sent as the bearer token
+    #[arg(long, env = "SMALLY_API_KEY")]
+    key: String,
+
+    /// Serve /v1/embed from this process instead of --target (needs the
+    /// server's .env: database, Redis, model)
+    #[arg(long)]
+    local: bool,
+
+    /// Concurrent workers, each sending one request at a time
+    #[arg(long, default_value_t = 8)]
+    concurrency: usize,
+
+    /// Measured duration in seconds
+    #[arg(long, default_value_t = 30)]
+    duration: u64,
+
+    /// Text lengths in words with relative weights, `words:weight,...`
+    #[arg(long, default_value = "8:0.6,32:0.3,96:0.1")]
+    lengths: String,
+
+    /// Share of requests reusing a pooled text (cache hits), 0 to 1
+    #[arg(long, default_value_t = 0.5)]
+    cache_hit_ratio: f64,
+
+    /// Texts in the reuse pool (each sent once before measuring)
+    #[arg(long, default_value_t = 200)]
+    pool_size: usize,
+
+    /// Send `truncate: true`, so long texts are cut instead of rejected
+    #[arg(long)]
+    truncate: bool,
+
+    /// Per-request timeout in seconds
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    if !(0.0..=1.0).contains(&args.cache_hit_ratio) {
+        bail!("--cache-hit-ratio must be between 0 and 1");
+    }
+    if args.concurrency == 0 || args.duration == 0 {
+        bail!("--concurrency and --duration must be at least 1");
+    }
+    let lengths = LengthDistribution::parse(&args.lengths).context("invalid --lengths")?;
+
+    let target = if args.local {
+        let url = local::spawn()
+            .await
+            .context("failed to start local server")?;
+        println!("Local server on {}", url);
+        url
+    } else {
+        args.target.trim_end_matches('/').to_string()
+    };
+
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(args.timeout))
+        .pool_max_idle_per_host(args.concurrency)
+        .build()?;
+    let source = Arc::new(TextSource::new(
+        lengths,
+        args.pool_size,
+        args.cache_hit_ratio,
+        &mut StdRng::from_entropy(),
+    ));
+    let embed_url = format!("{}/v1/embed", target);
+
+    // Warm the cache with the pool so reuses are hits
+    println!("Warming {} pooled texts...", source.pool().len());
+    for text in source.pool() {
+        send(&client, &embed_url, &args.key, text, args.truncate).await;
+    }
+
+    let before = scrape(&client, &target).await;
+    println!(
+        "Running {} workers for {}s against {}...",
+        args.concurrency, args.duration, embed_url
+    );
+
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(args.duration);
+    let workers: Vec<_> = (0..args.concurrency)
+        .map(|_| {
+            let (client, source, url, key) = (
+                client.clone(),
+                source.clone(),
+                embed_url.clone(),
+                args.key.clone(),
+            );
+            let truncate = args.truncate;
+            tokio::spawn(async move {
+                let mut rng = StdRng::from_entropy();
+                let mut samples = Samples::default();
+                while Instant::now() < deadline {
+                    let text = source.next(&mut rng);
+                    let sent = Instant::now();
+                    let outcome = send(&client, &url, &key, &text, truncate).await;
+                    samples.record(sent.elapsed(), outcome);
+                }
+                samples
+            })
+        })
+        .collect();
+
+    let mut samples = Samples::default();
+    for worker in workers {
+        samples.merge(worker.await?);
+    }
+    let elapsed = started.elapsed();
+
+    let after = scrape(&client, &target).await;
+    let metric_deltas = before
+        .zip(after)
+        .map(|(before, after)| report::metric_deltas(&before, &after, "smally_"));
+
+    let report = Report {
+        elapsed,
+        concurrency: args.concurrency,
+        samples,
+        metric_deltas,
+    };
+    println!("\n{}", report.render());
+    Ok(())
+}
+
+/// POST one text to `/v1/embed`
+async fn send(
+    client: &reqwest::Client,
+    url: &str,
+    key: &str,
+    text: &str,
+    truncate: bool,
+) -> Outcome {
+    let body = serde_json::json!({ "text": text, "truncate": truncate });
+    match client.post(url).bearer_auth(key).json(&body).send().await {
+        Ok(response) => {
+            let status = response.status().as_u16();
+            // Read the body so the connection is reused
+            let _ = response.bytes().await;
+            Outcome::Status(status)
+        }
+        Err(_) => Outcome::Transport,
+    }
+}
+
+/// The server's metrics, or None if they can't be read
+async fn scrape(
+    client: &reqwest::Client,
+    target: &str,
+) -> Option<std::collections::BTreeMap<String, f64>> {
+    let response = client
+        .get(format!("{}/metrics", target))
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    Some(report::parse_metrics(&response.text().await.ok()?))
+}
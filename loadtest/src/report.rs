@@ -0,0 +1,195 @@
+//! What a run measured: client-side latencies and outcomes, and how the
+//! server's Prometheus metrics moved
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+/// Outcome of one request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// HTTP status received
+    Status(u16),
+    /// No response (connection error or timeout)
+    Transport,
+}
+
+/// Requests of a run, in completion order
+#[derive(Debug, Default)]
+pub struct Samples {
+    pub latencies: Vec<Duration>,
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Samples {
+    pub fn record(&mut self, latency: Duration, outcome: Outcome) {
+        self.latencies.push(latency);
+        self.outcomes.push(outcome);
+    }
+
+    pub fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        self.outcomes.extend(other.outcomes);
+    }
+}
+
+/// Latency at percentile `p` (0-100) of sorted latencies, nearest rank
+pub fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Prometheus text exposition as series -> value (`name{labels}`; comments
+/// and unparsable lines are skipped)
+pub fn parse_metrics(text: &str) -> BTreeMap<String, f64> {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (series, value) = line.trim().rsplit_once(' ')?;
+            Some((series.to_string(), value.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Series of `prefix` whose value changed between two scrapes, with the
+/// change (series new in `after` count from zero)
+pub fn metric_deltas(
+    before: &BTreeMap<String, f64>,
+    after: &BTreeMap<String, f64>,
+    prefix: &str,
+) -> Vec<(String, f64)> {
+    after
+        .iter()
+        .filter(|(series, _)| series.starts_with(prefix))
+        .filter_map(|(series, value)| {
+            let delta = value - before.get(series).copied().unwrap_or(0.0);
+            (delta != 0.0).then(|| (series.clone(), delta))
+        })
+        .collect()
+}
+
+/// A finished run
+pub struct Report {
+    pub elapsed: Duration,
+    pub concurrency: usize,
+    pub samples: Samples,
+    /// None if `/metrics` couldn't be scraped
+    pub metric_deltas: Option<Vec<(String, f64)>>,
+}
+
+impl Report {
+    pub fn render(&self) -> String {
+        let mut sorted = self.samples.latencies.clone();
+        sorted.sort();
+        let total = sorted.len();
+        let secs = self.elapsed.as_secs_f64().max(f64::EPSILON);
+
+        let (mut ok, mut limited, mut shed, mut other, mut transport) = (0, 0, 0, 0, 0);
+        for outcome in &self.samples.outcomes {
+            match outcome {
+                Outcome::Status(200..=299) => ok += 1,
+                Outcome::Status(429) => limited += 1,
+                Outcome::Status(503) => shed += 1,
+                Outcome::Status(_) => other += 1,
+                Outcome::Transport => transport += 1,
+            }
+        }
+
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "Requests:     {} in {:.1}s ({} workers)",
+            total, secs, self.concurrency
+        );
+        let _ = writeln!(
+            out,
+            "Achieved QPS: {:.1} ({:.1} successful)",
+            total as f64 / secs,
+            ok as f64 / secs
+        );
+        let _ = writeln!(
+            out,
+            "Latency (ms): p50 {:.1}  p90 {:.1}  p95 {:.1}  p99 {:.1}  max {:.1}",
+            ms(percentile(&sorted, 50.0)),
+            ms(percentile(&sorted, 90.0)),
+            ms(percentile(&sorted, 95.0)),
+            ms(percentile(&sorted, 99.0)),
+            ms(sorted.last().copied().unwrap_or_default()),
+        );
+        let _ = writeln!(
+            out,
+            "Outcomes:     2xx {}  429 {}  503 {}  other errors {}  transport errors {}",
+            ok, limited, shed, other, transport
+        );
+
+        match &self.metric_deltas {
+            Some(deltas) if deltas.is_empty() => {
+                let _ = writeln!(out, "\nServer metrics: no change");
+            }
+            Some(deltas) => {
+                let _ = writeln!(out, "\nServer metrics (change during the run):");
+                for (series, delta) in deltas {
+                    let _ = writeln!(out, "  {} {:+}", series, delta);
+                }
+            }
+            None => {
+                let _ = writeln!(
+                    out,
+                    "\nServer metrics: unavailable (/metrics not reachable)"
+                );
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentile_nearest_rank() {
+        let sorted: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile(&sorted, 50.0), Duration::from_millis(50));
+        assert_eq!(percentile(&sorted, 99.0), Duration::from_millis(99));
+        assert_eq!(percentile(&sorted, 100.0), Duration::from_millis(100));
+        assert_eq!(percentile(&sorted, 0.0), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50.0), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_metric_deltas() {
+        let before = parse_metrics(
+            "# HELP smally_requests_total Total\n\
+             # TYPE smally_requests_total counter\n\
+             smally_requests_total{cached=\"false\",status=\"success\"} 10\n\
+             smally_ws_connections 0\n\
+             process_cpu_seconds_total 1.5\n",
+        );
+        let after = parse_metrics(
+            "smally_requests_total{cached=\"false\",status=\"success\"} 25\n\
+             smally_requests_total{cached=\"true\",status=\"success\"} 4\n\
+             smally_ws_connections 0\n\
+             process_cpu_seconds_total 2.5\n\
+             garbage\n",
+        );
+
+        assert_eq!(
+            metric_deltas(&before, &after, "smally_"),
+            vec![
+                (
+                    "smally_requests_total{cached=\"false\",status=\"success\"}".to_string(),
+                    15.0
+                ),
+                (
+                    "smally_requests_total{cached=\"true\",status=\"success\"}".to_string(),
+                    4.0
+                ),
+            ]
+        );
+    }
+}
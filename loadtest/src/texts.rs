@@ -0,0 +1,189 @@
+//! Request texts: lengths drawn from a weighted distribution, and a pool of
+//! texts reused to hit the server's cache at a chosen ratio
+
+use anyhow::{bail, Context, Result};
+use rand::Rng;
+
+/// Words texts are made of
+const WORDS: [&str; 32] = [
+    "search",
+    "vector",
+    "model",
+    "query",
+    "document",
+    "embedding",
+    "cache",
+    "latency",
+    "customer",
+    "invoice",
+    "product",
+    "review",
+    "shipping",
+    "support",
+    "account",
+    "billing",
+    "the",
+    "a",
+    "quick",
+    "brown",
+    "fox",
+    "jumps",
+    "over",
+    "lazy",
+    "dog",
+    "and",
+    "with",
+    "for",
+    "fast",
+    "small",
+    "large",
+    "semantic",
+];
+
+/// Text lengths in words, each with a relative weight
+#[derive(Debug, Clone, PartialEq)]
+pub struct LengthDistribution {
+    buckets: Vec<(usize, f64)>,
+}
+
+impl LengthDistribution {
+    /// Parse `words:weight,...` (e.g. `5:0.5,20:0.3,100:0.2`); a bare
+    /// `words` has weight 1
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut buckets = Vec::new();
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (words, weight) = part.split_once(':').unwrap_or((part, "1"));
+            let words: usize = words
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid word count in '{}'", part))?;
+            let weight: f64 = weight
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid weight in '{}'", part))?;
+            if words == 0 || weight.is_nan() || weight <= 0.0 {
+                bail!("'{}' needs at least one word and a positive weight", part);
+            }
+            buckets.push((words, weight));
+        }
+        if buckets.is_empty() {
+            bail!("empty length distribution");
+        }
+        Ok(Self { buckets })
+    }
+
+    /// A length in words
+    pub fn sample(&self, rng: &mut impl Rng) -> usize {
+        let total: f64 = self.buckets.iter().map(|(_, weight)| weight).sum();
+        let mut pick = rng.gen_range(0.0..total);
+        for &(words, weight) in &self.buckets {
+            if pick < weight {
+                return words;
+            }
+            pick -= weight;
+        }
+        self.buckets[self.buckets.len() - 1].0
+    }
+}
+
+/// Random text of `words` words, starting with `tag` so texts with
+/// different tags never share a cache entry
+pub fn text(tag: &str, words: usize, rng: &mut impl Rng) -> String {
+    let mut text = tag.to_string();
+    for _ in 1..words {
+        text.push(' ');
+        text.push_str(WORDS[rng.gen_range(0..WORDS.len())]);
+    }
+    text
+}
+
+/// Texts for the requests of a run
+pub struct TextSource {
+    lengths: LengthDistribution,
+    /// Texts reused for cache hits
+    pool: Vec<String>,
+    cache_hit_ratio: f64,
+}
+
+impl TextSource {
+    pub fn new(
+        lengths: LengthDistribution,
+        pool_size: usize,
+        cache_hit_ratio: f64,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let pool = (0..pool_size)
+            .map(|i| {
+                let words = lengths.sample(rng);
+                text(&format!("pool{}", i), words, rng)
+            })
+            .collect();
+        Self {
+            lengths,
+            pool,
+            cache_hit_ratio,
+        }
+    }
+
+    /// Texts to send once before measuring, so reuses are cache hits
+    pub fn pool(&self) -> &[String] {
+        &self.pool
+    }
+
+    /// Next request text: a pooled one with probability `cache_hit_ratio`,
+    /// else one never sent before
+    pub fn next(&self, rng: &mut impl Rng) -> String {
+        if !self.pool.is_empty() && rng.gen_bool(self.cache_hit_ratio) {
+            return self.pool[rng.gen_range(0..self.pool.len())].clone();
+        }
+        let tag = format!("miss{:016x}", rng.gen::<u64>());
+        text(&tag, self.lengths.sample(rng), rng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_parse_and_sample_distribution() {
+        let lengths = LengthDistribution::parse("5:3, 50:1").unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+        let samples: Vec<usize> = (0..4000).map(|_| lengths.sample(&mut rng)).collect();
+        let short = samples.iter().filter(|&&w| w == 5).count();
+        assert!(samples.iter().all(|&w| w == 5 || w == 50));
+        assert!((2800..3200).contains(&short), "{}", short);
+
+        assert_eq!(
+            LengthDistribution::parse("12").unwrap(),
+            LengthDistribution {
+                buckets: vec![(12, 1.0)]
+            }
+        );
+        for invalid in ["", "0:1", "5:0", "5:x", "x:1", "5:-1"] {
+            assert!(LengthDistribution::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_cache_hit_ratio() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let lengths = LengthDistribution::parse("8").unwrap();
+        let source = TextSource::new(lengths, 10, 0.75, &mut rng);
+        assert_eq!(source.pool().len(), 10);
+        assert!(source.pool().iter().all(|t| t.split(' ').count() == 8));
+
+        let texts: Vec<String> = (0..4000).map(|_| source.next(&mut rng)).collect();
+        let pooled = texts.iter().filter(|t| source.pool().contains(t)).count();
+        assert!((2850..3150).contains(&pooled), "{}", pooled);
+
+        // Misses are never repeated
+        let mut misses: Vec<&String> = texts.iter().filter(|t| t.starts_with("miss")).collect();
+        let count = misses.len();
+        misses.sort();
+        misses.dedup();
+        assert_eq!(misses.len(), count);
+    }
+}
@@ -0,0 +1,4 @@
+-- Optional HMAC request signing per API key.
+-- The signing secret itself is never stored: it is derived from the server
+-- secret and the key_id (see auth::signing::derive_signing_secret).
+ALTER TABLE api_keys ADD COLUMN require_request_signing BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,13 @@
+-- Organization data exports (account takeout)
+CREATE TABLE organization_exports (
+    id UUID PRIMARY KEY,
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    requested_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    status VARCHAR(50) NOT NULL DEFAULT 'pending', -- 'pending', 'ready', 'failed'
+    archive BYTEA,
+    error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    completed_at TIMESTAMP
+);
+
+CREATE INDEX idx_organization_exports_org_time ON organization_exports(organization_id, created_at DESC);
@@ -0,0 +1,6 @@
+-- Opaque API keys: random secrets stored as a SHA-256 hash, with the
+-- CBOR-encoded token data that a CWT would otherwise carry
+ALTER TABLE api_keys ADD COLUMN key_hash VARCHAR(64);
+ALTER TABLE api_keys ADD COLUMN token_data BYTEA;
+
+CREATE UNIQUE INDEX idx_api_keys_key_hash ON api_keys(key_hash) WHERE key_hash IS NOT NULL;
@@ -0,0 +1,27 @@
+-- Monthly billing statements (immutable; regeneration adds a new version)
+CREATE TABLE statements (
+    id UUID PRIMARY KEY,
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    month DATE NOT NULL, -- first day of the billed month
+    version INTEGER NOT NULL DEFAULT 1,
+
+    -- Terms applied to the month
+    tier VARCHAR(50) NOT NULL,
+    monthly_quota BIGINT NOT NULL,
+    max_tokens INTEGER NOT NULL,
+    price_per_million_tokens BIGINT NOT NULL, -- cents
+
+    -- Totals (from usage_events)
+    total_requests BIGINT NOT NULL,
+    total_tokens BIGINT NOT NULL,
+    cost_cents BIGINT NOT NULL,
+    key_breakdown JSONB NOT NULL DEFAULT '[]',
+
+    generated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    generated_by VARCHAR(50) NOT NULL, -- 'scheduler' or 'admin'
+    reason TEXT,
+
+    UNIQUE (organization_id, month, version)
+);
+
+CREATE INDEX idx_statements_org_month ON statements(organization_id, month DESC, version DESC);
@@ -0,0 +1,17 @@
+-- Per-organization usage alert settings (no row = defaults: 80% and 100%, owners)
+CREATE TABLE usage_alert_settings (
+    organization_id UUID PRIMARY KEY REFERENCES organizations(id) ON DELETE CASCADE,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    thresholds INTEGER[] NOT NULL DEFAULT '{80,100}', -- percent of the monthly quota
+    recipients TEXT[] NOT NULL DEFAULT '{}', -- empty = organization owners
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Alerts already sent (each threshold fires once per month)
+CREATE TABLE usage_alerts_sent (
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    month DATE NOT NULL, -- first day of the month
+    threshold INTEGER NOT NULL,
+    sent_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (organization_id, month, threshold)
+);
@@ -0,0 +1,27 @@
+-- Composite indexes for the org-scoped listings that run next to the
+-- per-request inserts. Each comment names the queries the index serves.
+
+-- api_request_log(organization_id, request_timestamp) already exists as
+-- idx_api_request_log_org_time (event sourcing schema); it serves the
+-- per-organization request history and export listings.
+
+-- Statement generation (billing::statements: usage per key for one org and
+-- month), the organization export (api::exports: daily usage of one org)
+-- and the dashboard usage charts. idx_usage_events_org_product only helps
+-- these when the product is also filtered on.
+CREATE INDEX IF NOT EXISTS idx_usage_events_org_time
+    ON usage_events(organization_id, timestamp);
+
+-- API key listings (api::api_keys list, web::api_keys show, api::exports):
+-- `WHERE organization_id = $1 ORDER BY created_at` uses the prefix, and
+-- active-key listings (`AND is_active = true ORDER BY created_at`) are read
+-- in index order without a sort.
+CREATE INDEX IF NOT EXISTS idx_api_keys_org_active_created
+    ON api_keys(organization_id, is_active, created_at);
+
+-- Organization switcher and membership checks by user
+-- (web::organizations list, member lookups): `WHERE om.user_id = $1`.
+-- idx_organization_members_user_id from the initial schema covers this;
+-- the statement is repeated so the intent is recorded next to the others.
+CREATE INDEX IF NOT EXISTS idx_organization_members_user_id
+    ON organization_members(user_id);
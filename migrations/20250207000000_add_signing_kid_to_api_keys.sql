@@ -0,0 +1,6 @@
+-- Which Ed25519 key signed each CWT API key (hex kid), so rotated-out
+-- public keys can be dropped once no active key depends on them.
+-- NULL for opaque keys and for keys issued before kids were recorded.
+ALTER TABLE api_keys ADD COLUMN signing_kid VARCHAR(16);
+
+CREATE INDEX idx_api_keys_signing_kid ON api_keys(signing_kid) WHERE is_active = true;
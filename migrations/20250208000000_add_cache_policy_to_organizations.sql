@@ -0,0 +1,7 @@
+-- Per-organization embedding cache policy:
+--   shared   = cache keyed by content only (default, entries shared across orgs)
+--   private  = cache keys salted with the organization id
+--   disabled = never written to Redis (in-process cache only, salted)
+ALTER TABLE organizations
+    ADD COLUMN cache_policy VARCHAR(16) NOT NULL DEFAULT 'shared'
+    CHECK (cache_policy IN ('shared', 'private', 'disabled'));
@@ -0,0 +1,6 @@
+-- Per-organization model settings:
+--   allowed_models = models the organization's API keys may use (NULL = all served models)
+--   default_model  = model for requests that don't name one (NULL = the server default)
+ALTER TABLE organizations
+    ADD COLUMN allowed_models TEXT[],
+    ADD COLUMN default_model VARCHAR(255);
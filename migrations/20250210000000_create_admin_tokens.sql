@@ -0,0 +1,12 @@
+-- Issued admin tokens (recorded by create_admin_token), so they can be
+-- listed and revoked individually. Revocation itself is enforced through
+-- Redis (admin_revoked:<id>); revoked_at is the durable record.
+CREATE TABLE admin_tokens (
+    id UUID PRIMARY KEY, -- the token's cti claim
+    scope VARCHAR(50) NOT NULL,
+    issued_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP
+);
+
+CREATE INDEX idx_admin_tokens_expires_at ON admin_tokens(expires_at DESC);
@@ -0,0 +1,5 @@
+-- API version pinned to a key at creation (X-Smally-Version when a request
+-- doesn't send one). NULL = the default version, for keys issued before
+-- versioning.
+ALTER TABLE api_keys
+    ADD COLUMN api_version VARCHAR(10);
@@ -0,0 +1,4 @@
+-- Feature flags the organization is allowlisted for (flags in `allowlist`
+-- mode are only enabled for organizations listing them; see src/flags.rs)
+ALTER TABLE organizations
+    ADD COLUMN feature_flags TEXT[] NOT NULL DEFAULT '{}';
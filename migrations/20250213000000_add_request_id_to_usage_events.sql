@@ -0,0 +1,6 @@
+-- Request each usage event bills, so events replayed from the usage outbox
+-- after a crash (billing::outbox) are recorded once
+ALTER TABLE usage_events ADD COLUMN IF NOT EXISTS request_id UUID;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_usage_events_request_id
+    ON usage_events(request_id) WHERE request_id IS NOT NULL;
@@ -0,0 +1,15 @@
+-- Non-human principals managing an organization through the API with a
+-- long-lived `sa_` token. The token only names the account; organization
+-- and role are read from here on every (uncached) request, so revoking or
+-- deleting the row cuts access.
+CREATE TABLE service_accounts (
+    id UUID PRIMARY KEY, -- the token's cti claim
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    role VARCHAR(50) NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMP
+);
+
+CREATE INDEX idx_service_accounts_organization_id ON service_accounts(organization_id);
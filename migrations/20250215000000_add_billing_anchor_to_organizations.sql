@@ -0,0 +1,5 @@
+-- Day of the month the organization's billing cycle (and free tier quota)
+-- resets on; NULL = calendar month. Months shorter than the anchor reset on
+-- their last day (see src/billing/cycle.rs)
+ALTER TABLE organizations
+    ADD COLUMN billing_anchor_day INTEGER CHECK (billing_anchor_day BETWEEN 1 AND 31);
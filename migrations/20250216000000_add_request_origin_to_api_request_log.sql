@@ -0,0 +1,12 @@
+-- Where API requests came from, for abuse investigations: the client IP
+-- (proxy-aware, see TRUST_PROXY_HEADERS) and the User-Agent header
+ALTER TABLE api_request_log
+    ADD COLUMN client_ip VARCHAR(45),
+    ADD COLUMN user_agent VARCHAR(512);
+
+-- Distinct IPs per key over the last day
+CREATE INDEX idx_api_request_log_key_time ON api_request_log(api_key_id, request_timestamp);
+
+-- Privacy-sensitive organizations can opt out of IP storage
+ALTER TABLE organizations
+    ADD COLUMN store_client_ip BOOLEAN NOT NULL DEFAULT true;
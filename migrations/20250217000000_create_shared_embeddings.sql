@@ -0,0 +1,17 @@
+-- Embedding results shared through signed links (POST /v1/share)
+CREATE TABLE shared_embeddings (
+    id UUID PRIMARY KEY,
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    api_key_id UUID REFERENCES api_keys(key_id) ON DELETE SET NULL,
+    request_id UUID NOT NULL, -- api_request_log row of the embedding
+    input_text TEXT, -- NULL when the text wasn't shared
+    embedding JSONB NOT NULL, -- full precision
+    model VARCHAR(255) NOT NULL, -- with its revision, as in the response
+    tokens INTEGER NOT NULL,
+    options JSONB NOT NULL, -- normalize, post_process, truncate, precision
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP
+);
+
+CREATE INDEX idx_shared_embeddings_org_time ON shared_embeddings(organization_id, created_at DESC);
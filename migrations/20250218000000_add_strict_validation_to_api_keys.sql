@@ -0,0 +1,3 @@
+-- Keys whose requests get strict body validation (unknown fields are
+-- rejected, as with X-Smally-Strict: true; see api::strict).
+ALTER TABLE api_keys ADD COLUMN strict_validation BOOLEAN NOT NULL DEFAULT false;
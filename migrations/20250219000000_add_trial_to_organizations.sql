@@ -0,0 +1,10 @@
+-- Free trial of a higher tier: while trial_expires_at is in the future the
+-- organization is served as trial_tier, then falls back to tier. Both are
+-- NULL without a trial (see src/billing/trial.rs)
+ALTER TABLE organizations
+    ADD COLUMN trial_tier VARCHAR(50),
+    ADD COLUMN trial_expires_at TIMESTAMP;
+
+-- Expiry job
+CREATE INDEX idx_organizations_trial_expires_at ON organizations(trial_expires_at)
+    WHERE trial_expires_at IS NOT NULL;
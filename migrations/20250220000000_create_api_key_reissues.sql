@@ -0,0 +1,25 @@
+-- Reissuing API keys with current claims (POST /v1/admin/keys/reissue).
+-- Tokens carry a generation ('g' claim, 0 at creation); a reissue signs the
+-- key's next generation under the same key_id. Older generations keep
+-- validating until they're revoked (revoked_before:<key_id> in Redis).
+ALTER TABLE api_keys ADD COLUMN token_generation INTEGER NOT NULL DEFAULT 0;
+
+-- One row per reissued token. The token waits, sealed (see
+-- src/auth/sealed.rs), until an owner or admin retrieves it or
+-- retrieve_before passes; either way sealed_token is cleared.
+CREATE TABLE api_key_reissues (
+    id UUID PRIMARY KEY,
+    api_key_id UUID NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    generation INTEGER NOT NULL,
+    signing_kid VARCHAR(16) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- pending, retrieved, expired, completed
+    sealed_token TEXT,
+    retrieve_before TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    retrieved_at TIMESTAMP,
+    completed_at TIMESTAMP, -- older generations revoked
+    UNIQUE (api_key_id, generation)
+);
+
+CREATE INDEX idx_api_key_reissues_org_status ON api_key_reissues(organization_id, status);
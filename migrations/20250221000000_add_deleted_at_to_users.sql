@@ -0,0 +1,8 @@
+-- Deleted accounts stay behind as anonymized tombstones with deleted_at
+-- set. Only live accounts hold their email, so the email of a deleted one
+-- can be registered again; deactivated accounts (is_active = false) still
+-- hold theirs (see src/services/users.rs)
+ALTER TABLE users ADD COLUMN deleted_at TIMESTAMP;
+
+ALTER TABLE users DROP CONSTRAINT users_email_key;
+CREATE UNIQUE INDEX idx_users_email_live ON users(email) WHERE deleted_at IS NULL;
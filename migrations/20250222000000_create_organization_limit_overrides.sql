@@ -0,0 +1,11 @@
+-- Creation limits raised for one organization (PUT
+-- /v1/admin/organizations/:org_id/limits). NULL keeps the tier's limit, 0
+-- lifts it. max_organizations applies to the organization's owner (see
+-- src/services/limits.rs).
+CREATE TABLE organization_limit_overrides (
+    organization_id UUID PRIMARY KEY REFERENCES organizations(id) ON DELETE CASCADE,
+    max_organizations INTEGER,
+    max_api_keys INTEGER,
+    creations_per_hour INTEGER,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
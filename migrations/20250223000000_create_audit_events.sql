@@ -0,0 +1,18 @@
+-- Audit events of organization members (actions on keys and settings,
+-- dashboard logins), kept so owners can see who does what (GET
+-- /v1/organizations/:org_id/members/:user_id/activity, see
+-- src/services/activity.rs). The `audit` log target keeps logging them.
+CREATE TABLE audit_events (
+    id UUID PRIMARY KEY,
+    organization_id UUID NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    action VARCHAR(64) NOT NULL,
+    target VARCHAR(255),
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_audit_events_member ON audit_events(organization_id, user_id, created_at);
+
+-- Who created a key (NULL for keys created by service accounts, the CLI,
+-- or before this column)
+ALTER TABLE api_keys ADD COLUMN created_by UUID REFERENCES users(id) ON DELETE SET NULL;
@@ -0,0 +1,117 @@
+-- long-running
+-- Timestamps become TIMESTAMPTZ. Every value was already written as UTC
+-- (sessions run in UTC, the app binds UTC), except usage_events rows from
+-- the response path, which used the server's local clock; on servers not
+-- running in UTC those are off by their offset and aren't corrected here.
+-- Rewrites every table (api_request_log and usage_events are large): run
+-- with `smally migrate`.
+
+ALTER TABLE users
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC',
+    ALTER COLUMN updated_at TYPE TIMESTAMPTZ USING updated_at AT TIME ZONE 'UTC',
+    ALTER COLUMN deleted_at TYPE TIMESTAMPTZ USING deleted_at AT TIME ZONE 'UTC';
+
+ALTER TABLE organizations
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC',
+    ALTER COLUMN updated_at TYPE TIMESTAMPTZ USING updated_at AT TIME ZONE 'UTC',
+    ALTER COLUMN trial_expires_at TYPE TIMESTAMPTZ USING trial_expires_at AT TIME ZONE 'UTC';
+
+ALTER TABLE organization_members
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC';
+
+ALTER TABLE api_keys
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC',
+    ALTER COLUMN last_used_at TYPE TIMESTAMPTZ USING last_used_at AT TIME ZONE 'UTC';
+
+ALTER TABLE api_request_log
+    ALTER COLUMN request_timestamp TYPE TIMESTAMPTZ USING request_timestamp AT TIME ZONE 'UTC',
+    ALTER COLUMN response_timestamp TYPE TIMESTAMPTZ USING response_timestamp AT TIME ZONE 'UTC',
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC',
+    ALTER COLUMN updated_at TYPE TIMESTAMPTZ USING updated_at AT TIME ZONE 'UTC';
+
+ALTER TABLE usage_events
+    ALTER COLUMN timestamp TYPE TIMESTAMPTZ USING timestamp AT TIME ZONE 'UTC';
+
+ALTER TABLE organization_exports
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC',
+    ALTER COLUMN completed_at TYPE TIMESTAMPTZ USING completed_at AT TIME ZONE 'UTC';
+
+ALTER TABLE statements
+    ALTER COLUMN generated_at TYPE TIMESTAMPTZ USING generated_at AT TIME ZONE 'UTC';
+
+ALTER TABLE usage_alert_settings
+    ALTER COLUMN updated_at TYPE TIMESTAMPTZ USING updated_at AT TIME ZONE 'UTC';
+
+ALTER TABLE usage_alerts_sent
+    ALTER COLUMN sent_at TYPE TIMESTAMPTZ USING sent_at AT TIME ZONE 'UTC';
+
+ALTER TABLE admin_tokens
+    ALTER COLUMN issued_at TYPE TIMESTAMPTZ USING issued_at AT TIME ZONE 'UTC',
+    ALTER COLUMN expires_at TYPE TIMESTAMPTZ USING expires_at AT TIME ZONE 'UTC',
+    ALTER COLUMN revoked_at TYPE TIMESTAMPTZ USING revoked_at AT TIME ZONE 'UTC';
+
+ALTER TABLE service_accounts
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC',
+    ALTER COLUMN revoked_at TYPE TIMESTAMPTZ USING revoked_at AT TIME ZONE 'UTC';
+
+ALTER TABLE shared_embeddings
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC',
+    ALTER COLUMN expires_at TYPE TIMESTAMPTZ USING expires_at AT TIME ZONE 'UTC',
+    ALTER COLUMN revoked_at TYPE TIMESTAMPTZ USING revoked_at AT TIME ZONE 'UTC';
+
+ALTER TABLE api_key_reissues
+    ALTER COLUMN retrieve_before TYPE TIMESTAMPTZ USING retrieve_before AT TIME ZONE 'UTC',
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC',
+    ALTER COLUMN retrieved_at TYPE TIMESTAMPTZ USING retrieved_at AT TIME ZONE 'UTC',
+    ALTER COLUMN completed_at TYPE TIMESTAMPTZ USING completed_at AT TIME ZONE 'UTC';
+
+ALTER TABLE organization_limit_overrides
+    ALTER COLUMN updated_at TYPE TIMESTAMPTZ USING updated_at AT TIME ZONE 'UTC';
+
+ALTER TABLE audit_events
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC';
+
+-- Same function, over TIMESTAMPTZ bounds
+DROP FUNCTION IF EXISTS recalculate_usage(TIMESTAMP, TIMESTAMP);
+CREATE OR REPLACE FUNCTION recalculate_usage(
+    p_start_date TIMESTAMPTZ DEFAULT NULL,
+    p_end_date TIMESTAMPTZ DEFAULT NULL
+) RETURNS TABLE (
+    deleted_count BIGINT,
+    inserted_count BIGINT
+) AS $$
+DECLARE
+    v_start TIMESTAMPTZ := COALESCE(p_start_date, '-infinity');
+    v_end TIMESTAMPTZ := COALESCE(p_end_date, 'infinity');
+    v_deleted BIGINT;
+    v_inserted BIGINT;
+BEGIN
+    -- Delete old calculated usage for the period
+    DELETE FROM usage_events
+    WHERE timestamp >= v_start AND timestamp < v_end;
+
+    GET DIAGNOSTICS v_deleted = ROW_COUNT;
+
+    -- Recalculate from successful requests in api_request_log
+    INSERT INTO usage_events (organization_id, api_key_id, product, event_type, tokens, requests, timestamp)
+    SELECT
+        organization_id,
+        api_key_id,
+        product,
+        'inference' as event_type,
+        tokens,
+        1 as requests,
+        response_timestamp as timestamp
+    FROM api_request_log
+    WHERE response_timestamp >= v_start
+      AND response_timestamp < v_end
+      AND status = 'success'
+      AND tokens IS NOT NULL;
+
+    GET DIAGNOSTICS v_inserted = ROW_COUNT;
+
+    RAISE NOTICE 'Recalculated usage: deleted %, inserted % rows', v_deleted, v_inserted;
+
+    RETURN QUERY SELECT v_deleted, v_inserted;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,5 @@
+-- Data residency: the region an organization's requests must be served in
+-- ('any', or a region name of the deployment's DEPLOYMENT_REGION /
+-- REGION_BASE_URLS settings; see src/residency.rs)
+ALTER TABLE organizations
+    ADD COLUMN region VARCHAR(32) NOT NULL DEFAULT 'any';
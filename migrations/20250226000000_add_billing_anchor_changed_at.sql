@@ -0,0 +1,5 @@
+-- When billing_anchor_day was last changed: changes are limited to one per
+-- ANCHOR_CHANGE_COOLDOWN_DAYS (see src/services/organizations.rs). NULL =
+-- never changed.
+ALTER TABLE organizations
+    ADD COLUMN billing_anchor_changed_at TIMESTAMPTZ;
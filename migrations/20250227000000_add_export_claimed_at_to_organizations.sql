@@ -0,0 +1,6 @@
+-- When the organization's current data export was requested: a new export
+-- claims the slot only if it's empty or older than the cooldown, in one
+-- conditional UPDATE. Failed and abandoned exports release it (see
+-- src/api/exports.rs).
+ALTER TABLE organizations
+    ADD COLUMN export_claimed_at TIMESTAMPTZ;
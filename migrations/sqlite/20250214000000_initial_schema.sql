@@ -0,0 +1,166 @@
+-- SQLite schema (`sqlite` feature) for local development and CI, matching
+-- the Postgres migrations in ../ up to 20250214000000. Add a migration here
+-- whenever a Postgres migration changes a table the control plane uses.
+--
+-- Adapted types: UUIDs are 16-byte BLOBs (as sqlx encodes them), timestamps
+-- are TEXT, and list columns (TEXT[]/INTEGER[] in Postgres) hold a JSON
+-- array. api_request_log and usage_events exist so reads work, but nothing
+-- is written to them on SQLite.
+
+CREATE TABLE users (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    email VARCHAR(255) UNIQUE NOT NULL,
+    name VARCHAR(255),
+    password_hash VARCHAR(255),
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    last_selected_org_id BLOB REFERENCES organizations(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE organizations (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    name VARCHAR(255) NOT NULL,
+    owner_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    tier VARCHAR(50) NOT NULL DEFAULT 'free',
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    cache_policy VARCHAR(16) NOT NULL DEFAULT 'shared'
+        CHECK (cache_policy IN ('shared', 'private', 'disabled')),
+    allowed_models TEXT, -- JSON array, NULL = all served models
+    default_model VARCHAR(255),
+    feature_flags TEXT NOT NULL DEFAULT '[]', -- JSON array
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE organization_members (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    organization_id BLOB NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role VARCHAR(50) NOT NULL DEFAULT 'member', -- owner, admin, member
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(organization_id, user_id)
+);
+
+CREATE TABLE api_keys (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    organization_id BLOB NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    key_id BLOB NOT NULL UNIQUE,
+    name VARCHAR(255) NOT NULL DEFAULT 'Default API Key',
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMP,
+    require_request_signing BOOLEAN NOT NULL DEFAULT false,
+    key_hash VARCHAR(64),
+    token_data BLOB,
+    signing_kid VARCHAR(16),
+    api_version VARCHAR(10)
+);
+
+CREATE TABLE api_request_log (
+    request_id BLOB PRIMARY KEY,
+    organization_id BLOB NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    api_key_id BLOB NOT NULL REFERENCES api_keys(key_id) ON DELETE SET NULL,
+    product VARCHAR(50) NOT NULL,
+    endpoint VARCHAR(100) NOT NULL,
+    input_text TEXT NOT NULL,
+    input_metadata TEXT,
+    request_timestamp TIMESTAMP NOT NULL,
+    tokens INTEGER,
+    response_metadata TEXT,
+    response_timestamp TIMESTAMP,
+    status VARCHAR(50) DEFAULT 'pending',
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP
+);
+
+CREATE TABLE usage_events (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    organization_id BLOB NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    api_key_id BLOB REFERENCES api_keys(key_id) ON DELETE SET NULL,
+    product VARCHAR(50) NOT NULL,
+    event_type VARCHAR(50) NOT NULL,
+    tokens INTEGER DEFAULT 0,
+    requests INTEGER DEFAULT 1,
+    units INTEGER DEFAULT 0,
+    timestamp TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    request_id BLOB
+);
+
+CREATE TABLE organization_exports (
+    id BLOB PRIMARY KEY,
+    organization_id BLOB NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    requested_by BLOB REFERENCES users(id) ON DELETE SET NULL,
+    status VARCHAR(50) NOT NULL DEFAULT 'pending', -- 'pending', 'ready', 'failed'
+    archive BLOB,
+    error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    completed_at TIMESTAMP
+);
+
+CREATE TABLE statements (
+    id BLOB PRIMARY KEY,
+    organization_id BLOB NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    month DATE NOT NULL, -- first day of the billed month
+    version INTEGER NOT NULL DEFAULT 1,
+    tier VARCHAR(50) NOT NULL,
+    monthly_quota BIGINT NOT NULL,
+    max_tokens INTEGER NOT NULL,
+    price_per_million_tokens BIGINT NOT NULL, -- cents
+    total_requests BIGINT NOT NULL,
+    total_tokens BIGINT NOT NULL,
+    cost_cents BIGINT NOT NULL,
+    key_breakdown TEXT NOT NULL DEFAULT '[]',
+    generated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    generated_by VARCHAR(50) NOT NULL, -- 'scheduler' or 'admin'
+    reason TEXT,
+    UNIQUE (organization_id, month, version)
+);
+
+CREATE TABLE usage_alert_settings (
+    organization_id BLOB PRIMARY KEY REFERENCES organizations(id) ON DELETE CASCADE,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    thresholds TEXT NOT NULL DEFAULT '[80,100]', -- JSON array, percent of the monthly quota
+    recipients TEXT NOT NULL DEFAULT '[]', -- JSON array, empty = organization owners
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE usage_alerts_sent (
+    organization_id BLOB NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    month DATE NOT NULL,
+    threshold INTEGER NOT NULL,
+    sent_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (organization_id, month, threshold)
+);
+
+CREATE TABLE admin_tokens (
+    id BLOB PRIMARY KEY, -- the token's cti claim
+    scope VARCHAR(50) NOT NULL,
+    issued_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP
+);
+
+CREATE TABLE service_accounts (
+    id BLOB PRIMARY KEY, -- the token's cti claim
+    organization_id BLOB NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    name VARCHAR(255) NOT NULL,
+    role VARCHAR(50) NOT NULL,
+    created_by BLOB REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    revoked_at TIMESTAMP
+);
+
+CREATE INDEX idx_organizations_owner_id ON organizations(owner_id);
+CREATE INDEX idx_organization_members_org_id ON organization_members(organization_id);
+CREATE INDEX idx_organization_members_user_id ON organization_members(user_id);
+CREATE INDEX idx_users_last_selected_org_id ON users(last_selected_org_id);
+CREATE INDEX idx_api_keys_org_active_created ON api_keys(organization_id, is_active, created_at);
+CREATE UNIQUE INDEX idx_api_keys_key_hash ON api_keys(key_hash) WHERE key_hash IS NOT NULL;
+CREATE INDEX idx_api_keys_signing_kid ON api_keys(signing_kid) WHERE is_active = true;
+CREATE INDEX idx_usage_events_org_time ON usage_events(organization_id, timestamp);
+CREATE UNIQUE INDEX idx_usage_events_request_id ON usage_events(request_id) WHERE request_id IS NOT NULL;
+CREATE INDEX idx_organization_exports_org_time ON organization_exports(organization_id, created_at DESC);
+CREATE INDEX idx_statements_org_month ON statements(organization_id, month DESC, version DESC);
+CREATE INDEX idx_admin_tokens_expires_at ON admin_tokens(expires_at DESC);
+CREATE INDEX idx_service_accounts_organization_id ON service_accounts(organization_id);
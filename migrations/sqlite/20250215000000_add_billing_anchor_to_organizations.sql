@@ -0,0 +1,3 @@
+-- See ../20250215000000_add_billing_anchor_to_organizations.sql
+ALTER TABLE organizations
+    ADD COLUMN billing_anchor_day INTEGER CHECK (billing_anchor_day BETWEEN 1 AND 31);
@@ -0,0 +1,7 @@
+-- See ../20250216000000_add_request_origin_to_api_request_log.sql
+ALTER TABLE api_request_log ADD COLUMN client_ip VARCHAR(45);
+ALTER TABLE api_request_log ADD COLUMN user_agent VARCHAR(512);
+
+CREATE INDEX idx_api_request_log_key_time ON api_request_log(api_key_id, request_timestamp);
+
+ALTER TABLE organizations ADD COLUMN store_client_ip BOOLEAN NOT NULL DEFAULT true;
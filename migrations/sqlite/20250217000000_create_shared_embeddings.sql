@@ -0,0 +1,17 @@
+-- See ../20250217000000_create_shared_embeddings.sql
+CREATE TABLE shared_embeddings (
+    id BLOB PRIMARY KEY,
+    organization_id BLOB NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    api_key_id BLOB REFERENCES api_keys(key_id) ON DELETE SET NULL,
+    request_id BLOB NOT NULL,
+    input_text TEXT,
+    embedding TEXT NOT NULL, -- JSON array
+    model VARCHAR(255) NOT NULL,
+    tokens INTEGER NOT NULL,
+    options TEXT NOT NULL, -- JSON object
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL,
+    revoked_at TIMESTAMP
+);
+
+CREATE INDEX idx_shared_embeddings_org_time ON shared_embeddings(organization_id, created_at DESC);
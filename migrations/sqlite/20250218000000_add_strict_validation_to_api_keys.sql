@@ -0,0 +1,2 @@
+-- See ../20250218000000_add_strict_validation_to_api_keys.sql
+ALTER TABLE api_keys ADD COLUMN strict_validation BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,6 @@
+-- See ../20250219000000_add_trial_to_organizations.sql
+ALTER TABLE organizations ADD COLUMN trial_tier VARCHAR(50);
+ALTER TABLE organizations ADD COLUMN trial_expires_at TIMESTAMP;
+
+CREATE INDEX idx_organizations_trial_expires_at ON organizations(trial_expires_at)
+    WHERE trial_expires_at IS NOT NULL;
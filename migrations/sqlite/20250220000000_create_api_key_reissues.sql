@@ -0,0 +1,19 @@
+-- See ../20250220000000_create_api_key_reissues.sql
+ALTER TABLE api_keys ADD COLUMN token_generation INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE api_key_reissues (
+    id BLOB PRIMARY KEY,
+    api_key_id BLOB NOT NULL REFERENCES api_keys(id) ON DELETE CASCADE,
+    organization_id BLOB NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    generation INTEGER NOT NULL,
+    signing_kid VARCHAR(16) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    sealed_token TEXT,
+    retrieve_before TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    retrieved_at TIMESTAMP,
+    completed_at TIMESTAMP,
+    UNIQUE (api_key_id, generation)
+);
+
+CREATE INDEX idx_api_key_reissues_org_status ON api_key_reissues(organization_id, status);
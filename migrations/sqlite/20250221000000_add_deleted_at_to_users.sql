@@ -0,0 +1,36 @@
+-- no-transaction
+-- See ../20250221000000_add_deleted_at_to_users.sql
+--
+-- SQLite can't drop the UNIQUE on email, so the table is rebuilt
+-- (https://www.sqlite.org/lang_altertable.html#otheralter). Foreign keys
+-- are off meanwhile, or dropping the old table would cascade to the
+-- organizations and memberships referencing it.
+PRAGMA foreign_keys = OFF;
+
+BEGIN;
+
+CREATE TABLE users_new (
+    id BLOB PRIMARY KEY DEFAULT (randomblob(16)),
+    email VARCHAR(255) NOT NULL,
+    name VARCHAR(255),
+    password_hash VARCHAR(255),
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    last_selected_org_id BLOB REFERENCES organizations(id) ON DELETE SET NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    deleted_at TIMESTAMP
+);
+
+INSERT INTO users_new (id, email, name, password_hash, is_active, last_selected_org_id, created_at, updated_at)
+    SELECT id, email, name, password_hash, is_active, last_selected_org_id, created_at, updated_at
+    FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
+
+CREATE INDEX idx_users_last_selected_org_id ON users(last_selected_org_id);
+CREATE UNIQUE INDEX idx_users_email_live ON users(email) WHERE deleted_at IS NULL;
+
+COMMIT;
+
+PRAGMA foreign_keys = ON;
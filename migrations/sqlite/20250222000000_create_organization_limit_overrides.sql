@@ -0,0 +1,8 @@
+-- See ../20250222000000_create_organization_limit_overrides.sql
+CREATE TABLE organization_limit_overrides (
+    organization_id BLOB PRIMARY KEY REFERENCES organizations(id) ON DELETE CASCADE,
+    max_organizations INTEGER,
+    max_api_keys INTEGER,
+    creations_per_hour INTEGER,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
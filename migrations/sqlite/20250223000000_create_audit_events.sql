@@ -0,0 +1,13 @@
+-- See ../20250223000000_create_audit_events.sql
+CREATE TABLE audit_events (
+    id BLOB PRIMARY KEY,
+    organization_id BLOB NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+    user_id BLOB NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    action VARCHAR(64) NOT NULL,
+    target VARCHAR(255),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_audit_events_member ON audit_events(organization_id, user_id, created_at);
+
+ALTER TABLE api_keys ADD COLUMN created_by BLOB REFERENCES users(id) ON DELETE SET NULL;
@@ -0,0 +1,4 @@
+-- See ../20250224000000_use_timestamptz.sql
+-- SQLite has no TIMESTAMPTZ: timestamps stay UTC text in the format of
+-- CURRENT_TIMESTAMP (see database::timestamp), so there is nothing to change.
+SELECT 1;
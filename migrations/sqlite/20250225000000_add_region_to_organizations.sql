@@ -0,0 +1,2 @@
+-- See ../20250225000000_add_region_to_organizations.sql
+ALTER TABLE organizations ADD COLUMN region VARCHAR(32) NOT NULL DEFAULT 'any';
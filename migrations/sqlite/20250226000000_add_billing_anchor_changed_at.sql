@@ -0,0 +1,2 @@
+-- See ../20250226000000_add_billing_anchor_changed_at.sql
+ALTER TABLE organizations ADD COLUMN billing_anchor_changed_at TIMESTAMP;
@@ -0,0 +1,2 @@
+-- See ../20250227000000_add_export_claimed_at_to_organizations.sql
+ALTER TABLE organizations ADD COLUMN export_claimed_at TIMESTAMP;
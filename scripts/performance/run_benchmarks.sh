@@ -14,7 +14,7 @@ echo ""
 
 # 1. Criterion Benchmarks (Unit-level)
 echo -e "${GREEN}Running Criterion Benchmarks...${NC}"
-echo "These test individual components (cache, tokenizer, inference)"
+echo "These test individual components (cache, tokenizer, inference, token verification)"
 echo ""
 
 if [ -d "models/all-MiniLM-L6-v2-onnx" ]; then
@@ -22,10 +22,12 @@ if [ -d "models/all-MiniLM-L6-v2-onnx" ]; then
   cargo bench --bench cache_bench
   cargo bench --bench tokenizer_bench
   cargo bench --bench inference_bench
+  cargo bench --bench auth_bench
 else
-  echo "⚠ Model not found, running only cache benchmarks"
+  echo "⚠ Model not found, running only cache and token verification benchmarks"
   echo "  Run 'make model' to download the model for full benchmarks"
   cargo bench --bench cache_bench
+  cargo bench --bench auth_bench
 fi
 
 echo ""
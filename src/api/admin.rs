@@ -0,0 +1,908 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::AdminTokenClaims;
+#[cfg(feature = "control-plane")]
+use crate::auth::{admin, keyring};
+use crate::billing::{alerts, concurrency, gc, trial};
+use crate::cache::CacheStats;
+use crate::config;
+use crate::flags::{self, Flag, FlagMode};
+use crate::inference::tokenizer::SpecialTokens;
+#[cfg(feature = "inference")]
+use crate::inference::{self, tokenizer::Tokenizer};
+use crate::jobs;
+use crate::models::TierType;
+use crate::monitoring::info::InstanceInfo;
+use crate::monitoring::probes;
+#[cfg(feature = "control-plane")]
+use crate::services::{api_keys, key_reissue, limits};
+use crate::state::AppState;
+use crate::uuid_dashless::DashlessUuid;
+
+use super::error::ApiError;
+use super::strict::StrictJson;
+
+/// Query parameters of `GET /v1/admin/model/tokenizer`
+#[derive(Debug, Deserialize)]
+pub struct TokenizerQuery {
+    /// Optional text to tokenize
+    pub sample: Option<String>,
+}
+
+/// One WordPiece piece of the sample
+#[derive(Debug, Serialize)]
+pub struct TokenPiece {
+    pub piece: String,
+    pub id: i64,
+}
+
+/// Tokenization of the sample text
+#[derive(Debug, Serialize)]
+pub struct TokenizedSample {
+    pub text: String,
+    pub pieces: Vec<TokenPiece>,
+    /// Ids as fed to the model ([CLS] ... [SEP], before truncation/padding)
+    pub input_ids: Vec<i64>,
+}
+
+/// Loaded tokenizer details
+#[derive(Debug, Serialize)]
+pub struct TokenizerInfo {
+    pub model: String,
+    pub max_tokens: usize,
+    pub vocab_size: usize,
+    pub do_lower_case: bool,
+    pub special_tokens: SpecialTokens,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample: Option<TokenizedSample>,
+}
+
+#[cfg(feature = "inference")]
+fn tokenizer_info(
+    tokenizer: &Tokenizer,
+    model: String,
+    max_tokens: usize,
+    sample: Option<String>,
+) -> TokenizerInfo {
+    let sample = sample.map(|text| TokenizedSample {
+        pieces: tokenizer
+            .pieces(&text)
+            .into_iter()
+            .map(|(piece, id)| TokenPiece { piece, id })
+            .collect(),
+        input_ids: tokenizer.encode(&text, true),
+        text,
+    });
+
+    TokenizerInfo {
+        model,
+        max_tokens,
+        vocab_size: tokenizer.vocab_size(),
+        do_lower_case: tokenizer.do_lower_case(),
+        special_tokens: tokenizer.special_tokens(),
+        sample,
+    }
+}
+
+/// Describe the loaded tokenizer, optionally tokenizing `?sample=` (admin token required)
+#[cfg(feature = "inference")]
+pub async fn admin_tokenizer_handler(
+    _admin_token: AdminTokenClaims,
+    Query(query): Query<TokenizerQuery>,
+) -> Result<Response, ApiError> {
+    let settings = config::get_settings();
+
+    if let Some(sample) = &query.sample {
+        if sample.chars().count() > settings.max_text_chars {
+            return Err(ApiError::BadRequest(format!(
+                "sample exceeds {} characters",
+                settings.max_text_chars
+            )));
+        }
+    }
+
+    // Only hold the model lock long enough to clone the tokenizer handle
+    let tokenizer = inference::get_model().read().tokenizer();
+    let info = tokenizer_info(
+        &tokenizer,
+        settings.model_name.clone(),
+        settings.max_tokens,
+        query.sample,
+    );
+
+    Ok((StatusCode::OK, Json(info)).into_response())
+}
+
+/// Drop stale rate-limit keys from Redis now (admin token required)
+pub async fn admin_redis_gc_handler(_admin_token: AdminTokenClaims) -> Result<Response, ApiError> {
+    let report = gc::run()
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Redis GC failed: {}", e)))?;
+
+    tracing::warn!(
+        target: "audit",
+        action = "redis.gc",
+        scanned = report.scanned,
+        removed = report.removed,
+        "Redis garbage collection run"
+    );
+
+    Ok((StatusCode::OK, Json(report)).into_response())
+}
+
+/// Requests currently running for one API key
+#[derive(Debug, Serialize)]
+pub struct KeyInFlight {
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub key_id: uuid::Uuid,
+    pub requests: u32,
+}
+
+/// Response of `GET /v1/admin/stats`
+#[derive(Debug, Serialize)]
+pub struct AdminStats {
+    /// Keys with running embed requests, busiest first
+    pub in_flight: Vec<KeyInFlight>,
+    pub cache: CacheStats,
+}
+
+fn in_flight_stats(limiter: &concurrency::ConcurrencyLimiter) -> Vec<KeyInFlight> {
+    limiter
+        .snapshot()
+        .into_iter()
+        .map(|(key_id, requests)| KeyInFlight { key_id, requests })
+        .collect()
+}
+
+/// Live server stats: in-flight requests per key and cache size (admin token required)
+pub async fn admin_stats_handler(
+    State(state): State<AppState>,
+    _admin_token: AdminTokenClaims,
+) -> Result<Response, ApiError> {
+    let stats = AdminStats {
+        in_flight: in_flight_stats(concurrency::limiter()),
+        cache: state.cache.get_stats(),
+    };
+
+    Ok((StatusCode::OK, Json(stats)).into_response())
+}
+
+/// Redis reachability as seen by this instance
+#[derive(Debug, Serialize)]
+pub struct RedisStatus {
+    pub connected: bool,
+    pub latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Response of `GET /v1/admin/cache/stats`
+#[derive(Debug, Serialize)]
+pub struct CacheStatsResponse {
+    #[serde(flatten)]
+    pub cache: CacheStats,
+    pub redis: RedisStatus,
+}
+
+/// L1 size, hit/miss counters and Redis connectivity of this instance (admin token required)
+pub async fn admin_cache_stats_handler(
+    State(state): State<AppState>,
+    _admin_token: AdminTokenClaims,
+) -> Result<Response, ApiError> {
+    let probe = probes::measure(probes::REDIS, probes::check_redis(state.redis.clone())).await;
+    let stats = CacheStatsResponse {
+        cache: state.cache.get_stats(),
+        redis: RedisStatus {
+            connected: probe.ok,
+            latency_ms: probe.latency_ms,
+            error: probe.error,
+        },
+    };
+
+    Ok((StatusCode::OK, Json(stats)).into_response())
+}
+
+/// Request body for `POST /v1/admin/cache/clear`
+#[derive(Debug, Deserialize)]
+pub struct CacheClearRequest {
+    /// Also delete the embeddings in Redis, shared by every instance
+    #[serde(default)]
+    pub include_l2: bool,
+}
+
+/// Response of `POST /v1/admin/cache/clear`
+#[derive(Debug, Serialize)]
+pub struct CacheClearReport {
+    pub l1_cleared: usize,
+    /// Absent when L2 was left alone
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub l2_removed: Option<u64>,
+}
+
+/// Empty this instance's L1 cache and optionally the Redis L2 cache (admin token required)
+pub async fn admin_cache_clear_handler(
+    State(state): State<AppState>,
+    _admin_token: AdminTokenClaims,
+    StrictJson(payload): StrictJson<CacheClearRequest>,
+) -> Result<Response, ApiError> {
+    let l1_cleared = state.cache.clear_l1();
+    let l2_removed = if payload.include_l2 {
+        let removed = state
+            .cache
+            .clear_l2()
+            .await
+            .map_err(|e| ApiError::InternalError(format!("Redis error: {}", e)))?;
+        Some(removed)
+    } else {
+        None
+    };
+
+    tracing::warn!(
+        target: "audit",
+        action = "cache.clear",
+        l1_cleared,
+        l2_removed = ?l2_removed,
+        "Embedding cache cleared"
+    );
+
+    Ok((
+        StatusCode::OK,
+        Json(CacheClearReport {
+            l1_cleared,
+            l2_removed,
+        }),
+    )
+        .into_response())
+}
+
+/// Background jobs with their last run and next scheduled time (admin token required)
+pub async fn admin_jobs_handler(_admin_token: AdminTokenClaims) -> Result<Response, ApiError> {
+    Ok((StatusCode::OK, Json(jobs::statuses())).into_response())
+}
+
+/// Request body for `POST /v1/admin/usage-alerts`
+#[derive(Debug, Deserialize)]
+pub struct UsageAlertsRequest {
+    /// `false` stops usage alert emails for every organization
+    pub enabled: bool,
+}
+
+/// Switch usage alerts on or off globally (admin token required)
+pub async fn admin_usage_alerts_handler(
+    _admin_token: AdminTokenClaims,
+    StrictJson(payload): StrictJson<UsageAlertsRequest>,
+) -> Result<Response, ApiError> {
+    alerts::set_globally_enabled(payload.enabled)
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Redis error: {}", e)))?;
+
+    tracing::warn!(
+        target: "audit",
+        action = "usage_alert.toggle",
+        enabled = payload.enabled,
+        "Usage alerts toggled globally"
+    );
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({ "enabled": payload.enabled })),
+    )
+        .into_response())
+}
+
+/// Query parameters of `GET /v1/admin/signing-keys`
+#[cfg(feature = "control-plane")]
+#[derive(Debug, Deserialize)]
+pub struct SigningKeysQuery {
+    /// Only list keys signed by this kid
+    pub kid: Option<String>,
+}
+
+/// An active CWT API key and the kid that signed it
+#[cfg(feature = "control-plane")]
+#[derive(Debug, Serialize)]
+pub struct SignedKeyEntry {
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub id: uuid::Uuid,
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub key_id: uuid::Uuid,
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub organization_id: uuid::Uuid,
+    pub kid: Option<String>,
+}
+
+/// Response of `GET /v1/admin/signing-keys`
+#[cfg(feature = "control-plane")]
+#[derive(Debug, Serialize)]
+pub struct SigningKeysReport {
+    /// Kid new API keys are signed with
+    pub signing_kid: Option<String>,
+    /// Kids tokens are verified against, newest first
+    pub verification_kids: Vec<String>,
+    /// Active keys per kid (`unknown` = issued before kids were recorded)
+    pub active_keys_by_kid: std::collections::BTreeMap<String, usize>,
+    pub keys: Vec<SignedKeyEntry>,
+}
+
+#[cfg(feature = "control-plane")]
+fn signing_keys_report(
+    signing_kid: Option<String>,
+    verification_kids: Vec<String>,
+    keys: Vec<api_keys::SignedKey>,
+) -> SigningKeysReport {
+    let mut active_keys_by_kid = std::collections::BTreeMap::new();
+    for key in &keys {
+        let kid = key.signing_kid.as_deref().unwrap_or("unknown");
+        *active_keys_by_kid.entry(kid.to_string()).or_insert(0) += 1;
+    }
+
+    SigningKeysReport {
+        signing_kid,
+        verification_kids,
+        active_keys_by_kid,
+        keys: keys
+            .into_iter()
+            .map(|key| SignedKeyEntry {
+                id: key.id,
+                key_id: key.key_id,
+                organization_id: key.organization_id,
+                kid: key.signing_kid,
+            })
+            .collect(),
+    }
+}
+
+/// Which key signed each active API key, to drive re-issuance before an
+/// old public key is dropped (admin token required)
+#[cfg(feature = "control-plane")]
+pub async fn admin_signing_keys_handler(
+    State(state): State<AppState>,
+    _admin_token: AdminTokenClaims,
+    Query(query): Query<SigningKeysQuery>,
+) -> Result<Response, ApiError> {
+    let signing_kid = keyring::signing_kid();
+    let verification_kids = keyring::Keyring::configured()
+        .map_err(|e| ApiError::InternalError(format!("Invalid public key: {}", e)))?
+        .kids();
+
+    let keys = api_keys::active_by_signing_kid(&state.db, query.kid.as_deref()).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(signing_keys_report(signing_kid, verification_kids, keys)),
+    )
+        .into_response())
+}
+
+/// Request body for `POST /v1/admin/keys/reissue`; unset fields don't
+/// filter
+#[cfg(feature = "control-plane")]
+#[derive(Debug, Default, Deserialize)]
+pub struct ReissueKeysRequest {
+    pub organization_id: Option<DashlessUuid>,
+    /// Keys signed with this kid (see `GET /v1/admin/signing-keys`)
+    pub signing_kid: Option<String>,
+    /// Keys created before this date
+    pub created_before: Option<chrono::NaiveDate>,
+    /// Keys by id (as in `GET /v1/organizations/:org_id/keys`)
+    pub key_ids: Option<Vec<DashlessUuid>>,
+    /// List the keys that would be reissued without reissuing them
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[cfg(feature = "control-plane")]
+impl ReissueKeysRequest {
+    fn filter(&self) -> key_reissue::ReissueFilter {
+        key_reissue::ReissueFilter {
+            org_id: self.organization_id.map(DashlessUuid::into_inner),
+            signing_kid: self.signing_kid.clone(),
+            created_before: self
+                .created_before
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|at| at.and_utc()),
+            ids: self
+                .key_ids
+                .as_ref()
+                .map(|ids| ids.iter().map(|id| id.into_inner()).collect()),
+        }
+    }
+}
+
+/// Reissue API keys with current claims, under the same key_id; owners
+/// retrieve the new tokens and the old ones keep working until revoked
+/// (admin token required)
+#[cfg(feature = "control-plane")]
+pub async fn admin_reissue_keys_handler(
+    State(state): State<AppState>,
+    admin_token: AdminTokenClaims,
+    StrictJson(payload): StrictJson<ReissueKeysRequest>,
+) -> Result<Response, ApiError> {
+    let pool = &state.db;
+    let filter = payload.filter();
+
+    if payload.dry_run {
+        let keys: Vec<serde_json::Value> = key_reissue::candidates(pool, &filter)
+            .await?
+            .into_iter()
+            .map(|key| {
+                serde_json::json!({
+                    "id": DashlessUuid::new(key.id),
+                    "key_id": DashlessUuid::new(key.key_id),
+                    "organization_id": DashlessUuid::new(key.organization_id),
+                    "name": key.name,
+                    "signing_kid": key.signing_kid,
+                    "generation": key.token_generation + 1,
+                })
+            })
+            .collect();
+        return Ok((
+            StatusCode::OK,
+            Json(serde_json::json!({ "dry_run": true, "count": keys.len(), "keys": keys })),
+        )
+            .into_response());
+    }
+
+    let reissued = key_reissue::reissue(pool, &filter, chrono::Utc::now()).await?;
+
+    tracing::warn!(
+        target: "audit",
+        action = "api_key.reissue_batch",
+        count = reissued.len(),
+        requested_by = %admin_token.token_id(),
+        "API keys reissued"
+    );
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({ "count": reissued.len(), "reissues": reissued })),
+    )
+        .into_response())
+}
+
+/// Query parameters of `GET /v1/admin/keys/reissue`
+#[cfg(feature = "control-plane")]
+#[derive(Debug, Deserialize)]
+pub struct ReissueProgressQuery {
+    pub organization_id: Option<DashlessUuid>,
+}
+
+/// Progress of key reissuance: reissues per status, and each reissue
+/// (admin token required)
+#[cfg(feature = "control-plane")]
+pub async fn admin_reissue_progress_handler(
+    State(state): State<AppState>,
+    _admin_token: AdminTokenClaims,
+    Query(query): Query<ReissueProgressQuery>,
+) -> Result<Response, ApiError> {
+    let reissues = key_reissue::list(
+        &state.db,
+        query.organization_id.map(DashlessUuid::into_inner),
+    )
+    .await?;
+
+    let by_status: std::collections::BTreeMap<&str, usize> = key_reissue::ReissueStatus::ALL
+        .into_iter()
+        .map(|status| {
+            (
+                status.as_str(),
+                reissues.iter().filter(|r| r.status == status).count(),
+            )
+        })
+        .collect();
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({ "by_status": by_status, "reissues": reissues })),
+    )
+        .into_response())
+}
+
+/// Request body for `POST /v1/admin/keys/reissue/revoke-superseded`
+#[cfg(feature = "control-plane")]
+#[derive(Debug, Default, Deserialize)]
+pub struct RevokeSupersededRequest {
+    pub organization_id: Option<DashlessUuid>,
+    pub key_ids: Option<Vec<DashlessUuid>>,
+}
+
+/// Revoke the tokens superseded by retrieved reissues, leaving only the
+/// new ones valid (admin token required)
+#[cfg(feature = "control-plane")]
+pub async fn admin_revoke_superseded_handler(
+    State(state): State<AppState>,
+    admin_token: AdminTokenClaims,
+    StrictJson(payload): StrictJson<RevokeSupersededRequest>,
+) -> Result<Response, ApiError> {
+    let filter = key_reissue::ReissueFilter {
+        org_id: payload.organization_id.map(DashlessUuid::into_inner),
+        ids: payload
+            .key_ids
+            .map(|ids| ids.into_iter().map(DashlessUuid::into_inner).collect()),
+        ..Default::default()
+    };
+    let completed = key_reissue::revoke_superseded(&state.db, &filter, chrono::Utc::now()).await?;
+
+    tracing::warn!(
+        target: "audit",
+        action = "api_key.revoke_superseded_batch",
+        count = completed.len(),
+        requested_by = %admin_token.token_id(),
+        "Superseded API key tokens revoked"
+    );
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({ "count": completed.len(), "reissues": completed })),
+    )
+        .into_response())
+}
+
+/// Issued admin tokens, latest expiry first (admin token required)
+#[cfg(feature = "control-plane")]
+pub async fn admin_tokens_handler(
+    State(state): State<AppState>,
+    _admin_token: AdminTokenClaims,
+) -> Result<Response, ApiError> {
+    let tokens = admin::list(&state.db)
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
+
+    Ok((StatusCode::OK, Json(tokens)).into_response())
+}
+
+/// Revoke an admin token until it expires (admin token required)
+#[cfg(feature = "control-plane")]
+pub async fn admin_revoke_token_handler(
+    State(state): State<AppState>,
+    admin_token: AdminTokenClaims,
+    axum::extract::Path(token_id): axum::extract::Path<DashlessUuid>,
+) -> Result<Response, ApiError> {
+    let token_id = token_id.into_inner();
+    let mut conn = state.redis.clone();
+
+    let token = admin::revoke(&state.db, &mut conn, token_id)
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Failed to revoke admin token: {}", e)))?
+        .ok_or_else(|| ApiError::NotFound("Admin token not found".to_string()))?;
+
+    tracing::warn!(
+        target: "audit",
+        action = "admin_token.revoke",
+        token_id = %token_id,
+        revoked_by = %admin_token.token_id(),
+        "Admin token revoked"
+    );
+
+    Ok((StatusCode::OK, Json(token)).into_response())
+}
+
+/// A feature flag and the organizations allowlisted for it
+#[derive(Debug, Serialize)]
+pub struct FlagState {
+    pub flag: Flag,
+    pub mode: FlagMode,
+    /// Only consulted in `allowlist` mode
+    pub organizations: Vec<DashlessUuid>,
+}
+
+/// Feature flags of this deployment with their per-organization
+/// allowlists (admin token required)
+pub async fn admin_flags_handler(
+    State(state): State<AppState>,
+    _admin_token: AdminTokenClaims,
+) -> Result<Response, ApiError> {
+    let overrides = flags::overrides(&state.db)
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
+
+    let states: Vec<FlagState> = Flag::ALL
+        .into_iter()
+        .map(|flag| FlagState {
+            flag,
+            mode: flag.mode(),
+            organizations: overrides
+                .iter()
+                .filter(|(_, names)| names.iter().any(|n| n == flag.as_str()))
+                .map(|(org_id, _)| DashlessUuid::new(*org_id))
+                .collect(),
+        })
+        .collect();
+
+    Ok((StatusCode::OK, Json(states)).into_response())
+}
+
+/// Request body for `PUT /v1/admin/organizations/:org_id/flags`
+#[derive(Debug, Deserialize)]
+pub struct SetOrgFlagsRequest {
+    /// Flags to allowlist the organization for (replaces the current list)
+    pub flags: Vec<String>,
+}
+
+/// Replace the flags an organization is allowlisted for (admin token
+/// required)
+pub async fn admin_set_org_flags_handler(
+    State(state): State<AppState>,
+    admin_token: AdminTokenClaims,
+    axum::extract::Path(org_id): axum::extract::Path<DashlessUuid>,
+    StrictJson(payload): StrictJson<SetOrgFlagsRequest>,
+) -> Result<Response, ApiError> {
+    let org_id = org_id.into_inner();
+    let requested = payload
+        .flags
+        .iter()
+        .map(|name| {
+            Flag::parse(name).ok_or_else(|| {
+                let known: Vec<&str> = Flag::ALL.iter().map(|f| f.as_str()).collect();
+                ApiError::BadRequest(format!(
+                    "Unknown feature flag '{}' (known: {})",
+                    name,
+                    known.join(", ")
+                ))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let found = flags::set_org_flags(&state.db, org_id, &requested)
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
+    if !found {
+        return Err(ApiError::NotFound("Organization not found".to_string()));
+    }
+
+    tracing::warn!(
+        target: "audit",
+        action = "feature_flags.update",
+        org_id = %org_id,
+        flags = ?payload.flags,
+        updated_by = %admin_token.token_id(),
+        "Organization feature flags updated"
+    );
+
+    let flags = flags::org_flags(org_id).await;
+    Ok((StatusCode::OK, Json(serde_json::json!({ "flags": flags }))).into_response())
+}
+
+/// Request body for `POST /v1/admin/organizations/:org_id/trial`
+#[derive(Debug, Deserialize)]
+pub struct GrantTrialRequest {
+    /// Trial tier (`pro` or `scale`), above the organization's tier
+    pub tier: String,
+    /// Length of the trial, or of the extension if one of this tier is running
+    pub days: i64,
+}
+
+/// Grant an organization a trial of a higher tier, or extend the running
+/// one (admin token required)
+pub async fn admin_grant_trial_handler(
+    State(state): State<AppState>,
+    admin_token: AdminTokenClaims,
+    axum::extract::Path(org_id): axum::extract::Path<DashlessUuid>,
+    StrictJson(payload): StrictJson<GrantTrialRequest>,
+) -> Result<Response, ApiError> {
+    let org_id = org_id.into_inner();
+    let tier = TierType::parse(&payload.tier).ok_or_else(|| {
+        ApiError::BadRequest(format!(
+            "Unknown tier '{}' (expected pro or scale)",
+            payload.tier
+        ))
+    })?;
+    if !(1..=trial::MAX_TRIAL_DAYS).contains(&payload.days) {
+        return Err(ApiError::BadRequest(format!(
+            "days must be between 1 and {}",
+            trial::MAX_TRIAL_DAYS
+        )));
+    }
+
+    let now = chrono::Utc::now();
+    let granted = trial::grant(&state.db, org_id, tier, payload.days, now)
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
+    let trial = match granted {
+        trial::Grant::Granted(trial) => trial,
+        trial::Grant::NotFound => {
+            return Err(ApiError::NotFound("Organization not found".to_string()))
+        }
+        trial::Grant::NotAnUpgrade(base) => {
+            return Err(ApiError::BadRequest(format!(
+                "The organization is already on the {} tier",
+                base.display_name()
+            )))
+        }
+    };
+
+    tracing::warn!(
+        target: "audit",
+        action = "trial.grant",
+        org_id = %org_id,
+        tier = %payload.tier,
+        days = payload.days,
+        expires_at = %trial.expires_at,
+        granted_by = %admin_token.token_id(),
+        "Organization trial granted"
+    );
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "trial": trial,
+            "days_left": trial.days_left(now),
+        })),
+    )
+        .into_response())
+}
+
+#[cfg(feature = "control-plane")]
+/// Response of the organization limits endpoints
+#[derive(Debug, Serialize)]
+pub struct OrgLimits {
+    /// Limits set for the organization (null = the tier's)
+    pub overrides: limits::Overrides,
+    /// Limits in effect for the organization's API keys
+    pub effective: limits::CreationLimits,
+}
+
+#[cfg(feature = "control-plane")]
+async fn org_limits(state: &AppState, org_id: uuid::Uuid) -> Result<OrgLimits, ApiError> {
+    Ok(OrgLimits {
+        overrides: limits::overrides(&state.db, org_id).await?,
+        effective: limits::for_organization(&state.db, org_id).await?,
+    })
+}
+
+#[cfg(feature = "control-plane")]
+/// Creation limits of an organization (admin token required)
+pub async fn admin_org_limits_handler(
+    State(state): State<AppState>,
+    _admin_token: AdminTokenClaims,
+    axum::extract::Path(org_id): axum::extract::Path<DashlessUuid>,
+) -> Result<Response, ApiError> {
+    let limits = org_limits(&state, org_id.into_inner()).await?;
+    Ok((StatusCode::OK, Json(limits)).into_response())
+}
+
+#[cfg(feature = "control-plane")]
+/// Replace the creation limits set for an organization; null fields fall
+/// back to the tier's, 0 lifts a limit (admin token required)
+pub async fn admin_set_org_limits_handler(
+    State(state): State<AppState>,
+    admin_token: AdminTokenClaims,
+    axum::extract::Path(org_id): axum::extract::Path<DashlessUuid>,
+    StrictJson(payload): StrictJson<limits::Overrides>,
+) -> Result<Response, ApiError> {
+    let org_id = org_id.into_inner();
+    limits::set_overrides(&state.db, org_id, &payload).await?;
+
+    tracing::warn!(
+        target: "audit",
+        action = "organization.limits_update",
+        org_id = %org_id,
+        max_organizations = ?payload.max_organizations,
+        max_api_keys = ?payload.max_api_keys,
+        creations_per_hour = ?payload.creations_per_hour,
+        updated_by = %admin_token.token_id(),
+        "Organization creation limits updated"
+    );
+
+    let limits = org_limits(&state, org_id).await?;
+    Ok((StatusCode::OK, Json(limits)).into_response())
+}
+
+/// Response of `GET /v1/admin/config`, also logged at startup
+#[derive(Debug, Serialize)]
+pub struct EffectiveConfig {
+    /// Settings in effect, secrets masked
+    pub settings: serde_json::Value,
+    /// Where each variable the settings were read from came from
+    pub sources: std::collections::BTreeMap<String, config::provenance::Source>,
+    pub build: super::BuildInfo,
+}
+
+impl EffectiveConfig {
+    pub fn current() -> Self {
+        let settings = config::get_settings().masked();
+        EffectiveConfig {
+            settings,
+            sources: config::provenance::sources(),
+            build: super::BuildInfo::current(),
+        }
+    }
+}
+
+/// The configuration this instance runs with, secrets masked (admin token
+/// required)
+pub async fn admin_config_handler(_admin_token: AdminTokenClaims) -> Result<Response, ApiError> {
+    Ok((StatusCode::OK, Json(EffectiveConfig::current())).into_response())
+}
+
+/// Build, model and configuration of this instance, for fleet inventory
+/// (admin token required)
+pub async fn instance_info_handler(_admin_token: AdminTokenClaims) -> Result<Response, ApiError> {
+    Ok((StatusCode::OK, Json(InstanceInfo::current().await)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "inference")]
+    fn test_tokenizer_info_with_sample() {
+        let dir = std::env::temp_dir().join(format!("smally-tokenizer-{}", uuid::Uuid::now_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("vocab.txt"),
+            ["[PAD]", "[UNK]", "[CLS]", "[SEP]", "hello"].join("\n"),
+        )
+        .unwrap();
+        let tokenizer = Tokenizer::new(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        let info = tokenizer_info(&tokenizer, "test".to_string(), 128, Some("Hello".into()));
+        let json = serde_json::to_value(&info).unwrap();
+
+        assert_eq!(json["vocab_size"], 5);
+        assert_eq!(json["do_lower_case"], true);
+        assert_eq!(json["special_tokens"]["cls"]["id"], 2);
+        assert_eq!(json["special_tokens"]["unk"]["token"], "[UNK]");
+        assert_eq!(json["sample"]["pieces"][0]["piece"], "hello");
+        assert_eq!(json["sample"]["pieces"][0]["id"], 4);
+        assert_eq!(json["sample"]["input_ids"], serde_json::json!([2, 4, 3]));
+
+        let info = tokenizer_info(&tokenizer, "test".to_string(), 128, None);
+        assert!(serde_json::to_value(&info).unwrap().get("sample").is_none());
+    }
+
+    #[test]
+    fn test_in_flight_stats() {
+        let limiter = concurrency::ConcurrencyLimiter::new();
+        let busy = uuid::Uuid::now_v7();
+        let idle = uuid::Uuid::now_v7();
+        let _a = limiter.try_acquire(busy, 0).unwrap();
+        let _b = limiter.try_acquire(busy, 0).unwrap();
+        drop(limiter.try_acquire(idle, 0));
+
+        let json = serde_json::to_value(in_flight_stats(&limiter)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{ "key_id": busy.simple().to_string(), "requests": 2 }])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "control-plane")]
+    fn test_signing_keys_report_counts_per_kid() {
+        let key = |kid: Option<&str>| api_keys::SignedKey {
+            id: uuid::Uuid::now_v7(),
+            key_id: uuid::Uuid::now_v7(),
+            organization_id: uuid::Uuid::now_v7(),
+            signing_kid: kid.map(String::from),
+        };
+
+        let report = signing_keys_report(
+            Some("new".to_string()),
+            vec!["new".to_string(), "old".to_string()],
+            vec![
+                key(Some("old")),
+                key(None),
+                key(Some("new")),
+                key(Some("old")),
+            ],
+        );
+        let json = serde_json::to_value(&report).unwrap();
+
+        assert_eq!(json["signing_kid"], "new");
+        assert_eq!(
+            json["active_keys_by_kid"],
+            serde_json::json!({ "new": 1, "old": 2, "unknown": 1 })
+        );
+        assert_eq!(json["keys"].as_array().unwrap().len(), 4);
+        assert_eq!(json["keys"][1]["kid"], serde_json::Value::Null);
+    }
+}
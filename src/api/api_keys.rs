@@ -5,110 +5,30 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use chrono::Utc;
 use serde_json::json;
-use uuid::Uuid;
 
-use crate::auth::session::SessionClaims;
-use crate::auth::{sign_token_direct, TokenData};
-use crate::config;
+use crate::auth::service_account::Principal;
 use crate::database;
-use crate::models::{APIKey, APIKeyResponse, CreateAPIKeyRequest, OrganizationRole, TierType};
+use crate::models::{APIKeyResponse, CreateAPIKeyRequest};
+use crate::services;
 use crate::uuid_dashless::DashlessUuid;
 
-use super::users::ApiError;
+use super::error::ApiError;
+use super::strict::StrictJson;
+use super::versions::ApiVersion;
 
 /// Create a new API key (CWT token) for an organization
 pub async fn create_api_key_handler(
-    claims: SessionClaims,
+    principal: Principal,
     Path(org_id): Path<DashlessUuid>,
-    Json(payload): Json<CreateAPIKeyRequest>,
+    StrictJson(payload): StrictJson<CreateAPIKeyRequest>,
 ) -> Result<Response, ApiError> {
-    let pool = database::get_db();
-    let user_id: uuid::Uuid = claims
-        .sub
-        .parse()
-        .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?;
-    let org_id = org_id.into_inner();
-
-    // Check if user is a member of the organization
-    #[derive(sqlx::FromRow)]
-    struct MemberInfo {
-        role: OrganizationRole,
-        tier: TierType,
-    }
-
-    let member = sqlx::query_as::<_, MemberInfo>(
-        "SELECT om.role, o.tier
-         FROM organization_members om
-         INNER JOIN organizations o ON om.organization_id = o.id
-         WHERE om.organization_id = $1 AND om.user_id = $2",
-    )
-    .bind(org_id)
-    .bind(user_id)
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
-    .ok_or_else(|| {
-        ApiError::Unauthorized("You are not a member of this organization".to_string())
-    })?;
-
-    // Only owners and admins can create API keys
-    if member.role != OrganizationRole::Owner && member.role != OrganizationRole::Admin {
-        return Err(ApiError::Unauthorized(
-            "Only owners and admins can create API keys".to_string(),
-        ));
-    }
-
-    // Get organization tier (use provided tier or organization's tier)
-    let tier = payload.tier.unwrap_or(member.tier);
-
-    // Generate key_id (UUIDv7)
-    let key_id = Uuid::now_v7();
-
-    // Create API key record in database
-    let api_key = sqlx::query_as::<_, APIKey>(
-        "INSERT INTO api_keys (organization_id, key_id, name, is_active, created_at, last_used_at)
-         VALUES ($1, $2, $3, $4, $5, $6)
-         RETURNING *",
-    )
-    .bind(org_id)
-    .bind(key_id)
-    .bind(&payload.name)
-    .bind(true)
-    .bind(Utc::now().naive_utc())
-    .bind(None::<chrono::NaiveDateTime>)
-    .fetch_one(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Failed to create API key: {}", e)))?;
-
-    // Generate CWT token
-    let settings = config::get_settings();
-    let private_key_bytes = hex::decode(&settings.token_private_key)
-        .map_err(|e| ApiError::InternalError(format!("Invalid private key: {}", e)))?;
-
-    let signing_key = ed25519_dalek::SigningKey::from_bytes(
-        &private_key_bytes[..]
-            .try_into()
-            .map_err(|_| ApiError::InternalError("Invalid private key length".to_string()))?,
-    );
-
-    // Create token data
-    let (max_tokens, monthly_quota) = get_tier_limits(tier);
-
-    let token_data = TokenData {
-        org_id,
-        key_id,
-        tier,
-        max_tokens: max_tokens as i32,
-        monthly_quota,
-    };
-
-    let token = sign_token_direct(&token_data, &signing_key)
-        .map_err(|e| ApiError::InternalError(format!("Failed to sign token: {}", e)))?;
+    let actor = principal.actor()?;
 
-    // Add prefix to token
-    let prefixed_token = format!("{}{}", settings.api_key_prefix, token);
+    let issued =
+        services::api_keys::create(database::get_db(), org_id.into_inner(), actor, &payload)
+            .await?;
+    let api_key = issued.api_key;
 
     let response = APIKeyResponse {
         id: api_key.id,
@@ -117,7 +37,13 @@ pub async fn create_api_key_handler(
         is_active: api_key.is_active,
         created_at: api_key.created_at,
         last_used_at: api_key.last_used_at,
-        token: Some(prefixed_token),
+        require_request_signing: api_key.require_request_signing,
+        strict_validation: api_key.strict_validation,
+        api_version: ApiVersion::pinned(api_key.api_version.as_deref())
+            .as_str()
+            .to_string(),
+        token: Some(issued.token),
+        signing_secret: issued.signing_secret,
     };
 
     Ok((StatusCode::CREATED, Json(response)).into_response())
@@ -125,39 +51,12 @@ pub async fn create_api_key_handler(
 
 /// List API keys for an organization
 pub async fn list_api_keys_handler(
-    claims: SessionClaims,
-    Path(org_id): Path<i64>,
+    principal: Principal,
+    Path(org_id): Path<DashlessUuid>,
 ) -> Result<Response, ApiError> {
-    let pool = database::get_db();
-    let user_id: i64 = claims
-        .sub
-        .parse()
-        .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?;
-
-    // Check if user is a member of the organization
-    let member_exists = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM organization_members WHERE organization_id = $1 AND user_id = $2",
-    )
-    .bind(org_id)
-    .bind(user_id)
-    .fetch_one(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
-
-    if member_exists == 0 {
-        return Err(ApiError::Unauthorized(
-            "You are not a member of this organization".to_string(),
-        ));
-    }
+    let actor = principal.actor()?;
 
-    // Get API keys
-    let api_keys = sqlx::query_as::<_, APIKey>(
-        "SELECT * FROM api_keys WHERE organization_id = $1 ORDER BY created_at DESC",
-    )
-    .bind(org_id)
-    .fetch_all(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
+    let api_keys = services::api_keys::list(database::get_db(), org_id.into_inner(), actor).await?;
 
     let responses: Vec<APIKeyResponse> = api_keys
         .into_iter()
@@ -168,7 +67,13 @@ pub async fn list_api_keys_handler(
             is_active: key.is_active,
             created_at: key.created_at,
             last_used_at: key.last_used_at,
+            require_request_signing: key.require_request_signing,
+            strict_validation: key.strict_validation,
+            api_version: ApiVersion::pinned(key.api_version.as_deref())
+                .as_str()
+                .to_string(),
             token: None, // Don't return token in list
+            signing_secret: None,
         })
         .collect();
 
@@ -177,93 +82,98 @@ pub async fn list_api_keys_handler(
 
 /// Revoke an API key
 pub async fn revoke_api_key_handler(
-    claims: SessionClaims,
-    Path((org_id, key_id)): Path<(i64, i64)>,
+    principal: Principal,
+    Path((org_id, key_id)): Path<(DashlessUuid, DashlessUuid)>,
 ) -> Result<Response, ApiError> {
-    let pool = database::get_db();
-    let user_id: i64 = claims
-        .sub
-        .parse()
-        .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?;
-
-    // Check if user is owner or admin of the organization
-    let member_role = sqlx::query_scalar::<_, String>(
-        "SELECT role FROM organization_members WHERE organization_id = $1 AND user_id = $2",
+    let actor = principal.actor()?;
+
+    services::api_keys::revoke(
+        database::get_db(),
+        org_id.into_inner(),
+        actor,
+        key_id.into_inner(),
     )
-    .bind(org_id)
-    .bind(user_id)
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
-    .ok_or_else(|| {
-        ApiError::Unauthorized("You are not a member of this organization".to_string())
-    })?;
-
-    let role: OrganizationRole = serde_json::from_str(&format!("\"{}\"", member_role))
-        .map_err(|e| ApiError::InternalError(format!("Invalid role: {}", e)))?;
-
-    if role != OrganizationRole::Owner && role != OrganizationRole::Admin {
-        return Err(ApiError::Unauthorized(
-            "Only owners and admins can revoke API keys".to_string(),
-        ));
-    }
+    .await?;
 
-    // Deactivate the API key
-    let result =
-        sqlx::query("UPDATE api_keys SET is_active = false WHERE id = $1 AND organization_id = $2")
-            .bind(key_id)
-            .bind(org_id)
-            .execute(pool)
-            .await
-            .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
+    Ok((
+        StatusCode::OK,
+        Json(json!({ "message": "API key revoked successfully" })),
+    )
+        .into_response())
+}
 
-    if result.rows_affected() == 0 {
-        return Err(ApiError::BadRequest("API key not found".to_string()));
-    }
+/// Reissued tokens waiting to be retrieved (owners and admins)
+pub async fn list_reissued_keys_handler(
+    principal: Principal,
+    Path(org_id): Path<DashlessUuid>,
+) -> Result<Response, ApiError> {
+    let actor = principal.actor()?;
 
-    // TODO: Add key to Redis revocation list
-    // This requires getting the UUID key_id from the database first
-    let uuid_key_id = sqlx::query_scalar::<_, Uuid>("SELECT key_id FROM api_keys WHERE id = $1")
-        .bind(key_id)
-        .fetch_one(pool)
-        .await
-        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
-
-    // Add to Redis revocation list (expires in 1 year - same as token expiration)
-    if let Ok(redis_client) = redis::Client::open(config::get_settings().redis_url.as_str()) {
-        if let Ok(mut conn) = redis_client.get_multiplexed_async_connection().await {
-            use redis::AsyncCommands;
-            let _: Result<(), _> = conn
-                .set_ex(
-                    format!("revoked:{}", uuid_key_id),
-                    1,
-                    365 * 24 * 60 * 60, // 1 year in seconds
-                )
-                .await;
-        }
-    }
+    let pending = services::key_reissue::pending(
+        database::get_db(),
+        org_id.into_inner(),
+        actor,
+        chrono::Utc::now(),
+    )
+    .await?;
+
+    Ok((StatusCode::OK, Json(pending)).into_response())
+}
+
+/// Retrieve a reissued token (once; owners and admins). The key's older
+/// tokens keep working until they're revoked.
+pub async fn retrieve_reissued_key_handler(
+    principal: Principal,
+    Path((org_id, reissue_id)): Path<(DashlessUuid, DashlessUuid)>,
+) -> Result<Response, ApiError> {
+    let actor = principal.actor()?;
+
+    let (reissue, token) = services::key_reissue::retrieve(
+        database::get_db(),
+        org_id.into_inner(),
+        actor,
+        reissue_id.into_inner(),
+        chrono::Utc::now(),
+    )
+    .await?;
 
     Ok((
         StatusCode::OK,
-        Json(json!({ "message": "API key revoked successfully" })),
+        Json(json!({
+            "id": DashlessUuid::new(reissue.api_key_id),
+            "key_id": DashlessUuid::new(reissue.key_id),
+            "generation": reissue.generation,
+            "token": token,
+        })),
     )
         .into_response())
 }
 
-/// Get tier limits
-fn get_tier_limits(tier: TierType) -> (usize, i32) {
-    let settings = config::get_settings();
-    match tier {
-        TierType::Free => (settings.max_tokens, settings.free_tier_limit),
-        TierType::Pro => (settings.max_tokens, settings.pro_tier_limit),
-        TierType::Scale => (settings.max_tokens, settings.scale_tier_limit),
-    }
+/// Requests and distinct client IPs of a key over the last 24 hours
+pub async fn key_usage_handler(
+    principal: Principal,
+    Path((org_id, key_id)): Path<(DashlessUuid, DashlessUuid)>,
+) -> Result<Response, ApiError> {
+    let actor = principal.actor()?;
+
+    let usage = services::api_keys::usage(
+        database::get_db(),
+        org_id.into_inner(),
+        actor,
+        key_id.into_inner(),
+    )
+    .await?;
+
+    Ok((StatusCode::OK, Json(usage)).into_response())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::helpers::{cleanup_db, create_test_user, setup};
+    use crate::auth::{opaque, TokenClaims, TokenData};
+    use crate::models::TierType;
+    use crate::test_utils::factory::{self, TestUser};
+    use crate::test_utils::helpers::{cleanup_db, setup};
     use axum::{
         body::Body,
         http::{Request, StatusCode},
@@ -272,6 +182,7 @@ mod tests {
     use serde_json::json;
     use serial_test::serial;
     use tower::ServiceExt;
+    use uuid::Uuid;
 
     fn app() -> Router {
         Router::new()
@@ -295,7 +206,11 @@ mod tests {
         setup().await;
         cleanup_db().await;
 
-        let (_user_id, token, org_id) = create_test_user("test@example.com", "password123").await;
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
 
         let app = app();
 
@@ -307,7 +222,7 @@ mod tests {
             .oneshot(
                 Request::builder()
                     .method("POST")
-                    .uri(&format!("/organizations/{}/keys", org_id))
+                    .uri(format!("/organizations/{}/keys", org_id))
                     .header("authorization", format!("Bearer {}", token))
                     .header("content-type", "application/json")
                     .body(Body::from(serde_json::to_vec(&payload).unwrap()))
@@ -326,6 +241,7 @@ mod tests {
         assert_eq!(key_response.name, "Test API Key");
         assert!(key_response.is_active);
         assert!(key_response.token.is_some());
+        assert_eq!(key_response.api_version, ApiVersion::LATEST.as_str());
 
         // Verify token starts with configured prefix
         let token_str = key_response.token.unwrap();
@@ -341,7 +257,11 @@ mod tests {
         setup().await;
         cleanup_db().await;
 
-        let (_user_id, token, org_id) = create_test_user("test@example.com", "password123").await;
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
 
         // Create a key first
         let app1 = app();
@@ -350,7 +270,7 @@ mod tests {
         app1.oneshot(
             Request::builder()
                 .method("POST")
-                .uri(&format!("/organizations/{}/keys", org_id))
+                .uri(format!("/organizations/{}/keys", org_id))
                 .header("authorization", format!("Bearer {}", token))
                 .header("content-type", "application/json")
                 .body(Body::from(serde_json::to_vec(&payload).unwrap()))
@@ -365,7 +285,7 @@ mod tests {
             .oneshot(
                 Request::builder()
                     .method("GET")
-                    .uri(&format!("/organizations/{}/keys", org_id))
+                    .uri(format!("/organizations/{}/keys", org_id))
                     .header("authorization", format!("Bearer {}", token))
                     .body(Body::empty())
                     .unwrap(),
@@ -394,7 +314,11 @@ mod tests {
         setup().await;
         cleanup_db().await;
 
-        let (_user_id, token, org_id) = create_test_user("test@example.com", "password123").await;
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
 
         // Create a key first
         let app1 = app();
@@ -404,7 +328,7 @@ mod tests {
             .oneshot(
                 Request::builder()
                     .method("POST")
-                    .uri(&format!("/organizations/{}/keys", org_id))
+                    .uri(format!("/organizations/{}/keys", org_id))
                     .header("authorization", format!("Bearer {}", token))
                     .header("content-type", "application/json")
                     .body(Body::from(serde_json::to_vec(&payload).unwrap()))
@@ -424,7 +348,7 @@ mod tests {
             .oneshot(
                 Request::builder()
                     .method("DELETE")
-                    .uri(&format!(
+                    .uri(format!(
                         "/organizations/{}/keys/{}",
                         org_id, key_response.id
                     ))
@@ -446,10 +370,8 @@ mod tests {
         setup().await;
         cleanup_db().await;
 
-        let (_user_id1, _token1, org_id1) =
-            create_test_user("owner@example.com", "password123").await;
-        let (_user_id2, token2, _org_id2) =
-            create_test_user("other@example.com", "password123").await;
+        let org_id1 = factory::user("owner@example.com").await.org_id;
+        let token2 = factory::user("other@example.com").await.session_token;
 
         let app = app();
 
@@ -462,7 +384,7 @@ mod tests {
             .oneshot(
                 Request::builder()
                     .method("POST")
-                    .uri(&format!("/organizations/{}/keys", org_id1))
+                    .uri(format!("/organizations/{}/keys", org_id1))
                     .header("authorization", format!("Bearer {}", token2))
                     .header("content-type", "application/json")
                     .body(Body::from(serde_json::to_vec(&payload).unwrap()))
@@ -475,4 +397,159 @@ mod tests {
 
         cleanup_db().await;
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_key_ids_accept_both_forms_and_return_dashless() {
+        setup().await;
+        cleanup_db().await;
+
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
+
+        // Create with the hyphenated org id
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/organizations/{}/keys", org_id.hyphenated()))
+                    .header("authorization", format!("Bearer {}", token))
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        serde_json::to_vec(&json!({"name": "Key"})).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let key_id = json["id"].as_str().unwrap().to_string();
+        assert_eq!(key_id.len(), 32);
+        assert!(!key_id.contains('-'));
+
+        // List with the dashless org id
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri(format!("/organizations/{}/keys", org_id.simple()))
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Revoke with a hyphenated key id
+        let key_uuid = Uuid::parse_str(&key_id).unwrap();
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .method("DELETE")
+                    .uri(format!(
+                        "/organizations/{}/keys/{}",
+                        org_id.simple(),
+                        key_uuid.hyphenated()
+                    ))
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_opaque_key_lookup_against_schema() {
+        setup().await;
+        cleanup_db().await;
+
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
+
+        // Disabled by default: creation is refused
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/organizations/{}/keys", org_id))
+                    .header("authorization", format!("Bearer {}", token))
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        serde_json::to_vec(&json!({"name": "Opaque", "opaque": true})).unwrap(),
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // Stored the way the handler stores it when enabled
+        let key = opaque::generate_opaque_key();
+        let key_id = Uuid::now_v7();
+        let token_data = TokenClaims::from_token_data(TokenData {
+            org_id,
+            key_id,
+            tier: TierType::Free,
+            max_tokens: 64,
+            monthly_quota: 1000,
+            require_signing: false,
+            max_concurrency: None,
+            scopes: None,
+            models: None,
+            api_version: None,
+            strict_validation: false,
+            generation: 0,
+        })
+        .to_cbor_bytes()
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO api_keys (organization_id, key_id, name, key_hash, token_data)
+             VALUES ($1, $2, 'Opaque', $3, $4)",
+        )
+        .bind(org_id)
+        .bind(key_id)
+        .bind(opaque::hash_opaque_key(&key))
+        .bind(token_data)
+        .execute(database::get_db())
+        .await
+        .unwrap();
+
+        let cache = opaque::ApiKeyCache::new(std::time::Duration::ZERO, 16);
+        let claims = cache.lookup(&key).await.unwrap().unwrap();
+        assert_eq!(claims.key_id(), key_id);
+        assert_eq!(claims.org_id(), org_id);
+        assert_eq!(claims.max_tokens(), 64);
+
+        // Unknown and deactivated keys resolve to nothing
+        assert!(cache
+            .lookup(&opaque::generate_opaque_key())
+            .await
+            .unwrap()
+            .is_none());
+        sqlx::query("UPDATE api_keys SET is_active = false WHERE key_id = $1")
+            .bind(key_id)
+            .execute(database::get_db())
+            .await
+            .unwrap();
+        assert!(cache.lookup(&key).await.unwrap().is_none());
+
+        cleanup_db().await;
+    }
 }
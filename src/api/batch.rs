@@ -0,0 +1,693 @@
+//! Batch embedding (`POST /v1/embed/batch`)
+//!
+//! Embeds up to `MAX_BATCH_SIZE` texts in one request: `{"texts": [...],
+//! "normalize": bool}` is answered with the embeddings in input order, each
+//! with its token count and whether it came from the cache, plus the total
+//! tokens and the latency of the whole batch. An optional `model` is
+//! resolved against the organization's and the key's allowed models as for
+//! `/v1/embed`, and its token ceiling bounds every text.
+//!
+//! A batch is one request to the key's limits: one concurrency slot, one
+//! rate limit hit (one request of a free tier's monthly quota), one
+//! api_request_log row, and one usage event billing the tokens of every
+//! text. Each text is looked up in the cache; only the misses run inference,
+//! in one inference queue slot and one batched model run
+//! (`EmbeddingModel::encode_batch`).
+//!
+//! Like `/v1/embed`, each embedding carries the `content_hash` of its text,
+//! and the response has `X-Cache`, `X-Inference-Ms` and `X-Tokens` headers
+//! for the batch as a whole (see `batch_cache_level`).
+//!
+//! The endpoint is dark-launched behind `FEATURE_BATCH_API` (off by
+//! default, see `flags`).
+//!
+//! Every text is validated before anything is counted: an empty list, an
+//! empty text or one over the key's limits rejects the whole batch with a
+//! 400 naming the text.
+
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Instant;
+use utoipa::ToSchema;
+
+use super::ip_limit::ClientIp;
+use super::{
+    insert_cache_headers, rate_limit_exceeded, rate_limit_headers, shed, strict,
+    verify_request_signature, ApiError, Embedding, ErrorResponse, RequestOrigin,
+};
+use crate::database::api_request_log::NewRequest;
+use crate::flags::{self, Flag};
+use crate::{auth, billing, cache, config, inference, monitoring, residency};
+
+/// Request to embed several texts
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchEmbedRequest {
+    /// Texts to embed (1 to `MAX_BATCH_SIZE`)
+    #[schema(example = json!(["Red cotton t-shirt", "Blue denim jacket"]))]
+    pub texts: Vec<String>,
+    /// Normalize the embeddings (default: false)
+    #[serde(default)]
+    #[schema(example = false)]
+    pub normalize: bool,
+    /// Model to use (see `GET /v1/models`); omitted = the organization's
+    /// default. 403 if the key or organization doesn't allow it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "all-MiniLM-L6-v2")]
+    pub model: Option<String>,
+}
+
+/// Embedding of one text of a batch
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchEmbedding {
+    /// Position of the text in `texts`
+    #[schema(example = 0)]
+    pub index: usize,
+    #[schema(value_type = Vec<f32>, example = json!([0.1, 0.2, 0.3]))]
+    pub embedding: Embedding,
+    /// Deterministic hash of the text under the model, as returned by
+    /// `/v1/embed`
+    #[schema(example = "9f86d081884c7d65")]
+    pub content_hash: String,
+    #[schema(example = 5)]
+    pub tokens: usize,
+    /// Served from the cache
+    #[schema(example = false)]
+    pub cached: bool,
+}
+
+/// Embeddings of a batch, in input order
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BatchEmbedResponse {
+    pub embeddings: Vec<BatchEmbedding>,
+    #[schema(example = "all-MiniLM-L6-v2@2024-01")]
+    pub model: String,
+    /// Tokens of every text (billed)
+    #[schema(example = 10)]
+    pub total_tokens: usize,
+    /// Texts served from the cache
+    #[schema(example = 1)]
+    pub cached: usize,
+    /// Time to embed the whole batch
+    #[schema(example = 3.2)]
+    pub latency_ms: f64,
+}
+
+/// An embedded text: values, tokens and the cache tier that served it
+struct Item {
+    embedding: Vec<f32>,
+    tokens: usize,
+    cache_level: Option<cache::CacheLevel>,
+}
+
+/// Embed several texts in one request
+///
+/// Texts are embedded as `/v1/embed` would embed each one, except that the
+/// batch counts as one request against the key's rate limit and is billed
+/// as one usage event with the tokens of every text. Cached texts skip
+/// inference. The batch is rejected with 400 if it is empty, has more than
+/// `MAX_BATCH_SIZE` texts, or any text is empty or over the key's limits.
+///
+/// Only served while `FEATURE_BATCH_API` enables it for the key's
+/// organization; otherwise 404 (or 403 in allowlist mode)
+/// `feature_not_enabled`, as for `/v1/embed/ws`.
+#[utoipa::path(
+    post,
+    path = "/v1/embed/batch",
+    tag = "embeddings",
+    request_body = BatchEmbedRequest,
+    responses(
+        (status = 200, description = "Embeddings in input order", body = BatchEmbedResponse,
+         headers(
+             ("X-Cache" = String, description = "Cache tier of the batch: miss if any text ran inference, else l2 if any text came from Redis, else l1"),
+             ("X-Inference-Ms" = String, description = "Model inference time of the batched run in milliseconds (absent when every text was cached)"),
+             ("X-Tokens" = String, description = "Tokens of every text")
+         )
+        ),
+        (status = 400, description = "Invalid request (names the offending text)", body = ErrorResponse),
+        (status = 401, description = "Unauthorized - invalid or missing API key, or missing/invalid request signature", body = ErrorResponse),
+        (status = 403, description = "Batch embedding is only enabled for allowlisted organizations (`feature_not_enabled`)", body = ErrorResponse),
+        (status = 404, description = "Batch embedding is not enabled (`feature_not_enabled`)", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded, or too many concurrent requests for the key", body = ErrorResponse),
+        (status = 451, description = "The organization is pinned to another region (`wrong_region`, names its `base_url`)", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+        (status = 503, description = "Inference queue full; retry after `estimated_wait_ms`", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn batch_embed_handler(
+    claims: auth::TokenClaims,
+    client_ip: ClientIp,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    if !claims.has_scope(auth::SCOPE_EMBED) {
+        return Err(ApiError::Unauthorized(
+            "API key is not allowed to create embeddings".to_string(),
+        ));
+    }
+    flags::check(Flag::BatchApi, claims.org_id()).await?;
+
+    embed_batch(claims, client_ip, method, uri, headers, body).await
+}
+
+/// `batch_embed_handler` once the key may use the endpoint
+async fn embed_batch(
+    claims: auth::TokenClaims,
+    client_ip: ClientIp,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let started = Instant::now();
+    let request_id = uuid::Uuid::now_v7();
+
+    if claims.require_signing() {
+        verify_request_signature(&claims, &method, &uri, &headers, &body).await?;
+    }
+    if strict::requested(&headers) || claims.strict_validation() {
+        let known = strict::fields::<BatchEmbedRequest>().unwrap_or_default();
+        let unknown = strict::unknown_body_fields(&body, known);
+        if !unknown.is_empty() {
+            return Err(ApiError::UnknownFields(unknown));
+        }
+    }
+    let req: BatchEmbedRequest = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid JSON body: {}", e)))?;
+
+    let tier = claims
+        .tier()
+        .map_err(|_| ApiError::InternalError("Failed to decode tier".to_string()))?;
+    let settings = config::get_settings();
+    validate(&req.texts, settings.max_batch_size, tier.limits().max_chars)?;
+
+    // The requested model if this key may use it, else the org's default,
+    // as for `/v1/embed`
+    let org_models = inference::catalog::org_models(claims.org_id()).await;
+    let model_id = inference::catalog::resolve(
+        req.model.as_deref(),
+        &inference::catalog::available_ids(),
+        &org_models,
+        claims.models(),
+    )?;
+    let model_info = inference::catalog::available()
+        .into_iter()
+        .find(|info| info.id == model_id)
+        .ok_or_else(|| ApiError::InternalError("Resolved model is not served".to_string()))?;
+
+    // Exact token counts, checked before any quota is consumed
+    let max_tokens = inference::effective_max_tokens(claims.max_tokens(), model_info.max_tokens);
+    let model = inference::get_model();
+    let counts: Vec<usize> = {
+        let model = model.read();
+        req.texts
+            .iter()
+            .map(|text| model.tokenizer().encode(text, true).len())
+            .collect()
+    };
+    check_token_limits(&counts, max_tokens)?;
+    // Before the texts are logged or cached
+    residency::check(claims.org_id()).await?;
+
+    // One api_request_log row for the whole batch
+    let origin = RequestOrigin::new(client_ip, &headers);
+    let buffer = billing::get_usage_buffer();
+    buffer.record_request(NewRequest {
+        request_id,
+        organization_id: claims.org_id(),
+        api_key_id: claims.key_id(),
+        product: "embeddings".to_string(),
+        endpoint: "/v1/embed/batch".to_string(),
+        input_text: serde_json::to_string(&req.texts).unwrap_or_default(),
+        input_metadata: Some(serde_json::json!({
+            "normalize": req.normalize,
+            "batch_size": req.texts.len(),
+            "model": model_id
+        })),
+        client_ip: origin.client_ip,
+        user_agent: origin.user_agent.clone(),
+    });
+
+    let max_concurrency = claims.max_concurrency();
+    let _in_flight = billing::concurrency::limiter()
+        .try_acquire(claims.key_id(), max_concurrency)
+        .ok_or_else(|| {
+            monitoring::ERROR_COUNT
+                .with_label_values(&["too_many_concurrent_requests"])
+                .inc();
+            ApiError::TooManyConcurrentRequests(format!(
+                "Too many concurrent requests for this API key (max {})",
+                max_concurrency
+            ))
+        })?;
+
+    let rate_limit = billing::check_rate_limit_from_claims(&claims)
+        .await
+        .map_err(|_| ApiError::InternalError("Failed to check rate limit".to_string()))?;
+    if !rate_limit.allowed {
+        return Err(rate_limit_exceeded(&claims, rate_limit));
+    }
+
+    // Cache lookups, in input order
+    let cache = cache::get_cache();
+    let cache_policy = cache::policy::policy_for(claims.org_id()).await;
+    let mut items: Vec<Option<Item>> = Vec::with_capacity(req.texts.len());
+    for text in &req.texts {
+        let hit = cache
+            .get(text, claims.org_id(), cache_policy)
+            .await
+            .filter(|(cached, _)| cached.tokens <= max_tokens);
+        items.push(hit.map(|(cached, level)| {
+            monitoring::CACHE_HITS
+                .with_label_values(&["total", cache_policy.as_str()])
+                .inc();
+            Item {
+                embedding: cached.embedding,
+                tokens: cached.tokens,
+                cache_level: Some(level),
+            }
+        }));
+    }
+
+    // Inference for the misses, once per distinct text, in one batched run
+    let misses = distinct_misses(&req.texts, &items);
+    let mut inference_ms = None;
+    if !misses.is_empty() {
+        let dispatcher = inference::dispatcher::dispatcher();
+        let results = {
+            let _slot = dispatcher
+                .try_enter()
+                .map_err(|overloaded| shed(request_id, overloaded))?;
+            let texts: Vec<&str> = misses.iter().map(|(text, _)| *text).collect();
+            model
+                .write()
+                .encode_batch_with_limit(&texts, req.normalize, max_tokens)
+        };
+        let results = results.map_err(|_| {
+            monitoring::ERROR_COUNT
+                .with_label_values(&["inference_error"])
+                .inc();
+            ApiError::InternalError("Failed to generate embedding".to_string())
+        })?;
+
+        // One run for all the misses, timed once
+        if let Some((_, metadata)) = results.first() {
+            monitoring::INFERENCE_LATENCY.observe(metadata.inference_time_ms / 1000.0);
+            dispatcher.record_latency(metadata.inference_time_ms);
+            inference_ms = Some(metadata.inference_time_ms);
+        }
+        for ((text, indexes), (embedding, metadata)) in misses.iter().zip(results) {
+            monitoring::CACHE_MISSES
+                .with_label_values(&[cache_policy.as_str()])
+                .inc();
+            cache
+                .set(
+                    text,
+                    claims.org_id(),
+                    cache_policy,
+                    cache::CachedEmbedding {
+                        embedding: embedding.clone(),
+                        tokens: metadata.tokens,
+                        model: metadata.model.clone(),
+                    },
+                )
+                .await;
+            for &index in indexes {
+                items[index] = Some(Item {
+                    embedding: embedding.clone(),
+                    tokens: metadata.tokens,
+                    cache_level: None,
+                });
+            }
+        }
+    }
+    let items: Vec<Item> = items.into_iter().flatten().collect();
+
+    // One request of a free tier's monthly quota, like the rate limit
+    if tier == crate::models::TierType::Free {
+        billing::increment_free_tier_counter(claims.org_id(), claims.monthly_quota());
+    }
+
+    let total_tokens: usize = items.iter().map(|item| item.tokens).sum();
+    let cached = items
+        .iter()
+        .filter(|item| item.cache_level.is_some())
+        .count();
+    let cache_level = batch_cache_level(items.iter().map(|item| item.cache_level));
+    let model_name = model_info.versioned_id();
+
+    monitoring::TOKEN_COUNT.observe(total_tokens as f64);
+    monitoring::REQUEST_COUNT
+        .with_label_values(&["success", &(cached == items.len()).to_string()])
+        .inc();
+    let latency_ms = started.elapsed().as_millis() as f64;
+    monitoring::REQUEST_LATENCY.observe(latency_ms / 1000.0);
+
+    // One usage event billing the tokens of every text
+    buffer.record_response(
+        request_id,
+        claims.org_id(),
+        claims.key_id(),
+        "embeddings",
+        total_tokens as i32,
+        serde_json::json!({
+            "model": model_name,
+            "batch_size": items.len(),
+            "cached": cached,
+            "latency_ms": latency_ms,
+            "normalize": req.normalize
+        }),
+    );
+
+    let response = BatchEmbedResponse {
+        embeddings: items
+            .into_iter()
+            .zip(&req.texts)
+            .enumerate()
+            .map(|(index, (item, text))| BatchEmbedding {
+                index,
+                embedding: Embedding::new(item.embedding, None),
+                content_hash: cache.content_hash(text),
+                tokens: item.tokens,
+                cached: item.cache_level.is_some(),
+            })
+            .collect(),
+        model: model_name,
+        total_tokens,
+        cached,
+        latency_ms,
+    };
+    let mut headers = rate_limit_headers(&rate_limit);
+    insert_cache_headers(&mut headers, cache_level, inference_ms, total_tokens);
+    Ok((StatusCode::OK, headers, Json(response)).into_response())
+}
+
+/// Cache tier of the batch as a whole, for `X-Cache`: a miss if any text
+/// ran inference, else the slowest tier that served a text
+fn batch_cache_level(
+    levels: impl IntoIterator<Item = Option<cache::CacheLevel>>,
+) -> Option<cache::CacheLevel> {
+    let mut batch = cache::CacheLevel::L1;
+    for level in levels {
+        match level? {
+            cache::CacheLevel::L2 => batch = cache::CacheLevel::L2,
+            cache::CacheLevel::L1 => {}
+        }
+    }
+    Some(batch)
+}
+
+/// Reject an empty or oversized batch, or one with an empty or overlong
+/// text
+fn validate(texts: &[String], max_batch_size: usize, max_chars: usize) -> Result<(), ApiError> {
+    if texts.is_empty() {
+        return Err(ApiError::BadRequest("texts cannot be empty".to_string()));
+    }
+    if texts.len() > max_batch_size {
+        return Err(ApiError::BadRequest(format!(
+            "Batch of {} texts exceeds the maximum of {}",
+            texts.len(),
+            max_batch_size
+        )));
+    }
+    for (index, text) in texts.iter().enumerate() {
+        if text.trim().is_empty() {
+            return Err(ApiError::BadRequest(format!(
+                "texts[{}] cannot be empty or only whitespace",
+                index
+            )));
+        }
+        if text.chars().count() > max_chars {
+            return Err(ApiError::BadRequest(format!(
+                "texts[{}] exceeds {} characters",
+                index, max_chars
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Reject the batch if a text is over the key's token limit (batches don't
+/// truncate)
+fn check_token_limits(counts: &[usize], max_tokens: usize) -> Result<(), ApiError> {
+    match counts.iter().position(|&tokens| tokens > max_tokens) {
+        None => Ok(()),
+        Some(index) => {
+            monitoring::ERROR_COUNT
+                .with_label_values(&["text_too_long"])
+                .inc();
+            Err(ApiError::BadRequestWithTokens(
+                format!(
+                    "texts[{}] is {} tokens, max {}",
+                    index, counts[index], max_tokens
+                ),
+                max_tokens,
+                counts[index],
+            ))
+        }
+    }
+}
+
+/// Texts without an item yet, each once, with every index it appears at
+/// (in order of first appearance)
+fn distinct_misses<'a, T>(texts: &'a [String], items: &[Option<T>]) -> Vec<(&'a str, Vec<usize>)> {
+    let mut misses: Vec<(&str, Vec<usize>)> = vec![];
+    let mut positions: HashMap<&str, usize> = HashMap::new();
+    for (index, text) in texts.iter().enumerate() {
+        if items[index].is_some() {
+            continue;
+        }
+        match positions.get(text.as_str()) {
+            Some(&position) => misses[position].1.push(index),
+            None => {
+                positions.insert(text, misses.len());
+                misses.push((text, vec![index]));
+            }
+        }
+    }
+    misses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "control-plane")]
+    use crate::database;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::factory;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    #[cfg(feature = "control-plane")]
+    use axum::{body::Body, http::Request, routing::post, Router};
+    #[cfg(feature = "control-plane")]
+    use serde_json::{json, Value};
+    #[cfg(feature = "control-plane")]
+    use tower::ServiceExt;
+
+    fn texts(texts: &[&str]) -> Vec<String> {
+        texts.iter().map(|text| text.to_string()).collect()
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&texts(&["a", "b"]), 2, 2000).is_ok());
+
+        let message = |result: Result<(), ApiError>| match result {
+            Err(ApiError::BadRequest(message)) => message,
+            other => panic!("expected a bad request, got {:?}", other.is_ok()),
+        };
+        assert_eq!(message(validate(&[], 2, 2000)), "texts cannot be empty");
+        assert_eq!(
+            message(validate(&texts(&["a", "b", "c"]), 2, 2000)),
+            "Batch of 3 texts exceeds the maximum of 2"
+        );
+        assert_eq!(
+            message(validate(&texts(&["a", " "]), 2, 2000)),
+            "texts[1] cannot be empty or only whitespace"
+        );
+        assert_eq!(
+            message(validate(&texts(&["a", "too long"]), 2, 5)),
+            "texts[1] exceeds 5 characters"
+        );
+    }
+
+    #[test]
+    fn test_token_limits_name_the_text() {
+        assert!(check_token_limits(&[5, 128], 128).is_ok());
+        match check_token_limits(&[5, 129, 200], 128) {
+            Err(ApiError::BadRequestWithTokens(message, max, tokens)) => {
+                assert_eq!(message, "texts[1] is 129 tokens, max 128");
+                assert_eq!((max, tokens), (128, 129));
+            }
+            _ => panic!("expected the second text to be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_batch_cache_level() {
+        use cache::CacheLevel::{L1, L2};
+
+        assert_eq!(batch_cache_level([Some(L1), Some(L1)]), Some(L1));
+        assert_eq!(batch_cache_level([Some(L1), Some(L2)]), Some(L2));
+        assert_eq!(batch_cache_level([Some(L2), None, Some(L1)]), None);
+    }
+
+    #[test]
+    fn test_distinct_misses() {
+        let batch = texts(&["a", "b", "a", "c", "b"]);
+        // "c" was cached
+        let items = [None, None, None, Some(()), None];
+        assert_eq!(
+            distinct_misses(&batch, &items),
+            vec![("a", vec![0, 2]), ("b", vec![1, 4])]
+        );
+    }
+
+    /// POST a batch to `app`
+    #[cfg(feature = "control-plane")]
+    async fn send(app: Router, token: &str, body: Value) -> (StatusCode, HeaderMap, Value) {
+        let request = Request::post("/v1/embed/batch")
+            .header("content-type", "application/json")
+            .header("authorization", format!("Bearer {}", token))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let (status, headers) = (response.status(), response.headers().clone());
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, headers, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    /// POST a batch past the feature flag
+    #[cfg(feature = "control-plane")]
+    async fn post_batch(token: &str, body: Value) -> (StatusCode, HeaderMap, Value) {
+        let app = Router::new().route("/v1/embed/batch", post(embed_batch));
+        send(app, token, body).await
+    }
+
+    /// With `FEATURE_BATCH_API` at its default (off), batches are refused
+    /// before the body is read
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    async fn test_batch_api_flag_off() {
+        setup().await;
+        cleanup_db().await;
+        let owner = factory::user("embed-batch-flag@example.com").await;
+        let key = factory::api_key(&owner).await;
+        assert_eq!(Flag::BatchApi.mode(), flags::FlagMode::Off);
+
+        let app = Router::new().route("/v1/embed/batch", post(batch_embed_handler));
+        let (status, _, body) = send(app, &key.token, json!({ "texts": [] })).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "feature_not_enabled");
+        cleanup_db().await;
+    }
+
+    /// The model is resolved and authorized as for `/v1/embed`
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    async fn test_batch_model_is_authorized() {
+        setup().await;
+        cleanup_db().await;
+        let owner = factory::user("embed-batch-models@example.com").await;
+        let key = factory::api_key(&owner).await;
+
+        let (status, _, body) = post_batch(
+            &key.token,
+            json!({ "texts": ["hello"], "model": "no-such-model" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body["message"].as_str().unwrap().contains("no-such-model"));
+
+        let allowed = vec!["some-other-model".to_string()];
+        sqlx::query("UPDATE organizations SET allowed_models = $1 WHERE id = $2")
+            .bind(database::list(&allowed))
+            .bind(owner.org_id)
+            .execute(database::get_db())
+            .await
+            .unwrap();
+        inference::catalog::invalidate(owner.org_id);
+        let (status, _, body) = post_batch(&key.token, json!({ "texts": ["hello"] })).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "model_not_allowed");
+        cleanup_db().await;
+    }
+
+    /// Cached and uncached texts come back in input order, billed as one
+    /// usage event
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    async fn test_mixed_batch_in_input_order() {
+        setup().await;
+        cleanup_db().await;
+        let owner = factory::user("embed-batch@example.com").await;
+        let key = factory::api_key(&owner).await;
+        let suffix = uuid::Uuid::now_v7().simple().to_string();
+        let (first, second) = (format!("first {}", suffix), format!("second {}", suffix));
+
+        let (status, headers, warm) = post_batch(&key.token, json!({ "texts": [second] })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["X-Cache"], "miss");
+        assert!(headers.get("X-Inference-Ms").is_some());
+
+        let (status, headers, batch) =
+            post_batch(&key.token, json!({ "texts": [first, second] })).await;
+        assert_eq!(status, StatusCode::OK);
+        let embeddings = batch["embeddings"].as_array().unwrap();
+        assert_eq!(embeddings[0]["index"], 0);
+        assert_eq!(embeddings[0]["cached"], false);
+        assert_eq!(embeddings[1]["cached"], true);
+        assert_eq!(
+            embeddings[1]["embedding"],
+            warm["embeddings"][0]["embedding"]
+        );
+        assert_eq!(batch["cached"], 1);
+
+        // Same content hashes as `/v1/embed` would return
+        let cache = cache::get_cache();
+        assert_eq!(embeddings[0]["content_hash"], cache.content_hash(&first));
+        assert_eq!(embeddings[1]["content_hash"], cache.content_hash(&second));
+        assert_eq!(
+            embeddings[1]["content_hash"],
+            warm["embeddings"][0]["content_hash"]
+        );
+
+        // One miss makes the whole batch a miss
+        assert_eq!(headers["X-Cache"], "miss");
+        assert!(headers.get("X-Inference-Ms").is_some());
+        assert_eq!(
+            headers["X-Tokens"].to_str().unwrap(),
+            batch["total_tokens"].to_string()
+        );
+
+        let (status, headers, _) =
+            post_batch(&key.token, json!({ "texts": [first, second] })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_ne!(headers["X-Cache"], "miss");
+        assert!(headers.get("X-Inference-Ms").is_none());
+
+        billing::get_usage_buffer().flush().await.unwrap();
+        let tokens: Vec<i32> = sqlx::query_scalar(
+            "SELECT tokens FROM usage_events WHERE api_key_id = $1 ORDER BY timestamp",
+        )
+        .bind(key.key_id)
+        .fetch_all(database::get_db())
+        .await
+        .unwrap();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[1] as i64, batch["total_tokens"].as_i64().unwrap());
+        cleanup_db().await;
+    }
+}
@@ -0,0 +1,132 @@
+//! `POST /v1/embed` under injected dependency faults (see `fault`)
+//!
+//! Like the embed tests these need the test environment (`setup()`).
+
+use super::*;
+use crate::database;
+use crate::fault::{self, FaultSpec, Target};
+use crate::test_utils::factory;
+use crate::test_utils::helpers::{cleanup_db, setup};
+use axum::{body::Body, http::Request, routing::post, Router};
+use serde_json::json;
+use serial_test::serial;
+use std::time::{Duration, Instant};
+use tower::ServiceExt;
+
+fn app() -> Router {
+    Router::new().route("/v1/embed", post(create_embedding_handler))
+}
+
+async fn embed_with_key(key: &factory::TestKey, text: &str) -> (StatusCode, HeaderMap) {
+    let request = Request::post("/v1/embed")
+        .header("content-type", "application/json")
+        .header("authorization", format!("Bearer {}", key.token))
+        .body(Body::from(json!({ "text": text }).to_string()))
+        .unwrap();
+    let response = app().oneshot(request).await.unwrap();
+    (response.status(), response.headers().clone())
+}
+
+fn fault(latency_ms: u64, error: bool, duration_secs: u64) -> FaultSpec {
+    FaultSpec {
+        latency_ms,
+        error,
+        probability: 1.0,
+        duration_secs,
+    }
+}
+
+/// Redis +500ms for 30s: requests slow down by a bounded amount and every
+/// one of them is still counted by the rate limiter
+#[tokio::test]
+#[serial]
+async fn test_slow_redis() {
+    setup().await;
+    cleanup_db().await;
+    let owner = factory::user("chaos-redis@example.com").await;
+    let key = factory::api_key(&owner).await;
+
+    fault::inject(Target::Redis, fault(500, false, 30)).unwrap();
+    let requests = (0..20).map(|i| {
+        let key = &key;
+        async move {
+            let started = Instant::now();
+            let (status, headers) = embed_with_key(key, &format!("slow redis {}", i)).await;
+            (status, headers, started.elapsed())
+        }
+    });
+    let results = futures_util::future::join_all(requests).await;
+    fault::clear_all();
+
+    let mut latencies: Vec<Duration> = results.iter().map(|(_, _, elapsed)| *elapsed).collect();
+    latencies.sort();
+    let p99 = latencies[(latencies.len() * 99).div_ceil(100) - 1];
+    assert!(p99 < Duration::from_secs(5), "p99 {:?}", p99);
+
+    // Each request took its own slot: remaining counts down without gaps
+    let mut remaining: Vec<i64> = results
+        .iter()
+        .map(|(status, headers, _)| {
+            assert_eq!(*status, StatusCode::OK);
+            headers["X-RateLimit-Remaining"]
+                .to_str()
+                .unwrap()
+                .parse()
+                .unwrap()
+        })
+        .collect();
+    remaining.sort();
+    remaining.dedup();
+    assert_eq!(remaining.len(), results.len());
+    assert_eq!(remaining[remaining.len() - 1] - remaining[0], 19);
+    cleanup_db().await;
+}
+
+/// Redis failing: the rate limiter can't count, so requests are refused
+/// rather than served unmetered
+#[tokio::test]
+#[serial]
+async fn test_failing_redis_fails_closed() {
+    setup().await;
+    cleanup_db().await;
+    let owner = factory::user("chaos-redis-down@example.com").await;
+    let key = factory::api_key(&owner).await;
+    let _ = embed_with_key(&key, "warm the key cache").await;
+
+    fault::inject(Target::Redis, fault(0, true, 30)).unwrap();
+    let (status, _) = embed_with_key(&key, "redis down").await;
+    fault::clear_all();
+
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    cleanup_db().await;
+}
+
+/// Postgres down for 10s: usage stays buffered while flushes fail and is
+/// written by the first flush after it comes back
+#[tokio::test]
+#[serial]
+#[cfg_attr(feature = "sqlite", ignore = "usage isn't recorded on SQLite")]
+async fn test_postgres_outage_keeps_usage() {
+    setup().await;
+    cleanup_db().await;
+    let owner = factory::user("chaos-postgres@example.com").await;
+    let key = factory::api_key(&owner).await;
+    billing::get_usage_buffer().flush().await.unwrap();
+
+    let (status, _) = embed_with_key(&key, "postgres outage").await;
+    assert_eq!(status, StatusCode::OK);
+
+    fault::inject(Target::Postgres, fault(0, true, 10)).unwrap();
+    assert!(billing::get_usage_buffer().flush().await.is_err());
+    assert!(billing::get_usage_buffer().flush().await.is_err());
+    fault::clear(Target::Postgres);
+
+    billing::get_usage_buffer().flush().await.unwrap();
+    let events: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM usage_events WHERE api_key_id = $1")
+        .bind(key.key_id)
+        .fetch_one(database::get_db())
+        .await
+        .unwrap();
+    assert_eq!(events, 1);
+    cleanup_db().await;
+}
@@ -0,0 +1,333 @@
+//! Embedding drift between the served model and a candidate
+//!
+//! `POST /v1/admin/models/compare` embeds each text with the served model
+//! (the baseline) and with a candidate from CANDIDATE_MODELS_DIR. It reports
+//! the cosine similarity of each pair, plus their mean, 5th percentile and
+//! minimum. Run it on customer-representative texts before switching the
+//! default model to see how far stored vectors would move.
+//!
+//! The baseline may be served from the shared embedding cache. The candidate
+//! never reads or writes the cache. Both models take inference queue slots,
+//! so a comparison is shed like any request when the queue is full. Nothing
+//! is rate limited, billed or recorded as usage.
+//!
+//! Up to `STREAM_THRESHOLD` texts get a single JSON body. Larger lists are
+//! streamed as NDJSON: a line per text as soon as it's compared, then a
+//! line with the stats.
+
+use axum::{
+    body::Body,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use uuid::Uuid;
+
+use crate::auth::AdminTokenClaims;
+use crate::inference::{self, catalog, math, EmbeddingModel};
+use crate::models::CachePolicy;
+use crate::{cache, config};
+
+use super::error::ApiError;
+use super::strict::StrictJson;
+
+/// Most texts one comparison accepts
+pub const MAX_TEXTS: usize = 10_000;
+
+/// Longer lists are streamed as NDJSON
+pub const STREAM_THRESHOLD: usize = 100;
+
+/// Request body for `POST /v1/admin/models/compare`
+#[derive(Debug, Deserialize)]
+pub struct CompareRequest {
+    pub texts: Vec<String>,
+    /// Served model to compare against (defaults to the server default)
+    pub baseline: Option<String>,
+    /// Subdirectory of CANDIDATE_MODELS_DIR
+    pub candidate: String,
+}
+
+/// Similarity of one text's two embeddings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextSimilarity {
+    /// Position of the text in the request
+    pub index: usize,
+    pub similarity: f32,
+}
+
+/// Aggregate of the per-text similarities
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimilarityStats {
+    pub count: usize,
+    pub mean: f32,
+    /// 5th percentile (nearest rank)
+    pub p5: f32,
+    pub min: f32,
+}
+
+/// Response of `POST /v1/admin/models/compare`
+#[derive(Debug, Serialize)]
+pub struct CompareResponse {
+    pub baseline: String,
+    pub candidate: String,
+    pub results: Vec<TextSimilarity>,
+    pub stats: SimilarityStats,
+}
+
+/// Stats of a non-empty list of similarities
+pub fn stats(similarities: &[f32]) -> SimilarityStats {
+    let mut sorted = similarities.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let count = sorted.len();
+    let rank = (0.05 * count as f64).ceil() as usize;
+
+    SimilarityStats {
+        count,
+        mean: sorted.iter().sum::<f32>() / count.max(1) as f32,
+        p5: sorted
+            .get(rank.clamp(1, count.max(1)) - 1)
+            .copied()
+            .unwrap_or(0.0),
+        min: sorted.first().copied().unwrap_or(0.0),
+    }
+}
+
+/// Directory of the candidate `name` in `dir` (None for names that could
+/// leave it)
+fn candidate_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    valid.then(|| dir.join(name))
+}
+
+fn validate(req: &CompareRequest) -> Result<String, ApiError> {
+    let settings = config::get_settings();
+    if req.texts.is_empty() {
+        return Err(ApiError::BadRequest("texts cannot be empty".to_string()));
+    }
+    if req.texts.len() > MAX_TEXTS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} texts can be compared at once",
+            MAX_TEXTS
+        )));
+    }
+    if let Some(index) = req.texts.iter().position(|text| text.trim().is_empty()) {
+        return Err(ApiError::BadRequest(format!(
+            "texts[{}] cannot be empty",
+            index
+        )));
+    }
+    if let Some(index) = req
+        .texts
+        .iter()
+        .position(|text| text.chars().count() > settings.max_text_chars)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "texts[{}] exceeds {} characters",
+            index, settings.max_text_chars
+        )));
+    }
+
+    let served = catalog::available();
+    let baseline = match &req.baseline {
+        None => &served[0],
+        Some(name) => served.iter().find(|m| &m.id == name).ok_or_else(|| {
+            ApiError::BadRequest(
+                catalog::ModelError::Unknown {
+                    model: name.clone(),
+                    available: catalog::available_ids(),
+                }
+                .to_string(),
+            )
+        })?,
+    };
+    Ok(baseline.versioned_id())
+}
+
+/// Load the candidate model (blocking: reads the model files)
+async fn load_candidate(name: &str) -> Result<EmbeddingModel, ApiError> {
+    let settings = config::get_settings();
+    let path = candidate_path(Path::new(&settings.candidate_models_dir), name)
+        .ok_or_else(|| ApiError::BadRequest(format!("Invalid candidate model '{}'", name)))?;
+    if !path.is_dir() {
+        return Err(ApiError::NotFound(format!(
+            "No candidate model '{}' in CANDIDATE_MODELS_DIR",
+            name
+        )));
+    }
+
+    let name = name.to_string();
+    let deterministic = settings.deterministic_inference;
+    tokio::task::spawn_blocking(move || EmbeddingModel::load_from(&path, &name, deterministic))
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Candidate loader failed: {}", e)))?
+        .map_err(|e| ApiError::BadRequest(format!("Failed to load candidate model: {}", e)))
+}
+
+fn overloaded() -> ApiError {
+    ApiError::TooManyRequests("Inference queue is full, retry the comparison later".to_string())
+}
+
+/// Embedding of `text` by the served model, from the shared cache if there
+async fn baseline_embedding(text: &str) -> Result<Vec<f32>, ApiError> {
+    if let Some((cached, _)) = cache::get_cache()
+        .get(text, Uuid::nil(), CachePolicy::Shared)
+        .await
+    {
+        return Ok(cached.embedding);
+    }
+
+    let _slot = inference::dispatcher::dispatcher()
+        .try_enter()
+        .map_err(|_| overloaded())?;
+    let mut model = inference::get_model().write();
+    model
+        .encode(text, true)
+        .map(|(embedding, _)| embedding)
+        .map_err(|e| ApiError::InternalError(format!("Baseline inference failed: {}", e)))
+}
+
+fn candidate_embedding(candidate: &mut EmbeddingModel, text: &str) -> Result<Vec<f32>, ApiError> {
+    let _slot = inference::dispatcher::dispatcher()
+        .try_enter()
+        .map_err(|_| overloaded())?;
+    candidate
+        .encode(text, true)
+        .map(|(embedding, _)| embedding)
+        .map_err(|e| ApiError::InternalError(format!("Candidate inference failed: {}", e)))
+}
+
+async fn compare_text(candidate: &mut EmbeddingModel, text: &str) -> Result<f32, ApiError> {
+    let baseline = baseline_embedding(text).await?;
+    let candidate = candidate_embedding(candidate, text)?;
+    Ok(math::cosine(&baseline, &candidate))
+}
+
+/// Compare the served model with a candidate on a list of texts (admin
+/// token required)
+pub async fn admin_compare_models_handler(
+    _admin_token: AdminTokenClaims,
+    StrictJson(req): StrictJson<CompareRequest>,
+) -> Result<Response, ApiError> {
+    let baseline = validate(&req)?;
+    let mut candidate = load_candidate(&req.candidate).await?;
+
+    tracing::info!(
+        target: "audit",
+        action = "models.compare",
+        baseline = %baseline,
+        candidate = %req.candidate,
+        texts = req.texts.len(),
+        "Model comparison started"
+    );
+
+    if req.texts.len() > STREAM_THRESHOLD {
+        return Ok(stream(baseline, req, candidate));
+    }
+
+    let mut results = Vec::with_capacity(req.texts.len());
+    for (index, text) in req.texts.iter().enumerate() {
+        let similarity = compare_text(&mut candidate, text).await?;
+        results.push(TextSimilarity { index, similarity });
+    }
+    let similarities: Vec<f32> = results.iter().map(|r| r.similarity).collect();
+
+    let response = CompareResponse {
+        baseline,
+        candidate: req.candidate,
+        stats: stats(&similarities),
+        results,
+    };
+    Ok((StatusCode::OK, Json(response)).into_response())
+}
+
+/// NDJSON response comparing the texts as it's read. An error ends the
+/// stream with an `{"error": ..., "index": ...}` line.
+fn stream(baseline: String, req: CompareRequest, mut candidate: EmbeddingModel) -> Response {
+    let (tx, rx) = mpsc::channel::<Result<String, std::convert::Infallible>>(32);
+
+    tokio::spawn(async move {
+        let mut similarities = Vec::with_capacity(req.texts.len());
+        for (index, text) in req.texts.iter().enumerate() {
+            let line = match compare_text(&mut candidate, text).await {
+                Ok(similarity) => {
+                    similarities.push(similarity);
+                    serde_json::json!(TextSimilarity { index, similarity })
+                }
+                Err(e) => {
+                    let _ = tx
+                        .send(Ok(ndjson(serde_json::json!({
+                            "error": e.into_client_message(),
+                            "index": index,
+                        }))))
+                        .await;
+                    return;
+                }
+            };
+            if tx.send(Ok(ndjson(line))).await.is_err() {
+                // Client went away
+                return;
+            }
+        }
+
+        let summary = serde_json::json!({
+            "baseline": baseline,
+            "candidate": req.candidate,
+            "stats": stats(&similarities),
+        });
+        let _ = tx.send(Ok(ndjson(summary))).await;
+    });
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response()
+}
+
+fn ndjson(value: serde_json::Value) -> String {
+    format!("{}\n", value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let similarities: Vec<f32> = (1..=100).map(|i| i as f32 / 100.0).collect();
+        let stats = stats(&similarities);
+        assert_eq!(stats.count, 100);
+        assert!((stats.mean - 0.505).abs() < 1e-4);
+        assert_eq!(stats.p5, 0.05);
+        assert_eq!(stats.min, 0.01);
+
+        let single = super::stats(&[0.9]);
+        assert_eq!((single.p5, single.min, single.mean), (0.9, 0.9, 0.9));
+    }
+
+    #[test]
+    fn test_candidate_path_stays_in_dir() {
+        let dir = Path::new("/models");
+        assert_eq!(
+            candidate_path(dir, "all-MiniLM-L12-v2"),
+            Some(PathBuf::from("/models/all-MiniLM-L12-v2"))
+        );
+        assert_eq!(
+            candidate_path(dir, "bge-small-en-v1.5"),
+            Some(PathBuf::from("/models/bge-small-en-v1.5"))
+        );
+        assert_eq!(candidate_path(dir, ""), None);
+        assert_eq!(candidate_path(dir, ".."), None);
+        assert_eq!(candidate_path(dir, "../etc"), None);
+        assert_eq!(candidate_path(dir, "a/b"), None);
+    }
+}
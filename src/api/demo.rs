@@ -0,0 +1,369 @@
+//! Public "try it" embedding for the landing page
+//!
+//! `POST /v1/demo/embed` needs no API key, so it is kept cheap and hard to
+//! abuse: inputs are capped at 200 characters, every request goes through
+//! the shared embedding cache, and only the first 8 dimensions are returned.
+//! Requests are limited per client IP and by a daily cap shared by all
+//! instances, and `DEMO_ENABLED=false` turns the endpoint off. Demo requests
+//! are not usage events; `smally_demo_requests_total` counts them.
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::ip_limit::{ClientIp, IpRateLimiter};
+use super::{versions, ErrorResponse, InferenceError, IN_FLIGHT};
+use crate::cache;
+use crate::models::CachePolicy;
+use crate::{config, inference, monitoring, redis_util};
+
+/// Longest accepted input, in characters
+pub const DEMO_MAX_CHARS: usize = 200;
+
+/// Leading dimensions returned
+pub const DEMO_DIMENSIONS: usize = 8;
+
+static LIMITER: Lazy<IpRateLimiter> = Lazy::new(|| {
+    IpRateLimiter::new(
+        config::get_settings().demo_per_ip_per_minute,
+        Duration::from_secs(60),
+    )
+});
+
+/// Request to the demo endpoint
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct DemoEmbedRequest {
+    /// Text to embed (at most 200 characters)
+    #[schema(example = "how to reset password")]
+    pub text: String,
+}
+
+/// Truncated embedding returned by the demo endpoint
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DemoEmbedResponse {
+    /// First 8 dimensions of the embedding
+    #[schema(example = json!([0.023, -0.041, 0.087, 0.011, -0.064, 0.032, 0.005, -0.019]))]
+    pub embedding: Vec<f32>,
+    /// Full dimension of the embedding
+    #[schema(example = 384)]
+    pub dimensions: usize,
+    /// Tokens in the input
+    #[schema(example = 6)]
+    pub tokens: usize,
+}
+
+/// Why a demo request was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DemoError {
+    Disabled,
+    InvalidText(String),
+    /// Seconds until the client's window resets
+    RateLimited(u64),
+    DailyCapReached,
+    /// Suggested wait in milliseconds while the inference queue is full
+    Overloaded(u64),
+    Internal(String),
+}
+
+impl DemoError {
+    /// Label of `smally_demo_requests_total` and the `error` field
+    fn kind(&self) -> &'static str {
+        match self {
+            DemoError::Disabled => "demo_disabled",
+            DemoError::InvalidText(_) => "invalid_request",
+            DemoError::RateLimited(_) => "rate_limit_exceeded",
+            DemoError::DailyCapReached => "demo_daily_cap",
+            DemoError::Overloaded(_) => "overloaded",
+            DemoError::Internal(_) => "internal_error",
+        }
+    }
+}
+
+impl fmt::Display for DemoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DemoError::Disabled => write!(f, "The demo is currently unavailable"),
+            DemoError::InvalidText(msg) | DemoError::Internal(msg) => write!(f, "{}", msg),
+            DemoError::RateLimited(retry_after) => write!(
+                f,
+                "Too many demo requests; try again in {} seconds or sign up for an API key",
+                retry_after
+            ),
+            DemoError::DailyCapReached => write!(
+                f,
+                "The demo has reached its daily limit; sign up for an API key to keep going"
+            ),
+            DemoError::Overloaded(wait_ms) => {
+                write!(f, "The demo is busy; try again in about {} ms", wait_ms)
+            }
+        }
+    }
+}
+
+impl IntoResponse for DemoError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            DemoError::Disabled | DemoError::DailyCapReached | DemoError::Overloaded(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            DemoError::InvalidText(_) => StatusCode::BAD_REQUEST,
+            DemoError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            DemoError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let error = ErrorResponse {
+            estimated_wait_ms: match self {
+                DemoError::Overloaded(wait_ms) => Some(wait_ms),
+                _ => None,
+            },
+            ..ErrorResponse::new(self.kind(), self.to_string())
+        };
+        let body = Json(versions::error_body(versions::current(), &error));
+
+        match self {
+            DemoError::RateLimited(retry_after) => (
+                status,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                body,
+            )
+                .into_response(),
+            DemoError::Overloaded(wait_ms) => (
+                status,
+                [(
+                    header::RETRY_AFTER,
+                    inference::dispatcher::retry_after_secs(wait_ms).to_string(),
+                )],
+                body,
+            )
+                .into_response(),
+            _ => (status, body).into_response(),
+        }
+    }
+}
+
+/// Try the API without an API key
+///
+/// Embeds up to 200 characters and returns the first 8 dimensions. Limited
+/// per client IP and per day; use `/v1/embed` with an API key for real work.
+#[utoipa::path(
+    post,
+    path = "/v1/demo/embed",
+    tag = "embeddings",
+    request_body = DemoEmbedRequest,
+    responses(
+        (status = 200, description = "Truncated embedding", body = DemoEmbedResponse),
+        (status = 400, description = "Empty or too long text", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this IP", body = ErrorResponse),
+        (status = 503, description = "Demo disabled, daily limit reached or inference at capacity", body = ErrorResponse)
+    )
+)]
+pub async fn demo_embed_handler(
+    ClientIp(ip): ClientIp,
+    Json(req): Json<DemoEmbedRequest>,
+) -> Result<Json<DemoEmbedResponse>, DemoError> {
+    embed(ip, &req.text).await.map(Json)
+}
+
+/// Embed `text` for the demo (shared by the API endpoint and the landing
+/// page form)
+pub async fn embed(ip: Option<IpAddr>, text: &str) -> Result<DemoEmbedResponse, DemoError> {
+    embed_full(ip, text).await.map(|(response, _)| response)
+}
+
+/// `embed`, also keeping the whole vector for the playground's server-side
+/// similarity and projection (it never leaves the server)
+pub async fn embed_full(
+    ip: Option<IpAddr>,
+    text: &str,
+) -> Result<(DemoEmbedResponse, Vec<f32>), DemoError> {
+    let result = run(ip, text)
+        .await
+        .map(|(embedding, tokens)| (truncated(&embedding, tokens), embedding));
+    monitoring::DEMO_REQUESTS
+        .with_label_values(&[match &result {
+            Ok(_) => "ok",
+            Err(e) => e.kind(),
+        }])
+        .inc();
+    result
+}
+
+async fn run(ip: Option<IpAddr>, text: &str) -> Result<(Vec<f32>, usize), DemoError> {
+    let settings = config::get_settings();
+    if !settings.demo_enabled {
+        return Err(DemoError::Disabled);
+    }
+    validate_text(text)?;
+
+    // Clients without a known address share one bucket
+    LIMITER
+        .hit(
+            ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            Instant::now(),
+        )
+        .map_err(DemoError::RateLimited)?;
+    check_daily_cap(settings.demo_daily_cap).await?;
+
+    // Content-only cache entries, shared with `/v1/embed` requests of
+    // organizations on the shared policy
+    let cache = cache::get_cache();
+    let (org_id, policy) = (Uuid::nil(), CachePolicy::Shared);
+    if let Some((cached, _)) = cache.get(text, org_id, policy).await {
+        monitoring::CACHE_HITS
+            .with_label_values(&["total", policy.as_str()])
+            .inc();
+        return Ok((cached.embedding, cached.tokens));
+    }
+
+    let model = inference::get_model();
+    let (result, _) = IN_FLIGHT
+        .run(&cache.key(text, org_id, policy), || async {
+            let dispatcher = inference::dispatcher::dispatcher();
+            let result = {
+                let _slot = dispatcher.try_enter().map_err(InferenceError::Overloaded)?;
+                let mut model_lock = model.write();
+                let truncated = model_lock.count_tokens(text) > model_lock.max_tokens();
+                model_lock
+                    .encode(text, true)
+                    .map(|output| (output, truncated))
+            };
+            let ((embedding, metadata), truncated) =
+                result.map_err(|e| InferenceError::Failed(e.to_string()))?;
+            monitoring::INFERENCE_LATENCY.observe(metadata.inference_time_ms / 1000.0);
+            dispatcher.record_latency(metadata.inference_time_ms);
+
+            // Truncated results would be wrong for keys with a higher ceiling
+            if !truncated {
+                cache
+                    .set(
+                        text,
+                        org_id,
+                        policy,
+                        cache::CachedEmbedding {
+                            embedding: embedding.clone(),
+                            tokens: metadata.tokens,
+                            model: metadata.model.clone(),
+                        },
+                    )
+                    .await;
+            }
+            Ok((embedding, metadata))
+        })
+        .await;
+    monitoring::CACHE_MISSES
+        .with_label_values(&[policy.as_str()])
+        .inc();
+
+    let (embedding, metadata) = result.map_err(|e| match e {
+        InferenceError::Overloaded(overloaded) => {
+            DemoError::Overloaded(inference::dispatcher::jittered_wait_ms(
+                overloaded.estimated_wait_ms,
+                &mut rand::thread_rng(),
+            ))
+        }
+        InferenceError::Failed(e) => {
+            tracing::error!("Demo inference failed: {}", e);
+            DemoError::Internal("Failed to generate embedding".to_string())
+        }
+    })?;
+    Ok((embedding, metadata.tokens))
+}
+
+fn validate_text(text: &str) -> Result<(), DemoError> {
+    if text.trim().is_empty() {
+        return Err(DemoError::InvalidText(
+            "Text cannot be empty or only whitespace".to_string(),
+        ));
+    }
+    if text.chars().count() > DEMO_MAX_CHARS {
+        return Err(DemoError::InvalidText(format!(
+            "The demo accepts up to {} characters",
+            DEMO_MAX_CHARS
+        )));
+    }
+    Ok(())
+}
+
+fn truncated(embedding: &[f32], tokens: usize) -> DemoEmbedResponse {
+    DemoEmbedResponse {
+        dimensions: embedding.len(),
+        embedding: embedding.iter().take(DEMO_DIMENSIONS).copied().collect(),
+        tokens,
+    }
+}
+
+/// Count the request against today's cap (UTC days, all instances)
+async fn check_daily_cap(cap: i64) -> Result<(), DemoError> {
+    if cap == 0 {
+        return Ok(());
+    }
+
+    let key = format!("demo:requests:{}", Utc::now().date_naive());
+    let mut conn = redis_util::get_connection();
+    let (count,): (i64,) = redis::pipe()
+        .atomic()
+        .incr(&key, 1)
+        .expire(&key, 2 * 24 * 60 * 60)
+        .ignore()
+        .query_async(&mut conn)
+        .await
+        .map_err(|e| {
+            tracing::error!("Demo daily cap check failed: {}", e);
+            DemoError::Internal("Failed to check the demo limit".to_string())
+        })?;
+
+    if count > cap {
+        return Err(DemoError::DailyCapReached);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_validation() {
+        assert!(validate_text("how to reset password").is_ok());
+        assert!(validate_text(&"é".repeat(DEMO_MAX_CHARS)).is_ok());
+        assert!(matches!(
+            validate_text("   "),
+            Err(DemoError::InvalidText(_))
+        ));
+        assert!(matches!(
+            validate_text(&"a".repeat(DEMO_MAX_CHARS + 1)),
+            Err(DemoError::InvalidText(_))
+        ));
+    }
+
+    #[test]
+    fn test_only_leading_dimensions_are_returned() {
+        let response = truncated(&(0..384).map(|i| i as f32).collect::<Vec<_>>(), 6);
+        assert_eq!(
+            response.embedding,
+            (0..8).map(|i| i as f32).collect::<Vec<_>>()
+        );
+        assert_eq!(response.dimensions, 384);
+        assert_eq!(response.tokens, 6);
+    }
+
+    #[test]
+    fn test_rate_limited_response_has_retry_after() {
+        let response = DemoError::RateLimited(42).into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "42");
+        assert_eq!(
+            DemoError::DailyCapReached.into_response().status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+}
@@ -0,0 +1,488 @@
+//! `POST /v1/embed` error matrix: authentication, validation, rate limiting,
+//! cache headers and usage recording
+//!
+//! Failures decided before the key is validated run anywhere. The rest go
+//! through the real validator, cache, rate limiter and model, so they need
+//! the test environment (`setup()`: Postgres, Redis and the model).
+
+use super::ip_limit::ClientIp;
+use super::*;
+#[cfg(feature = "control-plane")]
+use crate::models::CreateAPIKeyRequest;
+use crate::models::TierType;
+#[cfg(feature = "control-plane")]
+use crate::test_utils::factory;
+#[cfg(feature = "control-plane")]
+use crate::test_utils::helpers::cleanup_db;
+use crate::test_utils::helpers::setup;
+#[cfg(feature = "control-plane")]
+use crate::{database, services};
+use axum::{body::Body, http::Request, routing::post, Router};
+use serde_json::{json, Value};
+use serial_test::serial;
+use tower::ServiceExt;
+
+fn app() -> Router {
+    Router::new().route("/v1/embed", post(create_embedding_handler))
+}
+
+/// POST `body` to `/v1/embed` with `authorization` as the header
+async fn embed(authorization: Option<&str>, body: Value) -> (StatusCode, HeaderMap, Value) {
+    let mut request = Request::post("/v1/embed").header("content-type", "application/json");
+    if let Some(authorization) = authorization {
+        request = request.header("authorization", authorization);
+    }
+    let response = app()
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+
+    let (status, headers) = (response.status(), response.headers().clone());
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, headers, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[cfg(feature = "control-plane")]
+async fn embed_with_key(key: &factory::TestKey, body: Value) -> (StatusCode, HeaderMap, Value) {
+    embed(Some(&format!("Bearer {}", key.token)), body).await
+}
+
+/// A Free key that was never issued
+fn unissued_key() -> auth::TokenData {
+    auth::TokenData {
+        org_id: uuid::Uuid::now_v7(),
+        key_id: uuid::Uuid::now_v7(),
+        tier: TierType::Free,
+        max_tokens: 128,
+        monthly_quota: 20_000,
+        require_signing: false,
+        max_concurrency: None,
+        scopes: None,
+        models: None,
+        api_version: None,
+        strict_validation: false,
+        generation: 0,
+    }
+}
+
+/// Claims of `unissued_key()`, for checks made before the key is looked at
+fn unissued_claims() -> auth::TokenClaims {
+    auth::TokenClaims::from_token_data(unissued_key())
+}
+
+async fn error_of(result: Result<Response, ApiError>) -> (StatusCode, Value) {
+    let response = match result {
+        Ok(response) => response,
+        Err(e) => e.into_response(),
+    };
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+async fn test_missing_or_malformed_authorization() {
+    for authorization in [None, Some("Basic c2tfYWJj"), Some("Bearer"), Some("")] {
+        let (status, _, body) = embed(authorization, json!({"text": "hello"})).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{:?}", authorization);
+        assert_eq!(body["error"], "invalid_api_key", "{:?}", authorization);
+    }
+}
+
+#[cfg(feature = "control-plane")]
+#[tokio::test]
+async fn test_service_account_token_rejected() {
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+    let token = auth::service_account::sign_service_account_token(
+        uuid::Uuid::now_v7(),
+        uuid::Uuid::now_v7(),
+        &signing_key,
+        &auth::binding::TokenBinding::from_settings(),
+    )
+    .unwrap();
+
+    let authorization = format!("Bearer {}{}", auth::SERVICE_ACCOUNT_TOKEN_PREFIX, token);
+    let (status, _, body) = embed(Some(&authorization), json!({"text": "hello"})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], "invalid_api_key");
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .contains("Service account tokens"));
+}
+
+#[tokio::test]
+async fn test_empty_text_and_invalid_body() {
+    for text in ["", "   ", "\n\t"] {
+        let body = Bytes::from(json!({ "text": text }).to_string());
+        let result = create_embedding_handler(
+            unissued_claims(),
+            ClientIp(None),
+            Method::POST,
+            Uri::from_static("/v1/embed"),
+            HeaderMap::new(),
+            body,
+        )
+        .await;
+        let (status, body) = error_of(result).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "invalid_request");
+        assert!(body["message"].as_str().unwrap().contains("empty"));
+    }
+
+    let result = create_embedding_handler(
+        unissued_claims(),
+        ClientIp(None),
+        Method::POST,
+        Uri::from_static("/v1/embed"),
+        HeaderMap::new(),
+        Bytes::from_static(b"{\"text\": "),
+    )
+    .await;
+    let (status, body) = error_of(result).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Invalid JSON body"));
+}
+
+#[cfg(feature = "control-plane")]
+#[tokio::test]
+#[serial]
+async fn test_revoked_key() {
+    setup().await;
+    cleanup_db().await;
+    let owner = factory::user("embed-revoked@example.com").await;
+    let key = factory::api_key(&owner).await;
+
+    services::api_keys::revoke(database::get_db(), owner.org_id, owner.id, key.id)
+        .await
+        .unwrap();
+
+    let (status, _, body) = embed_with_key(&key, json!({"text": "hello"})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], "invalid_api_key");
+    assert!(body["message"].as_str().unwrap().contains("revoked"));
+    cleanup_db().await;
+}
+
+/// Check the signature headers of a `POST /v1/embed` with `body`
+async fn verify_signed(
+    claims: &auth::TokenClaims,
+    timestamp: &str,
+    signature: &str,
+    body: &[u8],
+) -> Result<Response, ApiError> {
+    let mut headers = HeaderMap::new();
+    headers.insert(auth::signing::TIMESTAMP_HEADER, timestamp.parse().unwrap());
+    headers.insert(auth::signing::SIGNATURE_HEADER, signature.parse().unwrap());
+    let uri: Uri = "/v1/embed".parse().unwrap();
+    verify_request_signature(claims, &Method::POST, &uri, &headers, body).await?;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// A captured signed request can't be replayed by respelling its signature
+/// (case, `v1=` prefix, whitespace)
+#[tokio::test]
+#[serial]
+async fn test_signed_request_replay_with_respelled_signature() {
+    setup().await;
+    let claims = unissued_claims();
+    let secret =
+        auth::signing::derive_signing_secret(&config::get_settings().secret_key, claims.key_id());
+    let body = br#"{"text":"hello"}"#;
+    let timestamp = chrono::Utc::now().timestamp().to_string();
+    let signature =
+        auth::signing::compute_signature(&secret, &timestamp, "POST", "/v1/embed", body);
+
+    assert!(verify_signed(&claims, &timestamp, &signature, body)
+        .await
+        .is_ok());
+    for replay in [
+        signature.clone(),
+        signature.to_uppercase(),
+        format!("v1={}", signature),
+        format!("v1={}", signature.to_uppercase()),
+        format!(" {} ", signature),
+    ] {
+        let (status, error) =
+            error_of(verify_signed(&claims, &timestamp, &replay, body).await).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", replay);
+        assert_eq!(error["error"], "invalid_signature");
+        assert!(error["message"]
+            .as_str()
+            .unwrap()
+            .contains("already been used"));
+    }
+}
+
+/// Revoking from the dashboard stops a key that was just used (its "not
+/// revoked" status cached) within a second
+#[cfg(feature = "web")]
+#[tokio::test]
+#[serial]
+async fn test_web_revocation_applies_at_once() {
+    setup().await;
+    cleanup_db().await;
+    let owner = factory::user("embed-web-revoked@example.com").await;
+    let key = factory::api_key(&owner).await;
+
+    let (status, _, _) = embed_with_key(&key, json!({"text": "hello"})).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let web = Router::new().route(
+        "/organizations/:id/keys/:key_id/revoke",
+        post(crate::web::api_keys::revoke),
+    );
+    let request = Request::post(format!(
+        "/organizations/{}/keys/{}/revoke",
+        owner.org_id.simple(),
+        key.id.simple()
+    ))
+    .header(
+        "cookie",
+        format!(
+            "{}={}",
+            crate::auth::session::SESSION_COOKIE_NAME,
+            owner.session_token
+        ),
+    )
+    .body(Body::empty())
+    .unwrap();
+    let revoked_at = Instant::now();
+    let response = web.oneshot(request).await.unwrap();
+    assert!(response.status().is_redirection());
+
+    let (status, _, body) = embed_with_key(&key, json!({"text": "hello"})).await;
+    assert!(revoked_at.elapsed() < std::time::Duration::from_secs(1));
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(body["message"].as_str().unwrap().contains("revoked"));
+    cleanup_db().await;
+}
+
+/// A key whose row was purged is treated as revoked
+#[cfg(feature = "control-plane")]
+#[tokio::test]
+#[serial]
+async fn test_purged_key_is_revoked() {
+    setup().await;
+    cleanup_db().await;
+    let owner = factory::user("embed-purged@example.com").await;
+    let key = factory::api_key(&owner).await;
+    sqlx::query("DELETE FROM api_keys WHERE id = $1")
+        .bind(key.id)
+        .execute(database::get_db())
+        .await
+        .unwrap();
+
+    let (status, _, body) = embed_with_key(&key, json!({"text": "hello"})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(body["message"].as_str().unwrap().contains("revoked"));
+    cleanup_db().await;
+}
+
+/// API keys carry no expiry; a key stops working when the signing key that
+/// issued it is retired from TOKEN_PUBLIC_KEYS
+#[tokio::test]
+#[serial]
+async fn test_key_from_retired_signing_key() {
+    setup().await;
+    let retired = ed25519_dalek::SigningKey::from_bytes(&[9; 32]);
+    let token = auth::sign_token_direct(
+        &unissued_key(),
+        &retired,
+        &auth::binding::TokenBinding::from_settings(),
+    )
+    .unwrap();
+    let prefix = &config::get_settings().api_key_prefix;
+
+    let (status, _, body) = embed(
+        Some(&format!("Bearer {}{}", prefix, token)),
+        json!({"text": "hello"}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"], "invalid_api_key");
+}
+
+#[cfg(feature = "control-plane")]
+#[tokio::test]
+#[serial]
+async fn test_length_boundaries() {
+    setup().await;
+    cleanup_db().await;
+    let owner = factory::user("embed-length@example.com").await;
+    let key = factory::api_key_with(
+        &owner,
+        CreateAPIKeyRequest {
+            max_tokens: Some(10),
+            ..factory::key_request("Ten tokens")
+        },
+    )
+    .await;
+
+    // Characters: exactly the tier's limit is accepted, one more is not
+    let max_chars = TierType::Free.limits().max_chars;
+    let at_limit = "a".repeat(max_chars);
+    let (status, _, _) = embed_with_key(&key, json!({"text": at_limit, "truncate": true})).await;
+    assert_eq!(status, StatusCode::OK);
+    let over = "a".repeat(max_chars + 1);
+    let (status, _, body) = embed_with_key(&key, json!({"text": over, "truncate": true})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "invalid_request");
+
+    // Tokens: 8 words + [CLS] and [SEP] fill the key's 10 exactly
+    let (status, headers, body) = embed_with_key(&key, json!({"text": "hello ".repeat(8)})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["tokens"], 10);
+    assert_eq!(headers["X-Tokens"], "10");
+
+    let (status, _, body) = embed_with_key(&key, json!({"text": "hello ".repeat(9)})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"], "text_too_long");
+    assert_eq!(body["max_tokens"], 10);
+    assert_eq!(body["tokens"], 11);
+    cleanup_db().await;
+}
+
+#[cfg(feature = "control-plane")]
+#[tokio::test]
+#[serial]
+async fn test_exhausted_quota() {
+    setup().await;
+    cleanup_db().await;
+    let owner = factory::user("embed-quota@example.com").await;
+    let key = factory::api_key(&owner).await;
+    let quota = TierType::Free.limits().monthly_quota;
+
+    // The billing cycle's quota already used up
+    let cycle = crate::billing::cycle::BillingCycle::containing(chrono::Utc::now(), None);
+    let month_key = format!("ratelimit:{}:{}", key.org_id, cycle.key());
+    let mut conn = crate::redis_util::get_connection();
+    let _: () = redis::AsyncCommands::set_ex(&mut conn, &month_key, quota, 60)
+        .await
+        .unwrap();
+
+    let (status, headers, body) = embed_with_key(&key, json!({"text": "hello"})).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["error"], "rate_limit_exceeded");
+    assert!(body["reset_at"].is_string());
+    assert_eq!(headers["X-RateLimit-Limit"], quota.to_string());
+    assert_eq!(headers["X-RateLimit-Remaining"], "0");
+    assert!(headers.contains_key("X-RateLimit-Reset"));
+    assert_eq!(
+        headers["X-RateLimit-Cycle-Start"],
+        cycle.start.timestamp().to_string()
+    );
+
+    let _: () = redis::AsyncCommands::del(&mut conn, &month_key)
+        .await
+        .unwrap();
+    cleanup_db().await;
+}
+
+#[cfg(feature = "control-plane")]
+#[tokio::test]
+#[serial]
+#[cfg_attr(feature = "sqlite", ignore = "usage isn't recorded on SQLite")]
+async fn test_cache_flag_and_usage_records() {
+    setup().await;
+    cleanup_db().await;
+    let owner = factory::user("embed-cache@example.com").await;
+    let key = factory::api_key(&owner).await;
+    let text = format!("cache check {}", uuid::Uuid::now_v7().simple());
+
+    let (status, headers, miss) = embed_with_key(&key, json!({ "text": text })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(miss["cached"], false);
+    assert_eq!(headers["X-Cache"], "miss");
+    assert!(headers.contains_key("X-Inference-Ms"));
+
+    let (status, headers, hit) = embed_with_key(&key, json!({ "text": text })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(hit["cached"], true);
+    assert_ne!(headers["X-Cache"], "miss");
+    assert!(!headers.contains_key("X-Inference-Ms"));
+    assert_eq!(hit["tokens"], miss["tokens"]);
+    assert_eq!(hit["embedding"], miss["embedding"]);
+
+    // Both billed, with the response's token count
+    billing::get_usage_buffer().flush().await.unwrap();
+    let tokens: Vec<i32> = sqlx::query_scalar(
+        "SELECT tokens FROM usage_events WHERE api_key_id = $1 ORDER BY timestamp",
+    )
+    .bind(key.key_id)
+    .fetch_all(database::get_db())
+    .await
+    .unwrap();
+    let expected = miss["tokens"].as_i64().unwrap() as i32;
+    assert_eq!(tokens, vec![expected, expected]);
+    cleanup_db().await;
+}
+
+#[cfg(feature = "control-plane")]
+#[tokio::test]
+#[serial]
+async fn test_timings_only_with_the_flag() {
+    setup().await;
+    cleanup_db().await;
+    let owner = factory::user("embed-timings@example.com").await;
+    let key = factory::api_key(&owner).await;
+
+    let (status, _, body) = embed_with_key(&key, json!({"text": "no timings"})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("timings").is_none());
+
+    let (status, _, body) = embed_with_key(
+        &key,
+        json!({"text": "with timings", "include_timings": true}),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let timings = &body["timings"];
+    let stages: f64 = timings::Stage::ALL
+        .iter()
+        .map(|stage| timings[stage.field()].as_f64().unwrap())
+        .sum();
+    let total = timings["total_ms"].as_f64().unwrap();
+    assert!(timings["inference_ms"].as_f64().unwrap() > 0.0);
+    assert!(stages <= total);
+    assert!(total - stages < 1.0, "{}", timings);
+    cleanup_db().await;
+}
+
+#[cfg(feature = "control-plane")]
+#[tokio::test]
+#[serial]
+async fn test_strict_keys_reject_unknown_fields() {
+    setup().await;
+    cleanup_db().await;
+    let owner = factory::user("embed-strict@example.com").await;
+    let lenient = factory::api_key(&owner).await;
+    let strict = factory::api_key_with(
+        &owner,
+        CreateAPIKeyRequest {
+            strict_validation: true,
+            ..factory::key_request("Strict")
+        },
+    )
+    .await;
+    let body = json!({"text": "hello", "normalise": true, "model_name": "x"});
+
+    let (status, _, _) = embed_with_key(&lenient, body.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = embed_with_key(&strict, body).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["type"], "unknown_fields");
+    assert_eq!(
+        body["error"]["unknown_fields"],
+        json!(["model_name", "normalise"])
+    );
+    cleanup_db().await;
+}
@@ -0,0 +1,106 @@
+//! Error type of the management and admin endpoints (`{"error": "..."}`
+//! bodies, or the unified shape from API version `2025-02-15`; see
+//! `api::versions`). The embed API has its own `api::ApiError` with richer
+//! bodies.
+
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use super::versions;
+
+/// Error responses for the management API
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized(String),
+    NotFound(String),
+    /// A creation limit is reached (403 `limit_reached`)
+    LimitReached(String),
+    TooManyRequests(String),
+    InternalError(String),
+}
+
+impl ApiError {
+    pub fn message(&self) -> &str {
+        match self {
+            ApiError::BadRequest(msg)
+            | ApiError::Unauthorized(msg)
+            | ApiError::NotFound(msg)
+            | ApiError::LimitReached(msg)
+            | ApiError::TooManyRequests(msg)
+            | ApiError::InternalError(msg) => msg,
+        }
+    }
+
+    /// Message shown to the client (internal errors are logged and replaced
+    /// by a generic message, see `internal_error`)
+    pub fn into_client_message(self) -> String {
+        match self {
+            ApiError::InternalError(detail) => internal_error(&detail).1,
+            ApiError::BadRequest(msg)
+            | ApiError::Unauthorized(msg)
+            | ApiError::NotFound(msg)
+            | ApiError::LimitReached(msg)
+            | ApiError::TooManyRequests(msg) => msg,
+        }
+    }
+}
+
+/// Log the detail of an internal error, redacted, under a new request_id.
+/// Returns the id and the message clients get instead of the detail.
+pub fn internal_error(detail: &str) -> (uuid::Uuid, String) {
+    let request_id = uuid::Uuid::now_v7();
+    tracing::error!(
+        request_id = %request_id,
+        "Internal error: {}",
+        crate::redact::redact(detail)
+    );
+    (
+        request_id,
+        format!(
+            "Internal server error (request_id {}); quote it when contacting support",
+            request_id
+        ),
+    )
+}
+
+/// `x-request-id` header naming the log entry of an internal error
+pub(crate) fn request_id_header(request_id: uuid::Uuid) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(&request_id.to_string()) {
+        headers.insert("x-request-id", value);
+    }
+    headers
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut headers = HeaderMap::new();
+        let (status, kind, message) = match self {
+            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, "invalid_request", msg),
+            ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, "unauthorized", msg),
+            ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, "not_found", msg),
+            ApiError::LimitReached(msg) => (StatusCode::FORBIDDEN, "limit_reached", msg),
+            ApiError::TooManyRequests(msg) => {
+                (StatusCode::TOO_MANY_REQUESTS, "rate_limit_exceeded", msg)
+            }
+            // The detail is logged, not returned
+            ApiError::InternalError(detail) => {
+                let (request_id, message) = internal_error(&detail);
+                headers = request_id_header(request_id);
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error", message)
+            }
+        };
+
+        let body = Json(versions::message_error_body(
+            versions::current(),
+            kind,
+            message,
+        ));
+
+        (status, headers, body).into_response()
+    }
+}
@@ -0,0 +1,236 @@
+//! `POST /v1/estimate`: what embedding a workload will cost and how long
+//! it will take (see `billing::estimate`)
+//!
+//! Samples are tokenized with the served model's tokenizer; nothing is
+//! embedded. An estimate uses up one of the key's dry runs (`DRY_RUN_RPM`
+//! per minute) and no quota, and isn't billed.
+
+use axum::{
+    body::Bytes,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{strict, ApiError, ErrorResponse};
+use crate::billing::estimate::{self, Estimate, LengthBucket, Plan, TextProfile};
+use crate::models::TierType;
+use crate::{auth, billing, config, inference};
+
+/// Most sample texts one estimate tokenizes
+pub const MAX_SAMPLES: usize = 100;
+
+/// Most buckets of a length histogram
+pub const MAX_BUCKETS: usize = 100;
+
+/// Largest volume estimated
+pub const MAX_VOLUME: u64 = 1_000_000_000_000;
+
+/// Request to estimate a workload. Give `samples`, `lengths` or both: with
+/// both, the samples only set the characters per token of the histogram.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct EstimateRequest {
+    /// Representative texts, tokenized exactly (up to 100, each within
+    /// the tier's character limit)
+    #[serde(default)]
+    #[schema(example = json!(["Red cotton t-shirt, slim fit, machine washable"]))]
+    pub samples: Vec<String>,
+    /// Length distribution of the texts (up to 100 buckets)
+    #[serde(default)]
+    pub lengths: Vec<LengthBucket>,
+    /// Number of texts to embed
+    #[schema(example = 10_000_000)]
+    pub volume: u64,
+}
+
+/// Estimate the tokens, cost and time of embedding a workload
+///
+/// Extrapolates token counts from sample texts or a length histogram to
+/// the volume, prices them at the caller's tier and paces them at its rate
+/// limit, one text per request and in batches. No inference runs; the
+/// estimate counts as a dry run (DRY_RUN_RPM per minute per key) and
+/// consumes no quota.
+#[utoipa::path(
+    post,
+    path = "/v1/estimate",
+    tag = "embeddings",
+    request_body = EstimateRequest,
+    responses(
+        (status = 200, description = "Estimate", body = Estimate),
+        (status = 400, description = "Invalid request", body = ErrorResponse),
+        (status = 401, description = "Unauthorized - invalid or missing API key", body = ErrorResponse),
+        (status = 429, description = "Too many estimates per minute", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn estimate_handler(
+    claims: auth::TokenClaims,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    if strict::requested(&headers) || claims.strict_validation() {
+        let known = strict::fields::<EstimateRequest>().unwrap_or_default();
+        let unknown = strict::unknown_body_fields(&body, known);
+        if !unknown.is_empty() {
+            return Err(ApiError::UnknownFields(unknown));
+        }
+    }
+    let req: EstimateRequest = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid JSON body: {}", e)))?;
+
+    let tier = claims
+        .tier()
+        .map_err(|_| ApiError::InternalError("Failed to decode tier".to_string()))?;
+    let limits = tier.limits();
+    validate(&req, limits.max_chars)?;
+
+    let dry_run_limit = billing::check_dry_run_limit(&claims)
+        .await
+        .map_err(|_| ApiError::InternalError("Failed to check rate limit".to_string()))?;
+    if !dry_run_limit.allowed {
+        return Err(ApiError::RateLimitExceeded(
+            "Too many dry runs and estimates per minute".to_string(),
+            dry_run_limit,
+        ));
+    }
+
+    let settings = config::get_settings();
+    let max_tokens = inference::effective_max_tokens(claims.max_tokens(), settings.max_tokens);
+    let samples: Vec<(usize, usize)> = {
+        let model = inference::get_model();
+        let model = model.read();
+        req.samples
+            .iter()
+            .map(|text| {
+                let tokens = model.tokenizer().encode(text, true).len();
+                (text.chars().count(), tokens)
+            })
+            .collect()
+    };
+    let profile = profile(&samples, &req.lengths, max_tokens)
+        .ok_or_else(|| ApiError::BadRequest("lengths need a positive share".to_string()))?;
+
+    let plan = Plan {
+        tier,
+        limits,
+        max_tokens,
+        // Trials are billed at the base tier
+        price_per_million_tokens: claims.signed_tier().limits().price_per_million_tokens,
+        monthly_quota: (tier == TierType::Free).then(|| i64::from(claims.monthly_quota())),
+    };
+    let estimate = estimate::estimate(&plan, profile, req.volume, settings.max_batch_size);
+    Ok(Json(estimate).into_response())
+}
+
+fn validate(req: &EstimateRequest, max_chars: usize) -> Result<(), ApiError> {
+    if !(1..=MAX_VOLUME).contains(&req.volume) {
+        return Err(ApiError::BadRequest(format!(
+            "volume must be between 1 and {}",
+            MAX_VOLUME
+        )));
+    }
+    if req.samples.is_empty() && req.lengths.is_empty() {
+        return Err(ApiError::BadRequest(
+            "Give samples, lengths or both".to_string(),
+        ));
+    }
+    if req.samples.len() > MAX_SAMPLES {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} samples",
+            MAX_SAMPLES
+        )));
+    }
+    if let Some(index) = req
+        .samples
+        .iter()
+        .position(|text| text.chars().count() > max_chars)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "samples[{}] exceeds {} characters",
+            index, max_chars
+        )));
+    }
+    if req.lengths.len() > MAX_BUCKETS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} length buckets",
+            MAX_BUCKETS
+        )));
+    }
+    if req
+        .lengths
+        .iter()
+        .any(|bucket| !bucket.share.is_finite() || bucket.share < 0.0)
+    {
+        return Err(ApiError::BadRequest(
+            "Length shares must be 0 or more".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Profile of the histogram if there is one (at the samples' characters
+/// per token), else of the samples
+fn profile(
+    samples: &[(usize, usize)],
+    lengths: &[LengthBucket],
+    max_tokens: usize,
+) -> Option<TextProfile> {
+    if lengths.is_empty() {
+        let counts: Vec<usize> = samples.iter().map(|(_, tokens)| *tokens).collect();
+        return TextProfile::from_token_counts(&counts, max_tokens);
+    }
+    let chars_per_token =
+        estimate::chars_per_token(samples).unwrap_or(estimate::DEFAULT_CHARS_PER_TOKEN);
+    TextProfile::from_lengths(lengths, chars_per_token, max_tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(samples: usize, lengths: Vec<LengthBucket>, volume: u64) -> EstimateRequest {
+        EstimateRequest {
+            samples: vec!["a short text".to_string(); samples],
+            lengths,
+            volume,
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate(&request(1, vec![], 1), 2000).is_ok());
+        assert!(validate(&request(0, vec![], 1), 2000).is_err());
+        assert!(validate(&request(1, vec![], 0), 2000).is_err());
+        assert!(validate(&request(MAX_SAMPLES + 1, vec![], 1), 2000).is_err());
+        assert!(validate(&request(1, vec![], 1), 5).is_err());
+
+        let negative = LengthBucket {
+            chars: 300,
+            share: -1.0,
+        };
+        assert!(validate(&request(0, vec![negative], 1), 2000).is_err());
+    }
+
+    #[test]
+    fn test_samples_calibrate_the_histogram() {
+        // 8 characters per content token in the samples
+        let samples = [(80, 12)];
+        let lengths = [LengthBucket {
+            chars: 400,
+            share: 1.0,
+        }];
+
+        let calibrated = profile(&samples, &lengths, 128).unwrap();
+        assert_eq!(calibrated.mean_tokens, 52.0);
+        let default = profile(&[], &lengths, 128).unwrap();
+        assert_eq!(default.mean_tokens, 102.0);
+
+        // Without a histogram the samples are the workload
+        let sampled = profile(&samples, &[], 128).unwrap();
+        assert_eq!(sampled.mean_tokens, 12.0);
+    }
+}
@@ -0,0 +1,605 @@
+use anyhow::Result;
+use axum::{
+    extract::{Path, Query},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::auth::session::SessionClaims;
+use crate::config;
+use crate::database::{self, DbPool, UsageEventsRepo};
+use crate::models::{APIKey, Organization, OrganizationRole};
+use crate::services;
+use crate::uuid_dashless::DashlessUuid;
+
+use super::error::ApiError;
+
+/// How long a signed download URL stays valid (1 hour)
+const DOWNLOAD_URL_TTL_SECS: i64 = 3600;
+
+/// Minimum time between two exports of the same organization (1 day)
+const EXPORT_COOLDOWN_HOURS: i64 = 24;
+
+/// How long an export may stay pending before it's taken as abandoned (the
+/// process running it stopped) and no longer holds the cooldown
+const EXPORT_STALE_MINUTES: i64 = 30;
+
+/// Export job row (archive bytes are only loaded on download)
+#[derive(Debug, sqlx::FromRow)]
+struct ExportJob {
+    id: Uuid,
+    status: String,
+    error: Option<String>,
+    created_at: DateTime<Utc>,
+    completed_at: Option<DateTime<Utc>>,
+}
+
+/// Member entry in the export
+#[derive(Debug, Serialize, sqlx::FromRow)]
+struct ExportMember {
+    user_id: Uuid,
+    email: String,
+    name: Option<String>,
+    role: OrganizationRole,
+    created_at: DateTime<Utc>,
+}
+
+/// Query parameters of a signed download URL
+#[derive(Debug, Deserialize)]
+pub struct DownloadParams {
+    pub expires: i64,
+    pub signature: String,
+}
+
+/// Request a full data export of an organization (owner only)
+///
+/// The archive is assembled in the background; poll
+/// `GET /v1/organizations/:org_id/export/:job_id` for a download URL.
+pub async fn request_export_handler(
+    claims: SessionClaims,
+    Path(org_id): Path<DashlessUuid>,
+) -> Result<Response, ApiError> {
+    let pool = database::get_db();
+    let user_id: Uuid = claims
+        .sub
+        .parse()
+        .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?;
+    let org_id = org_id.into_inner();
+
+    require_owner(pool, org_id, user_id).await?;
+
+    let Some(job_id) = claim_export(pool, org_id, user_id, Utc::now()).await? else {
+        return Err(ApiError::TooManyRequests(
+            "An export was already requested for this organization in the last 24 hours"
+                .to_string(),
+        ));
+    };
+
+    tracing::info!(
+        target: "audit",
+        action = "organization.export",
+        org_id = %org_id,
+        user_id = %user_id,
+        job_id = %job_id,
+        "Organization data export requested"
+    );
+    services::activity::record(
+        pool,
+        org_id,
+        user_id,
+        "organization.export",
+        Some(&job_id.to_string()),
+    )
+    .await;
+
+    tokio::spawn(run_export(job_id, org_id));
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({
+            "id": job_id.simple().to_string(),
+            "status": "pending",
+        })),
+    )
+        .into_response())
+}
+
+/// Get the status of an export; includes a signed download URL once ready
+pub async fn get_export_handler(
+    claims: SessionClaims,
+    Path((org_id, job_id)): Path<(DashlessUuid, DashlessUuid)>,
+) -> Result<Response, ApiError> {
+    let pool = database::get_db();
+    let user_id: Uuid = claims
+        .sub
+        .parse()
+        .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?;
+    let org_id = org_id.into_inner();
+    let job_id = job_id.into_inner();
+
+    require_owner(pool, org_id, user_id).await?;
+
+    let job = sqlx::query_as::<_, ExportJob>(
+        "SELECT id, status, error, created_at, completed_at
+         FROM organization_exports
+         WHERE id = $1 AND organization_id = $2",
+    )
+    .bind(job_id)
+    .bind(org_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
+    .ok_or_else(|| ApiError::NotFound("Export not found".to_string()))?;
+
+    let (download_url, expires_at) = if job.status == "ready" {
+        let expires = Utc::now().timestamp() + DOWNLOAD_URL_TTL_SECS;
+        let signature = download_signature(&config::get_settings().secret_key, job.id, expires);
+        (
+            Some(format!(
+                "/v1/exports/{}/download?expires={}&signature={}",
+                job.id.simple(),
+                expires,
+                signature
+            )),
+            Some(expires),
+        )
+    } else {
+        (None, None)
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "id": job.id.simple().to_string(),
+            "status": job.status,
+            "error": job.error,
+            "created_at": job.created_at,
+            "completed_at": job.completed_at,
+            "download_url": download_url,
+            "expires_at": expires_at,
+        })),
+    )
+        .into_response())
+}
+
+/// Download an export archive via a signed, time-limited URL (no session needed)
+pub async fn download_export_handler(
+    Path(job_id): Path<DashlessUuid>,
+    Query(params): Query<DownloadParams>,
+) -> Result<Response, ApiError> {
+    let job_id = job_id.into_inner();
+
+    if !verify_download_signature(
+        &config::get_settings().secret_key,
+        job_id,
+        params.expires,
+        &params.signature,
+        Utc::now().timestamp(),
+    ) {
+        return Err(ApiError::Unauthorized(
+            "Download link is invalid or has expired".to_string(),
+        ));
+    }
+
+    let archive = sqlx::query_scalar::<_, Option<Vec<u8>>>(
+        "SELECT archive FROM organization_exports WHERE id = $1 AND status = 'ready'",
+    )
+    .bind(job_id)
+    .fetch_optional(database::get_db())
+    .await
+    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
+    .flatten()
+    .ok_or_else(|| ApiError::NotFound("Export not found".to_string()))?;
+
+    let filename = format!("smally-export-{}.tar.gz", job_id.simple());
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "application/gzip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        archive,
+    )
+        .into_response())
+}
+
+/// Ensure the user is an owner of the organization
+async fn require_owner(pool: &DbPool, org_id: Uuid, user_id: Uuid) -> Result<(), ApiError> {
+    let role = sqlx::query_scalar::<_, OrganizationRole>(
+        "SELECT role FROM organization_members WHERE organization_id = $1 AND user_id = $2",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
+    .ok_or_else(|| {
+        ApiError::Unauthorized("You are not a member of this organization".to_string())
+    })?;
+
+    if role != OrganizationRole::Owner {
+        return Err(ApiError::Unauthorized(
+            "Only owners can export organization data".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Start an export job of the organization, unless another one holds the
+/// cooldown (None). Failed exports don't count, nor do exports pending for
+/// longer than `EXPORT_STALE_MINUTES`, which are marked failed here.
+async fn claim_export(
+    pool: &DbPool,
+    org_id: Uuid,
+    user_id: Uuid,
+    now: DateTime<Utc>,
+) -> Result<Option<Uuid>, ApiError> {
+    let db_error = |e: sqlx::Error| ApiError::InternalError(format!("Database error: {}", e));
+
+    sqlx::query(
+        "UPDATE organization_exports
+         SET status = 'failed', error = 'The export was interrupted', completed_at = $2
+         WHERE organization_id = $1 AND status = 'pending' AND created_at <= $3",
+    )
+    .bind(org_id)
+    .bind(database::timestamp(now))
+    .bind(database::timestamp(
+        now - chrono::Duration::minutes(EXPORT_STALE_MINUTES),
+    ))
+    .execute(pool)
+    .await
+    .map_err(db_error)?;
+    release_failed_claim(pool, org_id).await.map_err(db_error)?;
+
+    // Claim and job row together, so a claim never outlives a failed insert.
+    // The claim is one conditional UPDATE: of concurrent requests, the
+    // others wait on the row and then find it taken.
+    let mut tx = pool.begin().await.map_err(db_error)?;
+    let claimed = sqlx::query(
+        "UPDATE organizations SET export_claimed_at = $2
+         WHERE id = $1 AND (export_claimed_at IS NULL OR export_claimed_at <= $3)",
+    )
+    .bind(org_id)
+    .bind(database::timestamp(now))
+    .bind(database::timestamp(
+        now - chrono::Duration::hours(EXPORT_COOLDOWN_HOURS),
+    ))
+    .execute(&mut *tx)
+    .await
+    .map_err(db_error)?;
+    if claimed.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    let job_id = Uuid::now_v7();
+    sqlx::query(
+        "INSERT INTO organization_exports (id, organization_id, requested_by, status, created_at)
+         VALUES ($1, $2, $3, 'pending', $4)",
+    )
+    .bind(job_id)
+    .bind(org_id)
+    .bind(user_id)
+    .bind(database::timestamp(now))
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| ApiError::InternalError(format!("Failed to create export: {}", e)))?;
+    tx.commit().await.map_err(db_error)?;
+
+    Ok(Some(job_id))
+}
+
+/// Free the cooldown of an organization whose claiming export failed (the
+/// claim and the job share their timestamp)
+async fn release_failed_claim(pool: &DbPool, org_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE organizations SET export_claimed_at = NULL
+         WHERE id = $1 AND export_claimed_at IN (
+             SELECT created_at FROM organization_exports
+             WHERE organization_id = $1 AND status = 'failed'
+         )",
+    )
+    .bind(org_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Background task: assemble the archive and store it on the job row
+async fn run_export(job_id: Uuid, org_id: Uuid) {
+    let pool = database::get_db();
+
+    let result = match collect_export_files(pool, org_id).await {
+        Ok(files) => build_archive(&files),
+        Err(e) => Err(e),
+    };
+
+    let update = match result {
+        Ok(archive) => sqlx::query(
+            "UPDATE organization_exports
+             SET status = 'ready', archive = $2, completed_at = $3
+             WHERE id = $1",
+        )
+        .bind(job_id)
+        .bind(archive)
+        .bind(database::timestamp(Utc::now())),
+        Err(e) => {
+            tracing::error!("Export {} failed: {}", job_id, e);
+            sqlx::query(
+                "UPDATE organization_exports
+                 SET status = 'failed', error = $2, completed_at = $3
+                 WHERE id = $1",
+            )
+            .bind(job_id)
+            .bind(e.to_string())
+            .bind(database::timestamp(Utc::now()))
+        }
+    };
+
+    if let Err(e) = update.execute(pool).await {
+        tracing::error!("Failed to update export {}: {}", job_id, e);
+    }
+    if let Err(e) = release_failed_claim(pool, org_id).await {
+        tracing::error!("Failed to release the export cooldown of {}: {}", org_id, e);
+    }
+}
+
+/// Gather all exportable data of an organization as (file name, JSON bytes)
+async fn collect_export_files(pool: &DbPool, org_id: Uuid) -> Result<Vec<(String, Vec<u8>)>> {
+    let organization =
+        sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE id = $1")
+            .bind(org_id)
+            .fetch_one(pool)
+            .await?;
+
+    let members = sqlx::query_as::<_, ExportMember>(
+        "SELECT u.id AS user_id, u.email, u.name, om.role, om.created_at
+         FROM organization_members om
+         INNER JOIN users u ON u.id = om.user_id
+         WHERE om.organization_id = $1
+         ORDER BY om.created_at",
+    )
+    .bind(org_id)
+    .fetch_all(pool)
+    .await?;
+
+    // Key metadata only: tokens are never stored, so they can't leak here
+    let api_keys = sqlx::query_as::<_, APIKey>(
+        "SELECT * FROM api_keys WHERE organization_id = $1 ORDER BY created_at",
+    )
+    .bind(org_id)
+    .fetch_all(pool)
+    .await?;
+
+    let usage_daily = UsageEventsRepo::new(pool).daily_by_product(org_id).await?;
+
+    let manifest = json!({
+        "organization_id": org_id,
+        "exported_at": Utc::now(),
+        "files": ["organization.json", "members.json", "api_keys.json", "usage_daily.json"],
+    });
+
+    Ok(vec![
+        (
+            "manifest.json".to_string(),
+            serde_json::to_vec_pretty(&manifest)?,
+        ),
+        (
+            "organization.json".to_string(),
+            serde_json::to_vec_pretty(&organization)?,
+        ),
+        (
+            "members.json".to_string(),
+            serde_json::to_vec_pretty(&members)?,
+        ),
+        (
+            "api_keys.json".to_string(),
+            serde_json::to_vec_pretty(&api_keys)?,
+        ),
+        (
+            "usage_daily.json".to_string(),
+            serde_json::to_vec_pretty(&usage_daily)?,
+        ),
+    ])
+}
+
+/// Pack files into a gzipped tarball
+fn build_archive(files: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    let mtime = Utc::now().timestamp() as u64;
+
+    for (name, data) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data.as_slice())?;
+    }
+
+    Ok(builder.into_inner()?.finish()?)
+}
+
+/// HMAC-SHA256 signature for a download URL (hex)
+fn download_signature(secret: &str, job_id: Uuid, expires: i64) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("export:{}:{}", job_id.simple(), expires).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Check a download URL signature and expiry (constant-time comparison)
+fn verify_download_signature(
+    secret: &str,
+    job_id: Uuid,
+    expires: i64,
+    signature: &str,
+    now: i64,
+) -> bool {
+    if now > expires {
+        return false;
+    }
+    let Ok(provided) = hex::decode(signature) else {
+        return false;
+    };
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("export:{}:{}", job_id.simple(), expires).as_bytes());
+    mac.verify_slice(&provided).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::factory;
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use serial_test::serial;
+    use std::io::Read;
+
+    #[test]
+    fn test_download_signature_roundtrip() {
+        let job_id = Uuid::now_v7();
+        let sig = download_signature("secret", job_id, 1_000);
+
+        assert!(verify_download_signature(
+            "secret", job_id, 1_000, &sig, 999
+        ));
+        assert!(verify_download_signature(
+            "secret", job_id, 1_000, &sig, 1_000
+        ));
+    }
+
+    #[test]
+    fn test_download_signature_rejects_expired_and_tampered() {
+        let job_id = Uuid::now_v7();
+        let sig = download_signature("secret", job_id, 1_000);
+
+        // Expired
+        assert!(!verify_download_signature(
+            "secret", job_id, 1_000, &sig, 1_001
+        ));
+        // Extended expiry
+        assert!(!verify_download_signature(
+            "secret", job_id, 9_999, &sig, 999
+        ));
+        // Other job
+        assert!(!verify_download_signature(
+            "secret",
+            Uuid::now_v7(),
+            1_000,
+            &sig,
+            999
+        ));
+        // Other server secret / garbage
+        assert!(!verify_download_signature(
+            "other", job_id, 1_000, &sig, 999
+        ));
+        assert!(!verify_download_signature(
+            "secret", job_id, 1_000, "xyz", 999
+        ));
+    }
+
+    #[test]
+    fn test_build_archive_contains_files() {
+        let files = vec![
+            (
+                "organization.json".to_string(),
+                b"{\"name\":\"Acme\"}".to_vec(),
+            ),
+            ("members.json".to_string(), b"[]".to_vec()),
+        ];
+
+        let archive = build_archive(&files).unwrap();
+
+        let decoder = flate2::read::GzDecoder::new(archive.as_slice());
+        let mut tarball = tar::Archive::new(decoder);
+        let mut entries = Vec::new();
+        for entry in tarball.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let name = entry.path().unwrap().to_string_lossy().to_string();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            entries.push((name, content));
+        }
+
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    "organization.json".to_string(),
+                    "{\"name\":\"Acme\"}".to_string()
+                ),
+                ("members.json".to_string(), "[]".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_export_cooldown_claims() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("export-claim@example.com").await;
+        let claim = |at: DateTime<Utc>| claim_export(pool, owner.org_id, owner.id, at);
+        let set_status = |job_id: Uuid, status: &'static str| {
+            sqlx::query("UPDATE organization_exports SET status = $1 WHERE id = $2")
+                .bind(status)
+                .bind(job_id)
+                .execute(pool)
+        };
+        let now = Utc::now();
+
+        // Of concurrent requests only one gets through
+        let (a, b) = tokio::join!(claim(now), claim(now));
+        let claimed: Vec<Uuid> = [a.unwrap(), b.unwrap()].into_iter().flatten().collect();
+        assert_eq!(claimed.len(), 1);
+
+        // A failed export frees the slot
+        set_status(claimed[0], "failed").await.unwrap();
+        let job_id = claim(now).await.unwrap().unwrap();
+
+        // So does an abandoned one, which is marked failed
+        assert!(claim(now + chrono::Duration::minutes(10))
+            .await
+            .unwrap()
+            .is_none());
+        let later = now + chrono::Duration::minutes(EXPORT_STALE_MINUTES + 1);
+        let job_id_2 = claim(later).await.unwrap().unwrap();
+        let status: String =
+            sqlx::query_scalar("SELECT status FROM organization_exports WHERE id = $1")
+                .bind(job_id)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+        assert_eq!(status, "failed");
+
+        // A finished export holds it for the whole cooldown
+        set_status(job_id_2, "ready").await.unwrap();
+        assert!(
+            claim(later + chrono::Duration::hours(EXPORT_COOLDOWN_HOURS - 1))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            claim(later + chrono::Duration::hours(EXPORT_COOLDOWN_HOURS))
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        cleanup_db().await;
+    }
+}
@@ -0,0 +1,134 @@
+//! Per-IP fixed-window throttling and client IP extraction, shared by the
+//! web login/registration forms and the public demo endpoint. Counters are
+//! in-process, so each instance enforces its own limit.
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap},
+};
+use dashmap::DashMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use crate::config;
+
+/// Fixed-window attempt counter keyed by client IP
+pub struct IpRateLimiter {
+    limit: u32,
+    window: Duration,
+    hits: DashMap<IpAddr, (u32, Instant)>,
+}
+
+impl IpRateLimiter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            hits: DashMap::new(),
+        }
+    }
+
+    /// Record an attempt. Returns the attempt number within the current
+    /// window, or the seconds until the window resets once over the limit.
+    pub fn hit(&self, ip: IpAddr, now: Instant) -> Result<u32, u64> {
+        let mut entry = self.hits.entry(ip).or_insert((0, now));
+        let (count, started) = entry.value_mut();
+
+        if now.duration_since(*started) >= self.window {
+            *count = 0;
+            *started = now;
+        }
+
+        if *count >= self.limit {
+            let reset = self.window - now.duration_since(*started);
+            return Err(reset.as_secs().max(1));
+        }
+
+        *count += 1;
+        Ok(*count)
+    }
+}
+
+/// Client IP: X-Forwarded-For (first hop) when proxy headers are trusted,
+/// otherwise the socket peer address. None when neither is available.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub Option<IpAddr>);
+
+fn forwarded_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    headers
+        .get("x-forwarded-for")?
+        .to_str()
+        .ok()?
+        .split(',')
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let forwarded = if config::get_settings().trust_proxy_headers {
+            forwarded_ip(&parts.headers)
+        } else {
+            None
+        };
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        Ok(ClientIp(forwarded.or(peer)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([203, 0, 113, last])
+    }
+
+    #[test]
+    fn test_limiter_blocks_after_limit() {
+        let limiter = IpRateLimiter::new(3, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert_eq!(limiter.hit(ip(1), now), Ok(1));
+        assert_eq!(limiter.hit(ip(1), now), Ok(2));
+        assert_eq!(limiter.hit(ip(1), now), Ok(3));
+        assert_eq!(limiter.hit(ip(1), now), Err(60));
+
+        // Other IPs are unaffected
+        assert_eq!(limiter.hit(ip(2), now), Ok(1));
+    }
+
+    #[test]
+    fn test_limiter_window_resets() {
+        let limiter = IpRateLimiter::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert_eq!(limiter.hit(ip(1), now), Ok(1));
+        assert_eq!(limiter.hit(ip(1), now + Duration::from_secs(45)), Err(15));
+        assert_eq!(limiter.hit(ip(1), now + Duration::from_secs(60)), Ok(1));
+    }
+
+    #[test]
+    fn test_forwarded_ip_takes_first_hop() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "198.51.100.7, 10.0.0.1".parse().unwrap());
+        assert_eq!(forwarded_ip(&headers), "198.51.100.7".parse().ok());
+
+        headers.insert("x-forwarded-for", "garbage".parse().unwrap());
+        assert_eq!(forwarded_ip(&headers), None);
+    }
+}
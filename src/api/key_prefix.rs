@@ -0,0 +1,141 @@
+//! Deprecation of API keys sent without `API_KEY_PREFIX`
+//!
+//! Keys used to be accepted with or without the prefix. Without it a key is
+//! just a base64 blob that secret scanners can't recognize, so unprefixed
+//! keys are being phased out: while `ALLOW_UNPREFIXED_TOKENS` is on (the
+//! default for now) they still work, but each use is counted per
+//! organization in `smally_unprefixed_tokens_total` and the response carries
+//! `Deprecation` and `Warning` headers. With it off they are rejected with
+//! 401 `missing_key_prefix`.
+
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::{config, monitoring};
+
+tokio::task_local! {
+    /// Whether the request being handled authenticated without the prefix
+    static UNPREFIXED: Arc<AtomicBool>;
+}
+
+/// Record that the request authenticated with an unprefixed key of `org_id`
+pub fn record_unprefixed(org_id: Uuid) {
+    monitoring::UNPREFIXED_TOKENS
+        .with_label_values(&[&org_id.to_string()])
+        .inc();
+    let _ = UNPREFIXED.try_with(|flag| flag.store(true, Ordering::Relaxed));
+}
+
+/// Add deprecation headers to responses of requests authenticated with an
+/// unprefixed key
+pub async fn key_prefix_middleware(request: Request, next: Next) -> Response {
+    if !request.uri().path().starts_with("/v1/") {
+        return next.run(request).await;
+    }
+
+    let unprefixed = Arc::new(AtomicBool::new(false));
+    let mut response = UNPREFIXED
+        .scope(unprefixed.clone(), next.run(request))
+        .await;
+
+    if unprefixed.load(Ordering::Relaxed) {
+        let headers = response.headers_mut();
+        headers.insert("deprecation", HeaderValue::from_static("true"));
+        let warning = format!(
+            "299 smally \"API keys without the '{}' prefix are deprecated and will be rejected; add the prefix to your key\"",
+            config::get_settings().api_key_prefix
+        );
+        if let Ok(value) = HeaderValue::from_str(&warning) {
+            headers.insert(header::WARNING, value);
+        }
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "control-plane")]
+    use crate::auth;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::factory;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use axum::{body::Body, routing::get, Router};
+    #[cfg(feature = "control-plane")]
+    use serial_test::serial;
+    use tower::ServiceExt;
+
+    async fn get_path(app: Router, path: &str) -> Response {
+        app.oneshot(Request::get(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_deprecation_headers() {
+        let org_id = Uuid::now_v7();
+        let app = Router::new()
+            .route(
+                "/v1/old",
+                get(move || async move { record_unprefixed(org_id) }),
+            )
+            .route("/v1/new", get(|| async {}))
+            .layer(axum::middleware::from_fn(key_prefix_middleware));
+
+        let response = get_path(app.clone(), "/v1/old").await;
+        assert_eq!(response.headers()["deprecation"], "true");
+        assert!(response.headers()[header::WARNING]
+            .to_str()
+            .unwrap()
+            .starts_with("299 smally"));
+        let counter = monitoring::UNPREFIXED_TOKENS.with_label_values(&[&org_id.to_string()]);
+        assert_eq!(counter.get(), 1.0);
+
+        let response = get_path(app, "/v1/new").await;
+        assert!(response.headers().get("deprecation").is_none());
+        assert!(response.headers().get(header::WARNING).is_none());
+    }
+
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    #[serial]
+    async fn test_unprefixed_key_is_accepted_with_warning() {
+        setup().await;
+        cleanup_db().await;
+        let owner = factory::user("prefix@example.com").await;
+        let key = factory::api_key(&owner).await;
+        let prefix = &config::get_settings().api_key_prefix;
+        let unprefixed = key.token.strip_prefix(prefix.as_str()).unwrap();
+
+        let app = Router::new()
+            .route("/v1/whoami", get(|_: auth::TokenClaims| async {}))
+            .layer(axum::middleware::from_fn(key_prefix_middleware));
+        let request = |token: &str| {
+            Request::get("/v1/whoami")
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(request(&key.token)).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert!(response.headers().get("deprecation").is_none());
+
+        let counter = monitoring::UNPREFIXED_TOKENS.with_label_values(&[&key.org_id.to_string()]);
+        let before = counter.get();
+        let response = app.oneshot(request(unprefixed)).await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["deprecation"], "true");
+        assert_eq!(counter.get(), before + 1.0);
+
+        cleanup_db().await;
+    }
+}
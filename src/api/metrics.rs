@@ -0,0 +1,282 @@
+//! Per-organization Prometheus metrics for customers' own scrapers
+//!
+//! `GET /v1/organizations/:org_id/metrics` renders a small exposition built
+//! on the fly from the organization's own Redis counters and usage events.
+//! The global registry (`/metrics`) is never consulted, so nothing from
+//! other tenants can end up in the output. Renderings are cached for 30
+//! seconds per organization to bound the cost of frequent scrapes.
+
+use axum::{
+    extract::Path,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::auth::{self, TokenClaims};
+use crate::billing::{self, statements};
+use crate::database::{self, DbPool, UsageEventsRepo};
+use crate::uuid_dashless::DashlessUuid;
+
+use super::ApiError;
+
+pub use crate::database::usage_events::KeyRequests;
+
+/// How long a rendering is served from memory
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Prometheus text format content type
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+static RENDERED: Lazy<DashMap<Uuid, (Instant, String)>> = Lazy::new(DashMap::new);
+
+/// Everything exposed for one organization
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrgMetrics {
+    pub requests: i64,
+    pub tokens: i64,
+    /// Monthly quota and what is left of it (tiers with an enforced quota only)
+    pub quota: Option<(i64, i64)>,
+    pub keys: Vec<KeyRequests>,
+}
+
+/// Organization metrics in the Prometheus text format (scoped API key with
+/// `metrics:read` for the same organization required)
+pub async fn org_metrics_handler(
+    claims: TokenClaims,
+    Path(org_id): Path<DashlessUuid>,
+) -> Result<Response, ApiError> {
+    let org_id = org_id.into_inner();
+    if !claims.has_scope(auth::SCOPE_METRICS_READ) {
+        return Err(ApiError::Unauthorized(format!(
+            "API key lacks the '{}' scope",
+            auth::SCOPE_METRICS_READ
+        )));
+    }
+    if claims.org_id() != org_id {
+        return Err(ApiError::Unauthorized(
+            "API key does not belong to this organization".to_string(),
+        ));
+    }
+
+    if let Some(cached) = RENDERED
+        .get(&org_id)
+        .filter(|entry| entry.0.elapsed() < CACHE_TTL)
+    {
+        return Ok(exposition(cached.1.clone()));
+    }
+
+    let mut metrics = collect(database::get_db(), org_id, Utc::now())
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
+
+    // Same source as the X-RateLimit-* headers (read-only, nothing consumed)
+    let decision = billing::peek_rate_limit_from_claims(&claims)
+        .await
+        .map_err(|_| ApiError::InternalError("Failed to check rate limit".to_string()))?;
+    metrics.quota = (decision.window == billing::LimitWindow::Month)
+        .then_some((decision.limit, decision.remaining));
+
+    let body = render(&metrics);
+    RENDERED.insert(org_id, (Instant::now(), body.clone()));
+
+    Ok(exposition(body))
+}
+
+fn exposition(body: String) -> Response {
+    (StatusCode::OK, [(header::CONTENT_TYPE, CONTENT_TYPE)], body).into_response()
+}
+
+/// This month's usage of an organization from its usage events
+pub async fn collect(pool: &DbPool, org_id: Uuid, now: DateTime<Utc>) -> sqlx::Result<OrgMetrics> {
+    let month_start = statements::month_start(now.date_naive())
+        .and_hms_opt(0, 0, 0)
+        .unwrap_or_default()
+        .and_utc();
+
+    let usage = UsageEventsRepo::new(pool);
+    let (requests, tokens) = usage.totals_since(org_id, month_start).await?;
+    let keys = usage
+        .requests_per_active_key_since(org_id, month_start)
+        .await?;
+
+    Ok(OrgMetrics {
+        requests,
+        tokens,
+        quota: None,
+        keys,
+    })
+}
+
+/// Render the metrics in the Prometheus text exposition format
+pub fn render(metrics: &OrgMetrics) -> String {
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, samples: &[(String, i64)]| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    };
+
+    gauge(
+        "smally_org_requests_month",
+        "Embedding requests this calendar month",
+        &[(String::new(), metrics.requests)],
+    );
+    gauge(
+        "smally_org_tokens_month",
+        "Tokens embedded this calendar month",
+        &[(String::new(), metrics.tokens)],
+    );
+    if let Some((limit, remaining)) = metrics.quota {
+        gauge(
+            "smally_org_quota_limit",
+            "Monthly request quota",
+            &[(String::new(), limit)],
+        );
+        gauge(
+            "smally_org_quota_remaining",
+            "Requests left in the monthly quota",
+            &[(String::new(), remaining)],
+        );
+    }
+    let keys: Vec<_> = metrics
+        .keys
+        .iter()
+        .map(|key| {
+            (
+                format!(
+                    "{{key_id=\"{}\",name=\"{}\"}}",
+                    key.key_id.simple(),
+                    escape_label(&key.name)
+                ),
+                key.requests,
+            )
+        })
+        .collect();
+    gauge(
+        "smally_org_key_requests_month",
+        "Embedding requests this calendar month per active API key",
+        &keys,
+    );
+
+    out
+}
+
+/// Escape a label value (backslash, double quote and newline)
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "control-plane")]
+    use crate::models::CreateAPIKeyRequest;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::factory;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    #[cfg(feature = "control-plane")]
+    use axum::{body::Body, http::Request, Router};
+    #[cfg(feature = "control-plane")]
+    use serial_test::serial;
+    #[cfg(feature = "control-plane")]
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_render() {
+        let key_id = Uuid::now_v7();
+        let metrics = OrgMetrics {
+            requests: 42,
+            tokens: 4200,
+            quota: Some((20_000, 19_958)),
+            keys: vec![KeyRequests {
+                key_id,
+                name: "Prod \"eu\"".to_string(),
+                requests: 42,
+            }],
+        };
+
+        let text = render(&metrics);
+        assert!(
+            text.contains("# TYPE smally_org_requests_month gauge\nsmally_org_requests_month 42\n")
+        );
+        assert!(text.contains("smally_org_tokens_month 4200\n"));
+        assert!(text.contains("smally_org_quota_remaining 19958\n"));
+        assert!(text.contains(&format!(
+            "smally_org_key_requests_month{{key_id=\"{}\",name=\"Prod \\\"eu\\\"\"}} 42\n",
+            key_id.simple()
+        )));
+
+        // Paid tiers have no enforced quota
+        let text = render(&OrgMetrics {
+            quota: None,
+            ..metrics
+        });
+        assert!(!text.contains("smally_org_quota"));
+    }
+
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    #[serial]
+    async fn test_metrics_require_scope_and_own_org() {
+        setup().await;
+        cleanup_db().await;
+        let app = || {
+            Router::new().route(
+                "/organizations/:org_id/metrics",
+                axum::routing::get(org_metrics_handler),
+            )
+        };
+        let get = |org_id: Uuid, token: String| {
+            Request::builder()
+                .uri(format!("/organizations/{}/metrics", org_id.simple()))
+                .header("authorization", format!("Bearer {}", token))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let owner = factory::user("metrics@example.com").await;
+        let org_id = owner.org_id;
+        let other_org_id = factory::user("other@example.com").await.org_id;
+        let scoped = factory::api_key_with(
+            &owner,
+            CreateAPIKeyRequest {
+                scopes: Some(vec![auth::SCOPE_METRICS_READ.to_string()]),
+                ..factory::key_request("Metrics")
+            },
+        )
+        .await
+        .token;
+
+        let response = app().oneshot(get(org_id, scoped.clone())).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text.contains("smally_org_requests_month 0"));
+        assert!(text.contains("smally_org_quota_limit 20000"));
+
+        // Another organization's metrics are not readable with this key
+        let response = app().oneshot(get(other_org_id, scoped)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Embed-only keys can't read metrics
+        let embed_only = factory::api_key(&owner).await.token;
+        let response = app().oneshot(get(org_id, embed_only)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        cleanup_db().await;
+    }
+}
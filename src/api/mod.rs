@@ -1,49 +1,231 @@
 use axum::{
     async_trait,
-    extract::{FromRequestParts, Json},
-    http::{request::Parts, HeaderMap, StatusCode},
+    extract::{FromRequestParts, Json, Path, Query},
+    http::{request::Parts, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
 use utoipa::ToSchema;
 
-use crate::{auth, billing, cache, config, inference, monitoring};
+use crate::{auth, billing, config, inference, monitoring, redact};
+// Only used by /v1/embed
+#[cfg(feature = "inference")]
+use {
+    crate::cache::{self, singleflight::SingleFlight},
+    crate::database::api_request_log::{NewRequest, USER_AGENT_MAX_CHARS},
+    axum::body::Bytes,
+    axum::http::{Method, Uri},
+    once_cell::sync::Lazy,
+    std::time::Instant,
+    timings::{Stage, StageTimer},
+};
 
+pub mod admin;
+#[cfg(feature = "control-plane")]
 pub mod api_keys;
+#[cfg(feature = "inference")]
+pub mod batch;
+#[cfg(all(
+    test,
+    feature = "inference",
+    feature = "control-plane",
+    feature = "fault-injection"
+))]
+mod chaos_tests;
+#[cfg(feature = "inference")]
+pub mod compare;
+#[cfg(feature = "inference")]
+pub mod demo;
+#[cfg(all(test, feature = "inference"))]
+mod embed_tests;
+pub mod error;
+#[cfg(feature = "inference")]
+pub mod estimate;
+#[cfg(feature = "control-plane")]
+pub mod exports;
+pub mod ip_limit;
+pub mod key_prefix;
+pub mod metrics;
+#[cfg(feature = "control-plane")]
 pub mod organizations;
+#[cfg(feature = "control-plane")]
+pub mod requests;
+#[cfg(feature = "control-plane")]
+pub mod service_accounts;
+pub mod share;
+pub mod snippets;
+#[cfg(feature = "control-plane")]
+pub mod statements;
+pub mod strict;
+pub mod timings;
+#[cfg(feature = "control-plane")]
+pub mod usage;
+#[cfg(feature = "control-plane")]
 pub mod users;
+pub mod versions;
+#[cfg(feature = "inference")]
+pub mod ws;
 
 /// Request to create text embeddings
-#[derive(Debug, Deserialize, ToSchema)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct EmbedRequest {
-    /// Text to embed (max 2000 characters)
+    /// Text to embed (max 2000 characters by default, see MAX_TEXT_CHARS)
     #[schema(example = "Hello world")]
     pub text: String,
     /// Whether to L2 normalize the embedding vector
     #[serde(default)]
     #[schema(default = false)]
     pub normalize: bool,
+    /// Validate and count tokens without running inference. Dry runs are
+    /// free (no quota is consumed, nothing is billed) but limited to a few
+    /// per minute per key (DRY_RUN_RPM).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[schema(default = false)]
+    pub dry_run: bool,
+    /// Skip language detection (`detected_language` is then omitted)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[schema(default = false)]
+    pub skip_language_detection: bool,
+    /// Truncate input longer than the key's token limit instead of
+    /// rejecting it (the response then has `truncated` and `original_tokens`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[schema(default = false)]
+    pub truncate: bool,
+    /// Round embedding values to this many decimal places (1-9) to shrink
+    /// the response; omitted = full f32 precision. 5 places keep cosine
+    /// similarity to the full vector above 0.99999; fewer trade accuracy
+    /// for size. Cached embeddings are always stored at full precision.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(minimum = 1, maximum = 9, example = 5)]
+    pub precision: Option<u8>,
+    /// Apply the model's post-processing transform (mean-centering / PCA
+    /// whitening, `W(x - mu)`) to the embedding; then `normalize` controls
+    /// whether the result is L2-normalized again. The embedding may have
+    /// fewer dimensions than the model. 400 if the model has no transform.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[schema(default = false)]
+    pub post_process: bool,
+    /// Model to use (see `GET /v1/models`); omitted = the organization's
+    /// default. 403 if the key or organization doesn't allow it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "all-MiniLM-L6-v2")]
+    pub model: Option<String>,
+    /// Add `timings`, the time spent per stage of the request, to the
+    /// response
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[schema(default = false)]
+    pub include_timings: bool,
+}
+
+/// Outcome of one inference, shared by the requests coalesced onto it
+#[cfg(feature = "inference")]
+type Inference = Result<(Vec<f32>, inference::Metadata), InferenceError>;
+
+/// Why an inference produced no embedding
+#[cfg(feature = "inference")]
+#[derive(Debug, Clone)]
+enum InferenceError {
+    /// Shed because the inference queue was full
+    Overloaded(inference::dispatcher::Overloaded),
+    Failed(String),
+}
+
+/// Cache misses being embedded, shared by identical concurrent requests
+#[cfg(feature = "inference")]
+static IN_FLIGHT: Lazy<SingleFlight<Inference>> = Lazy::new(SingleFlight::new);
+
+/// Allowed range of `EmbedRequest::precision`
+#[cfg(feature = "inference")]
+const PRECISION_RANGE: std::ops::RangeInclusive<u8> = 1..=9;
+
+/// Embedding values, rounded to `precision` decimal places when serialized
+#[derive(Debug, Clone, Default)]
+pub struct Embedding {
+    pub values: Vec<f32>,
+    pub precision: Option<u8>,
+}
+
+impl Embedding {
+    pub fn new(values: Vec<f32>, precision: Option<u8>) -> Self {
+        Embedding { values, precision }
+    }
+}
+
+impl Serialize for Embedding {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeSeq;
+
+        let mut seq = serializer.serialize_seq(Some(self.values.len()))?;
+        match self.precision {
+            Some(places) => {
+                let scale = 10f64.powi(places as i32);
+                for &value in &self.values {
+                    // f32 prints its shortest round-trip form, so the
+                    // rounded value comes out with at most `places` decimals
+                    let rounded = ((value as f64) * scale).round() / scale;
+                    seq.serialize_element(&(rounded as f32))?;
+                }
+            }
+            None => {
+                for value in &self.values {
+                    seq.serialize_element(value)?;
+                }
+            }
+        }
+        seq.end()
+    }
 }
 
 /// Embedding response with metadata
 #[derive(Debug, Serialize, ToSchema)]
 pub struct EmbedResponse {
-    /// 384-dimensional embedding vector
+    /// 384-dimensional embedding vector (rounded if `precision` was set)
     #[schema(value_type = Vec<f32>, example = json!([0.1, 0.2, 0.3]))]
-    pub embedding: Vec<f32>,
-    /// Model used for embedding
-    #[schema(example = "all-MiniLM-L6-v2")]
+    pub embedding: Embedding,
+    /// Model used for embedding, with its revision if the server has one
+    /// configured
+    #[schema(example = "all-MiniLM-L6-v2@2024-01")]
     pub model: String,
-    /// Number of tokens in input text
+    /// Number of tokens embedded (and billed); after truncation, the
+    /// truncated count
     #[schema(example = 5)]
     pub tokens: usize,
+    /// Present (true) when the input was truncated to the token limit
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// Token count of the input before truncation (truncated inputs only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 180)]
+    pub original_tokens: Option<usize>,
     /// Whether result was served from cache
     #[schema(example = false)]
     pub cached: bool,
+    /// Stable hash of the normalized text and model (same input, same hash)
+    #[schema(example = "9f2c3a1b7e4d5f60")]
+    pub content_hash: String,
     /// Total request latency in milliseconds
     #[schema(example = 25.3)]
     pub latency_ms: f64,
+    /// Present (true) when the request was a dry run; `embedding` is then empty
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    /// ISO 639-1 code of the detected input language (omitted if unsure)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "en")]
+    pub detected_language: Option<String>,
+    /// Confidence of `detected_language` (0.0 - 1.0)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0.92)]
+    pub language_confidence: Option<f32>,
+    /// Non-fatal issues, e.g. a language the model wasn't trained on
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Time spent per stage (only with `include_timings`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timings: Option<timings::Timings>,
 }
 
 /// Error response
@@ -58,9 +240,66 @@ pub struct ErrorResponse {
     /// Maximum allowed tokens (for token limit errors)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<usize>,
+    /// Exact token count of the rejected input (for token limit errors)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<usize>,
     /// Rate limit reset timestamp (for rate limit errors)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reset_at: Option<String>,
+    /// Models the key may use (for model_not_allowed errors)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub permitted_models: Option<Vec<String>>,
+    /// Suggested wait before retrying, in milliseconds (for overloaded errors)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1240)]
+    pub estimated_wait_ms: Option<u64>,
+    /// Id of the failed request; send it back as `X-Smally-Retry-Of` when
+    /// retrying (for overloaded errors)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Fields the endpoint doesn't know (for unknown_fields errors)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = json!(["normalise"]))]
+    pub unknown_fields: Option<Vec<String>>,
+    /// Region the organization is pinned to (for wrong_region errors)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "eu")]
+    pub region: Option<String>,
+    /// Base URL to send the organization's requests to (for wrong_region
+    /// errors, when known)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "https://eu.api.example.com")]
+    pub base_url: Option<String>,
+    /// Why the API key didn't verify (for invalid_api_key errors of signed
+    /// keys)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "token_truncated")]
+    pub code: Option<String>,
+    /// What to check, when there's more to say (for invalid_api_key errors)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "token appears truncated: expected ~12 more characters")]
+    pub hint: Option<String>,
+}
+
+impl ErrorResponse {
+    /// Error without details
+    pub fn new(error: &str, message: String) -> Self {
+        Self {
+            error: error.to_string(),
+            message,
+            max_tokens: None,
+            tokens: None,
+            reset_at: None,
+            permitted_models: None,
+            estimated_wait_ms: None,
+            request_id: None,
+            unknown_fields: None,
+            region: None,
+            base_url: None,
+            code: None,
+            hint: None,
+        }
+    }
 }
 
 /// Health check response
@@ -75,12 +314,27 @@ pub struct HealthResponse {
     /// Embedding model name
     #[schema(example = "sentence-transformers/all-MiniLM-L6-v2")]
     pub model: String,
+    /// Loaded models with their revisions
+    pub models: Vec<LoadedModel>,
+    /// Whether inference is configured for bitwise-reproducible output
+    /// (`DETERMINISTIC_INFERENCE`)
+    pub deterministic_inference: bool,
     /// Build information
     pub build: BuildInfo,
 }
 
-/// Build and version information
+/// A model loaded by this instance
 #[derive(Debug, Serialize, ToSchema)]
+pub struct LoadedModel {
+    #[schema(example = "all-MiniLM-L6-v2")]
+    pub id: String,
+    /// None if no revision is configured
+    #[schema(example = "2024-01")]
+    pub revision: Option<String>,
+}
+
+/// Build and version information
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct BuildInfo {
     /// Git commit hash
     pub git_hash: String,
@@ -98,6 +352,27 @@ pub struct BuildInfo {
     pub profile: String,
 }
 
+impl BuildInfo {
+    /// Information about this binary, captured by build.rs
+    pub fn current() -> Self {
+        let profile = if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        };
+
+        BuildInfo {
+            git_hash: env!("GIT_HASH").to_string(),
+            git_branch: env!("GIT_BRANCH").to_string(),
+            git_date: env!("GIT_DATE").to_string(),
+            git_dirty: env!("GIT_DIRTY").parse().unwrap_or(false),
+            build_timestamp: env!("BUILD_TIMESTAMP").to_string(),
+            rust_version: env!("RUST_VERSION").to_string(),
+            profile: profile.to_string(),
+        }
+    }
+}
+
 /// Health check endpoint
 ///
 /// Returns service status, version, and build information
@@ -112,28 +387,203 @@ pub struct BuildInfo {
 pub async fn health_handler() -> Json<HealthResponse> {
     let settings = config::get_settings();
 
-    let profile = if cfg!(debug_assertions) {
-        "debug"
-    } else {
-        "release"
-    };
-
     Json(HealthResponse {
         status: "healthy".to_string(),
         version: settings.version.clone(),
         model: settings.model_name.clone(),
-        build: BuildInfo {
-            git_hash: env!("GIT_HASH").to_string(),
-            git_branch: env!("GIT_BRANCH").to_string(),
-            git_date: env!("GIT_DATE").to_string(),
-            git_dirty: env!("GIT_DIRTY").parse().unwrap_or(false),
-            build_timestamp: env!("BUILD_TIMESTAMP").to_string(),
-            rust_version: env!("RUST_VERSION").to_string(),
-            profile: profile.to_string(),
-        },
+        models: inference::catalog::available()
+            .into_iter()
+            .map(|m| LoadedModel {
+                id: m.id,
+                revision: m.revision,
+            })
+            .collect(),
+        deterministic_inference: settings.deterministic_inference,
+        build: BuildInfo::current(),
     })
 }
 
+/// Query parameters for the readiness endpoint
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+pub struct ReadinessQuery {
+    /// Include recent probe failures
+    #[serde(default)]
+    pub verbose: bool,
+}
+
+/// Result of a background dependency probe
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DependencyStatus {
+    /// Dependency name: postgres, redis or tokenizer
+    #[schema(example = "postgres")]
+    pub dependency: String,
+    /// Whether the probe succeeded (false if it has not run yet)
+    pub up: bool,
+    /// Probe latency in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<f64>,
+    /// Failure reason
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// When the probe ran
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl From<monitoring::probes::ProbeResult> for DependencyStatus {
+    fn from(result: monitoring::probes::ProbeResult) -> Self {
+        Self {
+            dependency: result.dependency.to_string(),
+            up: result.ok,
+            latency_ms: Some(result.latency_ms),
+            error: result.error,
+            checked_at: Some(result.checked_at),
+        }
+    }
+}
+
+/// Readiness of a component (a group of dependencies routes need)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ComponentStatus {
+    /// Component: inference, database, redis or cache
+    #[schema(example = "inference")]
+    pub component: String,
+    /// "ready" or "not_ready"
+    #[schema(example = "ready")]
+    pub status: String,
+    /// Why the component isn't ready
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl From<monitoring::readiness::ComponentState> for ComponentStatus {
+    fn from(state: monitoring::readiness::ComponentState) -> Self {
+        Self {
+            component: state.component.to_string(),
+            status: readiness_status(state.ready).to_string(),
+            reason: state.reason,
+        }
+    }
+}
+
+fn readiness_status(ready: bool) -> &'static str {
+    if ready {
+        "ready"
+    } else {
+        "not_ready"
+    }
+}
+
+fn readiness_code(ready: bool) -> StatusCode {
+    if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    }
+}
+
+/// Readiness response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    /// "ready" or "not_ready" (ready when every component is)
+    #[schema(example = "ready")]
+    pub status: String,
+    /// Readiness of each component
+    pub components: Vec<ComponentStatus>,
+    /// Latest probe of each dependency
+    pub dependencies: Vec<DependencyStatus>,
+    /// Recent failed probes, newest first (verbose only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recent_failures: Option<Vec<DependencyStatus>>,
+}
+
+/// Readiness check
+///
+/// Reports each component (inference, database, redis, cache) and the latest
+/// background probe of each dependency (Postgres, Redis, the cache,
+/// tokenizer; probed every 15 seconds). Returns 503 when any component is
+/// down.
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "health",
+    params(ReadinessQuery),
+    responses(
+        (status = 200, description = "All components are ready", body = ReadinessResponse),
+        (status = 503, description = "A component is down", body = ReadinessResponse)
+    )
+)]
+pub async fn readiness_handler(Query(query): Query<ReadinessQuery>) -> Response {
+    let dependencies: Vec<DependencyStatus> = monitoring::probes::latest()
+        .into_iter()
+        .map(|(dependency, result)| match result {
+            Some(result) => result.into(),
+            None => DependencyStatus {
+                dependency: dependency.to_string(),
+                up: false,
+                latency_ms: None,
+                error: Some("not probed yet".to_string()),
+                checked_at: None,
+            },
+        })
+        .collect();
+    let components: Vec<ComponentStatus> = monitoring::readiness::components()
+        .into_iter()
+        .map(ComponentStatus::from)
+        .collect();
+    let ready = components
+        .iter()
+        .all(|component| component.reason.is_none());
+
+    let recent_failures = query.verbose.then(|| {
+        monitoring::probes::recent_failures()
+            .into_iter()
+            .map(DependencyStatus::from)
+            .collect()
+    });
+
+    (
+        readiness_code(ready),
+        Json(ReadinessResponse {
+            status: readiness_status(ready).to_string(),
+            components,
+            dependencies,
+            recent_failures,
+        }),
+    )
+        .into_response()
+}
+
+/// Readiness of one component
+///
+/// For probes of deployments that only need part of the service: the
+/// deployment serving embeddings can use `/health/ready/inference`, which
+/// stays down while the model can't serve, without being affected by
+/// anything else.
+#[utoipa::path(
+    get,
+    path = "/health/ready/{component}",
+    tag = "health",
+    params(
+        ("component" = String, Path, description = "inference, database, redis or cache")
+    ),
+    responses(
+        (status = 200, description = "The component is ready", body = ComponentStatus),
+        (status = 503, description = "The component is down", body = ComponentStatus),
+        (status = 404, description = "No such component")
+    )
+)]
+pub async fn component_readiness_handler(Path(component): Path<String>) -> Response {
+    match monitoring::readiness::component(&component) {
+        Some(state) => (
+            readiness_code(state.ready),
+            Json(ComponentStatus::from(state)),
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
 /// API information endpoint
 ///
 /// Returns basic API information and available endpoints
@@ -153,12 +603,336 @@ pub async fn root_handler() -> Json<serde_json::Value> {
         "version": settings.version,
         "endpoints": {
             "/v1/embed": "POST - Create embeddings",
+            "/v1/snippets": "GET - Usage examples (?lang=curl|python|node|rust)",
             "/health": "GET - Health check",
+            "/health/ready": "GET - Readiness (components and dependency probes, ?verbose=true)",
+            "/health/ready/{component}": "GET - Readiness of one component (inference, database, redis, cache)",
             "/metrics": "GET - Prometheus metrics"
         }
     }))
 }
 
+/// Query parameters for the snippets endpoint
+#[derive(Debug, Deserialize, utoipa::IntoParams)]
+pub struct SnippetsQuery {
+    /// Language: curl, python, node or rust
+    #[param(value_type = String, example = "curl")]
+    pub lang: snippets::SnippetLang,
+}
+
+/// Usage examples for one language
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SnippetsResponse {
+    /// Requested language
+    #[schema(example = "curl")]
+    pub lang: String,
+    /// Base URL the examples point at
+    #[schema(example = "https://api.example.com")]
+    pub base_url: String,
+    /// Snippets keyed by variant: basic, batch, error_handling
+    pub snippets: std::collections::BTreeMap<String, String>,
+}
+
+/// Ready-to-paste usage examples
+///
+/// Returns curl/Python/Node/Rust examples for the embedding endpoint, using
+/// this server's base URL and a placeholder key with the configured prefix.
+#[utoipa::path(
+    get,
+    path = "/v1/snippets",
+    tag = "health",
+    params(SnippetsQuery),
+    responses(
+        (status = 200, description = "Usage examples", body = SnippetsResponse),
+        (status = 400, description = "Unknown language")
+    )
+)]
+pub async fn snippets_handler(Query(query): Query<SnippetsQuery>) -> Json<SnippetsResponse> {
+    let settings = config::get_settings();
+    let api_key = snippets::placeholder_api_key();
+
+    let snippets = snippets::SnippetVariant::ALL
+        .iter()
+        .map(|variant| {
+            let name = serde_json::to_value(variant)
+                .ok()
+                .and_then(|v| v.as_str().map(str::to_string))
+                .unwrap_or_default();
+            (
+                name,
+                snippets::render(query.lang, *variant, &settings.public_base_url, &api_key),
+            )
+        })
+        .collect();
+
+    Json(SnippetsResponse {
+        lang: query.lang.as_str().to_string(),
+        base_url: settings.public_base_url.clone(),
+        snippets,
+    })
+}
+
+/// One enforced rate limit window of the caller
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RateLimitWindow {
+    /// Window: minute or month
+    #[schema(example = "month")]
+    pub window: String,
+    /// Requests allowed per window
+    #[schema(example = 20000)]
+    pub limit: i64,
+    /// Requests counted in the current window
+    #[schema(example = 12400)]
+    pub used: i64,
+    /// Requests left in the current window
+    #[schema(example = 7600)]
+    pub remaining: i64,
+    /// When the window resets (UTC)
+    #[schema(example = "2025-03-01T00:00:00Z")]
+    pub reset_at: String,
+    /// Start of the billing cycle (monthly window only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "2025-02-01T00:00:00Z")]
+    pub cycle_start: Option<String>,
+    /// End of the billing cycle, when the quota resets (monthly window only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "2025-03-01T00:00:00Z")]
+    pub cycle_end: Option<String>,
+    /// Month-end forecast of the billing cycle (monthly window only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forecast: Option<UsageForecast>,
+}
+
+/// Projected end-of-cycle total of one metric
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ProjectedUsage {
+    /// Used so far in the cycle
+    #[schema(example = 12400)]
+    pub used: i64,
+    /// Projected total when the cycle ends
+    #[schema(example = 23100)]
+    pub projected: i64,
+    /// Lower bound of the roughly 95% band (never below `used`)
+    #[schema(example = 21800)]
+    pub low: i64,
+    /// Upper bound of the roughly 95% band
+    #[schema(example = 24400)]
+    pub high: i64,
+}
+
+/// Where usage is heading by the end of the billing cycle: the daily usage
+/// so far, weighted by day of week and extended along its trend
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UsageForecast {
+    pub requests: ProjectedUsage,
+    pub tokens: ProjectedUsage,
+    /// Projected requests as a percentage of the quota
+    #[schema(example = 115.5)]
+    pub projected_percent: f64,
+    /// Day (UTC) the quota is projected to run out, omitted if it should
+    /// last the cycle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "2025-02-26")]
+    pub exhaustion_date: Option<String>,
+    /// Complete days of usage the forecast is based on
+    #[schema(example = 14)]
+    pub days_observed: usize,
+}
+
+impl From<billing::forecast::Projection> for ProjectedUsage {
+    fn from(projection: billing::forecast::Projection) -> Self {
+        ProjectedUsage {
+            used: projection.used,
+            projected: projection.projected,
+            low: projection.low,
+            high: projection.high,
+        }
+    }
+}
+
+impl From<billing::forecast::Forecast> for UsageForecast {
+    fn from(forecast: billing::forecast::Forecast) -> Self {
+        UsageForecast {
+            requests: forecast.requests.into(),
+            tokens: forecast.tokens.into(),
+            projected_percent: (forecast.projected_percent.unwrap_or(0.0) * 10.0).round() / 10.0,
+            exhaustion_date: forecast
+                .exhaustion_date
+                .map(|day| day.format("%Y-%m-%d").to_string()),
+            days_observed: forecast.days_observed,
+        }
+    }
+}
+
+/// Limits of the calling API key
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LimitsResponse {
+    /// Tier: free, pro or scale
+    #[schema(example = "free")]
+    pub tier: String,
+    /// Requests per billing cycle (only enforced on the free tier). Cycles
+    /// are calendar months unless the organization set a billing anchor day.
+    #[schema(example = 20000)]
+    pub monthly_quota: i32,
+    /// Token ceiling per request; longer inputs are truncated
+    #[schema(example = 128)]
+    pub max_tokens: usize,
+    /// Maximum input text length in characters
+    #[schema(example = 2000)]
+    pub max_input_chars: usize,
+    /// Concurrent requests per key (0 = unlimited)
+    #[schema(example = 2)]
+    pub max_concurrency: u32,
+    /// Enforced windows with their current usage. Unlimited windows are
+    /// omitted, as is the monthly window of paid (pay-as-you-go) tiers.
+    pub rate_limits: Vec<RateLimitWindow>,
+}
+
+/// Limits and remaining quota of the calling key
+///
+/// For showing usage ("12,400 of 20,000 requests") without hardcoding tier
+/// limits. Reads the same counters that produce 429 responses; calling it
+/// doesn't count against any limit. The monthly window also forecasts the
+/// end of the billing cycle ("on track to run out on the 26th").
+#[utoipa::path(
+    get,
+    path = "/v1/limits",
+    tag = "embeddings",
+    responses(
+        (status = 200, description = "Limits and current usage", body = LimitsResponse),
+        (status = 401, description = "Unauthorized - invalid or missing API key", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn limits_handler(claims: auth::TokenClaims) -> Result<Json<LimitsResponse>, ApiError> {
+    let tier = claims
+        .tier()
+        .map_err(|_| ApiError::InternalError("Failed to decode tier".to_string()))?;
+    let windows = billing::current_usage(&claims)
+        .await
+        .map_err(|_| ApiError::InternalError("Failed to read usage".to_string()))?;
+
+    let mut rate_limits = Vec::with_capacity(windows.len());
+    for w in windows {
+        // The forecast is a nicety: without it the limits are still right
+        let forecast = match w.cycle {
+            Some(cycle) => billing::cycle_forecast(
+                crate::database::get_db(),
+                claims.org_id(),
+                cycle,
+                w.used,
+                w.limit,
+            )
+            .await
+            .map_err(|e| tracing::warn!("Failed to forecast usage: {}", e))
+            .ok()
+            .map(UsageForecast::from),
+            None => None,
+        };
+        rate_limits.push(RateLimitWindow {
+            window: w.window.as_str().to_string(),
+            limit: w.limit,
+            used: w.used,
+            remaining: w.remaining,
+            reset_at: format_reset_at(w.reset_at),
+            cycle_start: w.cycle.map(|c| format_reset_at(c.start)),
+            cycle_end: w.cycle.map(|c| format_reset_at(c.end)),
+            forecast,
+        });
+    }
+
+    Ok(Json(LimitsResponse {
+        tier: format!("{:?}", tier).to_lowercase(),
+        monthly_quota: claims.monthly_quota(),
+        max_tokens: inference::effective_max_tokens(
+            claims.max_tokens(),
+            config::get_settings().max_tokens,
+        ),
+        max_input_chars: tier.limits().max_chars,
+        max_concurrency: claims.max_concurrency(),
+        rate_limits,
+    }))
+}
+
+/// A model and whether the caller may use it
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ModelEntry {
+    /// Identifier to pass as `model`
+    #[schema(example = "all-MiniLM-L6-v2")]
+    pub id: String,
+    /// Length of the embedding vectors
+    #[schema(example = 384)]
+    pub dimensions: usize,
+    /// Model's own token ceiling
+    #[schema(example = 128)]
+    pub max_tokens: usize,
+    /// Upstream license of the model (SPDX identifier)
+    #[schema(example = "Apache-2.0")]
+    pub license: Option<String>,
+    /// Where the model comes from
+    #[schema(example = "https://huggingface.co/sentence-transformers/all-MiniLM-L6-v2")]
+    pub source_url: Option<String>,
+    /// Revision of the served model files
+    #[schema(example = "2024-01")]
+    pub revision: Option<String>,
+    /// Whether the calling key may use this model
+    pub allowed: bool,
+    /// Whether requests without `model` use this one
+    pub default: bool,
+}
+
+/// Models served by this instance
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ModelsResponse {
+    pub data: Vec<ModelEntry>,
+}
+
+/// List models
+///
+/// Every model this instance serves, marking those the calling key may use
+/// (after the organization's `allowed_models` and the key's own model
+/// restriction) and the one used when a request doesn't name a model.
+#[utoipa::path(
+    get,
+    path = "/v1/models",
+    tag = "embeddings",
+    responses(
+        (status = 200, description = "Served models", body = ModelsResponse),
+        (status = 401, description = "Unauthorized - invalid or missing API key", body = ErrorResponse)
+    ),
+    security(
+        ("bearer_auth" = [])
+    )
+)]
+pub async fn models_handler(claims: auth::TokenClaims) -> Json<ModelsResponse> {
+    use inference::catalog;
+
+    let available = catalog::available();
+    let ids: Vec<String> = available.iter().map(|m| m.id.clone()).collect();
+    let org_models = catalog::org_models(claims.org_id()).await;
+    let permitted = catalog::permitted(&ids, &org_models, claims.models());
+    let default = catalog::resolve(None, &ids, &org_models, claims.models()).ok();
+
+    Json(ModelsResponse {
+        data: available
+            .into_iter()
+            .map(|m| ModelEntry {
+                allowed: permitted.contains(&m.id),
+                default: default.as_ref() == Some(&m.id),
+                id: m.id,
+                dimensions: m.dimensions,
+                max_tokens: m.max_tokens,
+                license: m.license,
+                source_url: m.source_url,
+                revision: m.revision,
+            })
+            .collect(),
+    })
+}
+
 /// Create text embeddings
 ///
 /// Generates a 384-dimensional embedding vector for the input text using
@@ -166,74 +940,167 @@ pub async fn root_handler() -> Json<serde_json::Value> {
 ///
 /// The endpoint supports caching for faster responses and includes rate limiting
 /// based on your subscription tier.
+///
+/// With `"dry_run": true` the request is validated and its tokens counted,
+/// but no embedding is computed: the response has an empty `embedding` and
+/// `"dry_run": true`. Dry runs are free (no quota, not billed) and limited
+/// to a few per minute per key.
+///
+/// Keys created with `require_request_signing` must also send
+/// `X-Smally-Timestamp` and `X-Smally-Signature` headers (see `auth::signing`).
+///
+/// With `X-Smally-Strict: true`, or a key created with `strict_validation`,
+/// unknown body fields are a 400 `unknown_fields` error (see `strict`).
+///
+/// The response shape depends on the API version (see `/v1/meta/versions`):
+/// from `2025-02-15`, `tokens`, `truncated` and `original_tokens` are nested
+/// under `usage` and errors are `{"error": {"type": ..., "message": ...}}`.
+#[cfg(feature = "inference")]
 #[utoipa::path(
     post,
     path = "/v1/embed",
     tag = "embeddings",
     request_body = EmbedRequest,
+    params(
+        ("X-Smally-Version" = Option<String>, Header, description = "API version (defaults to the one pinned to the key)"),
+        ("X-Smally-Strict" = Option<bool>, Header, description = "Reject unknown body fields (defaults to the key's `strict_validation`)"),
+        ("X-Smally-Retry-Of" = Option<String>, Header, description = "`request_id` of an overloaded response this request retries")
+    ),
     responses(
         (status = 200, description = "Successfully generated embedding", body = EmbedResponse,
          headers(
-             ("X-RateLimit-Limit" = String, description = "Monthly request limit"),
-             ("X-RateLimit-Remaining" = String, description = "Remaining requests this month"),
-             ("X-RateLimit-Reset" = String, description = "Reset timestamp")
+             ("X-Smally-Version" = String, description = "API version of the response"),
+             ("X-RateLimit-Limit" = String, description = "Request limit of the window (monthly quota on the free tier, per-minute limit on paid tiers)"),
+             ("X-RateLimit-Remaining" = String, description = "Remaining requests in the window"),
+             ("X-RateLimit-Reset" = String, description = "Unix timestamp at which the window resets"),
+             ("X-RateLimit-Cycle-Start" = String, description = "Unix timestamp at which the billing cycle started (monthly quota only)"),
+             ("X-RateLimit-Cycle-End" = String, description = "Unix timestamp at which the billing cycle ends (monthly quota only)"),
+             ("X-Cache" = String, description = "Cache tier that served the embedding: l1, l2 or miss"),
+             ("X-Inference-Ms" = String, description = "Model inference time in milliseconds (absent on cache hits)"),
+             ("X-Tokens" = String, description = "Tokens counted for the request")
          )
         ),
         (status = 400, description = "Invalid request", body = ErrorResponse),
-        (status = 401, description = "Unauthorized - invalid or missing API key", body = ErrorResponse),
-        (status = 429, description = "Rate limit exceeded", body = ErrorResponse),
-        (status = 500, description = "Internal server error", body = ErrorResponse)
+        (status = 401, description = "Unauthorized - invalid or missing API key, or missing/invalid request signature", body = ErrorResponse),
+        (status = 429, description = "Rate limit exceeded, or too many concurrent requests for the key", body = ErrorResponse),
+        (status = 451, description = "The organization is pinned to another region (`wrong_region`, names its `base_url`)", body = ErrorResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse),
+        (status = 503, description = "Inference queue full; retry after `estimated_wait_ms` with `X-Smally-Retry-Of`", body = ErrorResponse,
+         headers(
+             ("Retry-After" = String, description = "Seconds to wait before retrying"),
+             ("X-Request-Id" = String, description = "Id of the shed request")
+         )
+        )
     ),
     security(
         ("bearer_auth" = [])
     )
 )]
 pub async fn create_embedding_handler(
+    claims: auth::TokenClaims,
+    client_ip: ip_limit::ClientIp,
+    method: Method,
+    uri: Uri,
     headers: HeaderMap,
-    Json(req): Json<EmbedRequest>,
+    body: Bytes,
 ) -> Result<Response, ApiError> {
-    let start_time = Instant::now();
+    let mut timer = StageTimer::start();
 
     // Generate request ID for tracking
     let request_id = uuid::Uuid::now_v7();
 
-    // Get authorization header
-    let auth_header = headers.get("authorization").ok_or(ApiError::Unauthorized(
-        "Authorization header is required".to_string(),
-    ))?;
-
-    // Convert header value to string - handle both ASCII and UTF-8
-    let auth_str = auth_header.to_str().unwrap_or_else(|_| {
-        // Try as bytes
-        std::str::from_utf8(auth_header.as_bytes()).unwrap_or("")
-    });
-
-    // Extract Bearer token
-    let parts: Vec<&str> = auth_str.splitn(2, ' ').collect();
-    if parts.len() != 2 || parts[0].to_lowercase() != "bearer" {
+    if !claims.has_scope(auth::SCOPE_EMBED) {
         return Err(ApiError::Unauthorized(
-            "Authorization header must be 'Bearer <token>'".to_string(),
+            "API key is not allowed to create embeddings".to_string(),
         ));
     }
 
-    let full_token = parts[1];
+    // Verify request signature for keys that require it (before touching the body)
+    if claims.require_signing() {
+        verify_request_signature(&claims, &method, &uri, &headers, &body).await?;
+    }
 
-    // Check if token has configured prefix and strip it
-    let settings = config::get_settings();
-    let token = if full_token.starts_with(&settings.api_key_prefix) {
-        &full_token[settings.api_key_prefix.len()..] // Remove prefix
-    } else {
-        // Allow tokens without prefix for backward compatibility
-        full_token
-    };
+    if strict::requested(&headers) || claims.strict_validation() {
+        let known = strict::fields::<EmbedRequest>().unwrap_or_default();
+        let unknown = strict::unknown_body_fields(&body, known);
+        if !unknown.is_empty() {
+            return Err(ApiError::UnknownFields(unknown));
+        }
+    }
+    let req: EmbedRequest = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::BadRequest(format!("Invalid JSON body: {}", e)))?;
+    let prepared = prepare_embedding(&claims, &req, &mut timer).await?;
+    // Before the text is logged or cached
+    crate::residency::check(claims.org_id()).await?;
+    let origin = RequestOrigin::new(client_ip, &headers);
+
+    if req.dry_run {
+        return dry_run_embedding(
+            &claims,
+            &req,
+            &origin,
+            request_id,
+            prepared.tokens,
+            prepared.original_tokens,
+            timer,
+        )
+        .await;
+    }
 
-    // Validate token
-    let validator = auth::get_validator();
-    let claims = validator
-        .validate(token)
-        .await
-        .map_err(|e| ApiError::Unauthorized(format!("Token validation failed: {}", e)))?;
+    let embedded = run_embedding(
+        &claims,
+        &req,
+        &prepared,
+        &origin,
+        request_id,
+        "/v1/embed",
+        &mut timer,
+    )
+    .await?;
+
+    // A retry of a shed request: compare the wait we suggested with the
+    // time it actually took
+    if let Some(shed_id) = headers
+        .get(RETRY_OF_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| uuid::Uuid::parse_str(v.trim()).ok())
+    {
+        inference::dispatcher::dispatcher().retry_succeeded(shed_id, Instant::now());
+    }
+
+    let mut headers = rate_limit_headers(&embedded.rate_limit);
+    insert_cache_headers(
+        &mut headers,
+        embedded.cache_level,
+        embedded.inference_ms,
+        embedded.response.tokens,
+    );
+
+    let body = versions::embed_body(versions::current(), &embedded.response);
+    Ok((StatusCode::OK, headers, Json(body)).into_response())
+}
+
+/// A validated embed request, ready to run
+#[cfg(feature = "inference")]
+struct PreparedEmbedding {
+    transform: Option<std::sync::Arc<inference::transform::Transform>>,
+    model_id: String,
+    /// Token ceiling of the key for this model
+    max_tokens: usize,
+    /// Tokens to embed (after truncation)
+    tokens: usize,
+    /// Token count before truncation (truncated inputs only)
+    original_tokens: Option<usize>,
+}
 
+/// Validate a request against the key's limits and count its tokens,
+/// before any quota is consumed
+#[cfg(feature = "inference")]
+async fn prepare_embedding(
+    claims: &auth::TokenClaims,
+    req: &EmbedRequest,
+    timer: &mut StageTimer,
+) -> Result<PreparedEmbedding, ApiError> {
     // Validate text
     if req.text.trim().is_empty() {
         return Err(ApiError::BadRequest(
@@ -241,142 +1108,308 @@ pub async fn create_embedding_handler(
         ));
     }
 
-    if req.text.len() > 2000 {
-        return Err(ApiError::BadRequest(
-            "Text exceeds 2000 characters".to_string(),
-        ));
+    if let Some(precision) = req.precision {
+        if !PRECISION_RANGE.contains(&precision) {
+            return Err(ApiError::BadRequest(format!(
+                "precision must be between {} and {} decimal places",
+                PRECISION_RANGE.start(),
+                PRECISION_RANGE.end()
+            )));
+        }
     }
 
-    // Get settings early
-    let settings = config::get_settings();
+    let transform = if req.post_process {
+        let transform = inference::get_model().read().transform();
+        Some(transform.ok_or_else(|| {
+            ApiError::BadRequest("post_process is not available for this model".to_string())
+        })?)
+    } else {
+        None
+    };
 
-    // Fast validation: estimate tokens from text length
-    // Average: ~4 chars per token for BERT tokenizers
-    let estimated_tokens = req.text.len() / 4;
+    // The requested model if this key may use it, else the org's default
+    let org_models = inference::catalog::org_models(claims.org_id()).await;
+    let model_id = inference::catalog::resolve(
+        req.model.as_deref(),
+        &inference::catalog::available_ids(),
+        &org_models,
+        claims.models(),
+    )?;
 
-    // Reject if estimate is way over limit (2x buffer for safety)
-    if estimated_tokens > settings.max_tokens * 2 {
-        monitoring::ERROR_COUNT
-            .with_label_values(&["text_too_long"])
-            .inc();
-        return Err(ApiError::BadRequestWithTokens(
-            format!(
-                "Input text too long (estimated ~{} tokens, max {})",
-                estimated_tokens, settings.max_tokens
-            ),
-            settings.max_tokens,
-        ));
+    let tier_limits = claims
+        .tier()
+        .map_err(|_| ApiError::InternalError("Failed to decode tier".to_string()))?
+        .limits();
+    if req.text.chars().count() > tier_limits.max_chars {
+        return Err(ApiError::BadRequest(format!(
+            "Text exceeds {} characters",
+            tier_limits.max_chars
+        )));
     }
 
-    // Record request immediately to api_request_log (audit trail)
-    let buffer = billing::get_usage_buffer();
-    buffer.record_request(
+    let settings = config::get_settings();
+
+    // Per-key token ceiling from the signed claims, bounded by the model's max
+    let max_tokens = inference::effective_max_tokens(claims.max_tokens(), settings.max_tokens);
+
+    timer.lap(Stage::Validation);
+
+    // Exact token count (input is bounded by max_chars, so this is cheap),
+    // checked before any quota is consumed
+    let input_tokens = inference::get_model()
+        .read()
+        .tokenizer()
+        .encode(&req.text, true)
+        .len();
+    let original_tokens = check_token_limit(input_tokens, max_tokens, req.truncate)?;
+    timer.lap(Stage::Tokenization);
+
+    Ok(PreparedEmbedding {
+        transform,
+        model_id,
+        max_tokens,
+        tokens: input_tokens.min(max_tokens),
+        original_tokens,
+    })
+}
+
+/// Where a request came from, kept in the request log for abuse
+/// investigations
+#[cfg(feature = "inference")]
+#[derive(Debug, Clone, Default)]
+struct RequestOrigin {
+    client_ip: Option<std::net::IpAddr>,
+    user_agent: Option<String>,
+}
+
+#[cfg(feature = "inference")]
+impl RequestOrigin {
+    fn new(ip_limit::ClientIp(client_ip): ip_limit::ClientIp, headers: &HeaderMap) -> Self {
+        let user_agent = headers
+            .get(axum::http::header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(|ua| ua.chars().take(USER_AGENT_MAX_CHARS).collect());
+        RequestOrigin {
+            client_ip,
+            user_agent,
+        }
+    }
+}
+
+/// An embedding and how it was served
+#[cfg(feature = "inference")]
+struct Embedded {
+    response: EmbedResponse,
+    rate_limit: billing::RateLimitDecision,
+    cache_level: Option<cache::CacheLevel>,
+    inference_ms: Option<f64>,
+}
+
+/// Embed a prepared request: per-key concurrency and rate limits, the
+/// cache, inference, metrics and the usage events of `endpoint`
+#[cfg(feature = "inference")]
+#[tracing::instrument(
+    name = "embed",
+    skip_all,
+    fields(
+        request_id = %request_id,
+        endpoint = endpoint,
+        timings.validation_ms = tracing::field::Empty,
+        timings.tokenization_ms = tracing::field::Empty,
+        timings.rate_limit_ms = tracing::field::Empty,
+        timings.cache_lookup_ms = tracing::field::Empty,
+        timings.inference_ms = tracing::field::Empty,
+        timings.post_processing_ms = tracing::field::Empty,
+        timings.usage_recording_ms = tracing::field::Empty,
+        timings.total_ms = tracing::field::Empty,
+    )
+)]
+async fn run_embedding(
+    claims: &auth::TokenClaims,
+    req: &EmbedRequest,
+    prepared: &PreparedEmbedding,
+    origin: &RequestOrigin,
+    request_id: uuid::Uuid,
+    endpoint: &str,
+    timer: &mut StageTimer,
+) -> Result<Embedded, ApiError> {
+    let PreparedEmbedding {
+        transform,
+        model_id,
+        max_tokens,
+        original_tokens,
+        ..
+    } = prepared;
+    let (max_tokens, original_tokens) = (*max_tokens, *original_tokens);
+
+    // Record request immediately to api_request_log (audit trail)
+    let buffer = billing::get_usage_buffer();
+    buffer.record_request(NewRequest {
         request_id,
-        claims.org_id(),
-        claims.key_id(),
-        "embeddings".to_string(),
-        "/v1/embed".to_string(),
-        req.text.clone(),
-        Some(serde_json::json!({
-            "normalize": req.normalize
+        organization_id: claims.org_id(),
+        api_key_id: claims.key_id(),
+        product: "embeddings".to_string(),
+        endpoint: endpoint.to_string(),
+        input_text: req.text.clone(),
+        input_metadata: Some(serde_json::json!({
+            "normalize": req.normalize,
+            "post_process": req.post_process,
+            "model": model_id
         })),
-    );
+        client_ip: origin.client_ip,
+        user_agent: origin.user_agent.clone(),
+    });
+    timer.lap(Stage::UsageRecording);
 
     // Get model and cache
     let model = inference::get_model();
     let cache = cache::get_cache();
 
+    // Hold a per-key slot until the response is built; taken before the
+    // model lock so one key can't queue up the whole inference pool
+    let max_concurrency = claims.max_concurrency();
+    let _in_flight = billing::concurrency::limiter()
+        .try_acquire(claims.key_id(), max_concurrency)
+        .ok_or_else(|| {
+            monitoring::ERROR_COUNT
+                .with_label_values(&["too_many_concurrent_requests"])
+                .inc();
+            ApiError::TooManyConcurrentRequests(format!(
+                "Too many concurrent requests for this API key (max {})",
+                max_concurrency
+            ))
+        })?;
+
     // Check rate limit using token claims
-    let (is_allowed, rate_limit_info) = billing::check_rate_limit_from_claims(&claims)
+    let rate_limit = billing::check_rate_limit_from_claims(claims)
         .await
         .map_err(|_| ApiError::InternalError("Failed to check rate limit".to_string()))?;
 
-    if !is_allowed {
-        let tier = format!(
-            "{:?}",
-            claims
-                .tier()
-                .map_err(|_| ApiError::InternalError("Failed to decode tier".to_string()))?
-        )
-        .to_lowercase();
-        monitoring::RATE_LIMIT_EXCEEDED
-            .with_label_values(&[&tier])
-            .inc();
+    timer.lap(Stage::RateLimit);
 
-        let reset_at = rate_limit_info.get("reset_at").cloned();
-        return Err(ApiError::RateLimitExceeded(
-            "Monthly quota exhausted".to_string(),
-            reset_at,
-        ));
+    if !rate_limit.allowed {
+        return Err(rate_limit_exceeded(claims, rate_limit));
     }
 
-    // Check cache
-    let (embedding, model_name, cached, exact_tokens) =
-        if let Some(cached_data) = cache.get(&req.text).await {
-            monitoring::CACHE_HITS.with_label_values(&["total"]).inc();
+    // Check cache. Only untruncated embeddings are cached, so a hit is valid
+    // for this key as long as it fits within the key's token ceiling.
+    let cache_policy = cache::policy::policy_for(claims.org_id()).await;
+    let cached_hit = cache
+        .get(&req.text, claims.org_id(), cache_policy)
+        .await
+        .filter(|(cached_data, _)| cached_data.tokens <= max_tokens);
+    timer.lap(Stage::CacheLookup);
+
+    let (embedding, model_name, cache_level, inference_ms, exact_tokens) =
+        if let Some((cached_data, level)) = cached_hit {
+            monitoring::CACHE_HITS
+                .with_label_values(&["total", cache_policy.as_str()])
+                .inc();
 
             // Cache hit: use metadata from cache (no token counting needed!)
             (
                 cached_data.embedding,
                 cached_data.model,
-                true,
+                Some(level),
+                None,
                 cached_data.tokens,
             )
         } else {
-            // Cache miss: generate embedding
-            let (embedding, metadata) = {
-                let mut model_lock = model.write();
-                model_lock.encode(&req.text, req.normalize).map_err(|_| {
+            // Cache miss: generate embedding, or wait for an identical
+            // request already doing so. Truncated results depend on the
+            // key's token ceiling, so they only coalesce with the same one.
+            let flight_key = match original_tokens {
+                None => cache.key(&req.text, claims.org_id(), cache_policy),
+                Some(_) => format!(
+                    "{}:t{}",
+                    cache.key(&req.text, claims.org_id(), cache_policy),
+                    max_tokens
+                ),
+            };
+            let (result, coalesced) = IN_FLIGHT
+                .run(&flight_key, || async {
+                    let dispatcher = inference::dispatcher::dispatcher();
+                    let result = {
+                        let _slot = dispatcher.try_enter().map_err(InferenceError::Overloaded)?;
+                        let mut model_lock = model.write();
+                        model_lock.encode_with_limit(&req.text, req.normalize, max_tokens)
+                    };
+                    let (embedding, metadata) =
+                        result.map_err(|e| InferenceError::Failed(e.to_string()))?;
+
+                    // Record inference time
+                    monitoring::INFERENCE_LATENCY.observe(metadata.inference_time_ms / 1000.0);
+                    dispatcher.record_latency(metadata.inference_time_ms);
+
+                    // Cache the result WITH metadata (skip truncated results, they
+                    // would be wrong for keys with a higher ceiling)
+                    if original_tokens.is_none() {
+                        cache
+                            .set(
+                                &req.text,
+                                claims.org_id(),
+                                cache_policy,
+                                cache::CachedEmbedding {
+                                    embedding: embedding.clone(),
+                                    tokens: metadata.tokens,
+                                    model: metadata.model.clone(),
+                                },
+                            )
+                            .await;
+                    }
+                    Ok((embedding, metadata))
+                })
+                .await;
+            timer.lap(Stage::Inference);
+
+            monitoring::CACHE_MISSES
+                .with_label_values(&[cache_policy.as_str()])
+                .inc();
+            if coalesced {
+                monitoring::SINGLEFLIGHT_COALESCED
+                    .with_label_values(&[cache_policy.as_str()])
+                    .inc();
+            }
+            let (embedding, metadata) = result.map_err(|e| match e {
+                InferenceError::Overloaded(overloaded) => shed(request_id, overloaded),
+                InferenceError::Failed(_) => {
                     monitoring::ERROR_COUNT
                         .with_label_values(&["inference_error"])
                         .inc();
                     ApiError::InternalError("Failed to generate embedding".to_string())
-                })?
-            };
+                }
+            })?;
 
-            // Record inference time
-            monitoring::INFERENCE_LATENCY.observe(metadata.inference_time_ms / 1000.0);
-            monitoring::CACHE_MISSES.inc();
-
-            // Cache the result WITH metadata
-            cache
-                .set(
-                    &req.text,
-                    cache::CachedEmbedding {
-                        embedding: embedding.clone(),
-                        tokens: metadata.tokens,
-                        model: metadata.model.clone(),
-                    },
-                )
-                .await;
+            // Each request is billed and recorded on its own below; only the
+            // one that ran inference reports its time
+            let inference_ms = (!coalesced).then_some(metadata.inference_time_ms);
 
             // Use tokens from inference metadata (already counted!)
-            (embedding, metadata.model, false, metadata.tokens)
+            (
+                embedding,
+                metadata.model,
+                None,
+                inference_ms,
+                metadata.tokens,
+            )
         };
+    let cached = cache_level.is_some();
+
+    // Post-process after the cache: entries hold the raw pooled vector, so
+    // requests with and without the transform share them
+    let embedding = match transform {
+        Some(transform) => transform.apply(&embedding, req.normalize),
+        None => embedding,
+    };
+    let (detection, warnings) = language_metadata(req);
+    timer.lap(Stage::PostProcessing);
 
     // Increment Redis counter for free tier rate limiting
     let tier = claims
         .tier()
         .map_err(|_| ApiError::InternalError("Failed to decode tier".to_string()))?;
     if tier == crate::models::TierType::Free {
-        billing::increment_free_tier_counter(claims.org_id());
-    }
-
-    let mut headers = HeaderMap::new();
-    if let Some(limit) = rate_limit_info.get("limit") {
-        if let Ok(value) = limit.parse() {
-            headers.insert("X-RateLimit-Limit", value);
-        }
-    }
-    if let Some(remaining) = rate_limit_info.get("remaining") {
-        if let Ok(value) = remaining.parse() {
-            headers.insert("X-RateLimit-Remaining", value);
-        }
-    }
-    if let Some(reset_at) = rate_limit_info.get("reset_at") {
-        if let Ok(value) = reset_at.parse() {
-            headers.insert("X-RateLimit-Reset", value);
-        }
+        billing::increment_free_tier_counter(claims.org_id(), claims.monthly_quota());
     }
 
     monitoring::TOKEN_COUNT.observe(exact_tokens as f64);
@@ -385,7 +1418,7 @@ pub async fn create_embedding_handler(
         .inc();
 
     // Calculate total latency
-    let total_latency_ms = start_time.elapsed().as_millis() as f64;
+    let total_latency_ms = timer.started().elapsed().as_millis() as f64;
 
     monitoring::REQUEST_LATENCY.observe(total_latency_ms / 1000.0);
 
@@ -400,57 +1433,574 @@ pub async fn create_embedding_handler(
             "model": model_name,
             "cached": cached,
             "latency_ms": total_latency_ms,
-            "normalize": req.normalize
+            "normalize": req.normalize,
+            "post_process": req.post_process,
+            "timings": timer.timings()
         }),
     );
+    timer.lap(Stage::UsageRecording);
+    let timings = timer.timings();
+    timings.record(&tracing::Span::current());
 
     let response = EmbedResponse {
-        embedding,
+        embedding: Embedding::new(embedding, req.precision),
+        content_hash: cache.content_hash(&req.text),
         model: model_name,
         tokens: exact_tokens,
+        truncated: original_tokens.is_some(),
+        original_tokens,
         cached,
         latency_ms: total_latency_ms,
+        dry_run: false,
+        detected_language: detection.map(|d| d.language.to_string()),
+        language_confidence: detection.map(|d| d.confidence),
+        warnings,
+        timings: req.include_timings.then_some(timings),
     };
 
-    Ok((StatusCode::OK, headers, Json(response)).into_response())
+    Ok(Embedded {
+        response,
+        rate_limit,
+        cache_level,
+        inference_ms,
+    })
+}
+
+/// Header carrying the `request_id` of a shed request being retried
+#[cfg(feature = "inference")]
+const RETRY_OF_HEADER: &str = "x-smally-retry-of";
+
+/// 503 for a request shed by the inference queue. Each response gets its
+/// own jittered wait so shed clients don't all retry at the same moment.
+#[cfg(feature = "inference")]
+fn shed(request_id: uuid::Uuid, overloaded: inference::dispatcher::Overloaded) -> ApiError {
+    use inference::dispatcher;
+
+    monitoring::ERROR_COUNT
+        .with_label_values(&["overloaded"])
+        .inc();
+    let wait_ms =
+        dispatcher::jittered_wait_ms(overloaded.estimated_wait_ms, &mut rand::thread_rng());
+    dispatcher::dispatcher().track_shed(request_id, wait_ms, Instant::now());
+
+    ApiError::Overloaded {
+        message: format!(
+            "Inference is at capacity ({} requests queued); retry in about {} ms",
+            overloaded.queue_depth, wait_ms
+        ),
+        estimated_wait_ms: wait_ms,
+        request_id,
+    }
+}
+
+/// Dry run of `/v1/embed`: exact token count and quota headroom without
+/// inference, quota consumption or a usage event
+#[cfg(feature = "inference")]
+async fn dry_run_embedding(
+    claims: &auth::TokenClaims,
+    req: &EmbedRequest,
+    origin: &RequestOrigin,
+    request_id: uuid::Uuid,
+    tokens: usize,
+    original_tokens: Option<usize>,
+    mut timer: StageTimer,
+) -> Result<Response, ApiError> {
+    let dry_run_limit = billing::check_dry_run_limit(claims)
+        .await
+        .map_err(|_| ApiError::InternalError("Failed to check rate limit".to_string()))?;
+    if !dry_run_limit.allowed {
+        return Err(ApiError::RateLimitExceeded(
+            "Too many dry runs per minute".to_string(),
+            dry_run_limit,
+        ));
+    }
+
+    // Read the quota without incrementing it
+    let rate_limit = billing::peek_rate_limit_from_claims(claims)
+        .await
+        .map_err(|_| ApiError::InternalError("Failed to check rate limit".to_string()))?;
+    timer.lap(Stage::RateLimit);
+    if !rate_limit.allowed {
+        return Err(rate_limit_exceeded(claims, rate_limit));
+    }
+
+    let (detection, warnings) = language_metadata(req);
+    timer.lap(Stage::PostProcessing);
+    let cache = cache::get_cache();
+    let settings = config::get_settings();
+    let model_name = inference::catalog::with_revision(
+        inference::catalog::model_id(&settings.model_name),
+        settings.model_revision.as_deref(),
+    );
+
+    billing::get_usage_buffer().record_dry_run(
+        NewRequest {
+            request_id,
+            organization_id: claims.org_id(),
+            api_key_id: claims.key_id(),
+            product: "embeddings".to_string(),
+            endpoint: "/v1/embed".to_string(),
+            input_text: req.text.clone(),
+            input_metadata: Some(serde_json::json!({
+                "normalize": req.normalize,
+                "dry_run": true
+            })),
+            client_ip: origin.client_ip,
+            user_agent: origin.user_agent.clone(),
+        },
+        tokens as i32,
+    );
+    timer.lap(Stage::UsageRecording);
+
+    monitoring::REQUEST_COUNT
+        .with_label_values(&["dry_run", "false"])
+        .inc();
+
+    let response = EmbedResponse {
+        embedding: Embedding::default(),
+        content_hash: cache.content_hash(&req.text),
+        model: model_name,
+        tokens,
+        truncated: original_tokens.is_some(),
+        original_tokens,
+        cached: false,
+        latency_ms: timer.started().elapsed().as_millis() as f64,
+        dry_run: true,
+        detected_language: detection.map(|d| d.language.to_string()),
+        language_confidence: detection.map(|d| d.confidence),
+        warnings,
+        timings: req.include_timings.then(|| timer.timings()),
+    };
+
+    let body = versions::embed_body(versions::current(), &response);
+    Ok((StatusCode::OK, rate_limit_headers(&rate_limit), Json(body)).into_response())
+}
+
+/// Detected language and the warnings it raises for the loaded model
+#[cfg(feature = "inference")]
+fn language_metadata(req: &EmbedRequest) -> (Option<inference::language::Detection>, Vec<String>) {
+    if req.skip_language_detection {
+        return (None, Vec::new());
+    }
+    let detection = inference::language::detect(&req.text);
+    let warnings = detection
+        .and_then(|detection| {
+            inference::language::unsupported_warning(
+                &detection,
+                &config::get_settings().model_languages,
+            )
+        })
+        .into_iter()
+        .collect();
+    (detection, warnings)
+}
+
+/// 429 for an exceeded per-minute or monthly limit
+#[cfg(feature = "inference")]
+fn rate_limit_exceeded(
+    claims: &auth::TokenClaims,
+    decision: billing::RateLimitDecision,
+) -> ApiError {
+    let tier = claims
+        .tier()
+        .map(|tier| format!("{:?}", tier).to_lowercase())
+        .unwrap_or_default();
+    monitoring::RATE_LIMIT_EXCEEDED
+        .with_label_values(&[&tier])
+        .inc();
+
+    let message = match decision.window {
+        billing::LimitWindow::Minute => "Too many requests per minute",
+        _ => "Monthly quota exhausted",
+    };
+    ApiError::RateLimitExceeded(message.to_string(), decision)
+}
+
+/// Check the exact token count (with [CLS]/[SEP]) of the input against the
+/// key's limit. Returns the original count when the input will be truncated,
+/// `None` when it fits, and rejects it when it doesn't fit and `truncate`
+/// wasn't requested.
+#[cfg(feature = "inference")]
+fn check_token_limit(
+    tokens: usize,
+    max_tokens: usize,
+    truncate: bool,
+) -> Result<Option<usize>, ApiError> {
+    if tokens <= max_tokens {
+        return Ok(None);
+    }
+    if truncate {
+        return Ok(Some(tokens));
+    }
+
+    monitoring::ERROR_COUNT
+        .with_label_values(&["text_too_long"])
+        .inc();
+    Err(ApiError::BadRequestWithTokens(
+        format!(
+            "Input text is {} tokens, max {} (send \"truncate\": true to truncate it)",
+            tokens, max_tokens
+        ),
+        max_tokens,
+        tokens,
+    ))
+}
+
+/// `X-RateLimit-*` headers for a rate limit decision (reset and cycle
+/// bounds as Unix timestamps); none when no limit applies
+fn rate_limit_headers(decision: &billing::RateLimitDecision) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if decision.window == billing::LimitWindow::Unlimited {
+        return headers;
+    }
+    headers.insert("X-RateLimit-Limit", HeaderValue::from(decision.limit));
+    headers.insert(
+        "X-RateLimit-Remaining",
+        HeaderValue::from(decision.remaining),
+    );
+    headers.insert(
+        "X-RateLimit-Reset",
+        HeaderValue::from(decision.reset_at.timestamp()),
+    );
+    if let Some(cycle) = decision.cycle {
+        headers.insert(
+            "X-RateLimit-Cycle-Start",
+            HeaderValue::from(cycle.start.timestamp()),
+        );
+        headers.insert(
+            "X-RateLimit-Cycle-End",
+            HeaderValue::from(cycle.end.timestamp()),
+        );
+    }
+    headers
+}
+
+/// Reset time as shown in JSON bodies
+pub(crate) fn format_reset_at(reset_at: chrono::DateTime<chrono::Utc>) -> String {
+    reset_at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// `X-Cache` (l1, l2 or miss), `X-Inference-Ms` (misses only) and `X-Tokens`
+#[cfg(feature = "inference")]
+fn insert_cache_headers(
+    headers: &mut HeaderMap,
+    cache_level: Option<cache::CacheLevel>,
+    inference_ms: Option<f64>,
+    tokens: usize,
+) {
+    let cache = cache_level.map_or("miss", cache::CacheLevel::as_str);
+    headers.insert("X-Cache", HeaderValue::from_static(cache));
+    if let Some(ms) = inference_ms {
+        if let Ok(value) = HeaderValue::from_str(&format!("{:.2}", ms)) {
+            headers.insert("X-Inference-Ms", value);
+        }
+    }
+    headers.insert("X-Tokens", HeaderValue::from(tokens));
+}
+
+/// Verify the HMAC signature of a request made with a signing-required key
+#[cfg(feature = "inference")]
+async fn verify_request_signature(
+    claims: &auth::TokenClaims,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), ApiError> {
+    use auth::signing::{self, SignatureError};
+
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let timestamp = header(signing::TIMESTAMP_HEADER);
+    let signature = header(signing::SIGNATURE_HEADER);
+
+    let path = uri
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or_else(|| uri.path());
+
+    let secret =
+        signing::derive_signing_secret(&config::get_settings().secret_key, claims.key_id());
+
+    let result = signing::verify_signature(
+        &secret,
+        timestamp,
+        signature,
+        method.as_str(),
+        path,
+        body,
+        chrono::Utc::now().timestamp(),
+    );
+
+    let result = match result {
+        Ok(verified) => {
+            // Replay protection: each signature may only be used once
+            let first_use = auth::get_validator()
+                .mark_signature_seen(claims.key_id(), &verified)
+                .await
+                .map_err(|_| {
+                    ApiError::InternalError("Failed to check request signature".to_string())
+                })?;
+            if first_use {
+                Ok(())
+            } else {
+                Err(SignatureError::Replayed)
+            }
+        }
+        Err(e) => Err(e),
+    };
+
+    result.map_err(|e| {
+        monitoring::ERROR_COUNT
+            .with_label_values(&["invalid_signature"])
+            .inc();
+        match e {
+            SignatureError::Missing => ApiError::SignatureRequired(e.to_string()),
+            _ => ApiError::InvalidSignature(e.to_string()),
+        }
+    })
 }
 
 #[derive(Debug)]
 pub enum ApiError {
     BadRequest(String),
-    BadRequestWithTokens(String, usize),
+    /// Message, the key's max tokens, the exact token count of the input
+    BadRequestWithTokens(String, usize, usize),
     Unauthorized(String),
-    RateLimitExceeded(String, Option<String>),
+    /// A signed API key that didn't verify
+    InvalidToken(auth::TokenError),
+    /// An unprefixed API key while `ALLOW_UNPREFIXED_TOKENS` is off
+    MissingKeyPrefix(String),
+    SignatureRequired(String),
+    InvalidSignature(String),
+    RateLimitExceeded(String, billing::RateLimitDecision),
+    TooManyConcurrentRequests(String),
+    /// Message, the models the key may use
+    ModelNotAllowed(String, Vec<String>),
+    /// Fields unknown to the endpoint, under strict validation
+    UnknownFields(Vec<String>),
+    /// A dark-launched feature the organization can't use yet
+    FeatureNotEnabled(crate::flags::NotEnabled),
+    /// The model can't serve (failed to load); the control plane is up
+    ModelUnavailable(String),
+    /// The organization is pinned to another region than this instance's
+    WrongRegion {
+        region: String,
+        base_url: Option<String>,
+    },
+    /// Shed by the inference queue
+    Overloaded {
+        message: String,
+        /// Suggested wait, jittered
+        estimated_wait_ms: u64,
+        request_id: uuid::Uuid,
+    },
     InternalError(String),
 }
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
+impl From<crate::flags::NotEnabled> for ApiError {
+    fn from(e: crate::flags::NotEnabled) -> Self {
+        ApiError::FeatureNotEnabled(e)
+    }
+}
+
+impl From<crate::residency::Refused> for ApiError {
+    fn from(e: crate::residency::Refused) -> Self {
+        use crate::residency::Refused;
+
+        match e {
+            Refused::WrongRegion { region, base_url } => ApiError::WrongRegion { region, base_url },
+            Refused::Unknown => {
+                ApiError::InternalError("Failed to look up the organization's region".to_string())
+            }
+        }
+    }
+}
+
+impl From<inference::catalog::ModelError> for ApiError {
+    fn from(e: inference::catalog::ModelError) -> Self {
+        use inference::catalog::ModelError;
+
+        match &e {
+            ModelError::Unknown { .. } => ApiError::BadRequest(e.to_string()),
+            ModelError::NotAllowed { permitted, .. } => {
+                ApiError::ModelNotAllowed(e.to_string(), permitted.clone())
+            }
+            ModelError::NonePermitted => ApiError::ModelNotAllowed(e.to_string(), Vec::new()),
+        }
+    }
+}
+
+impl ApiError {
+    /// Status, headers and body of the error
+    fn into_parts(self) -> (StatusCode, HeaderMap, ErrorResponse) {
+        let tokens = match &self {
+            ApiError::BadRequestWithTokens(_, _, tokens) => Some(*tokens),
+            _ => None,
+        };
+        // Logged under a request_id; the client only gets the id
+        let mut internal = match &self {
+            ApiError::InternalError(detail) => Some(error::internal_error(detail)),
+            _ => None,
+        };
+        let headers = match &self {
+            ApiError::RateLimitExceeded(_, decision) => rate_limit_headers(decision),
+            ApiError::Overloaded {
+                estimated_wait_ms,
+                request_id,
+                ..
+            } => {
+                let mut headers = HeaderMap::new();
+                let retry_after = inference::dispatcher::retry_after_secs(*estimated_wait_ms);
+                if let Ok(value) = HeaderValue::from_str(&retry_after.to_string()) {
+                    headers.insert(axum::http::header::RETRY_AFTER, value);
+                }
+                if let Ok(value) = HeaderValue::from_str(&request_id.to_string()) {
+                    headers.insert("x-request-id", value);
+                }
+                headers
+            }
+            ApiError::InternalError(_) => internal
+                .as_ref()
+                .map(|(request_id, _)| error::request_id_header(*request_id))
+                .unwrap_or_default(),
+            _ => HeaderMap::new(),
+        };
+        let (estimated_wait_ms, request_id) = match &self {
+            ApiError::Overloaded {
+                estimated_wait_ms,
+                request_id,
+                ..
+            } => (Some(*estimated_wait_ms), Some(request_id.to_string())),
+            ApiError::InternalError(_) => (
+                None,
+                internal
+                    .as_ref()
+                    .map(|(request_id, _)| request_id.to_string()),
+            ),
+            _ => (None, None),
+        };
+        let permitted_models = match &self {
+            ApiError::ModelNotAllowed(_, permitted) => Some(permitted.clone()),
+            _ => None,
+        };
+        let unknown_fields = match &self {
+            ApiError::UnknownFields(fields) => Some(fields.clone()),
+            _ => None,
+        };
+        let (region, base_url) = match &self {
+            ApiError::WrongRegion { region, base_url } => (Some(region.clone()), base_url.clone()),
+            _ => (None, None),
+        };
+        let (code, hint) = match &self {
+            ApiError::InvalidToken(e) => (Some(e.code().to_string()), e.hint()),
+            _ => (None, None),
+        };
         let (status, error_type, message, max_tokens, reset_at) = match self {
             ApiError::BadRequest(msg) => {
                 (StatusCode::BAD_REQUEST, "invalid_request", msg, None, None)
             }
-            ApiError::BadRequestWithTokens(msg, tokens) => (
+            ApiError::BadRequestWithTokens(msg, max_tokens, _) => (
                 StatusCode::BAD_REQUEST,
                 "text_too_long",
                 msg,
-                Some(tokens),
+                Some(max_tokens),
                 None,
             ),
             ApiError::Unauthorized(msg) => {
                 (StatusCode::UNAUTHORIZED, "invalid_api_key", msg, None, None)
             }
-            ApiError::RateLimitExceeded(msg, reset) => (
+            ApiError::InvalidToken(e) => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_api_key",
+                format!("Token validation failed: {}", e),
+                None,
+                None,
+            ),
+            ApiError::MissingKeyPrefix(msg) => (
+                StatusCode::UNAUTHORIZED,
+                "missing_key_prefix",
+                msg,
+                None,
+                None,
+            ),
+            ApiError::SignatureRequired(msg) => (
+                StatusCode::UNAUTHORIZED,
+                "signature_required",
+                msg,
+                None,
+                None,
+            ),
+            ApiError::InvalidSignature(msg) => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_signature",
+                msg,
+                None,
+                None,
+            ),
+            ApiError::RateLimitExceeded(msg, decision) => (
                 StatusCode::TOO_MANY_REQUESTS,
                 "rate_limit_exceeded",
                 msg,
                 None,
-                reset,
+                Some(format_reset_at(decision.reset_at)),
+            ),
+            ApiError::TooManyConcurrentRequests(msg) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "too_many_concurrent_requests",
+                msg,
+                None,
+                None,
+            ),
+            ApiError::ModelNotAllowed(msg, _) => {
+                (StatusCode::FORBIDDEN, "model_not_allowed", msg, None, None)
+            }
+            ApiError::UnknownFields(fields) => (
+                StatusCode::BAD_REQUEST,
+                "unknown_fields",
+                strict::message(&fields),
+                None,
+                None,
+            ),
+            ApiError::FeatureNotEnabled(e) => {
+                (e.status(), "feature_not_enabled", e.to_string(), None, None)
+            }
+            ApiError::WrongRegion { region, base_url } => (
+                StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+                "wrong_region",
+                match base_url {
+                    Some(url) => format!(
+                        "This organization's data must stay in region '{}'; send requests to {}",
+                        region, url
+                    ),
+                    None => format!(
+                        "This organization's data must stay in region '{}', which this deployment doesn't serve",
+                        region
+                    ),
+                },
+                None,
+                None,
+            ),
+            ApiError::ModelUnavailable(msg) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "model_unavailable",
+                msg,
+                None,
+                None,
             ),
-            ApiError::InternalError(msg) => (
+            ApiError::Overloaded { message, .. } => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "overloaded",
+                message,
+                None,
+                None,
+            ),
+            ApiError::InternalError(_) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "internal_error",
-                msg,
+                internal
+                    .take()
+                    .map(|(_, message)| message)
+                    .unwrap_or_default(),
                 None,
                 None,
             ),
@@ -460,82 +2010,203 @@ impl IntoResponse for ApiError {
             error: error_type.to_string(),
             message,
             max_tokens,
+            tokens,
             reset_at,
+            permitted_models,
+            estimated_wait_ms,
+            request_id,
+            unknown_fields,
+            region,
+            base_url,
+            code,
+            hint,
         };
 
-        (status, Json(error_response)).into_response()
+        (status, headers, error_response)
     }
 }
 
-/// Extractor for session authentication
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, headers, error_response) = self.into_parts();
+        let body = versions::error_body(versions::current(), &error_response);
+        (status, headers, Json(body)).into_response()
+    }
+}
+
+/// Bearer token from the `Authorization` header (shared by all extractors)
+fn bearer_token(headers: &HeaderMap) -> Result<&str, String> {
+    let auth_header = headers
+        .get("authorization")
+        .ok_or_else(|| "Authorization header is required".to_string())?;
+
+    let auth_str = auth_header
+        .to_str()
+        .map_err(|_| "Invalid authorization header".to_string())?;
+
+    match auth_str.split_once(' ') {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => Ok(token),
+        _ => Err("Authorization header must be 'Bearer <token>'".to_string()),
+    }
+}
+
+/// API key from the `Authorization` header, without the configured prefix,
+/// and whether it had the prefix. Tokens without it are only accepted while
+/// `allow_unprefixed` is set (see `key_prefix`).
+fn api_key_token<'a>(
+    headers: &'a HeaderMap,
+    prefix: &str,
+    allow_unprefixed: bool,
+) -> Result<(&'a str, bool), ApiError> {
+    let full_token = bearer_token(headers).map_err(ApiError::Unauthorized)?;
+    match full_token.strip_prefix(prefix) {
+        Some(token) => Ok((token, true)),
+        None if allow_unprefixed => Ok((full_token, false)),
+        None => Err(ApiError::MissingKeyPrefix(format!(
+            "API keys must start with '{}'; add the prefix to your key",
+            prefix
+        ))),
+    }
+}
+
+/// Extractor for API key authentication (embedding endpoints)
 #[async_trait]
-impl<S> FromRequestParts<S> for auth::session::SessionClaims
+impl<S> FromRequestParts<S> for auth::TokenClaims
 where
     S: Send + Sync,
 {
-    type Rejection = users::ApiError;
+    type Rejection = ApiError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        // Get authorization header
-        let auth_header = parts.headers.get("authorization").ok_or_else(|| {
-            users::ApiError::Unauthorized("Authorization header is required".to_string())
-        })?;
-
-        // Convert header value to string
-        let auth_str = auth_header.to_str().map_err(|_| {
-            users::ApiError::Unauthorized("Invalid authorization header".to_string())
-        })?;
-
-        // Extract Bearer token
-        let parts: Vec<&str> = auth_str.splitn(2, ' ').collect();
-        if parts.len() != 2 || parts[0].to_lowercase() != "bearer" {
-            return Err(users::ApiError::Unauthorized(
-                "Authorization header must be 'Bearer <token>'".to_string(),
+        let settings = config::get_settings();
+        // Service account tokens only work on the management API
+        if bearer_token(&parts.headers)
+            .is_ok_and(|token| token.starts_with(auth::SERVICE_ACCOUNT_TOKEN_PREFIX))
+        {
+            return Err(ApiError::Unauthorized(
+                "Service account tokens can't be used for embeddings; use an API key".to_string(),
             ));
         }
+        let (token, prefixed) = api_key_token(
+            &parts.headers,
+            &settings.api_key_prefix,
+            settings.allow_unprefixed_tokens,
+        )?;
+
+        // Validate token (opaque keys are looked up, CWT keys verified offline)
+        let claims = if auth::opaque::is_opaque_key(token) {
+            if !settings.opaque_api_keys {
+                return Err(ApiError::Unauthorized(
+                    "Opaque API keys are not enabled".to_string(),
+                ));
+            }
+            auth::opaque::validate(token).await
+        } else {
+            auth::get_validator().validate(token).await
+        }
+        .map_err(|e| match e.downcast_ref::<auth::TokenError>() {
+            Some(e) => ApiError::InvalidToken(e.clone()),
+            None => ApiError::Unauthorized(format!(
+                "Token validation failed: {}",
+                redact::redact(&e.to_string())
+            )),
+        })?;
+        // Served as the organization's trial tier while it has one
+        let claims = billing::trial::resolve(claims).await;
 
-        let token = parts[1];
-
-        // Verify session token
-        let claims = auth::session::verify_session_token(token)
-            .map_err(|e| users::ApiError::Unauthorized(format!("Invalid session token: {}", e)))?;
+        if !prefixed {
+            key_prefix::record_unprefixed(claims.org_id());
+        }
 
+        // Responses use the key's version unless the request names one
+        versions::pin(versions::ApiVersion::pinned(claims.api_version()));
         Ok(claims)
     }
 }
 
-/// Extractor for admin token authentication (protects registration/login endpoints)
+/// Extractor for session authentication
+#[cfg(feature = "control-plane")]
 #[async_trait]
-impl<S> FromRequestParts<S> for auth::AdminTokenClaims
+impl<S> FromRequestParts<S> for auth::session::SessionClaims
 where
     S: Send + Sync,
 {
-    type Rejection = users::ApiError;
+    type Rejection = error::ApiError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        // Get authorization header
-        let auth_header = parts.headers.get("authorization").ok_or_else(|| {
-            users::ApiError::Unauthorized("Authorization header is required".to_string())
+        let token = bearer_token(&parts.headers).map_err(error::ApiError::Unauthorized)?;
+
+        // Verify session token
+        let claims = auth::session::verify_session_token(token).map_err(|e| {
+            error::ApiError::Unauthorized(format!(
+                "Invalid session token: {}",
+                redact::redact(&e.to_string())
+            ))
         })?;
 
-        // Convert header value to string
-        let auth_str = auth_header.to_str().map_err(|_| {
-            users::ApiError::Unauthorized("Invalid authorization header".to_string())
+        Ok(auth::session::validate_org_context(claims).await)
+    }
+}
+
+/// Extractor for the management API: a session or a service account token
+#[cfg(feature = "control-plane")]
+#[async_trait]
+impl<S> FromRequestParts<S> for auth::service_account::Principal
+where
+    S: Send + Sync,
+{
+    type Rejection = error::ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let full_token = bearer_token(&parts.headers).map_err(error::ApiError::Unauthorized)?;
+
+        let Some(token) = full_token.strip_prefix(auth::SERVICE_ACCOUNT_TOKEN_PREFIX) else {
+            return auth::session::SessionClaims::from_request_parts(parts, state)
+                .await
+                .map(Self::User);
+        };
+
+        let keyring = auth::keyring::Keyring::configured()
+            .map_err(|e| error::ApiError::InternalError(format!("Invalid public key: {}", e)))?;
+        let token_data = auth::service_account::validate_service_account_token(
+            token,
+            keyring,
+            &auth::binding::TokenBinding::from_settings(),
+        )
+        .map_err(|e| {
+            error::ApiError::Unauthorized(format!(
+                "Invalid service account token: {}",
+                redact::redact(&e.to_string())
+            ))
         })?;
 
-        // Extract Bearer token
-        let token_parts: Vec<&str> = auth_str.splitn(2, ' ').collect();
-        if token_parts.len() != 2 || token_parts[0].to_lowercase() != "bearer" {
-            return Err(users::ApiError::Unauthorized(
-                "Authorization header must be 'Bearer <token>'".to_string(),
-            ));
-        }
+        let account = auth::service_account::resolve(token_data)
+            .await
+            .map_err(|e| {
+                error::ApiError::Unauthorized(format!(
+                    "Invalid service account token: {}",
+                    redact::redact(&e.to_string())
+                ))
+            })?;
 
-        let full_token = token_parts[1];
+        Ok(Self::ServiceAccount(account))
+    }
+}
+
+/// Extractor for admin token authentication (protects registration/login endpoints)
+#[async_trait]
+impl<S> FromRequestParts<S> for auth::AdminTokenClaims
+where
+    S: Send + Sync,
+{
+    type Rejection = error::ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let full_token = bearer_token(&parts.headers).map_err(error::ApiError::Unauthorized)?;
 
         // Check if token has admin_ prefix
         if !full_token.starts_with("admin_") {
-            return Err(users::ApiError::Unauthorized(
+            return Err(error::ApiError::Unauthorized(
                 "Invalid admin token format".to_string(),
             ));
         }
@@ -543,33 +2214,55 @@ where
         // Strip prefix and validate
         let token = &full_token[6..]; // Remove "admin_" prefix
 
-        // Get public key from settings
-        let settings = config::get_settings();
-        let public_key_bytes = hex::decode(&settings.token_public_key).map_err(|_| {
-            users::ApiError::InternalError("Failed to decode public key".to_string())
-        })?;
-        let verifying_key = ed25519_dalek::VerifyingKey::from_bytes(
-            &public_key_bytes[..]
-                .try_into()
-                .map_err(|_| users::ApiError::InternalError("Invalid public key".to_string()))?,
-        )
-        .map_err(|_| users::ApiError::InternalError("Invalid public key".to_string()))?;
+        // Verification keys from settings
+        let keyring = auth::keyring::Keyring::configured()
+            .map_err(|e| error::ApiError::InternalError(format!("Invalid public key: {}", e)))?;
 
         // Verify admin token
-        let token_data = auth::validate_admin_token(token, &verifying_key)
-            .map_err(|e| users::ApiError::Unauthorized(format!("Invalid admin token: {}", e)))?;
+        let token_data = auth::validate_admin_token(
+            token,
+            keyring,
+            &auth::binding::TokenBinding::from_settings(),
+            chrono::Duration::days(config::get_settings().admin_token_max_days),
+        )
+        .map_err(|e| {
+            error::ApiError::Unauthorized(format!(
+                "Invalid admin token: {}",
+                redact::redact(&e.to_string())
+            ))
+        })?;
+
+        // Revoked tokens are rejected; if that can't be checked, so is the token
+        let revoked = auth::admin::is_revoked(token_data.token_id)
+            .await
+            .map_err(|e| {
+                error::ApiError::InternalError(format!(
+                    "Failed to check admin token revocation: {}",
+                    e
+                ))
+            })?;
+        if revoked {
+            return Err(error::ApiError::Unauthorized(
+                "Admin token revoked".to_string(),
+            ));
+        }
 
         Ok(auth::AdminTokenClaims::new(token_data))
     }
 }
 
-/// OpenAPI documentation
+/// OpenAPI documentation of the endpoints in every build (see `openapi()`)
 #[derive(utoipa::OpenApi)]
 #[openapi(
     paths(
-        create_embedding_handler,
         health_handler,
+        readiness_handler,
+        component_readiness_handler,
         root_handler,
+        snippets_handler,
+        limits_handler,
+        models_handler,
+        versions::meta_versions_handler,
     ),
     components(
         schemas(
@@ -577,7 +2270,21 @@ where
             EmbedResponse,
             ErrorResponse,
             HealthResponse,
+            LoadedModel,
             BuildInfo,
+            ReadinessResponse,
+            ComponentStatus,
+            DependencyStatus,
+            SnippetsResponse,
+            LimitsResponse,
+            RateLimitWindow,
+            UsageForecast,
+            ProjectedUsage,
+            ModelsResponse,
+            ModelEntry,
+            versions::VersionsResponse,
+            versions::VersionEntry,
+            timings::Timings,
         )
     ),
     tags(
@@ -587,7 +2294,7 @@ where
     info(
         title = "Smally Embeddings API",
         version = "0.1.0",
-        description = "Fast, production-ready text embedding API using sentence transformers",
+        description = "Fast, production-ready text embedding API using sentence transformers.\n\nIDs are returned as 32-character lowercase hex without dashes (e.g. `550e8400e29b41d4a716446655440000`). Path parameters accept both the dashless and the hyphenated form.",
         contact(
             name = "API Support",
             url = "https://github.com/yourusername/smally"
@@ -604,6 +2311,41 @@ where
 )]
 pub struct ApiDoc;
 
+/// OpenAPI documentation of `/v1/embed`, `/v1/embed/batch`,
+/// `/v1/estimate` and `/v1/demo/embed`
+#[cfg(feature = "inference")]
+#[derive(utoipa::OpenApi)]
+#[openapi(
+    paths(
+        create_embedding_handler,
+        batch::batch_embed_handler,
+        estimate::estimate_handler,
+        demo::demo_embed_handler
+    ),
+    components(schemas(
+        batch::BatchEmbedRequest,
+        batch::BatchEmbedResponse,
+        batch::BatchEmbedding,
+        demo::DemoEmbedRequest,
+        demo::DemoEmbedResponse,
+        estimate::EstimateRequest,
+        billing::estimate::Estimate,
+        billing::estimate::LengthBucket,
+        billing::estimate::Throughput,
+    ))
+)]
+struct EmbedDoc;
+
+/// OpenAPI documentation of the endpoints this build serves
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+
+    let doc = ApiDoc::openapi();
+    #[cfg(feature = "inference")]
+    let doc = doc.merge_from(EmbedDoc::openapi());
+    doc
+}
+
 /// Security scheme for Bearer token authentication and code samples
 struct SecurityAddon;
 
@@ -623,3 +2365,449 @@ impl utoipa::Modify for SecurityAddon {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+
+    async fn extract(auth: Option<HeaderValue>) -> Result<auth::TokenClaims, ApiError> {
+        let mut request = Request::builder().uri("/v1/embed");
+        if let Some(value) = auth {
+            request = request.header("authorization", value);
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+        auth::TokenClaims::from_request_parts(&mut parts, &()).await
+    }
+
+    fn unauthorized_message(result: Result<auth::TokenClaims, ApiError>) -> String {
+        match result {
+            Err(ApiError::Unauthorized(message)) => message,
+            Err(other) => panic!("expected Unauthorized, got {:?}", other),
+            Ok(_) => panic!("expected Unauthorized, got claims"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_claims_missing_header() {
+        let message = unauthorized_message(extract(None).await);
+        assert_eq!(message, "Authorization header is required");
+    }
+
+    #[tokio::test]
+    async fn test_token_claims_wrong_scheme() {
+        for value in ["Basic dXNlcjpwYXNz", "sk_abc", "Bearer"] {
+            let message =
+                unauthorized_message(extract(Some(HeaderValue::from_static(value))).await);
+            assert_eq!(message, "Authorization header must be 'Bearer <token>'");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_token_claims_non_utf8_header() {
+        let value = HeaderValue::from_bytes(b"Bearer \xff\xfe").unwrap();
+        let message = unauthorized_message(extract(Some(value)).await);
+        assert_eq!(message, "Invalid authorization header");
+    }
+
+    #[test]
+    fn test_invalid_token_keeps_the_error_type() {
+        let (status, _, body) =
+            ApiError::InvalidToken(auth::TokenError::Truncated { missing_chars: 12 }).into_parts();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body.error, "invalid_api_key");
+        assert_eq!(body.code.as_deref(), Some("token_truncated"));
+        assert_eq!(
+            body.hint.as_deref(),
+            Some("token appears truncated: expected ~12 more characters")
+        );
+
+        let (_, _, body) = ApiError::InvalidToken(auth::TokenError::BadSignature).into_parts();
+        assert_eq!(body.code.as_deref(), Some("token_bad_signature"));
+        assert!(serde_json::to_value(&body).unwrap().get("hint").is_none());
+    }
+
+    #[test]
+    fn test_dry_run_defaults_off_and_is_only_serialized_when_set() {
+        let req: EmbedRequest = serde_json::from_str(r#"{"text": "hi"}"#).unwrap();
+        assert!(!req.dry_run);
+        assert!(!serde_json::to_string(&req).unwrap().contains("dry_run"));
+
+        let response = EmbedResponse {
+            embedding: Embedding::default(),
+            model: "all-MiniLM-L6-v2".to_string(),
+            tokens: 3,
+            truncated: false,
+            original_tokens: None,
+            cached: false,
+            content_hash: "0".repeat(16),
+            latency_ms: 0.0,
+            dry_run: true,
+            detected_language: None,
+            language_confidence: None,
+            warnings: Vec::new(),
+            timings: None,
+        };
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["dry_run"], true);
+        assert_eq!(json["embedding"], serde_json::json!([]));
+        assert!(json.get("detected_language").is_none());
+        assert!(json.get("warnings").is_none());
+        assert!(json.get("truncated").is_none());
+        assert!(json.get("original_tokens").is_none());
+    }
+
+    /// Random unit vectors of the model's dimension
+    fn unit_vectors() -> Vec<Vec<f32>> {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(7);
+        (0..100)
+            .map(|_| {
+                let mut v: Vec<f32> = (0..384).map(|_| rng.gen_range(-1.0..1.0)).collect();
+                inference::math::l2_normalize(&mut v);
+                v
+            })
+            .collect()
+    }
+
+    fn rounded(values: &[f32], precision: Option<u8>) -> (usize, Vec<f32>) {
+        let json = serde_json::to_string(&Embedding::new(values.to_vec(), precision)).unwrap();
+        (json.len(), serde_json::from_str(&json).unwrap())
+    }
+
+    #[test]
+    fn test_precision_shrinks_payload() {
+        for v in unit_vectors() {
+            let (full, _) = rounded(&v, None);
+            let (five, values) = rounded(&v, Some(5));
+            assert!(five * 100 < full * 80, "{} vs {} bytes", five, full);
+            assert!(values
+                .iter()
+                .all(|x| x.to_string().split('.').nth(1).map_or(0, str::len) <= 5));
+        }
+    }
+
+    #[test]
+    fn test_precision_keeps_cosine_similarity() {
+        for v in unit_vectors() {
+            for precision in 5..=9 {
+                let (_, values) = rounded(&v, Some(precision));
+                let similarity = inference::math::cosine(&v, &values);
+                assert!(similarity > 0.99999, "{} at {}", similarity, precision);
+            }
+            // Full precision round-trips exactly
+            assert_eq!(rounded(&v, None).1, v);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "inference")]
+    fn test_precision_parsing() {
+        let req: EmbedRequest = serde_json::from_str(r#"{"text": "hi"}"#).unwrap();
+        assert_eq!(req.precision, None);
+        let req: EmbedRequest = serde_json::from_str(r#"{"text": "hi", "precision": 5}"#).unwrap();
+        assert!(PRECISION_RANGE.contains(&req.precision.unwrap()));
+        assert!(!PRECISION_RANGE.contains(&0));
+        assert!(!PRECISION_RANGE.contains(&10));
+        // Negative or non-integer values don't parse
+        assert!(
+            serde_json::from_str::<EmbedRequest>(r#"{"text": "hi", "precision": -1}"#).is_err()
+        );
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "inference")]
+    async fn test_token_limit() {
+        // Exactly at the limit: embedded as is
+        assert!(matches!(check_token_limit(128, 128, false), Ok(None)));
+        assert!(matches!(check_token_limit(128, 128, true), Ok(None)));
+
+        // One over: rejected with the exact count
+        let err = check_token_limit(129, 128, false).unwrap_err();
+        let response = err.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "text_too_long");
+        assert_eq!(json["max_tokens"], 128);
+        assert_eq!(json["tokens"], 129);
+
+        // Truncation requested: accepted, original count reported
+        assert!(matches!(check_token_limit(129, 128, true), Ok(Some(129))));
+        let req: EmbedRequest =
+            serde_json::from_str(r#"{"text": "hi", "truncate": true}"#).unwrap();
+        assert!(req.truncate);
+    }
+
+    #[test]
+    #[cfg(feature = "inference")]
+    fn test_language_metadata_can_be_skipped() {
+        let mut req: EmbedRequest =
+            serde_json::from_str(r#"{"text": "今天天气很好，我们去公园散步吧。"}"#).unwrap();
+        req.skip_language_detection = true;
+
+        let (detection, warnings) = language_metadata(&req);
+        assert!(detection.is_none());
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "inference")]
+    fn test_request_origin() {
+        let ip = "203.0.113.7".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            axum::http::header::USER_AGENT,
+            HeaderValue::from_str(&"a".repeat(600)).unwrap(),
+        );
+        let origin = RequestOrigin::new(ip_limit::ClientIp(Some(ip)), &headers);
+        assert_eq!(origin.client_ip, Some(ip));
+        assert_eq!(origin.user_agent.unwrap().len(), USER_AGENT_MAX_CHARS);
+
+        let origin = RequestOrigin::new(ip_limit::ClientIp(None), &HeaderMap::new());
+        assert!(origin.client_ip.is_none() && origin.user_agent.is_none());
+    }
+
+    fn decision(allowed: bool, window: billing::LimitWindow) -> billing::RateLimitDecision {
+        billing::RateLimitDecision {
+            allowed,
+            limit: 20000,
+            remaining: if allowed { 19999 } else { 0 },
+            reset_at: "2025-02-01T00:00:00+01:00".parse().unwrap(),
+            window,
+            cycle: None,
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_headers() {
+        // Always present on success, reset as a Unix timestamp whatever the
+        // timezone of the reset time
+        let headers = rate_limit_headers(&decision(true, billing::LimitWindow::Month));
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers["X-RateLimit-Limit"], "20000");
+        assert_eq!(headers["X-RateLimit-Remaining"], "19999");
+        assert_eq!(headers["X-RateLimit-Reset"], "1738364400");
+
+        // The monthly window also reports its billing cycle
+        let cycle = billing::cycle::BillingCycle::containing(
+            "2025-02-10T12:00:00Z".parse().unwrap(),
+            Some(20),
+        );
+        let headers = rate_limit_headers(&billing::RateLimitDecision {
+            cycle: Some(cycle),
+            ..decision(true, billing::LimitWindow::Month)
+        });
+        assert_eq!(headers["X-RateLimit-Cycle-Start"], "1737331200");
+        assert_eq!(headers["X-RateLimit-Cycle-End"], "1740009600");
+
+        assert!(rate_limit_headers(&decision(true, billing::LimitWindow::Unlimited)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_exceeded_has_headers_and_reset() {
+        let response = ApiError::RateLimitExceeded(
+            "Too many requests per minute".to_string(),
+            decision(false, billing::LimitWindow::Minute),
+        )
+        .into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["X-RateLimit-Remaining"], "0");
+        assert_eq!(response.headers()["X-RateLimit-Reset"], "1738364400");
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "rate_limit_exceeded");
+        assert_eq!(json["reset_at"], "2025-01-31T23:00:00Z");
+    }
+
+    #[tokio::test]
+    async fn test_too_many_concurrent_requests_error() {
+        let response = ApiError::TooManyConcurrentRequests("max 2".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "too_many_concurrent_requests");
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "inference")]
+    async fn test_overloaded_error() {
+        let request_id = uuid::Uuid::now_v7();
+        let overloaded = inference::dispatcher::Overloaded {
+            queue_depth: 64,
+            estimated_wait_ms: 1500.0,
+        };
+        let response = shed(request_id, overloaded).into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()["x-request-id"], request_id.to_string());
+
+        // Jittered around the estimate, and consistent with Retry-After
+        let retry_after: u64 = response.headers()["retry-after"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "overloaded");
+        assert_eq!(json["request_id"], request_id.to_string());
+        let wait_ms = json["estimated_wait_ms"].as_u64().unwrap();
+        assert!((1350..=1650).contains(&wait_ms), "{}", wait_ms);
+        assert_eq!(retry_after, 2);
+
+        // The retry is measured against that wait
+        let ratio = inference::dispatcher::dispatcher()
+            .retry_succeeded(request_id, Instant::now())
+            .unwrap();
+        assert!(ratio < 1.0);
+    }
+
+    #[test]
+    #[cfg(feature = "inference")]
+    fn test_cache_headers() {
+        let mut headers = HeaderMap::new();
+        insert_cache_headers(&mut headers, None, Some(12.345), 7);
+        assert_eq!(headers["X-Cache"], "miss");
+        assert_eq!(headers["X-Inference-Ms"], "12.35");
+        assert_eq!(headers["X-Tokens"], "7");
+
+        let mut headers = HeaderMap::new();
+        insert_cache_headers(&mut headers, Some(cache::CacheLevel::L2), None, 7);
+        assert_eq!(headers["X-Cache"], "l2");
+        assert!(headers.get("X-Inference-Ms").is_none());
+    }
+
+    #[test]
+    fn test_api_key_token_prefix_handling() {
+        let mut headers = HeaderMap::new();
+
+        headers.insert(
+            "authorization",
+            HeaderValue::from_static("Bearer sk_abc123"),
+        );
+        assert_eq!(
+            api_key_token(&headers, "sk_", false).unwrap(),
+            ("abc123", true)
+        );
+
+        // Scheme is case-insensitive
+        headers.insert(
+            "authorization",
+            HeaderValue::from_static("bearer sk_abc123"),
+        );
+        assert_eq!(
+            api_key_token(&headers, "sk_", false).unwrap(),
+            ("abc123", true)
+        );
+
+        // Missing prefix: passed through unchanged while allowed
+        headers.insert("authorization", HeaderValue::from_static("Bearer abc123"));
+        assert_eq!(
+            api_key_token(&headers, "sk_", true).unwrap(),
+            ("abc123", false)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unprefixed_token_rejected_when_disallowed() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer abc123"));
+
+        let response = api_key_token(&headers, "sk_", false)
+            .unwrap_err()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "missing_key_prefix");
+        assert!(json["message"].as_str().unwrap().contains("'sk_'"));
+    }
+
+    /// Whether `text` quotes a secret: a URL password or a prefixed token
+    fn contains_secret(text: &str, api_key_prefix: &str) -> bool {
+        let url_password = text.split("://").skip(1).any(|rest| {
+            let authority = rest.split(['/', ' ', '"']).next().unwrap_or("");
+            authority
+                .rsplit_once('@')
+                .and_then(|(userinfo, _)| userinfo.split_once(':'))
+                .is_some_and(|(_, password)| password != redact::MASK)
+        });
+        let token = text.match_indices(api_key_prefix).any(|(i, _)| {
+            text[i + api_key_prefix.len()..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='))
+                .count()
+                >= 8
+        });
+        url_password || token
+    }
+
+    #[tokio::test]
+    async fn test_internal_errors_quote_no_secrets() {
+        let prefix = config::get_settings().api_key_prefix.clone();
+        let detail = format!(
+            "Database error: error connecting to postgres://smally:hunter2@db/smally while checking {}0oRDoQEnoFhMpgF4JDAxOTRmNGQ4LWE2YjEtNzAwMC04MDAw",
+            prefix
+        );
+        assert!(contains_secret(&detail, &prefix));
+
+        let responses = [
+            ApiError::InternalError(detail.clone()).into_response(),
+            error::ApiError::InternalError(detail.clone()).into_response(),
+        ];
+        for response in responses {
+            assert_eq!(response.status(), 500);
+            let request_id = response.headers()["x-request-id"]
+                .to_str()
+                .unwrap()
+                .to_string();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body = String::from_utf8(body.to_vec()).unwrap();
+            assert!(!contains_secret(&body, &prefix), "{}", body);
+            assert!(!body.contains("hunter2"));
+            assert!(body.contains(&request_id));
+        }
+
+        let message = error::ApiError::InternalError(detail.clone()).into_client_message();
+        assert!(!contains_secret(&message, &prefix), "{}", message);
+
+        // What is logged keeps the context but not the secrets
+        let logged = redact::redact(&detail);
+        assert!(logged.starts_with("Database error: error connecting to postgres://smally:"));
+        assert!(!contains_secret(&logged, &prefix), "{}", logged);
+    }
+
+    /// Every header parameter of `/v1/embed` makes it into the spec
+    #[cfg(feature = "inference")]
+    #[test]
+    fn test_embed_header_params_in_spec() {
+        let spec = serde_json::to_value(openapi()).unwrap();
+        let params = spec["paths"]["/v1/embed"]["post"]["parameters"]
+            .as_array()
+            .unwrap();
+        let headers: Vec<&str> = params
+            .iter()
+            .filter(|param| param["in"] == "header")
+            .filter_map(|param| param["name"].as_str())
+            .collect();
+        assert_eq!(
+            headers,
+            ["X-Smally-Version", "X-Smally-Strict", "X-Smally-Retry-Of"]
+        );
+    }
+}
@@ -1,64 +1,58 @@
 use anyhow::Result;
 use axum::{
-    extract::Path,
+    extract::{Path, Query},
     http::StatusCode,
     response::{IntoResponse, Response},
     Json,
 };
-use chrono::Utc;
 use serde_json::json;
 
+use crate::auth::service_account::Principal;
 use crate::auth::session::SessionClaims;
 use crate::database;
 use crate::models::{
-    CreateOrganizationRequest, InviteMemberRequest, Organization, OrganizationResponse,
-    OrganizationRole, TierType,
+    CreateOrganizationRequest, InviteMemberRequest, OrganizationResponse, OrganizationRole,
+    UpdateBillingAnchorRequest, UpdateCachePolicyRequest, UpdateIpStorageRequest,
+    UpdateModelSettingsRequest, UpdateRegionRequest,
 };
+use crate::services::{self, activity::ActivityQuery, organizations::Membership};
 use crate::uuid_dashless::DashlessUuid;
 
-use super::users::ApiError;
+use super::error::ApiError;
+use super::strict::StrictJson;
+
+impl From<Membership> for OrganizationResponse {
+    fn from(org: Membership) -> Self {
+        let trial = org.active_trial(chrono::Utc::now());
+        OrganizationResponse {
+            id: org.id,
+            name: org.name,
+            tier: org.tier,
+            role: org.role,
+            is_active: org.is_active,
+            cache_policy: org.cache_policy,
+            allowed_models: org.allowed_models,
+            default_model: org.default_model,
+            billing_anchor_day: org.billing_anchor_day,
+            store_client_ip: org.store_client_ip,
+            region: org.region,
+            trial,
+            created_at: org.created_at,
+        }
+    }
+}
 
 /// Create a new organization
 pub async fn create_organization_handler(
     claims: SessionClaims,
-    Json(payload): Json<CreateOrganizationRequest>,
+    StrictJson(payload): StrictJson<CreateOrganizationRequest>,
 ) -> Result<Response, ApiError> {
-    let pool = database::get_db();
-    let user_id: i64 = claims
+    let user_id: uuid::Uuid = claims
         .sub
         .parse()
         .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?;
 
-    // Create organization
-    let tier = payload.tier.unwrap_or(TierType::Free);
-
-    let org = sqlx::query_as::<_, Organization>(
-        "INSERT INTO organizations (name, owner_id, tier, is_active, created_at, updated_at)
-         VALUES ($1, $2, $3, $4, $5, $6)
-         RETURNING *",
-    )
-    .bind(&payload.name)
-    .bind(user_id)
-    .bind(tier)
-    .bind(true)
-    .bind(Utc::now().naive_utc())
-    .bind(Utc::now().naive_utc())
-    .fetch_one(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Failed to create organization: {}", e)))?;
-
-    // Add creator as owner
-    sqlx::query(
-        "INSERT INTO organization_members (organization_id, user_id, role, created_at)
-         VALUES ($1, $2, $3, $4)",
-    )
-    .bind(org.id)
-    .bind(user_id)
-    .bind("owner")
-    .bind(Utc::now().naive_utc())
-    .execute(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Failed to add organization member: {}", e)))?;
+    let org = services::organizations::create(database::get_db(), user_id, &payload).await?;
 
     let response = OrganizationResponse {
         id: org.id,
@@ -66,6 +60,13 @@ pub async fn create_organization_handler(
         tier: org.tier,
         role: OrganizationRole::Owner,
         is_active: org.is_active,
+        cache_policy: org.cache_policy,
+        allowed_models: org.allowed_models,
+        default_model: org.default_model,
+        billing_anchor_day: org.billing_anchor_day,
+        store_client_ip: org.store_client_ip,
+        region: org.region,
+        trial: None,
         created_at: org.created_at,
     };
 
@@ -74,173 +75,172 @@ pub async fn create_organization_handler(
 
 /// List user's organizations
 pub async fn list_organizations_handler(claims: SessionClaims) -> Result<Response, ApiError> {
-    let pool = database::get_db();
     let user_id: uuid::Uuid = claims
         .sub
         .parse()
         .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?;
 
-    #[derive(sqlx::FromRow)]
-    struct OrgWithRole {
-        id: uuid::Uuid,
-        name: String,
-        tier: TierType,
-        is_active: bool,
-        created_at: chrono::NaiveDateTime,
-        role: OrganizationRole,
-    }
-
-    let orgs = sqlx::query_as::<_, OrgWithRole>(
-        "SELECT o.id, o.name, o.tier, o.is_active, o.created_at, om.role
-         FROM organizations o
-         INNER JOIN organization_members om ON o.id = om.organization_id
-         WHERE om.user_id = $1
-         ORDER BY o.created_at DESC",
-    )
-    .bind(user_id)
-    .fetch_all(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
-
-    let responses: Vec<OrganizationResponse> = orgs
-        .into_iter()
-        .map(|org| OrganizationResponse {
-            id: org.id,
-            name: org.name,
-            tier: org.tier,
-            role: org.role,
-            is_active: org.is_active,
-            created_at: org.created_at,
-        })
-        .collect();
+    let orgs = services::organizations::list_for_user(database::get_db(), user_id).await?;
+    let responses: Vec<OrganizationResponse> = orgs.into_iter().map(Into::into).collect();
 
     Ok((StatusCode::OK, Json(responses)).into_response())
 }
 
 /// Get organization by ID
 pub async fn get_organization_handler(
-    claims: SessionClaims,
+    principal: Principal,
     Path(org_id): Path<DashlessUuid>,
 ) -> Result<Response, ApiError> {
-    let pool = database::get_db();
-    let user_id: uuid::Uuid = claims
-        .sub
-        .parse()
-        .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?;
-    let org_id = org_id.into_inner();
-
-    #[derive(sqlx::FromRow)]
-    struct OrgWithRole {
-        id: uuid::Uuid,
-        name: String,
-        tier: TierType,
-        is_active: bool,
-        created_at: chrono::NaiveDateTime,
-        role: OrganizationRole,
-    }
+    let actor = principal.actor()?;
 
-    let org = sqlx::query_as::<_, OrgWithRole>(
-        "SELECT o.id, o.name, o.tier, o.is_active, o.created_at, om.role
-         FROM organizations o
-         INNER JOIN organization_members om ON o.id = om.organization_id
-         WHERE o.id = $1 AND om.user_id = $2",
-    )
-    .bind(org_id)
-    .bind(user_id)
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
-    .ok_or_else(|| ApiError::Unauthorized("Organization not found or access denied".to_string()))?;
+    let org =
+        services::organizations::membership(database::get_db(), org_id.into_inner(), actor).await?;
 
-    let response = OrganizationResponse {
-        id: org.id,
-        name: org.name,
-        tier: org.tier,
-        role: org.role,
-        is_active: org.is_active,
-        created_at: org.created_at,
-    };
-
-    Ok((StatusCode::OK, Json(response)).into_response())
+    Ok((StatusCode::OK, Json(OrganizationResponse::from(org))).into_response())
 }
 
 /// Invite member to organization
 pub async fn invite_member_handler(
-    claims: SessionClaims,
-    Path(org_id): Path<i64>,
-    Json(payload): Json<InviteMemberRequest>,
+    principal: Principal,
+    Path(org_id): Path<DashlessUuid>,
+    StrictJson(payload): StrictJson<InviteMemberRequest>,
 ) -> Result<Response, ApiError> {
-    let pool = database::get_db();
-    let user_id: i64 = claims
-        .sub
-        .parse()
-        .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?;
+    let actor = principal.actor()?;
 
-    // Check if requester is owner or admin
-    let member_role = sqlx::query_scalar::<_, OrganizationRole>(
-        "SELECT role FROM organization_members WHERE organization_id = $1 AND user_id = $2",
+    services::organizations::invite(database::get_db(), org_id.into_inner(), actor, &payload)
+        .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({ "message": "Member invited successfully" })),
     )
-    .bind(org_id)
-    .bind(user_id)
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
-    .ok_or_else(|| {
-        ApiError::Unauthorized("You are not a member of this organization".to_string())
-    })?;
-
-    if member_role != OrganizationRole::Owner && member_role != OrganizationRole::Admin {
-        return Err(ApiError::Unauthorized(
-            "Only owners and admins can invite members".to_string(),
-        ));
-    }
+        .into_response())
+}
 
-    // Find user by email
-    let invited_user = sqlx::query_scalar::<_, i64>("SELECT id FROM users WHERE email = $1")
-        .bind(&payload.email)
-        .fetch_optional(pool)
-        .await
-        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
-        .ok_or_else(|| ApiError::BadRequest("User not found".to_string()))?;
+/// Recent activity of a member: audit events (paginated, `from`/`to`
+/// days), keys they created and their last dashboard login. Members see
+/// their own; owners and admins anyone's.
+pub async fn member_activity_handler(
+    principal: Principal,
+    Path((org_id, user_id)): Path<(DashlessUuid, DashlessUuid)>,
+    Query(query): Query<ActivityQuery>,
+) -> Result<Response, ApiError> {
+    let actor = principal.actor()?;
+
+    let activity = services::activity::member_activity(
+        database::get_db(),
+        org_id.into_inner(),
+        actor,
+        user_id.into_inner(),
+        &query,
+    )
+    .await?;
 
-    // Check if already a member
-    let existing = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM organization_members WHERE organization_id = $1 AND user_id = $2",
+    Ok((StatusCode::OK, Json(activity)).into_response())
+}
+
+/// Change the organization's embedding cache policy (owner or admin)
+pub async fn update_cache_policy_handler(
+    principal: Principal,
+    Path(org_id): Path<DashlessUuid>,
+    StrictJson(payload): StrictJson<UpdateCachePolicyRequest>,
+) -> Result<Response, ApiError> {
+    let actor = principal.actor()?;
+
+    let org = services::organizations::set_cache_policy(
+        database::get_db(),
+        org_id.into_inner(),
+        actor,
+        payload.cache_policy,
     )
-    .bind(org_id)
-    .bind(invited_user)
-    .fetch_one(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
-
-    if existing > 0 {
-        return Err(ApiError::BadRequest("User is already a member".to_string()));
-    }
+    .await?;
 
-    // Add member
-    sqlx::query(
-        "INSERT INTO organization_members (organization_id, user_id, role, created_at)
-         VALUES ($1, $2, $3, $4)",
+    Ok((StatusCode::OK, Json(OrganizationResponse::from(org))).into_response())
+}
+
+/// Restrict the organization's models and set its default (owner or admin)
+pub async fn update_model_settings_handler(
+    principal: Principal,
+    Path(org_id): Path<DashlessUuid>,
+    StrictJson(payload): StrictJson<UpdateModelSettingsRequest>,
+) -> Result<Response, ApiError> {
+    let actor = principal.actor()?;
+
+    let org = services::organizations::set_model_settings(
+        database::get_db(),
+        org_id.into_inner(),
+        actor,
+        payload.allowed_models,
+        payload.default_model,
     )
-    .bind(org_id)
-    .bind(invited_user)
-    .bind(payload.role)
-    .bind(Utc::now().naive_utc())
-    .execute(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Failed to add member: {}", e)))?;
+    .await?;
 
-    Ok((
-        StatusCode::CREATED,
-        Json(json!({ "message": "Member invited successfully" })),
+    Ok((StatusCode::OK, Json(OrganizationResponse::from(org))).into_response())
+}
+
+/// Anchor the organization's billing cycle to a day of the month (owner or
+/// admin)
+pub async fn update_billing_anchor_handler(
+    principal: Principal,
+    Path(org_id): Path<DashlessUuid>,
+    StrictJson(payload): StrictJson<UpdateBillingAnchorRequest>,
+) -> Result<Response, ApiError> {
+    let actor = principal.actor()?;
+
+    let org = services::organizations::set_billing_anchor(
+        database::get_db(),
+        org_id.into_inner(),
+        actor,
+        payload.billing_anchor_day,
     )
-        .into_response())
+    .await?;
+
+    Ok((StatusCode::OK, Json(OrganizationResponse::from(org))).into_response())
+}
+
+/// Keep or stop keeping client IPs in the request log (owner or admin)
+pub async fn update_ip_storage_handler(
+    principal: Principal,
+    Path(org_id): Path<DashlessUuid>,
+    StrictJson(payload): StrictJson<UpdateIpStorageRequest>,
+) -> Result<Response, ApiError> {
+    let actor = principal.actor()?;
+
+    let org = services::organizations::set_ip_storage(
+        database::get_db(),
+        org_id.into_inner(),
+        actor,
+        payload.store_client_ip,
+    )
+    .await?;
+
+    Ok((StatusCode::OK, Json(OrganizationResponse::from(org))).into_response())
+}
+
+/// Pin the organization to a region, or `any` (owner or admin)
+pub async fn update_region_handler(
+    principal: Principal,
+    Path(org_id): Path<DashlessUuid>,
+    StrictJson(payload): StrictJson<UpdateRegionRequest>,
+) -> Result<Response, ApiError> {
+    let actor = principal.actor()?;
+
+    let org = services::organizations::set_region(
+        database::get_db(),
+        org_id.into_inner(),
+        actor,
+        &payload.region,
+    )
+    .await?;
+
+    Ok((StatusCode::OK, Json(OrganizationResponse::from(org))).into_response())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::helpers::{cleanup_db, create_test_user, setup};
+    use crate::test_utils::factory::{self, TestUser};
+    use crate::test_utils::helpers::{cleanup_db, setup};
     use axum::{
         body::Body,
         http::{Request, StatusCode},
@@ -268,6 +268,22 @@ mod tests {
                 "/organizations/:org_id/members",
                 axum::routing::post(invite_member_handler),
             )
+            .route(
+                "/organizations/:org_id/cache-policy",
+                axum::routing::put(update_cache_policy_handler),
+            )
+            .route(
+                "/organizations/:org_id/models",
+                axum::routing::put(update_model_settings_handler),
+            )
+            .route(
+                "/organizations/:org_id/billing-anchor",
+                axum::routing::put(update_billing_anchor_handler),
+            )
+            .route(
+                "/organizations/:org_id/region",
+                axum::routing::put(update_region_handler),
+            )
     }
 
     #[tokio::test]
@@ -276,7 +292,7 @@ mod tests {
         setup().await;
         cleanup_db().await;
 
-        let (_user_id, token, _org_id) = create_test_user("test@example.com", "password123").await;
+        let token = factory::user("test@example.com").await.session_token;
 
         let app = app();
 
@@ -317,7 +333,7 @@ mod tests {
         setup().await;
         cleanup_db().await;
 
-        let (_user_id, token, _org_id) = create_test_user("test@example.com", "password123").await;
+        let token = factory::user("test@example.com").await.session_token;
 
         let app = app();
 
@@ -353,7 +369,11 @@ mod tests {
         setup().await;
         cleanup_db().await;
 
-        let (_user_id, token, org_id) = create_test_user("test@example.com", "password123").await;
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
 
         let app = app();
 
@@ -361,7 +381,7 @@ mod tests {
             .oneshot(
                 Request::builder()
                     .method("GET")
-                    .uri(&format!("/organizations/{}", org_id))
+                    .uri(format!("/organizations/{}", org_id))
                     .header("authorization", format!("Bearer {}", token))
                     .body(Body::empty())
                     .unwrap(),
@@ -382,16 +402,208 @@ mod tests {
         cleanup_db().await;
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn test_update_cache_policy() {
+        setup().await;
+        cleanup_db().await;
+
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
+
+        let put = |policy: &str| {
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/organizations/{}/cache-policy", org_id))
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::to_vec(&json!({ "cache_policy": policy })).unwrap(),
+                ))
+                .unwrap()
+        };
+
+        let response = app().oneshot(put("disabled")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let org: OrganizationResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(org.cache_policy, crate::models::CachePolicy::Disabled);
+
+        let response = app().oneshot(put("everyone")).await.unwrap();
+        assert!(response.status().is_client_error());
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_update_region() {
+        setup().await;
+        cleanup_db().await;
+
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
+
+        let put = |region: &str| {
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/organizations/{}/region", org_id))
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::to_vec(&json!({ "region": region })).unwrap(),
+                ))
+                .unwrap()
+        };
+
+        // Only regions of the deployment (none configured in tests)
+        let response = app().oneshot(put("mars")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app().oneshot(put("any")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let org: OrganizationResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(org.region, "any");
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_update_model_settings() {
+        setup().await;
+        cleanup_db().await;
+
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
+        let model = crate::inference::catalog::available_ids().remove(0);
+
+        let put = |payload: serde_json::Value| {
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/organizations/{}/models", org_id))
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&payload).unwrap()))
+                .unwrap()
+        };
+
+        let response = app()
+            .oneshot(put(
+                json!({ "allowed_models": [model], "default_model": model }),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let org: OrganizationResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(org.allowed_models, Some(vec![model.clone()]));
+        assert_eq!(org.default_model, Some(model));
+
+        // Unknown models and empty lists are rejected
+        for payload in [
+            json!({ "allowed_models": ["no-such-model"] }),
+            json!({ "allowed_models": [] }),
+            json!({ "default_model": "no-such-model" }),
+        ] {
+            let response = app().oneshot(put(payload)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_update_billing_anchor() {
+        setup().await;
+        cleanup_db().await;
+
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
+
+        let put = |payload: serde_json::Value| {
+            Request::builder()
+                .method("PUT")
+                .uri(format!("/organizations/{}/billing-anchor", org_id))
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(serde_json::to_vec(&payload).unwrap()))
+                .unwrap()
+        };
+
+        let response = app()
+            .oneshot(put(json!({ "billing_anchor_day": 31 })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let org: OrganizationResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(org.billing_anchor_day, Some(31));
+        assert_eq!(crate::billing::cycle::org_anchor(org_id).await, Some(31));
+
+        for day in [0, 32, -1] {
+            let response = app()
+                .oneshot(put(json!({ "billing_anchor_day": day })))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+
+        // The anchor can't move again within the cooldown
+        let response = app().oneshot(put(json!({}))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(crate::billing::cycle::org_anchor(org_id).await, Some(31));
+
+        // Once it has passed, omitting the day goes back to calendar months
+        sqlx::query(
+            "UPDATE organizations SET billing_anchor_changed_at = billing_anchor_changed_at - INTERVAL '31 days' WHERE id = $1",
+        )
+        .bind(org_id)
+        .execute(database::get_db())
+        .await
+        .unwrap();
+        let response = app().oneshot(put(json!({}))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(crate::billing::cycle::org_anchor(org_id).await, None);
+
+        cleanup_db().await;
+    }
+
     #[tokio::test]
     #[serial]
     async fn test_invite_member() {
         setup().await;
         cleanup_db().await;
 
-        let (_user_id1, token1, org_id) =
-            create_test_user("owner@example.com", "password123").await;
-        let (_user_id2, _token2, _org_id2) =
-            create_test_user("member@example.com", "password123").await;
+        let TestUser {
+            session_token: token1,
+            org_id,
+            ..
+        } = factory::user("owner@example.com").await;
+        factory::user("member@example.com").await;
 
         let app = app();
 
@@ -404,7 +616,7 @@ mod tests {
             .oneshot(
                 Request::builder()
                     .method("POST")
-                    .uri(&format!("/organizations/{}/members", org_id))
+                    .uri(format!("/organizations/{}/members", org_id))
                     .header("authorization", format!("Bearer {}", token1))
                     .header("content-type", "application/json")
                     .body(Body::from(serde_json::to_vec(&payload).unwrap()))
@@ -417,4 +629,41 @@ mod tests {
 
         cleanup_db().await;
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_org_id_accepts_both_forms_and_returns_dashless() {
+        setup().await;
+        cleanup_db().await;
+
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
+
+        for path_id in [org_id.simple().to_string(), org_id.hyphenated().to_string()] {
+            let response = app()
+                .oneshot(
+                    Request::builder()
+                        .method("GET")
+                        .uri(format!("/organizations/{}", path_id))
+                        .header("authorization", format!("Bearer {}", token))
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["id"], org_id.simple().to_string());
+        }
+
+        cleanup_db().await;
+    }
 }
@@ -0,0 +1,184 @@
+//! Request log of an organization: recent requests with where they came
+//! from (client IP and User-Agent), for members and for admins
+//! investigating abuse
+
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::auth::service_account::Principal;
+use crate::auth::AdminTokenClaims;
+use crate::database::{self, ApiRequestLogRepo};
+use crate::services;
+use crate::uuid_dashless::DashlessUuid;
+
+use super::error::ApiError;
+
+/// Requests returned when `limit` is omitted
+const DEFAULT_LIMIT: i64 = 50;
+/// Most requests returned at once
+const MAX_LIMIT: i64 = 500;
+
+/// Query parameters of the request log endpoints
+#[derive(Debug, Deserialize)]
+pub struct RequestsQuery {
+    /// Only requests of this key (its `key_id`)
+    pub key_id: Option<DashlessUuid>,
+    /// Number of requests, newest first (default 50, at most 500)
+    pub limit: Option<i64>,
+}
+
+/// Recent requests of an organization (members)
+pub async fn list_requests_handler(
+    principal: Principal,
+    Path(org_id): Path<DashlessUuid>,
+    Query(query): Query<RequestsQuery>,
+) -> Result<Response, ApiError> {
+    let org_id = org_id.into_inner();
+    services::organizations::membership(database::get_db(), org_id, principal.actor()?).await?;
+
+    recent_requests(org_id, query).await
+}
+
+/// Recent requests of any organization (admin token required)
+pub async fn admin_list_requests_handler(
+    _admin_token: AdminTokenClaims,
+    Path(org_id): Path<DashlessUuid>,
+    Query(query): Query<RequestsQuery>,
+) -> Result<Response, ApiError> {
+    recent_requests(org_id.into_inner(), query).await
+}
+
+async fn recent_requests(org_id: Uuid, query: RequestsQuery) -> Result<Response, ApiError> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(ApiError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+
+    let requests = ApiRequestLogRepo::new(database::get_db())
+        .recent(org_id, query.key_id.map(DashlessUuid::into_inner), limit)
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
+
+    Ok((StatusCode::OK, Json(requests)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::api_request_log::NewRequest;
+    use crate::test_utils::factory;
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use axum::{body::Body, http::Request, Router};
+    use serial_test::serial;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/organizations/:org_id/requests",
+                axum::routing::get(list_requests_handler),
+            )
+            .route(
+                "/organizations/:org_id/keys/:key_id/usage",
+                axum::routing::get(crate::api::api_keys::key_usage_handler),
+            )
+    }
+
+    async fn get_json(uri: String, token: &str) -> (StatusCode, serde_json::Value) {
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    #[serial]
+    #[cfg_attr(feature = "sqlite", ignore = "requests aren't logged on SQLite")]
+    async fn test_requests_show_origin() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+
+        let owner = factory::user("requests@example.com").await;
+        let key = factory::api_key(&owner).await;
+        let repo = ApiRequestLogRepo::new(pool);
+        for ip in ["203.0.113.7", "203.0.113.7", "198.51.100.1"] {
+            repo.insert_pending(&NewRequest {
+                request_id: Uuid::now_v7(),
+                organization_id: key.org_id,
+                api_key_id: key.key_id,
+                product: "embeddings".to_string(),
+                endpoint: "/v1/embed".to_string(),
+                input_text: "hello".to_string(),
+                input_metadata: None,
+                client_ip: Some(ip.parse().unwrap()),
+                user_agent: Some("smally-python/0.3".to_string()),
+            })
+            .await
+            .unwrap();
+        }
+
+        let (status, body) = get_json(
+            format!("/organizations/{}/requests?limit=2", owner.org_id),
+            &owner.session_token,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let requests = body.as_array().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0]["client_ip"], "198.51.100.1");
+        assert_eq!(requests[0]["user_agent"], "smally-python/0.3");
+        assert!(requests[0].get("input_text").is_none());
+
+        let usage_uri = format!("/organizations/{}/keys/{}/usage", owner.org_id, key.id);
+        let (status, body) = get_json(usage_uri.clone(), &owner.session_token).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["requests_24h"], 3);
+        assert_eq!(body["distinct_ips_24h"], 2);
+
+        // Opting out of IP storage erases the stored IPs
+        services::organizations::set_ip_storage(pool, owner.org_id, owner.id, false)
+            .await
+            .unwrap();
+        let (_, body) = get_json(usage_uri, &owner.session_token).await;
+        assert_eq!(body["distinct_ips_24h"], 0);
+
+        let (status, _) = get_json(
+            format!("/organizations/{}/requests?limit=0", owner.org_id),
+            &owner.session_token,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Other users see neither
+        let other = factory::user("other@example.com").await;
+        let (status, _) = get_json(
+            format!("/organizations/{}/requests", owner.org_id),
+            &other.session_token,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        cleanup_db().await;
+    }
+}
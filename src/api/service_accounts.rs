@@ -0,0 +1,273 @@
+use anyhow::Result;
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::auth::session::SessionClaims;
+use crate::database;
+use crate::models::{CreateServiceAccountRequest, ServiceAccountResponse};
+use crate::services;
+use crate::uuid_dashless::DashlessUuid;
+
+use super::error::ApiError;
+use super::strict::StrictJson;
+
+/// Create a service account (owner only); the token is returned once
+pub async fn create_service_account_handler(
+    claims: SessionClaims,
+    Path(org_id): Path<DashlessUuid>,
+    StrictJson(payload): StrictJson<CreateServiceAccountRequest>,
+) -> Result<Response, ApiError> {
+    let user_id: uuid::Uuid = claims
+        .sub
+        .parse()
+        .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?;
+
+    let issued = services::service_accounts::create(
+        database::get_db(),
+        org_id.into_inner(),
+        user_id,
+        &payload,
+    )
+    .await?;
+
+    let response = ServiceAccountResponse {
+        token: Some(issued.token),
+        ..issued.account.into()
+    };
+
+    Ok((StatusCode::CREATED, Json(response)).into_response())
+}
+
+/// List an organization's service accounts
+pub async fn list_service_accounts_handler(
+    claims: SessionClaims,
+    Path(org_id): Path<DashlessUuid>,
+) -> Result<Response, ApiError> {
+    let user_id: uuid::Uuid = claims
+        .sub
+        .parse()
+        .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?;
+
+    let accounts =
+        services::service_accounts::list(database::get_db(), org_id.into_inner(), user_id).await?;
+    let responses: Vec<ServiceAccountResponse> = accounts.into_iter().map(Into::into).collect();
+
+    Ok((StatusCode::OK, Json(responses)).into_response())
+}
+
+/// Revoke a service account (owner only)
+pub async fn revoke_service_account_handler(
+    claims: SessionClaims,
+    Path((org_id, id)): Path<(DashlessUuid, DashlessUuid)>,
+) -> Result<Response, ApiError> {
+    let user_id: uuid::Uuid = claims
+        .sub
+        .parse()
+        .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?;
+
+    let account = services::service_accounts::revoke(
+        database::get_db(),
+        org_id.into_inner(),
+        user_id,
+        id.into_inner(),
+    )
+    .await?;
+
+    Ok((StatusCode::OK, Json(ServiceAccountResponse::from(account))).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{api_keys, statements};
+    use crate::models::InviteMemberRequest;
+    use crate::models::OrganizationRole;
+    use crate::test_utils::factory::{self, TestUser};
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        routing::{delete, get, post},
+        Router,
+    };
+    use serde_json::json;
+    use serial_test::serial;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/organizations/:org_id/service-accounts",
+                post(create_service_account_handler).get(list_service_accounts_handler),
+            )
+            .route(
+                "/organizations/:org_id/service-accounts/:id",
+                delete(revoke_service_account_handler),
+            )
+            .route(
+                "/organizations/:org_id/keys",
+                post(api_keys::create_api_key_handler).get(api_keys::list_api_keys_handler),
+            )
+            .route(
+                "/organizations/:org_id/statements",
+                get(statements::list_statements_handler),
+            )
+    }
+
+    async fn send(
+        method: &str,
+        uri: String,
+        token: &str,
+        body: Option<serde_json::Value>,
+    ) -> Response {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("authorization", format!("Bearer {}", token));
+        let body = match body {
+            Some(body) => {
+                request = request.header("content-type", "application/json");
+                Body::from(serde_json::to_vec(&body).unwrap())
+            }
+            None => Body::empty(),
+        };
+        app().oneshot(request.body(body).unwrap()).await.unwrap()
+    }
+
+    async fn create_account(owner: &TestUser, role: &str) -> ServiceAccountResponse {
+        let response = send(
+            "POST",
+            format!("/organizations/{}/service-accounts", owner.org_id),
+            &owner.session_token,
+            Some(json!({"name": "ci", "role": role})),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_service_account_roles() {
+        setup().await;
+        cleanup_db().await;
+        let owner = factory::user("owner@example.com").await;
+
+        let member = create_account(&owner, "member").await;
+        let token = member.token.unwrap();
+        assert!(token.starts_with("sa_"));
+
+        // Members read, but can't manage keys
+        let keys = format!("/organizations/{}/keys", owner.org_id);
+        let response = send("GET", keys.clone(), &token, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(
+            "GET",
+            format!("/organizations/{}/statements", owner.org_id),
+            &token,
+            None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send("POST", keys.clone(), &token, Some(json!({"name": "k"}))).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Admins can
+        let admin = create_account(&owner, "admin").await.token.unwrap();
+        let response = send("POST", keys.clone(), &admin, Some(json!({"name": "k"}))).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // Never owners, and never managing service accounts themselves
+        let accounts = format!("/organizations/{}/service-accounts", owner.org_id);
+        let response = send(
+            "POST",
+            accounts.clone(),
+            &owner.session_token,
+            Some(json!({"name": "root", "role": "owner"})),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = send("GET", accounts, &admin, None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Confined to their organization
+        let other = factory::user("other@example.com").await;
+        let response = send(
+            "GET",
+            format!("/organizations/{}/keys", other.org_id),
+            &admin,
+            None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_only_owners_manage_service_accounts() {
+        setup().await;
+        cleanup_db().await;
+        let owner = factory::user("owner@example.com").await;
+        let admin = factory::user("admin@example.com").await;
+        services::organizations::invite(
+            database::get_db(),
+            owner.org_id,
+            owner.id,
+            &InviteMemberRequest {
+                email: "admin@example.com".to_string(),
+                role: OrganizationRole::Admin,
+            },
+        )
+        .await
+        .unwrap();
+
+        let accounts = format!("/organizations/{}/service-accounts", owner.org_id);
+        let response = send(
+            "POST",
+            accounts.clone(),
+            &admin.session_token,
+            Some(json!({"name": "ci", "role": "member"})),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let account = create_account(&owner, "member").await;
+        let token = account.token.unwrap();
+        let revoke = format!("{}/{}", accounts, account.id);
+        let response = send("DELETE", revoke.clone(), &admin.session_token, None).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Listed for members, without the token
+        let response = send("GET", accounts, &admin.session_token, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let listed: Vec<ServiceAccountResponse> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].token.is_none() && listed[0].revoked_at.is_none());
+
+        // Revoked tokens stop working
+        let response = send("DELETE", revoke, &owner.session_token, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = send(
+            "GET",
+            format!("/organizations/{}/keys", owner.org_id),
+            &token,
+            None,
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        cleanup_db().await;
+    }
+}
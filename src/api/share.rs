@@ -0,0 +1,413 @@
+//! Shared embedding results
+//!
+//! `POST /v1/share` embeds a request like `/v1/embed` (billed the same way)
+//! and stores the result behind a signed link, so support and sales can
+//! show it to someone without a key: `GET /share/:token` renders it in the
+//! web UI and `GET /v1/shared/:token` returns it as JSON. A link only opens
+//! the result of the organization that created it, expires after
+//! `SHARE_LINK_TTL_SECS` (or sooner, if asked) and can be revoked with
+//! `DELETE /v1/share/:id`. Every view is audit logged.
+
+use axum::{
+    extract::Path,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::types::Json as SqlJson;
+use uuid::Uuid;
+
+use crate::uuid_dashless::DashlessUuid;
+use crate::{auth, config, database};
+
+use super::error::ApiError;
+use super::Embedding;
+
+/// Options the shared embedding was made with
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ShareOptions {
+    pub normalize: bool,
+    pub post_process: bool,
+    pub truncate: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub precision: Option<u8>,
+}
+
+/// Request to `POST /v1/share`: an embed request plus how to share it
+#[cfg(feature = "inference")]
+#[derive(Debug, Deserialize)]
+pub struct ShareRequest {
+    #[serde(flatten)]
+    pub embed: super::EmbedRequest,
+    /// Show the input text on the shared page (false = only the vector)
+    #[serde(default = "default_include_text")]
+    pub include_text: bool,
+    /// Expire the link sooner than `SHARE_LINK_TTL_SECS`
+    #[serde(default)]
+    pub expires_in_secs: Option<u64>,
+}
+
+#[cfg(feature = "inference")]
+fn default_include_text() -> bool {
+    true
+}
+
+/// Fields of `ShareRequest` besides the flattened embed request's
+#[cfg(feature = "inference")]
+const SHARE_FIELDS: [&str; 2] = ["include_text", "expires_in_secs"];
+
+#[derive(Debug, sqlx::FromRow)]
+struct ShareRow {
+    id: Uuid,
+    organization_id: Uuid,
+    input_text: Option<String>,
+    embedding: SqlJson<Vec<f32>>,
+    model: String,
+    tokens: i32,
+    options: SqlJson<ShareOptions>,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+/// A shared result, as shown behind its link
+#[derive(Debug, Serialize)]
+pub struct SharedEmbedding {
+    pub id: String,
+    /// Input text (None when it wasn't shared)
+    pub text: Option<String>,
+    /// Rounded to `options.precision`, like the original response
+    pub embedding: Embedding,
+    pub dimensions: usize,
+    /// Model with its revision (e.g. `all-MiniLM-L6-v2@2024-01`)
+    pub model: String,
+    pub tokens: i32,
+    pub options: ShareOptions,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Link token of a share: its id and a signature binding it to the
+/// organization
+pub fn share_token(secret: &str, id: Uuid, org_id: Uuid) -> String {
+    let signature = share_mac(secret, id, org_id).finalize().into_bytes();
+    format!("{}.{}", id.simple(), hex::encode(signature))
+}
+
+/// HMAC-SHA256 over the share id and its organization
+fn share_mac(secret: &str, id: Uuid, org_id: Uuid) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("share:{}:{}", id.simple(), org_id.simple()).as_bytes());
+    mac
+}
+
+/// Share id named by a token (unverified until checked against its row)
+fn token_id(token: &str) -> Option<(Uuid, &str)> {
+    let (id, signature) = token.split_once('.')?;
+    Some((Uuid::try_parse(id).ok()?, signature))
+}
+
+/// Check a token's signature for the share's organization (constant-time
+/// comparison)
+fn verify_share_token(secret: &str, id: Uuid, org_id: Uuid, signature: &str) -> bool {
+    let Ok(provided) = hex::decode(signature) else {
+        return false;
+    };
+    share_mac(secret, id, org_id)
+        .verify_slice(&provided)
+        .is_ok()
+}
+
+/// Public URLs of a share: the web page and its JSON variant
+#[cfg(feature = "inference")]
+fn share_urls(token: &str) -> (String, String) {
+    let base_url = config::get_settings()
+        .public_base_url
+        .trim_end_matches('/')
+        .to_string();
+    (
+        format!("{}/share/{}", base_url, token),
+        format!("{}/v1/shared/{}", base_url, token),
+    )
+}
+
+/// Open a share link, audit logging the view (`via`: page or json). Bad
+/// signatures, expired and revoked links all look the same: not found.
+pub async fn open(token: &str, via: &str) -> Result<SharedEmbedding, ApiError> {
+    let not_found = || ApiError::NotFound("Share link is invalid or has expired".to_string());
+    let (id, signature) = token_id(token).ok_or_else(not_found)?;
+
+    let row = sqlx::query_as::<_, ShareRow>(
+        "SELECT id, organization_id, input_text, embedding, model, tokens, options,
+                created_at, expires_at, revoked_at
+         FROM shared_embeddings WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(database::get_db())
+    .await
+    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
+    .ok_or_else(not_found)?;
+
+    if !verify_share_token(
+        &config::get_settings().secret_key,
+        row.id,
+        row.organization_id,
+        signature,
+    ) || row.revoked_at.is_some()
+        || row.expires_at <= Utc::now()
+    {
+        return Err(not_found());
+    }
+
+    tracing::info!(
+        target: "audit",
+        action = "share.view",
+        share_id = %row.id,
+        org_id = %row.organization_id,
+        via = via,
+        "Shared embedding viewed"
+    );
+
+    let options = row.options.0;
+    let values = row.embedding.0;
+    Ok(SharedEmbedding {
+        id: row.id.simple().to_string(),
+        text: row.input_text,
+        dimensions: values.len(),
+        embedding: Embedding::new(values, options.precision),
+        model: row.model,
+        tokens: row.tokens,
+        options,
+        created_at: row.created_at,
+        expires_at: row.expires_at,
+    })
+}
+
+/// Keep shared results out of search engines
+pub fn noindex_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("x-robots-tag", HeaderValue::from_static("noindex"));
+    headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    headers
+}
+
+/// Embed a request and share the result (API key with the `embed` scope)
+///
+/// Billed like `/v1/embed`. Returns the link (`url`), its JSON variant and
+/// when it expires.
+#[cfg(feature = "inference")]
+pub async fn create_share_handler(
+    claims: auth::TokenClaims,
+    client_ip: super::ip_limit::ClientIp,
+    method: axum::http::Method,
+    uri: axum::http::Uri,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<Response, super::ApiError> {
+    let mut timer = super::timings::StageTimer::start();
+    let request_id = Uuid::now_v7();
+
+    if !claims.has_scope(auth::SCOPE_EMBED) {
+        return Err(super::ApiError::Unauthorized(
+            "API key is not allowed to create embeddings".to_string(),
+        ));
+    }
+    if claims.require_signing() {
+        super::verify_request_signature(&claims, &method, &uri, &headers, &body).await?;
+    }
+
+    if super::strict::requested(&headers) || claims.strict_validation() {
+        let mut known = super::strict::fields::<super::EmbedRequest>()
+            .unwrap_or_default()
+            .to_vec();
+        known.extend(SHARE_FIELDS);
+        let unknown = super::strict::unknown_body_fields(&body, &known);
+        if !unknown.is_empty() {
+            return Err(super::ApiError::UnknownFields(unknown));
+        }
+    }
+    let req: ShareRequest = serde_json::from_slice(&body)
+        .map_err(|e| super::ApiError::BadRequest(format!("Invalid JSON body: {}", e)))?;
+    if req.embed.dry_run {
+        return Err(super::ApiError::BadRequest(
+            "Dry runs have no result to share".to_string(),
+        ));
+    }
+
+    let max_ttl = config::get_settings().share_link_ttl_secs;
+    let ttl = match req.expires_in_secs {
+        None => max_ttl,
+        Some(secs) if (1..=max_ttl).contains(&secs) => secs,
+        Some(_) => {
+            return Err(super::ApiError::BadRequest(format!(
+                "expires_in_secs must be between 1 and {}",
+                max_ttl
+            )))
+        }
+    };
+
+    let prepared = super::prepare_embedding(&claims, &req.embed, &mut timer).await?;
+    let origin = super::RequestOrigin::new(client_ip, &headers);
+    let embedded = super::run_embedding(
+        &claims,
+        &req.embed,
+        &prepared,
+        &origin,
+        request_id,
+        "/v1/share",
+        &mut timer,
+    )
+    .await?;
+
+    let id = Uuid::now_v7();
+    let now = Utc::now();
+    let expires_at = now + chrono::Duration::seconds(ttl as i64);
+    let options = ShareOptions {
+        normalize: req.embed.normalize,
+        post_process: req.embed.post_process,
+        truncate: req.embed.truncate,
+        precision: req.embed.precision,
+    };
+    let response = &embedded.response;
+
+    sqlx::query(
+        "INSERT INTO shared_embeddings
+            (id, organization_id, api_key_id, request_id, input_text, embedding, model,
+             tokens, options, created_at, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+    )
+    .bind(id)
+    .bind(claims.org_id())
+    .bind(claims.key_id())
+    .bind(request_id)
+    .bind(req.include_text.then_some(req.embed.text.as_str()))
+    .bind(SqlJson(&response.embedding.values))
+    .bind(&response.model)
+    .bind(response.tokens as i32)
+    .bind(SqlJson(&options))
+    .bind(database::timestamp(now))
+    .bind(database::timestamp(expires_at))
+    .execute(database::get_db())
+    .await
+    .map_err(|e| super::ApiError::InternalError(format!("Failed to share embedding: {}", e)))?;
+
+    tracing::info!(
+        target: "audit",
+        action = "share.create",
+        share_id = %id,
+        org_id = %claims.org_id(),
+        key_id = %claims.key_id(),
+        request_id = %request_id,
+        include_text = req.include_text,
+        expires_at = %expires_at,
+        "Embedding shared"
+    );
+
+    let token = share_token(&config::get_settings().secret_key, id, claims.org_id());
+    let (url, json_url) = share_urls(&token);
+    Ok((
+        StatusCode::CREATED,
+        super::rate_limit_headers(&embedded.rate_limit),
+        Json(serde_json::json!({
+            "id": id.simple().to_string(),
+            "url": url,
+            "json_url": json_url,
+            "expires_at": expires_at,
+            "model": response.model,
+            "tokens": response.tokens,
+        })),
+    )
+        .into_response())
+}
+
+/// A shared result as JSON (no key needed, only the link token)
+pub async fn get_shared_handler(Path(token): Path<String>) -> Result<Response, ApiError> {
+    let shared = open(&token, "json").await?;
+    Ok((StatusCode::OK, noindex_headers(), Json(shared)).into_response())
+}
+
+/// Revoke a share link of the key's organization
+pub async fn revoke_share_handler(
+    claims: auth::TokenClaims,
+    Path(id): Path<DashlessUuid>,
+) -> Result<Response, ApiError> {
+    let id = id.into_inner();
+    let revoked = sqlx::query(
+        "UPDATE shared_embeddings SET revoked_at = $3
+         WHERE id = $1 AND organization_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(id)
+    .bind(claims.org_id())
+    .bind(database::timestamp(Utc::now()))
+    .execute(database::get_db())
+    .await
+    .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
+    .rows_affected();
+
+    if revoked == 0 {
+        return Err(ApiError::NotFound("Share not found".to_string()));
+    }
+
+    tracing::info!(
+        target: "audit",
+        action = "share.revoke",
+        share_id = %id,
+        org_id = %claims.org_id(),
+        key_id = %claims.key_id(),
+        "Share link revoked"
+    );
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_token_roundtrip() {
+        let (id, org_id) = (Uuid::now_v7(), Uuid::now_v7());
+        let token = share_token("secret", id, org_id);
+
+        let (parsed, signature) = token_id(&token).unwrap();
+        assert_eq!(parsed, id);
+        assert!(verify_share_token("secret", id, org_id, signature));
+    }
+
+    #[test]
+    fn test_share_token_rejects_other_org_and_tampering() {
+        let (id, org_id) = (Uuid::now_v7(), Uuid::now_v7());
+        let token = share_token("secret", id, org_id);
+        let (_, signature) = token_id(&token).unwrap();
+
+        // Another organization's share, another server secret, garbage
+        assert!(!verify_share_token("secret", id, Uuid::now_v7(), signature));
+        assert!(!verify_share_token("other", id, org_id, signature));
+        assert!(!verify_share_token("secret", id, org_id, "xyz"));
+        assert!(token_id("not-a-token").is_none());
+        assert!(token_id("zzzz.abcd").is_none());
+    }
+
+    #[cfg(feature = "inference")]
+    #[test]
+    fn test_share_request_defaults() {
+        let req: ShareRequest =
+            serde_json::from_str(r#"{"text": "hello", "normalize": true}"#).unwrap();
+        assert_eq!(req.embed.text, "hello");
+        assert!(req.embed.normalize);
+        assert!(req.include_text);
+        assert_eq!(req.expires_in_secs, None);
+
+        let req: ShareRequest = serde_json::from_str(
+            r#"{"text": "hello", "include_text": false, "expires_in_secs": 60}"#,
+        )
+        .unwrap();
+        assert!(!req.include_text);
+        assert_eq!(req.expires_in_secs, Some(60));
+    }
+}
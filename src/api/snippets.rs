@@ -0,0 +1,180 @@
+//! Ready-to-paste usage examples for the embedding API.
+//!
+//! The request body in every snippet is serialized from an `EmbedRequest`, so
+//! adding a field to the request schema shows up here without editing templates.
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::EmbedRequest;
+use crate::config;
+
+/// Supported snippet languages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnippetLang {
+    Curl,
+    Python,
+    Node,
+    Rust,
+}
+
+impl SnippetLang {
+    pub const ALL: [SnippetLang; 4] = [
+        SnippetLang::Curl,
+        SnippetLang::Python,
+        SnippetLang::Node,
+        SnippetLang::Rust,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SnippetLang::Curl => "curl",
+            SnippetLang::Python => "python",
+            SnippetLang::Node => "node",
+            SnippetLang::Rust => "rust",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SnippetLang::Curl => "cURL",
+            SnippetLang::Python => "Python",
+            SnippetLang::Node => "Node.js",
+            SnippetLang::Rust => "Rust",
+        }
+    }
+}
+
+/// Snippet variants
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnippetVariant {
+    /// Single request
+    Basic,
+    /// Several texts, one request each (the API embeds one text per request)
+    Batch,
+    /// Handles the documented error codes
+    ErrorHandling,
+}
+
+impl SnippetVariant {
+    pub const ALL: [SnippetVariant; 3] = [
+        SnippetVariant::Basic,
+        SnippetVariant::Batch,
+        SnippetVariant::ErrorHandling,
+    ];
+}
+
+/// Placeholder API key using the configured prefix
+pub fn placeholder_api_key() -> String {
+    format!("{}YOUR_API_KEY", config::get_settings().api_key_prefix)
+}
+
+/// Example request body, serialized from the real request type
+fn example_body(text: &str) -> String {
+    serde_json::to_string(&EmbedRequest {
+        text: text.to_string(),
+        normalize: true,
+        dry_run: false,
+        skip_language_detection: false,
+        truncate: false,
+        precision: None,
+        post_process: false,
+        model: None,
+        include_timings: false,
+    })
+    .expect("EmbedRequest serializes")
+}
+
+/// Render a snippet for the given language and variant
+pub fn render(lang: SnippetLang, variant: SnippetVariant, base_url: &str, api_key: &str) -> String {
+    let url = format!("{}/v1/embed", base_url.trim_end_matches('/'));
+    let body = example_body("Hello world");
+
+    match (lang, variant) {
+        (SnippetLang::Curl, SnippetVariant::Basic) => format!(
+            "curl -X POST {url} \\\n  -H \"Authorization: Bearer {api_key}\" \\\n  -H \"Content-Type: application/json\" \\\n  -d '{body}'\n"
+        ),
+        (SnippetLang::Curl, SnippetVariant::Batch) => format!(
+            "for text in \"first text\" \"second text\"; do\n  curl -s -X POST {url} \\\n    -H \"Authorization: Bearer {api_key}\" \\\n    -H \"Content-Type: application/json\" \\\n    -d \"$(jq -n --arg text \"$text\" '{template}')\"\ndone\n",
+            template = example_body("$TEXT").replace("\"$TEXT\"", "$text"),
+        ),
+        (SnippetLang::Curl, SnippetVariant::ErrorHandling) => format!(
+            "status=$(curl -s -o response.json -w '%{{http_code}}' -X POST {url} \\\n  -H \"Authorization: Bearer {api_key}\" \\\n  -H \"Content-Type: application/json\" \\\n  -d '{body}')\n\ncase \"$status\" in\n  200) jq '.embedding | length' response.json ;;\n  401) echo \"Invalid API key: $(jq -r .message response.json)\" ;;\n  429) echo \"Quota exhausted, resets at $(jq -r .reset_at response.json)\" ;;\n  400) echo \"Bad request: $(jq -r .message response.json)\" ;;\n  *) echo \"Server error ($status), retry later\" ;;\nesac\n"
+        ),
+        (SnippetLang::Python, SnippetVariant::Basic) => format!(
+            "import requests\n\nresponse = requests.post(\n    \"{url}\",\n    headers={{\"Authorization\": \"Bearer {api_key}\"}},\n    json={body},\n)\nresponse.raise_for_status()\nembedding = response.json()[\"embedding\"]\n"
+        ),
+        (SnippetLang::Python, SnippetVariant::Batch) => format!(
+            "import requests\n\ntexts = [\"first text\", \"second text\"]\nsession = requests.Session()\nsession.headers[\"Authorization\"] = \"Bearer {api_key}\"\n\nembeddings = []\nfor text in texts:\n    body = {template}\n    body[\"text\"] = text\n    response = session.post(\"{url}\", json=body)\n    response.raise_for_status()\n    embeddings.append(response.json()[\"embedding\"])\n",
+            template = body,
+        ),
+        (SnippetLang::Python, SnippetVariant::ErrorHandling) => format!(
+            "import requests\n\nresponse = requests.post(\n    \"{url}\",\n    headers={{\"Authorization\": \"Bearer {api_key}\"}},\n    json={body},\n)\n\nif response.ok:\n    embedding = response.json()[\"embedding\"]\nelse:\n    error = response.json()\n    if response.status_code == 401:\n        raise RuntimeError(f\"Invalid API key: {{error['message']}}\")\n    elif response.status_code == 429:\n        raise RuntimeError(f\"Quota exhausted, resets at {{error.get('reset_at')}}\")\n    elif error[\"error\"] == \"text_too_long\":\n        raise ValueError(f\"Text exceeds {{error['max_tokens']}} tokens\")\n    else:\n        raise RuntimeError(f\"{{error['error']}}: {{error['message']}}\")\n"
+        ),
+        (SnippetLang::Node, SnippetVariant::Basic) => format!(
+            "const response = await fetch(\"{url}\", {{\n  method: \"POST\",\n  headers: {{\n    \"Authorization\": \"Bearer {api_key}\",\n    \"Content-Type\": \"application/json\",\n  }},\n  body: JSON.stringify({body}),\n}});\nconst {{ embedding }} = await response.json();\n"
+        ),
+        (SnippetLang::Node, SnippetVariant::Batch) => format!(
+            "const texts = [\"first text\", \"second text\"];\n\nconst embeddings = await Promise.all(\n  texts.map(async (text) => {{\n    const response = await fetch(\"{url}\", {{\n      method: \"POST\",\n      headers: {{\n        \"Authorization\": \"Bearer {api_key}\",\n        \"Content-Type\": \"application/json\",\n      }},\n      body: JSON.stringify({{ ...{body}, text }}),\n    }});\n    if (!response.ok) throw new Error(`HTTP ${{response.status}}`);\n    return (await response.json()).embedding;\n  }}),\n);\n"
+        ),
+        (SnippetLang::Node, SnippetVariant::ErrorHandling) => format!(
+            "const response = await fetch(\"{url}\", {{\n  method: \"POST\",\n  headers: {{\n    \"Authorization\": \"Bearer {api_key}\",\n    \"Content-Type\": \"application/json\",\n  }},\n  body: JSON.stringify({body}),\n}});\n\nif (!response.ok) {{\n  const error = await response.json();\n  switch (response.status) {{\n    case 401:\n      throw new Error(`Invalid API key: ${{error.message}}`);\n    case 429:\n      throw new Error(`Quota exhausted, resets at ${{error.reset_at}}`);\n    default:\n      throw new Error(`${{error.error}}: ${{error.message}}`);\n  }}\n}}\nconst {{ embedding }} = await response.json();\n"
+        ),
+        (SnippetLang::Rust, SnippetVariant::Basic) => format!(
+            "// reqwest = {{ version = \"0.12\", features = [\"json\"] }}\nlet client = reqwest::Client::new();\nlet response: serde_json::Value = client\n    .post(\"{url}\")\n    .bearer_auth(\"{api_key}\")\n    .json(&serde_json::json!({body}))\n    .send()\n    .await?\n    .error_for_status()?\n    .json()\n    .await?;\nlet embedding = &response[\"embedding\"];\n"
+        ),
+        (SnippetLang::Rust, SnippetVariant::Batch) => format!(
+            "let client = reqwest::Client::new();\nlet texts = [\"first text\", \"second text\"];\n\nlet mut embeddings = Vec::new();\nfor text in texts {{\n    let mut body = serde_json::json!({body});\n    body[\"text\"] = text.into();\n    let response: serde_json::Value = client\n        .post(\"{url}\")\n        .bearer_auth(\"{api_key}\")\n        .json(&body)\n        .send()\n        .await?\n        .error_for_status()?\n        .json()\n        .await?;\n    embeddings.push(response[\"embedding\"].clone());\n}}\n"
+        ),
+        (SnippetLang::Rust, SnippetVariant::ErrorHandling) => format!(
+            "let client = reqwest::Client::new();\nlet response = client\n    .post(\"{url}\")\n    .bearer_auth(\"{api_key}\")\n    .json(&serde_json::json!({body}))\n    .send()\n    .await?;\n\nlet status = response.status();\nlet body: serde_json::Value = response.json().await?;\nmatch status.as_u16() {{\n    200 => println!(\"dimensions: {{}}\", body[\"embedding\"].as_array().map_or(0, |v| v.len())),\n    401 => eprintln!(\"Invalid API key: {{}}\", body[\"message\"]),\n    429 => eprintln!(\"Quota exhausted, resets at {{}}\", body[\"reset_at\"]),\n    _ => eprintln!(\"{{}}: {{}}\", body[\"error\"], body[\"message\"]),\n}}\n"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_snippets_use_base_url_and_key() {
+        for lang in SnippetLang::ALL {
+            for variant in SnippetVariant::ALL {
+                let snippet = render(lang, variant, "https://api.example.com/", "sk_test");
+                assert!(
+                    snippet.contains("https://api.example.com/v1/embed"),
+                    "{:?}/{:?} missing URL",
+                    lang,
+                    variant
+                );
+                assert!(
+                    snippet.contains("sk_test"),
+                    "{:?}/{:?} missing key",
+                    lang,
+                    variant
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_snippet_body_matches_request_schema() {
+        let snippet = render(
+            SnippetLang::Curl,
+            SnippetVariant::Basic,
+            "http://localhost:8000",
+            "sk_test",
+        );
+        let body = snippet.split('\'').nth(1).unwrap();
+        let parsed: EmbedRequest = serde_json::from_str(body).unwrap();
+        assert_eq!(parsed.text, "Hello world");
+    }
+
+    #[test]
+    fn test_lang_deserialize() {
+        let lang: SnippetLang = serde_json::from_str("\"python\"").unwrap();
+        assert_eq!(lang, SnippetLang::Python);
+        assert!(serde_json::from_str::<SnippetLang>("\"cobol\"").is_err());
+    }
+}
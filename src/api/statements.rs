@@ -0,0 +1,304 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::auth::service_account::Principal;
+use crate::auth::AdminTokenClaims;
+use crate::billing::statements;
+use crate::database::{self, DbPool};
+use crate::services::{self, ServiceError};
+use crate::uuid_dashless::DashlessUuid;
+
+use super::error::ApiError;
+use super::strict::StrictJson;
+
+/// Query parameters of `GET .../statements/:month`
+#[derive(Debug, Deserialize)]
+pub struct StatementQuery {
+    /// Specific statement version (defaults to the latest)
+    pub version: Option<i32>,
+}
+
+/// Request body for `POST /v1/admin/statements/regenerate`
+#[derive(Debug, Deserialize)]
+pub struct RegenerateStatementRequest {
+    pub organization_id: DashlessUuid,
+    /// Month as `YYYY-MM`
+    pub month: String,
+    /// Why the statement is being regenerated (kept on the new version)
+    pub reason: String,
+}
+
+/// List the latest version of each monthly statement of an organization
+pub async fn list_statements_handler(
+    principal: Principal,
+    Path(org_id): Path<DashlessUuid>,
+) -> Result<Response, ApiError> {
+    let pool = database::get_db();
+    let org_id = org_id.into_inner();
+    require_member(pool, org_id, &principal).await?;
+
+    let statements = statements::list_latest(pool, org_id)
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
+
+    Ok((StatusCode::OK, Json(statements)).into_response())
+}
+
+/// Get a stored statement for one month (`?version=N` for an older version)
+pub async fn get_statement_handler(
+    principal: Principal,
+    Path((org_id, month)): Path<(DashlessUuid, String)>,
+    Query(query): Query<StatementQuery>,
+) -> Result<Response, ApiError> {
+    let pool = database::get_db();
+    let org_id = org_id.into_inner();
+    let month = parse_month(&month)?;
+    require_member(pool, org_id, &principal).await?;
+
+    let statement = statements::find(pool, org_id, month, query.version)
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
+        .ok_or_else(|| ApiError::NotFound("Statement not found".to_string()))?;
+
+    Ok((StatusCode::OK, Json(statement)).into_response())
+}
+
+/// Regenerate a closed month as a new statement version (admin token required)
+pub async fn admin_regenerate_statement_handler(
+    _admin_token: AdminTokenClaims,
+    StrictJson(payload): StrictJson<RegenerateStatementRequest>,
+) -> Result<Response, ApiError> {
+    let org_id = payload.organization_id.into_inner();
+    let month = parse_month(&payload.month)?;
+
+    if month >= statements::month_start(Utc::now().date_naive()) {
+        return Err(ApiError::BadRequest(
+            "Only closed months can have statements".to_string(),
+        ));
+    }
+    let reason = payload.reason.trim();
+    if reason.is_empty() {
+        return Err(ApiError::BadRequest("reason is required".to_string()));
+    }
+
+    let statement = statements::regenerate(database::get_db(), org_id, month, reason)
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Failed to regenerate statement: {}", e)))?;
+
+    tracing::warn!(
+        target: "audit",
+        action = "statement.regenerate",
+        org_id = %org_id,
+        month = %payload.month,
+        version = statement.version,
+        reason = reason,
+        "Billing statement regenerated"
+    );
+
+    Ok((StatusCode::CREATED, Json(statement)).into_response())
+}
+
+fn parse_month(month: &str) -> Result<NaiveDate, ApiError> {
+    statements::parse_month(month)
+        .ok_or_else(|| ApiError::BadRequest("month must be formatted as YYYY-MM".to_string()))
+}
+
+/// Ensure the caller is a member of the organization
+async fn require_member(
+    pool: &DbPool,
+    org_id: Uuid,
+    principal: &Principal,
+) -> Result<(), ApiError> {
+    services::organizations::membership(pool, org_id, principal.actor()?)
+        .await
+        .map_err(|e| match e {
+            ServiceError::Forbidden(_) => {
+                ApiError::Unauthorized("Organization not found or access denied".to_string())
+            }
+            e => e.into(),
+        })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::factory::{self, TestUser};
+    use crate::test_utils::helpers::{cleanup_db, create_test_admin_token, setup};
+    use axum::{body::Body, http::Request, Router};
+    use serde_json::json;
+    use serial_test::serial;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/organizations/:org_id/statements",
+                axum::routing::get(list_statements_handler),
+            )
+            .route(
+                "/organizations/:org_id/statements/:month",
+                axum::routing::get(get_statement_handler),
+            )
+            .route(
+                "/admin/statements/regenerate",
+                axum::routing::post(admin_regenerate_statement_handler),
+            )
+    }
+
+    async fn get_json(uri: String, token: &str) -> (StatusCode, serde_json::Value) {
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_statements_return_stored_numbers() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
+        let month = statements::previous_month(Utc::now().date_naive());
+        let label = statements::format_month(month);
+
+        sqlx::query("UPDATE organizations SET created_at = $2 WHERE id = $1")
+            .bind(org_id)
+            .bind(database::timestamp(
+                month.and_hms_opt(0, 0, 0).unwrap().and_utc(),
+            ))
+            .execute(pool)
+            .await
+            .unwrap();
+        let add_usage = |tokens: i32| {
+            sqlx::query(
+                "INSERT INTO usage_events (organization_id, product, event_type, tokens, requests, timestamp)
+                 VALUES ($1, 'embed', 'inference', $2, 1, $3)",
+            )
+            .bind(org_id)
+            .bind(tokens)
+            .bind(database::timestamp(month.and_hms_opt(12, 0, 0).unwrap().and_utc()))
+            .execute(pool)
+        };
+        add_usage(500).await.unwrap();
+        statements::generate_month(pool, month).await.unwrap();
+
+        // Late usage doesn't change the closed statement
+        add_usage(700).await.unwrap();
+
+        let (status, body) = get_json(
+            format!("/organizations/{}/statements/{}", org_id, label),
+            &token,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["month"], label);
+        assert_eq!(body["version"], 1);
+        assert_eq!(body["total_tokens"], 500);
+
+        let (status, body) =
+            get_json(format!("/organizations/{}/statements", org_id), &token).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 1);
+
+        let (status, _) = get_json(
+            format!("/organizations/{}/statements/2020-01", org_id),
+            &token,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get_json(
+            format!("/organizations/{}/statements/january", org_id),
+            &token,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Other users can't see the statements
+        let other_token = factory::user("other@example.com").await.session_token;
+        let (status, _) = get_json(
+            format!("/organizations/{}/statements", org_id),
+            &other_token,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_regenerate_requires_admin_and_adds_version() {
+        setup().await;
+        cleanup_db().await;
+
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("test@example.com").await;
+        let month = statements::format_month(statements::previous_month(Utc::now().date_naive()));
+        let regenerate = |token: String| {
+            Request::builder()
+                .method("POST")
+                .uri("/admin/statements/regenerate")
+                .header("authorization", format!("Bearer {}", token))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    serde_json::to_vec(&json!({
+                        "organization_id": org_id,
+                        "month": month,
+                        "reason": "token recount",
+                    }))
+                    .unwrap(),
+                ))
+                .unwrap()
+        };
+
+        // Session tokens are not enough
+        let response = app().oneshot(regenerate(token)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        for expected_version in [1, 2] {
+            let response = app()
+                .oneshot(regenerate(create_test_admin_token()))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::CREATED);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(json["version"], expected_version);
+            assert_eq!(json["generated_by"], "admin");
+        }
+
+        cleanup_db().await;
+    }
+}
@@ -0,0 +1,223 @@
+//! Strict request validation (`X-Smally-Strict: true`)
+//!
+//! Request bodies are parsed leniently: fields an endpoint doesn't know are
+//! ignored, so a misspelled `normalise` silently means `normalize: false`.
+//! A request with `X-Smally-Strict: true`, or any embed request with a key
+//! created with `strict_validation`, is checked strictly instead: unknown
+//! top-level fields are a 400 `unknown_fields` error naming every one of
+//! them.
+//!
+//! The request types stay lenient (`deny_unknown_fields` would apply to
+//! everyone, and stops at the first unknown field). The raw body is checked
+//! against the field names the type declares to serde instead. The admin
+//! limit overrides are the exception (see `services::limits::Overrides`).
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::de::{self, DeserializeOwned, Visitor};
+use serde_json::Value;
+
+use super::{versions, ErrorResponse};
+
+/// Request header opting into strict validation
+pub const STRICT_HEADER: &str = "x-smally-strict";
+
+/// Whether the request sent `X-Smally-Strict: true`
+pub fn requested(headers: &HeaderMap) -> bool {
+    headers
+        .get(STRICT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("true"))
+}
+
+/// Field names `T` deserializes from (None for types that aren't plain
+/// structs, e.g. with `#[serde(flatten)]`, whose fields serde doesn't list)
+pub fn fields<T: DeserializeOwned>() -> Option<&'static [&'static str]> {
+    let mut fields = None;
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+/// Top-level keys of `body` not in `known` (none if `body` isn't an object:
+/// parsing reports that)
+pub fn unknown_fields(body: &Value, known: &[&str]) -> Vec<String> {
+    match body.as_object() {
+        Some(object) => object
+            .keys()
+            .filter(|key| !known.contains(&key.as_str()))
+            .cloned()
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Unknown top-level fields of a raw JSON body for `known`
+pub fn unknown_body_fields(body: &[u8], known: &[&str]) -> Vec<String> {
+    serde_json::from_slice(body)
+        .map(|body: Value| unknown_fields(&body, known))
+        .unwrap_or_default()
+}
+
+/// Message of the `unknown_fields` error
+pub fn message(unknown: &[String]) -> String {
+    let names: Vec<String> = unknown.iter().map(|name| format!("'{}'", name)).collect();
+    format!(
+        "Unknown field{} {} (strict validation is on)",
+        if unknown.len() == 1 { "" } else { "s" },
+        names.join(", ")
+    )
+}
+
+/// Body of the 400 `unknown_fields` error
+pub fn error(unknown: Vec<String>) -> ErrorResponse {
+    ErrorResponse {
+        unknown_fields: Some(unknown.clone()),
+        ..ErrorResponse::new("unknown_fields", message(&unknown))
+    }
+}
+
+/// `Json` extractor of the management endpoints: lenient by default, and
+/// rejecting unknown fields with 400 when the request sends
+/// `X-Smally-Strict: true`. Malformed bodies get `Json`'s usual rejections.
+pub struct StrictJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for StrictJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !requested(req.headers()) {
+            let Json(value) = Json::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            return Ok(StrictJson(value));
+        }
+
+        let Json(body) = Json::<Value>::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        if let Some(known) = fields::<T>() {
+            let unknown = unknown_fields(&body, known);
+            if !unknown.is_empty() {
+                let body = versions::error_body(versions::current(), &error(unknown));
+                return Err((StatusCode::BAD_REQUEST, Json(body)).into_response());
+            }
+        }
+
+        // Same rejection as `Json` for bodies of the wrong shape
+        let bytes = serde_json::to_vec(&body).unwrap_or_default();
+        Json::<T>::from_bytes(&bytes)
+            .map(|Json(value)| StrictJson(value))
+            .map_err(IntoResponse::into_response)
+    }
+}
+
+/// Deserializer that only records the field names a struct asks for
+struct FieldNames<'a>(&'a mut Option<&'static [&'static str]>);
+
+impl<'de> de::Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = Some(fields);
+        Err(de::Error::custom("field names only"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateAPIKeyRequest;
+    use axum::{body::Body, http::header};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize)]
+    struct Flattened {
+        #[serde(flatten)]
+        #[allow(dead_code)]
+        rest: serde_json::Map<String, Value>,
+    }
+
+    #[test]
+    fn test_fields_of_request_types() {
+        let known = fields::<crate::api::EmbedRequest>().unwrap();
+        assert!(known.contains(&"text"));
+        assert!(known.contains(&"normalize"));
+        assert!(fields::<CreateAPIKeyRequest>()
+            .unwrap()
+            .contains(&"strict_validation"));
+        assert_eq!(fields::<Flattened>(), None);
+    }
+
+    #[test]
+    fn test_unknown_fields_are_all_named() {
+        let known = fields::<crate::api::EmbedRequest>().unwrap();
+        let body = br#"{"text": "hi", "normalise": true, "model_name": "x"}"#;
+        let unknown = unknown_body_fields(body, known);
+        assert_eq!(unknown, vec!["model_name", "normalise"]);
+        assert_eq!(
+            message(&unknown),
+            "Unknown fields 'model_name', 'normalise' (strict validation is on)"
+        );
+        assert!(unknown_body_fields(br#"{"text": "hi"}"#, known).is_empty());
+        assert!(unknown_body_fields(b"[1, 2]", known).is_empty());
+    }
+
+    fn request(strict: bool, body: Value) -> Request {
+        let mut builder = Request::builder()
+            .method("POST")
+            .header(header::CONTENT_TYPE, "application/json");
+        if strict {
+            builder = builder.header(STRICT_HEADER, "true");
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_extractor_is_lenient_without_the_header() {
+        let body = json!({"name": "key", "tierr": "pro"});
+
+        let StrictJson(req) =
+            StrictJson::<CreateAPIKeyRequest>::from_request(request(false, body.clone()), &())
+                .await
+                .unwrap();
+        assert_eq!(req.name, "key");
+
+        let response = StrictJson::<CreateAPIKeyRequest>::from_request(request(true, body), &())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: Value = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(json["error"], "unknown_fields");
+        assert_eq!(json["unknown_fields"], json!(["tierr"]));
+    }
+}
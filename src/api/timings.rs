@@ -0,0 +1,201 @@
+//! Per-stage latency of embed requests
+//!
+//! `latency_ms` covers the whole handler, which leaves clients guessing how
+//! much of what they measured is their own network time. `StageTimer`
+//! splits the handler into stages: each `lap` charges the time since the
+//! previous lap to a stage, so the stages add up to the total. The
+//! breakdown always goes into the request's `response_metadata` and span;
+//! the response carries it when the request sets `include_timings`.
+//!
+//! API key validation runs in the extractor, before the handler starts the
+//! timer, and isn't part of `validation_ms`.
+
+use serde::Serialize;
+use std::time::Instant;
+use utoipa::ToSchema;
+
+/// A stage of an embed request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Request signature, body parsing and limit checks
+    Validation,
+    /// Token count of the input
+    Tokenization,
+    /// Per-key concurrency slot and rate limit
+    RateLimit,
+    CacheLookup,
+    /// Model run on a cache miss (or the wait for an identical one)
+    Inference,
+    /// Embedding transform and language detection
+    PostProcessing,
+    /// Request log and usage buffer
+    UsageRecording,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 7] = [
+        Stage::Validation,
+        Stage::Tokenization,
+        Stage::RateLimit,
+        Stage::CacheLookup,
+        Stage::Inference,
+        Stage::PostProcessing,
+        Stage::UsageRecording,
+    ];
+
+    /// Field name in `Timings` and in the request span
+    pub fn field(self) -> &'static str {
+        match self {
+            Stage::Validation => "validation_ms",
+            Stage::Tokenization => "tokenization_ms",
+            Stage::RateLimit => "rate_limit_ms",
+            Stage::CacheLookup => "cache_lookup_ms",
+            Stage::Inference => "inference_ms",
+            Stage::PostProcessing => "post_processing_ms",
+            Stage::UsageRecording => "usage_recording_ms",
+        }
+    }
+}
+
+/// Time spent per stage, in milliseconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct Timings {
+    #[schema(example = 0.2)]
+    pub validation_ms: f64,
+    #[schema(example = 0.1)]
+    pub tokenization_ms: f64,
+    #[schema(example = 0.6)]
+    pub rate_limit_ms: f64,
+    #[schema(example = 0.4)]
+    pub cache_lookup_ms: f64,
+    #[schema(example = 11.8)]
+    pub inference_ms: f64,
+    #[schema(example = 0.1)]
+    pub post_processing_ms: f64,
+    #[schema(example = 0.1)]
+    pub usage_recording_ms: f64,
+    /// Whole handler (the stages add up to it)
+    #[schema(example = 13.3)]
+    pub total_ms: f64,
+}
+
+impl Timings {
+    fn stage_mut(&mut self, stage: Stage) -> &mut f64 {
+        match stage {
+            Stage::Validation => &mut self.validation_ms,
+            Stage::Tokenization => &mut self.tokenization_ms,
+            Stage::RateLimit => &mut self.rate_limit_ms,
+            Stage::CacheLookup => &mut self.cache_lookup_ms,
+            Stage::Inference => &mut self.inference_ms,
+            Stage::PostProcessing => &mut self.post_processing_ms,
+            Stage::UsageRecording => &mut self.usage_recording_ms,
+        }
+    }
+
+    pub fn stage(&self, stage: Stage) -> f64 {
+        match stage {
+            Stage::Validation => self.validation_ms,
+            Stage::Tokenization => self.tokenization_ms,
+            Stage::RateLimit => self.rate_limit_ms,
+            Stage::CacheLookup => self.cache_lookup_ms,
+            Stage::Inference => self.inference_ms,
+            Stage::PostProcessing => self.post_processing_ms,
+            Stage::UsageRecording => self.usage_recording_ms,
+        }
+    }
+
+    /// Record the breakdown on `span`, which must declare the fields
+    /// `timings.<stage>_ms` and `timings.total_ms`
+    pub fn record(&self, span: &tracing::Span) {
+        for stage in Stage::ALL {
+            span.record(
+                format!("timings.{}", stage.field()).as_str(),
+                self.stage(stage),
+            );
+        }
+        span.record("timings.total_ms", self.total_ms);
+    }
+}
+
+/// Lap timer of one request
+#[derive(Debug, Clone)]
+pub struct StageTimer {
+    started: Instant,
+    last_lap: Instant,
+    timings: Timings,
+}
+
+impl StageTimer {
+    pub fn start() -> Self {
+        Self::start_at(Instant::now())
+    }
+
+    pub fn start_at(started: Instant) -> Self {
+        StageTimer {
+            started,
+            last_lap: started,
+            timings: Timings::default(),
+        }
+    }
+
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+
+    /// Charge the time since the previous lap to `stage`
+    pub fn lap(&mut self, stage: Stage) {
+        let now = Instant::now();
+        *self.timings.stage_mut(stage) += millis(now - self.last_lap);
+        self.last_lap = now;
+    }
+
+    /// The breakdown so far, with the total up to now
+    pub fn timings(&self) -> Timings {
+        Timings {
+            total_ms: millis(self.started.elapsed()),
+            ..self.timings.clone()
+        }
+    }
+}
+
+fn millis(duration: std::time::Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_laps_add_up_to_the_total() {
+        let mut timer = StageTimer::start();
+        std::thread::sleep(Duration::from_millis(5));
+        timer.lap(Stage::Validation);
+        std::thread::sleep(Duration::from_millis(10));
+        timer.lap(Stage::Inference);
+        std::thread::sleep(Duration::from_millis(2));
+        timer.lap(Stage::UsageRecording);
+        std::thread::sleep(Duration::from_millis(2));
+        timer.lap(Stage::UsageRecording);
+
+        let timings = timer.timings();
+        assert!(timings.validation_ms >= 5.0);
+        assert!(timings.inference_ms >= 10.0);
+        assert!(timings.usage_recording_ms >= 4.0);
+        assert_eq!(timings.cache_lookup_ms, 0.0);
+
+        let sum: f64 = Stage::ALL.iter().map(|&s| timings.stage(s)).sum();
+        assert!(sum <= timings.total_ms);
+        assert!(timings.total_ms - sum < 1.0, "{:?}", timings);
+    }
+
+    #[test]
+    fn test_field_names_match_serialization() {
+        let json = serde_json::to_value(Timings::default()).unwrap();
+        for stage in Stage::ALL {
+            assert!(json.get(stage.field()).is_some(), "{}", stage.field());
+        }
+        assert!(json.get("total_ms").is_some());
+    }
+}
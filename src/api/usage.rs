@@ -0,0 +1,204 @@
+//! Usage of an organization per day, for members to see their own
+//! consumption
+
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Days, NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::auth::service_account::Principal;
+use crate::billing::statements;
+use crate::billing::usage::{self, MAX_REPORT_DAYS};
+use crate::database;
+use crate::services::{self, ServiceError};
+use crate::uuid_dashless::DashlessUuid;
+
+use super::error::ApiError;
+
+/// Query parameters of `GET /v1/organizations/:org_id/usage`
+#[derive(Debug, Default, Deserialize)]
+pub struct UsageQuery {
+    /// First day (UTC, default the first of `to`'s month)
+    pub from: Option<NaiveDate>,
+    /// Last day, included (default the last of `from`'s month)
+    pub to: Option<NaiveDate>,
+    /// `key` for a per-key breakdown of each day
+    pub group_by: Option<String>,
+}
+
+/// Requests and tokens of an organization per day over a period (members);
+/// the current month by default. Non-members get a 404, as if the
+/// organization didn't exist.
+pub async fn usage_handler(
+    principal: Principal,
+    Path(org_id): Path<DashlessUuid>,
+    Query(query): Query<UsageQuery>,
+) -> Result<Response, ApiError> {
+    let org_id = org_id.into_inner();
+    let (from, to) = period(&query, Utc::now().date_naive())?;
+    let by_key = match query.group_by.as_deref() {
+        None => false,
+        Some("key") => true,
+        Some(_) => return Err(ApiError::BadRequest("group_by must be key".to_string())),
+    };
+    services::organizations::membership(database::get_db(), org_id, principal.actor()?)
+        .await
+        .map_err(|e| match e {
+            ServiceError::Forbidden(_) => ApiError::NotFound("Organization not found".to_string()),
+            e => e.into(),
+        })?;
+
+    let report = usage::usage_report(database::get_db(), org_id, from, to, by_key)
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
+
+    Ok((StatusCode::OK, Json(report)).into_response())
+}
+
+/// First and last day of the requested period
+fn period(query: &UsageQuery, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), ApiError> {
+    let last_of_month = |day| statements::next_month(day) - Days::new(1);
+    let (from, to) = match (query.from, query.to) {
+        (Some(from), Some(to)) => (from, to),
+        (Some(from), None) => (from, last_of_month(from)),
+        (None, Some(to)) => (statements::month_start(to), to),
+        (None, None) => (statements::month_start(today), last_of_month(today)),
+    };
+    if from > to {
+        return Err(ApiError::BadRequest(
+            "from must not be after to".to_string(),
+        ));
+    }
+    if (to - from).num_days() + 1 > MAX_REPORT_DAYS {
+        return Err(ApiError::BadRequest(format!(
+            "The period can't be longer than {} days",
+            MAX_REPORT_DAYS
+        )));
+    }
+    Ok((from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::factory::{self, TestUser};
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use axum::{body::Body, http::Request, Router};
+    use serial_test::serial;
+    use tower::ServiceExt;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn query(from: Option<NaiveDate>, to: Option<NaiveDate>) -> UsageQuery {
+        UsageQuery {
+            from,
+            to,
+            group_by: None,
+        }
+    }
+
+    #[test]
+    fn test_period_defaults_and_limits() {
+        let today = date(2024, 2, 10);
+        assert_eq!(
+            period(&query(None, None), today).unwrap(),
+            (date(2024, 2, 1), date(2024, 2, 29))
+        );
+        assert_eq!(
+            period(&query(Some(date(2024, 1, 15)), None), today).unwrap(),
+            (date(2024, 1, 15), date(2024, 1, 31))
+        );
+        assert_eq!(
+            period(&query(None, Some(date(2023, 12, 20))), today).unwrap(),
+            (date(2023, 12, 1), date(2023, 12, 20))
+        );
+        // 92 days, both ends included
+        assert!(period(
+            &query(Some(date(2024, 1, 1)), Some(date(2024, 4, 1))),
+            today
+        )
+        .is_ok());
+
+        assert!(matches!(
+            period(
+                &query(Some(date(2024, 1, 1)), Some(date(2024, 4, 2))),
+                today
+            ),
+            Err(ApiError::BadRequest(_))
+        ));
+        assert!(matches!(
+            period(
+                &query(Some(date(2024, 1, 2)), Some(date(2024, 1, 1))),
+                today
+            ),
+            Err(ApiError::BadRequest(_))
+        ));
+    }
+
+    async fn get_json(uri: String, token: &str) -> (StatusCode, serde_json::Value) {
+        let app = Router::new().route(
+            "/organizations/:org_id/usage",
+            axum::routing::get(usage_handler),
+        );
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(uri)
+                    .header("authorization", format!("Bearer {}", token))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_usage_is_for_members() {
+        setup().await;
+        cleanup_db().await;
+        let TestUser {
+            session_token: token,
+            org_id,
+            ..
+        } = factory::user("usage@example.com").await;
+        let uri = |params: &str| format!("/organizations/{}/usage{}", org_id.simple(), params);
+
+        let (status, body) = get_json(uri("?from=2024-01-01&to=2024-01-31"), &token).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["from"], "2024-01-01");
+        assert_eq!(body["total_requests"], 0);
+        assert_eq!(body["days"].as_array().unwrap().len(), 31);
+        assert!(body["days"][0].get("keys").is_none());
+
+        let (status, body) = get_json(uri("?group_by=key"), &token).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["days"][0]["keys"].is_array());
+
+        for params in [
+            "?from=2024-02-01&to=2024-01-01",
+            "?from=2024-01-01&to=2024-06-30",
+            "?group_by=product",
+        ] {
+            let (status, _) = get_json(uri(params), &token).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", params);
+        }
+
+        let other_token = factory::user("usage-other@example.com").await.session_token;
+        let (status, _) = get_json(uri(""), &other_token).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        cleanup_db().await;
+    }
+}
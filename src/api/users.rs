@@ -4,134 +4,37 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use bcrypt::{hash, verify, DEFAULT_COST};
-use chrono::Utc;
-use serde_json::json;
-use validator::Validate;
+use bcrypt::verify;
+use chrono::DateTime;
 
-use crate::auth::session::{create_session_token, SessionClaims};
-use crate::database;
-use crate::models::{AuthResponse, CreateUserRequest, LoginRequest, TierType, User, UserResponse};
+use crate::auth::session::{
+    create_session_token, session_duration, SessionClaims, SESSION_DURATION_SHORT,
+};
+use crate::models::{
+    AuthResponse, CreateUserRequest, LoginRequest, ProfileResponse, User, UserResponse,
+};
+use crate::{database, services};
+
+use super::error::ApiError;
+use super::strict::StrictJson;
 
 /// Register a new user (requires admin token)
 pub async fn register_handler(
     _admin_token: crate::auth::AdminTokenClaims,
-    Json(payload): Json<CreateUserRequest>,
+    StrictJson(payload): StrictJson<CreateUserRequest>,
 ) -> Result<Response, ApiError> {
-    let pool = database::get_db();
-
-    // Validate input using validator crate
-    payload.validate().map_err(|e| {
-        let error_msg = e
-            .field_errors()
-            .iter()
-            .map(|(field, errors)| {
-                format!(
-                    "{}: {}",
-                    field,
-                    errors
-                        .iter()
-                        .filter_map(|e| e.message.as_ref())
-                        .map(|m| m.to_string())
-                        .collect::<Vec<_>>()
-                        .join(", ")
-                )
-            })
-            .collect::<Vec<_>>()
-            .join("; ");
-        ApiError::BadRequest(format!("Validation failed: {}", error_msg))
-    })?;
-
-    // Additional email validation - check for disposable/temporary email domains
-    let email_lower = payload.email.to_lowercase();
-    let disposable_domains = [
-        "tempmail.com",
-        "throwaway.email",
-        "guerrillamail.com",
-        "10minutemail.com",
-        "mailinator.com",
-    ];
-
-    if disposable_domains
-        .iter()
-        .any(|domain| email_lower.ends_with(domain))
-    {
-        return Err(ApiError::BadRequest(
-            "Disposable email addresses are not allowed".to_string(),
-        ));
-    }
-
-    // Check if user already exists
-    let existing_user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = $1")
-        .bind(&payload.email)
-        .fetch_optional(pool)
-        .await
-        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?;
-
-    if existing_user.is_some() {
-        return Err(ApiError::BadRequest("Email already registered".to_string()));
-    }
-
-    // Hash password
-    let password_hash = hash(&payload.password, DEFAULT_COST)
-        .map_err(|e| ApiError::InternalError(format!("Password hashing failed: {}", e)))?;
-
-    // Create user
-    let user = sqlx::query_as::<_, User>(
-        "INSERT INTO users (email, name, password_hash, is_active, created_at, updated_at)
-         VALUES ($1, $2, $3, $4, $5, $6, $7)
-         RETURNING *",
-    )
-    .bind(&payload.email)
-    .bind(&payload.name)
-    .bind(&password_hash)
-    .bind(true)
-    .bind(Utc::now().naive_utc())
-    .bind(Utc::now().naive_utc())
-    .fetch_one(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Failed to create user: {}", e)))?;
-
-    // Create personal organization for the user
-    let org_name = format!("{}' Organization", payload.email);
-
-    let org_id = sqlx::query_scalar::<_, i64>(
-        "INSERT INTO organizations (name, owner_id, tier, is_active, created_at, updated_at)
-         VALUES ($1, $2, $3, $4, $5)
-         RETURNING id",
-    )
-    .bind(&org_name)
-    .bind(user.id)
-    .bind(TierType::Free)
-    .bind(true)
-    .bind(Utc::now().naive_utc())
-    .bind(Utc::now().naive_utc())
-    .fetch_one(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Failed to create organization: {}", e)))?;
-
-    // Add user as owner of the organization
-    sqlx::query(
-        "INSERT INTO organization_members (organization_id, user_id, role, created_at)
-         VALUES ($1, $2, $3, $4)",
-    )
-    .bind(org_id)
-    .bind(user.id)
-    .bind("owner")
-    .bind(Utc::now().naive_utc())
-    .execute(pool)
-    .await
-    .map_err(|e| ApiError::InternalError(format!("Failed to add organization member: {}", e)))?;
+    let registered = services::users::register(database::get_db(), &payload).await?;
+    let user = registered.user;
 
     // Generate session token
-    let token = create_session_token(user.id, &user.email)
+    let token = create_session_token(user.id, &user.email, SESSION_DURATION_SHORT)
         .map_err(|e| ApiError::InternalError(format!("Failed to create session token: {}", e)))?;
 
     let response = AuthResponse {
         user: UserResponse {
             id: user.id,
-            email: user.email.clone(),
-            name: user.name.clone(),
+            email: user.email,
+            name: user.name,
             is_active: user.is_active,
             created_at: user.created_at,
         },
@@ -144,21 +47,25 @@ pub async fn register_handler(
 /// Login user (requires admin token)
 pub async fn login_handler(
     _admin_token: crate::auth::AdminTokenClaims,
-    Json(payload): Json<LoginRequest>,
+    StrictJson(payload): StrictJson<LoginRequest>,
 ) -> Result<Response, ApiError> {
     let pool = database::get_db();
 
     // Find user by email
-    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = $1")
-        .bind(&payload.email)
-        .fetch_optional(pool)
-        .await
-        .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
-        .ok_or_else(|| ApiError::Unauthorized("Invalid email or password".to_string()))?;
+    let user =
+        sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = $1 AND deleted_at IS NULL")
+            .bind(&payload.email)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
+            .ok_or_else(|| ApiError::Unauthorized("Invalid email or password".to_string()))?;
 
-    // Check if user is active
+    // Disabled accounts get the same answer as bad credentials
     if !user.is_active {
-        return Err(ApiError::Unauthorized("Account is disabled".to_string()));
+        tracing::warn!(target: "audit", email = %payload.email, "Login to disabled account");
+        return Err(ApiError::Unauthorized(
+            "Invalid email or password".to_string(),
+        ));
     }
 
     // Verify password
@@ -177,8 +84,10 @@ pub async fn login_handler(
     }
 
     // Generate session token
-    let token = create_session_token(user.id, &user.email)
-        .map_err(|e| ApiError::InternalError(format!("Failed to create session token: {}", e)))?;
+    let token = create_session_token(user.id, &user.email, session_duration(payload.remember_me))
+        .map_err(|e| {
+        ApiError::InternalError(format!("Failed to create session token: {}", e))
+    })?;
 
     let response = AuthResponse {
         user: UserResponse {
@@ -199,58 +108,42 @@ pub async fn get_profile_handler(claims: SessionClaims) -> Result<Response, ApiE
     let pool = database::get_db();
 
     let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
-        .bind(claims.sub.parse::<i64>().unwrap())
+        .bind(
+            claims
+                .sub
+                .parse::<uuid::Uuid>()
+                .map_err(|_| ApiError::Unauthorized("Invalid user ID".to_string()))?,
+        )
         .fetch_optional(pool)
         .await
         .map_err(|e| ApiError::InternalError(format!("Database error: {}", e)))?
         .ok_or_else(|| ApiError::Unauthorized("User not found".to_string()))?;
 
-    let response = UserResponse {
-        id: user.id,
-        email: user.email.clone(),
-        name: user.name.clone(),
-        is_active: user.is_active,
-        created_at: user.created_at,
+    let response = ProfileResponse {
+        user: UserResponse {
+            id: user.id,
+            email: user.email.clone(),
+            name: user.name.clone(),
+            is_active: user.is_active,
+            created_at: user.created_at,
+        },
+        session_expires_at: DateTime::from_timestamp(claims.exp, 0).unwrap_or_default(),
     };
 
     Ok((StatusCode::OK, Json(response)).into_response())
 }
 
-/// Error responses for user API
-#[derive(Debug)]
-pub enum ApiError {
-    BadRequest(String),
-    Unauthorized(String),
-    InternalError(String),
-}
-
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
-        let (status, message) = match self {
-            ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
-            ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
-            ApiError::InternalError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-        };
-
-        let body = Json(json!({
-            "error": message,
-        }));
-
-        (status, body).into_response()
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_utils::helpers::{
-        cleanup_db, create_test_admin_token, create_test_user, setup,
-    };
+    use crate::test_utils::factory;
+    use crate::test_utils::helpers::{cleanup_db, create_test_admin_token, setup};
     use axum::{
         body::Body,
         http::{Request, StatusCode},
         Router,
     };
+    use chrono::Utc;
     use serde_json::json;
     use serial_test::serial;
     use tower::ServiceExt;
@@ -309,7 +202,7 @@ mod tests {
         setup().await;
         cleanup_db().await;
 
-        create_test_user("test@example.com", "password123").await;
+        factory::user("test@example.com").await;
 
         let app = app();
         let admin_token = create_test_admin_token();
@@ -343,7 +236,7 @@ mod tests {
         setup().await;
         cleanup_db().await;
 
-        create_test_user("test@example.com", "password123").await;
+        factory::user("test@example.com").await;
 
         let app = app();
         let admin_token = create_test_admin_token();
@@ -385,7 +278,7 @@ mod tests {
         setup().await;
         cleanup_db().await;
 
-        create_test_user("test@example.com", "password123").await;
+        factory::user("test@example.com").await;
 
         let app = app();
         let admin_token = create_test_admin_token();
@@ -419,7 +312,7 @@ mod tests {
         setup().await;
         cleanup_db().await;
 
-        let (_user_id, token, _org_id) = create_test_user("test@example.com", "password123").await;
+        let token = factory::user("test@example.com").await.session_token;
 
         let app = app();
 
@@ -440,9 +333,10 @@ mod tests {
         let body = axum::body::to_bytes(response.into_body(), usize::MAX)
             .await
             .unwrap();
-        let user_response: UserResponse = serde_json::from_slice(&body).unwrap();
+        let profile: ProfileResponse = serde_json::from_slice(&body).unwrap();
 
-        assert_eq!(user_response.email, "test@example.com");
+        assert_eq!(profile.user.email, "test@example.com");
+        assert!(profile.session_expires_at > Utc::now());
 
         cleanup_db().await;
     }
@@ -527,7 +421,7 @@ mod tests {
         setup().await;
         cleanup_db().await;
 
-        create_test_user("test@example.com", "password123").await;
+        factory::user("test@example.com").await;
 
         let app = app();
 
@@ -0,0 +1,422 @@
+//! Date-based API versions (`X-Smally-Version`)
+//!
+//! Response shapes that would break existing clients only change behind a
+//! new version. A request's effective version is its `X-Smally-Version`
+//! header if sent, else the version pinned to its API key at creation, else
+//! `ApiVersion::DEFAULT` (keys issued before versioning and unauthenticated
+//! calls get the original shapes). `/v1` responses echo the effective
+//! version in the same header.
+//!
+//! Handlers don't branch on the version: bodies whose shape differs go
+//! through the serializers here (`embed_body`, `error_body`,
+//! `message_error_body`), which know every version's shape.
+
+use axum::{
+    extract::Request,
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, Mutex};
+use utoipa::ToSchema;
+
+use super::{EmbedResponse, ErrorResponse};
+
+/// Request and response header carrying the version
+pub const VERSION_HEADER: &str = "x-smally-version";
+
+/// A version of the `/v1` response shapes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiVersion {
+    /// The original shapes
+    V20250101,
+    /// Token counts nested under `usage` in embed responses, and errors as
+    /// `{"error": {"type": ..., "message": ...}}` on every endpoint
+    V20250215,
+}
+
+impl ApiVersion {
+    /// Supported versions, oldest first
+    pub const ALL: [ApiVersion; 2] = [ApiVersion::V20250101, ApiVersion::V20250215];
+
+    /// Pinned to new API keys
+    pub const LATEST: ApiVersion = ApiVersion::V20250215;
+
+    /// For requests with neither a header nor a pinned version
+    pub const DEFAULT: ApiVersion = ApiVersion::V20250101;
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiVersion::V20250101 => "2025-01-01",
+            ApiVersion::V20250215 => "2025-02-15",
+        }
+    }
+
+    /// Parse a supported version (`YYYY-MM-DD`)
+    pub fn parse(version: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|v| v.as_str() == version.trim())
+    }
+
+    /// Version of a key pinned to `pinned` (None or no longer supported:
+    /// the default)
+    pub fn pinned(pinned: Option<&str>) -> Self {
+        pinned.and_then(Self::parse).unwrap_or(Self::DEFAULT)
+    }
+
+    /// Last day the version is served (None = no sunset scheduled)
+    pub fn sunset(self) -> Option<NaiveDate> {
+        match self {
+            ApiVersion::V20250101 | ApiVersion::V20250215 => None,
+        }
+    }
+
+    /// What changed in this version
+    pub fn changes(self) -> &'static str {
+        match self {
+            ApiVersion::V20250101 => "Initial version",
+            ApiVersion::V20250215 => {
+                "Embed responses report tokens under `usage`; errors are `{\"error\": {\"type\", \"message\", ...}}` on every endpoint"
+            }
+        }
+    }
+
+    fn nests_usage(self) -> bool {
+        self >= ApiVersion::V20250215
+    }
+
+    fn nests_errors(self) -> bool {
+        self >= ApiVersion::V20250215
+    }
+}
+
+/// Version state of the request being handled
+#[derive(Debug, Default)]
+struct Negotiation {
+    /// From `X-Smally-Version`
+    requested: Option<ApiVersion>,
+    /// Pinned to the API key, set once the key is authenticated
+    pinned: Mutex<Option<ApiVersion>>,
+}
+
+impl Negotiation {
+    fn effective(&self) -> ApiVersion {
+        self.requested
+            .or(*self.pinned.lock().unwrap())
+            .unwrap_or(ApiVersion::DEFAULT)
+    }
+}
+
+tokio::task_local! {
+    static NEGOTIATION: Arc<Negotiation>;
+}
+
+/// Effective version of the request being handled (the default outside
+/// `version_middleware`)
+pub fn current() -> ApiVersion {
+    NEGOTIATION
+        .try_with(|n| n.effective())
+        .unwrap_or(ApiVersion::DEFAULT)
+}
+
+/// Record the version pinned to the authenticated API key
+pub fn pin(version: ApiVersion) {
+    let _ = NEGOTIATION.try_with(|n| *n.pinned.lock().unwrap() = Some(version));
+}
+
+/// Resolve the version of `/v1` requests and echo it in the response.
+/// Unsupported header values are rejected.
+pub async fn version_middleware(request: Request, next: Next) -> Response {
+    if !request.uri().path().starts_with("/v1/") {
+        return next.run(request).await;
+    }
+
+    let requested = match request.headers().get(VERSION_HEADER) {
+        None => None,
+        Some(value) => match value.to_str().ok().and_then(ApiVersion::parse) {
+            Some(version) => Some(version),
+            None => return unsupported_version_response(),
+        },
+    };
+
+    let negotiation = Arc::new(Negotiation {
+        requested,
+        ..Default::default()
+    });
+    let mut response = NEGOTIATION
+        .scope(negotiation.clone(), next.run(request))
+        .await;
+    response.headers_mut().insert(
+        VERSION_HEADER,
+        HeaderValue::from_static(negotiation.effective().as_str()),
+    );
+    response
+}
+
+fn unsupported_version_response() -> Response {
+    let supported: Vec<&str> = ApiVersion::ALL.iter().map(|v| v.as_str()).collect();
+    let error = ErrorResponse::new(
+        "unsupported_version",
+        format!(
+            "Unsupported X-Smally-Version (supported: {})",
+            supported.join(", ")
+        ),
+    );
+    let body = error_body(ApiVersion::DEFAULT, &error);
+    (StatusCode::BAD_REQUEST, Json(body)).into_response()
+}
+
+/// `/v1/embed` response body
+pub fn embed_body(version: ApiVersion, response: &EmbedResponse) -> Value {
+    let mut body = serde_json::to_value(response).expect("embed responses serialize");
+
+    if version.nests_usage() {
+        if let Some(fields) = body.as_object_mut() {
+            let mut usage = Map::new();
+            for field in ["tokens", "truncated", "original_tokens"] {
+                if let Some(value) = fields.remove(field) {
+                    usage.insert(field.to_string(), value);
+                }
+            }
+            fields.insert("usage".to_string(), Value::Object(usage));
+        }
+    }
+    body
+}
+
+/// Error body of the embed API
+pub fn error_body(version: ApiVersion, error: &ErrorResponse) -> Value {
+    let mut body = serde_json::to_value(error).expect("error responses serialize");
+
+    if version.nests_errors() {
+        if let Some(fields) = body.as_object_mut() {
+            if let Some(kind) = fields.remove("error") {
+                fields.insert("type".to_string(), kind);
+            }
+        }
+        body = json!({ "error": body });
+    }
+    body
+}
+
+/// Error body of the management endpoints (`{"error": message}` before
+/// errors were unified)
+pub fn message_error_body(version: ApiVersion, kind: &str, message: String) -> Value {
+    if version.nests_errors() {
+        error_body(version, &ErrorResponse::new(kind, message))
+    } else {
+        json!({ "error": message })
+    }
+}
+
+/// A supported API version
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct VersionEntry {
+    /// Value of `X-Smally-Version`
+    #[schema(example = "2025-02-15")]
+    pub version: String,
+    /// Last day the version is served (null = no sunset scheduled)
+    pub sunset: Option<NaiveDate>,
+    /// What changed in this version
+    pub changes: String,
+}
+
+/// Supported API versions
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct VersionsResponse {
+    /// Version pinned to new API keys
+    #[schema(example = "2025-02-15")]
+    pub latest: String,
+    /// Version of requests without a header or a pinned version
+    #[schema(example = "2025-01-01")]
+    pub default: String,
+    /// Supported versions, oldest first
+    pub data: Vec<VersionEntry>,
+}
+
+/// List API versions
+///
+/// Versions accepted in `X-Smally-Version` (and when creating API keys),
+/// with their sunset dates.
+#[utoipa::path(
+    get,
+    path = "/v1/meta/versions",
+    tag = "health",
+    responses(
+        (status = 200, description = "Supported versions", body = VersionsResponse)
+    )
+)]
+pub async fn meta_versions_handler() -> Json<VersionsResponse> {
+    Json(VersionsResponse {
+        latest: ApiVersion::LATEST.as_str().to_string(),
+        default: ApiVersion::DEFAULT.as_str().to_string(),
+        data: ApiVersion::ALL
+            .into_iter()
+            .map(|v| VersionEntry {
+                version: v.as_str().to_string(),
+                sunset: v.sunset(),
+                changes: v.changes().to_string(),
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::Embedding;
+    use axum::{body::Body, http::HeaderMap, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn embed_response(original_tokens: Option<usize>) -> EmbedResponse {
+        EmbedResponse {
+            embedding: Embedding::default(),
+            model: "all-MiniLM-L6-v2".to_string(),
+            tokens: 128,
+            truncated: original_tokens.is_some(),
+            original_tokens,
+            cached: false,
+            content_hash: "0".repeat(16),
+            latency_ms: 1.0,
+            dry_run: false,
+            detected_language: None,
+            language_confidence: None,
+            warnings: Vec::new(),
+            timings: None,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ApiVersion::parse("2025-01-01"), Some(ApiVersion::V20250101));
+        assert_eq!(ApiVersion::parse("2024-12-31"), None);
+        assert_eq!(ApiVersion::pinned(None), ApiVersion::DEFAULT);
+        assert_eq!(ApiVersion::pinned(Some("2025-02-15")), ApiVersion::LATEST);
+        assert_eq!(ApiVersion::pinned(Some("1999-01-01")), ApiVersion::DEFAULT);
+        assert_eq!(ApiVersion::ALL.last(), Some(&ApiVersion::LATEST));
+    }
+
+    #[test]
+    fn test_embed_shapes() {
+        let response = embed_response(Some(180));
+
+        let legacy = embed_body(ApiVersion::V20250101, &response);
+        assert_eq!(legacy["tokens"], 128);
+        assert_eq!(legacy["truncated"], true);
+        assert_eq!(legacy["original_tokens"], 180);
+        assert!(legacy.get("usage").is_none());
+
+        let nested = embed_body(ApiVersion::V20250215, &response);
+        assert_eq!(
+            nested["usage"],
+            json!({"tokens": 128, "truncated": true, "original_tokens": 180})
+        );
+        assert!(nested.get("tokens").is_none());
+        assert_eq!(nested["model"], legacy["model"]);
+
+        // Untruncated: only the count
+        let nested = embed_body(ApiVersion::V20250215, &embed_response(None));
+        assert_eq!(nested["usage"], json!({"tokens": 128}));
+    }
+
+    #[test]
+    fn test_message_error_shapes() {
+        let legacy = message_error_body(ApiVersion::V20250101, "not_found", "Gone".to_string());
+        assert_eq!(legacy, json!({"error": "Gone"}));
+
+        let nested = message_error_body(ApiVersion::V20250215, "not_found", "Gone".to_string());
+        assert_eq!(
+            nested,
+            json!({"error": {"type": "not_found", "message": "Gone"}})
+        );
+    }
+
+    /// `/v1/embed` without credentials: the extractor's error, in the shape
+    /// of the requested version
+    async fn unauthorized(version: Option<&str>) -> (StatusCode, HeaderMap, Value) {
+        let app = Router::new()
+            .route(
+                "/v1/embed",
+                axum::routing::post(|_: crate::auth::TokenClaims| async { StatusCode::OK }),
+            )
+            .layer(axum::middleware::from_fn(version_middleware));
+
+        let mut request = Request::builder().method("POST").uri("/v1/embed");
+        if let Some(version) = version {
+            request = request.header(VERSION_HEADER, version);
+        }
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (
+            parts.status,
+            parts.headers,
+            serde_json::from_slice(&body).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_error_shape_follows_header() {
+        let (status, headers, legacy) = unauthorized(None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(headers[VERSION_HEADER], "2025-01-01");
+        assert_eq!(legacy["error"], "invalid_api_key");
+        assert_eq!(legacy["message"], "Authorization header is required");
+
+        let (status, headers, nested) = unauthorized(Some("2025-02-15")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(headers[VERSION_HEADER], "2025-02-15");
+        assert_eq!(nested["error"]["type"], "invalid_api_key");
+        assert_eq!(
+            nested["error"]["message"],
+            "Authorization header is required"
+        );
+
+        let (status, _, body) = unauthorized(Some("2030-01-01")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "unsupported_version");
+    }
+
+    #[tokio::test]
+    async fn test_pinned_version_applies_without_header() {
+        let app = Router::new()
+            .route(
+                "/v1/probe",
+                get(|| async {
+                    pin(ApiVersion::V20250215);
+                    Json(json!({ "version": current().as_str() }))
+                }),
+            )
+            .layer(axum::middleware::from_fn(version_middleware));
+
+        let send = |version: Option<&'static str>| {
+            let mut request = Request::builder().uri("/v1/probe");
+            if let Some(version) = version {
+                request = request.header(VERSION_HEADER, version);
+            }
+            app.clone().oneshot(request.body(Body::empty()).unwrap())
+        };
+
+        let response = send(None).await.unwrap();
+        assert_eq!(response.headers()[VERSION_HEADER], "2025-02-15");
+
+        // The header wins over the key's version
+        let response = send(Some("2025-01-01")).await.unwrap();
+        assert_eq!(response.headers()[VERSION_HEADER], "2025-01-01");
+    }
+
+    #[tokio::test]
+    async fn test_versions_listing() {
+        let Json(versions) = meta_versions_handler().await;
+        assert_eq!(versions.latest, ApiVersion::LATEST.as_str());
+        assert_eq!(versions.default, ApiVersion::DEFAULT.as_str());
+        assert_eq!(versions.data.len(), ApiVersion::ALL.len());
+        assert!(versions.data.iter().all(|v| v.sunset.is_none()));
+    }
+}
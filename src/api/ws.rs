@@ -0,0 +1,397 @@
+//! Embedding over a WebSocket (`GET /v1/embed/ws`)
+//!
+//! For interactive callers (search-as-you-type) that embed many tiny inputs
+//! in a row, where per-request HTTP overhead dominates. The API key is
+//! authenticated once, at the upgrade; after that each text frame
+//! `{"id", "text", "normalize"}` is answered with
+//! `{"id", "embedding", "tokens", "cached"}`, or `{"id", "error": {...}}`
+//! for a frame that failed. Frames take the same path as `/v1/embed`
+//! (validation, the key's rate limit and concurrency cap, cache, usage
+//! events), one frame at a time per session.
+//!
+//! Gated by the `websocket_embed` feature flag (on by default).
+//!
+//! Sessions may send `WS_MAX_FRAMES_PER_SECOND` frames per second (extra
+//! frames are answered with a `frame_rate_exceeded` error) and are closed
+//! after `WS_IDLE_TIMEOUT_SECS` without a frame.
+
+use axum::{
+    extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    http::{HeaderMap, Method, Uri},
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tokio::time;
+
+use super::ip_limit::ClientIp;
+use super::timings::StageTimer;
+use super::versions::{self, ApiVersion};
+use super::{
+    prepare_embedding, run_embedding, verify_request_signature, ApiError, EmbedRequest, Embedding,
+    ErrorResponse, RequestOrigin,
+};
+use crate::flags::{self, Flag};
+use crate::{auth, config, monitoring, residency};
+
+/// Largest accepted frame (inputs are bounded by the tier's `max_chars`
+/// well below this)
+const MAX_FRAME_BYTES: usize = 64 * 1024;
+
+/// Embed request frame
+#[derive(Debug, Deserialize)]
+pub struct EmbedFrame {
+    /// Echoed in the reply (any JSON value chosen by the client)
+    #[serde(default)]
+    pub id: Value,
+    pub text: String,
+    #[serde(default)]
+    pub normalize: bool,
+}
+
+/// Reply to an embed request frame
+#[derive(Debug, Serialize)]
+pub struct EmbedReply {
+    pub id: Value,
+    pub embedding: Embedding,
+    pub tokens: usize,
+    pub cached: bool,
+}
+
+/// Frames allowed per one-second window of a session
+#[derive(Debug)]
+struct FrameRate {
+    max_per_second: u32,
+    window_start: Instant,
+    frames: u32,
+}
+
+impl FrameRate {
+    fn new(max_per_second: u32, now: Instant) -> Self {
+        Self {
+            max_per_second,
+            window_start: now,
+            frames: 0,
+        }
+    }
+
+    /// Count a frame; false if it exceeds the rate
+    fn allow(&mut self, now: Instant) -> bool {
+        if self.max_per_second == 0 {
+            return true;
+        }
+        if now.duration_since(self.window_start) >= Duration::from_secs(1) {
+            self.window_start = now;
+            self.frames = 0;
+        }
+        self.frames += 1;
+        self.frames <= self.max_per_second
+    }
+}
+
+/// Stream embeddings over a WebSocket
+///
+/// Upgrades to a WebSocket after authenticating the API key (and, for keys
+/// that require it, the request signature of the upgrade request).
+pub async fn ws_embed_handler(
+    claims: auth::TokenClaims,
+    client_ip: ClientIp,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    if !claims.has_scope(auth::SCOPE_EMBED) {
+        return Err(ApiError::Unauthorized(
+            "API key is not allowed to create embeddings".to_string(),
+        ));
+    }
+    residency::check(claims.org_id()).await?;
+    flags::check(Flag::WebsocketEmbed, claims.org_id()).await?;
+    if claims.require_signing() {
+        verify_request_signature(&claims, &method, &uri, &headers, &[]).await?;
+    }
+
+    let origin = RequestOrigin::new(client_ip, &headers);
+    Ok(ws
+        .max_message_size(MAX_FRAME_BYTES)
+        .on_upgrade(move |socket| session(socket, claims, origin)))
+}
+
+/// Open sessions, for `smally_ws_connections`
+struct ConnectionGauge;
+
+impl ConnectionGauge {
+    fn open() -> Self {
+        monitoring::WS_CONNECTIONS.inc();
+        Self
+    }
+}
+
+impl Drop for ConnectionGauge {
+    fn drop(&mut self) {
+        monitoring::WS_CONNECTIONS.dec();
+    }
+}
+
+/// Answer frames until the client leaves, goes idle or the socket fails
+async fn session(mut socket: WebSocket, claims: auth::TokenClaims, origin: RequestOrigin) {
+    let _gauge = ConnectionGauge::open();
+    let settings = config::get_settings();
+    let idle_timeout = Duration::from_secs(settings.ws_idle_timeout_secs);
+    let mut rate = FrameRate::new(settings.ws_max_frames_per_second, Instant::now());
+
+    loop {
+        let message = match time::timeout(idle_timeout, socket.recv()).await {
+            Err(_) => {
+                let _ = socket
+                    .send(Message::Close(Some(CloseFrame {
+                        code: close_code::NORMAL,
+                        reason: "Idle timeout".into(),
+                    })))
+                    .await;
+                break;
+            }
+            Ok(Some(Ok(message))) => message,
+            // Disconnected
+            Ok(None) | Ok(Some(Err(_))) => break,
+        };
+
+        let reply = match message {
+            Message::Text(text) => handle_frame(&claims, &origin, &text, &mut rate).await,
+            Message::Binary(_) => error_reply(
+                Value::Null,
+                ErrorResponse::new("invalid_request", "Frames must be JSON text".to_string()),
+            ),
+            Message::Close(_) => break,
+            // Pings are answered by the socket itself
+            Message::Ping(_) | Message::Pong(_) => continue,
+        };
+
+        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Reply to one text frame
+async fn handle_frame(
+    claims: &auth::TokenClaims,
+    origin: &RequestOrigin,
+    text: &str,
+    rate: &mut FrameRate,
+) -> Value {
+    let start_time = Instant::now();
+
+    let frame: EmbedFrame = match serde_json::from_str(text) {
+        Ok(frame) => frame,
+        Err(e) => {
+            return error_reply(
+                Value::Null,
+                ErrorResponse::new("invalid_request", format!("Invalid frame: {}", e)),
+            )
+        }
+    };
+
+    if !rate.allow(start_time) {
+        return error_reply(
+            frame.id,
+            ErrorResponse::new(
+                "frame_rate_exceeded",
+                format!("More than {} frames per second", rate.max_per_second),
+            ),
+        );
+    }
+
+    let req = EmbedRequest {
+        text: frame.text,
+        normalize: frame.normalize,
+        dry_run: false,
+        skip_language_detection: true,
+        truncate: false,
+        precision: None,
+        post_process: false,
+        model: None,
+        include_timings: false,
+    };
+    let request_id = uuid::Uuid::now_v7();
+
+    let mut timer = StageTimer::start_at(start_time);
+    let embedded = match prepare_embedding(claims, &req, &mut timer).await {
+        Ok(prepared) => {
+            run_embedding(
+                claims,
+                &req,
+                &prepared,
+                origin,
+                request_id,
+                "/v1/embed/ws",
+                &mut timer,
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+
+    match embedded {
+        Ok(embedded) => {
+            monitoring::WS_FRAMES.with_label_values(&["ok"]).inc();
+            let reply = EmbedReply {
+                id: frame.id,
+                embedding: embedded.response.embedding,
+                tokens: embedded.response.tokens,
+                cached: embedded.response.cached,
+            };
+            serde_json::to_value(reply).expect("embed replies serialize")
+        }
+        Err(e) => error_reply(frame.id, e.into_parts().2),
+    }
+}
+
+/// `{"id", "error": {"type", "message", ...}}`
+fn error_reply(id: Value, error: ErrorResponse) -> Value {
+    monitoring::WS_FRAMES
+        .with_label_values(&[error.error.as_str()])
+        .inc();
+
+    let mut reply = versions::error_body(ApiVersion::LATEST, &error);
+    reply["id"] = id;
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "control-plane")]
+    use crate::models::{CreateAPIKeyRequest, TierType};
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::factory;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use axum::{routing::get, Router};
+    #[cfg(feature = "control-plane")]
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::json;
+    #[cfg(feature = "control-plane")]
+    use serial_test::serial;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite;
+    #[cfg(feature = "control-plane")]
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    /// Serve `/v1/embed/ws` on a local port
+    async fn serve() -> String {
+        let app = Router::new().route("/v1/embed/ws", get(ws_embed_handler));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("ws://{}/v1/embed/ws", addr)
+    }
+
+    #[test]
+    fn test_frame_rate() {
+        let start = Instant::now();
+        let mut rate = FrameRate::new(3, start);
+        assert!((0..3).all(|_| rate.allow(start)));
+        assert!(!rate.allow(start + Duration::from_millis(999)));
+
+        // Next window
+        assert!(rate.allow(start + Duration::from_secs(1)));
+
+        let mut unlimited = FrameRate::new(0, start);
+        assert!((0..1000).all(|_| unlimited.allow(start)));
+    }
+
+    #[test]
+    fn test_error_reply_shape() {
+        let reply = error_reply(
+            json!(7),
+            ErrorResponse::new("frame_rate_exceeded", "Slow down".to_string()),
+        );
+        assert_eq!(
+            reply,
+            json!({
+                "id": 7,
+                "error": {"type": "frame_rate_exceeded", "message": "Slow down"}
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_requires_api_key() {
+        let url = serve().await;
+        match tokio_tungstenite::connect_async(url).await {
+            Err(tungstenite::Error::Http(response)) => {
+                assert_eq!(response.status(), 401);
+            }
+            other => panic!("expected a 401 handshake, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    #[serial]
+    async fn test_streams_100_frames() {
+        setup().await;
+        cleanup_db().await;
+
+        // A tier whose per-minute limit fits 100 frames
+        let owner = factory::user("ws@example.com").await;
+        let key = factory::api_key_with(
+            &owner,
+            CreateAPIKeyRequest {
+                tier: Some(TierType::Scale),
+                ..factory::key_request("Streaming")
+            },
+        )
+        .await;
+
+        let mut request = serve().await.into_client_request().unwrap();
+        request.headers_mut().insert(
+            "authorization",
+            format!("Bearer {}", key.token).parse().unwrap(),
+        );
+        let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+        // Paced to the frame rate; ten texts new to the cache, so frames
+        // after the first ten are hits
+        let settings = config::get_settings();
+        let pace = Duration::from_millis(1000 / settings.ws_max_frames_per_second.max(1) as u64);
+        let run = uuid::Uuid::now_v7().simple();
+        for i in 0..100 {
+            let text = format!("query {} {}", run, i % 10);
+            let frame = json!({"id": i, "text": text, "normalize": true});
+            socket
+                .send(tungstenite::Message::Text(frame.to_string()))
+                .await
+                .unwrap();
+
+            let reply = match socket.next().await.unwrap().unwrap() {
+                tungstenite::Message::Text(text) => serde_json::from_str::<Value>(&text).unwrap(),
+                other => panic!("unexpected message {:?}", other),
+            };
+            assert_eq!(reply["id"], i, "{}", reply);
+            assert_eq!(
+                reply["embedding"].as_array().unwrap().len(),
+                settings.embedding_dim
+            );
+            assert!(reply["tokens"].as_u64().unwrap() > 0);
+            assert_eq!(reply["cached"], i >= 10);
+
+            time::sleep(pace).await;
+        }
+
+        // Bad frames get an error reply and keep the session open
+        socket
+            .send(tungstenite::Message::Text("not json".to_string()))
+            .await
+            .unwrap();
+        let reply = socket.next().await.unwrap().unwrap();
+        let reply: Value = serde_json::from_str(reply.to_text().unwrap()).unwrap();
+        assert_eq!(reply["error"]["type"], "invalid_request");
+
+        socket.close(None).await.unwrap();
+        cleanup_db().await;
+    }
+}
@@ -0,0 +1,144 @@
+//! Admin token registry and revocation
+//!
+//! Every admin token carries an id (the `cti` claim) and is recorded in
+//! `admin_tokens` when `create_admin_token` issues it, so operators can see
+//! which tokens exist and revoke one without rotating the signing key.
+//! Revoked ids are kept in Redis (`admin_revoked:<id>`, expiring with the
+//! token) and checked by the `AdminTokenClaims` extractor on every request.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use redis::AsyncCommands;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::database::{self, DbPool};
+use crate::redis_util::{self, RedisConnection};
+
+/// An issued admin token
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct AdminTokenRecord {
+    pub id: Uuid,
+    pub scope: String,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+fn revoked_key(id: Uuid) -> String {
+    format!("admin_revoked:{}", id.simple())
+}
+
+/// Record a token at issuance
+pub async fn record(pool: &DbPool, id: Uuid, scope: &str, expires_at: i64) -> Result<()> {
+    let expires_at = DateTime::<Utc>::from_timestamp(expires_at, 0)
+        .ok_or_else(|| anyhow::anyhow!("Invalid expiration"))?;
+
+    sqlx::query("INSERT INTO admin_tokens (id, scope, expires_at) VALUES ($1, $2, $3)")
+        .bind(id)
+        .bind(scope)
+        .bind(database::timestamp(expires_at))
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Issued tokens, latest expiry first (expired ones included)
+pub async fn list(pool: &DbPool) -> sqlx::Result<Vec<AdminTokenRecord>> {
+    sqlx::query_as::<_, AdminTokenRecord>(
+        "SELECT id, scope, issued_at, expires_at, revoked_at
+         FROM admin_tokens
+         ORDER BY expires_at DESC, id",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Revoke a token until it expires. None if no such token was issued.
+pub async fn revoke(
+    pool: &DbPool,
+    conn: &mut RedisConnection,
+    id: Uuid,
+) -> Result<Option<AdminTokenRecord>> {
+    let Some(token) = sqlx::query_as::<_, AdminTokenRecord>(
+        "UPDATE admin_tokens SET revoked_at = COALESCE(revoked_at, CURRENT_TIMESTAMP)
+         WHERE id = $1
+         RETURNING id, scope, issued_at, expires_at, revoked_at",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let remaining = (token.expires_at - Utc::now()).num_seconds();
+    if remaining > 0 {
+        let _: () = conn.set_ex(revoked_key(id), 1, remaining as u64).await?;
+    }
+    Ok(Some(token))
+}
+
+/// Whether the token has been revoked. Errors are the caller's to handle:
+/// admin access fails closed.
+pub async fn is_revoked(id: Uuid) -> Result<bool> {
+    let mut conn = redis_util::get_connection();
+    Ok(conn.exists(revoked_key(id)).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::binding::TokenBinding;
+    use crate::auth::keyring::Keyring;
+    use crate::auth::{sign_admin_token, validate_admin_token};
+    use crate::database;
+    use crate::test_utils::helpers::setup;
+    use chrono::Duration;
+    use serial_test::serial;
+
+    #[test]
+    fn test_validity_is_bounded() {
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[9; 32]);
+        let keyring = Keyring::new(vec![signing_key.verifying_key()]);
+        let binding = TokenBinding::default();
+        let token_id = Uuid::now_v7();
+        let expiration = (Utc::now() + Duration::days(10)).timestamp();
+
+        let token = sign_admin_token(token_id, "ui", expiration, &signing_key, &binding).unwrap();
+        let data = validate_admin_token(&token, &keyring, &binding, Duration::days(30)).unwrap();
+        assert_eq!(data.token_id, token_id);
+        assert_eq!(data.scope, "ui");
+
+        let err = validate_admin_token(&token, &keyring, &binding, Duration::days(7)).unwrap_err();
+        assert!(err.to_string().contains("exceeds 7 days"));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_revoked_tokens_are_listed_and_flagged() {
+        setup().await;
+        let pool = database::get_db();
+        sqlx::query("DELETE FROM admin_tokens")
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let id = Uuid::now_v7();
+        let expires_at = (Utc::now() + Duration::days(1)).timestamp();
+        record(pool, id, "ui", expires_at).await.unwrap();
+        assert!(!is_revoked(id).await.unwrap());
+
+        let mut conn = redis_util::get_connection();
+        let revoked = revoke(pool, &mut conn, id).await.unwrap().unwrap();
+        assert!(revoked.revoked_at.is_some());
+        assert!(is_revoked(id).await.unwrap());
+        assert_eq!(list(pool).await.unwrap(), vec![revoked]);
+
+        // Unknown ids aren't revoked
+        assert!(revoke(pool, &mut conn, Uuid::now_v7())
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,172 @@
+//! Issuer/audience binding for CWTs, so tokens signed in one environment
+//! (say staging) don't validate in another one that shares its keypair.
+//!
+//! Tokens carry the registered `iss` and `aud` claims from `TOKEN_ISSUER`
+//! and `TOKEN_AUDIENCE`, and verification requires exact matches. Tokens
+//! issued before binding was configured have neither claim. They are
+//! accepted only before `TOKEN_LEGACY_UNTIL`, so a deployment can re-issue
+//! keys and then stop accepting the old ones.
+
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, Utc};
+use coset::cwt::{ClaimsSet, ClaimsSetBuilder};
+
+use crate::config;
+
+/// Issuer and audience tokens are signed with and checked against
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TokenBinding {
+    /// `iss` claim (empty = not set)
+    pub issuer: String,
+    /// `aud` claim (empty = not set)
+    pub audience: String,
+    /// Tokens without `iss`/`aud` are still accepted before this date
+    pub legacy_until: Option<NaiveDate>,
+}
+
+impl TokenBinding {
+    /// Binding configured for this deployment
+    pub fn from_settings() -> Self {
+        let settings = config::get_settings();
+        Self {
+            issuer: settings.token_issuer.clone(),
+            audience: settings.token_audience.clone(),
+            legacy_until: settings.token_legacy_until,
+        }
+    }
+
+    /// Add the configured claims to a token being built
+    pub(crate) fn apply(&self, mut builder: ClaimsSetBuilder) -> ClaimsSetBuilder {
+        if !self.issuer.is_empty() {
+            builder = builder.issuer(self.issuer.clone());
+        }
+        if !self.audience.is_empty() {
+            builder = builder.audience(self.audience.clone());
+        }
+        builder
+    }
+
+    /// Check the claims of a token whose signature has been verified
+    pub(crate) fn check(&self, claims: &ClaimsSet) -> Result<()> {
+        self.check_on(claims, Utc::now().date_naive())
+    }
+
+    fn check_on(&self, claims: &ClaimsSet, today: NaiveDate) -> Result<()> {
+        let unbound = self.issuer.is_empty() && self.audience.is_empty();
+
+        if claims.issuer.is_none() && claims.audience.is_none() {
+            return match self.legacy_until {
+                _ if unbound => Ok(()),
+                Some(until) if today < until => Ok(()),
+                _ => Err(anyhow!("Token has no issuer or audience")),
+            };
+        }
+
+        if claims.issuer.as_deref().unwrap_or_default() != self.issuer {
+            return Err(anyhow!("Token issuer mismatch"));
+        }
+        if claims.audience.as_deref().unwrap_or_default() != self.audience {
+            return Err(anyhow!("Token audience mismatch"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::keyring::Keyring;
+    use crate::auth::{
+        sign_admin_token, sign_token_direct, validate_admin_token, verify_token_direct, TokenData,
+    };
+    use crate::models::TierType;
+    use uuid::Uuid;
+
+    fn binding(issuer: &str, audience: &str) -> TokenBinding {
+        TokenBinding {
+            issuer: issuer.to_string(),
+            audience: audience.to_string(),
+            legacy_until: None,
+        }
+    }
+
+    fn keys() -> (ed25519_dalek::SigningKey, Keyring) {
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let keyring = Keyring::new(vec![signing_key.verifying_key()]);
+        (signing_key, keyring)
+    }
+
+    fn token_data() -> TokenData {
+        TokenData {
+            org_id: Uuid::now_v7(),
+            key_id: Uuid::now_v7(),
+            tier: TierType::Free,
+            max_tokens: 128,
+            monthly_quota: 20000,
+            require_signing: false,
+            max_concurrency: None,
+            scopes: None,
+            models: None,
+            api_version: None,
+            strict_validation: false,
+            generation: 0,
+        }
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_api_token_rejected_under_another_issuer() {
+        let (signing_key, keyring) = keys();
+        let staging = binding("smally-staging", "smally-api");
+        let production = binding("smally-production", "smally-api");
+
+        let token = sign_token_direct(&token_data(), &signing_key, &staging).unwrap();
+        assert!(verify_token_direct(&token, &keyring, &staging).is_ok());
+        assert!(verify_token_direct(&token, &keyring, &production).is_err());
+        assert!(verify_token_direct(&token, &keyring, &binding("", "")).is_err());
+    }
+
+    #[test]
+    fn test_admin_token_rejected_under_another_audience() {
+        let (signing_key, keyring) = keys();
+        let ui = binding("smally", "smally-ui");
+        let expiration = Utc::now().timestamp() + 60;
+
+        let token = sign_admin_token(Uuid::now_v7(), "ui", expiration, &signing_key, &ui).unwrap();
+        assert!(validate_admin_token(&token, &keyring, &ui, chrono::Duration::days(1)).is_ok());
+        assert!(validate_admin_token(
+            &token,
+            &keyring,
+            &binding("smally", "smally-api"),
+            chrono::Duration::days(1)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_legacy_tokens_accepted_until_cutoff() {
+        let mut bound = binding("smally-production", "smally-api");
+        let legacy = ClaimsSetBuilder::new().build();
+
+        // Nothing configured: claim-less tokens are all there is
+        assert!(binding("", "")
+            .check_on(&legacy, date("2030-01-01"))
+            .is_ok());
+
+        // Bound without a migration window: rejected
+        assert!(bound.check_on(&legacy, date("2025-01-01")).is_err());
+
+        bound.legacy_until = Some(date("2025-03-01"));
+        assert!(bound.check_on(&legacy, date("2025-02-28")).is_ok());
+        assert!(bound.check_on(&legacy, date("2025-03-01")).is_err());
+
+        // A token with only one of the claims isn't legacy
+        let partial = ClaimsSetBuilder::new()
+            .issuer("smally-production".to_string())
+            .build();
+        assert!(bound.check_on(&partial, date("2025-02-28")).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! Ed25519 verification keys, for rotating the token signing keypair.
+//!
+//! Tokens name the key that signed them with a `kid` protected-header
+//! parameter (the first 8 bytes of the SHA-256 of the public key), so
+//! verification looks up one key instead of trying each. Tokens signed
+//! before `kid` was written are tried against every key in order.
+//!
+//! To rotate: generate a new keypair, set `TOKEN_PRIVATE_KEY` to the new
+//! private key and put the new public key first in `TOKEN_PUBLIC_KEYS`.
+//! Old keys keep validating until their public key is removed, which
+//! `GET /v1/admin/signing-keys` shows is safe once no active key uses it.
+
+use anyhow::{anyhow, Result};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use once_cell::sync::Lazy;
+use sha2::{Digest, Sha256};
+
+use crate::config;
+
+/// Length of a key id in bytes (hex-encoded it's twice that)
+const KID_LEN: usize = 8;
+
+/// Key id of a public key
+pub fn kid(key: &VerifyingKey) -> Vec<u8> {
+    Sha256::digest(key.as_bytes())[..KID_LEN].to_vec()
+}
+
+/// Key id as shown in the admin API and stored on API keys
+pub fn kid_hex(key: &VerifyingKey) -> String {
+    hex::encode(kid(key))
+}
+
+/// Kid new tokens are signed with (`TOKEN_PRIVATE_KEY`), None if the
+/// private key isn't a valid Ed25519 key
+pub fn signing_kid() -> Option<String> {
+    hex::decode(&config::get_settings().token_private_key)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .map(|bytes| kid_hex(&ed25519_dalek::SigningKey::from_bytes(&bytes).verifying_key()))
+}
+
+/// Public keys tokens may be signed with, newest first
+#[derive(Debug, Clone, Default)]
+pub struct Keyring {
+    keys: Vec<(Vec<u8>, VerifyingKey)>,
+}
+
+impl Keyring {
+    pub fn new(keys: Vec<VerifyingKey>) -> Self {
+        Self {
+            keys: keys.into_iter().map(|key| (kid(&key), key)).collect(),
+        }
+    }
+
+    /// Keys from hex-encoded public keys
+    pub fn from_hex(keys: &[String]) -> Result<Self> {
+        let keys = keys
+            .iter()
+            .map(|key_hex| {
+                let bytes =
+                    hex::decode(key_hex).map_err(|e| anyhow!("Invalid public key: {}", e))?;
+                let bytes: [u8; 32] = bytes[..]
+                    .try_into()
+                    .map_err(|_| anyhow!("Invalid public key length"))?;
+                VerifyingKey::from_bytes(&bytes).map_err(|e| anyhow!("Invalid public key: {}", e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::new(keys))
+    }
+
+    /// Keys configured by `TOKEN_PUBLIC_KEYS` (or `TOKEN_PUBLIC_KEY`)
+    pub fn from_settings() -> Result<Self> {
+        Self::from_hex(&config::get_settings().token_public_keys)
+    }
+
+    /// Keys of `from_settings`, parsed on first use and shared after that
+    /// (the settings don't change while the process runs)
+    pub fn configured() -> Result<&'static Keyring> {
+        static CONFIGURED: Lazy<Result<Keyring, String>> =
+            Lazy::new(|| Keyring::from_settings().map_err(|e| e.to_string()));
+        CONFIGURED.as_ref().map_err(|e| anyhow!("{}", e))
+    }
+
+    /// Hex key ids, newest first
+    pub fn kids(&self) -> Vec<String> {
+        self.keys.iter().map(|(kid, _)| hex::encode(kid)).collect()
+    }
+
+    /// Verify `signature` over `data` with the key named by `kid`, or with
+    /// any key when the token predates `kid`
+    pub fn verify(&self, kid: &[u8], data: &[u8], signature: &Signature) -> Result<()> {
+        if kid.is_empty() {
+            return self
+                .keys
+                .iter()
+                .find(|(_, key)| key.verify(data, signature).is_ok())
+                .map(|_| ())
+                .ok_or_else(|| anyhow!("Signature verification failed"));
+        }
+
+        let (_, key) = self
+            .keys
+            .iter()
+            .find(|(id, _)| id == kid)
+            .ok_or_else(|| anyhow!("Unknown signing key id {}", hex::encode(kid)))?;
+        key.verify(data, signature)
+            .map_err(|e| anyhow!("Signature verification failed: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::binding::TokenBinding;
+    use crate::auth::{
+        sign_admin_token, sign_token_direct, validate_admin_token, verify_token_direct, TokenData,
+    };
+    use crate::models::TierType;
+    use ed25519_dalek::SigningKey;
+    use uuid::Uuid;
+
+    fn token_data() -> TokenData {
+        TokenData {
+            org_id: Uuid::now_v7(),
+            key_id: Uuid::now_v7(),
+            tier: TierType::Free,
+            max_tokens: 128,
+            monthly_quota: 20000,
+            require_signing: false,
+            max_concurrency: None,
+            scopes: None,
+            models: None,
+            api_version: None,
+            strict_validation: false,
+            generation: 0,
+        }
+    }
+
+    #[test]
+    fn test_kid_is_stable_and_distinct() {
+        let old = SigningKey::from_bytes(&[1; 32]).verifying_key();
+        let new = SigningKey::from_bytes(&[2; 32]).verifying_key();
+
+        assert_eq!(kid_hex(&old), kid_hex(&old));
+        assert_eq!(kid_hex(&old).len(), KID_LEN * 2);
+        assert_ne!(kid_hex(&old), kid_hex(&new));
+
+        let keyring =
+            Keyring::from_hex(&[hex::encode(new.as_bytes()), hex::encode(old.as_bytes())]).unwrap();
+        assert_eq!(keyring.kids(), vec![kid_hex(&new), kid_hex(&old)]);
+        assert!(Keyring::from_hex(&["abcd".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_old_kid_verifies_after_rotation() {
+        let old = SigningKey::from_bytes(&[1; 32]);
+        let new = SigningKey::from_bytes(&[2; 32]);
+        let binding = TokenBinding::default();
+        let rotated = Keyring::new(vec![new.verifying_key(), old.verifying_key()]);
+
+        let old_token = sign_token_direct(&token_data(), &old, &binding).unwrap();
+        let new_token = sign_token_direct(&token_data(), &new, &binding).unwrap();
+        assert!(verify_token_direct(&old_token, &rotated, &binding).is_ok());
+        assert!(verify_token_direct(&new_token, &rotated, &binding).is_ok());
+
+        let expiration = chrono::Utc::now().timestamp() + 60;
+        let admin_token =
+            sign_admin_token(Uuid::now_v7(), "ui", expiration, &old, &binding).unwrap();
+        assert!(
+            validate_admin_token(&admin_token, &rotated, &binding, chrono::Duration::days(1))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_unknown_kid_rejected() {
+        let old = SigningKey::from_bytes(&[1; 32]);
+        let new = SigningKey::from_bytes(&[2; 32]);
+        let binding = TokenBinding::default();
+
+        // The old public key has been dropped
+        let keyring = Keyring::new(vec![new.verifying_key()]);
+        let token = sign_token_direct(&token_data(), &old, &binding).unwrap();
+        let err = verify_token_direct(&token, &keyring, &binding).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<crate::auth::TokenError>(),
+            Some(&crate::auth::TokenError::BadSignature)
+        );
+
+        let expiration = chrono::Utc::now().timestamp() + 60;
+        let admin_token =
+            sign_admin_token(Uuid::now_v7(), "ui", expiration, &old, &binding).unwrap();
+        assert!(
+            validate_admin_token(&admin_token, &keyring, &binding, chrono::Duration::days(1))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_generation_claim_survives_signing() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let binding = TokenBinding::default();
+        let keyring = Keyring::new(vec![key.verifying_key()]);
+
+        // Keys issued before reissuing existed carry no generation: 0
+        let original = sign_token_direct(&token_data(), &key, &binding).unwrap();
+        let claims = verify_token_direct(&original, &keyring, &binding).unwrap();
+        assert_eq!(claims.generation(), 0);
+
+        let reissued = sign_token_direct(
+            &TokenData {
+                generation: 2,
+                ..token_data()
+            },
+            &key,
+            &binding,
+        )
+        .unwrap();
+        let claims = verify_token_direct(&reissued, &keyring, &binding).unwrap();
+        assert_eq!(claims.generation(), 2);
+    }
+
+    #[test]
+    fn test_tokens_without_kid_try_each_key() {
+        let old = SigningKey::from_bytes(&[1; 32]);
+        let new = SigningKey::from_bytes(&[2; 32]);
+        let keyring = Keyring::new(vec![new.verifying_key(), old.verifying_key()]);
+
+        use ed25519_dalek::Signer;
+        let signature = old.sign(b"payload");
+        assert!(keyring.verify(&[], b"payload", &signature).is_ok());
+        assert!(keyring.verify(&[], b"tampered", &signature).is_err());
+        assert!(Keyring::new(vec![new.verifying_key()])
+            .verify(&[], b"payload", &signature)
+            .is_err());
+    }
+
+    #[test]
+    fn test_measure_verification() {
+        // The throwaway token verifies under this deployment's binding
+        let mean = crate::auth::measure_verification(10).unwrap();
+        assert!(mean > std::time::Duration::ZERO);
+    }
+}
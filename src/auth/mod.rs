@@ -1,12 +1,10 @@
 use anyhow::{anyhow, Result};
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use coset::{
     cwt::{ClaimsSetBuilder, Timestamp},
     iana, CborSerializable, CoseSign1Builder, HeaderBuilder,
 };
 use dashmap::DashMap;
-use redis::aio::ConnectionManager;
-use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -14,10 +12,36 @@ use std::time::{Duration, Instant};
 use tracing::{info, warn};
 use uuid::Uuid;
 
-use crate::config;
+use crate::database::DbPool;
 use crate::models::TierType;
-
+use crate::redis_util::RedisConnection;
+
+use self::binding::TokenBinding;
+use self::keyring::Keyring;
+pub use self::token_error::TokenError;
+
+pub mod admin;
+pub mod binding;
+pub mod keyring;
+pub mod opaque;
+#[cfg(feature = "control-plane")]
+pub mod sealed;
+#[cfg(feature = "control-plane")]
+pub mod service_account;
+#[cfg(feature = "control-plane")]
 pub mod session;
+pub mod signing;
+pub mod token_error;
+
+/// Scope allowing `/v1/embed`
+pub const SCOPE_EMBED: &str = "embed";
+/// Scope allowing `/v1/organizations/:org_id/metrics`
+pub const SCOPE_METRICS_READ: &str = "metrics:read";
+/// Scopes an API key may be issued with
+pub const SCOPES: [&str; 2] = [SCOPE_EMBED, SCOPE_METRICS_READ];
+
+/// Prefix of service account tokens (see `service_account`)
+pub const SERVICE_ACCOUNT_TOKEN_PREFIX: &str = "sa_";
 
 /// CBOR-encoded token data (ultra-compact binary format with fixed-length fields)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,11 +61,40 @@ pub struct TokenData {
     /// Monthly quota
     #[serde(rename = "q")]
     pub monthly_quota: i32,
+    /// Requests must carry an HMAC signature (see `signing`)
+    #[serde(rename = "r", default)]
+    pub require_signing: bool,
+    /// Per-key concurrent request cap (None = the tier's)
+    #[serde(rename = "c", default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<u32>,
+    /// What the key may access (None = embed only, as for keys issued before scopes)
+    #[serde(rename = "s", default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<String>>,
+    /// Models the key may use (None = any the organization allows)
+    #[serde(rename = "n", default, skip_serializing_if = "Option::is_none")]
+    pub models: Option<Vec<String>>,
+    /// API version pinned at creation (None = the default, as for keys
+    /// issued before versioning)
+    #[serde(rename = "v", default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    /// Reject request bodies with unknown fields (see `api::strict`)
+    #[serde(rename = "u", default)]
+    pub strict_validation: bool,
+    /// Incremented each time the key is reissued with current claims (see
+    /// `services::key_reissue`); 0 for tokens issued at creation
+    #[serde(rename = "g", default)]
+    pub generation: u32,
 }
 
 /// Admin token data - simpler token for UI/admin operations (no quotas/usage tracking)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AdminTokenData {
+    /// Token id (`cti`), recorded at issuance and used for revocation
+    #[serde(rename = "j")]
+    pub token_id: Uuid,
+    /// Issue time (Unix timestamp)
+    #[serde(rename = "i")]
+    pub issued_at: i64,
     /// Expiration time (Unix timestamp)
     #[serde(rename = "e")]
     pub expiration: i64,
@@ -55,16 +108,21 @@ pub struct AdminTokenData {
 pub struct TokenClaims {
     /// Decoded token data (cached for efficiency)
     data: TokenData,
+    /// Tier the organization is served as right now, when above the signed
+    /// one (a trial, see `billing::trial`); never signed into the token
+    tier_override: Option<TierType>,
 }
 
 impl TokenClaims {
     /// Create TokenClaims from TokenData
     pub fn from_token_data(data: TokenData) -> Self {
-        Self { data }
+        Self {
+            data,
+            tier_override: None,
+        }
     }
 
     /// Get CBOR-encoded bytes
-    #[allow(dead_code)]
     pub fn to_cbor_bytes(&self) -> Result<Vec<u8>, anyhow::Error> {
         let mut cbor_bytes = Vec::new();
         ciborium::into_writer(&self.data, &mut cbor_bytes)?;
@@ -72,10 +130,9 @@ impl TokenClaims {
     }
 
     /// Decode from CBOR bytes
-    #[allow(dead_code)]
     pub fn from_cbor_bytes(cbor_bytes: &[u8]) -> Result<Self, anyhow::Error> {
         let data: TokenData = ciborium::from_reader(cbor_bytes)?;
-        Ok(Self { data })
+        Ok(Self::from_token_data(data))
     }
 
     /// Get org_id
@@ -88,13 +145,23 @@ impl TokenClaims {
         self.data.key_id
     }
 
-    /// Get tier
+    /// Tier the request is served as: the signed tier, unless overridden
     pub fn tier(&self) -> Result<TierType, anyhow::Error> {
-        Ok(self.data.tier)
+        Ok(self.tier_override.unwrap_or(self.data.tier))
+    }
+
+    /// Tier signed into the token at creation
+    pub fn signed_tier(&self) -> TierType {
+        self.data.tier
+    }
+
+    /// Serve the request as `tier` (None or the signed tier = no override)
+    pub fn with_tier(mut self, tier: TierType) -> Self {
+        self.tier_override = (tier != self.data.tier).then_some(tier);
+        self
     }
 
     /// Get max_tokens
-    #[allow(dead_code)]
     pub fn max_tokens(&self) -> usize {
         self.data.max_tokens as usize
     }
@@ -103,6 +170,51 @@ impl TokenClaims {
     pub fn monthly_quota(&self) -> i32 {
         self.data.monthly_quota
     }
+
+    /// Whether requests with this key must be HMAC-signed
+    pub fn require_signing(&self) -> bool {
+        self.data.require_signing
+    }
+
+    /// Whether the key was issued with `scope`
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match &self.data.scopes {
+            Some(scopes) => scopes.iter().any(|s| s == scope),
+            None => scope == SCOPE_EMBED,
+        }
+    }
+
+    /// Models the key was restricted to at creation (None = unrestricted)
+    pub fn models(&self) -> Option<&[String]> {
+        self.data.models.as_deref()
+    }
+
+    /// API version pinned to the key at creation
+    pub fn api_version(&self) -> Option<&str> {
+        self.data.api_version.as_deref()
+    }
+
+    /// Whether requests with this key get strict body validation
+    pub fn strict_validation(&self) -> bool {
+        self.data.strict_validation
+    }
+
+    /// Reissue generation of the token (0 = issued at creation)
+    pub fn generation(&self) -> u32 {
+        self.data.generation
+    }
+
+    /// Concurrent request cap of this key (0 = unlimited)
+    pub fn max_concurrency(&self) -> u32 {
+        let tier = self.tier_override.unwrap_or(self.data.tier);
+        let tier_cap = tier.limits().max_concurrency;
+        match self.data.max_concurrency {
+            // A cap picked under a trial is lowered with the tier when it ends
+            Some(cap) if tier_cap > 0 => cap.min(tier_cap),
+            Some(cap) => cap,
+            None => tier_cap,
+        }
+    }
 }
 
 /// Maximum allowed CBOR payload size (2KB - reasonable for CWT ClaimsSet)
@@ -115,10 +227,12 @@ const MAX_CBOR_SIZE: usize = 2048;
 pub fn sign_token_direct(
     token_data: &TokenData,
     signing_key: &ed25519_dalek::SigningKey,
+    binding: &TokenBinding,
 ) -> Result<String, anyhow::Error> {
     // Build CWT ClaimsSet with custom claims
     // Use text claims for compact encoding (single-letter keys)
-    let claims = ClaimsSetBuilder::new()
+    let mut builder = binding
+        .apply(ClaimsSetBuilder::new())
         .text_claim(
             "o".to_string(),
             ciborium::value::Value::Text(token_data.org_id.to_string()),
@@ -138,8 +252,57 @@ pub fn sign_token_direct(
         .text_claim(
             "q".to_string(),
             ciborium::value::Value::Integer((token_data.monthly_quota as i64).into()),
-        )
-        .build();
+        );
+
+    // Only emitted when set, so existing keys keep their exact encoding
+    if token_data.require_signing {
+        builder = builder.text_claim("r".to_string(), ciborium::value::Value::Bool(true));
+    }
+    if token_data.strict_validation {
+        builder = builder.text_claim("u".to_string(), ciborium::value::Value::Bool(true));
+    }
+    if token_data.generation > 0 {
+        builder = builder.text_claim(
+            "g".to_string(),
+            ciborium::value::Value::Integer(token_data.generation.into()),
+        );
+    }
+    if let Some(scopes) = &token_data.scopes {
+        builder = builder.text_claim(
+            "s".to_string(),
+            ciborium::value::Value::Array(
+                scopes
+                    .iter()
+                    .map(|scope| ciborium::value::Value::Text(scope.clone()))
+                    .collect(),
+            ),
+        );
+    }
+    if let Some(models) = &token_data.models {
+        builder = builder.text_claim(
+            "n".to_string(),
+            ciborium::value::Value::Array(
+                models
+                    .iter()
+                    .map(|model| ciborium::value::Value::Text(model.clone()))
+                    .collect(),
+            ),
+        );
+    }
+    if let Some(api_version) = &token_data.api_version {
+        builder = builder.text_claim(
+            "v".to_string(),
+            ciborium::value::Value::Text(api_version.clone()),
+        );
+    }
+    if let Some(max_concurrency) = token_data.max_concurrency {
+        builder = builder.text_claim(
+            "c".to_string(),
+            ciborium::value::Value::Integer(max_concurrency.into()),
+        );
+    }
+
+    let claims = builder.build();
 
     // Serialize ClaimsSet to CBOR
     let claims_bytes = claims
@@ -155,9 +318,10 @@ pub fn sign_token_direct(
         ));
     }
 
-    // Create COSE protected header with EdDSA algorithm
+    // Create COSE protected header with EdDSA algorithm and the signing key's id
     let protected = HeaderBuilder::new()
         .algorithm(iana::Algorithm::EdDSA)
+        .key_id(keyring::kid(&signing_key.verifying_key()))
         .build();
 
     // Create COSE_Sign1 structure with ClaimsSet as payload
@@ -185,46 +349,48 @@ pub fn sign_token_direct(
 }
 
 /// Verify and decode CWT token using COSET
-/// Validates COSE structure, Ed25519 signature, and decodes CWT ClaimsSet
+/// Validates COSE structure, Ed25519 signature, and decodes CWT ClaimsSet.
+/// Failures before the claims are read are a `TokenError`.
 pub fn verify_token_direct(
     token: &str,
-    verifying_key: &ed25519_dalek::VerifyingKey,
+    keys: &Keyring,
+    binding: &TokenBinding,
 ) -> Result<TokenClaims, anyhow::Error> {
+    let max_len = MAX_CBOR_SIZE + 200; // ClaimsSet + COSE overhead
+
     // Decode base64
-    let cwt_bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, token)?;
+    let cwt_bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, token)
+        .map_err(|_| token_error::undecodable(token, max_len))?;
+    let incomplete = |reason| {
+        token_error::truncation(token.len(), &cwt_bytes, max_len)
+            .unwrap_or(TokenError::Malformed(reason))
+    };
 
     // Validate size constraints
     if cwt_bytes.len() < 100 {
-        return Err(anyhow!("Token too short: minimum CWT size is ~100 bytes"));
+        return Err(incomplete("minimum CWT size is ~100 bytes").into());
     }
-
-    let max_len = MAX_CBOR_SIZE + 200; // ClaimsSet + COSE overhead
     if cwt_bytes.len() > max_len {
-        return Err(anyhow!(
-            "Token too large: {} bytes exceeds maximum",
-            cwt_bytes.len()
-        ));
+        return Err(TokenError::Malformed("token exceeds the maximum CWT size").into());
     }
 
     // Deserialize COSE_Sign1 from CBOR
     let sign1 = coset::CoseSign1::from_slice(&cwt_bytes)
-        .map_err(|e| anyhow!("Invalid COSE_Sign1 structure: {}", e))?;
+        .map_err(|_| incomplete("not a COSE_Sign1 structure"))?;
 
     // Verify algorithm is EdDSA
     let protected = &sign1.protected.header;
     if protected.alg != Some(coset::Algorithm::Assigned(iana::Algorithm::EdDSA)) {
-        return Err(anyhow!("Invalid algorithm: expected EdDSA"));
+        return Err(TokenError::WrongAlgorithm.into());
     }
 
-    // Verify signature using COSE Sig_structure
-    use ed25519_dalek::Verifier;
+    // Verify signature using COSE Sig_structure, with the key named by `kid`
     let tbs = sign1.tbs_data(b"Signature1");
     let signature = ed25519_dalek::Signature::from_slice(&sign1.signature)
-        .map_err(|e| anyhow!("Invalid signature format: {}", e))?;
+        .map_err(|_| TokenError::BadSignature)?;
 
-    verifying_key
-        .verify(&tbs, &signature)
-        .map_err(|e| anyhow!("Signature verification failed: {}", e))?;
+    keys.verify(&protected.key_id, &tbs, &signature)
+        .map_err(|_| TokenError::BadSignature)?;
 
     // Extract and deserialize CWT ClaimsSet from payload
     let payload = sign1
@@ -235,12 +401,22 @@ pub fn verify_token_direct(
     let claims = coset::cwt::ClaimsSet::from_slice(payload)
         .map_err(|e| anyhow!("Invalid CWT ClaimsSet: {}", e))?;
 
+    // Issuer/audience must be this environment's
+    binding.check(&claims)?;
+
     // Extract custom text claims
     let mut org_id_str = None;
     let mut key_id_str = None;
     let mut tier_value = None;
     let mut max_tokens_value = None;
     let mut monthly_quota_value = None;
+    let mut require_signing = false;
+    let mut strict_validation = false;
+    let mut max_concurrency = None;
+    let mut scopes = None;
+    let mut models = None;
+    let mut api_version = None;
+    let mut generation = 0;
 
     for (name, value) in &claims.rest {
         match name {
@@ -275,6 +451,51 @@ pub fn verify_token_direct(
                     monthly_quota_value = Some(val as i32);
                 }
             }
+            coset::cwt::ClaimName::Text(key) if key == "r" => {
+                if let ciborium::value::Value::Bool(b) = value {
+                    require_signing = *b;
+                }
+            }
+            coset::cwt::ClaimName::Text(key) if key == "u" => {
+                if let ciborium::value::Value::Bool(b) = value {
+                    strict_validation = *b;
+                }
+            }
+            coset::cwt::ClaimName::Text(key) if key == "s" => {
+                if let ciborium::value::Value::Array(values) = value {
+                    scopes = Some(
+                        values
+                            .iter()
+                            .filter_map(|v| v.as_text().map(String::from))
+                            .collect(),
+                    );
+                }
+            }
+            coset::cwt::ClaimName::Text(key) if key == "n" => {
+                if let ciborium::value::Value::Array(values) = value {
+                    models = Some(
+                        values
+                            .iter()
+                            .filter_map(|v| v.as_text().map(String::from))
+                            .collect(),
+                    );
+                }
+            }
+            coset::cwt::ClaimName::Text(key) if key == "v" => {
+                if let ciborium::value::Value::Text(s) = value {
+                    api_version = Some(s.clone());
+                }
+            }
+            coset::cwt::ClaimName::Text(key) if key == "c" => {
+                if let ciborium::value::Value::Integer(i) = value {
+                    max_concurrency = u32::try_from(*i).ok();
+                }
+            }
+            coset::cwt::ClaimName::Text(key) if key == "g" => {
+                if let ciborium::value::Value::Integer(i) = value {
+                    generation = u32::try_from(*i).unwrap_or(0);
+                }
+            }
             _ => {} // Ignore unknown claims
         }
     }
@@ -300,6 +521,13 @@ pub fn verify_token_direct(
         tier,
         max_tokens,
         monthly_quota,
+        require_signing,
+        max_concurrency,
+        scopes,
+        models,
+        api_version,
+        strict_validation,
+        generation,
     };
 
     Ok(TokenClaims::from_token_data(token_data))
@@ -313,86 +541,188 @@ pub struct TokenLimits {
     pub monthly_quota: i32,
 }
 
+/// Mean time of one `verify_token_direct` over `iterations` runs, on a
+/// token signed with a throwaway key and this deployment's binding (no key
+/// material needed, and the cost is the same as with the real keys)
+pub fn measure_verification(iterations: u32) -> Result<Duration> {
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&rand::random());
+    let keys = Keyring::new(vec![signing_key.verifying_key()]);
+    let binding = TokenBinding::from_settings();
+    let data = TokenData {
+        org_id: Uuid::now_v7(),
+        key_id: Uuid::now_v7(),
+        tier: TierType::Free,
+        max_tokens: 128,
+        monthly_quota: 20000,
+        require_signing: false,
+        max_concurrency: None,
+        scopes: Some(vec![SCOPE_EMBED.to_string()]),
+        models: None,
+        api_version: None,
+        strict_validation: false,
+        generation: 0,
+    };
+    let token = sign_token_direct(&data, &signing_key, &binding)?;
+
+    let started = Instant::now();
+    for _ in 0..iterations {
+        verify_token_direct(&token, &keys, &binding)?;
+    }
+    Ok(started.elapsed() / iterations.max(1))
+}
+
+/// Redis key marking every token of a key as revoked
+pub fn revoked_key(key_id: Uuid) -> String {
+    format!("revoked:{}", key_id)
+}
+
+/// Redis key holding the oldest generation of a key's tokens still valid
+/// (older ones were superseded by a reissue and revoked)
+pub fn revoked_before_key(key_id: Uuid) -> String {
+    format!("revoked_before:{}", key_id)
+}
+
 /// Revocation status cache entry
 #[derive(Clone)]
 struct RevocationStatus {
     is_revoked: bool,
+    /// Tokens of an older generation are revoked
+    min_generation: u32,
     fresh_until: Instant,
     valid_until: Instant,
     refreshing: Arc<AtomicBool>,
 }
 
+impl RevocationStatus {
+    fn verdict(&self, claims: TokenClaims) -> Result<TokenClaims> {
+        if self.is_revoked {
+            Err(anyhow!("Token revoked"))
+        } else if claims.generation() < self.min_generation {
+            Err(anyhow!("Token revoked (superseded by a reissued token)"))
+        } else {
+            Ok(claims)
+        }
+    }
+}
+
 /// Token validator with stale-while-revalidate revocation checking
+///
+/// A key is revoked when Redis says so (`revoked:<key_id>`, written first
+/// by `services::api_keys::revoke`) or, with `with_key_rows`, when its
+/// `api_keys` row is inactive or gone. Revocations made in this process
+/// apply at once (`mark_revoked`); other instances see them when their
+/// cached status turns stale (`fresh_ttl`).
 pub struct TokenValidator {
-    public_key: Vec<u8>,
+    keyring: Keyring,
     revocation_cache: Arc<DashMap<String, RevocationStatus>>,
-    redis_client: ConnectionManager,
+    redis_client: RedisConnection,
+    /// Where key rows are looked up (None: Redis only)
+    key_rows: Option<DbPool>,
     fresh_ttl: Duration,
     stale_ttl: Duration,
+    binding: TokenBinding,
 }
 
 impl TokenValidator {
     /// Create a new token validator
     pub async fn new(
-        public_key_hex: &str,
-        redis_client: ConnectionManager,
+        keyring: Keyring,
+        redis_client: RedisConnection,
         fresh_ttl_seconds: u64,
         stale_ttl_seconds: u64,
+        binding: TokenBinding,
     ) -> Result<Self> {
-        let public_key = hex::decode(public_key_hex)?;
-
         Ok(Self {
-            public_key,
+            keyring,
             revocation_cache: Arc::new(DashMap::new()),
             redis_client,
+            key_rows: None,
             fresh_ttl: Duration::from_secs(fresh_ttl_seconds),
             stale_ttl: Duration::from_secs(stale_ttl_seconds),
+            binding,
         })
     }
 
+    /// Validator for the configured keys and binding, caching revocation
+    /// status for 5 minutes (fresh) and up to an hour (stale)
+    pub async fn from_settings(redis_client: RedisConnection) -> Result<Self> {
+        Self::new(
+            Keyring::configured()?.clone(),
+            redis_client,
+            300,  // 5 minutes fresh TTL
+            3600, // 60 minutes stale TTL
+            TokenBinding::from_settings(),
+        )
+        .await
+    }
+
+    /// Also treat keys whose `api_keys` row is inactive or missing (purged)
+    /// as revoked
+    pub fn with_key_rows(mut self, pool: DbPool) -> Self {
+        self.key_rows = Some(pool);
+        self
+    }
+
+    /// Revoke a key in this process's cache at once, without waiting for
+    /// its status to turn stale (Redis is expected to say so already)
+    pub fn mark_revoked(&self, key_id: Uuid) {
+        let now = Instant::now();
+        self.revocation_cache.insert(
+            key_id.to_string(),
+            RevocationStatus {
+                is_revoked: true,
+                min_generation: 0,
+                fresh_until: now + self.fresh_ttl,
+                valid_until: now + self.stale_ttl,
+                refreshing: Arc::new(AtomicBool::new(false)),
+            },
+        );
+    }
+
     /// Validate a directly signed token with stale-while-revalidate revocation checking
     pub async fn validate(&self, token: &str) -> Result<TokenClaims> {
         // Step 1: Verify Ed25519 signature (~10μs, no network)
-        let verifying_key = ed25519_dalek::VerifyingKey::from_bytes(
-            &self.public_key[..]
-                .try_into()
-                .map_err(|_| anyhow!("Invalid public key length"))?,
-        )?;
-        let claims = verify_token_direct(token, &verifying_key)?;
+        let claims = verify_token_direct(token, &self.keyring, &self.binding)?;
 
         // Step 2: Check revocation with stale-while-revalidate
-        let key_id = claims.key_id().to_string();
+        self.check_revocation(claims).await
+    }
 
-        if let Some(status) = self.revocation_cache.get(&key_id) {
+    /// Reject claims whose key has been revoked, or whose generation was
+    /// superseded by a reissue and revoked (stale-while-revalidate cache in
+    /// front of Redis). Shared by CWT and opaque key authentication.
+    pub async fn check_revocation(&self, claims: TokenClaims) -> Result<TokenClaims> {
+        let key_id = claims.key_id();
+        let cache_key = key_id.to_string();
+
+        if let Some(status) = self.revocation_cache.get(&cache_key) {
             let now = Instant::now();
 
             // Case 1: Fresh - serve immediately
             if now < status.fresh_until {
-                if status.is_revoked {
-                    return Err(anyhow!("Token revoked"));
-                }
-                return Ok(claims);
+                return status.verdict(claims);
             }
 
             // Case 2: Stale but valid - serve stale + refresh in background
             if now < status.valid_until {
-                let result = if status.is_revoked {
-                    Err(anyhow!("Token revoked"))
-                } else {
-                    Ok(claims.clone())
-                };
+                let result = status.verdict(claims);
 
                 // Trigger background refresh (only if not already refreshing)
                 if !status.refreshing.swap(true, Ordering::Relaxed) {
                     let cache = self.revocation_cache.clone();
                     let redis = self.redis_client.clone();
-                    let key_id = key_id.clone();
+                    let key_rows = self.key_rows.clone();
                     let fresh_ttl = self.fresh_ttl;
                     let stale_ttl = self.stale_ttl;
 
                     tokio::spawn(async move {
                         if let Err(e) = Self::refresh_revocation_status(
-                            &cache, &redis, &key_id, fresh_ttl, stale_ttl,
+                            &cache,
+                            &redis,
+                            key_rows.as_ref(),
+                            key_id,
+                            fresh_ttl,
+                            stale_ttl,
                         )
                         .await
                         {
@@ -406,60 +736,82 @@ impl TokenValidator {
 
             // Case 3: Expired - remove from cache, fall through to Redis check
             drop(status);
-            self.revocation_cache.remove(&key_id);
+            self.revocation_cache.remove(&cache_key);
         }
 
         // Cache miss or expired - check Redis (blocking, but rare)
-        let is_revoked = self.check_redis_revocation(&key_id).await?;
+        let (is_revoked, min_generation) =
+            Self::fetch_revocation(&self.redis_client, self.key_rows.as_ref(), key_id).await?;
 
         // Cache the result
         let now = Instant::now();
-        self.revocation_cache.insert(
-            key_id.clone(),
-            RevocationStatus {
-                is_revoked,
-                fresh_until: now + self.fresh_ttl,
-                valid_until: now + self.stale_ttl,
-                refreshing: Arc::new(AtomicBool::new(false)),
-            },
-        );
+        let status = RevocationStatus {
+            is_revoked,
+            min_generation,
+            fresh_until: now + self.fresh_ttl,
+            valid_until: now + self.stale_ttl,
+            refreshing: Arc::new(AtomicBool::new(false)),
+        };
+        let result = status.verdict(claims);
+        self.revocation_cache.insert(cache_key, status);
+        result
+    }
 
+    /// Whether a key is revoked, and its oldest valid generation, from Redis
+    /// and then (if Redis doesn't say revoked) the key's row
+    async fn fetch_revocation(
+        redis: &RedisConnection,
+        key_rows: Option<&DbPool>,
+        key_id: Uuid,
+    ) -> Result<(bool, u32)> {
+        let mut conn = redis.clone();
+        let (is_revoked, min_generation): (bool, Option<u32>) = redis::pipe()
+            .exists(revoked_key(key_id))
+            .get(revoked_before_key(key_id))
+            .query_async(&mut conn)
+            .await
+            .unwrap_or((false, None));
+        let min_generation = min_generation.unwrap_or(0);
         if is_revoked {
-            Err(anyhow!("Token revoked"))
-        } else {
-            Ok(claims)
+            return Ok((true, min_generation));
         }
-    }
 
-    /// Check if a key is revoked in Redis
-    async fn check_redis_revocation(&self, key_id: &str) -> Result<bool> {
-        let mut conn = self.redis_client.clone();
-        let exists: bool = conn
-            .exists(format!("revoked:{}", key_id))
-            .await
-            .unwrap_or(false);
-        Ok(exists)
+        let Some(pool) = key_rows else {
+            return Ok((false, min_generation));
+        };
+        // A revocation whose Redis write was lost, or a purged key. The
+        // database being down doesn't revoke anything.
+        let active: Result<Option<bool>, sqlx::Error> =
+            sqlx::query_scalar("SELECT is_active FROM api_keys WHERE key_id = $1")
+                .bind(key_id)
+                .fetch_optional(pool)
+                .await;
+        match active {
+            Ok(active) => Ok((active != Some(true), min_generation)),
+            Err(e) => {
+                warn!("Could not read API key {} for revocation: {}", key_id, e);
+                Ok((false, min_generation))
+            }
+        }
     }
 
     /// Background refresh of revocation status
     async fn refresh_revocation_status(
         cache: &DashMap<String, RevocationStatus>,
-        redis: &ConnectionManager,
-        key_id: &str,
+        redis: &RedisConnection,
+        key_rows: Option<&DbPool>,
+        key_id: Uuid,
         fresh_ttl: Duration,
         stale_ttl: Duration,
     ) -> Result<()> {
-        let mut conn = redis.clone();
-        let is_revoked: bool = conn
-            .exists(format!("revoked:{}", key_id))
-            .await
-            .unwrap_or(false);
+        let (is_revoked, min_generation) = Self::fetch_revocation(redis, key_rows, key_id).await?;
 
         let now = Instant::now();
         cache.insert(
             key_id.to_string(),
             RevocationStatus {
                 is_revoked,
+                min_generation,
                 fresh_until: now + fresh_ttl,
                 valid_until: now + stale_ttl,
                 refreshing: Arc::new(AtomicBool::new(false)),
@@ -467,12 +819,28 @@ impl TokenValidator {
         );
 
         info!(
-            "Background revocation refresh: key {} revoked={}",
-            key_id, is_revoked
+            "Background revocation refresh: key {} revoked={} min_generation={}",
+            key_id, is_revoked, min_generation
         );
         Ok(())
     }
 
+    /// Record a key's verified request signature (as returned by
+    /// `signing::verify_signature`) as seen for the replay window.
+    /// Returns false if the signature was already used.
+    pub async fn mark_signature_seen(&self, key_id: Uuid, signature: &str) -> Result<bool> {
+        let mut conn = self.redis_client.clone();
+        let inserted: Option<String> = redis::cmd("SET")
+            .arg(signing::replay_key(key_id, signature))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(signing::REPLAY_WINDOW_SECS)
+            .query_async(&mut conn)
+            .await?;
+        Ok(inserted.is_some())
+    }
+
     /// Periodically clean up expired cache entries
     #[allow(dead_code)]
     pub fn cleanup_expired(&self) {
@@ -486,16 +854,22 @@ impl TokenValidator {
 // Admin Token Functions (for UI/CLI access to user management endpoints)
 // ============================================================================
 
-/// Sign an admin token (simpler than API tokens, no usage tracking)
+/// Sign an admin token (simpler than API tokens, no usage tracking).
+/// `token_id` should be recorded (see `admin::record`) so it can be revoked.
 pub fn sign_admin_token(
+    token_id: Uuid,
     scope: &str,
     expiration: i64,
     signing_key: &ed25519_dalek::SigningKey,
+    binding: &TokenBinding,
 ) -> Result<String> {
     use base64::Engine as _;
 
     // Build CWT ClaimsSet
-    let claims = ClaimsSetBuilder::new()
+    let claims = binding
+        .apply(ClaimsSetBuilder::new())
+        .cwt_id(token_id.as_bytes().to_vec())
+        .issued_at(Timestamp::WholeSeconds(Utc::now().timestamp()))
         .expiration_time(Timestamp::WholeSeconds(expiration))
         .text_claim(
             "s".to_string(),
@@ -503,9 +877,10 @@ pub fn sign_admin_token(
         )
         .build();
 
-    // Build protected header with algorithm
+    // Build protected header with algorithm and the signing key's id
     let protected = HeaderBuilder::new()
         .algorithm(iana::Algorithm::EdDSA)
+        .key_id(keyring::kid(&signing_key.verifying_key()))
         .build();
 
     // Sign the token
@@ -532,14 +907,17 @@ pub fn sign_admin_token(
     Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&token_bytes))
 }
 
-/// Validate an admin token
+/// Validate an admin token (signature, binding, id and lifetime; revocation
+/// is checked by the extractor). Tokens valid for longer than `max_validity`
+/// are rejected, however they were issued.
 pub fn validate_admin_token(
     token: &str,
-    verifying_key: &ed25519_dalek::VerifyingKey,
+    keys: &Keyring,
+    binding: &TokenBinding,
+    max_validity: chrono::Duration,
 ) -> Result<AdminTokenData> {
     use base64::Engine as _;
     use coset::CoseSign1;
-    use ed25519_dalek::Verifier;
 
     // Decode base64
     let token_bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
@@ -550,14 +928,13 @@ pub fn validate_admin_token(
     let sign1 = CoseSign1::from_slice(&token_bytes)
         .map_err(|e| anyhow!("Invalid COSE structure: {}", e))?;
 
-    // Verify signature
+    // Verify signature with the key named by `kid`
+    let kid = &sign1.protected.header.key_id;
     sign1
         .verify_signature(&[], |sig, data| {
             let signature = ed25519_dalek::Signature::from_slice(sig)
                 .map_err(|e| anyhow!("Invalid signature format: {}", e))?;
-            verifying_key
-                .verify(data, &signature)
-                .map_err(|e| anyhow!("Signature verification failed: {}", e))
+            keys.verify(kid, data, &signature)
         })
         .map_err(|e| anyhow!("Token verification failed: {}", e))?;
 
@@ -570,20 +947,42 @@ pub fn validate_admin_token(
     )
     .map_err(|e| anyhow!("Invalid claims: {}", e))?;
 
-    // Check expiration and extract timestamp
-    let exp_timestamp = if let Some(ref exp) = claims.expiration_time {
-        let timestamp = match exp {
-            Timestamp::WholeSeconds(s) => *s,
-            Timestamp::FractionalSeconds(f) => *f as i64,
-        };
+    binding.check(&claims)?;
 
-        if Utc::now().timestamp() > timestamp {
-            return Err(anyhow!("Token expired"));
-        }
-        timestamp
-    } else {
-        return Err(anyhow!("Token missing expiration"));
+    let token_id = claims
+        .cwt_id
+        .as_deref()
+        .and_then(|id| Uuid::from_slice(id).ok())
+        .ok_or_else(|| anyhow!("Missing token id"))?;
+
+    let seconds = |timestamp: &Timestamp| match timestamp {
+        Timestamp::WholeSeconds(s) => *s,
+        Timestamp::FractionalSeconds(f) => *f as i64,
     };
+    let issued_at = claims
+        .issued_at
+        .as_ref()
+        .map(seconds)
+        .ok_or_else(|| anyhow!("Token missing issue time"))?;
+    let exp_timestamp = claims
+        .expiration_time
+        .as_ref()
+        .map(seconds)
+        .ok_or_else(|| anyhow!("Token missing expiration"))?;
+
+    // Expired, or valid for too long (counting from issuance or from now,
+    // so a future `iat` doesn't help)
+    let now = Utc::now().timestamp();
+    if now > exp_timestamp {
+        return Err(anyhow!("Token expired"));
+    }
+    let max_secs = max_validity.num_seconds();
+    if exp_timestamp - issued_at > max_secs || exp_timestamp - now > max_secs {
+        return Err(anyhow!(
+            "Token validity exceeds {} days",
+            max_validity.num_days()
+        ));
+    }
 
     // Extract scope from custom claims
     let scope = claims
@@ -602,47 +1001,17 @@ pub fn validate_admin_token(
         .ok_or_else(|| anyhow!("Missing scope claim"))?;
 
     Ok(AdminTokenData {
+        token_id,
+        issued_at,
         expiration: exp_timestamp,
         scope,
     })
 }
 
-/// Global token validator instance
-static TOKEN_VALIDATOR: once_cell::sync::OnceCell<TokenValidator> =
-    once_cell::sync::OnceCell::new();
-
-/// Initialize the global token validator
-pub async fn init_token_validator() -> Result<()> {
-    // If already initialized, return early
-    if TOKEN_VALIDATOR.get().is_some() {
-        return Ok(());
-    }
-
-    let settings = config::get_settings();
-
-    // Get Redis connection
-    let redis_client = redis::Client::open(settings.redis_url.as_str())?;
-    let conn = ConnectionManager::new(redis_client).await?;
-
-    let validator = TokenValidator::new(
-        &settings.token_public_key,
-        conn,
-        300,  // 5 minutes fresh TTL
-        3600, // 60 minutes stale TTL
-    )
-    .await?;
-
-    TOKEN_VALIDATOR.set(validator).ok(); // Ignore error if already set
-
-    info!("Token validator initialized");
-    Ok(())
-}
-
-/// Get the global token validator
+/// The token validator of the process-wide `AppState` (prefer
+/// `AppState::validator` where the state is at hand)
 pub fn get_validator() -> &'static TokenValidator {
-    TOKEN_VALIDATOR
-        .get()
-        .expect("Token validator not initialized")
+    &crate::state::global().validator
 }
 
 // ============================================================================
@@ -667,4 +1036,8 @@ impl AdminTokenClaims {
     pub fn expiration(&self) -> i64 {
         self.data.expiration
     }
+
+    pub fn token_id(&self) -> Uuid {
+        self.data.token_id
+    }
 }
@@ -0,0 +1,266 @@
+//! Opaque API keys
+//!
+//! An alternative to self-contained CWT keys for customers who want plain
+//! revocable strings. The key is `op_` followed by 64 hex chars of randomness;
+//! only its SHA-256 hash is stored, next to the CBOR-encoded `TokenData` a CWT
+//! would carry. Lookups go through a short-lived in-memory cache and then the
+//! same revocation check as CWT keys. Enabled with `OPAQUE_API_KEYS=true`.
+//!
+//! Keys come from clients, so the cache is an LRU of bounded size: a flood
+//! of made-up keys (whose misses are cached too) evicts older entries rather
+//! than growing memory.
+
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use super::{get_validator, TokenClaims};
+use crate::cache::lru::LruCache;
+use crate::database;
+
+/// Marker distinguishing opaque keys from CWT tokens (after the key prefix)
+pub const OPAQUE_KEY_MARKER: &str = "op_";
+
+/// How long lookups (hits and misses) are cached
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Most lookups cached at once
+const CACHE_CAPACITY: usize = 10_000;
+
+/// Generate a new opaque key (without the configured API key prefix)
+pub fn generate_opaque_key() -> String {
+    let secret: [u8; 32] = rand::random();
+    format!("{}{}", OPAQUE_KEY_MARKER, hex::encode(secret))
+}
+
+/// Whether a (prefix-stripped) token is an opaque key rather than a CWT
+pub fn is_opaque_key(token: &str) -> bool {
+    token.starts_with(OPAQUE_KEY_MARKER)
+}
+
+/// Hash stored in `api_keys.key_hash`
+pub fn hash_opaque_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Cache of key hash -> token claims (None = unknown or inactive key),
+/// evicting the least recently used lookup beyond `capacity`
+pub struct ApiKeyCache {
+    entries: Mutex<LruCache<String, (Option<TokenClaims>, Instant)>>,
+    ttl: Duration,
+}
+
+impl ApiKeyCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            ttl,
+        }
+    }
+
+    /// Resolve an opaque key against `api_keys`
+    pub async fn lookup(&self, key: &str) -> Result<Option<TokenClaims>> {
+        self.lookup_with(key, |key_hash| async move {
+            let token_data = sqlx::query_scalar::<_, Vec<u8>>(
+                "SELECT token_data FROM api_keys
+                 WHERE key_hash = $1 AND is_active = true AND token_data IS NOT NULL",
+            )
+            .bind(key_hash)
+            .fetch_optional(database::get_db())
+            .await?;
+
+            token_data
+                .map(|bytes| TokenClaims::from_cbor_bytes(&bytes))
+                .transpose()
+        })
+        .await
+    }
+
+    async fn lookup_with<F, Fut>(&self, key: &str, load: F) -> Result<Option<TokenClaims>>
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: Future<Output = Result<Option<TokenClaims>>>,
+    {
+        let key_hash = hash_opaque_key(key);
+
+        let cached = self.entries.lock().get(&key_hash);
+        if let Some((claims, cached_at)) = cached {
+            if cached_at.elapsed() < self.ttl {
+                return Ok(claims);
+            }
+        }
+
+        let claims = load(key_hash.clone()).await?;
+        self.entries
+            .lock()
+            .put(key_hash, (claims.clone(), Instant::now()));
+        Ok(claims)
+    }
+
+    /// Drop a cached lookup (e.g. right after revoking the key)
+    pub fn invalidate(&self, key_hash: &str) {
+        self.entries.lock().remove(&key_hash.to_string());
+    }
+}
+
+/// Process-wide opaque key cache
+pub static API_KEY_CACHE: Lazy<ApiKeyCache> =
+    Lazy::new(|| ApiKeyCache::new(CACHE_TTL, CACHE_CAPACITY));
+
+/// Validate an opaque key: cached lookup, then the shared revocation check
+pub async fn validate(key: &str) -> Result<TokenClaims> {
+    let claims = API_KEY_CACHE
+        .lookup(key)
+        .await?
+        .ok_or_else(|| anyhow!("Unknown or inactive API key"))?;
+
+    get_validator().check_revocation(claims).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::TokenData;
+    use crate::models::TierType;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use uuid::Uuid;
+
+    fn claims() -> TokenClaims {
+        TokenClaims::from_token_data(TokenData {
+            org_id: Uuid::now_v7(),
+            key_id: Uuid::now_v7(),
+            tier: TierType::Pro,
+            max_tokens: 64,
+            monthly_quota: 1000,
+            require_signing: false,
+            max_concurrency: None,
+            scopes: None,
+            models: None,
+            api_version: None,
+            strict_validation: false,
+            generation: 0,
+        })
+    }
+
+    #[test]
+    fn test_generated_keys() {
+        let key = generate_opaque_key();
+        assert!(is_opaque_key(&key));
+        assert_eq!(key.len(), OPAQUE_KEY_MARKER.len() + 64);
+        assert_ne!(key, generate_opaque_key());
+
+        // CWT tokens are base64 and never start with the marker
+        assert!(!is_opaque_key("0oRDoQEnoFhM"));
+    }
+
+    #[test]
+    fn test_hash_is_sha256_hex() {
+        assert_eq!(
+            hash_opaque_key("op_test"),
+            "8cb8a94c34ce53add38d4b38aeef077210c06f66f4cfa4a0f383a42dca15138b"
+        );
+        assert_ne!(hash_opaque_key("op_a"), hash_opaque_key("op_b"));
+    }
+
+    #[test]
+    fn test_token_data_roundtrip() {
+        let original = claims();
+        let bytes = original.to_cbor_bytes().unwrap();
+        let decoded = TokenClaims::from_cbor_bytes(&bytes).unwrap();
+        assert_eq!(decoded.key_id(), original.key_id());
+        assert_eq!(decoded.max_tokens(), 64);
+        assert_eq!(decoded.tier().unwrap(), TierType::Pro);
+    }
+
+    #[tokio::test]
+    async fn test_cache_hits_and_misses() {
+        let cache = ApiKeyCache::new(Duration::from_secs(60), 16);
+        let loads = AtomicUsize::new(0);
+        let expected = claims();
+
+        for _ in 0..3 {
+            let found = cache
+                .lookup_with("op_known", |_| {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    let claims = expected.clone();
+                    async move { Ok(Some(claims)) }
+                })
+                .await
+                .unwrap();
+            assert_eq!(found.unwrap().key_id(), expected.key_id());
+        }
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        // Unknown keys are cached too, so guessing doesn't hammer the database
+        for _ in 0..3 {
+            let found = cache
+                .lookup_with("op_unknown", |_| {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    async { Ok(None) }
+                })
+                .await
+                .unwrap();
+            assert!(found.is_none());
+        }
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cache_expiry_and_invalidate() {
+        let cache = ApiKeyCache::new(Duration::ZERO, 16);
+        let loads = AtomicUsize::new(0);
+        for _ in 0..2 {
+            cache
+                .lookup_with("op_key", |_| {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    async { Ok(None) }
+                })
+                .await
+                .unwrap();
+        }
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+
+        let cache = ApiKeyCache::new(Duration::from_secs(60), 16);
+        cache
+            .lookup_with("op_key", |_| async { Ok(Some(claims())) })
+            .await
+            .unwrap();
+        cache.invalidate(&hash_opaque_key("op_key"));
+        let found = cache
+            .lookup_with("op_key", |_| async { Ok(None) })
+            .await
+            .unwrap();
+        assert!(found.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cache_is_bounded() {
+        let cache = ApiKeyCache::new(Duration::from_secs(60), 16);
+        cache
+            .lookup_with("op_known", |_| async { Ok(Some(claims())) })
+            .await
+            .unwrap();
+
+        // A flood of made-up keys evicts rather than grows
+        for i in 0..1000 {
+            cache
+                .lookup_with(&format!("op_guess{}", i), |_| async { Ok(None) })
+                .await
+                .unwrap();
+        }
+        assert_eq!(cache.entries.lock().len(), 16);
+
+        let loads = AtomicUsize::new(0);
+        cache
+            .lookup_with("op_known", |_| {
+                loads.fetch_add(1, Ordering::SeqCst);
+                async { Ok(Some(claims())) }
+            })
+            .await
+            .unwrap();
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,100 @@
+//! Secrets kept at rest for a while, encrypted
+//!
+//! Reissued API keys wait in the database until their owner retrieves them
+//! (see `services::key_reissue`). They're sealed with AES-256-GCM under a
+//! key derived from `SECRET_KEY`, with a random nonce, and bound to the row
+//! they're stored in (`context` is the associated data), so a sealed value
+//! copied to another row doesn't open.
+//!
+//! Format: `base64(nonce || ciphertext || tag)`.
+
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::Sha256;
+
+use crate::config;
+
+/// Sealing key for `server_secret`
+fn sealing_key(server_secret: &str) -> LessSafeKey {
+    let mut mac = Hmac::<Sha256>::new_from_slice(server_secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(b"smally-sealed:v1");
+    let key = mac.finalize().into_bytes();
+    LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &key).expect("AES-256 takes 32-byte keys"))
+}
+
+/// Encrypt `plaintext` with `SECRET_KEY`
+pub fn seal(plaintext: &str, context: &[u8]) -> Result<String> {
+    seal_with(&config::get_settings().secret_key, plaintext, context)
+}
+
+/// Decrypt what `seal` produced with the same `context`
+pub fn open(sealed: &str, context: &[u8]) -> Result<String> {
+    open_with(&config::get_settings().secret_key, sealed, context)
+}
+
+fn seal_with(server_secret: &str, plaintext: &str, context: &[u8]) -> Result<String> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow!("No randomness for the nonce"))?;
+
+    let mut in_out = plaintext.as_bytes().to_vec();
+    sealing_key(server_secret)
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(context),
+            &mut in_out,
+        )
+        .map_err(|_| anyhow!("Encryption failed"))?;
+
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&in_out);
+    Ok(base64::Engine::encode(
+        &base64::engine::general_purpose::STANDARD,
+        out,
+    ))
+}
+
+fn open_with(server_secret: &str, sealed: &str, context: &[u8]) -> Result<String> {
+    let bytes = base64::Engine::decode(&base64::engine::general_purpose::STANDARD, sealed)
+        .map_err(|e| anyhow!("Invalid sealed value: {}", e))?;
+    if bytes.len() < NONCE_LEN {
+        return Err(anyhow!("Invalid sealed value: too short"));
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow!("Invalid nonce"))?;
+
+    let mut in_out = ciphertext.to_vec();
+    let plaintext = sealing_key(server_secret)
+        .open_in_place(nonce, Aad::from(context), &mut in_out)
+        .map_err(|_| anyhow!("Decryption failed (wrong key or context)"))?;
+    String::from_utf8(plaintext.to_vec()).map_err(|e| anyhow!("Invalid sealed value: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_round_trip() {
+        let sealed = seal_with("secret", "sk_token", b"row-1").unwrap();
+        assert!(!sealed.contains("sk_token"));
+        assert_eq!(open_with("secret", &sealed, b"row-1").unwrap(), "sk_token");
+
+        // Fresh nonce each time
+        assert_ne!(sealed, seal_with("secret", "sk_token", b"row-1").unwrap());
+
+        // Another row, another server secret, or tampering: nothing opens
+        assert!(open_with("secret", &sealed, b"row-2").is_err());
+        assert!(open_with("other", &sealed, b"row-1").is_err());
+        let mut tampered =
+            base64::Engine::decode(&base64::engine::general_purpose::STANDARD, &sealed).unwrap();
+        *tampered.last_mut().unwrap() ^= 1;
+        let tampered = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, tampered);
+        assert!(open_with("secret", &tampered, b"row-1").is_err());
+        assert!(open_with("secret", "AAAA", b"row-1").is_err());
+    }
+}
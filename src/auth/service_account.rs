@@ -0,0 +1,247 @@
+//! Service account tokens (`sa_`) and the management API principal
+//!
+//! A service account is a non-human member of one organization, created by
+//! an owner for automation (CI, Terraform). Its token is a CWT signed like
+//! API keys, without an expiry, naming only the account and its
+//! organization; the role is looked up in `service_accounts` (cached
+//! briefly), so revoking the account cuts access. The token authenticates
+//! against the organization management endpoints through `Principal`,
+//! never against the embedding endpoints.
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use coset::{
+    cwt::{ClaimName, ClaimsSet, ClaimsSetBuilder, Timestamp},
+    iana, CborSerializable, CoseSign1, CoseSign1Builder, HeaderBuilder,
+};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::binding::TokenBinding;
+use super::keyring::{self, Keyring};
+use super::session::SessionClaims;
+use crate::database;
+use crate::models::OrganizationRole;
+use crate::services::{Actor, ServiceError};
+
+/// Claim holding the account's organization (also marks the token kind)
+const ORG_CLAIM: &str = "sa";
+
+/// How long a resolved account is trusted before hitting the database again
+const ACCOUNT_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// An account's organization and role, None if revoked or unknown
+type ResolvedAccount = Option<(Uuid, OrganizationRole)>;
+
+/// Account id -> (resolved account, checked at)
+static ACCOUNT_CACHE: Lazy<DashMap<Uuid, (ResolvedAccount, Instant)>> = Lazy::new(DashMap::new);
+
+/// What a service account token says
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServiceAccountTokenData {
+    /// Account id (`cti`)
+    pub id: Uuid,
+    pub org_id: Uuid,
+}
+
+/// An authenticated, active service account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServiceAccountClaims {
+    pub id: Uuid,
+    pub org_id: Uuid,
+    pub role: OrganizationRole,
+}
+
+/// Caller of a management endpoint: a signed-in user or a service account
+#[derive(Debug, Clone)]
+pub enum Principal {
+    User(SessionClaims),
+    ServiceAccount(ServiceAccountClaims),
+}
+
+impl Principal {
+    /// Who service calls are made on behalf of
+    pub fn actor(&self) -> Result<Actor, ServiceError> {
+        match self {
+            Principal::User(claims) => claims
+                .sub
+                .parse()
+                .map(Actor::User)
+                .map_err(|_| ServiceError::Forbidden("Invalid user ID".to_string())),
+            Principal::ServiceAccount(account) => Ok(Actor::ServiceAccount {
+                id: account.id,
+                org_id: account.org_id,
+                role: account.role,
+            }),
+        }
+    }
+}
+
+/// Sign a token for a service account (without the `sa_` prefix)
+pub fn sign_service_account_token(
+    id: Uuid,
+    org_id: Uuid,
+    signing_key: &ed25519_dalek::SigningKey,
+    binding: &TokenBinding,
+) -> Result<String> {
+    use base64::Engine as _;
+
+    let claims = binding
+        .apply(ClaimsSetBuilder::new())
+        .cwt_id(id.as_bytes().to_vec())
+        .issued_at(Timestamp::WholeSeconds(Utc::now().timestamp()))
+        .text_claim(
+            ORG_CLAIM.to_string(),
+            ciborium::value::Value::Bytes(org_id.as_bytes().to_vec()),
+        )
+        .build();
+
+    let protected = HeaderBuilder::new()
+        .algorithm(iana::Algorithm::EdDSA)
+        .key_id(keyring::kid(&signing_key.verifying_key()))
+        .build();
+
+    let sign1 = CoseSign1Builder::new()
+        .protected(protected)
+        .payload(
+            claims
+                .to_vec()
+                .map_err(|e| anyhow!("Failed to encode claims: {}", e))?,
+        )
+        .try_create_signature(&[], |bytes| {
+            use ed25519_dalek::Signer;
+            Ok::<Vec<u8>, coset::CoseError>(signing_key.sign(bytes).to_vec())
+        })
+        .map_err(|e| anyhow!("Failed to sign token: {}", e))?
+        .build();
+
+    let token_bytes = sign1
+        .to_vec()
+        .map_err(|e| anyhow!("Failed to encode token: {}", e))?;
+    Ok(base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(&token_bytes))
+}
+
+/// Validate a service account token (signature, binding and claims; whether
+/// the account is still active is checked by `resolve`)
+pub fn validate_service_account_token(
+    token: &str,
+    keys: &Keyring,
+    binding: &TokenBinding,
+) -> Result<ServiceAccountTokenData> {
+    use base64::Engine as _;
+
+    let token_bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(token)
+        .map_err(|e| anyhow!("Invalid base64: {}", e))?;
+    let sign1 = CoseSign1::from_slice(&token_bytes)
+        .map_err(|e| anyhow!("Invalid COSE structure: {}", e))?;
+
+    let kid = &sign1.protected.header.key_id;
+    sign1
+        .verify_signature(&[], |sig, data| {
+            let signature = ed25519_dalek::Signature::from_slice(sig)
+                .map_err(|e| anyhow!("Invalid signature format: {}", e))?;
+            keys.verify(kid, data, &signature)
+        })
+        .map_err(|e| anyhow!("Token verification failed: {}", e))?;
+
+    let claims = ClaimsSet::from_slice(
+        sign1
+            .payload
+            .as_ref()
+            .ok_or_else(|| anyhow!("Missing payload"))?,
+    )
+    .map_err(|e| anyhow!("Invalid claims: {}", e))?;
+    binding.check(&claims)?;
+
+    let id = claims
+        .cwt_id
+        .as_deref()
+        .and_then(|id| Uuid::from_slice(id).ok())
+        .ok_or_else(|| anyhow!("Missing token id"))?;
+    let org_id = claims
+        .rest
+        .iter()
+        .find_map(|(name, value)| match (name, value) {
+            (ClaimName::Text(key), ciborium::value::Value::Bytes(bytes)) if key == ORG_CLAIM => {
+                Uuid::from_slice(bytes).ok()
+            }
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("Not a service account token"))?;
+
+    Ok(ServiceAccountTokenData { id, org_id })
+}
+
+/// The active account behind a validated token (cached briefly). Errors if
+/// the account was revoked, deleted or moved.
+pub async fn resolve(token: ServiceAccountTokenData) -> Result<ServiceAccountClaims> {
+    let cached = ACCOUNT_CACHE
+        .get(&token.id)
+        .filter(|entry| entry.1.elapsed() < ACCOUNT_CACHE_TTL)
+        .map(|entry| entry.0);
+
+    let account = match cached {
+        Some(account) => account,
+        None => {
+            let account = sqlx::query_as::<_, (Uuid, OrganizationRole)>(
+                "SELECT organization_id, role FROM service_accounts
+                 WHERE id = $1 AND revoked_at IS NULL",
+            )
+            .bind(token.id)
+            .fetch_optional(database::get_db())
+            .await?;
+            ACCOUNT_CACHE.insert(token.id, (account, Instant::now()));
+            account
+        }
+    };
+
+    match account {
+        Some((org_id, role)) if org_id == token.org_id => Ok(ServiceAccountClaims {
+            id: token.id,
+            org_id,
+            role,
+        }),
+        _ => Err(anyhow!("Service account revoked")),
+    }
+}
+
+/// Forget a cached account (call after revoking it)
+pub fn invalidate(id: Uuid) {
+    ACCOUNT_CACHE.remove(&id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{sign_admin_token, validate_admin_token};
+
+    #[test]
+    fn test_token_round_trip_and_kind() {
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[5; 32]);
+        let keyring = Keyring::new(vec![signing_key.verifying_key()]);
+        let binding = TokenBinding::default();
+        let (id, org_id) = (Uuid::now_v7(), Uuid::now_v7());
+
+        let token = sign_service_account_token(id, org_id, &signing_key, &binding).unwrap();
+        let data = validate_service_account_token(&token, &keyring, &binding).unwrap();
+        assert_eq!(data, ServiceAccountTokenData { id, org_id });
+
+        // Signed by another key
+        let other = Keyring::new(vec![
+            ed25519_dalek::SigningKey::from_bytes(&[6; 32]).verifying_key()
+        ]);
+        assert!(validate_service_account_token(&token, &other, &binding).is_err());
+
+        // Not interchangeable with admin tokens
+        let expiration = (Utc::now() + chrono::Duration::days(1)).timestamp();
+        let admin = sign_admin_token(id, "ui", expiration, &signing_key, &binding).unwrap();
+        let err = validate_service_account_token(&admin, &keyring, &binding).unwrap_err();
+        assert!(err.to_string().contains("Not a service account token"));
+        assert!(
+            validate_admin_token(&token, &keyring, &binding, chrono::Duration::days(30)).is_err()
+        );
+    }
+}
@@ -2,15 +2,22 @@ use anyhow::{anyhow, Result};
 use axum::{
     async_trait,
     extract::FromRequestParts,
-    http::{header, request::Parts, StatusCode},
+    http::{header, request::Parts},
     response::{IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::cookie::{Cookie, SameSite};
 use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use dashmap::DashMap;
+use jsonwebtoken::{
+    decode, encode, errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Instant;
+use uuid::Uuid;
 
-use crate::config;
+use crate::{config, database, monitoring};
 
 /// JWT session claims for authenticated users
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,68 +30,186 @@ pub struct SessionClaims {
     pub iat: i64,
     /// User email
     pub email: String,
-    /// Current organization context (optional)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub current_org_id: Option<String>,
+    /// Active organization context. Checked against current membership on
+    /// every request; dropped (None) once the user is no longer a member.
+    #[serde(
+        default,
+        alias = "current_org_id",
+        with = "crate::uuid_dashless::simple::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub org_id: Option<Uuid>,
+}
+
+/// Session lifetime without "remember me" (and for new registrations)
+pub const SESSION_DURATION_SHORT: Duration = Duration::hours(24);
+
+/// Session lifetime when "remember me" is checked
+pub const SESSION_DURATION_REMEMBER: Duration = Duration::days(30);
+
+/// Session lifetime for a login, depending on "remember me"
+pub fn session_duration(remember_me: bool) -> Duration {
+    if remember_me {
+        SESSION_DURATION_REMEMBER
+    } else {
+        SESSION_DURATION_SHORT
+    }
 }
 
-/// Generate a JWT session token for a user
-pub fn create_session_token(user_id: uuid::Uuid, email: &str) -> Result<String> {
-    create_session_token_with_org(user_id, email, None)
+/// Generate a JWT session token for a user, valid for `duration`
+pub fn create_session_token(user_id: Uuid, email: &str, duration: Duration) -> Result<String> {
+    create_session_token_with_org(user_id, email, None, duration)
 }
 
-/// Generate a JWT session token with organization context
+/// Generate a JWT session token with organization context, valid for `duration`
 pub fn create_session_token_with_org(
-    user_id: uuid::Uuid,
+    user_id: Uuid,
     email: &str,
-    org_id: Option<uuid::Uuid>,
+    org_id: Option<Uuid>,
+    duration: Duration,
 ) -> Result<String> {
-    let settings = config::get_settings();
-
     let now = Utc::now();
-    let exp = now + Duration::days(7); // 7_day session
+    let exp = now + duration;
 
     let claims = SessionClaims {
         sub: user_id.to_string(),
         exp: exp.timestamp(),
         iat: now.timestamp(),
         email: email.to_string(),
-        current_org_id: org_id.map(|id| id.to_string()),
+        org_id,
     };
 
+    sign_with(&claims, &config::get_settings().jwt_secrets)
+}
+
+/// Verify and decode a JWT session token
+pub fn verify_session_token(token: &str) -> Result<SessionClaims> {
+    verify_with(token, &config::get_settings().jwt_secrets)
+}
+
+/// Sign with the primary (first) secret
+fn sign_with(claims: &SessionClaims, secrets: &[String]) -> Result<String> {
+    let secret = secrets
+        .first()
+        .ok_or_else(|| anyhow!("No session secret configured"))?;
+
     let token = encode(
         &Header::new(Algorithm::HS256),
-        &claims,
-        &EncodingKey::from_secret(settings.jwt_secret.as_bytes()),
+        claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
     )?;
 
     Ok(token)
 }
 
-/// Verify and decode a JWT session token
-pub fn verify_session_token(token: &str) -> Result<SessionClaims> {
-    let settings = config::get_settings();
-
+/// Verify against each secret in order. Only a signature mismatch moves on
+/// to the next one; an expired token is rejected outright. Matches on older
+/// secrets are counted so we know when they can be dropped.
+fn verify_with(token: &str, secrets: &[String]) -> Result<SessionClaims> {
     let validation = Validation::new(Algorithm::HS256);
+    let mut last_error = anyhow!("No session secret configured");
 
-    let token_data = decode::<SessionClaims>(
-        token,
-        &DecodingKey::from_secret(settings.jwt_secret.as_bytes()),
-        &validation,
+    for (index, secret) in secrets.iter().enumerate() {
+        match decode::<SessionClaims>(
+            token,
+            &DecodingKey::from_secret(secret.as_bytes()),
+            &validation,
+        ) {
+            Ok(token_data) => {
+                if index > 0 {
+                    monitoring::SESSION_SECRET_FALLBACKS
+                        .with_label_values(&[&index.to_string()])
+                        .inc();
+                }
+                return Ok(token_data.claims);
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::InvalidSignature) => {
+                last_error = anyhow!("Invalid session token: {}", e);
+            }
+            Err(e) => return Err(anyhow!("Invalid session token: {}", e)),
+        }
+    }
+
+    Err(last_error)
+}
+
+/// How long a membership lookup is trusted before hitting the database again
+const MEMBERSHIP_CACHE_TTL_SECS: u64 = 30;
+
+/// (user_id, org_id) -> (is_member, checked_at)
+static MEMBERSHIP_CACHE: Lazy<DashMap<(Uuid, Uuid), (bool, Instant)>> = Lazy::new(DashMap::new);
+
+/// Whether the user is currently a member of the organization (cached briefly)
+pub async fn is_org_member(user_id: Uuid, org_id: Uuid) -> Result<bool> {
+    if let Some(entry) = MEMBERSHIP_CACHE.get(&(user_id, org_id)) {
+        let (is_member, checked_at) = *entry;
+        if checked_at.elapsed().as_secs() < MEMBERSHIP_CACHE_TTL_SECS {
+            return Ok(is_member);
+        }
+    }
+
+    let is_member = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM organization_members WHERE organization_id = $1 AND user_id = $2)",
     )
-    .map_err(|e| anyhow!("Invalid session token: {}", e))?;
+    .bind(org_id)
+    .bind(user_id)
+    .fetch_one(database::get_db())
+    .await?;
+
+    MEMBERSHIP_CACHE.insert((user_id, org_id), (is_member, Instant::now()));
+    Ok(is_member)
+}
 
-    Ok(token_data.claims)
+/// Forget a cached membership lookup (call after adding or removing members)
+pub fn invalidate_membership(user_id: Uuid, org_id: Uuid) {
+    MEMBERSHIP_CACHE.remove(&(user_id, org_id));
+}
+
+/// Drop the organization context from `claims` if the user is no longer a
+/// member of that organization. Lookup failures also drop the context.
+pub async fn validate_org_context(claims: SessionClaims) -> SessionClaims {
+    resolve_org_context(claims, is_org_member).await
+}
+
+async fn resolve_org_context<F, Fut>(mut claims: SessionClaims, is_member: F) -> SessionClaims
+where
+    F: FnOnce(Uuid, Uuid) -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    let Some(org_id) = claims.org_id else {
+        return claims;
+    };
+    let Ok(user_id) = Uuid::parse_str(&claims.sub) else {
+        claims.org_id = None;
+        return claims;
+    };
+
+    match is_member(user_id, org_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            tracing::info!(
+                "Dropping org context {} from session of user {}: membership revoked",
+                org_id,
+                user_id
+            );
+            claims.org_id = None;
+        }
+        Err(e) => {
+            tracing::warn!("Failed to check org membership: {}", e);
+            claims.org_id = None;
+        }
+    }
+    claims
 }
 
 /// Session cookie name
 pub const SESSION_COOKIE_NAME: &str = "session";
 
-/// Create a session cookie with security settings
-pub fn create_session_cookie(token: &str) -> Cookie<'static> {
+/// Create a session cookie with security settings, expiring with the token
+pub fn create_session_cookie(token: &str, duration: Duration) -> Cookie<'static> {
     Cookie::build((SESSION_COOKIE_NAME, token.to_string()))
         .path("/")
-        .max_age(time::Duration::days(7))
+        .max_age(time::Duration::seconds(duration.num_seconds()))
         .same_site(SameSite::Lax)
         .http_only(true)
         // TODO: Enable secure flag in production (requires HTTPS)
@@ -107,19 +232,23 @@ pub struct SessionCookie {
 }
 
 impl SessionCookie {
-    pub fn user_id(&self) -> uuid::Uuid {
-        uuid::Uuid::parse_str(&self.claims.sub).unwrap_or_default()
+    pub fn user_id(&self) -> Uuid {
+        Uuid::parse_str(&self.claims.sub).unwrap_or_default()
     }
 
     pub fn email(&self) -> &str {
         &self.claims.email
     }
 
-    pub fn current_org_id(&self) -> Option<uuid::Uuid> {
-        self.claims
-            .current_org_id
-            .as_ref()
-            .and_then(|id| uuid::Uuid::parse_str(id).ok())
+    /// Time left until the session expires (used to keep the expiry when
+    /// re-issuing the token, e.g. on organization switch)
+    pub fn remaining(&self) -> Duration {
+        Duration::seconds((self.claims.exp - Utc::now().timestamp()).max(0))
+    }
+
+    /// Active organization from the session (already membership-checked)
+    pub fn current_org_id(&self) -> Option<Uuid> {
+        self.claims.org_id
     }
 }
 
@@ -151,9 +280,7 @@ where
             .split(';')
             .map(|s| s.trim())
             .find_map(|cookie| {
-                let mut parts = cookie.splitn(2, '=');
-                let name = parts.next()?;
-                let value = parts.next()?;
+                let (name, value) = cookie.split_once('=')?;
                 if name == SESSION_COOKIE_NAME {
                     Some(value)
                 } else {
@@ -167,7 +294,151 @@ where
             tracing::warn!("Invalid session token: {}", e);
             Redirect::to(&redirect_url).into_response()
         })?;
+        let claims = validate_org_context(claims).await;
 
         Ok(SessionCookie { claims })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(org_id: Option<Uuid>) -> SessionClaims {
+        SessionClaims {
+            sub: Uuid::now_v7().to_string(),
+            exp: 0,
+            iat: 0,
+            email: "user@example.com".to_string(),
+            org_id,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_org_context_kept_for_member() {
+        let org_id = Uuid::now_v7();
+        let resolved = resolve_org_context(claims(Some(org_id)), |_, _| async { Ok(true) }).await;
+        assert_eq!(resolved.org_id, Some(org_id));
+    }
+
+    #[tokio::test]
+    async fn test_org_context_dropped_when_membership_revoked() {
+        let resolved =
+            resolve_org_context(claims(Some(Uuid::now_v7())), |_, _| async { Ok(false) }).await;
+        assert_eq!(resolved.org_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_org_context_dropped_on_lookup_error() {
+        let resolved = resolve_org_context(claims(Some(Uuid::now_v7())), |_, _| async {
+            Err(anyhow!("database unavailable"))
+        })
+        .await;
+        assert_eq!(resolved.org_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_no_lookup_without_org_context() {
+        let resolved = resolve_org_context(claims(None), |_, _| async {
+            panic!("membership should not be looked up")
+        })
+        .await;
+        assert_eq!(resolved.org_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_cached_revocation_is_honoured() {
+        let (user_id, org_id) = (Uuid::now_v7(), Uuid::now_v7());
+        MEMBERSHIP_CACHE.insert((user_id, org_id), (false, Instant::now()));
+        assert!(!is_org_member(user_id, org_id).await.unwrap());
+        invalidate_membership(user_id, org_id);
+        assert!(MEMBERSHIP_CACHE.get(&(user_id, org_id)).is_none());
+    }
+
+    #[test]
+    fn test_org_claim_serialization() {
+        let org_id = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
+        let json = serde_json::to_value(claims(Some(org_id))).unwrap();
+        assert_eq!(json["org_id"], "550e8400e29b41d4a716446655440000");
+        assert!(serde_json::to_value(claims(None))
+            .unwrap()
+            .get("org_id")
+            .is_none());
+
+        // Tokens issued before the rename still carry their org context
+        let legacy: SessionClaims = serde_json::from_value(serde_json::json!({
+            "sub": "u", "exp": 0, "iat": 0, "email": "e",
+            "current_org_id": "550e8400-e29b-41d4-a716-446655440000"
+        }))
+        .unwrap();
+        assert_eq!(legacy.org_id, Some(org_id));
+    }
+
+    fn session_claims() -> SessionClaims {
+        let now = Utc::now();
+        SessionClaims {
+            sub: Uuid::now_v7().to_string(),
+            exp: (now + Duration::hours(1)).timestamp(),
+            iat: now.timestamp(),
+            email: "test@example.com".to_string(),
+            org_id: None,
+        }
+    }
+
+    fn secrets(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_old_cookies_verify_during_rotation() {
+        let old_token = sign_with(&session_claims(), &secrets(&["old"])).unwrap();
+
+        // Overlap: new sessions use the new secret, old ones still verify
+        let rotated = secrets(&["new", "old"]);
+        let new_token = sign_with(&session_claims(), &rotated).unwrap();
+        let fallbacks = monitoring::SESSION_SECRET_FALLBACKS.with_label_values(&["1"]);
+        let before = fallbacks.get();
+        assert!(verify_with(&old_token, &rotated).is_ok());
+        assert_eq!(fallbacks.get(), before + 1.0);
+        assert!(verify_with(&new_token, &rotated).is_ok());
+        assert!(verify_with(&new_token, &secrets(&["old"])).is_err());
+
+        // Old secret dropped
+        let dropped = secrets(&["new"]);
+        assert!(verify_with(&old_token, &dropped).is_err());
+        assert!(verify_with(&new_token, &dropped).is_ok());
+    }
+
+    #[test]
+    fn test_expired_session_not_retried() {
+        let mut claims = session_claims();
+        claims.exp = Utc::now().timestamp() - 3600;
+        let token = sign_with(&claims, &secrets(&["old"])).unwrap();
+
+        let err = verify_with(&token, &secrets(&["new", "old"])).unwrap_err();
+        assert!(err.to_string().contains("ExpiredSignature"));
+        assert!(sign_with(&claims, &[]).is_err());
+    }
+
+    #[test]
+    fn test_remember_me_extends_session() {
+        let user_id = Uuid::now_v7();
+        let short =
+            create_session_token(user_id, "u@example.com", session_duration(false)).unwrap();
+        let long = create_session_token(user_id, "u@example.com", session_duration(true)).unwrap();
+
+        let short = verify_session_token(&short).unwrap();
+        let long = verify_session_token(&long).unwrap();
+        assert_eq!(short.exp - short.iat, SESSION_DURATION_SHORT.num_seconds());
+        assert_eq!(long.exp - long.iat, SESSION_DURATION_REMEMBER.num_seconds());
+        assert!(long.exp > short.exp);
+    }
+
+    #[test]
+    fn test_cookie_max_age_matches_duration() {
+        let cookie = create_session_cookie("token", SESSION_DURATION_REMEMBER);
+        assert_eq!(cookie.max_age(), Some(time::Duration::days(30)));
+        let cookie = create_session_cookie("token", SESSION_DURATION_SHORT);
+        assert_eq!(cookie.max_age(), Some(time::Duration::hours(24)));
+    }
+}
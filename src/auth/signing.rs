@@ -0,0 +1,346 @@
+//! Optional HMAC request signing for API keys created with
+//! `require_request_signing=true`.
+//!
+//! # Canonicalization
+//!
+//! The string to sign is built from four parts joined by a single `\n` (0x0A):
+//!
+//! ```text
+//! {timestamp}\n{METHOD}\n{path}\n{body}
+//! ```
+//!
+//! - `timestamp`: the exact value of the `X-Smally-Timestamp` header, Unix seconds
+//!   as a decimal integer (e.g. `1735689600`). It must be within ±5 minutes of
+//!   server time.
+//! - `METHOD`: the HTTP method in uppercase (e.g. `POST`).
+//! - `path`: the request path plus the query string if present, exactly as sent
+//!   (e.g. `/v1/embed` or `/v1/embed?x=1`). No scheme, host or trailing newline.
+//! - `body`: the raw request body bytes, exactly as sent. An empty body is
+//!   allowed, and the string then ends with the third `\n`.
+//!
+//! The signature is `hex(HMAC-SHA256(signing_secret, string_to_sign))` in
+//! lowercase. It is sent in the `X-Smally-Signature` header. A `v1=` prefix is
+//! also accepted.
+//!
+//! The signing secret is returned once, when the key is created. The server
+//! derives it from its own secret and the key_id, so it is never stored.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Header carrying the Unix timestamp (seconds) the request was signed at
+pub const TIMESTAMP_HEADER: &str = "x-smally-timestamp";
+
+/// Header carrying the hex-encoded HMAC-SHA256 signature
+pub const SIGNATURE_HEADER: &str = "x-smally-signature";
+
+/// Maximum allowed clock skew between client and server (±5 minutes)
+pub const MAX_CLOCK_SKEW_SECS: i64 = 300;
+
+/// How long seen signatures are remembered for replay protection.
+/// Must cover the whole skew window on both sides.
+pub const REPLAY_WINDOW_SECS: u64 = (2 * MAX_CLOCK_SKEW_SECS) as u64;
+
+/// Reasons a signed request can be rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureError {
+    /// Timestamp or signature header missing
+    Missing,
+    /// Timestamp header is not a decimal integer
+    InvalidTimestamp,
+    /// Timestamp outside the allowed clock skew window
+    Expired,
+    /// Signature is malformed or does not match
+    Mismatch,
+    /// Signature was already used within the replay window
+    Replayed,
+}
+
+impl std::fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::Missing => write!(
+                f,
+                "This API key requires signed requests (X-Smally-Timestamp and X-Smally-Signature headers)"
+            ),
+            SignatureError::InvalidTimestamp => {
+                write!(f, "X-Smally-Timestamp must be Unix time in seconds")
+            }
+            SignatureError::Expired => write!(
+                f,
+                "Request timestamp is outside the allowed window of ±{} seconds",
+                MAX_CLOCK_SKEW_SECS
+            ),
+            SignatureError::Mismatch => write!(f, "Request signature does not match"),
+            SignatureError::Replayed => write!(f, "Request signature has already been used"),
+        }
+    }
+}
+
+/// Derive the per-key signing secret (hex) from the server secret and key_id
+pub fn derive_signing_secret(server_secret: &str, key_id: Uuid) -> String {
+    let mut mac = HmacSha256::new_from_slice(server_secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(b"smally-request-signing:");
+    mac.update(key_id.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Build the canonical string to sign (see module docs)
+pub fn canonical_string(timestamp: &str, method: &str, path: &str, body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(timestamp.len() + method.len() + path.len() + body.len() + 3);
+    out.extend_from_slice(timestamp.as_bytes());
+    out.push(b'\n');
+    out.extend_from_slice(method.to_ascii_uppercase().as_bytes());
+    out.push(b'\n');
+    out.extend_from_slice(path.as_bytes());
+    out.push(b'\n');
+    out.extend_from_slice(body);
+    out
+}
+
+/// Compute the lowercase hex signature for a request
+pub fn compute_signature(
+    secret: &str,
+    timestamp: &str,
+    method: &str,
+    path: &str,
+    body: &[u8],
+) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(&canonical_string(timestamp, method, path, body));
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Verify timestamp freshness and signature (constant-time comparison),
+/// returning the signature as lowercase hex without prefix, the form replay
+/// protection is keyed on. Replay protection is checked separately, because
+/// it needs Redis.
+pub fn verify_signature(
+    secret: &str,
+    timestamp: Option<&str>,
+    signature: Option<&str>,
+    method: &str,
+    path: &str,
+    body: &[u8],
+    now: i64,
+) -> Result<String, SignatureError> {
+    let (timestamp, signature) = match (timestamp, signature) {
+        (Some(t), Some(s)) => (t.trim(), s.trim()),
+        _ => return Err(SignatureError::Missing),
+    };
+
+    let ts: i64 = timestamp
+        .parse()
+        .map_err(|_| SignatureError::InvalidTimestamp)?;
+    if (now - ts).abs() > MAX_CLOCK_SKEW_SECS {
+        return Err(SignatureError::Expired);
+    }
+
+    let signature = signature.strip_prefix("v1=").unwrap_or(signature);
+    let provided = hex::decode(signature).map_err(|_| SignatureError::Mismatch)?;
+
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(&canonical_string(timestamp, method, path, body));
+    mac.verify_slice(&provided)
+        .map_err(|_| SignatureError::Mismatch)?;
+    Ok(hex::encode(provided))
+}
+
+/// Redis key remembering a verified signature (as returned by
+/// `verify_signature`) of a key for the replay window
+pub fn replay_key(key_id: Uuid, signature: &str) -> String {
+    format!("sigseen:{}:{}", key_id.simple(), signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test-signing-secret";
+    const TS: &str = "1735689600";
+    const BODY: &[u8] = br#"{"text":"Hello world"}"#;
+
+    #[test]
+    fn test_canonical_string_vector() {
+        let canonical = canonical_string(TS, "post", "/v1/embed", BODY);
+        assert_eq!(
+            canonical,
+            b"1735689600\nPOST\n/v1/embed\n{\"text\":\"Hello world\"}".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_canonical_string_empty_body() {
+        let canonical = canonical_string(TS, "GET", "/v1/embed?x=1", b"");
+        assert_eq!(canonical, b"1735689600\nGET\n/v1/embed?x=1\n".to_vec());
+    }
+
+    #[test]
+    fn test_signature_vector() {
+        // HMAC-SHA256("test-signing-secret", "1735689600\nPOST\n/v1/embed\n{\"text\":\"Hello world\"}")
+        assert_eq!(
+            compute_signature(SECRET, TS, "POST", "/v1/embed", BODY),
+            "c552dcc99047e1be56d4831ff3ee8ed3eea12364a5b08fd1a0b76b49b4c0d915"
+        );
+    }
+
+    #[test]
+    fn test_derive_signing_secret_is_stable_per_key() {
+        let key_a = Uuid::parse_str("0190f1c2-0000-7000-8000-000000000001").unwrap();
+        let key_b = Uuid::parse_str("0190f1c2-0000-7000-8000-000000000002").unwrap();
+
+        let secret_a = derive_signing_secret("server", key_a);
+        assert_eq!(secret_a, derive_signing_secret("server", key_a));
+        assert_ne!(secret_a, derive_signing_secret("server", key_b));
+        assert_ne!(secret_a, derive_signing_secret("other", key_a));
+        assert_eq!(secret_a.len(), 64);
+    }
+
+    #[test]
+    fn test_verify_valid_signature() {
+        let sig = compute_signature(SECRET, TS, "POST", "/v1/embed", BODY);
+        let now = TS.parse::<i64>().unwrap() + 10;
+
+        assert_eq!(
+            verify_signature(SECRET, Some(TS), Some(&sig), "POST", "/v1/embed", BODY, now),
+            Ok(sig.clone())
+        );
+        let prefixed = format!("v1={}", sig);
+        assert_eq!(
+            verify_signature(
+                SECRET,
+                Some(TS),
+                Some(&prefixed),
+                "POST",
+                "/v1/embed",
+                BODY,
+                now
+            ),
+            Ok(sig)
+        );
+    }
+
+    #[test]
+    fn test_spellings_of_a_signature_share_a_replay_key() {
+        let sig = compute_signature(SECRET, TS, "POST", "/v1/embed", BODY);
+        let now = TS.parse::<i64>().unwrap();
+        let key_id = Uuid::parse_str("0190f1c2-0000-7000-8000-000000000001").unwrap();
+        let replay_key_of = |header: &str| {
+            let verified = verify_signature(
+                SECRET,
+                Some(TS),
+                Some(header),
+                "POST",
+                "/v1/embed",
+                BODY,
+                now,
+            )
+            .unwrap();
+            replay_key(key_id, &verified)
+        };
+
+        let expected = replay_key_of(&sig);
+        assert_eq!(expected, format!("sigseen:{}:{}", key_id.simple(), sig));
+        for header in [
+            sig.to_uppercase(),
+            format!("v1={}", sig),
+            format!("v1={}", sig.to_uppercase()),
+            format!("  {}\t", sig),
+        ] {
+            assert_eq!(replay_key_of(&header), expected, "{}", header);
+        }
+
+        // The same signature under another key is another entry
+        let other = Uuid::parse_str("0190f1c2-0000-7000-8000-000000000002").unwrap();
+        assert_ne!(replay_key(other, &sig), expected);
+    }
+
+    #[test]
+    fn test_verify_rejects_tampering() {
+        let sig = compute_signature(SECRET, TS, "POST", "/v1/embed", BODY);
+        let now = TS.parse::<i64>().unwrap();
+
+        let tampered_body = br#"{"text":"Hello World"}"#;
+        assert_eq!(
+            verify_signature(
+                SECRET,
+                Some(TS),
+                Some(&sig),
+                "POST",
+                "/v1/embed",
+                tampered_body,
+                now
+            ),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify_signature(SECRET, Some(TS), Some(&sig), "POST", "/v1/other", BODY, now),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify_signature(
+                "wrong",
+                Some(TS),
+                Some(&sig),
+                "POST",
+                "/v1/embed",
+                BODY,
+                now
+            ),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify_signature(SECRET, Some(TS), Some("zz"), "POST", "/v1/embed", BODY, now),
+            Err(SignatureError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn test_verify_clock_skew_window() {
+        let sig = compute_signature(SECRET, TS, "POST", "/v1/embed", BODY);
+        let ts = TS.parse::<i64>().unwrap();
+
+        for now in [ts - MAX_CLOCK_SKEW_SECS, ts + MAX_CLOCK_SKEW_SECS] {
+            assert!(
+                verify_signature(SECRET, Some(TS), Some(&sig), "POST", "/v1/embed", BODY, now)
+                    .is_ok()
+            );
+        }
+        for now in [ts - MAX_CLOCK_SKEW_SECS - 1, ts + MAX_CLOCK_SKEW_SECS + 1] {
+            assert_eq!(
+                verify_signature(SECRET, Some(TS), Some(&sig), "POST", "/v1/embed", BODY, now),
+                Err(SignatureError::Expired)
+            );
+        }
+    }
+
+    #[test]
+    fn test_verify_missing_headers() {
+        assert_eq!(
+            verify_signature(SECRET, None, Some("ab"), "POST", "/v1/embed", BODY, 0),
+            Err(SignatureError::Missing)
+        );
+        assert_eq!(
+            verify_signature(SECRET, Some(TS), None, "POST", "/v1/embed", BODY, 0),
+            Err(SignatureError::Missing)
+        );
+        assert_eq!(
+            verify_signature(
+                SECRET,
+                Some("yesterday"),
+                Some("ab"),
+                "POST",
+                "/v1/embed",
+                BODY,
+                0
+            ),
+            Err(SignatureError::InvalidTimestamp)
+        );
+    }
+}
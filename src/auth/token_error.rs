@@ -0,0 +1,341 @@
+//! Why an API key token failed to verify, for clients to act on.
+//!
+//! Keys get mangled on their way into config files and environment
+//! variables: cut off at a line width, re-encoded, pasted with a character
+//! missing. Each failure of `verify_token_direct` before the claims are
+//! read has a stable code, and a truncated token (one whose CBOR length
+//! prefixes promise more bytes than it has) says about how much of it is
+//! missing. Nothing derived from the signing keys is ever reported.
+
+use base64::Engine;
+
+/// Deepest CBOR nesting walked when looking for truncation (tokens use 3)
+const MAX_DEPTH: usize = 8;
+
+/// Reasons a token can't be verified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenError {
+    /// Not standard base64
+    InvalidEncoding,
+    /// Valid so far but cut off, about `missing_chars` characters short
+    Truncated { missing_chars: usize },
+    /// Not a COSE_Sign1 structure of a plausible size
+    Malformed(&'static str),
+    /// Signed with another algorithm than EdDSA
+    WrongAlgorithm,
+    /// Signed by an unknown key, or altered after signing
+    BadSignature,
+}
+
+impl TokenError {
+    /// Stable code of the failure (`code` of the error response)
+    pub fn code(&self) -> &'static str {
+        match self {
+            TokenError::InvalidEncoding => "token_invalid_encoding",
+            TokenError::Truncated { .. } => "token_truncated",
+            TokenError::Malformed(_) => "token_malformed",
+            TokenError::WrongAlgorithm => "token_wrong_algorithm",
+            TokenError::BadSignature => "token_bad_signature",
+        }
+    }
+
+    /// What the client can do about it, when there's more to say
+    pub fn hint(&self) -> Option<String> {
+        match self {
+            TokenError::InvalidEncoding => Some(
+                "token contains characters outside standard base64; check it wasn't altered when copied"
+                    .to_string(),
+            ),
+            TokenError::Truncated { missing_chars } => Some(format!(
+                "token appears truncated: expected ~{} more characters",
+                missing_chars
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenError::InvalidEncoding => write!(f, "Token is not valid base64"),
+            TokenError::Truncated { .. } => write!(f, "Token is incomplete"),
+            TokenError::Malformed(reason) => write!(f, "Token is malformed: {}", reason),
+            TokenError::WrongAlgorithm => write!(f, "Invalid algorithm: expected EdDSA"),
+            TokenError::BadSignature => write!(f, "Token signature is invalid"),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+/// Error for a token that isn't valid base64: truncated if what decodes of
+/// it is the start of a token, invalid otherwise
+pub fn undecodable(token: &str, max_len: usize) -> TokenError {
+    let trimmed = token.trim_end_matches('=');
+    if trimmed
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/')
+    {
+        // Only whole groups of 4 characters, the cut may be inside the last
+        let whole = &trimmed[..trimmed.len() / 4 * 4];
+        if let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(whole) {
+            if let Some(error) = truncation(token.len(), &bytes, max_len) {
+                return error;
+            }
+        }
+    }
+    TokenError::InvalidEncoding
+}
+
+/// `Truncated` if `bytes`, decoded from `token_chars` characters, is the
+/// start of a CBOR item of at most `max_len` bytes
+pub fn truncation(token_chars: usize, bytes: &[u8], max_len: usize) -> Option<TokenError> {
+    if bytes.is_empty() {
+        return None;
+    }
+    let expected = bytes.len() + cbor_shortfall(bytes)?;
+    if expected > max_len {
+        return None;
+    }
+    let expected_chars = expected.div_ceil(3) * 4;
+    Some(TokenError::Truncated {
+        missing_chars: expected_chars.saturating_sub(token_chars).max(1),
+    })
+}
+
+/// Bytes missing at the end of `bytes` for its first CBOR item to be
+/// complete, None if it is (or isn't CBOR a token would use). Items after
+/// the one cut off count as nothing, so this is a lower bound.
+pub fn cbor_shortfall(bytes: &[u8]) -> Option<usize> {
+    match item_end(bytes, 0, 0) {
+        Err(Walk::Missing(missing)) => Some(missing),
+        _ => None,
+    }
+}
+
+enum Walk {
+    Missing(usize),
+    Invalid,
+}
+
+/// Offset just past the CBOR item starting at `pos`
+fn item_end(bytes: &[u8], pos: usize, depth: usize) -> Result<usize, Walk> {
+    if depth > MAX_DEPTH {
+        return Err(Walk::Invalid);
+    }
+    let initial = *bytes
+        .get(pos)
+        .ok_or_else(|| Walk::Missing(pos + 1 - bytes.len()))?;
+    let (major, info) = (initial >> 5, initial & 0x1f);
+    let (argument, pos) = match info {
+        0..=23 => (u64::from(info), pos + 1),
+        24..=27 => {
+            let end = pos + 1 + (1 << (info - 24));
+            if end > bytes.len() {
+                return Err(Walk::Missing(end - bytes.len()));
+            }
+            let argument = bytes[pos + 1..end]
+                .iter()
+                .fold(0u64, |value, byte| value << 8 | u64::from(*byte));
+            (argument, end)
+        }
+        // Indefinite lengths and reserved values; tokens use neither
+        _ => return Err(Walk::Invalid),
+    };
+
+    match major {
+        // Integers, simple values and floats: just the header
+        0 | 1 | 7 => Ok(pos),
+        // Byte and text strings
+        2 | 3 => {
+            let end = usize::try_from(argument)
+                .ok()
+                .and_then(|len| pos.checked_add(len))
+                .ok_or(Walk::Invalid)?;
+            if end > bytes.len() {
+                return Err(Walk::Missing(end - bytes.len()));
+            }
+            Ok(end)
+        }
+        // Arrays and maps; the count is bounded by the bytes left, an item
+        // past the end stops the walk
+        4 | 5 => {
+            let items = if major == 5 {
+                argument.saturating_mul(2)
+            } else {
+                argument
+            };
+            let mut pos = pos;
+            for _ in 0..items {
+                pos = item_end(bytes, pos, depth + 1)?;
+            }
+            Ok(pos)
+        }
+        // Tag: the tagged item follows
+        _ => item_end(bytes, pos, depth + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::binding::TokenBinding;
+    use crate::auth::keyring::Keyring;
+    use crate::auth::{sign_token_direct, verify_token_direct, TokenData};
+    use crate::models::TierType;
+    use coset::{iana, CborSerializable, CoseSign1Builder, HeaderBuilder};
+    use ed25519_dalek::SigningKey;
+    use uuid::Uuid;
+
+    const CODES: [&str; 5] = [
+        "token_invalid_encoding",
+        "token_truncated",
+        "token_malformed",
+        "token_wrong_algorithm",
+        "token_bad_signature",
+    ];
+
+    fn signed_token() -> (String, Keyring) {
+        let signing_key = SigningKey::from_bytes(&[3; 32]);
+        let data = TokenData {
+            org_id: Uuid::now_v7(),
+            key_id: Uuid::now_v7(),
+            tier: TierType::Pro,
+            max_tokens: 512,
+            monthly_quota: 1_000_000,
+            require_signing: false,
+            max_concurrency: Some(4),
+            scopes: Some(vec!["embed".to_string()]),
+            models: None,
+            api_version: None,
+            strict_validation: false,
+            generation: 0,
+        };
+        let token = sign_token_direct(&data, &signing_key, &TokenBinding::default()).unwrap();
+        (token, Keyring::new(vec![signing_key.verifying_key()]))
+    }
+
+    /// Code of the failure, checking it's stable and one of the known codes
+    fn error_code(token: &str, keys: &Keyring) -> (&'static str, TokenError) {
+        let error = verify_token_direct(token, keys, &TokenBinding::default())
+            .expect_err("a mangled token must not verify");
+        let error = error
+            .downcast_ref::<TokenError>()
+            .unwrap_or_else(|| panic!("untyped error for {:?}: {}", token, error))
+            .clone();
+        let again = verify_token_direct(token, keys, &TokenBinding::default()).unwrap_err();
+        assert_eq!(again.downcast_ref::<TokenError>(), Some(&error));
+        assert!(CODES.contains(&error.code()));
+        (error.code(), error)
+    }
+
+    #[test]
+    fn test_cbor_shortfall() {
+        assert_eq!(cbor_shortfall(&[0x01]), None);
+        // Array of 2 with one item
+        assert_eq!(cbor_shortfall(&[0x82, 0x01]), Some(1));
+        // 3-byte string with 1 byte
+        assert_eq!(cbor_shortfall(&[0x43, 0xaa]), Some(2));
+        // 1-byte length prefix cut off
+        assert_eq!(cbor_shortfall(&[0x58]), Some(1));
+        // 300-byte string with 10 bytes
+        let mut bytes = vec![0x59, 0x01, 0x2c];
+        bytes.extend([0; 10]);
+        assert_eq!(cbor_shortfall(&bytes), Some(290));
+        // Indefinite lengths aren't followed
+        assert_eq!(cbor_shortfall(&[0x9f, 0x01]), None);
+    }
+
+    #[test]
+    fn test_truncated_tokens() {
+        let (token, keys) = signed_token();
+        assert!(verify_token_direct(&token, &keys, &TokenBinding::default()).is_ok());
+
+        for len in 0..token.len() {
+            let (code, error) = error_code(&token[..len], &keys);
+            if len < 4 {
+                // Not even one whole byte group to look at
+                assert_ne!(code, "token_bad_signature", "cut at {}", len);
+                continue;
+            }
+            assert_eq!(code, "token_truncated", "cut at {}", len);
+            let TokenError::Truncated { missing_chars } = error else {
+                unreachable!()
+            };
+            assert!(missing_chars >= 1, "cut at {}", len);
+            assert!(missing_chars <= token.len() - len + 4, "cut at {}", len);
+            assert!(error
+                .hint()
+                .unwrap()
+                .contains(&format!("~{}", missing_chars)));
+        }
+
+        // Cut inside the signature, the estimate is exact to the group
+        let (_, error) = error_code(&token[..token.len() - 20], &keys);
+        assert_eq!(
+            error.hint().unwrap(),
+            "token appears truncated: expected ~20 more characters"
+        );
+    }
+
+    #[test]
+    fn test_bit_flipped_tokens() {
+        let (token, keys) = signed_token();
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(&token)
+            .unwrap();
+
+        for i in 0..bytes.len() {
+            for bit in 0..8 {
+                let mut flipped = bytes.clone();
+                flipped[i] ^= 1 << bit;
+                let flipped = base64::engine::general_purpose::STANDARD.encode(&flipped);
+                let (code, _) = error_code(&flipped, &keys);
+                // The signature is the last 64 bytes
+                if i >= bytes.len() - 64 {
+                    assert_eq!(code, "token_bad_signature", "byte {} bit {}", i, bit);
+                }
+            }
+        }
+
+        // Flips in the text itself (staying ASCII)
+        for i in 0..token.len() {
+            for bit in 0..7 {
+                let mut flipped = token.clone().into_bytes();
+                flipped[i] ^= 1 << bit;
+                let flipped = String::from_utf8(flipped).unwrap();
+                if flipped == token {
+                    continue;
+                }
+                error_code(&flipped, &keys);
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrong_algorithm_and_unknown_key() {
+        let (token, _) = signed_token();
+        let other_keys = Keyring::new(vec![SigningKey::from_bytes(&[4; 32]).verifying_key()]);
+        assert_eq!(error_code(&token, &other_keys).0, "token_bad_signature");
+
+        let sign1 = CoseSign1Builder::new()
+            .protected(
+                HeaderBuilder::new()
+                    .algorithm(iana::Algorithm::ES256)
+                    .build(),
+            )
+            .payload(vec![0; 120])
+            .signature(vec![0; 64])
+            .build();
+        let token = base64::engine::general_purpose::STANDARD.encode(sign1.to_vec().unwrap());
+        let (code, error) = error_code(&token, &other_keys);
+        assert_eq!(code, "token_wrong_algorithm");
+        assert_eq!(error.hint(), None);
+
+        assert_eq!(
+            error_code("not a token!", &other_keys).0,
+            "token_invalid_encoding"
+        );
+    }
+}
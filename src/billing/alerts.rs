@@ -0,0 +1,508 @@
+//! Usage alerts: email an organization when its monthly usage crosses a
+//! percentage of the quota
+//!
+//! Thresholds and recipients are per organization (`usage_alert_settings`,
+//! edited on the billing page); without a row, owners are alerted at 80% and
+//! 100%. Each threshold fires at most once per billing cycle
+//! (`usage_alerts_sent`, keyed by the month the cycle started in), even if
+//! the counter crosses it again. Admins can switch all alerts off
+//! with `POST /v1/admin/usage-alerts`.
+//!
+//! On top of the thresholds, organizations get one forecast alert per cycle
+//! when the month-end forecast (`billing::forecast`) exceeds the quota while
+//! less than 80% of it is used, i.e. before the threshold alerts would say
+//! anything. It's recorded in `usage_alerts_sent` as threshold 0.
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use redis::AsyncCommands;
+use tracing::error;
+use uuid::Uuid;
+
+use super::cycle::BillingCycle;
+use super::forecast::Forecast;
+use super::statements::{format_month, month_start};
+use crate::database::DbPool;
+use crate::notifications::email::{self, EmailSender};
+use crate::notifications::templates;
+use crate::{database, redis_util};
+
+/// Thresholds used when an organization has no settings row
+pub const DEFAULT_THRESHOLDS: [i32; 2] = [80, 100];
+
+/// `usage_alerts_sent` threshold recording the forecast alert (configured
+/// thresholds are 1-100)
+pub const FORECAST_ALERT: i32 = 0;
+
+/// Maximum number of thresholds / recipients per organization
+pub const MAX_THRESHOLDS: usize = 10;
+pub const MAX_RECIPIENTS: usize = 10;
+
+/// Redis flag set while alerts are disabled globally (shared by all instances)
+const DISABLED_KEY: &str = "usage_alerts:disabled";
+
+/// Alert settings of an organization
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct AlertSettings {
+    pub enabled: bool,
+    /// Percentages of the monthly quota, ascending
+    #[cfg_attr(feature = "sqlite", sqlx(json))]
+    pub thresholds: Vec<i32>,
+    /// Recipient emails (empty = organization owners)
+    #[cfg_attr(feature = "sqlite", sqlx(json))]
+    pub recipients: Vec<String>,
+}
+
+impl Default for AlertSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            thresholds: DEFAULT_THRESHOLDS.to_vec(),
+            recipients: Vec::new(),
+        }
+    }
+}
+
+/// Thresholds crossed when usage goes from `before` to `after` requests
+pub fn crossed_thresholds(before: i64, after: i64, quota: i64, thresholds: &[i32]) -> Vec<i32> {
+    if quota <= 0 || after <= before {
+        return Vec::new();
+    }
+    thresholds
+        .iter()
+        .copied()
+        .filter(|&threshold| {
+            let mark = threshold as i64 * quota;
+            before * 100 < mark && mark <= after * 100
+        })
+        .collect()
+}
+
+/// Parse comma-separated percentages (1-100), sorted and deduplicated
+pub fn parse_thresholds(input: &str) -> Result<Vec<i32>, String> {
+    let mut thresholds = Vec::new();
+    for part in input.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let threshold: i32 = part
+            .trim_end_matches('%')
+            .parse()
+            .map_err(|_| format!("'{}' is not a percentage", part))?;
+        if !(1..=100).contains(&threshold) {
+            return Err(format!("{}% is not between 1% and 100%", threshold));
+        }
+        thresholds.push(threshold);
+    }
+    thresholds.sort_unstable();
+    thresholds.dedup();
+    if thresholds.len() > MAX_THRESHOLDS {
+        return Err(format!("At most {} thresholds are allowed", MAX_THRESHOLDS));
+    }
+    Ok(thresholds)
+}
+
+/// Parse comma- or newline-separated recipient emails
+pub fn parse_recipients(input: &str) -> Result<Vec<String>, String> {
+    let mut recipients: Vec<String> = Vec::new();
+    for email in input
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+    {
+        if !validator::ValidateEmail::validate_email(&email) {
+            return Err(format!("'{}' is not a valid email address", email));
+        }
+        let email = email.to_lowercase();
+        if !recipients.contains(&email) {
+            recipients.push(email);
+        }
+    }
+    if recipients.len() > MAX_RECIPIENTS {
+        return Err(format!("At most {} recipients are allowed", MAX_RECIPIENTS));
+    }
+    Ok(recipients)
+}
+
+/// Settings of an organization (defaults if never saved)
+pub async fn load_settings(pool: &DbPool, org_id: Uuid) -> Result<AlertSettings> {
+    let settings = sqlx::query_as::<_, AlertSettings>(
+        "SELECT enabled, thresholds, recipients FROM usage_alert_settings WHERE organization_id = $1",
+    )
+    .bind(org_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(settings.unwrap_or_default())
+}
+
+pub async fn save_settings(pool: &DbPool, org_id: Uuid, settings: &AlertSettings) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO usage_alert_settings (organization_id, enabled, thresholds, recipients, updated_at)
+         VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP)
+         ON CONFLICT (organization_id) DO UPDATE
+         SET enabled = $2, thresholds = $3, recipients = $4, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(org_id)
+    .bind(settings.enabled)
+    .bind(database::list(&settings.thresholds))
+    .bind(database::list(&settings.recipients))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Whether alerts are switched off for everyone
+pub async fn globally_disabled() -> Result<bool> {
+    let mut conn = redis_util::get_connection();
+    Ok(conn.exists(DISABLED_KEY).await?)
+}
+
+/// Admin override: switch alerts off (or back on) for all organizations
+pub async fn set_globally_enabled(enabled: bool) -> Result<()> {
+    let mut conn = redis_util::get_connection();
+    if enabled {
+        conn.del::<_, ()>(DISABLED_KEY).await?;
+    } else {
+        conn.set::<_, _, ()>(DISABLED_KEY, 1).await?;
+    }
+    Ok(())
+}
+
+/// Send the alerts for thresholds crossed by a usage change. Returns the
+/// number of thresholds that fired (already-sent ones are skipped).
+pub async fn evaluate(
+    pool: &DbPool,
+    sender: &dyn EmailSender,
+    org_id: Uuid,
+    cycle_start: NaiveDate,
+    before: i64,
+    after: i64,
+    quota: i64,
+) -> Result<usize> {
+    let settings = load_settings(pool, org_id).await?;
+    if !settings.enabled {
+        return Ok(0);
+    }
+    let crossed = crossed_thresholds(before, after, quota, &settings.thresholds);
+    if crossed.is_empty() {
+        return Ok(0);
+    }
+
+    let month = month_start(cycle_start);
+    let (org_name, recipients) = recipients(pool, org_id, &settings).await?;
+    let mut fired = 0;
+
+    for threshold in crossed {
+        if !claim(pool, org_id, month, threshold).await? {
+            continue;
+        }
+
+        let email = templates::usage_alert(&org_name, threshold, after, quota, month);
+        send(sender, &recipients, &email).await;
+
+        tracing::warn!(
+            target: "audit",
+            action = "usage_alert.sent",
+            org_id = %org_id,
+            month = %format_month(month),
+            threshold = threshold,
+            usage = after,
+            quota = quota,
+            recipients = recipients.len(),
+            "Usage alert sent"
+        );
+        fired += 1;
+    }
+
+    Ok(fired)
+}
+
+/// Send the forecast alert if `forecast` exceeds the quota while usage is
+/// still below 80% of it (once per cycle). Returns whether it fired.
+pub async fn evaluate_forecast(
+    pool: &DbPool,
+    sender: &dyn EmailSender,
+    org_id: Uuid,
+    cycle_start: NaiveDate,
+    forecast: &Forecast,
+    quota: i64,
+) -> Result<bool> {
+    if !forecast.should_alert(quota) {
+        return Ok(false);
+    }
+    let settings = load_settings(pool, org_id).await?;
+    if !settings.enabled {
+        return Ok(false);
+    }
+
+    let month = month_start(cycle_start);
+    if !claim(pool, org_id, month, FORECAST_ALERT).await? {
+        return Ok(false);
+    }
+    let (org_name, recipients) = recipients(pool, org_id, &settings).await?;
+    let email = templates::usage_forecast_alert(
+        &org_name,
+        forecast.requests.used,
+        forecast.requests.projected,
+        quota,
+        forecast.exhaustion_date,
+        month,
+    );
+    send(sender, &recipients, &email).await;
+
+    tracing::warn!(
+        target: "audit",
+        action = "usage_alert.forecast_sent",
+        org_id = %org_id,
+        month = %format_month(month),
+        usage = forecast.requests.used,
+        projected = forecast.requests.projected,
+        quota = quota,
+        recipients = recipients.len(),
+        "Usage forecast alert sent"
+    );
+    Ok(true)
+}
+
+/// Claim an alert for the month; losing the race means another request
+/// (or instance) already sent it
+async fn claim(pool: &DbPool, org_id: Uuid, month: NaiveDate, threshold: i32) -> Result<bool> {
+    let claimed = sqlx::query(
+        "INSERT INTO usage_alerts_sent (organization_id, month, threshold)
+         VALUES ($1, $2, $3)
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(org_id)
+    .bind(month)
+    .bind(threshold)
+    .execute(pool)
+    .await?
+    .rows_affected()
+        == 1;
+    Ok(claimed)
+}
+
+async fn send(sender: &dyn EmailSender, recipients: &[String], email: &templates::Email) {
+    for to in recipients {
+        if let Err(e) = sender
+            .send(to, &email.subject, &email.html_body, &email.text_body)
+            .await
+        {
+            error!("Failed to send usage alert to {}: {}", to, e);
+        }
+    }
+}
+
+/// Whether usage moved past a whole percentage of the quota (the only
+/// changes that can cross a threshold)
+pub fn crosses_percentage(before: i64, after: i64, quota: i64) -> bool {
+    quota > 0 && after > before && before * 100 / quota != after * 100 / quota
+}
+
+/// Evaluate the alerts after the counter of `cycle` moved; errors are
+/// logged. The forecast is only recomputed when usage moves past a whole
+/// percentage, at most 80 times a cycle.
+pub async fn check(org_id: Uuid, before: i64, after: i64, quota: i64, cycle: BillingCycle) {
+    if !crosses_percentage(before, after, quota) {
+        return;
+    }
+    match globally_disabled().await {
+        Ok(false) => {}
+        Ok(true) => return,
+        Err(e) => {
+            error!("Failed to read usage alert override: {}", e);
+            return;
+        }
+    }
+
+    // Queued, so a slow mail server doesn't hold up usage accounting
+    let sender = email::outbox();
+    let pool = database::get_db();
+    let cycle_start = cycle.start.date_naive();
+    if let Err(e) = evaluate(pool, sender, org_id, cycle_start, before, after, quota).await {
+        error!("Failed to evaluate usage alerts for {}: {}", org_id, e);
+    }
+
+    if after * 100 >= super::forecast::ALERT_BELOW_PERCENT * quota {
+        return;
+    }
+    let result = match super::cycle_forecast(pool, org_id, cycle, after, quota).await {
+        Ok(forecast) => {
+            evaluate_forecast(pool, sender, org_id, cycle_start, &forecast, quota).await
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = result {
+        error!("Failed to evaluate the usage forecast of {}: {}", org_id, e);
+    }
+}
+
+/// Organization name and the emails to alert
+async fn recipients(
+    pool: &DbPool,
+    org_id: Uuid,
+    settings: &AlertSettings,
+) -> Result<(String, Vec<String>)> {
+    let org_name: String = sqlx::query_scalar("SELECT name FROM organizations WHERE id = $1")
+        .bind(org_id)
+        .fetch_one(pool)
+        .await?;
+
+    if !settings.recipients.is_empty() {
+        return Ok((org_name, settings.recipients.clone()));
+    }
+    Ok((org_name, owner_emails(pool, org_id).await?))
+}
+
+/// Emails of the organization's owners
+pub async fn owner_emails(pool: &DbPool, org_id: Uuid) -> Result<Vec<String>> {
+    let owners = sqlx::query_scalar(
+        "SELECT u.email FROM organization_members om
+         INNER JOIN users u ON u.id = om.user_id
+         WHERE om.organization_id = $1 AND om.role = 'owner'
+         ORDER BY u.email",
+    )
+    .bind(org_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(owners)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::factory;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    #[cfg(feature = "control-plane")]
+    use axum::async_trait;
+    #[cfg(feature = "control-plane")]
+    use parking_lot::Mutex;
+    #[cfg(feature = "control-plane")]
+    use serial_test::serial;
+
+    #[cfg(feature = "control-plane")]
+    #[derive(Default)]
+    struct RecordingSender {
+        sent: Mutex<Vec<(String, String)>>,
+    }
+
+    #[cfg(feature = "control-plane")]
+    #[async_trait]
+    impl EmailSender for RecordingSender {
+        async fn send(&self, to: &str, subject: &str, _html: &str, _text: &str) -> Result<()> {
+            self.sent.lock().push((to.to_string(), subject.to_string()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_crossed_thresholds() {
+        let thresholds = [50, 80, 100];
+
+        assert_eq!(crossed_thresholds(79, 80, 100, &thresholds), vec![80]);
+        assert!(crossed_thresholds(80, 81, 100, &thresholds).is_empty());
+        assert_eq!(
+            crossed_thresholds(0, 100, 100, &thresholds),
+            vec![50, 80, 100]
+        );
+        // 80% of 20000 is 16000
+        assert!(crossed_thresholds(15_998, 15_999, 20_000, &thresholds).is_empty());
+        assert_eq!(
+            crossed_thresholds(15_999, 16_000, 20_000, &thresholds),
+            vec![80]
+        );
+        assert!(crossed_thresholds(10, 20, 0, &thresholds).is_empty());
+
+        assert!(crosses_percentage(15_999, 16_000, 20_000));
+        assert!(!crosses_percentage(16_000, 16_001, 20_000));
+    }
+
+    #[test]
+    fn test_parse_settings_inputs() {
+        assert_eq!(parse_thresholds("100, 80%,80").unwrap(), vec![80, 100]);
+        assert!(parse_thresholds("0").is_err());
+        assert!(parse_thresholds("eighty").is_err());
+        assert_eq!(parse_thresholds("").unwrap(), Vec::<i32>::new());
+
+        assert_eq!(
+            parse_recipients("Ops@Example.com\nfinance@example.com, ops@example.com").unwrap(),
+            vec!["ops@example.com", "finance@example.com"]
+        );
+        assert!(parse_recipients("not-an-email").is_err());
+    }
+
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    #[serial]
+    async fn test_crossing_80_percent_twice_sends_one_email() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let org_id = factory::user("owner@example.com").await.org_id;
+        let sender = RecordingSender::default();
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+
+        let fired = evaluate(pool, &sender, org_id, today, 79, 80, 100)
+            .await
+            .unwrap();
+        assert_eq!(fired, 1);
+
+        // The counter crosses 80% again in the same month (e.g. after a reset)
+        let fired = evaluate(pool, &sender, org_id, today, 79, 80, 100)
+            .await
+            .unwrap();
+        assert_eq!(fired, 0);
+
+        let sent = sender.sent.lock().clone();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, "owner@example.com");
+        assert!(sent[0].1.contains("80%"));
+
+        // Next month the threshold fires again
+        let next_month = NaiveDate::from_ymd_opt(2025, 4, 2).unwrap();
+        let fired = evaluate(pool, &sender, org_id, next_month, 79, 80, 100)
+            .await
+            .unwrap();
+        assert_eq!(fired, 1);
+
+        cleanup_db().await;
+    }
+
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    #[serial]
+    async fn test_forecast_alert_fires_once_per_cycle() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let org_id = factory::user("owner@example.com").await.org_id;
+        let sender = RecordingSender::default();
+        let cycle = BillingCycle::containing("2025-03-10T12:00:00Z".parse().unwrap(), None);
+        let cycle_start = cycle.start.date_naive();
+        let now = "2025-03-10T12:00:00Z".parse().unwrap();
+
+        // 40% used a third of the way in: on track for ~130%
+        let forecast = super::super::forecast::forecast(&[], 400, 0, 1_000, cycle, now);
+        assert!(
+            evaluate_forecast(pool, &sender, org_id, cycle_start, &forecast, 1_000)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !evaluate_forecast(pool, &sender, org_id, cycle_start, &forecast, 1_000)
+                .await
+                .unwrap()
+        );
+
+        // The 80% threshold is still sent on its own
+        let fired = evaluate(pool, &sender, org_id, cycle_start, 799, 800, 1_000)
+            .await
+            .unwrap();
+        assert_eq!(fired, 1);
+
+        let sent = sender.sent.lock().clone();
+        assert_eq!(sent.len(), 2);
+        assert!(sent[0].1.contains("on track to exceed"));
+
+        cleanup_db().await;
+    }
+}
@@ -0,0 +1,158 @@
+//! Soft cap on concurrent requests per API key
+//!
+//! In-flight requests are counted per key_id. A request takes a slot before
+//! it may run inference and gives it back when its guard is dropped, so one
+//! client can't occupy the model with many parallel requests even while it
+//! is within its quota. The cap comes from the tier (`*_TIER_CONCURRENCY`)
+//! unless the key carries a lower one.
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use uuid::Uuid;
+
+/// In-flight request counts per API key
+#[derive(Default)]
+pub struct ConcurrencyLimiter {
+    in_flight: DashMap<Uuid, u32>,
+}
+
+/// Slot held by a running request (released on drop)
+pub struct InFlightGuard<'a> {
+    limiter: &'a ConcurrencyLimiter,
+    key_id: Uuid,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take a slot for `key_id`, or None if `limit` requests are already
+    /// running (a limit of 0 means unlimited)
+    pub fn try_acquire(&self, key_id: Uuid, limit: u32) -> Option<InFlightGuard<'_>> {
+        let mut count = self.in_flight.entry(key_id).or_insert(0);
+        if limit > 0 && *count >= limit {
+            return None;
+        }
+        *count += 1;
+        drop(count);
+
+        Some(InFlightGuard {
+            limiter: self,
+            key_id,
+        })
+    }
+
+    /// Requests currently running for a key
+    pub fn in_flight(&self, key_id: Uuid) -> u32 {
+        self.in_flight.get(&key_id).map_or(0, |count| *count)
+    }
+
+    /// Keys with running requests and their counts, busiest first
+    pub fn snapshot(&self) -> Vec<(Uuid, u32)> {
+        let mut counts: Vec<_> = self
+            .in_flight
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        counts
+    }
+
+    fn release(&self, key_id: Uuid) {
+        // Drop idle keys so the map only holds keys with running requests
+        self.in_flight.remove_if_mut(&key_id, |_, count| {
+            *count = count.saturating_sub(1);
+            *count == 0
+        });
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.limiter.release(self.key_id);
+    }
+}
+
+static LIMITER: Lazy<ConcurrencyLimiter> = Lazy::new(ConcurrencyLimiter::new);
+
+/// Process-wide limiter used by the embed endpoint
+pub fn limiter() -> &'static ConcurrencyLimiter {
+    &LIMITER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use tokio::sync::Barrier;
+
+    #[test]
+    fn test_guard_releases_slot() {
+        let limiter = ConcurrencyLimiter::new();
+        let key_id = Uuid::now_v7();
+
+        let first = limiter.try_acquire(key_id, 2).unwrap();
+        let second = limiter.try_acquire(key_id, 2).unwrap();
+        assert!(limiter.try_acquire(key_id, 2).is_none());
+        assert_eq!(limiter.in_flight(key_id), 2);
+
+        // Other keys are not affected
+        assert!(limiter.try_acquire(Uuid::now_v7(), 2).is_some());
+
+        drop(first);
+        assert_eq!(limiter.in_flight(key_id), 1);
+        assert!(limiter.try_acquire(key_id, 2).is_some());
+
+        drop(second);
+        assert_eq!(limiter.in_flight(key_id), 0);
+        assert!(limiter.snapshot().is_empty());
+    }
+
+    #[test]
+    fn test_zero_limit_is_unlimited() {
+        let limiter = ConcurrencyLimiter::new();
+        let key_id = Uuid::now_v7();
+
+        let guards: Vec<_> = (0..100)
+            .map(|_| limiter.try_acquire(key_id, 0).unwrap())
+            .collect();
+        assert_eq!(limiter.snapshot(), vec![(key_id, 100)]);
+        drop(guards);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_simultaneous_requests_over_cap_are_rejected() {
+        const CAP: u32 = 3;
+        const REQUESTS: usize = 10;
+
+        let limiter: &'static ConcurrencyLimiter = Box::leak(Box::new(ConcurrencyLimiter::new()));
+        let key_id = Uuid::now_v7();
+        let started = Arc::new(Barrier::new(REQUESTS));
+        let finish = Arc::new(Barrier::new(REQUESTS));
+
+        let tasks: Vec<_> = (0..REQUESTS)
+            .map(|_| {
+                let started = started.clone();
+                let finish = finish.clone();
+                tokio::spawn(async move {
+                    started.wait().await;
+                    let guard = limiter.try_acquire(key_id, CAP);
+                    let admitted = guard.is_some();
+                    // Hold the slot until every request has tried
+                    finish.wait().await;
+                    admitted
+                })
+            })
+            .collect();
+
+        let mut admitted = 0;
+        for task in tasks {
+            admitted += task.await.unwrap() as u32;
+        }
+
+        assert_eq!(admitted, CAP);
+        assert_eq!(limiter.in_flight(key_id), 0);
+    }
+}
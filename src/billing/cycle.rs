@@ -0,0 +1,243 @@
+//! Billing cycles: the window the free tier's quota is counted over
+//!
+//! By default a cycle is the calendar month. Organizations with a
+//! `billing_anchor_day` (1-31, set with
+//! `PUT /v1/organizations/:org_id/billing-anchor`) reset on that day of the
+//! month instead, or on the last day of shorter months (anchor 31 resets on
+//! Feb 28/29, Apr 30, ...). Changing the anchor carries the current
+//! cycle's count over to the new cycle and is allowed once every 30 days
+//! (see `services::organizations::set_billing_anchor`).
+//!
+//! The anchor is read on every free tier request, so it's cached in process
+//! like the cache policy (see `cache::policy`).
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::database;
+
+/// How long an anchor lookup is trusted before hitting the database again
+const ANCHOR_CACHE_TTL_SECS: u64 = 30;
+
+/// Valid anchor days
+pub const ANCHOR_DAYS: std::ops::RangeInclusive<u32> = 1..=31;
+
+/// Bounds of a billing cycle (start inclusive, end exclusive, midnight UTC)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BillingCycle {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+impl BillingCycle {
+    /// Cycle containing `now` for an anchor day (None = calendar month)
+    pub fn containing(now: DateTime<Utc>, anchor_day: Option<u32>) -> Self {
+        let anchor_day = anchor_day.unwrap_or(1);
+        let today = now.date_naive();
+        let (year, month) = (today.year(), today.month());
+
+        let this_month = anchor_date(year, month, anchor_day);
+        let (start, end) = if today >= this_month {
+            let (next_year, next_month) = next_month(year, month);
+            (this_month, anchor_date(next_year, next_month, anchor_day))
+        } else {
+            let (prev_year, prev_month) = previous_month(year, month);
+            (anchor_date(prev_year, prev_month, anchor_day), this_month)
+        };
+
+        BillingCycle {
+            start: midnight(start),
+            end: midnight(end),
+        }
+    }
+
+    /// Redis key suffix of the cycle's counter
+    pub fn key(&self) -> String {
+        self.start.format("%Y-%m-%d").to_string()
+    }
+}
+
+/// The anchor day in a month, clamped to the month's last day
+fn anchor_date(year: i32, month: u32, anchor_day: u32) -> NaiveDate {
+    let (next_year, next_month) = next_month(year, month);
+    let last_day = NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|first| first.pred_opt())
+        .map_or(28, |last| last.day());
+    NaiveDate::from_ymd_opt(year, month, anchor_day.clamp(1, last_day))
+        .expect("clamped day is valid")
+}
+
+fn next_month(year: i32, month: u32) -> (i32, u32) {
+    if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    }
+}
+
+fn previous_month(year: i32, month: u32) -> (i32, u32) {
+    if month == 1 {
+        (year - 1, 12)
+    } else {
+        (year, month - 1)
+    }
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0)
+        .expect("midnight is valid")
+        .and_utc()
+}
+
+/// org_id -> (anchor day, checked_at)
+static ANCHOR_CACHE: Lazy<DashMap<Uuid, (Option<u32>, Instant)>> = Lazy::new(DashMap::new);
+
+/// Billing anchor day of the organization. Lookup failures fall back to the
+/// last known anchor, else to the calendar month.
+pub async fn org_anchor(org_id: Uuid) -> Option<u32> {
+    if let Some(entry) = ANCHOR_CACHE.get(&org_id) {
+        let (anchor, checked_at) = *entry;
+        if checked_at.elapsed().as_secs() < ANCHOR_CACHE_TTL_SECS {
+            return anchor;
+        }
+    }
+
+    let row = sqlx::query_scalar::<_, Option<i32>>(
+        "SELECT billing_anchor_day FROM organizations WHERE id = $1",
+    )
+    .bind(org_id)
+    .fetch_optional(database::get_db())
+    .await;
+
+    match row {
+        Ok(anchor) => {
+            let anchor = anchor.flatten().map(|day| day as u32);
+            ANCHOR_CACHE.insert(org_id, (anchor, Instant::now()));
+            anchor
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load billing anchor of {}: {}", org_id, e);
+            ANCHOR_CACHE.get(&org_id).and_then(|entry| entry.0)
+        }
+    }
+}
+
+/// Current billing cycle of the organization
+pub async fn current(org_id: Uuid, now: DateTime<Utc>) -> BillingCycle {
+    BillingCycle::containing(now, org_anchor(org_id).await)
+}
+
+/// Forget a cached anchor (call after changing it)
+pub fn invalidate(org_id: Uuid) {
+    ANCHOR_CACHE.remove(&org_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle(now: &str, anchor_day: Option<u32>) -> (String, String) {
+        let cycle = BillingCycle::containing(now.parse().unwrap(), anchor_day);
+        (
+            cycle.start.date_naive().to_string(),
+            cycle.end.date_naive().to_string(),
+        )
+    }
+
+    fn dates(start: &str, end: &str) -> (String, String) {
+        (start.to_string(), end.to_string())
+    }
+
+    #[test]
+    fn test_calendar_month_without_anchor() {
+        assert_eq!(
+            cycle("2025-03-15T10:00:00Z", None),
+            dates("2025-03-01", "2025-04-01")
+        );
+        assert_eq!(
+            cycle("2025-12-31T23:59:59Z", None),
+            dates("2025-12-01", "2026-01-01")
+        );
+        assert_eq!(
+            cycle("2025-03-15T10:00:00Z", Some(1)),
+            cycle("2025-03-15T10:00:00Z", None)
+        );
+    }
+
+    #[test]
+    fn test_anchor_day() {
+        // Before, on and after the anchor day
+        assert_eq!(
+            cycle("2025-03-19T23:59:59Z", Some(20)),
+            dates("2025-02-20", "2025-03-20")
+        );
+        assert_eq!(
+            cycle("2025-03-20T00:00:00Z", Some(20)),
+            dates("2025-03-20", "2025-04-20")
+        );
+        // Across the year boundary
+        assert_eq!(
+            cycle("2026-01-05T00:00:00Z", Some(20)),
+            dates("2025-12-20", "2026-01-20")
+        );
+        assert_eq!(
+            cycle("2025-12-25T00:00:00Z", Some(20)),
+            dates("2025-12-20", "2026-01-20")
+        );
+    }
+
+    #[test]
+    fn test_anchor_beyond_month_end() {
+        // Anchor 31 resets on the last day of shorter months
+        assert_eq!(
+            cycle("2025-02-28T00:00:00Z", Some(31)),
+            dates("2025-02-28", "2025-03-31")
+        );
+        assert_eq!(
+            cycle("2025-02-27T12:00:00Z", Some(31)),
+            dates("2025-01-31", "2025-02-28")
+        );
+        assert_eq!(
+            cycle("2025-04-30T00:00:00Z", Some(31)),
+            dates("2025-04-30", "2025-05-31")
+        );
+        assert_eq!(
+            cycle("2025-03-30T00:00:00Z", Some(31)),
+            dates("2025-02-28", "2025-03-31")
+        );
+        assert_eq!(
+            cycle("2025-02-28T00:00:00Z", Some(30)),
+            dates("2025-02-28", "2025-03-30")
+        );
+    }
+
+    #[test]
+    fn test_leap_years() {
+        assert_eq!(
+            cycle("2024-02-29T08:00:00Z", Some(30)),
+            dates("2024-02-29", "2024-03-30")
+        );
+        assert_eq!(
+            cycle("2024-02-28T08:00:00Z", Some(29)),
+            dates("2024-01-29", "2024-02-29")
+        );
+        assert_eq!(
+            cycle("2025-02-28T08:00:00Z", Some(29)),
+            dates("2025-02-28", "2025-03-29")
+        );
+        // 2100 isn't a leap year
+        assert_eq!(
+            cycle("2100-02-28T08:00:00Z", Some(29)),
+            dates("2100-02-28", "2100-03-29")
+        );
+    }
+
+    #[test]
+    fn test_cycle_key() {
+        let cycle = BillingCycle::containing("2025-03-02T10:00:00Z".parse().unwrap(), Some(20));
+        assert_eq!(cycle.key(), "2025-02-20");
+    }
+}
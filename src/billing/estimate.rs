@@ -0,0 +1,315 @@
+//! Cost and duration estimates (`POST /v1/estimate`)
+//!
+//! Answers "what will 10M texts of ~300 characters cost, and how long will
+//! they take" from a profile of the texts: exact token counts of sample
+//! texts, or a length histogram converted at a characters-per-token ratio
+//! (measured on the samples when there are some). The profile is scaled to
+//! the volume and priced with the tier's `TierLimits`. Durations are at the
+//! tier's requests per minute, for single requests and for batches of
+//! `MAX_BATCH_SIZE` texts.
+//!
+//! Nothing here needs the model, so pages can price a histogram without it.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::statements::compute_cost_cents;
+use crate::models::{TierLimits, TierType};
+
+/// Characters per token of English prose, for histograms without samples
+pub const DEFAULT_CHARS_PER_TOKEN: f64 = 4.0;
+
+/// Tokens the tokenizer adds to every text ([CLS] and [SEP])
+pub const SPECIAL_TOKENS: usize = 2;
+
+/// Share of the texts having about `chars` characters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LengthBucket {
+    #[schema(example = 300)]
+    pub chars: usize,
+    /// Relative weight (shares are normalized, so counts work too)
+    #[schema(example = 0.8)]
+    pub share: f64,
+}
+
+/// Tokens per text of a workload
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextProfile {
+    /// Mean billed tokens per text (after truncation to `max_tokens`)
+    pub mean_tokens: f64,
+    /// Share of the texts over `max_tokens` (truncated, or rejected
+    /// without `truncate`)
+    pub over_limit_share: f64,
+}
+
+impl TextProfile {
+    /// Profile of texts with these exact token counts (None if empty)
+    pub fn from_token_counts(counts: &[usize], max_tokens: usize) -> Option<Self> {
+        if counts.is_empty() {
+            return None;
+        }
+        let n = counts.len() as f64;
+        Some(TextProfile {
+            mean_tokens: counts
+                .iter()
+                .map(|&t| t.min(max_tokens) as f64)
+                .sum::<f64>()
+                / n,
+            over_limit_share: counts.iter().filter(|&&t| t > max_tokens).count() as f64 / n,
+        })
+    }
+
+    /// Profile of a length histogram at `chars_per_token` (None without a
+    /// positive share)
+    pub fn from_lengths(
+        buckets: &[LengthBucket],
+        chars_per_token: f64,
+        max_tokens: usize,
+    ) -> Option<Self> {
+        let total: f64 = buckets.iter().map(|bucket| bucket.share).sum();
+        if !total.is_finite() || total <= 0.0 {
+            return None;
+        }
+
+        let mut profile = TextProfile {
+            mean_tokens: 0.0,
+            over_limit_share: 0.0,
+        };
+        for bucket in buckets {
+            let weight = bucket.share / total;
+            let tokens = (bucket.chars as f64 / chars_per_token).ceil() as usize + SPECIAL_TOKENS;
+            profile.mean_tokens += weight * tokens.min(max_tokens) as f64;
+            if tokens > max_tokens {
+                profile.over_limit_share += weight;
+            }
+        }
+        Some(profile)
+    }
+}
+
+/// Characters per token of tokenized samples (`(chars, tokens)` each,
+/// special tokens included), None if they have no content tokens
+pub fn chars_per_token(samples: &[(usize, usize)]) -> Option<f64> {
+    let chars: usize = samples.iter().map(|(chars, _)| chars).sum();
+    let tokens: usize = samples
+        .iter()
+        .map(|(_, tokens)| tokens.saturating_sub(SPECIAL_TOKENS))
+        .sum();
+    (chars > 0 && tokens > 0).then(|| chars as f64 / tokens as f64)
+}
+
+/// How long the texts take at one request size
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Throughput {
+    /// `single` (one text per request) or `batch`
+    #[schema(example = "single")]
+    pub mode: String,
+    #[schema(example = 1)]
+    pub texts_per_request: u64,
+    #[schema(example = 10_000_000)]
+    pub requests: u64,
+    /// Rate limit of the tier (omitted if unlimited)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 600)]
+    pub requests_per_minute: Option<u32>,
+    /// Wall-clock time at that rate (omitted if unlimited)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1_000_000)]
+    pub seconds: Option<u64>,
+}
+
+impl Throughput {
+    fn new(mode: &str, texts: u64, texts_per_request: u64, rpm: u32) -> Self {
+        let requests = texts.div_ceil(texts_per_request.max(1));
+        Throughput {
+            mode: mode.to_string(),
+            texts_per_request,
+            requests,
+            requests_per_minute: (rpm > 0).then_some(rpm),
+            seconds: (rpm > 0).then(|| requests.saturating_mul(60).div_ceil(u64::from(rpm))),
+        }
+    }
+}
+
+/// Estimated tokens, cost and time of embedding a volume of texts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Estimate {
+    #[schema(example = "pro")]
+    pub tier: String,
+    /// Texts to embed
+    #[schema(example = 10_000_000)]
+    pub texts: u64,
+    /// Mean billed tokens per text
+    #[schema(example = 72.5)]
+    pub tokens_per_text: f64,
+    #[schema(example = 725_000_000)]
+    pub estimated_tokens: u64,
+    /// Share of the texts over the key's token limit: truncated with
+    /// `truncate`, rejected without
+    #[schema(example = 0.02)]
+    pub over_limit_share: f64,
+    /// Token limit per text of the key
+    #[schema(example = 128)]
+    pub max_tokens: usize,
+    #[schema(example = 50)]
+    pub price_per_million_tokens_cents: i64,
+    #[schema(example = 36_250)]
+    pub cost_cents: i64,
+    /// Requests per month of the free tier (omitted for paid tiers)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 20_000)]
+    pub monthly_quota: Option<i64>,
+    /// Months of quota the texts take one per request (omitted for paid
+    /// tiers)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 500)]
+    pub months_of_quota: Option<u64>,
+    pub throughput: Vec<Throughput>,
+}
+
+/// Who the estimate is for
+#[derive(Debug, Clone, Copy)]
+pub struct Plan {
+    /// Tier the requests run at (a running trial's)
+    pub tier: TierType,
+    pub limits: TierLimits,
+    /// Token limit per text of the key
+    pub max_tokens: usize,
+    /// Cents per million tokens billed (the base tier's during a trial)
+    pub price_per_million_tokens: i64,
+    /// Requests per month, for tiers with a monthly quota
+    pub monthly_quota: Option<i64>,
+}
+
+/// Estimate `texts` texts of `profile` on `plan`, with batches of
+/// `batch_size` texts
+pub fn estimate(plan: &Plan, profile: TextProfile, texts: u64, batch_size: usize) -> Estimate {
+    let estimated_tokens = (profile.mean_tokens * texts as f64).round() as u64;
+    let rpm = plan.limits.rpm;
+
+    let mut throughput = vec![Throughput::new("single", texts, 1, rpm)];
+    if batch_size > 1 {
+        throughput.push(Throughput::new("batch", texts, batch_size as u64, rpm));
+    }
+
+    Estimate {
+        tier: format!("{:?}", plan.tier).to_lowercase(),
+        texts,
+        tokens_per_text: (profile.mean_tokens * 10.0).round() / 10.0,
+        estimated_tokens,
+        over_limit_share: (profile.over_limit_share * 1000.0).round() / 1000.0,
+        max_tokens: plan.max_tokens,
+        price_per_million_tokens_cents: plan.price_per_million_tokens,
+        cost_cents: compute_cost_cents(
+            estimated_tokens.min(i64::MAX as u64) as i64,
+            plan.price_per_million_tokens,
+        ),
+        monthly_quota: plan.monthly_quota,
+        months_of_quota: plan
+            .monthly_quota
+            .filter(|quota| *quota > 0)
+            .map(|quota| texts.div_ceil(quota as u64)),
+        throughput,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+
+    #[test]
+    fn test_profile_from_token_counts_truncates() {
+        let profile = TextProfile::from_token_counts(&[10, 20, 200, 30], 128).unwrap();
+        assert_eq!(profile.mean_tokens, (10.0 + 20.0 + 128.0 + 30.0) / 4.0);
+        assert_eq!(profile.over_limit_share, 0.25);
+        assert!(TextProfile::from_token_counts(&[], 128).is_none());
+    }
+
+    #[test]
+    fn test_profile_from_lengths() {
+        // 300 chars at 4 per token = 75 tokens + [CLS]/[SEP]; 1000 chars
+        // is over the limit
+        let buckets = [
+            LengthBucket {
+                chars: 300,
+                share: 3.0,
+            },
+            LengthBucket {
+                chars: 1000,
+                share: 1.0,
+            },
+        ];
+        let profile = TextProfile::from_lengths(&buckets, 4.0, 128).unwrap();
+        assert_eq!(profile.mean_tokens, 0.75 * 77.0 + 0.25 * 128.0);
+        assert_eq!(profile.over_limit_share, 0.25);
+
+        let empty = [LengthBucket {
+            chars: 300,
+            share: 0.0,
+        }];
+        assert!(TextProfile::from_lengths(&empty, 4.0, 128).is_none());
+    }
+
+    #[test]
+    fn test_chars_per_token_excludes_special_tokens() {
+        assert_eq!(chars_per_token(&[(40, 12), (20, 7)]), Some(4.0));
+        assert_eq!(chars_per_token(&[(0, 2)]), None);
+    }
+
+    #[test]
+    fn test_estimate_prices_and_paces_the_volume() {
+        let plan = Plan {
+            tier: TierType::Pro,
+            limits: TierLimits {
+                rpm: 600,
+                ..TierLimits::from_settings(TierType::Pro, &Settings::new())
+            },
+            max_tokens: 128,
+            price_per_million_tokens: 50,
+            monthly_quota: None,
+        };
+        let profile = TextProfile {
+            mean_tokens: 77.0,
+            over_limit_share: 0.0,
+        };
+
+        let estimate = estimate(&plan, profile, 10_000_000, 32);
+        assert_eq!(estimate.tier, "pro");
+        assert_eq!(estimate.estimated_tokens, 770_000_000);
+        // 770M tokens at 50 cents per million
+        assert_eq!(estimate.cost_cents, 38_500);
+        assert_eq!(estimate.months_of_quota, None);
+
+        let single = &estimate.throughput[0];
+        assert_eq!(single.requests, 10_000_000);
+        assert_eq!(single.seconds, Some(1_000_000));
+        let batch = &estimate.throughput[1];
+        assert_eq!(batch.requests, 312_500);
+        assert_eq!(batch.seconds, Some(31_250));
+    }
+
+    #[test]
+    fn test_estimate_free_tier_quota_and_unlimited_rate() {
+        let plan = Plan {
+            tier: TierType::Free,
+            limits: TierLimits {
+                rpm: 0,
+                ..TierLimits::from_settings(TierType::Free, &Settings::new())
+            },
+            max_tokens: 128,
+            price_per_million_tokens: 0,
+            monthly_quota: Some(20_000),
+        };
+        let profile = TextProfile {
+            mean_tokens: 10.0,
+            over_limit_share: 0.0,
+        };
+
+        let estimate = estimate(&plan, profile, 50_000, 1);
+        assert_eq!(estimate.cost_cents, 0);
+        assert_eq!(estimate.months_of_quota, Some(3));
+        assert_eq!(estimate.throughput.len(), 1);
+        assert_eq!(estimate.throughput[0].seconds, None);
+    }
+}
@@ -0,0 +1,421 @@
+//! Month-end usage forecast: "will we run out before the cycle ends?"
+//!
+//! Pure math over the daily usage of the current billing cycle, used by
+//! `GET /v1/limits` and the forecast usage alert. Each complete day of the
+//! cycle is one observation (days without usage count as zero):
+//!
+//! - Day-of-week weights: a weekday's mean relative to the overall mean,
+//!   shrunk towards 1 by one pseudo-observation so a single busy Monday
+//!   doesn't triple every Monday. Weekdays not seen yet weigh 1.
+//! - A line through the last full week's mean, sloped by how much it
+//!   changed from the week before, extended to the end of the cycle and
+//!   scaled by each day's weight. Comparing whole weeks keeps the weekly
+//!   pattern out of the slope. Before `MIN_TREND_DAYS` days the line is
+//!   flat, and days it projects below zero count as 0.
+//! - The band is the projection ± 1.96 residual standard deviations summed
+//!   over the remaining days (roughly 95%, ignoring the slope's own error).
+//!
+//! Without any usage in the history (first day of a cycle, or no usage
+//! events recorded yet) the cycle's average rate so far is extended instead,
+//! with a daily spread as large as the rate itself.
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+
+use super::cycle::BillingCycle;
+use crate::database::usage_events::DailyTotals;
+
+/// Complete days of history before a trend is fitted (two full weeks)
+pub const MIN_TREND_DAYS: usize = 2 * WEEK;
+
+const WEEK: usize = 7;
+
+/// The forecast alert only fires while actual usage is below this
+/// percentage of the quota (above it, the threshold alerts take over)
+pub const ALERT_BELOW_PERCENT: i64 = 80;
+
+/// z-score of the band
+const BAND_Z: f64 = 1.96;
+
+/// Shortest elapsed time the average rate is computed over, so the first
+/// minutes of a cycle don't extrapolate a handful of requests into millions
+const MIN_ELAPSED_DAYS: f64 = 1.0 / 24.0;
+
+/// End-of-cycle projection of one metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Projection {
+    /// Used so far in the cycle
+    pub used: i64,
+    /// Projected total at the end of the cycle
+    pub projected: i64,
+    /// Lower and upper bound of the band (low is never below `used`)
+    pub low: i64,
+    pub high: i64,
+}
+
+/// Forecast of a billing cycle
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forecast {
+    pub requests: Projection,
+    pub tokens: Projection,
+    /// Projected requests as a percentage of the quota (None without quota)
+    pub projected_percent: Option<f64>,
+    /// Day the quota is projected to run out (None if it lasts the cycle)
+    pub exhaustion_date: Option<NaiveDate>,
+    /// Complete days the forecast is based on
+    pub days_observed: usize,
+}
+
+impl Forecast {
+    /// Whether to send the forecast alert: the quota is projected to be
+    /// exceeded while less than `ALERT_BELOW_PERCENT` of it is used
+    pub fn should_alert(&self, quota: i64) -> bool {
+        quota > 0
+            && self.requests.used * 100 < ALERT_BELOW_PERCENT * quota
+            && self.requests.projected > quota
+    }
+}
+
+/// Forecast the cycle containing `now`. `days` is the cycle's daily usage
+/// (missing days are zero, today's partial row and days outside the cycle
+/// are ignored), `used_requests` / `used_tokens` the totals so far
+/// including today, and `quota` the request quota (0 = none).
+pub fn forecast(
+    days: &[DailyTotals],
+    used_requests: i64,
+    used_tokens: i64,
+    quota: i64,
+    cycle: BillingCycle,
+    now: DateTime<Utc>,
+) -> Forecast {
+    let history = History::new(days, cycle, now);
+    let requests = history.model(|d| d.requests, used_requests);
+    let tokens = history.model(|d| d.tokens, used_tokens);
+
+    let requests_projection = history.project(&requests, used_requests);
+    Forecast {
+        projected_percent: (quota > 0)
+            .then(|| requests_projection.projected as f64 * 100.0 / quota as f64),
+        exhaustion_date: history.exhaustion_date(&requests, used_requests, quota),
+        requests: requests_projection,
+        tokens: history.project(&tokens, used_tokens),
+        days_observed: history.complete.len(),
+    }
+}
+
+/// The cycle's complete days and where `now` falls in it
+struct History {
+    /// Usage per complete day, from the cycle's first day to yesterday
+    complete: Vec<DailyTotals>,
+    start: NaiveDate,
+    today: NaiveDate,
+    end: NaiveDate,
+    /// Part of today still ahead (0-1)
+    today_remaining: f64,
+    /// Days elapsed since the cycle started (fractional)
+    elapsed_days: f64,
+}
+
+/// Expected usage per day of one metric
+struct Model {
+    /// Usage before the weekday weight is applied: `level` on day `origin`
+    /// of the cycle, changing by `slope` per day
+    level: f64,
+    slope: f64,
+    origin: f64,
+    /// Weight per weekday, Monday first
+    weights: [f64; 7],
+    /// Residual standard deviation of a day
+    sigma: f64,
+}
+
+impl History {
+    fn new(days: &[DailyTotals], cycle: BillingCycle, now: DateTime<Utc>) -> Self {
+        let start = cycle.start.date_naive();
+        let end = cycle.end.date_naive();
+        let today = now.date_naive().clamp(start, end);
+
+        let mut complete: Vec<DailyTotals> = start
+            .iter_days()
+            .take_while(|day| *day < today)
+            .map(|day| DailyTotals {
+                day,
+                requests: 0,
+                tokens: 0,
+            })
+            .collect();
+        for usage in days {
+            if let Some(slot) = complete.iter_mut().find(|slot| slot.day == usage.day) {
+                slot.requests += usage.requests;
+                slot.tokens += usage.tokens;
+            }
+        }
+
+        let midnight = today.and_hms_opt(0, 0, 0).expect("midnight").and_utc();
+        let into_today = (now - midnight).num_seconds().clamp(0, 86_400) as f64 / 86_400.0;
+        let elapsed = (now - cycle.start).num_seconds().max(0) as f64 / 86_400.0;
+
+        History {
+            complete,
+            start,
+            today,
+            end,
+            today_remaining: if today < end { 1.0 - into_today } else { 0.0 },
+            elapsed_days: elapsed,
+        }
+    }
+
+    /// Fit the model of a metric (`used` is its total so far)
+    fn model(&self, metric: impl Fn(&DailyTotals) -> i64, used: i64) -> Model {
+        let values: Vec<f64> = self.complete.iter().map(|d| metric(d) as f64).collect();
+        let n = values.len();
+        let total: f64 = values.iter().sum();
+
+        if total <= 0.0 {
+            // No history: the average rate of the cycle so far
+            let rate = used.max(0) as f64 / self.elapsed_days.max(MIN_ELAPSED_DAYS);
+            return Model {
+                level: rate,
+                slope: 0.0,
+                origin: 0.0,
+                weights: [1.0; 7],
+                sigma: rate,
+            };
+        }
+
+        let mean = total / n as f64;
+        let mut sums = [0.0; 7];
+        let mut counts = [0usize; 7];
+        for (usage, value) in self.complete.iter().zip(&values) {
+            let weekday = weekday(usage.day);
+            sums[weekday] += value;
+            counts[weekday] += 1;
+        }
+        let mut weights = [1.0; 7];
+        for (weight, (sum, count)) in weights.iter_mut().zip(sums.iter().zip(counts)) {
+            *weight = (sum + mean) / ((count + 1) as f64 * mean);
+        }
+        let norm = weights.iter().sum::<f64>() / 7.0;
+        for weight in &mut weights {
+            *weight /= norm;
+        }
+
+        let day_weights: Vec<f64> = self
+            .complete
+            .iter()
+            .map(|usage| weights[weekday(usage.day)])
+            .collect();
+        let week_mean = |weeks_back: usize| {
+            let end = n - weeks_back * WEEK;
+            values[end - WEEK..end].iter().sum::<f64>() / WEEK as f64
+        };
+        // Mid-point of the last full week
+        let origin = (n as f64 - 1.0) - (WEEK as f64 - 1.0) / 2.0;
+        let (level, slope, params) = if n >= MIN_TREND_DAYS {
+            let level = week_mean(0);
+            (level, (level - week_mean(1)) / WEEK as f64, 2)
+        } else if n >= WEEK {
+            (week_mean(0), 0.0, 1)
+        } else {
+            (total / day_weights.iter().sum::<f64>(), 0.0, 1)
+        };
+        let line = |t: f64| level + slope * (t - origin);
+
+        let sigma = if n > params {
+            let residuals: f64 = values
+                .iter()
+                .zip(&day_weights)
+                .enumerate()
+                .map(|(t, (value, weight))| (value - weight * line(t as f64)).powi(2))
+                .sum();
+            (residuals / (n - params) as f64).sqrt()
+        } else {
+            // Too few days to tell the spread
+            level.abs()
+        };
+
+        Model {
+            level,
+            slope,
+            origin,
+            weights,
+            sigma,
+        }
+    }
+
+    /// Remaining days of the cycle with the part of each still ahead:
+    /// the rest of today, then every later day
+    fn remaining(&self) -> impl Iterator<Item = (NaiveDate, f64)> + '_ {
+        std::iter::once((self.today, self.today_remaining))
+            .chain(
+                self.today
+                    .iter_days()
+                    .skip(1)
+                    .take_while(|day| *day < self.end)
+                    .map(|day| (day, 1.0)),
+            )
+            .filter(|(_, share)| *share > 0.0)
+    }
+
+    fn project(&self, model: &Model, used: i64) -> Projection {
+        let mut expected = 0.0;
+        let mut variance = 0.0;
+        for (day, share) in self.remaining() {
+            expected += model.expected(self.offset(day), day) * share;
+            variance += (model.sigma * share).powi(2);
+        }
+
+        let used = used.max(0);
+        let projected = used + expected.round() as i64;
+        let band = (BAND_Z * variance.sqrt()).round() as i64;
+        Projection {
+            used,
+            projected,
+            low: (projected - band).max(used),
+            high: projected + band,
+        }
+    }
+
+    /// First day cumulative usage is projected to reach `quota`
+    fn exhaustion_date(&self, model: &Model, used: i64, quota: i64) -> Option<NaiveDate> {
+        if quota <= 0 {
+            return None;
+        }
+        if used >= quota {
+            return Some(self.today);
+        }
+        let mut cumulative = used as f64;
+        for (day, share) in self.remaining() {
+            cumulative += model.expected(self.offset(day), day) * share;
+            if cumulative >= quota as f64 {
+                return Some(day);
+            }
+        }
+        None
+    }
+
+    /// Days since the cycle started
+    fn offset(&self, day: NaiveDate) -> f64 {
+        (day - self.start).num_days() as f64
+    }
+}
+
+impl Model {
+    /// Expected usage on `day`, `t` days into the cycle
+    fn expected(&self, t: f64, day: NaiveDate) -> f64 {
+        (self.level + self.slope * (t - self.origin)).max(0.0) * self.weights[weekday(day)]
+    }
+}
+
+/// Monday = 0
+fn weekday(day: NaiveDate) -> usize {
+    day.weekday().num_days_from_monday() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn march() -> BillingCycle {
+        BillingCycle::containing("2025-03-15T12:00:00Z".parse().unwrap(), None)
+    }
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, d).unwrap()
+    }
+
+    fn days(requests: impl Fn(NaiveDate) -> i64, until: u32) -> Vec<DailyTotals> {
+        (1..until)
+            .map(|d| DailyTotals {
+                day: day(d),
+                requests: requests(day(d)),
+                tokens: requests(day(d)) * 10,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_steady_usage_projects_linearly() {
+        // 100 requests a day for the first 14 days, now midnight of the 15th
+        let history = days(|_| 100, 15);
+        let now = "2025-03-15T00:00:00Z".parse().unwrap();
+        let forecast = forecast(&history, 1_400, 14_000, 5_000, march(), now);
+
+        assert_eq!(forecast.days_observed, 14);
+        assert_eq!(forecast.requests.projected, 3_100);
+        assert_eq!(forecast.tokens.projected, 31_000);
+        // No spread, no band
+        assert_eq!(forecast.requests.low, 3_100);
+        assert_eq!(forecast.requests.high, 3_100);
+        assert_eq!(forecast.projected_percent, Some(62.0));
+        assert_eq!(forecast.exhaustion_date, None);
+        assert!(!forecast.should_alert(5_000));
+
+        // A smaller quota runs out on the 20th (1400 + 6 * 100 = 2000)
+        let forecast = super::forecast(&history, 1_400, 14_000, 2_000, march(), now);
+        assert_eq!(forecast.exhaustion_date, Some(day(20)));
+        assert!(forecast.should_alert(2_000));
+    }
+
+    #[test]
+    fn test_weekday_weighting() {
+        // Weekdays busy, weekends idle (March 2025 starts on a Saturday)
+        let weekly = |d: NaiveDate| if weekday(d) < 5 { 100 } else { 0 };
+        let history = days(weekly, 15);
+        let used: i64 = history.iter().map(|d| d.requests).sum();
+        let now = "2025-03-15T00:00:00Z".parse().unwrap();
+        let forecast = forecast(&history, used, used * 10, 0, march(), now);
+
+        // 11 of the 17 days left are weekdays: 1100 more requests. A flat
+        // rate would project 17 days at the 10-in-14 average.
+        let weighted = forecast.requests.projected - used;
+        let flat = 17 * used / 14;
+        assert!(
+            (weighted - 1_100).abs() < (flat - 1_100).abs(),
+            "weighted {} vs flat {}",
+            weighted,
+            flat
+        );
+        assert!(forecast.requests.low <= forecast.requests.projected);
+        assert!(forecast.requests.high >= forecast.requests.projected);
+        assert_eq!(forecast.projected_percent, None);
+        assert_eq!(forecast.exhaustion_date, None);
+    }
+
+    #[test]
+    fn test_trend_and_band() {
+        // Growing by 10 a day with noise
+        let history = days(
+            |d| 50 + 10 * d.day() as i64 + if d.day() % 2 == 0 { 15 } else { -15 },
+            15,
+        );
+        let used: i64 = history.iter().map(|d| d.requests).sum();
+        let now = "2025-03-15T00:00:00Z".parse().unwrap();
+        let forecast = forecast(&history, used, 0, 10_000, march(), now);
+
+        // A flat projection at the mean would stay below a growing one
+        let flat = used + 17 * used / 14;
+        assert!(forecast.requests.projected > flat);
+        assert!(forecast.requests.low < forecast.requests.projected);
+        assert!(forecast.requests.high > forecast.requests.projected);
+        assert!(forecast.requests.low >= used);
+    }
+
+    #[test]
+    fn test_without_history_extends_the_average_rate() {
+        // Half a day into the cycle, 50 requests
+        let now = "2025-03-01T12:00:00Z".parse().unwrap();
+        let forecast = forecast(&[], 50, 500, 1_000, march(), now);
+        assert_eq!(forecast.days_observed, 0);
+        // 100 a day: 50 more today plus 30 days
+        assert_eq!(forecast.requests.projected, 3_100);
+        assert!(forecast.requests.low > 50 && forecast.requests.low < 3_100);
+        assert!(forecast.requests.high > 3_100);
+        assert_eq!(forecast.exhaustion_date, Some(day(10)));
+        assert!(forecast.should_alert(1_000));
+
+        // Already over the quota: exhausted today, and no forecast alert
+        let forecast = super::forecast(&[], 1_200, 0, 1_000, march(), now);
+        assert_eq!(forecast.exhaustion_date, Some(day(1)));
+        assert!(!forecast.should_alert(1_000));
+    }
+}
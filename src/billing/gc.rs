@@ -0,0 +1,171 @@
+//! Garbage collection of stale rate-limit keys in Redis
+//!
+//! Free tier counters (`ratelimit:{id}:{YYYY-MM-DD}`, keyed on the start of
+//! the billing cycle) expire after 32 days, but until then every org and
+//! cycle leaves a key behind. The sweep drops counters of cycles that
+//! started more than two months ago, which are neither the current nor the
+//! previous cycle (the previous one is kept for late reads around the cycle
+//! boundary). It runs weekly and on demand via
+//! `POST /v1/admin/maintenance/redis-gc`.
+//!
+//! The SCAN walks the keyspace in small batches with a pause in between so a
+//! sweep never monopolizes Redis.
+//!
+//! Revocation entries (`revoked:*`) are not swept: whether a revoked token
+//! has expired can only be told once api_keys records an expiry
+//! (`expires_at`). Until then they live out their one-year TTL.
+
+use anyhow::Result;
+use chrono::{Months, NaiveDate, Utc};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::time;
+use tracing::{error, info};
+
+use crate::monitoring::{REDIS_GC_KEYS_REMOVED, REDIS_GC_KEYS_SCANNED};
+use crate::redis_util::{self, RedisConnection};
+
+/// Prefix of the free tier cycle counters
+pub const RATE_LIMIT_PREFIX: &str = "ratelimit";
+
+/// Keys requested per SCAN call
+const SCAN_BATCH: usize = 500;
+
+/// Pause between SCAN calls
+const SCAN_PAUSE: Duration = Duration::from_millis(50);
+
+/// How often the background sweep runs
+const GC_INTERVAL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Outcome of a sweep
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct GcReport {
+    pub scanned: u64,
+    pub removed: u64,
+}
+
+/// Whether a `ratelimit:{id}:{YYYY-MM-DD}` key belongs to a cycle that
+/// started more than two months before `today`. Keys that don't parse are
+/// left alone.
+pub fn is_stale_rate_limit_key(key: &str, today: NaiveDate) -> bool {
+    let Some(rest) = key
+        .strip_prefix(RATE_LIMIT_PREFIX)
+        .and_then(|k| k.strip_prefix(':'))
+    else {
+        return false;
+    };
+    let Some((_, start)) = rest.rsplit_once(':') else {
+        return false;
+    };
+    let Some(cutoff) = today.checked_sub_months(Months::new(2)) else {
+        return false;
+    };
+    parse_cycle_start(start).is_some_and(|start| start < cutoff)
+}
+
+/// Parse the `YYYY-MM-DD` cycle start of a counter key
+fn parse_cycle_start(s: &str) -> Option<NaiveDate> {
+    if s.len() != 10 {
+        return None;
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
+/// Sweep stale rate-limit counters
+pub async fn sweep_rate_limit_keys(conn: &RedisConnection) -> Result<GcReport> {
+    let mut conn = conn.clone();
+    let today = Utc::now().date_naive();
+    let pattern = format!("{}:*", RATE_LIMIT_PREFIX);
+    let mut report = GcReport::default();
+    let mut cursor: u64 = 0;
+
+    loop {
+        let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(&pattern)
+            .arg("COUNT")
+            .arg(SCAN_BATCH)
+            .query_async(&mut conn)
+            .await?;
+
+        report.scanned += keys.len() as u64;
+        let stale: Vec<&String> = keys
+            .iter()
+            .filter(|key| is_stale_rate_limit_key(key, today))
+            .collect();
+        if !stale.is_empty() {
+            // UNLINK frees memory in the background instead of blocking like DEL
+            let removed: u64 = redis::cmd("UNLINK")
+                .arg(&stale)
+                .query_async(&mut conn)
+                .await?;
+            report.removed += removed;
+        }
+
+        cursor = next;
+        if cursor == 0 {
+            break;
+        }
+        time::sleep(SCAN_PAUSE).await;
+    }
+
+    REDIS_GC_KEYS_SCANNED
+        .with_label_values(&[RATE_LIMIT_PREFIX])
+        .inc_by(report.scanned as f64);
+    REDIS_GC_KEYS_REMOVED
+        .with_label_values(&[RATE_LIMIT_PREFIX])
+        .inc_by(report.removed as f64);
+
+    Ok(report)
+}
+
+/// Sweep using the shared Redis connection
+pub async fn run() -> Result<GcReport> {
+    sweep_rate_limit_keys(&redis_util::get_connection()).await
+}
+
+/// Start the weekly background sweep (first run one interval after startup)
+pub fn start_gc_task() {
+    tokio::spawn(async move {
+        let mut interval = time::interval_at(time::Instant::now() + GC_INTERVAL, GC_INTERVAL);
+        loop {
+            interval.tick().await;
+            match run().await {
+                Ok(report) => info!(
+                    "Redis GC: removed {} of {} rate-limit keys",
+                    report.removed, report.scanned
+                ),
+                Err(e) => error!("Redis GC failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_rate_limit_keys() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 15).unwrap();
+        let org = "0191c3a0-0000-7000-8000-000000000000";
+        let key = |start: &str| format!("ratelimit:{}:{}", org, start);
+
+        // Current and previous cycles are kept, calendar months or anchored
+        for start in ["2025-03-01", "2025-02-01", "2025-02-20", "2025-01-20"] {
+            assert!(!is_stale_rate_limit_key(&key(start), today), "{}", start);
+        }
+
+        for start in ["2025-01-01", "2024-12-20", "2024-12-01"] {
+            assert!(is_stale_rate_limit_key(&key(start), today), "{}", start);
+        }
+
+        // Anything unexpected is left alone, including the old month keys
+        assert!(!is_stale_rate_limit_key("ratelimit:garbage", today));
+        assert!(!is_stale_rate_limit_key(&key("2024-01"), today));
+        assert!(!is_stale_rate_limit_key(&key("2024-1-01"), today));
+        assert!(!is_stale_rate_limit_key(&format!("revoked:{}", org), today));
+        assert!(!is_stale_rate_limit_key("ratelimitx:a:2020-01-01", today));
+    }
+}
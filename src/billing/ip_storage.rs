@@ -0,0 +1,53 @@
+//! Per-organization opt-out of client IP storage
+//!
+//! Request logs keep the client IP for abuse investigations unless the
+//! organization turned `store_client_ip` off
+//! (`PUT /v1/organizations/:org_id/ip-storage`). The setting is read for
+//! every logged request, so it's cached in process like the cache policy.
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::database;
+
+/// How long a lookup is trusted before hitting the database again
+const IP_STORAGE_CACHE_TTL_SECS: u64 = 30;
+
+/// org_id -> (store_client_ip, checked_at)
+static IP_STORAGE_CACHE: Lazy<DashMap<Uuid, (bool, Instant)>> = Lazy::new(DashMap::new);
+
+/// Whether the organization's client IPs may be stored. Lookup failures
+/// fall back to not storing them.
+pub async fn stores_client_ip(org_id: Uuid) -> bool {
+    if let Some(entry) = IP_STORAGE_CACHE.get(&org_id) {
+        let (store, checked_at) = *entry;
+        if checked_at.elapsed().as_secs() < IP_STORAGE_CACHE_TTL_SECS {
+            return store;
+        }
+    }
+
+    let store =
+        sqlx::query_scalar::<_, bool>("SELECT store_client_ip FROM organizations WHERE id = $1")
+            .bind(org_id)
+            .fetch_optional(database::get_db())
+            .await;
+
+    match store {
+        Ok(store) => {
+            let store = store.unwrap_or(true);
+            IP_STORAGE_CACHE.insert(org_id, (store, Instant::now()));
+            store
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load IP storage setting of {}: {}", org_id, e);
+            false
+        }
+    }
+}
+
+/// Forget a cached setting (call after changing it)
+pub fn invalidate(org_id: Uuid) {
+    IP_STORAGE_CACHE.remove(&org_id);
+}
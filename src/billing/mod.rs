@@ -1,98 +1,143 @@
-use anyhow::{anyhow, Result};
-use chrono::{Datelike, NaiveDateTime, Utc};
+pub mod alerts;
+pub mod concurrency;
+pub mod cycle;
+pub mod estimate;
+pub mod forecast;
+pub mod gc;
+pub mod ip_storage;
+pub mod outbox;
+pub mod statements;
+pub mod trial;
+pub mod usage;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
 use parking_lot::Mutex;
-use redis::aio::ConnectionManager;
 use redis::AsyncCommands;
-use sqlx::PgPool;
-use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::time;
+use tokio::sync::Notify;
 use tracing::info;
 
+use self::cycle::BillingCycle;
+use self::outbox::{Outbox, OutboxEntry};
 use crate::auth::TokenClaims;
-use crate::config;
+use crate::database::api_request_log::{NewRequest, ResponseUpdate};
+use crate::database::usage_events::NewUsageEvent;
+use crate::database::{ApiRequestLogRepo, DbPool, UsageEventsRepo};
 use crate::models::TierType;
+use crate::redis_util;
+use crate::{config, jobs, monitoring};
 
-// Response update for batching
-#[derive(Clone, Debug)]
-struct ResponseUpdate {
-    request_id: uuid::Uuid,
-    tokens: i32,
-    response_metadata: serde_json::Value,
-    timestamp: NaiveDateTime,
-}
-
-// Usage event for batching
-#[derive(Clone, Debug)]
-struct UsageEvent {
-    organization_id: uuid::Uuid,
-    api_key_id: uuid::Uuid,
-    product: String,
-    event_type: String,
-    tokens: i32,
-    requests: i32,
-    timestamp: NaiveDateTime,
+impl From<OutboxEntry> for (ResponseUpdate, NewUsageEvent) {
+    fn from(entry: OutboxEntry) -> Self {
+        let usage = NewUsageEvent {
+            organization_id: entry.organization_id,
+            api_key_id: entry.api_key_id,
+            product: entry.product,
+            event_type: "inference".to_string(),
+            tokens: entry.tokens,
+            requests: 1,
+            timestamp: entry.timestamp,
+            request_id: entry.request_id,
+        };
+        let response_update = ResponseUpdate {
+            request_id: entry.request_id,
+            tokens: entry.tokens,
+            response_metadata: entry.response_metadata,
+            timestamp: entry.timestamp,
+        };
+        (response_update, usage)
+    }
 }
 
 // Buffer for batching usage updates
 pub struct UsageBuffer {
     response_updates_buffer: Arc<Mutex<Vec<ResponseUpdate>>>,
-    usage_events_buffer: Arc<Mutex<Vec<UsageEvent>>>,
-    pool: &'static PgPool,
+    usage_events_buffer: Arc<Mutex<Vec<NewUsageEvent>>>,
+    /// Durable log of responses not yet flushed (USAGE_OUTBOX_PATH)
+    outbox: Option<Outbox>,
+    pool: DbPool,
+    /// Buffered responses past which a flush is requested at once
+    /// (USAGE_FLUSH_MAX_BUFFER)
+    max_buffer: usize,
+    /// Woken when the buffers grow past `max_buffer`
+    flush_wanted: Notify,
+    /// Held by the running flush, so interval and size-triggered flushes
+    /// never overlap
+    flushing: tokio::sync::Mutex<()>,
 }
 
-// Global usage buffer instance
-static USAGE_BUFFER: once_cell::sync::OnceCell<Arc<UsageBuffer>> = once_cell::sync::OnceCell::new();
-
-// Global Redis connection for rate limiting
-static REDIS_CONNECTION: once_cell::sync::OnceCell<ConnectionManager> =
-    once_cell::sync::OnceCell::new();
-
 impl UsageBuffer {
-    pub fn new(pool: &'static PgPool) -> Self {
+    pub fn new(pool: DbPool) -> Self {
         Self {
             response_updates_buffer: Arc::new(Mutex::new(Vec::new())),
             usage_events_buffer: Arc::new(Mutex::new(Vec::new())),
+            outbox: None,
             pool,
+            max_buffer: config::get_settings().usage_flush_max_buffer,
+            flush_wanted: Notify::new(),
+            flushing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Buffer that appends responses to `outbox` before acknowledging them
+    pub fn with_outbox(pool: DbPool, outbox: Outbox) -> Self {
+        Self {
+            outbox: Some(outbox),
+            ..Self::new(pool)
         }
     }
 
+    /// Buffer for `pool`, with the outbox at `USAGE_OUTBOX_PATH` if set
+    pub fn from_settings(pool: DbPool) -> Result<Self> {
+        Ok(match &config::get_settings().usage_outbox_path {
+            Some(path) => {
+                info!("Usage outbox at {}", path);
+                UsageBuffer::with_outbox(pool, Outbox::open(path)?)
+            }
+            None => UsageBuffer::new(pool),
+        })
+    }
+
+    /// Replay usage left in the outbox by a crash (before serving requests)
+    pub async fn replay_outbox(&self) -> Result<()> {
+        let (_, replayed) = self.flush_outbox().await?;
+        if replayed > 0 {
+            info!("Replayed {} usage events from the outbox", replayed);
+        }
+        Ok(())
+    }
+
     /// Record incoming API request immediately (non-blocking insert to api_request_log)
     /// This creates an audit trail of ALL requests, even if they fail later
-    pub fn record_request(
-        &self,
-        request_id: uuid::Uuid,
-        organization_id: uuid::Uuid,
-        api_key_id: uuid::Uuid,
-        product: String,
-        endpoint: String,
-        input_text: String,
-        input_metadata: Option<serde_json::Value>,
-    ) {
-        let pool = self.pool;
+    pub fn record_request(&self, mut request: NewRequest) {
+        let pool = self.pool.clone();
 
         // Spawn non-blocking insert - don't wait for database
         tokio::spawn(async move {
-            let result = sqlx::query(
-                "INSERT INTO api_request_log
-                 (request_id, organization_id, api_key_id, product, endpoint, input_text, input_metadata, request_timestamp, status)
-                 VALUES ($1, $2, $3, $4, $5, $6, $7, NOW(), 'pending')",
-            )
-            .bind(request_id)
-            .bind(organization_id)
-            .bind(api_key_id)
-            .bind(product)
-            .bind(endpoint)
-            .bind(input_text)
-            .bind(input_metadata)
-            .execute(pool)
-            .await;
-
-            if let Err(e) = result {
-                tracing::error!("Failed to record request {}: {}", request_id, e);
+            drop_opted_out_ip(&mut request).await;
+            if let Err(e) = ApiRequestLogRepo::new(&pool).insert_pending(&request).await {
+                tracing::error!("Failed to record request {}: {}", request.request_id, e);
             } else {
-                tracing::debug!("Recorded request {} to api_request_log", request_id);
+                tracing::debug!("Recorded request {} to api_request_log", request.request_id);
+            }
+        });
+    }
+
+    /// Record a dry run: one api_request_log row with status `dry_run` and
+    /// the token count, and no usage event (dry runs are free)
+    pub fn record_dry_run(&self, mut request: NewRequest, tokens: i32) {
+        let pool = self.pool.clone();
+
+        tokio::spawn(async move {
+            drop_opted_out_ip(&mut request).await;
+            if let Err(e) = ApiRequestLogRepo::new(&pool)
+                .insert_dry_run(&request, tokens)
+                .await
+            {
+                tracing::error!("Failed to record dry run {}: {}", request.request_id, e);
             }
         });
     }
@@ -108,170 +153,276 @@ impl UsageBuffer {
         tokens: i32,
         response_metadata: serde_json::Value,
     ) {
-        let now = chrono::Local::now().naive_local();
-
-        // Buffer the response update for api_request_log
-        let response_update = ResponseUpdate {
+        let entry = OutboxEntry {
             request_id,
-            tokens,
-            response_metadata,
-            timestamp: now,
-        };
-        self.response_updates_buffer.lock().push(response_update);
-
-        // Buffer the usage event for billing
-        let usage = UsageEvent {
             organization_id,
             api_key_id,
             product: product.to_string(),
-            event_type: "inference".to_string(),
             tokens,
-            requests: 1,
-            timestamp: now,
+            response_metadata,
+            timestamp: chrono::Utc::now(),
         };
-        self.usage_events_buffer.lock().push(usage);
+
+        if let Some(outbox) = &self.outbox {
+            match outbox.append(&entry) {
+                Ok(()) => return,
+                // Still billed from memory, just not crash-safe
+                Err(e) => tracing::error!(
+                    "Failed to append {} to the usage outbox, buffering in memory: {}",
+                    request_id,
+                    e
+                ),
+            }
+        }
+
+        // Buffer the response update for api_request_log and the usage
+        // event for billing
+        let (response_update, usage) = entry.into();
+        let responses = {
+            let mut buffer = self.response_updates_buffer.lock();
+            buffer.push(response_update);
+            buffer.len()
+        };
+        let events = {
+            let mut buffer = self.usage_events_buffer.lock();
+            buffer.push(usage);
+            buffer.len()
+        };
+        // Don't wait for the next interval under bursty load
+        if responses.max(events) > self.max_buffer {
+            self.flush_wanted.notify_one();
+        }
     }
 
     // Flush buffered records to database (batch insert)
     pub async fn flush(&self) -> Result<(usize, usize)> {
-        // 1. Flush response updates to api_request_log
+        let _flushing = self.flushing.lock().await;
         let response_updates = {
             let mut buffer = self.response_updates_buffer.lock();
             std::mem::take(&mut *buffer)
         };
-
-        let response_count = if !response_updates.is_empty() {
-            let count = response_updates.len();
-            info!("Flushing {} response updates to api_request_log", count);
-
-            // Batch update using individual queries (PostgreSQL doesn't support batch UPDATE well)
-            for update in response_updates {
-                sqlx::query(
-                    "UPDATE api_request_log
-                     SET tokens = $1,
-                         response_metadata = $2,
-                         response_timestamp = $3,
-                         status = 'success',
-                         updated_at = NOW()
-                     WHERE request_id = $4",
-                )
-                .bind(update.tokens)
-                .bind(update.response_metadata)
-                .bind(update.timestamp)
-                .bind(update.request_id)
-                .execute(self.pool)
-                .await?;
-            }
-
-            info!("Successfully flushed {} response updates", count);
-            count
-        } else {
-            0
-        };
-
-        // 2. Flush usage events
         let usage_events = {
             let mut buffer = self.usage_events_buffer.lock();
             std::mem::take(&mut *buffer)
         };
+        let (responses, events) = match self.write(&response_updates, &usage_events).await {
+            Ok(counts) => counts,
+            Err(e) => {
+                // Put the records back (ahead of newer ones) for the next
+                // flush; rewriting is safe since both writes are idempotent
+                requeue(&self.response_updates_buffer, response_updates);
+                requeue(&self.usage_events_buffer, usage_events);
+                return Err(e);
+            }
+        };
 
-        let usage_count = if !usage_events.is_empty() {
-            let count = usage_events.len();
-            info!("Flushing {} usage events", count);
+        let (replayed_responses, replayed_events) = self.flush_outbox().await?;
+        Ok((responses + replayed_responses, events + replayed_events))
+    }
 
-            // Batch insert using QueryBuilder
-            let mut query_builder = sqlx::QueryBuilder::new(
-                "INSERT INTO usage_events (organization_id, api_key_id, product, event_type, tokens, requests, timestamp) ",
-            );
+    /// Write the outbox entries past its checkpoint to the database, then
+    /// advance the checkpoint. Run at startup to replay what a crash left
+    /// behind (a no-op without an outbox).
+    pub async fn flush_outbox(&self) -> Result<(usize, usize)> {
+        let Some(outbox) = &self.outbox else {
+            return Ok((0, 0));
+        };
 
-            query_builder.push_values(usage_events, |mut b, event| {
-                b.push_bind(event.organization_id)
-                    .push_bind(event.api_key_id)
-                    .push_bind(event.product)
-                    .push_bind(event.event_type)
-                    .push_bind(event.tokens)
-                    .push_bind(event.requests)
-                    .push_bind(event.timestamp);
-            });
+        let (entries, offset) = outbox.pending()?;
+        let (response_updates, usage_events): (Vec<_>, Vec<_>) =
+            entries.into_iter().map(Into::into).unzip();
+        let counts = self.write(&response_updates, &usage_events).await?;
+        outbox.checkpoint(offset)?;
+        Ok(counts)
+    }
 
-            query_builder.build().execute(self.pool).await?;
+    async fn write(
+        &self,
+        response_updates: &[ResponseUpdate],
+        usage_events: &[NewUsageEvent],
+    ) -> Result<(usize, usize)> {
+        // 1. Flush response updates to api_request_log
+        if !response_updates.is_empty() {
+            info!(
+                "Flushing {} response updates to api_request_log",
+                response_updates.len()
+            );
+            let (_, rejected) = ApiRequestLogRepo::new(&self.pool)
+                .complete_batch_or_each(response_updates)
+                .await?;
+            // Usage events of rejected responses are still recorded below
+            for (request_id, reason) in &rejected {
+                tracing::error!(
+                    "Dropped the response update of request {}: {}",
+                    request_id,
+                    reason
+                );
+            }
+            monitoring::RESPONSE_UPDATES_REJECTED.inc_by(rejected.len() as f64);
+        }
 
-            info!("Successfully flushed {} usage events", count);
-            count
-        } else {
-            0
-        };
+        // 2. Flush usage events
+        if !usage_events.is_empty() {
+            info!("Flushing {} usage events", usage_events.len());
+            UsageEventsRepo::new(&self.pool)
+                .insert_batch(usage_events)
+                .await?;
+        }
 
-        Ok((response_count, usage_count))
+        Ok((response_updates.len(), usage_events.len()))
     }
 
-    // Start background flush task (every 5 seconds)
+    // Start background flush job (every USAGE_FLUSH_INTERVAL seconds, as
+    // soon as the buffers outgrow USAGE_FLUSH_MAX_BUFFER, and once more on
+    // shutdown)
     pub fn start_flush_task(self: Arc<Self>) {
+        let interval = Duration::from_secs(config::get_settings().usage_flush_interval_secs);
+        self.clone().start_size_triggered_flush(interval);
+        jobs::register(
+            jobs::Job::new("usage_flush", interval, move || {
+                let buffer = self.clone();
+                async move { buffer.flush().await.map(|_| ()) }
+            })
+            .timeout(Duration::from_secs(30))
+            .run_on_shutdown(),
+        );
+    }
+
+    /// Flush whenever `record_response` finds the buffers full. After a
+    /// failed flush, wait `backoff` before trying again (the interval job
+    /// keeps retrying meanwhile).
+    fn start_size_triggered_flush(self: Arc<Self>, backoff: Duration) {
         tokio::spawn(async move {
-            let mut interval = time::interval(Duration::from_secs(5));
             loop {
-                interval.tick().await;
+                self.flush_wanted.notified().await;
                 if let Err(e) = self.flush().await {
-                    tracing::error!("Failed to flush usage buffer: {}", e);
+                    tracing::error!("Size-triggered usage flush failed: {}", e);
+                    tokio::time::sleep(backoff).await;
                 }
             }
         });
     }
 }
 
-// Initialize global usage buffer
-pub fn init_usage_buffer(pool: &'static PgPool) -> Result<()> {
-    // If already initialized, return early
-    if USAGE_BUFFER.get().is_some() {
-        return Ok(());
-    }
+/// Return records taken for a failed flush to the front of `buffer`
+fn requeue<T>(buffer: &Mutex<Vec<T>>, mut records: Vec<T>) {
+    let mut buffer = buffer.lock();
+    records.append(&mut buffer);
+    *buffer = records;
+}
 
-    let buffer = Arc::new(UsageBuffer::new(pool));
-    buffer.clone().start_flush_task();
-    USAGE_BUFFER.set(buffer).ok(); // Ignore error if already set
-    info!("Usage buffer initialized with 5-second flush interval");
-    Ok(())
+/// Clear the client IP of requests from organizations that opted out of
+/// IP storage
+async fn drop_opted_out_ip(request: &mut NewRequest) {
+    if request.client_ip.is_some() && !ip_storage::stores_client_ip(request.organization_id).await {
+        request.client_ip = None;
+    }
 }
 
-// Get global usage buffer
+/// The usage buffer of the process-wide `AppState` (prefer
+/// `AppState::usage` where the state is at hand)
 pub fn get_usage_buffer() -> &'static Arc<UsageBuffer> {
-    USAGE_BUFFER.get().expect("Usage buffer not initialized")
+    &crate::state::global().usage
 }
 
-// Initialize global Redis connection for rate limiting
-pub async fn init_redis() -> Result<()> {
-    // If already initialized, return early
-    if REDIS_CONNECTION.get().is_some() {
-        return Ok(());
-    }
+// ====== Token-based functions ======
 
-    let settings = config::get_settings();
-    let redis_client = redis::Client::open(settings.redis_url.as_str())?;
-    let conn = ConnectionManager::new(redis_client).await?;
-    REDIS_CONNECTION.set(conn).ok(); // Ignore error if already set
-    info!("Redis connection for billing initialized");
-    Ok(())
+/// Window a rate limit decision was made for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitWindow {
+    /// Fixed one-minute window (per-minute tier limit, dry runs)
+    Minute,
+    /// Billing cycle (free tier quota): the calendar month, or a month
+    /// starting on the organization's billing anchor day
+    Month,
+    /// No limit applies (paid tier without a per-minute limit)
+    Unlimited,
 }
 
-// Get global Redis connection
-fn get_redis_connection() -> &'static ConnectionManager {
-    REDIS_CONNECTION
-        .get()
-        .expect("Redis connection not initialized")
+impl LimitWindow {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LimitWindow::Minute => "minute",
+            LimitWindow::Month => "month",
+            LimitWindow::Unlimited => "unlimited",
+        }
+    }
 }
 
-// ====== Token-based functions ======
+/// Outcome of a rate limit check, carried to the response headers and the
+/// 429 error body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    /// Requests allowed per window
+    pub limit: i64,
+    /// Requests left in the window after this one
+    pub remaining: i64,
+    /// When the window resets
+    pub reset_at: DateTime<Utc>,
+    pub window: LimitWindow,
+    /// Bounds of the billing cycle (monthly window only)
+    pub cycle: Option<BillingCycle>,
+}
+
+impl RateLimitDecision {
+    /// Decision for a window where `used` requests have been counted
+    fn counted(
+        window: LimitWindow,
+        limit: i64,
+        used: i64,
+        allowed: bool,
+        reset_at: DateTime<Utc>,
+    ) -> Self {
+        RateLimitDecision {
+            allowed,
+            limit,
+            remaining: (limit - used).max(0),
+            reset_at,
+            window,
+            cycle: None,
+        }
+    }
+
+    /// Decision for the monthly window of a billing cycle
+    fn cycle(cycle: BillingCycle, limit: i64, used: i64) -> Self {
+        RateLimitDecision {
+            cycle: Some(cycle),
+            ..Self::counted(LimitWindow::Month, limit, used, used < limit, cycle.end)
+        }
+    }
+
+    fn unlimited(now: DateTime<Utc>) -> Self {
+        RateLimitDecision {
+            allowed: true,
+            limit: 0,
+            remaining: 0,
+            reset_at: now,
+            window: LimitWindow::Unlimited,
+            cycle: None,
+        }
+    }
+}
 
 /// Check rate limit using token claims (no DB required)
-pub async fn check_rate_limit_from_claims(
-    claims: &TokenClaims,
-) -> Result<(bool, HashMap<String, String>)> {
-    // Skip rate limiting for paid tiers (they use pay-as-you-go)
+///
+/// Every tier is subject to its per-minute limit (`TierLimits::rpm`); the
+/// free tier is additionally capped by the monthly quota signed into the
+/// token. Denials carry the window that was exhausted; on success the free
+/// tier reports its monthly quota and paid tiers their per-minute limit.
+pub async fn check_rate_limit_from_claims(claims: &TokenClaims) -> Result<RateLimitDecision> {
     let tier = claims.tier()?;
+
+    let minute = check_rpm_redis(claims, tier.limits().rpm).await?;
+    if !minute.allowed {
+        return Ok(minute);
+    }
+
+    // Skip the monthly quota for paid tiers (they use pay-as-you-go)
     match tier {
         TierType::Pro | TierType::Scale => {
             info!("Skipping rate limit check for paid tier: {:?}", tier);
-            Ok((true, HashMap::new()))
+            Ok(minute)
         }
         TierType::Free => {
             // Free tier: check Redis quota
@@ -281,19 +432,135 @@ pub async fn check_rate_limit_from_claims(
     }
 }
 
-/// Redis-based rate limiting using token claims
-async fn check_rate_limit_redis_from_claims(
-    claims: &TokenClaims,
-) -> Result<(bool, HashMap<String, String>)> {
-    // Use global Redis connection
-    let mut conn = get_redis_connection().clone();
+/// Per-minute window: Redis key suffix and seconds until the window resets
+fn rpm_window(now_secs: i64) -> (i64, i64) {
+    let minute = now_secs.div_euclid(60);
+    (minute, 60 - now_secs.rem_euclid(60))
+}
+
+/// Fixed one-minute window counter per organization (0 = unlimited)
+async fn check_rpm_redis(claims: &TokenClaims, rpm: u32) -> Result<RateLimitDecision> {
+    check_minute_window("rpm", claims.org_id(), rpm, true).await
+}
 
-    // Get current month for key
+/// Fixed one-minute window counter `{prefix}:{id}:{minute}` (0 = unlimited).
+/// With `increment` false the counter is only read, and the request is
+/// allowed if it would still fit.
+async fn check_minute_window(
+    prefix: &str,
+    id: uuid::Uuid,
+    limit: u32,
+    increment: bool,
+) -> Result<RateLimitDecision> {
     let now = Utc::now();
-    let month_key = format!("ratelimit:{}:{}", claims.org_id(), now.format("%Y-%m"));
+    if limit == 0 {
+        return Ok(RateLimitDecision::unlimited(now));
+    }
 
-    // Get current count from Redis
-    let count: i64 = conn.get(&month_key).await.unwrap_or(0);
+    let mut conn = redis_util::get_connection();
+    let (minute, reset_in) = rpm_window(now.timestamp());
+    let key = format!("{}:{}:{}", prefix, id, minute);
+
+    // Requests counted in the window, including this one
+    let count: i64 = if increment {
+        let (count,): (i64,) = redis::pipe()
+            .atomic()
+            .incr(&key, 1)
+            .expire(&key, 60)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        count
+    } else {
+        let count: Option<i64> = conn.get(&key).await?;
+        count.unwrap_or(0) + 1
+    };
+
+    Ok(RateLimitDecision::counted(
+        LimitWindow::Minute,
+        limit as i64,
+        count,
+        count <= limit as i64,
+        now + chrono::Duration::seconds(reset_in),
+    ))
+}
+
+/// Read-only rate limit check for dry runs: same outcome as
+/// `check_rate_limit_from_claims`, but no counter is incremented.
+pub async fn peek_rate_limit_from_claims(claims: &TokenClaims) -> Result<RateLimitDecision> {
+    let tier = claims.tier()?;
+
+    let minute = check_minute_window("rpm", claims.org_id(), tier.limits().rpm, false).await?;
+    if !minute.allowed {
+        return Ok(minute);
+    }
+
+    match tier {
+        TierType::Pro | TierType::Scale => Ok(minute),
+        TierType::Free => check_rate_limit_redis_from_claims(claims).await,
+    }
+}
+
+/// Per-key limit on dry runs (`DRY_RUN_RPM` per minute), so they can't be
+/// used as a free tokenizer
+pub async fn check_dry_run_limit(claims: &TokenClaims) -> Result<RateLimitDecision> {
+    let limit = config::get_settings().dry_run_rpm;
+    check_minute_window("dry_run", claims.key_id(), limit, true).await
+}
+
+/// Redis key of an organization's free tier counter for a billing cycle
+fn cycle_key(org_id: uuid::Uuid, cycle: &BillingCycle) -> String {
+    format!("ratelimit:{}:{}", org_id, cycle.key())
+}
+
+/// Expiry of a cycle counter, refreshed on every increment
+const CYCLE_COUNTER_TTL_SECS: i64 = 60 * 60 * 24 * 32; // 32 days
+
+/// Raise the counter in KEYS[2] to the one in KEYS[1], returning the result
+static CARRY_OVER_SCRIPT: Lazy<redis::Script> = Lazy::new(|| {
+    redis::Script::new(
+        r"
+        local carried = tonumber(redis.call('GET', KEYS[1]) or '0')
+        local current = tonumber(redis.call('GET', KEYS[2]) or '0')
+        if carried > current then
+            redis.call('SET', KEYS[2], carried, 'EX', ARGV[1])
+            return carried
+        end
+        return current
+        ",
+    )
+});
+
+/// Carry the free tier counter of an organization's billing cycle over to
+/// the cycle replacing it when the billing anchor changes, so moving the
+/// anchor doesn't start the quota over. The new cycle's counter becomes
+/// the higher of the two; returns it.
+pub async fn carry_over_cycle(
+    org_id: uuid::Uuid,
+    from: &BillingCycle,
+    to: &BillingCycle,
+) -> Result<i64> {
+    let mut conn = redis_util::get_connection();
+    let count: i64 = CARRY_OVER_SCRIPT
+        .key(cycle_key(org_id, from))
+        .key(cycle_key(org_id, to))
+        .arg(CYCLE_COUNTER_TTL_SECS)
+        .invoke_async(&mut conn)
+        .await?;
+    Ok(count)
+}
+
+/// Redis-based rate limiting using token claims
+async fn check_rate_limit_redis_from_claims(claims: &TokenClaims) -> Result<RateLimitDecision> {
+    // Use global Redis connection
+    let mut conn = redis_util::get_connection();
+
+    // Counter of the organization's current billing cycle
+    let cycle = cycle::current(claims.org_id(), Utc::now()).await;
+    let count: i64 = conn
+        .get(cycle_key(claims.org_id(), &cycle))
+        .await
+        .unwrap_or(0);
 
     info!(
         "Redis rate limit check: org {} count {}",
@@ -301,69 +568,633 @@ async fn check_rate_limit_redis_from_claims(
         count
     );
 
-    // Calculate month end for reset_at
-    let year = now.year();
-    let month = now.month();
-    let next_month = if month == 12 { 1 } else { month + 1 };
-    let next_year = if month == 12 { year + 1 } else { year };
-    let month_end = chrono::NaiveDate::from_ymd_opt(next_year, next_month, 1)
-        .ok_or_else(|| anyhow!("Invalid date"))?
-        .and_hms_opt(0, 0, 0)
-        .ok_or_else(|| anyhow!("Invalid time"))?;
-
     // Get limit from token (embedded in token, no config needed!)
     let limit = claims.monthly_quota() as i64;
 
-    // Check if exceeded
-    let is_allowed = count < limit;
-    let remaining = (limit - count).max(0);
+    Ok(RateLimitDecision::cycle(cycle, limit, count))
+}
+
+/// Usage of one enforced rate limit window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowUsage {
+    pub window: LimitWindow,
+    pub limit: i64,
+    pub used: i64,
+    pub remaining: i64,
+    pub reset_at: DateTime<Utc>,
+    /// Bounds of the billing cycle (monthly window only)
+    pub cycle: Option<BillingCycle>,
+}
 
-    let mut rate_limit_info = HashMap::new();
-    rate_limit_info.insert("limit".to_string(), limit.to_string());
-    rate_limit_info.insert("remaining".to_string(), remaining.to_string());
-    rate_limit_info.insert(
-        "reset_at".to_string(),
-        month_end.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
-    );
-    rate_limit_info.insert("current_usage".to_string(), count.to_string());
+impl WindowUsage {
+    fn new(window: LimitWindow, limit: i64, used: i64, reset_at: DateTime<Utc>) -> Self {
+        WindowUsage {
+            window,
+            limit,
+            used,
+            remaining: (limit - used).max(0),
+            reset_at,
+            cycle: None,
+        }
+    }
+
+    fn cycle(cycle: BillingCycle, limit: i64, used: i64) -> Self {
+        WindowUsage {
+            cycle: Some(cycle),
+            ..Self::new(LimitWindow::Month, limit, used, cycle.end)
+        }
+    }
+}
+
+/// Windows enforced for the caller and their usage so far, read from the
+/// counters `check_rate_limit_from_claims` increments (nothing is counted).
+/// Unlimited windows, and the monthly quota of paid tiers, are left out.
+pub async fn current_usage(claims: &TokenClaims) -> Result<Vec<WindowUsage>> {
+    let tier = claims.tier()?;
+    let mut conn = redis_util::get_connection();
+    let now = Utc::now();
+    let mut windows = Vec::new();
+
+    let rpm = tier.limits().rpm;
+    if rpm > 0 {
+        let (minute, reset_in) = rpm_window(now.timestamp());
+        let key = format!("rpm:{}:{}", claims.org_id(), minute);
+        let used: Option<i64> = conn.get(&key).await?;
+        windows.push(WindowUsage::new(
+            LimitWindow::Minute,
+            rpm as i64,
+            used.unwrap_or(0),
+            now + chrono::Duration::seconds(reset_in),
+        ));
+    }
+
+    if tier == TierType::Free {
+        windows.push(quota_usage(claims.org_id(), claims.monthly_quota() as i64).await?);
+    }
+
+    Ok(windows)
+}
+
+/// Usage of a monthly quota in the organization's current billing cycle,
+/// read from the counter the free tier rate limit checks
+pub async fn quota_usage(org_id: uuid::Uuid, quota: i64) -> Result<WindowUsage> {
+    let mut conn = redis_util::get_connection();
+    let cycle = cycle::current(org_id, Utc::now()).await;
+    let used: Option<i64> = conn.get(cycle_key(org_id, &cycle)).await?;
+    Ok(WindowUsage::cycle(cycle, quota, used.unwrap_or(0)))
+}
 
-    Ok((is_allowed, rate_limit_info))
+/// Month-end forecast of an organization's billing cycle, from its daily
+/// usage events. `used_requests` is the cycle's counter, which includes
+/// usage not flushed to the database yet.
+pub async fn cycle_forecast(
+    pool: &DbPool,
+    org_id: uuid::Uuid,
+    cycle: BillingCycle,
+    used_requests: i64,
+    quota: i64,
+) -> Result<forecast::Forecast> {
+    let days = UsageEventsRepo::new(pool)
+        .daily_totals_since(org_id, cycle.start)
+        .await?;
+    let used_tokens = days.iter().map(|day| day.tokens).sum();
+    Ok(forecast::forecast(
+        &days,
+        used_requests,
+        used_tokens,
+        quota,
+        cycle,
+        Utc::now(),
+    ))
 }
 
-/// Increment Redis counter for free tier rate limiting (async, non-blocking)
-pub fn increment_free_tier_counter(org_id: uuid::Uuid) {
+/// Increment Redis counter for free tier rate limiting (async, non-blocking),
+/// then check the organization's usage alerts against `monthly_quota`
+pub fn increment_free_tier_counter(org_id: uuid::Uuid, monthly_quota: i32) {
     tokio::spawn(async move {
-        if let Err(e) = increment_redis_counter_simple(org_id).await {
-            info!("Failed to increment Redis counter for free tier: {}", e);
+        let cycle = cycle::current(org_id, Utc::now()).await;
+        match increment_redis_counter_simple(org_id, &cycle).await {
+            Ok(count) => {
+                let quota = monthly_quota as i64;
+                alerts::check(org_id, count - 1, count, quota, cycle).await
+            }
+            Err(e) => info!("Failed to increment Redis counter for free tier: {}", e),
         }
     });
 }
 
-/// Increment Redis counter (simplified - no API key ID)
-async fn increment_redis_counter_simple(user_id: uuid::Uuid) -> Result<()> {
-    let mut conn = get_redis_connection().clone();
+/// Increment Redis counter (simplified - no API key ID), returning the new count
+async fn increment_redis_counter_simple(user_id: uuid::Uuid, cycle: &BillingCycle) -> Result<i64> {
+    let mut conn = redis_util::get_connection();
 
-    // Get current month for key
-    let now = Utc::now();
-    let month_key = format!("ratelimit:{}:{}", user_id, now.format("%Y-%m"));
+    let month_key = cycle_key(user_id, cycle);
 
     // Atomically increment counter and set expiration
-    let _: () = redis::pipe()
+    let (count,): (i64,) = redis::pipe()
         .atomic()
         .incr(&month_key, 1)
-        .expire(&month_key, 60 * 60 * 24 * 32) // 32 days
+        .expire(&month_key, CYCLE_COUNTER_TTL_SECS)
+        .ignore()
         .query_async(&mut conn)
         .await?;
 
-    Ok(())
+    Ok(count)
 }
 
-/// Hash key_id to get a deterministic API key ID
-fn hash_key_id(key_id: uuid::Uuid) -> i64 {
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpm_window() {
+        assert_eq!(rpm_window(0), (0, 60));
+        assert_eq!(rpm_window(59), (0, 1));
+        assert_eq!(rpm_window(60), (1, 60));
+        assert_eq!(rpm_window(1_700_000_015), (28_333_333, 25));
+    }
+
+    #[test]
+    fn test_window_usage() {
+        let cycle = BillingCycle::containing("2025-12-15T10:00:00Z".parse().unwrap(), None);
+        let usage = WindowUsage::cycle(cycle, 20_000, 12_400);
+        assert_eq!(usage.remaining, 7_600);
+        assert_eq!(usage.reset_at.to_rfc3339(), "2026-01-01T00:00:00+00:00");
+        let reset_at = usage.reset_at;
+
+        // Overshoot (concurrent requests) never shows negative headroom
+        assert_eq!(
+            WindowUsage::new(LimitWindow::Minute, 60, 61, reset_at).remaining,
+            0
+        );
+        let decision = RateLimitDecision::counted(LimitWindow::Minute, 60, 61, false, reset_at);
+        assert_eq!(decision.remaining, 0);
+
+        // The monthly window resets at the end of the billing cycle
+        let decision = RateLimitDecision::cycle(cycle, 20_000, 20_000);
+        assert!(!decision.allowed);
+        assert_eq!(decision.reset_at, cycle.end);
+        assert_eq!(decision.cycle, Some(cycle));
+    }
+
+    /// p95 of the per-request audit insert while dashboard-style listings run
+    /// concurrently. Needs Postgres; run with `cargo test -- --ignored`.
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    #[ignore]
+    #[serial_test::serial]
+    async fn test_request_log_insert_latency_under_concurrent_reads() {
+        use crate::database;
+        #[cfg(feature = "control-plane")]
+        use crate::test_utils::factory;
+        use crate::test_utils::helpers::{cleanup_db, setup};
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Instant;
+
+        const INSERTS: usize = 500;
+        const READERS: usize = 4;
+        const P95_BUDGET: Duration = Duration::from_millis(25);
+
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("load@example.com").await;
+        let org_id = owner.org_id;
+        let key_id = factory::api_key(&owner).await.key_id;
+        let repo = ApiRequestLogRepo::new(pool);
+
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..READERS)
+            .map(|_| {
+                let done = done.clone();
+                tokio::spawn(async move {
+                    while !done.load(Ordering::Relaxed) {
+                        sqlx::query(
+                            "SELECT request_id FROM api_request_log
+                             WHERE organization_id = $1 ORDER BY request_timestamp DESC LIMIT 50",
+                        )
+                        .bind(org_id)
+                        .fetch_all(pool)
+                        .await
+                        .unwrap();
+                        sqlx::query(
+                            "SELECT * FROM api_keys WHERE organization_id = $1 ORDER BY created_at DESC",
+                        )
+                        .bind(org_id)
+                        .fetch_all(pool)
+                        .await
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        let mut latencies = Vec::with_capacity(INSERTS);
+        for _ in 0..INSERTS {
+            let started = Instant::now();
+            repo.insert_pending(&NewRequest {
+                request_id: uuid::Uuid::now_v7(),
+                organization_id: org_id,
+                api_key_id: key_id,
+                product: "embeddings".to_string(),
+                endpoint: "/v1/embed".to_string(),
+                input_text: "load test".to_string(),
+                input_metadata: None,
+                client_ip: None,
+                user_agent: None,
+            })
+            .await
+            .unwrap();
+            latencies.push(started.elapsed());
+        }
+
+        done.store(true, Ordering::Relaxed);
+        for reader in readers {
+            reader.await.unwrap();
+        }
+
+        latencies.sort();
+        let p95 = latencies[INSERTS * 95 / 100];
+        assert!(
+            p95 <= P95_BUDGET,
+            "p95 insert latency {:?} over {:?}",
+            p95,
+            P95_BUDGET
+        );
+
+        cleanup_db().await;
+    }
+
+    /// Usage is stamped in UTC whatever the server's timezone
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_usage_timestamps_ignore_the_local_timezone() {
+        // UTC+14: a local clock would stamp the event 14 hours ahead
+        std::env::set_var("TZ", "Pacific/Kiritimati");
+        let pool = DbPool::connect_lazy(if cfg!(feature = "sqlite") {
+            "sqlite::memory:"
+        } else {
+            "postgres://localhost/unused"
+        })
+        .unwrap();
+        let buffer = UsageBuffer::new(pool);
+        let before = Utc::now();
+        buffer.record_response(
+            uuid::Uuid::now_v7(),
+            uuid::Uuid::now_v7(),
+            uuid::Uuid::now_v7(),
+            "embeddings",
+            7,
+            serde_json::json!({"cached": false}),
+        );
+        let after = Utc::now();
+        std::env::remove_var("TZ");
+
+        let event = buffer.usage_events_buffer.lock()[0].timestamp;
+        assert!((before..=after).contains(&event), "{} not in UTC", event);
+        assert_eq!(buffer.response_updates_buffer.lock()[0].timestamp, event);
+    }
+
+    /// Growing past the size limit wakes the flush task once
+    #[tokio::test]
+    async fn test_full_buffer_requests_a_flush() {
+        let pool = DbPool::connect_lazy(if cfg!(feature = "sqlite") {
+            "sqlite::memory:"
+        } else {
+            "postgres://localhost/unused"
+        })
+        .unwrap();
+        let mut buffer = UsageBuffer::new(pool);
+        buffer.max_buffer = 3;
+        let record = |buffer: &UsageBuffer| {
+            buffer.record_response(
+                uuid::Uuid::now_v7(),
+                uuid::Uuid::now_v7(),
+                uuid::Uuid::now_v7(),
+                "embeddings",
+                7,
+                serde_json::json!({"cached": false}),
+            )
+        };
+        async fn woken(buffer: &UsageBuffer) -> bool {
+            let notified = buffer.flush_wanted.notified();
+            tokio::time::timeout(Duration::from_millis(50), notified)
+                .await
+                .is_ok()
+        }
 
-    let mut hasher = DefaultHasher::new();
-    key_id.hash(&mut hasher);
-    hasher.finish() as i64
+        for _ in 0..3 {
+            record(&buffer);
+        }
+        assert!(!woken(&buffer).await);
+
+        record(&buffer);
+        record(&buffer);
+        assert!(woken(&buffer).await);
+        // One wake-up for both records
+        assert!(!woken(&buffer).await);
+    }
+
+    /// Records made from many tasks while size-triggered flushes run are
+    /// all billed, once
+    #[cfg(feature = "control-plane")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    #[serial_test::serial]
+    #[cfg_attr(feature = "sqlite", ignore = "usage isn't recorded on SQLite")]
+    async fn test_size_triggered_flush_loses_no_concurrent_records() {
+        use crate::database;
+        #[cfg(feature = "control-plane")]
+        use crate::test_utils::factory;
+        use crate::test_utils::helpers::{cleanup_db, setup};
+
+        const TASKS: usize = 8;
+        const PER_TASK: usize = 250;
+
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("size-flush@example.com").await;
+        let key = factory::api_key(&owner).await;
+        let mut buffer = UsageBuffer::new(pool.clone());
+        buffer.max_buffer = 100;
+        let buffer = Arc::new(buffer);
+        buffer
+            .clone()
+            .start_size_triggered_flush(Duration::from_secs(1));
+
+        let writers: Vec<_> = (0..TASKS)
+            .map(|_| {
+                let buffer = buffer.clone();
+                tokio::spawn(async move {
+                    for _ in 0..PER_TASK {
+                        buffer.record_response(
+                            uuid::Uuid::now_v7(),
+                            key.org_id,
+                            key.key_id,
+                            "embeddings",
+                            1,
+                            serde_json::json!({"cached": false}),
+                        );
+                        tokio::task::yield_now().await;
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+
+        let billed = || async {
+            sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM usage_events WHERE organization_id = $1",
+            )
+            .bind(key.org_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+        };
+        // Flushed without waiting for an interval, down to the limit
+        let total = (TASKS * PER_TASK) as i64;
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        while billed().await < total - buffer.max_buffer as i64 {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "no size-triggered flush"
+            );
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        buffer.flush().await.unwrap();
+        assert_eq!(billed().await, total);
+        assert!(buffer.usage_events_buffer.lock().is_empty());
+
+        cleanup_db().await;
+    }
+
+    /// Source files using the local clock (times are UTC everywhere)
+    fn local_clock_uses(dir: &std::path::Path, found: &mut Vec<String>) {
+        // Split so this file doesn't match itself
+        let patterns = [["Local", "::now"].concat(), ["naive", "_local"].concat()];
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                local_clock_uses(&path, found);
+                continue;
+            }
+            if path.extension().is_none_or(|ext| ext != "rs") {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap();
+            for (number, line) in source.lines().enumerate() {
+                if patterns
+                    .iter()
+                    .any(|pattern| line.contains(pattern.as_str()))
+                {
+                    found.push(format!("{}:{}", path.display(), number + 1));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_local_clock() {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        let mut found = vec![];
+        for dir in ["src", "cli/src", "benches"] {
+            local_clock_uses(&root.join(dir), &mut found);
+        }
+        assert!(
+            found.is_empty(),
+            "use Utc instead of the local clock: {:?}",
+            found
+        );
+    }
+
+    /// Env var that turns `outbox_crash_child` into a worker: it records
+    /// responses through an outbox ("path,org_id,key_id,count") and aborts
+    const CRASH_CHILD_ENV: &str = "SMALLY_OUTBOX_CRASH_CHILD";
+
+    /// Child half of `test_outbox_survives_crash` (a no-op when run alone)
+    #[tokio::test]
+    #[ignore]
+    async fn outbox_crash_child() {
+        let Ok(spec) = std::env::var(CRASH_CHILD_ENV) else {
+            return;
+        };
+        let parts: Vec<&str> = spec.split(',').collect();
+        let (org_id, key_id) = (parts[1].parse().unwrap(), parts[2].parse().unwrap());
+        let count: usize = parts[3].parse().unwrap();
+
+        // Never connects: recording only touches the outbox
+        let pool = DbPool::connect_lazy(if cfg!(feature = "sqlite") {
+            "sqlite::memory:"
+        } else {
+            "postgres://localhost/unused"
+        })
+        .unwrap();
+        let buffer = UsageBuffer::with_outbox(pool, Outbox::open(parts[0]).unwrap());
+        for _ in 0..count {
+            buffer.record_response(
+                uuid::Uuid::now_v7(),
+                org_id,
+                key_id,
+                "embeddings",
+                7,
+                serde_json::json!({"cached": false}),
+            );
+        }
+        // Killed before the flush task ever ran
+        std::process::abort();
+    }
+
+    /// Responses acknowledged by a process killed before flushing are
+    /// billed once on restart, also if the restart itself crashes between
+    /// the database write and the checkpoint
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    #[serial_test::serial]
+    #[cfg_attr(feature = "sqlite", ignore = "usage isn't recorded on SQLite")]
+    async fn test_outbox_survives_crash() {
+        use crate::database;
+        #[cfg(feature = "control-plane")]
+        use crate::test_utils::factory;
+        use crate::test_utils::helpers::{cleanup_db, setup};
+
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("outbox@example.com").await;
+        let key = factory::api_key(&owner).await;
+        let path = std::env::temp_dir()
+            .join(format!("smally-outbox-{}", uuid::Uuid::now_v7()))
+            .join("usage.outbox");
+
+        let status = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "billing::tests::outbox_crash_child", "--ignored"])
+            .env(
+                CRASH_CHILD_ENV,
+                format!("{},{},{},5", path.display(), key.org_id, key.key_id),
+            )
+            .status()
+            .unwrap();
+        // Killed by a signal, not a test failure
+        assert_eq!(status.code(), None, "the child should have aborted");
+
+        let billed = || async {
+            sqlx::query_as::<_, (i64, Option<i64>)>(
+                "SELECT COUNT(*), SUM(tokens) FROM usage_events WHERE organization_id = $1",
+            )
+            .bind(key.org_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+        };
+        assert_eq!(billed().await, (0, None));
+
+        // Restart: replayed, then the restart dies before checkpointing
+        let unflushed = std::fs::read(&path).unwrap();
+        let buffer = UsageBuffer::with_outbox(pool.clone(), Outbox::open(&path).unwrap());
+        assert_eq!(buffer.flush_outbox().await.unwrap(), (5, 5));
+        assert_eq!(billed().await, (5, Some(35)));
+        drop(buffer);
+        std::fs::write(&path, &unflushed).unwrap();
+        std::fs::remove_file(format!("{}.checkpoint", path.display())).unwrap();
+
+        // Second restart: replayed again, billed once
+        let buffer = UsageBuffer::with_outbox(pool.clone(), Outbox::open(&path).unwrap());
+        buffer.flush_outbox().await.unwrap();
+        assert_eq!(billed().await, (5, Some(35)));
+        assert_eq!(buffer.flush_outbox().await.unwrap(), (0, 0));
+
+        cleanup_db().await;
+    }
+
+    /// A flush of 1,000 responses completes them in one statement; a row
+    /// Postgres rejects is dropped alone, and its usage is still billed
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    #[serial_test::serial]
+    #[cfg_attr(feature = "sqlite", ignore = "usage isn't recorded on SQLite")]
+    async fn test_flush_of_1000_responses_skips_rejected_rows() {
+        use crate::database;
+        #[cfg(feature = "control-plane")]
+        use crate::test_utils::factory;
+        use crate::test_utils::helpers::{cleanup_db, setup};
+
+        const RESPONSES: usize = 1000;
+
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("flush@example.com").await;
+        let key = factory::api_key(&owner).await;
+        let repo = ApiRequestLogRepo::new(pool);
+        let buffer = UsageBuffer::new(pool.clone());
+
+        let mut request_ids = Vec::with_capacity(RESPONSES);
+        for i in 0..RESPONSES {
+            let request_id = uuid::Uuid::now_v7();
+            repo.insert_pending(&NewRequest {
+                request_id,
+                organization_id: key.org_id,
+                api_key_id: key.key_id,
+                product: "embeddings".to_string(),
+                endpoint: "/v1/embed".to_string(),
+                input_text: format!("text {}", i),
+                input_metadata: None,
+                client_ip: None,
+                user_agent: None,
+            })
+            .await
+            .unwrap();
+            // jsonb can't hold a NUL character
+            let metadata = if i == 500 {
+                serde_json::json!({"note": "a\u{0000}b"})
+            } else {
+                serde_json::json!({"cached": i % 2 == 0})
+            };
+            buffer.record_response(
+                request_id,
+                key.org_id,
+                key.key_id,
+                "embeddings",
+                i as i32 % 7 + 1,
+                metadata,
+            );
+            request_ids.push(request_id);
+        }
+
+        assert_eq!(buffer.flush().await.unwrap(), (RESPONSES, RESPONSES));
+        assert!(buffer.response_updates_buffer.lock().is_empty());
+
+        let rows =
+            sqlx::query_as::<_, (uuid::Uuid, String, Option<i32>, Option<serde_json::Value>)>(
+                "SELECT request_id, status, tokens, response_metadata FROM api_request_log
+             WHERE organization_id = $1 ORDER BY request_id",
+            )
+            .bind(key.org_id)
+            .fetch_all(pool)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), RESPONSES);
+        for (i, (request_id, status, tokens, metadata)) in rows.into_iter().enumerate() {
+            assert_eq!(request_id, request_ids[i]);
+            if i == 500 {
+                assert_eq!((status.as_str(), tokens, metadata), ("pending", None, None));
+            } else {
+                assert_eq!(status, "success");
+                assert_eq!(tokens, Some(i as i32 % 7 + 1));
+                assert_eq!(metadata, Some(serde_json::json!({"cached": i % 2 == 0})));
+            }
+        }
+
+        let (events,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM usage_events WHERE organization_id = $1")
+                .bind(key.org_id)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+        assert_eq!(events, RESPONSES as i64);
+
+        cleanup_db().await;
+    }
 }
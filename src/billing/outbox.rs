@@ -0,0 +1,307 @@
+//! Write-ahead outbox for usage (`USAGE_OUTBOX_PATH`)
+//!
+//! By default responses are buffered in memory until the next flush, so a
+//! crash loses up to one flush interval of usage. With an outbox configured,
+//! `record_response` first appends the response as a JSON line to a local
+//! file. The flush task writes the lines past the checkpoint to Postgres and
+//! then advances the checkpoint (kept next to the file, in `<path>.checkpoint`).
+//! On startup, lines left past the checkpoint by a crash are replayed before
+//! requests are served.
+//!
+//! Appends are fsync'd every `SYNC_EVERY` lines and before each flush; a
+//! killed process loses nothing, a power loss at most the unsynced lines.
+//! A crash between the database write and the checkpoint replays the same
+//! lines again, so usage events are deduplicated on request_id. Once every
+//! line is checkpointed the file is truncated.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Appends between fsyncs
+const SYNC_EVERY: usize = 64;
+
+/// A response waiting to be written to api_request_log and usage_events
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub request_id: Uuid,
+    pub organization_id: Uuid,
+    pub api_key_id: Uuid,
+    pub product: String,
+    pub tokens: i32,
+    pub response_metadata: serde_json::Value,
+    #[serde(deserialize_with = "utc_or_naive")]
+    pub timestamp: DateTime<Utc>,
+}
+
+/// An RFC 3339 timestamp, or a naive one (read as UTC) from a line written
+/// before outbox timestamps carried their offset
+fn utc_or_naive<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    value
+        .parse::<DateTime<Utc>>()
+        .or_else(|_| value.parse::<NaiveDateTime>().map(|at| at.and_utc()))
+        .map_err(serde::de::Error::custom)
+}
+
+/// Append-only file of responses with a checkpoint offset
+pub struct Outbox {
+    path: PathBuf,
+    checkpoint_path: PathBuf,
+    state: Mutex<State>,
+}
+
+struct State {
+    file: File,
+    /// Appends since the last fsync
+    unsynced: usize,
+    /// Bytes already written to the database
+    checkpoint: u64,
+}
+
+impl Outbox {
+    /// Open (or create) the outbox at `path`. A line torn by a crash
+    /// mid-append is dropped; it was never acknowledged.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let checkpoint_path = PathBuf::from(format!("{}.checkpoint", path.display()));
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .read(true)
+            .open(&path)?;
+        let complete = complete_len(&path)?;
+        if complete < file.metadata()?.len() {
+            tracing::warn!(
+                "Dropping a torn line at the end of usage outbox {}",
+                path.display()
+            );
+            file.set_len(complete)?;
+            file.sync_data()?;
+        }
+
+        let checkpoint = match fs::read_to_string(&checkpoint_path) {
+            Ok(offset) => offset.trim().parse().unwrap_or_else(|_| {
+                tracing::warn!(
+                    "Invalid usage outbox checkpoint {}, replaying from the start",
+                    checkpoint_path.display()
+                );
+                0
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        // Past the end only after a crash while truncating a fully
+        // checkpointed file
+        let checkpoint = if checkpoint > complete { 0 } else { checkpoint };
+
+        Ok(Self {
+            path,
+            checkpoint_path,
+            state: Mutex::new(State {
+                file,
+                unsynced: 0,
+                checkpoint,
+            }),
+        })
+    }
+
+    /// Append an entry (fsync'd every `SYNC_EVERY` appends)
+    pub fn append(&self, entry: &OutboxEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        let mut state = self.state.lock();
+        let len = state.file.metadata()?.len();
+        if let Err(e) = state.file.write_all(&line) {
+            // Don't leave a partial line for the next append to run into
+            let _ = state.file.set_len(len);
+            return Err(e);
+        }
+        state.unsynced += 1;
+        if state.unsynced >= SYNC_EVERY {
+            state.file.sync_data()?;
+            state.unsynced = 0;
+        }
+        Ok(())
+    }
+
+    /// Entries past the checkpoint, and the offset to checkpoint once they
+    /// are written
+    pub fn pending(&self) -> io::Result<(Vec<OutboxEntry>, u64)> {
+        let checkpoint = {
+            let mut state = self.state.lock();
+            if state.unsynced > 0 {
+                state.file.sync_data()?;
+                state.unsynced = 0;
+            }
+            state.checkpoint
+        };
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(checkpoint))?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        // Only whole lines; an append may be in progress
+        let complete = bytes.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        let entries = bytes[..complete]
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .filter_map(|line| match serde_json::from_slice(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    tracing::error!("Skipping unreadable usage outbox line: {}", e);
+                    None
+                }
+            })
+            .collect();
+
+        Ok((entries, checkpoint + complete as u64))
+    }
+
+    /// Record that everything before `offset` is in the database. Truncates
+    /// the file once nothing is left past the checkpoint.
+    pub fn checkpoint(&self, offset: u64) -> io::Result<()> {
+        let mut state = self.state.lock();
+        if offset <= state.checkpoint {
+            return Ok(());
+        }
+
+        let offset = if offset == state.file.metadata()?.len() {
+            state.file.set_len(0)?;
+            state.file.sync_data()?;
+            0
+        } else {
+            offset
+        };
+
+        let tmp = PathBuf::from(format!("{}.tmp", self.checkpoint_path.display()));
+        let mut file = File::create(&tmp)?;
+        file.write_all(offset.to_string().as_bytes())?;
+        file.sync_data()?;
+        fs::rename(&tmp, &self.checkpoint_path)?;
+
+        state.checkpoint = offset;
+        Ok(())
+    }
+}
+
+/// Length of the file up to its last newline
+fn complete_len(path: &Path) -> io::Result<u64> {
+    let bytes = fs::read(path)?;
+    Ok(bytes.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("smally-outbox-{}", Uuid::now_v7()))
+            .join("usage.outbox")
+    }
+
+    fn entry(tokens: i32) -> OutboxEntry {
+        OutboxEntry {
+            request_id: Uuid::now_v7(),
+            organization_id: Uuid::now_v7(),
+            api_key_id: Uuid::now_v7(),
+            product: "embeddings".to_string(),
+            tokens,
+            response_metadata: serde_json::json!({"cached": false}),
+            timestamp: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_timestamps_round_trip_as_utc() {
+        let line = serde_json::to_value(entry(1)).unwrap();
+        assert!(line["timestamp"].as_str().unwrap().ends_with('Z'));
+
+        // Lines from before the offset was written are read as UTC
+        let mut old = line;
+        old["timestamp"] = serde_json::json!("2025-03-01T23:30:00.250");
+        let entry: OutboxEntry = serde_json::from_value(old).unwrap();
+        assert_eq!(
+            entry.timestamp.to_rfc3339(),
+            "2025-03-01T23:30:00.250+00:00"
+        );
+    }
+
+    #[test]
+    fn test_pending_until_checkpointed() {
+        let path = temp_path();
+        let outbox = Outbox::open(&path).unwrap();
+        let entries: Vec<_> = (1..=3).map(entry).collect();
+        for e in &entries[..2] {
+            outbox.append(e).unwrap();
+        }
+
+        let (pending, offset) = outbox.pending().unwrap();
+        assert_eq!(pending, entries[..2]);
+
+        // Appended while the first two were being written
+        outbox.append(&entries[2]).unwrap();
+        outbox.checkpoint(offset).unwrap();
+        let (pending, offset) = outbox.pending().unwrap();
+        assert_eq!(pending, entries[2..]);
+
+        // Fully checkpointed: the file is emptied
+        outbox.checkpoint(offset).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        assert!(outbox.pending().unwrap().0.is_empty());
+        outbox.append(&entries[0]).unwrap();
+        assert_eq!(outbox.pending().unwrap().0, entries[..1]);
+    }
+
+    #[test]
+    fn test_reopen_after_crash() {
+        let path = temp_path();
+        let entries: Vec<_> = (1..=3).map(entry).collect();
+        {
+            let outbox = Outbox::open(&path).unwrap();
+            for e in &entries {
+                outbox.append(e).unwrap();
+            }
+            let first_line = fs::read(&path).unwrap().iter().position(|&b| b == b'\n');
+            outbox.checkpoint(first_line.unwrap() as u64 + 1).unwrap();
+            // Killed mid-append
+            let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+            file.write_all(b"{\"request_id\":").unwrap();
+        }
+
+        let outbox = Outbox::open(&path).unwrap();
+        assert_eq!(outbox.pending().unwrap().0, entries[1..]);
+        outbox.append(&entries[0]).unwrap();
+        let (pending, _) = outbox.pending().unwrap();
+        assert_eq!(pending.len(), 3);
+        assert_eq!(pending[2], entries[0]);
+    }
+
+    #[test]
+    fn test_checkpoint_past_end_replays_from_start() {
+        let path = temp_path();
+        let outbox = Outbox::open(&path).unwrap();
+        outbox.append(&entry(1)).unwrap();
+        drop(outbox);
+        // Crash between truncating and writing the checkpoint
+        fs::write(
+            PathBuf::from(format!("{}.checkpoint", path.display())),
+            "4096",
+        )
+        .unwrap();
+
+        let outbox = Outbox::open(&path).unwrap();
+        assert_eq!(outbox.pending().unwrap().0.len(), 1);
+    }
+}
@@ -0,0 +1,483 @@
+//! Monthly billing statements
+//!
+//! On the 1st of each month every organization gets a statement for the
+//! previous month, aggregated from `usage_events` and frozen in the
+//! `statements` table together with the tier terms in effect. Statements are
+//! never recomputed when read. An admin can regenerate a month, which stores a
+//! new version next to the previous ones.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize, Serializer};
+use sqlx::types::Json;
+use std::time::Duration;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::database::{self, Db, DbPool};
+use crate::models::TierType;
+
+/// Delay after midnight UTC on the 1st before the scheduled run, so usage
+/// buffered in the last seconds of the month has been flushed
+const SCHEDULE_DELAY_SECS: i64 = 5 * 60;
+
+/// Who produced a statement version
+pub const GENERATED_BY_SCHEDULER: &str = "scheduler";
+pub const GENERATED_BY_ADMIN: &str = "admin";
+
+/// Usage of a single API key within the month
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct KeyUsage {
+    /// None for usage of keys that have since been deleted
+    #[serde(with = "crate::uuid_dashless::simple::option")]
+    pub key_id: Option<Uuid>,
+    pub name: Option<String>,
+    pub requests: i64,
+    pub tokens: i64,
+}
+
+/// Stored statement (one version of one organization's month)
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Statement {
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub id: Uuid,
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub organization_id: Uuid,
+    #[serde(serialize_with = "serialize_month")]
+    pub month: NaiveDate,
+    pub version: i32,
+    pub tier: TierType,
+    pub monthly_quota: i64,
+    pub max_tokens: i32,
+    /// Cents per million tokens
+    pub price_per_million_tokens: i64,
+    pub total_requests: i64,
+    pub total_tokens: i64,
+    pub cost_cents: i64,
+    pub key_breakdown: Json<Vec<KeyUsage>>,
+    pub generated_at: DateTime<Utc>,
+    pub generated_by: String,
+    pub reason: Option<String>,
+}
+
+fn serialize_month<S>(month: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&format_month(*month))
+}
+
+/// Cost in cents, rounded up to the next cent
+pub fn compute_cost_cents(total_tokens: i64, price_per_million_tokens: i64) -> i64 {
+    let micro_cents = total_tokens.max(0) as i128 * price_per_million_tokens.max(0) as i128;
+    let cents = (micro_cents + 999_999) / 1_000_000;
+    cents.min(i64::MAX as i128) as i64
+}
+
+/// First day of the month containing `date`
+pub fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).expect("day 1 exists in every month")
+}
+
+/// First day of the month after `month`
+pub fn next_month(month: NaiveDate) -> NaiveDate {
+    month_start(month)
+        .checked_add_months(chrono::Months::new(1))
+        .expect("date in range")
+}
+
+/// First day of the month before the one containing `today`
+pub fn previous_month(today: NaiveDate) -> NaiveDate {
+    month_start(today)
+        .checked_sub_months(chrono::Months::new(1))
+        .expect("date in range")
+}
+
+/// Parse a `YYYY-MM` month
+pub fn parse_month(s: &str) -> Option<NaiveDate> {
+    if s.len() != 7 {
+        return None;
+    }
+    NaiveDate::parse_from_str(&format!("{}-01", s), "%Y-%m-%d").ok()
+}
+
+/// Format a month as `YYYY-MM`
+pub fn format_month(month: NaiveDate) -> String {
+    month.format("%Y-%m").to_string()
+}
+
+/// Time until the next scheduled run (the 1st of next month, shortly after midnight UTC)
+fn until_next_run(now: DateTime<Utc>) -> Duration {
+    let next_run = next_month(now.date_naive())
+        .and_hms_opt(0, 0, 0)
+        .expect("valid time")
+        .and_utc()
+        + chrono::Duration::seconds(SCHEDULE_DELAY_SECS);
+    (next_run - now).to_std().unwrap_or_default()
+}
+
+/// Aggregate one organization's usage for `month`
+async fn collect_usage(pool: &DbPool, org_id: Uuid, month: NaiveDate) -> Result<Vec<KeyUsage>> {
+    let start = month.and_hms_opt(0, 0, 0).expect("valid time").and_utc();
+    let end = next_month(month)
+        .and_hms_opt(0, 0, 0)
+        .expect("valid time")
+        .and_utc();
+
+    let usage = sqlx::query_as::<_, KeyUsage>(
+        "SELECT ue.api_key_id AS key_id, k.name,
+                CAST(COALESCE(SUM(ue.requests), 0) AS BIGINT) AS requests,
+                CAST(COALESCE(SUM(ue.tokens), 0) AS BIGINT) AS tokens
+         FROM usage_events ue
+         LEFT JOIN api_keys k ON k.key_id = ue.api_key_id
+         WHERE ue.organization_id = $1 AND ue.timestamp >= $2 AND ue.timestamp < $3
+         GROUP BY ue.api_key_id, k.name
+         ORDER BY tokens DESC, requests DESC",
+    )
+    .bind(org_id)
+    .bind(database::timestamp(start))
+    .bind(database::timestamp(end))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(usage)
+}
+
+/// Insert a statement version; returns None when that version already exists
+#[allow(clippy::too_many_arguments)]
+async fn insert_statement<'e, E>(
+    executor: E,
+    org_id: Uuid,
+    tier: TierType,
+    month: NaiveDate,
+    version: i32,
+    usage: Vec<KeyUsage>,
+    generated_by: &str,
+    reason: Option<&str>,
+) -> Result<Option<Statement>>
+where
+    E: sqlx::Executor<'e, Database = Db>,
+{
+    let limits = tier.limits();
+    let total_requests: i64 = usage.iter().map(|k| k.requests).sum();
+    let total_tokens: i64 = usage.iter().map(|k| k.tokens).sum();
+    let cost_cents = compute_cost_cents(total_tokens, limits.price_per_million_tokens);
+
+    let statement = sqlx::query_as::<_, Statement>(
+        "INSERT INTO statements
+         (id, organization_id, month, version, tier, monthly_quota, max_tokens,
+          price_per_million_tokens, total_requests, total_tokens, cost_cents, key_breakdown,
+          generated_at, generated_by, reason)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+         ON CONFLICT (organization_id, month, version) DO NOTHING
+         RETURNING *",
+    )
+    .bind(Uuid::now_v7())
+    .bind(org_id)
+    .bind(month)
+    .bind(version)
+    .bind(tier)
+    .bind(limits.monthly_quota as i64)
+    .bind(limits.max_tokens as i32)
+    .bind(limits.price_per_million_tokens)
+    .bind(total_requests)
+    .bind(total_tokens)
+    .bind(cost_cents)
+    .bind(Json(usage))
+    .bind(database::timestamp(Utc::now()))
+    .bind(generated_by)
+    .bind(reason)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(statement)
+}
+
+/// Generate version 1 of `month` for every organization that existed during
+/// it. Organizations that already have a statement are skipped, so running
+/// this more than once (or on several instances) is harmless. Returns the
+/// number of statements created.
+pub async fn generate_month(pool: &DbPool, month: NaiveDate) -> Result<usize> {
+    let month = month_start(month);
+    let month_end = next_month(month)
+        .and_hms_opt(0, 0, 0)
+        .expect("valid time")
+        .and_utc();
+
+    let orgs = sqlx::query_as::<_, (Uuid, TierType)>(
+        "SELECT o.id, o.tier FROM organizations o
+         WHERE o.created_at < $1
+           AND NOT EXISTS (
+               SELECT 1 FROM statements s WHERE s.organization_id = o.id AND s.month = $2
+           )",
+    )
+    .bind(database::timestamp(month_end))
+    .bind(month)
+    .fetch_all(pool)
+    .await?;
+
+    let mut created = 0;
+    for (org_id, tier) in orgs {
+        let usage = collect_usage(pool, org_id, month).await?;
+        let inserted = insert_statement(
+            pool,
+            org_id,
+            tier,
+            month,
+            1,
+            usage,
+            GENERATED_BY_SCHEDULER,
+            None,
+        )
+        .await?;
+        if inserted.is_some() {
+            created += 1;
+        }
+    }
+
+    Ok(created)
+}
+
+/// Recompute `month` for one organization as a new statement version
+pub async fn regenerate(
+    pool: &DbPool,
+    org_id: Uuid,
+    month: NaiveDate,
+    reason: &str,
+) -> Result<Statement> {
+    let month = month_start(month);
+    let mut tx = pool.begin().await?;
+
+    let tier = sqlx::query_scalar::<_, TierType>("SELECT tier FROM organizations WHERE id = $1")
+        .bind(org_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| anyhow!("Organization not found"))?;
+
+    let version = sqlx::query_scalar::<_, i32>(
+        "SELECT COALESCE(MAX(version), 0) + 1 FROM statements
+         WHERE organization_id = $1 AND month = $2",
+    )
+    .bind(org_id)
+    .bind(month)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let usage = collect_usage(pool, org_id, month).await?;
+    let statement = insert_statement(
+        &mut *tx,
+        org_id,
+        tier,
+        month,
+        version,
+        usage,
+        GENERATED_BY_ADMIN,
+        Some(reason),
+    )
+    .await?
+    .ok_or_else(|| anyhow!("Statement is being regenerated concurrently"))?;
+
+    tx.commit().await?;
+    Ok(statement)
+}
+
+#[cfg(not(feature = "sqlite"))]
+const LIST_LATEST: &str = "SELECT DISTINCT ON (month) * FROM statements
+     WHERE organization_id = $1
+     ORDER BY month DESC, version DESC";
+#[cfg(feature = "sqlite")]
+const LIST_LATEST: &str = "SELECT * FROM statements s
+     WHERE organization_id = $1
+       AND version = (SELECT MAX(version) FROM statements
+                      WHERE organization_id = s.organization_id AND month = s.month)
+     ORDER BY month DESC";
+
+/// Latest version of every statement of an organization, newest month first
+pub async fn list_latest(pool: &DbPool, org_id: Uuid) -> Result<Vec<Statement>> {
+    let statements = sqlx::query_as::<_, Statement>(LIST_LATEST)
+        .bind(org_id)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(statements)
+}
+
+/// A stored statement: the given version, or the latest one
+pub async fn find(
+    pool: &DbPool,
+    org_id: Uuid,
+    month: NaiveDate,
+    version: Option<i32>,
+) -> Result<Option<Statement>> {
+    let statement = sqlx::query_as::<_, Statement>(
+        "SELECT * FROM statements
+         WHERE organization_id = $1 AND month = $2 AND (CAST($3 AS INTEGER) IS NULL OR version = $3)
+         ORDER BY version DESC
+         LIMIT 1",
+    )
+    .bind(org_id)
+    .bind(month_start(month))
+    .bind(version)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(statement)
+}
+
+/// Start the background task generating last month's statements. It runs
+/// once at startup (catching up on a missed 1st) and then on every 1st.
+pub fn start_statement_task(pool: &'static DbPool) {
+    tokio::spawn(async move {
+        loop {
+            let month = previous_month(Utc::now().date_naive());
+            match generate_month(pool, month).await {
+                Ok(created) => info!(
+                    "Generated {} statements for {}",
+                    created,
+                    format_month(month)
+                ),
+                Err(e) => tracing::error!("Failed to generate statements: {}", e),
+            }
+
+            tokio::time::sleep(until_next_run(Utc::now())).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "control-plane")]
+    use crate::database;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::factory;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    #[cfg(feature = "control-plane")]
+    use serial_test::serial;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_month_arithmetic() {
+        assert_eq!(month_start(date(2025, 3, 31)), date(2025, 3, 1));
+        assert_eq!(next_month(date(2025, 12, 15)), date(2026, 1, 1));
+        assert_eq!(previous_month(date(2025, 1, 1)), date(2024, 12, 1));
+        assert_eq!(previous_month(date(2025, 3, 31)), date(2025, 2, 1));
+    }
+
+    #[test]
+    fn test_parse_and_format_month() {
+        assert_eq!(parse_month("2025-02"), Some(date(2025, 2, 1)));
+        assert_eq!(format_month(date(2025, 2, 1)), "2025-02");
+        assert_eq!(parse_month("2025-13"), None);
+        assert_eq!(parse_month("2025-2"), None);
+        assert_eq!(parse_month("2025-02-01"), None);
+    }
+
+    #[test]
+    fn test_cost_rounds_up_to_cent() {
+        assert_eq!(compute_cost_cents(0, 500), 0);
+        assert_eq!(compute_cost_cents(1_000_000, 500), 500);
+        assert_eq!(compute_cost_cents(1, 500), 1);
+        assert_eq!(compute_cost_cents(2_500_000, 25), 63);
+        assert_eq!(compute_cost_cents(1_000_000, 0), 0);
+    }
+
+    #[test]
+    fn test_next_run_is_first_of_next_month() {
+        let now = date(2025, 1, 31).and_hms_opt(23, 0, 0).unwrap().and_utc();
+        assert_eq!(
+            until_next_run(now),
+            Duration::from_secs(3600 + SCHEDULE_DELAY_SECS as u64)
+        );
+
+        // Just before the scheduled time on the 1st: wait only for the delay
+        let now = date(2025, 2, 1).and_hms_opt(0, 0, 0).unwrap().and_utc();
+        assert_eq!(
+            until_next_run(now),
+            (date(2025, 3, 1).and_hms_opt(0, 5, 0).unwrap().and_utc() - now)
+                .to_std()
+                .unwrap()
+        );
+    }
+
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    #[serial]
+    async fn test_generate_month_is_idempotent() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+
+        let org_id = factory::user("test@example.com").await.org_id;
+        let month = previous_month(Utc::now().date_naive());
+
+        // Organization must predate the month to be billed for it
+        sqlx::query("UPDATE organizations SET created_at = $2 WHERE id = $1")
+            .bind(org_id)
+            .bind(database::timestamp(
+                month.and_hms_opt(0, 0, 0).unwrap().and_utc(),
+            ))
+            .execute(pool)
+            .await
+            .unwrap();
+
+        for (tokens, day) in [(100, 1), (250, 15), (999, 40)] {
+            sqlx::query(
+                "INSERT INTO usage_events (organization_id, product, event_type, tokens, requests, timestamp)
+                 VALUES ($1, 'embed', 'inference', $2, 1, $3)",
+            )
+            .bind(org_id)
+            .bind(tokens)
+            .bind(database::timestamp(month.and_hms_opt(12, 0, 0).unwrap().and_utc() + chrono::Duration::days(day - 1)))
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(generate_month(pool, month).await.unwrap(), 1);
+        let first = find(pool, org_id, month, None).await.unwrap().unwrap();
+
+        // Second run creates nothing and leaves the stored numbers untouched
+        assert_eq!(generate_month(pool, month).await.unwrap(), 0);
+        let count = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM statements WHERE organization_id = $1",
+        )
+        .bind(org_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        assert_eq!(count, 1);
+
+        let second = find(pool, org_id, month, None).await.unwrap().unwrap();
+        assert_eq!(second.id, first.id);
+        assert_eq!(second.version, 1);
+        // The event on day 40 falls into the following month
+        assert_eq!(second.total_requests, 2);
+        assert_eq!(second.total_tokens, 350);
+        assert_eq!(second.generated_by, GENERATED_BY_SCHEDULER);
+
+        // Regeneration adds a version instead of overwriting
+        let regenerated = regenerate(pool, org_id, month, "token recount")
+            .await
+            .unwrap();
+        assert_eq!(regenerated.version, 2);
+        assert_eq!(regenerated.reason.as_deref(), Some("token recount"));
+        assert_eq!(
+            find(pool, org_id, month, Some(1))
+                .await
+                .unwrap()
+                .unwrap()
+                .id,
+            first.id
+        );
+        let latest = list_latest(pool, org_id).await.unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].version, 2);
+
+        cleanup_db().await;
+    }
+}
@@ -0,0 +1,433 @@
+//! Free trials: a higher tier for a limited time
+//!
+//! Admins grant an organization a trial tier until a date
+//! (`POST /v1/admin/organizations/:org_id/trial`; granting the tier again
+//! while the trial runs extends it). Until then the organization is served
+//! as the trial tier: its limits and rate limits, and the ceilings of new
+//! keys. Keys keep the base tier signed in and are upgraded per request
+//! (`TokenClaims::with_tier`), so they fall back on their own when the trial
+//! ends. Statements keep pricing the base tier: trial usage isn't charged.
+//!
+//! Resolution never waits for an expiry to be processed: an expired trial
+//! stops counting right away. The daily `trial_expiry` job then clears it
+//! and emails the owners.
+//!
+//! The trial is read on every API request, so it's cached in process like
+//! the billing anchor (see `cycle::org_anchor`).
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::alerts;
+use crate::auth::TokenClaims;
+use crate::database::{self, DbPool};
+use crate::jobs;
+use crate::models::TierType;
+use crate::notifications::email::{self, EmailSender};
+use crate::notifications::templates;
+
+/// Longest trial (or extension) granted at once, in days
+pub const MAX_TRIAL_DAYS: i64 = 90;
+
+/// How long a trial lookup is trusted before hitting the database again
+const TRIAL_CACHE_TTL_SECS: u64 = 30;
+
+/// How often expired trials are cleared
+const EXPIRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
+
+/// A trial tier and when it ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trial {
+    pub tier: TierType,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Trial {
+    /// Trial stored in `trial_tier` / `trial_expires_at` (None unless both
+    /// are set)
+    pub fn from_columns(tier: Option<TierType>, expires_at: Option<DateTime<Utc>>) -> Option<Self> {
+        Some(Trial {
+            tier: tier?,
+            expires_at: expires_at?,
+        })
+    }
+
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        now < self.expires_at
+    }
+
+    /// Days left, counting a started day as a whole one (0 once expired)
+    pub fn days_left(&self, now: DateTime<Utc>) -> i64 {
+        let seconds = (self.expires_at - now).num_seconds().max(0);
+        (seconds + 86_399) / 86_400
+    }
+}
+
+/// Tier an organization on `base` is served as at `now`. A trial only ever
+/// raises the tier.
+pub fn effective_tier(base: TierType, trial: Option<Trial>, now: DateTime<Utc>) -> TierType {
+    match trial {
+        Some(trial) if trial.is_active(now) && trial.tier.to_u8() > base.to_u8() => trial.tier,
+        _ => base,
+    }
+}
+
+/// End of a trial of `tier` granted for `days` at `now`: an extension of
+/// the running trial if it's of the same tier, else a fresh trial
+pub fn granted_until(
+    current: Option<Trial>,
+    tier: TierType,
+    days: i64,
+    now: DateTime<Utc>,
+) -> DateTime<Utc> {
+    let start = match current {
+        Some(trial) if trial.tier == tier && trial.is_active(now) => trial.expires_at,
+        _ => now,
+    };
+    start + Duration::days(days)
+}
+
+/// org_id -> (trial, checked_at)
+static TRIAL_CACHE: Lazy<DashMap<Uuid, (Option<Trial>, Instant)>> = Lazy::new(DashMap::new);
+
+/// Trial of the organization, expired or not. Lookup failures fall back to
+/// the last known trial, else to none.
+pub async fn org_trial(org_id: Uuid) -> Option<Trial> {
+    if let Some(entry) = TRIAL_CACHE.get(&org_id) {
+        let (trial, checked_at) = *entry;
+        if checked_at.elapsed().as_secs() < TRIAL_CACHE_TTL_SECS {
+            return trial;
+        }
+    }
+
+    let row = sqlx::query_as::<_, (Option<TierType>, Option<DateTime<Utc>>)>(
+        "SELECT trial_tier, trial_expires_at FROM organizations WHERE id = $1",
+    )
+    .bind(org_id)
+    .fetch_optional(database::get_db())
+    .await;
+
+    match row {
+        Ok(row) => {
+            let trial = row.and_then(|(tier, expires_at)| Trial::from_columns(tier, expires_at));
+            TRIAL_CACHE.insert(org_id, (trial, Instant::now()));
+            trial
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load trial of {}: {}", org_id, e);
+            TRIAL_CACHE.get(&org_id).and_then(|entry| entry.0)
+        }
+    }
+}
+
+/// Forget a cached trial (call after changing it)
+pub fn invalidate(org_id: Uuid) {
+    TRIAL_CACHE.remove(&org_id);
+}
+
+/// Claims of an API key, served as the tier its organization is on now
+pub async fn resolve(claims: TokenClaims) -> TokenClaims {
+    let trial = org_trial(claims.org_id()).await;
+    let tier = effective_tier(claims.signed_tier(), trial, Utc::now());
+    claims.with_tier(tier)
+}
+
+/// Outcome of `grant`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Grant {
+    Granted(Trial),
+    /// The organization is already on this tier or a higher one
+    NotAnUpgrade(TierType),
+    NotFound,
+}
+
+/// Grant (or extend) a trial of `tier` for `days`
+pub async fn grant(
+    pool: &DbPool,
+    org_id: Uuid,
+    tier: TierType,
+    days: i64,
+    now: DateTime<Utc>,
+) -> Result<Grant> {
+    let row = sqlx::query_as::<_, (TierType, Option<TierType>, Option<DateTime<Utc>>)>(
+        "SELECT tier, trial_tier, trial_expires_at FROM organizations WHERE id = $1",
+    )
+    .bind(org_id)
+    .fetch_optional(pool)
+    .await?;
+    let Some((base, trial_tier, trial_expires_at)) = row else {
+        return Ok(Grant::NotFound);
+    };
+    if tier.to_u8() <= base.to_u8() {
+        return Ok(Grant::NotAnUpgrade(base));
+    }
+
+    let current = Trial::from_columns(trial_tier, trial_expires_at);
+    let trial = Trial {
+        tier,
+        expires_at: granted_until(current, tier, days, now),
+    };
+    sqlx::query(
+        "UPDATE organizations SET trial_tier = $1, trial_expires_at = $2, updated_at = $3
+         WHERE id = $4",
+    )
+    .bind(trial.tier)
+    .bind(database::timestamp(trial.expires_at))
+    .bind(database::timestamp(now))
+    .bind(org_id)
+    .execute(pool)
+    .await?;
+    invalidate(org_id);
+
+    Ok(Grant::Granted(trial))
+}
+
+/// Clear the trials that ended by `now` and email each organization's
+/// owners. Returns the number of trials cleared.
+pub async fn expire_due(
+    pool: &DbPool,
+    sender: &dyn EmailSender,
+    now: DateTime<Utc>,
+) -> Result<usize> {
+    let due = sqlx::query_as::<_, (Uuid, String, TierType, TierType, DateTime<Utc>)>(
+        "SELECT id, name, tier, trial_tier, trial_expires_at FROM organizations
+         WHERE trial_tier IS NOT NULL AND trial_expires_at <= $1",
+    )
+    .bind(database::timestamp(now))
+    .fetch_all(pool)
+    .await?;
+
+    let mut expired = 0;
+    for (org_id, org_name, base, trial_tier, expires_at) in due {
+        // Losing the race means another instance (or a new grant) got there
+        // first
+        let cleared = sqlx::query(
+            "UPDATE organizations SET trial_tier = NULL, trial_expires_at = NULL, updated_at = $1
+             WHERE id = $2 AND trial_expires_at = $3",
+        )
+        .bind(database::timestamp(now))
+        .bind(org_id)
+        .bind(database::timestamp(expires_at))
+        .execute(pool)
+        .await?
+        .rows_affected()
+            == 1;
+        if !cleared {
+            continue;
+        }
+        invalidate(org_id);
+        expired += 1;
+
+        let email = templates::trial_ended(&org_name, trial_tier, base);
+        for to in alerts::owner_emails(pool, org_id).await? {
+            if let Err(e) = sender
+                .send(&to, &email.subject, &email.html_body, &email.text_body)
+                .await
+            {
+                error!("Failed to send trial notice to {}: {}", to, e);
+            }
+        }
+
+        tracing::info!(
+            target: "audit",
+            action = "trial.expired",
+            org_id = %org_id,
+            trial_tier = ?trial_tier,
+            tier = ?base,
+            expires_at = %expires_at,
+            "Organization trial expired"
+        );
+    }
+
+    Ok(expired)
+}
+
+/// Register the daily expiry job
+pub fn start_expiry_task(pool: &'static DbPool) {
+    jobs::register(
+        jobs::Job::new("trial_expiry", EXPIRY_INTERVAL, move || async move {
+            let expired = expire_due(pool, email::outbox(), Utc::now()).await?;
+            if expired > 0 {
+                info!("Expired {} trials", expired);
+            }
+            Ok(())
+        })
+        .jitter(std::time::Duration::from_secs(10 * 60)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::TokenData;
+    use crate::config::Settings;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::factory;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    #[cfg(feature = "control-plane")]
+    use axum::async_trait;
+    #[cfg(feature = "control-plane")]
+    use parking_lot::Mutex;
+    #[cfg(feature = "control-plane")]
+    use serial_test::serial;
+
+    #[cfg(feature = "control-plane")]
+    #[derive(Default)]
+    struct RecordingSender {
+        sent: Mutex<Vec<(String, String)>>,
+    }
+
+    #[cfg(feature = "control-plane")]
+    #[async_trait]
+    impl EmailSender for RecordingSender {
+        async fn send(&self, to: &str, subject: &str, _html: &str, _text: &str) -> Result<()> {
+            self.sent.lock().push((to.to_string(), subject.to_string()));
+            Ok(())
+        }
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M")
+            .unwrap()
+            .and_utc()
+    }
+
+    #[test]
+    fn test_effective_tier() {
+        let trial = Trial {
+            tier: TierType::Pro,
+            expires_at: at("2025-03-15 12:00"),
+        };
+
+        assert_eq!(
+            effective_tier(TierType::Free, Some(trial), at("2025-03-15 11:59")),
+            TierType::Pro
+        );
+        // Expired trials stop counting before the job clears them
+        assert_eq!(
+            effective_tier(TierType::Free, Some(trial), at("2025-03-15 12:00")),
+            TierType::Free
+        );
+        assert_eq!(
+            effective_tier(TierType::Free, None, at("2025-03-01 00:00")),
+            TierType::Free
+        );
+        // A trial never lowers the tier
+        assert_eq!(
+            effective_tier(TierType::Scale, Some(trial), at("2025-03-01 00:00")),
+            TierType::Scale
+        );
+    }
+
+    #[test]
+    fn test_granting_again_extends_the_running_trial() {
+        let now = at("2025-03-10 09:00");
+        let running = Trial {
+            tier: TierType::Pro,
+            expires_at: at("2025-03-15 12:00"),
+        };
+
+        assert_eq!(
+            granted_until(None, TierType::Pro, 14, now),
+            at("2025-03-24 09:00")
+        );
+        assert_eq!(
+            granted_until(Some(running), TierType::Pro, 14, now),
+            at("2025-03-29 12:00")
+        );
+        // Another tier, or an expired trial, starts over
+        assert_eq!(
+            granted_until(Some(running), TierType::Scale, 14, now),
+            at("2025-03-24 09:00")
+        );
+        assert_eq!(
+            granted_until(Some(running), TierType::Pro, 14, at("2025-03-16 09:00")),
+            at("2025-03-30 09:00")
+        );
+
+        assert_eq!(running.days_left(at("2025-03-10 09:00")), 6);
+        assert_eq!(running.days_left(at("2025-03-15 00:00")), 1);
+        assert_eq!(running.days_left(at("2025-03-16 00:00")), 0);
+    }
+
+    #[test]
+    fn test_keys_fall_back_to_the_signed_tier() {
+        let free = Settings::new().free_tier_concurrency as u32;
+        let pro = Settings::new().pro_tier_concurrency as u32;
+        // Created during a Pro trial with the Pro concurrency cap
+        let claims = TokenClaims::from_token_data(TokenData {
+            org_id: Uuid::now_v7(),
+            key_id: Uuid::now_v7(),
+            tier: TierType::Free,
+            max_tokens: 128,
+            monthly_quota: 20_000,
+            require_signing: false,
+            max_concurrency: Some(pro),
+            scopes: None,
+            models: None,
+            api_version: None,
+            strict_validation: false,
+            generation: 0,
+        });
+
+        let trial = claims.clone().with_tier(TierType::Pro);
+        assert_eq!(trial.tier().unwrap(), TierType::Pro);
+        assert_eq!(trial.signed_tier(), TierType::Free);
+        assert_eq!(trial.max_concurrency(), pro);
+
+        let expired = trial.with_tier(TierType::Free);
+        assert_eq!(expired.tier().unwrap(), TierType::Free);
+        assert_eq!(expired.max_concurrency(), free);
+        // The override is never signed into the token
+        let decoded = TokenClaims::from_cbor_bytes(&expired.to_cbor_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.tier().unwrap(), TierType::Free);
+    }
+
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    #[serial]
+    async fn test_grant_then_expire() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let org_id = factory::user("owner@example.com").await.org_id;
+        let now = Utc::now();
+
+        assert_eq!(
+            grant(pool, org_id, TierType::Free, 14, now).await.unwrap(),
+            Grant::NotAnUpgrade(TierType::Free)
+        );
+        assert_eq!(
+            grant(pool, Uuid::now_v7(), TierType::Pro, 14, now)
+                .await
+                .unwrap(),
+            Grant::NotFound
+        );
+        let Grant::Granted(trial) = grant(pool, org_id, TierType::Pro, 14, now).await.unwrap()
+        else {
+            panic!("trial not granted");
+        };
+        assert_eq!(org_trial(org_id).await, Some(trial));
+
+        // Nothing is due until the trial ends
+        let sender = RecordingSender::default();
+        assert_eq!(expire_due(pool, &sender, now).await.unwrap(), 0);
+
+        let later = trial.expires_at + Duration::minutes(1);
+        assert_eq!(expire_due(pool, &sender, later).await.unwrap(), 1);
+        assert_eq!(expire_due(pool, &sender, later).await.unwrap(), 0);
+        assert_eq!(org_trial(org_id).await, None);
+
+        let sent = sender.sent.lock().clone();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].0, "owner@example.com");
+        assert!(sent[0].1.starts_with("The Pro trial of"));
+    }
+}
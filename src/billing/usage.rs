@@ -0,0 +1,243 @@
+//! Usage reports: an organization's requests and tokens per day over a
+//! period, read live from `usage_events` (unlike statements, which are
+//! frozen). Usage still buffered in memory shows up after the next flush.
+
+use anyhow::Result;
+use chrono::{Days, NaiveDate, NaiveTime};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::database::usage_events::DailyKeyUsage;
+use crate::database::{DbPool, UsageEventsRepo};
+
+/// Longest period of one report, in days
+pub const MAX_REPORT_DAYS: i64 = 92;
+
+/// Usage of an API key on one day
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeyDayUsage {
+    /// None for usage of keys that have since been deleted
+    #[serde(with = "crate::uuid_dashless::simple::option")]
+    pub key_id: Option<Uuid>,
+    pub name: Option<String>,
+    pub requests: i64,
+    pub tokens: i64,
+}
+
+/// Usage on one day (every day of the period, with or without usage)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DayUsage {
+    pub day: NaiveDate,
+    pub requests: i64,
+    pub tokens: i64,
+    /// Usage per API key, most tokens first (only when grouped by key)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keys: Option<Vec<KeyDayUsage>>,
+}
+
+/// Usage of an organization from `from` to `to` (days, UTC, both included)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UsageReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub total_requests: i64,
+    pub total_tokens: i64,
+    pub days: Vec<DayUsage>,
+}
+
+/// Usage of an organization per day from `from` to `to` (both included),
+/// per API key too when `by_key`. The caller checks the period.
+pub async fn usage_report(
+    pool: &DbPool,
+    org_id: Uuid,
+    from: NaiveDate,
+    to: NaiveDate,
+    by_key: bool,
+) -> Result<UsageReport> {
+    let start = from.and_time(NaiveTime::MIN).and_utc();
+    let end = (to + Days::new(1)).and_time(NaiveTime::MIN).and_utc();
+    let rows = UsageEventsRepo::new(pool)
+        .daily_by_key_between(org_id, start, end)
+        .await?;
+
+    Ok(build_report(from, to, rows, by_key))
+}
+
+/// Report from per-day, per-key rows (ordered by day)
+fn build_report(
+    from: NaiveDate,
+    to: NaiveDate,
+    rows: Vec<DailyKeyUsage>,
+    by_key: bool,
+) -> UsageReport {
+    let mut rows = rows.into_iter().peekable();
+    let mut days = Vec::new();
+    for day in from.iter_days().take_while(|day| *day <= to) {
+        let mut usage = DayUsage {
+            day,
+            requests: 0,
+            tokens: 0,
+            keys: by_key.then(Vec::new),
+        };
+        while let Some(row) = rows.next_if(|row| row.day <= day) {
+            if row.day < day {
+                continue;
+            }
+            usage.requests += row.requests;
+            usage.tokens += row.tokens;
+            if let Some(keys) = &mut usage.keys {
+                keys.push(KeyDayUsage {
+                    key_id: row.key_id,
+                    name: row.name,
+                    requests: row.requests,
+                    tokens: row.tokens,
+                });
+            }
+        }
+        days.push(usage);
+    }
+
+    UsageReport {
+        from,
+        to,
+        total_requests: days.iter().map(|day| day.requests).sum(),
+        total_tokens: days.iter().map(|day| day.tokens).sum(),
+        days,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "control-plane")]
+    use crate::database::{self, timestamp};
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::factory;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    #[cfg(feature = "control-plane")]
+    use serial_test::serial;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    fn row(day: NaiveDate, key: u128, requests: i64, tokens: i64) -> DailyKeyUsage {
+        DailyKeyUsage {
+            day,
+            key_id: Some(Uuid::from_u128(key)),
+            name: None,
+            requests,
+            tokens,
+        }
+    }
+
+    #[test]
+    fn test_build_report_fills_every_day() {
+        let rows = vec![
+            row(date(2024, 1, 1), 1, 2, 20),
+            row(date(2024, 1, 1), 2, 1, 5),
+            row(date(2024, 1, 3), 1, 4, 40),
+        ];
+
+        let report = build_report(date(2024, 1, 1), date(2024, 1, 4), rows.clone(), false);
+        assert_eq!((report.total_requests, report.total_tokens), (7, 65));
+        let per_day: Vec<(i64, i64)> = report
+            .days
+            .iter()
+            .map(|day| (day.requests, day.tokens))
+            .collect();
+        assert_eq!(per_day, vec![(3, 25), (0, 0), (4, 40), (0, 0)]);
+        assert!(report.days.iter().all(|day| day.keys.is_none()));
+
+        let report = build_report(date(2024, 1, 1), date(2024, 1, 4), rows, true);
+        let keys = report.days[0].keys.as_ref().unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].key_id, Some(Uuid::from_u128(1)));
+        assert_eq!(report.days[1].keys, Some(Vec::new()));
+
+        // Rows outside the period are left out
+        let report = build_report(
+            date(2024, 1, 2),
+            date(2024, 1, 2),
+            vec![
+                row(date(2024, 1, 1), 1, 1, 1),
+                row(date(2024, 1, 3), 1, 1, 1),
+            ],
+            false,
+        );
+        assert_eq!(report.days.len(), 1);
+        assert_eq!(report.total_requests, 0);
+    }
+
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    #[serial]
+    async fn test_usage_report_aggregates_usage_events() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("usage-report@example.com").await;
+        let key = factory::api_key(&owner).await;
+
+        let add_usage = |key_id: Option<Uuid>, tokens: i32, at: NaiveDate| {
+            sqlx::query(
+                "INSERT INTO usage_events (organization_id, api_key_id, product, event_type, tokens, requests, timestamp)
+                 VALUES ($1, $2, 'embeddings', 'inference', $3, 1, $4)",
+            )
+            .bind(owner.org_id)
+            .bind(key_id)
+            .bind(tokens)
+            .bind(timestamp(at.and_hms_opt(12, 0, 0).unwrap().and_utc()))
+            .execute(pool)
+        };
+        add_usage(Some(key.key_id), 10, date(2024, 1, 1))
+            .await
+            .unwrap();
+        add_usage(Some(key.key_id), 5, date(2024, 1, 1))
+            .await
+            .unwrap();
+        add_usage(None, 7, date(2024, 1, 2)).await.unwrap();
+        // Outside the period
+        add_usage(Some(key.key_id), 100, date(2024, 2, 1))
+            .await
+            .unwrap();
+
+        let report = usage_report(
+            pool,
+            owner.org_id,
+            date(2024, 1, 1),
+            date(2024, 1, 31),
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.days.len(), 31);
+        assert_eq!((report.total_requests, report.total_tokens), (3, 22));
+        assert_eq!(
+            report.days[0].keys,
+            Some(vec![KeyDayUsage {
+                key_id: Some(key.key_id),
+                name: Some("Test API Key".to_string()),
+                requests: 2,
+                tokens: 15,
+            }])
+        );
+        assert_eq!(report.days[1].keys.as_ref().unwrap()[0].key_id, None);
+
+        // Other organizations' usage isn't counted
+        let other = factory::user("usage-report-other@example.com").await;
+        let report = usage_report(
+            pool,
+            other.org_id,
+            date(2024, 1, 1),
+            date(2024, 1, 31),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.total_tokens, 0);
+
+        cleanup_db().await;
+    }
+}
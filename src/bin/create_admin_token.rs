@@ -1,8 +1,12 @@
-use anyhow::Result;
-use api::auth::sign_admin_token;
+use anyhow::{bail, Result};
+use api::auth::binding::TokenBinding;
+use api::auth::{admin, sign_admin_token};
 use api::config;
+use api::database::{self, Db};
 use chrono::{Duration, Utc};
 use clap::Parser;
+use sqlx::pool::PoolOptions;
+use uuid::Uuid;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Generate admin tokens for UI/CLI access", long_about = None)]
@@ -11,12 +15,13 @@ struct Args {
     #[arg(short, long, default_value = "ui")]
     scope: String,
 
-    /// Expiration in days (default: 365 days)
-    #[arg(short, long, default_value_t = 365)]
+    /// Expiration in days (at most ADMIN_TOKEN_MAX_DAYS)
+    #[arg(short, long, default_value_t = 30)]
     days: i64,
 }
 
-fn main() -> Result<()> {
+#[tokio::main]
+async fn main() -> Result<()> {
     let args = Args::parse();
 
     // Load environment variables
@@ -25,6 +30,14 @@ fn main() -> Result<()> {
     // Get settings to retrieve private key
     let settings = config::get_settings();
 
+    // Longer-lived tokens would be rejected by the server
+    if args.days < 1 || args.days > settings.admin_token_max_days {
+        bail!(
+            "--days must be between 1 and {} (ADMIN_TOKEN_MAX_DAYS)",
+            settings.admin_token_max_days
+        );
+    }
+
     // Parse signing key
     let private_key_bytes = hex::decode(&settings.token_private_key)?;
     let signing_key = ed25519_dalek::SigningKey::from_bytes(
@@ -37,18 +50,37 @@ fn main() -> Result<()> {
     let expiration = (Utc::now() + Duration::days(args.days)).timestamp();
 
     // Generate token
-    let token = sign_admin_token(&args.scope, expiration, &signing_key)?;
+    let token_id = Uuid::now_v7();
+    let token = sign_admin_token(
+        token_id,
+        &args.scope,
+        expiration,
+        &signing_key,
+        &TokenBinding::from_settings(),
+    )?;
+
+    // Record it so it can be listed and revoked
+    let pool = PoolOptions::<Db>::new()
+        .max_connections(1)
+        .connect_with(database::connect_options(&settings.database_url)?)
+        .await?;
+    admin::record(&pool, token_id, &args.scope, expiration).await?;
 
     // Print token with prefix
     let prefixed_token = format!("admin_{}", token);
 
     println!("\n✅ Admin token generated successfully!\n");
+    println!("Token ID:   {}", token_id.simple());
     println!("Scope:      {}", args.scope);
     println!("Expires in: {} days", args.days);
     println!("\nToken:");
     println!("{}", prefixed_token);
     println!("\nUse this token in the Authorization header:");
-    println!("Authorization: Bearer {}\n", prefixed_token);
+    println!("Authorization: Bearer {}", prefixed_token);
+    println!(
+        "\nRevoke with: DELETE /v1/admin/tokens/{}\n",
+        token_id.simple()
+    );
 
     Ok(())
 }
@@ -1,8 +1,12 @@
+use api::api::versions::ApiVersion;
+use api::auth::binding::TokenBinding;
+use api::auth::keyring::kid_hex;
 use api::auth::{sign_token_direct, TokenData};
 use api::config;
+use api::database::{self, Db};
 use api::models::TierType;
 use ed25519_dalek::SigningKey;
-use sqlx::postgres::PgPoolOptions;
+use sqlx::pool::PoolOptions;
 use std::env;
 use uuid::Uuid;
 
@@ -33,18 +37,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let key_name = &args[2];
 
     // Connect to database
-    let pool = PgPoolOptions::new()
+    let pool = PoolOptions::<Db>::new()
         .max_connections(5)
-        .connect(&settings.database_url)
+        .connect_with(database::connect_options(&settings.database_url)?)
         .await?;
 
     // Verify organization exists and get tier
-    let result: Option<(Uuid, String, bool)> = sqlx::query_as(
-        "SELECT id, tier, is_active FROM organizations WHERE id = $1",
-    )
-    .bind(org_id)
-    .fetch_optional(&pool)
-    .await?;
+    let result: Option<(Uuid, String, bool)> =
+        sqlx::query_as("SELECT id, tier, is_active FROM organizations WHERE id = $1")
+            .bind(org_id)
+            .fetch_optional(&pool)
+            .await?;
 
     let (org_id, tier_str, is_active) = match result {
         Some(org) => org,
@@ -77,43 +80,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Generate key_id (UUIDv7 - time-ordered)
     let key_id = Uuid::now_v7();
 
+    // Decode private key
+    let private_key_bytes = hex::decode(&private_key_hex)?;
+
+    // Create signing key
+    let signing_key = SigningKey::from_bytes(&private_key_bytes.try_into().unwrap());
+
+    // New keys are pinned to the latest API version
+    let api_version = ApiVersion::LATEST.as_str();
+
     // Insert API key record
     sqlx::query(
-        "INSERT INTO api_keys (organization_id, key_id, name, is_active, created_at)
-         VALUES ($1, $2, $3, $4, NOW())",
+        "INSERT INTO api_keys (organization_id, key_id, name, is_active, created_at, signing_kid, api_version)
+         VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, $5, $6)",
     )
     .bind(org_id)
     .bind(key_id)
     .bind(key_name)
     .bind(true)
+    .bind(kid_hex(&signing_key.verifying_key()))
+    .bind(api_version)
     .execute(&pool)
     .await?;
 
     println!("✅ API key record created in database");
 
-    // Determine limits based on tier
-    let (max_tokens, monthly_quota) = match tier {
-        TierType::Free => (128, 20_000),
-        TierType::Pro => (8192, 100_000),
-        TierType::Scale => (8192, 2_000_000),
-    };
-
-    // Decode private key
-    let private_key_bytes = hex::decode(&private_key_hex)?;
-
-    // Create signing key
-    let signing_key = SigningKey::from_bytes(&private_key_bytes.try_into().unwrap());
-
+    let limits = tier.limits();
     let token_data = TokenData {
         org_id,
         key_id,
         tier,
-        max_tokens,
-        monthly_quota,
+        max_tokens: limits.max_tokens as i32,
+        monthly_quota: limits.monthly_quota,
+        require_signing: false,
+        max_concurrency: None,
+        scopes: None,
+        models: None,
+        api_version: Some(api_version.to_string()),
+        strict_validation: false,
+        generation: 0,
     };
 
     // Sign token
-    let token = sign_token_direct(&token_data, &signing_key)?;
+    let token = sign_token_direct(&token_data, &signing_key, &TokenBinding::from_settings())?;
 
     // Add prefix
     let full_token = format!("{}{}", settings.api_key_prefix, token);
@@ -1,10 +1,25 @@
+//! Mint a token of any tier for an existing organization (development).
+//!
+//! Tokens are only accepted while their key has an active `api_keys` row
+//! (see `TokenValidator::check_revocation`), so the row is recorded too.
+//! Use `create_api_key` for keys of the organization's own tier.
+
+use api::auth::binding::TokenBinding;
+use api::auth::keyring::kid_hex;
 use api::auth::{sign_token_direct, TokenData};
+use api::config;
+use api::database::{self, Db};
 use api::models::TierType;
 use ed25519_dalek::SigningKey;
+use sqlx::pool::PoolOptions;
 use std::env;
 use uuid::Uuid;
 
-fn main() {
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenvy::dotenv().ok();
+    let settings = config::get_settings();
+
     // Get private key from environment or args
     let private_key_hex = env::var("TOKEN_PRIVATE_KEY").unwrap_or_else(|_| {
         eprintln!("Error: TOKEN_PRIVATE_KEY environment variable not set");
@@ -25,7 +40,8 @@ fn main() {
             "         cargo run --bin create_token 018d1234-5678-7abc-9def-0123456789ab free 018d1234-5678-7abc-9def-0123456789ab"
         );
         eprintln!("\nTiers: free, pro, scale");
-        eprintln!("\nIf key_id is not provided, a new UUIDv7 will be generated.");
+        eprintln!("\nThe organization must exist; the key is recorded in api_keys.");
+        eprintln!("If key_id is not provided, a new UUIDv7 will be generated.");
         std::process::exit(1);
     }
 
@@ -60,6 +76,21 @@ fn main() {
         }
     };
 
+    // Connect to database
+    let pool = PoolOptions::<Db>::new()
+        .max_connections(1)
+        .connect_with(database::connect_options(&settings.database_url)?)
+        .await?;
+
+    let org_exists: Option<Uuid> = sqlx::query_scalar("SELECT id FROM organizations WHERE id = $1")
+        .bind(org_id)
+        .fetch_optional(&pool)
+        .await?;
+    if org_exists.is_none() {
+        eprintln!("Error: Organization {} not found", org_id);
+        std::process::exit(1);
+    }
+
     // Decode private key
     let private_key_bytes = hex::decode(&private_key_hex).unwrap_or_else(|_| {
         eprintln!("Error: Invalid private key hex");
@@ -72,23 +103,37 @@ fn main() {
         std::process::exit(1);
     }));
 
-    // Determine limits based on tier
-    let (max_tokens, monthly_quota) = match tier_value {
-        TierType::Free => (128, 20_000),
-        TierType::Pro => (8192, 100_000),
-        TierType::Scale => (8192, 2_000_000),
-    };
+    // Record the key, or the token is rejected as revoked
+    sqlx::query(
+        "INSERT INTO api_keys (organization_id, key_id, name, is_active, created_at, signing_kid)
+         VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP, $5)",
+    )
+    .bind(org_id)
+    .bind(key_id)
+    .bind(format!("{} token (create_token)", tier_name))
+    .bind(true)
+    .bind(kid_hex(&signing_key.verifying_key()))
+    .execute(&pool)
+    .await?;
 
+    let limits = tier_value.limits();
     let token_data = TokenData {
         org_id,
         key_id,
         tier: tier_value,
-        max_tokens,
-        monthly_quota,
+        max_tokens: limits.max_tokens as i32,
+        monthly_quota: limits.monthly_quota,
+        require_signing: false,
+        max_concurrency: None,
+        scopes: None,
+        models: None,
+        api_version: None,
+        strict_validation: false,
+        generation: 0,
     };
 
     // Sign token with Ed25519 (compact direct signing)
-    let token = sign_token_direct(&token_data, &signing_key).unwrap();
+    let token = sign_token_direct(&token_data, &signing_key, &TokenBinding::from_settings())?;
 
     println!("\n=== Ed25519-Signed Token Generated ===\n");
     println!("Org ID: {}", org_id);
@@ -102,4 +147,6 @@ fn main() {
     println!("  -H \"Authorization: Bearer {}\" \\", token);
     println!("  -d '{{\"text\": \"Hello world\"}}'");
     println!();
+
+    Ok(())
 }
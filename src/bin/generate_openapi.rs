@@ -1,11 +1,9 @@
-use api::api::ApiDoc;
 use std::fs;
 use std::path::Path;
-use utoipa::OpenApi;
 
 fn main() -> anyhow::Result<()> {
     // Generate OpenAPI spec
-    let openapi = ApiDoc::openapi();
+    let openapi = api::api::openapi();
 
     // Serialize to JSON
     let json = openapi.to_pretty_json()?;
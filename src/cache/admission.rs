@@ -0,0 +1,227 @@
+//! Admission policy of the L1 cache (`L1_ADMISSION_POLICY`)
+//!
+//! With `always` every embedding computed or read from Redis goes into L1,
+//! evicting the least recently used entry, so a stream of one-off texts
+//! (queries that never repeat, strings with ids in them) can flush out
+//! entries that are hit all the time. With `tinylfu` a count-min sketch of
+//! how often each cache key was looked up recently decides: a new entry is
+//! only admitted when its key was looked up more often than the entry it
+//! would evict. Counters saturate at 15 and are halved every
+//! `SAMPLE_FACTOR` × width lookups, so old popularity fades.
+
+use seahash::SeaHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+
+use super::lru::LruCache;
+use crate::config;
+
+/// Rows of the count-min sketch (independent counters per key)
+const DEPTH: usize = 4;
+
+/// Highest count (counters are 4-bit in TinyLFU)
+const MAX_COUNT: u8 = 15;
+
+/// Lookups between agings, per counter in a row
+const SAMPLE_FACTOR: usize = 10;
+
+/// Smallest row width
+const MIN_WIDTH: usize = 16;
+
+/// Which entries go into the L1 cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdmissionPolicy {
+    /// Every entry, evicting the least recently used
+    Always,
+    /// Entries looked up more often than the one they'd evict
+    TinyLfu,
+}
+
+impl AdmissionPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "always" => Some(AdmissionPolicy::Always),
+            "tinylfu" => Some(AdmissionPolicy::TinyLfu),
+            _ => None,
+        }
+    }
+
+    /// `L1_ADMISSION_POLICY` (`Settings::validate` rejects unknown policies)
+    pub fn from_settings() -> Self {
+        Self::parse(&config::get_settings().l1_admission_policy).unwrap_or(AdmissionPolicy::Always)
+    }
+}
+
+/// Count-min sketch of recent lookups. Counters are atomics so lookups can
+/// be recorded under the L1 read lock; concurrent increments and agings may
+/// lose a count, which only makes the estimate a little rougher.
+pub struct FrequencySketch {
+    counters: Vec<AtomicU8>,
+    /// Row width - 1 (the width is a power of two)
+    mask: usize,
+    additions: AtomicUsize,
+    sample_size: usize,
+}
+
+impl FrequencySketch {
+    /// A sketch sized for a cache of `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        let width = capacity.max(MIN_WIDTH).next_power_of_two();
+        FrequencySketch {
+            counters: (0..width * DEPTH).map(|_| AtomicU8::new(0)).collect(),
+            mask: width - 1,
+            additions: AtomicUsize::new(0),
+            sample_size: width * SAMPLE_FACTOR,
+        }
+    }
+
+    /// Count one lookup of the key with this hash
+    pub fn increment(&self, hash: u64) {
+        for index in self.indexes(hash) {
+            let _ = self.counters[index].fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                (n < MAX_COUNT).then_some(n + 1)
+            });
+        }
+        if self.additions.fetch_add(1, Ordering::Relaxed) + 1 >= self.sample_size {
+            self.age();
+        }
+    }
+
+    /// Estimated recent lookups of the key with this hash (never fewer than
+    /// counted since the last aging)
+    pub fn estimate(&self, hash: u64) -> u8 {
+        self.indexes(hash)
+            .map(|index| self.counters[index].load(Ordering::Relaxed))
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Halve every counter
+    fn age(&self) {
+        self.additions
+            .store(self.sample_size / 2, Ordering::Relaxed);
+        for counter in &self.counters {
+            let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| Some(n / 2));
+        }
+    }
+
+    /// One counter per row, from the two halves of the hash
+    fn indexes(&self, hash: u64) -> impl Iterator<Item = usize> + '_ {
+        let (low, high) = (hash as u32 as usize, (hash >> 32) as usize);
+        (0..DEPTH).map(move |row| {
+            let column = low.wrapping_add(row.wrapping_mul(high | 1)) & self.mask;
+            row * (self.mask + 1) + column
+        })
+    }
+}
+
+/// Admission decisions for one L1 cache
+pub struct Admission {
+    /// None under `always`
+    sketch: Option<FrequencySketch>,
+}
+
+impl Admission {
+    pub fn new(policy: AdmissionPolicy, capacity: usize) -> Self {
+        Admission {
+            sketch: match policy {
+                AdmissionPolicy::Always => None,
+                AdmissionPolicy::TinyLfu => Some(FrequencySketch::new(capacity)),
+            },
+        }
+    }
+
+    /// Count a lookup of `key`
+    pub fn record<K: Hash>(&self, key: &K) {
+        if let Some(sketch) = &self.sketch {
+            sketch.increment(hash_key(key));
+        }
+    }
+
+    /// Whether putting `key` in `cache` is worth evicting the entry it would
+    /// replace (always true while the cache has room)
+    pub fn admits<K: Clone + Eq + Hash, V: Clone>(&self, cache: &LruCache<K, V>, key: &K) -> bool {
+        let Some(sketch) = &self.sketch else {
+            return true;
+        };
+        let Some(victim) = cache.eviction_candidate(key) else {
+            return true;
+        };
+        sketch.estimate(hash_key(key)) > sketch.estimate(hash_key(victim))
+    }
+}
+
+fn hash_key<K: Hash>(key: &K) -> u64 {
+    let mut hasher = SeaHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            AdmissionPolicy::parse("always"),
+            Some(AdmissionPolicy::Always)
+        );
+        assert_eq!(
+            AdmissionPolicy::parse(" TinyLFU "),
+            Some(AdmissionPolicy::TinyLfu)
+        );
+        assert_eq!(AdmissionPolicy::parse("lfu"), None);
+    }
+
+    #[test]
+    fn test_sketch_counts_saturate_and_age() {
+        let sketch = FrequencySketch::new(64);
+        for _ in 0..20 {
+            sketch.increment(42);
+        }
+        assert_eq!(sketch.estimate(42), MAX_COUNT);
+        assert_eq!(sketch.estimate(7), 0);
+
+        // Enough other lookups to trigger an aging halve the count
+        for hash in 0..sketch.sample_size as u64 {
+            sketch.increment(1_000_000 + hash);
+        }
+        assert!(sketch.estimate(42) <= MAX_COUNT / 2);
+    }
+
+    #[test]
+    fn test_tinylfu_keeps_hot_entries() {
+        let mut cache = LruCache::new(2);
+        // A roomy sketch, so the counts don't depend on collisions
+        let admission = Admission::new(AdmissionPolicy::TinyLfu, 1024);
+        let lookup = |cache: &mut LruCache<String, u32>, key: &str| {
+            let key = key.to_string();
+            admission.record(&key);
+            if cache.get(&key).is_none() && admission.admits(cache, &key) {
+                cache.put(key, 0);
+            }
+        };
+
+        for _ in 0..3 {
+            lookup(&mut cache, "hot-a");
+            lookup(&mut cache, "hot-b");
+        }
+        // One-off keys don't displace them
+        for i in 0..100 {
+            lookup(&mut cache, &format!("once-{}", i));
+        }
+        assert!(cache.get(&"hot-a".to_string()).is_some());
+        assert!(cache.get(&"hot-b".to_string()).is_some());
+
+        // A key that becomes hotter than the LRU entry gets in
+        for _ in 0..5 {
+            lookup(&mut cache, "new-hot");
+        }
+        assert!(cache.get(&"new-hot".to_string()).is_some());
+
+        // Always admits, whatever was looked up
+        let always = Admission::new(AdmissionPolicy::Always, 2);
+        assert!(always.admits(&cache, &"once-0".to_string()));
+    }
+}
@@ -68,7 +68,26 @@ impl<K: Clone + Eq + Hash, V: Clone> LruCache<K, V> {
         }
     }
 
-    #[allow(dead_code)]
+    /// Drop an entry; returns whether there was one
+    pub fn remove(&mut self, key: &K) -> bool {
+        let Some(node_ptr) = self.map.remove(key) else {
+            return false;
+        };
+        unsafe {
+            self.detach(node_ptr);
+            drop(Box::from_raw(node_ptr.as_ptr()));
+        }
+        true
+    }
+
+    /// Entry `put(incoming, ..)` would evict, if any
+    pub fn eviction_candidate(&self, incoming: &K) -> Option<&K> {
+        if self.map.len() < self.capacity || self.map.contains_key(incoming) {
+            return None;
+        }
+        unsafe { (*self.tail.get()).map(|tail| &(*tail.as_ptr()).key) }
+    }
+
     pub fn len(&self) -> usize {
         self.map.len()
     }
@@ -78,7 +97,6 @@ impl<K: Clone + Eq + Hash, V: Clone> LruCache<K, V> {
         self.map.is_empty()
     }
 
-    #[allow(dead_code)]
     pub fn capacity(&self) -> usize {
         self.capacity
     }
@@ -133,13 +151,80 @@ impl<K: Clone + Eq + Hash, V: Clone> LruCache<K, V> {
     }
 }
 
+impl<K, V> LruCache<K, V> {
+    /// Drop every entry
+    pub fn clear(&mut self) {
+        for (_, node_ptr) in self.map.drain() {
+            unsafe {
+                drop(Box::from_raw(node_ptr.as_ptr()));
+            }
+        }
+        *self.head.get_mut() = None;
+        *self.tail.get_mut() = None;
+    }
+}
+
 impl<K, V> Drop for LruCache<K, V> {
     fn drop(&mut self) {
-        // Clear the map which owns all the nodes
-        self.map.clear();
-        // The UnsafeCells will be dropped automatically
+        // The map's pointers own the nodes
+        self.clear();
     }
 }
 
 unsafe impl<K: Send, V: Send> Send for LruCache<K, V> {}
 unsafe impl<K: Sync, V: Sync> Sync for LruCache<K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clear() {
+        let mut cache = LruCache::new(2);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&"a"), None);
+
+        // Usable again, evicting in order
+        cache.put("c", 3);
+        cache.put("d", 4);
+        cache.put("e", 5);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"c"), None);
+        assert_eq!(cache.get(&"e"), Some(5));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut cache = LruCache::new(3);
+        cache.put("a", 1);
+        cache.put("b", 2);
+        cache.put("c", 3);
+        assert!(cache.remove(&"b"));
+        assert!(!cache.remove(&"b"));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"b"), None);
+
+        // The list stays linked: a is evicted first once full
+        cache.put("d", 4);
+        cache.get(&"c");
+        cache.put("e", 5);
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"c"), Some(3));
+    }
+
+    #[test]
+    fn test_eviction_candidate() {
+        let mut cache = LruCache::new(2);
+        cache.put("a", 1);
+        assert_eq!(cache.eviction_candidate(&"b"), None);
+        cache.put("b", 2);
+        assert_eq!(cache.eviction_candidate(&"c"), Some(&"a"));
+        cache.get(&"a");
+        assert_eq!(cache.eviction_candidate(&"c"), Some(&"b"));
+        // Updating an entry evicts nothing
+        assert_eq!(cache.eviction_candidate(&"a"), None);
+    }
+}
@@ -1,16 +1,37 @@
 use anyhow::Result;
-use once_cell::sync::OnceCell;
 use parking_lot::RwLock;
-use redis::{aio::ConnectionManager, AsyncCommands};
+use redis::AsyncCommands;
 use seahash::hash;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use uuid::Uuid;
 
 use crate::config;
+use crate::inference::catalog;
+use crate::inference::tokenizer::Tokenizer;
+use crate::redis_util::RedisConnection;
 
+pub mod admission;
 pub mod lru;
+pub mod policy;
+pub mod singleflight;
+pub mod writer;
+use crate::models::CachePolicy;
+use crate::monitoring::CACHE_L1_ADMISSION_REJECTIONS;
+use admission::{Admission, AdmissionPolicy};
 use lru::LruCache;
+use writer::{L2Write, L2Writer};
+
+/// Keys of every cached embedding, whatever their key version
+pub const KEY_PATTERN: &str = "embed:*";
+
+/// Keys requested per SCAN call when clearing L2
+const SCAN_BATCH: usize = 500;
+
+/// Key written by `check_writable`
+const PROBE_KEY: &str = "cache:probe";
+const PROBE_KEY_TTL_SECS: u64 = 60;
 
 /// Cached embedding with metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,43 +41,98 @@ pub struct CachedEmbedding {
     pub model: String,
 }
 
+/// L1 size and hit/miss counts of `get` since startup
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub l1_size: usize,
+    pub l1_maxsize: usize,
+    pub l1_hits: u64,
+    pub l2_hits: u64,
+    pub misses: u64,
+}
+
+/// Cache tier that served a hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheLevel {
+    /// In-process LRU
+    L1,
+    /// Redis
+    L2,
+}
+
+impl CacheLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CacheLevel::L1 => "l1",
+            CacheLevel::L2 => "l2",
+        }
+    }
+}
+
 pub struct EmbeddingCache {
     l1_cache: Arc<RwLock<LruCache<String, CachedEmbedding>>>,
-    redis_client: ConnectionManager,
+    l1_admission: Admission,
+    redis_client: RedisConnection,
+    l2_writer: L2Writer,
     l2_cache_ttl: u64,
+    model_name: String,
+    lowercase: bool,
+    l1_hits: AtomicU64,
+    l2_hits: AtomicU64,
+    misses: AtomicU64,
 }
 
-static CACHE: OnceCell<EmbeddingCache> = OnceCell::new();
-
 impl EmbeddingCache {
-    pub async fn new() -> Result<Self> {
+    /// An empty L1 cache in front of `redis_client`
+    pub fn new(redis_client: RedisConnection) -> Self {
         let settings = config::get_settings();
 
         // Initialize L1 cache
         let l1_cache = Arc::new(RwLock::new(LruCache::new(settings.l1_cache_size)));
 
-        // Connect to Redis
-        let client = redis::Client::open(settings.redis_url.as_str())?;
-        let redis_client = ConnectionManager::new(client).await?;
-
-        Ok(EmbeddingCache {
+        EmbeddingCache {
             l1_cache,
+            l1_admission: Admission::new(AdmissionPolicy::from_settings(), settings.l1_cache_size),
+            l2_writer: L2Writer::redis(redis_client.clone(), settings.l2_write_queue_size),
             redis_client,
             l2_cache_ttl: settings.l2_cache_ttl,
-        })
+            // A new revision of the same model never shares entries
+            model_name: catalog::with_revision(
+                &settings.model_name,
+                settings.model_revision.as_deref(),
+            ),
+            lowercase: Tokenizer::load_do_lower_case(std::path::Path::new(&settings.model_path)),
+            l1_hits: AtomicU64::new(0),
+            l2_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
     }
 
-    pub async fn get(&self, text: &str) -> Option<CachedEmbedding> {
-        let cache_key = self.get_cache_key(text);
+    /// Cached embedding and the tier it came from, under the requesting
+    /// organization's cache policy
+    pub async fn get(
+        &self,
+        text: &str,
+        org_id: Uuid,
+        policy: CachePolicy,
+    ) -> Option<(CachedEmbedding, CacheLevel)> {
+        let cache_key = self.key(text, org_id, policy);
+        self.l1_admission.record(&cache_key);
 
         // Check L1 cache
         {
             let cache = self.l1_cache.read();
             if let Some(cached) = cache.get(&cache_key) {
-                return Some(cached.clone());
+                self.l1_hits.fetch_add(1, Ordering::Relaxed);
+                return Some((cached.clone(), CacheLevel::L1));
             }
         }
 
+        if policy == CachePolicy::Disabled {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+
         // Check L2 cache (Redis)
         if let Ok(data) = self
             .redis_client
@@ -66,46 +142,142 @@ impl EmbeddingCache {
         {
             if let Some(cached) = Self::deserialize_cached_embedding(&data) {
                 // Populate L1 cache
-                let mut cache = self.l1_cache.write();
-                cache.put(cache_key, cached.clone());
-                return Some(cached);
+                self.put_l1(cache_key, cached.clone());
+                self.l2_hits.fetch_add(1, Ordering::Relaxed);
+                return Some((cached, CacheLevel::L2));
             }
         }
 
+        self.misses.fetch_add(1, Ordering::Relaxed);
         None
     }
 
-    pub async fn set(&self, text: &str, cached_embedding: CachedEmbedding) {
-        let cache_key = self.get_cache_key(text);
+    pub async fn set(
+        &self,
+        text: &str,
+        org_id: Uuid,
+        policy: CachePolicy,
+        cached_embedding: CachedEmbedding,
+    ) {
+        let cache_key = self.key(text, org_id, policy);
 
         // Set in L1 cache
-        {
-            let mut cache = self.l1_cache.write();
-            cache.put(cache_key.clone(), cached_embedding.clone());
+        self.put_l1(cache_key.clone(), cached_embedding.clone());
+
+        if policy == CachePolicy::Disabled {
+            return;
         }
 
-        // Set in L2 cache (async, non-blocking)
-        let serialized = Self::serialize_cached_embedding(&cached_embedding);
-        let ttl = self.l2_cache_ttl;
-        let mut client = self.redis_client.clone();
-        tokio::spawn(async move {
-            let _ = client.set_ex::<_, _, ()>(&cache_key, serialized, ttl).await;
+        // Set in L2 cache (queued for the writer; dropped if it's backed up)
+        self.l2_writer.submit(L2Write {
+            key: cache_key,
+            value: Self::serialize_cached_embedding(&cached_embedding),
+            ttl: self.l2_cache_ttl,
         });
     }
 
-    #[allow(dead_code)]
-    pub fn get_stats(&self) -> HashMap<String, usize> {
+    /// Put an entry in L1 unless the admission policy keeps it out
+    fn put_l1(&self, key: String, cached: CachedEmbedding) {
+        let mut cache = self.l1_cache.write();
+        if self.l1_admission.admits(&cache, &key) {
+            cache.put(key, cached);
+        } else {
+            CACHE_L1_ADMISSION_REJECTIONS.inc();
+        }
+    }
+
+    /// Write and read back a short-lived key (used by the dependency
+    /// probes). Unlike PING this fails when Redis can't store entries, e.g.
+    /// a read-only replica or maxmemory with `noeviction`.
+    pub async fn check_writable(&self) -> Result<()> {
+        let mut client = self.redis_client.clone();
+        let value = uuid::Uuid::now_v7().to_string();
+        client
+            .set_ex::<_, _, ()>(PROBE_KEY, &value, PROBE_KEY_TTL_SECS)
+            .await?;
+        let stored: Option<String> = client.get(PROBE_KEY).await?;
+        // Another instance may have written its own value in between
+        if stored.is_none() {
+            anyhow::bail!("probe key was not stored");
+        }
+        Ok(())
+    }
+
+    pub fn get_stats(&self) -> CacheStats {
         let cache = self.l1_cache.read();
-        let mut stats = HashMap::new();
-        stats.insert("l1_size".to_string(), cache.len());
-        stats.insert("l1_maxsize".to_string(), cache.capacity());
-        stats
+        CacheStats {
+            l1_size: cache.len(),
+            l1_maxsize: cache.capacity(),
+            l1_hits: self.l1_hits.load(Ordering::Relaxed),
+            l2_hits: self.l2_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
     }
 
-    fn get_cache_key(&self, text: &str) -> String {
-        let normalized = text.trim().to_lowercase();
-        let hash_value = hash(normalized.as_bytes());
-        format!("embed:v2:{:x}", hash_value)
+    /// Empty this instance's L1 cache; returns the number of entries dropped
+    pub fn clear_l1(&self) -> usize {
+        let mut cache = self.l1_cache.write();
+        let cleared = cache.len();
+        cache.clear();
+        cleared
+    }
+
+    /// Delete entries from this instance's L1 cache and from Redis; returns
+    /// the number of Redis keys removed
+    pub async fn remove(&self, keys: &[String]) -> Result<u64> {
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        {
+            let mut cache = self.l1_cache.write();
+            for key in keys {
+                cache.remove(key);
+            }
+        }
+        let removed = redis::cmd("UNLINK")
+            .arg(keys)
+            .query_async::<u64>(&mut self.redis_client.clone())
+            .await?;
+        Ok(removed)
+    }
+
+    /// Delete every embedding from Redis (all instances share it); returns
+    /// the number of keys removed. Writes still queued for the L2 writer
+    /// may land afterwards.
+    pub async fn clear_l2(&self) -> Result<u64> {
+        let mut conn = self.redis_client.clone();
+        let mut removed = 0;
+        let mut cursor: u64 = 0;
+        loop {
+            let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(KEY_PATTERN)
+                .arg("COUNT")
+                .arg(SCAN_BATCH)
+                .query_async(&mut conn)
+                .await?;
+            if !keys.is_empty() {
+                removed += redis::cmd("UNLINK")
+                    .arg(&keys)
+                    .query_async::<u64>(&mut conn)
+                    .await?;
+            }
+            cursor = next;
+            if cursor == 0 {
+                return Ok(removed);
+            }
+        }
+    }
+
+    /// Content hash under this cache's model and normalization settings
+    pub fn content_hash(&self, text: &str) -> String {
+        content_hash(text, &self.model_name, self.lowercase)
+    }
+
+    /// Cache key of a text for an organization under `policy`
+    pub fn key(&self, text: &str, org_id: Uuid, policy: CachePolicy) -> String {
+        cache_key(&self.content_hash(text), org_id, policy)
     }
 
     fn serialize_cached_embedding(cached: &CachedEmbedding) -> Vec<u8> {
@@ -118,17 +290,173 @@ impl EmbeddingCache {
     }
 }
 
-pub async fn init_cache() -> Result<()> {
-    // If already initialized, return early
-    if CACHE.get().is_some() {
-        return Ok(());
+/// Cache key for a content hash. Shared keys are the same for every
+/// organization; private (and disabled) keys hash the content hash together
+/// with the organization id, so neither the entry nor the hash of the text
+/// can be matched against another tenant's.
+pub fn cache_key(content_hash: &str, org_id: Uuid, policy: CachePolicy) -> String {
+    match policy {
+        CachePolicy::Shared => format!("embed:v3:n{}:{}", NORMALIZATION_VERSION, content_hash),
+        CachePolicy::Private | CachePolicy::Disabled => {
+            let mut material = Vec::with_capacity(16 + content_hash.len());
+            material.extend_from_slice(org_id.as_bytes());
+            material.extend_from_slice(content_hash.as_bytes());
+            format!(
+                "embed:v3:n{}:p:{:016x}",
+                NORMALIZATION_VERSION,
+                hash(&material)
+            )
+        }
+    }
+}
+
+/// Version of the text normalization rules below. Bump it whenever
+/// `normalize_text` changes, so old cache entries and hashes are not reused.
+pub const NORMALIZATION_VERSION: u8 = 1;
+
+/// Normalize text the same way the tokenizer sees it: whitespace is trimmed
+/// and collapsed (the tokenizer splits on whitespace), and case is folded only
+/// when the tokenizer lowercases (`do_lower_case`). Cased models keep "Apple"
+/// and "apple" apart.
+pub fn normalize_text(text: &str, lowercase: bool) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if lowercase {
+        collapsed.to_lowercase()
+    } else {
+        collapsed
     }
+}
+
+/// Deterministic content hash for a text under a given model.
+///
+/// Hex seahash of the normalization version, the model name and the
+/// normalized text. This is the cache key material and is returned to clients
+/// as `content_hash`, so it must stay stable across requests and instances.
+/// Changing it invalidates all caches.
+pub fn content_hash(text: &str, model: &str, lowercase: bool) -> String {
+    let normalized = normalize_text(text, lowercase);
 
-    let cache = EmbeddingCache::new().await?;
-    CACHE.set(cache).ok(); // Ignore error if already set
-    Ok(())
+    let mut material = Vec::with_capacity(model.len() + 2 + normalized.len());
+    material.push(NORMALIZATION_VERSION);
+    material.extend_from_slice(model.as_bytes());
+    material.push(0);
+    material.extend_from_slice(normalized.as_bytes());
+
+    format!("{:016x}", hash(&material))
 }
 
+/// The cache of the process-wide `AppState` (prefer `AppState::cache`
+/// where the state is at hand)
 pub fn get_cache() -> &'static EmbeddingCache {
-    CACHE.get().expect("Cache not initialized")
+    &crate::state::global().cache
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        let a = content_hash("Hello world", "all-MiniLM-L6-v2", true);
+        assert_eq!(a, content_hash("Hello world", "all-MiniLM-L6-v2", true));
+        assert_eq!(a.len(), 16);
+    }
+
+    #[test]
+    fn test_content_hash_uses_normalized_text() {
+        assert_eq!(
+            content_hash("  Hello   world\n", "model", true),
+            content_hash("hello world", "model", true)
+        );
+        assert_ne!(
+            content_hash("hello world", "model", true),
+            content_hash("hello there", "model", true)
+        );
+    }
+
+    #[test]
+    fn test_content_hash_depends_on_model() {
+        assert_ne!(
+            content_hash("hello", "model-a", true),
+            content_hash("hello", "model-b", true)
+        );
+
+        // A refreshed model never reuses entries; no revision keeps old ones
+        let name = "org/model";
+        assert_ne!(
+            content_hash(
+                "hello",
+                &catalog::with_revision(name, Some("2024-01")),
+                true
+            ),
+            content_hash(
+                "hello",
+                &catalog::with_revision(name, Some("2024-06")),
+                true
+            )
+        );
+        assert_eq!(
+            content_hash("hello", &catalog::with_revision(name, None), true),
+            content_hash("hello", name, true)
+        );
+    }
+
+    #[test]
+    fn test_private_keys_are_salted_per_org() {
+        let hash = content_hash("hello", "model", true);
+        let (a, b) = (Uuid::now_v7(), Uuid::now_v7());
+
+        // Shared: one entry for everyone
+        assert_eq!(
+            cache_key(&hash, a, CachePolicy::Shared),
+            cache_key(&hash, b, CachePolicy::Shared)
+        );
+
+        // Private: per org, stable, and never the shared key
+        let private = cache_key(&hash, a, CachePolicy::Private);
+        assert_eq!(private, cache_key(&hash, a, CachePolicy::Private));
+        assert_ne!(private, cache_key(&hash, b, CachePolicy::Private));
+        assert_ne!(private, cache_key(&hash, a, CachePolicy::Shared));
+        assert!(!private.contains(&hash));
+        assert_eq!(private, cache_key(&hash, a, CachePolicy::Disabled));
+    }
+
+    #[test]
+    fn test_cased_tokenizer_keeps_case_distinct() {
+        // Cased model (do_lower_case=false): distinct cache entries
+        assert_eq!(normalize_text(" Apple ", false), "Apple");
+        assert_ne!(
+            content_hash("Apple", "cased-model", false),
+            content_hash("apple", "cased-model", false)
+        );
+
+        // Uncased model: same entry
+        assert_eq!(
+            content_hash("Apple", "uncased-model", true),
+            content_hash("apple", "uncased-model", true)
+        );
+    }
+
+    #[test]
+    fn test_cased_config_is_read_from_model_dir() {
+        let dir = std::env::temp_dir().join(format!("smally-cased-{}", uuid::Uuid::now_v7()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Missing config defaults to lowercasing
+        assert!(Tokenizer::load_do_lower_case(&dir));
+
+        std::fs::write(
+            dir.join("tokenizer_config.json"),
+            r#"{"do_lower_case": false}"#,
+        )
+        .unwrap();
+        let lowercase = Tokenizer::load_do_lower_case(&dir);
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert!(!lowercase);
+        assert_ne!(
+            content_hash("Apple", "m", lowercase),
+            content_hash("apple", "m", lowercase)
+        );
+    }
 }
@@ -0,0 +1,54 @@
+//! Per-organization cache policy lookup
+//!
+//! The policy is read on every embed request, so it's cached in process for
+//! a short time. Changing it invalidates this instance's entry; other
+//! instances pick the change up within `POLICY_CACHE_TTL_SECS`.
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::database;
+use crate::models::CachePolicy;
+
+/// How long a policy lookup is trusted before hitting the database again
+const POLICY_CACHE_TTL_SECS: u64 = 30;
+
+/// org_id -> (policy, checked_at)
+static POLICY_CACHE: Lazy<DashMap<Uuid, (CachePolicy, Instant)>> = Lazy::new(DashMap::new);
+
+/// Cache policy of the organization. Lookup failures fall back to
+/// `Disabled`, so an outage never puts a private org's keys in Redis.
+pub async fn policy_for(org_id: Uuid) -> CachePolicy {
+    if let Some(entry) = POLICY_CACHE.get(&org_id) {
+        let (policy, checked_at) = *entry;
+        if checked_at.elapsed().as_secs() < POLICY_CACHE_TTL_SECS {
+            return policy;
+        }
+    }
+
+    let policy = sqlx::query_scalar::<_, CachePolicy>(
+        "SELECT cache_policy FROM organizations WHERE id = $1",
+    )
+    .bind(org_id)
+    .fetch_optional(database::get_db())
+    .await;
+
+    match policy {
+        Ok(policy) => {
+            let policy = policy.unwrap_or_default();
+            POLICY_CACHE.insert(org_id, (policy, Instant::now()));
+            policy
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load cache policy of {}: {}", org_id, e);
+            CachePolicy::Disabled
+        }
+    }
+}
+
+/// Forget a cached policy (call after changing it)
+pub fn invalidate(org_id: Uuid) {
+    POLICY_CACHE.remove(&org_id);
+}
@@ -0,0 +1,182 @@
+//! Coalescing of identical in-flight computations
+//!
+//! On a cache miss storm (e.g. a customer re-embedding their corpus),
+//! concurrent requests for the same text would each run inference, because
+//! the cache is only populated once the first one finishes. `SingleFlight`
+//! lets the first caller for a key compute while later callers for the same
+//! key wait for its result. The entry is removed when the computation
+//! finishes, fails or is cancelled; waiters of a cancelled leader compute
+//! on their own.
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::future::Future;
+use tokio::sync::watch;
+
+/// In-flight computations by key
+pub struct SingleFlight<T> {
+    in_flight: DashMap<String, watch::Receiver<Option<T>>>,
+}
+
+impl<T: Clone> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub fn new() -> Self {
+        SingleFlight {
+            in_flight: DashMap::new(),
+        }
+    }
+
+    /// Result of `compute` for `key`, shared with concurrent callers for the
+    /// same key. The flag is true if the result came from another caller's
+    /// computation. Errors are shared too (use a `Result` for `T`).
+    pub async fn run<F, Fut>(&self, key: &str, compute: F) -> (T, bool)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = T>,
+    {
+        let tx = match self.in_flight.entry(key.to_string()) {
+            Entry::Occupied(entry) => {
+                let mut rx = entry.get().clone();
+                drop(entry);
+                // Err: the leader was cancelled before finishing
+                if let Ok(value) = rx.wait_for(Option::is_some).await {
+                    return (value.clone().expect("waited for a value"), true);
+                }
+                return (compute().await, false);
+            }
+            Entry::Vacant(entry) => {
+                let (tx, rx) = watch::channel(None);
+                entry.insert(rx);
+                tx
+            }
+        };
+
+        let _guard = Remove {
+            in_flight: &self.in_flight,
+            key,
+        };
+        let value = compute().await;
+        tx.send_replace(Some(value.clone()));
+        (value, false)
+    }
+
+    /// Keys currently being computed
+    pub fn len(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.in_flight.is_empty()
+    }
+}
+
+/// Removes the leader's entry when it finishes or is dropped mid-flight
+struct Remove<'a, T> {
+    in_flight: &'a DashMap<String, watch::Receiver<Option<T>>>,
+    key: &'a str,
+}
+
+impl<T> Drop for Remove<'_, T> {
+    fn drop(&mut self) {
+        self.in_flight.remove(self.key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_concurrent_identical_requests_run_once() {
+        let flight = Arc::new(SingleFlight::<Result<Vec<f32>, String>>::new());
+        let inferences = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..50)
+            .map(|_| {
+                let (flight, inferences) = (flight.clone(), inferences.clone());
+                tokio::spawn(async move {
+                    flight
+                        .run("embed:v3:n1:abc", || async {
+                            inferences.fetch_add(1, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(200)).await;
+                            Ok(vec![0.6, 0.8])
+                        })
+                        .await
+                })
+            })
+            .collect();
+
+        let mut coalesced = 0;
+        for task in tasks {
+            let (result, shared) = task.await.unwrap();
+            assert_eq!(result.unwrap(), vec![0.6, 0.8]);
+            coalesced += shared as usize;
+        }
+
+        assert_eq!(inferences.load(Ordering::SeqCst), 1);
+        assert_eq!(coalesced, 49);
+        assert!(flight.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_shared_and_cleaned_up() {
+        let flight = Arc::new(SingleFlight::<Result<u32, String>>::new());
+
+        let leader = {
+            let flight = flight.clone();
+            tokio::spawn(async move {
+                flight
+                    .run("key", || async {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Err("inference failed".to_string())
+                    })
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let (follower, shared) = flight.run("key", || async { Ok(1) }).await;
+        assert!(shared);
+        assert_eq!(follower, Err("inference failed".to_string()));
+        assert_eq!(leader.await.unwrap().0, Err("inference failed".to_string()));
+
+        // Nothing left behind: the next call computes again
+        assert!(flight.is_empty());
+        assert_eq!(flight.run("key", || async { Ok(2) }).await, (Ok(2), false));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_leader_lets_waiters_compute() {
+        let flight = Arc::new(SingleFlight::<u32>::new());
+
+        let leader = {
+            let flight = flight.clone();
+            tokio::spawn(async move {
+                flight
+                    .run("key", || async {
+                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        1
+                    })
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let follower = {
+            let flight = flight.clone();
+            tokio::spawn(async move { flight.run("key", || async { 2 }).await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        leader.abort();
+
+        assert_eq!(follower.await.unwrap(), (2, false));
+        assert!(flight.is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+//! L2 (Redis) cache writes, off the request path
+//!
+//! Cache misses hand their entry to a bounded queue drained by one writer
+//! task, which sends whatever has queued up as a single pipeline. When Redis
+//! is slow the queue fills and further writes are dropped and counted in
+//! `smally_cache_l2_writes_dropped_total`; the entry is still in L1, and the
+//! next miss on another instance writes it again. Requests never wait on
+//! Redis to store an entry.
+
+use std::future::Future;
+use tokio::sync::mpsc;
+
+use crate::monitoring::{CACHE_L2_WRITES_DROPPED, CACHE_L2_WRITE_QUEUE_DEPTH};
+use crate::redis_util::RedisConnection;
+
+/// Most writes sent in one pipeline
+const MAX_BATCH: usize = 256;
+
+/// One entry to store in Redis
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct L2Write {
+    pub key: String,
+    pub value: Vec<u8>,
+    pub ttl: u64,
+}
+
+/// Handle to the writer task
+#[derive(Clone)]
+pub struct L2Writer {
+    tx: mpsc::Sender<L2Write>,
+}
+
+impl L2Writer {
+    /// Writer storing entries in Redis, queueing up to `capacity` writes
+    pub fn redis(client: RedisConnection, capacity: usize) -> Self {
+        Self::spawn(capacity, move |batch| {
+            let mut client = client.clone();
+            async move {
+                let mut pipe = redis::pipe();
+                for write in &batch {
+                    pipe.set_ex(&write.key, &write.value, write.ttl).ignore();
+                }
+                if let Err(e) = pipe.query_async::<()>(&mut client).await {
+                    tracing::warn!("Failed to write {} L2 cache entries: {}", batch.len(), e);
+                }
+            }
+        })
+    }
+
+    /// Writer handing batches of queued writes to `flush`, one at a time
+    pub fn spawn<F, Fut>(capacity: usize, mut flush: F) -> Self
+    where
+        F: FnMut(Vec<L2Write>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let (tx, mut rx) = mpsc::channel(capacity.max(1));
+        tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(MAX_BATCH);
+            while rx.recv_many(&mut batch, MAX_BATCH).await > 0 {
+                CACHE_L2_WRITE_QUEUE_DEPTH.sub(batch.len() as i64);
+                flush(std::mem::take(&mut batch)).await;
+            }
+        });
+        Self { tx }
+    }
+
+    /// Queue a write; returns false (and counts the drop) if the queue is
+    /// full or the writer is gone
+    pub fn submit(&self, write: L2Write) -> bool {
+        match self.tx.try_send(write) {
+            Ok(()) => {
+                CACHE_L2_WRITE_QUEUE_DEPTH.inc();
+                true
+            }
+            Err(_) => {
+                CACHE_L2_WRITES_DROPPED.inc();
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    fn write(i: usize) -> L2Write {
+        L2Write {
+            key: format!("embed:test:{}", i),
+            value: vec![i as u8],
+            ttl: 60,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batches_queued_writes() {
+        let flushed = Arc::new(Mutex::new(Vec::new()));
+        let sink = flushed.clone();
+        let writer = L2Writer::spawn(16, move |batch| {
+            sink.lock().unwrap().extend(batch);
+            async {}
+        });
+
+        for i in 0..10 {
+            assert!(writer.submit(write(i)));
+        }
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while flushed.lock().unwrap().len() < 10 {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            *flushed.lock().unwrap(),
+            (0..10).map(write).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_full_queue_drops_without_waiting() {
+        // Redis never answers: the writer holds one batch, the queue fills
+        let writer = L2Writer::spawn(8, |_batch| std::future::pending::<()>());
+        let dropped_before = CACHE_L2_WRITES_DROPPED.get();
+
+        let started = Instant::now();
+        let accepted = (0..1000).filter(|&i| writer.submit(write(i))).count();
+        let elapsed = started.elapsed();
+
+        // At most the queue plus the batch the writer took are accepted
+        assert!((8..=8 + MAX_BATCH).contains(&accepted), "{}", accepted);
+        assert!(CACHE_L2_WRITES_DROPPED.get() - dropped_before >= (1000 - accepted) as f64);
+        // Submitting never waits for the stalled writer
+        assert!(elapsed < Duration::from_millis(100), "{:?}", elapsed);
+    }
+}
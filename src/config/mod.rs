@@ -1,7 +1,12 @@
+pub mod provenance;
+
 use once_cell::sync::Lazy;
-use std::env;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::flags::{Flag, FlagMode};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Settings {
     // API Settings
     pub app_name: String,
@@ -12,45 +17,170 @@ pub struct Settings {
     // Server Settings
     pub host: String,
     pub port: u16,
+    /// Externally reachable base URL (used in docs and snippets)
+    pub public_base_url: String,
+    /// Region this instance serves (`any`: not a regional deployment); see
+    /// `residency`
+    pub deployment_region: String,
+    /// Base URL of each other regional deployment, from
+    /// `REGION_BASE_URLS=eu=https://eu.example.com,us=https://us.example.com`
+    pub region_base_urls: BTreeMap<String, String>,
     #[allow(dead_code)]
     pub workers: usize,
 
     // Model Settings
     pub model_name: String,
+    /// Upstream license of the model (SPDX identifier, shown in `/v1/models`)
+    pub model_license: Option<String>,
+    /// Where the model comes from (None = its Hugging Face page)
+    pub model_source_url: Option<String>,
+    /// Revision of the model files (None = unversioned). Appended to the
+    /// model in embed responses and part of the cache key.
+    pub model_revision: Option<String>,
     pub model_path: String,
     pub max_tokens: usize,
     pub embedding_dim: usize,
+    /// Model output holding the token embeddings (None = auto-detect)
+    pub onnx_output_name: Option<String>,
+    /// `auto`, `token` (mean-pool token embeddings) or `pooled` (sentence embedding output)
+    pub onnx_output_mode: String,
+    /// ONNX Runtime intra-op threads (0 = per-architecture default)
+    pub onnx_intra_threads: usize,
+    /// ONNX Runtime inter-op threads (0 = per-architecture default)
+    pub onnx_inter_threads: usize,
+    /// Single-threaded, deterministic ONNX execution (overrides the thread settings)
+    pub deterministic_inference: bool,
+    /// ISO 639-1 codes the model handles well (empty = multilingual, never warn)
+    pub model_languages: Vec<String>,
+    /// Post-processing transform file (None = `transform.bin` in MODEL_PATH, if present)
+    pub embedding_transform_path: Option<String>,
+    /// Inferences queued or running before new ones are shed with a 503 (0 = never shed)
+    pub max_inference_queue: usize,
+    /// Directory of candidate models for `POST /v1/admin/models/compare`
+    /// (one subdirectory per model, laid out like MODEL_PATH)
+    pub candidate_models_dir: String,
 
     // Cache Settings
     pub l1_cache_size: usize,
+    /// Which entries go into the L1 cache: `always` or `tinylfu` (only
+    /// entries looked up more often than the one they'd evict)
+    pub l1_admission_policy: String,
     pub l2_cache_ttl: u64,
+    /// L2 writes queued for Redis before new ones are dropped (kept in L1 only)
+    pub l2_write_queue_size: usize,
     pub redis_url: String,
+    /// Sentinel addresses (`redis://host:26379`); empty = connect to REDIS_URL directly
+    pub redis_sentinels: Vec<String>,
+    /// Master group name monitored by the sentinels
+    pub redis_sentinel_master: String,
     #[allow(dead_code)]
     pub redis_db: i32,
 
     // Database Settings
     pub database_url: String,
+    /// What startup does about migrations: `auto`, `check` or `skip` (see
+    /// `database::migrations`)
+    pub migrations_mode: String,
+    /// Let `auto` run migrations annotated `-- long-running` at startup
+    pub migrations_allow_long_running: bool,
+    /// Append-only file responses are written to before they're billed, so
+    /// a crash loses no usage (None = buffer in memory, see `billing::outbox`)
+    pub usage_outbox_path: Option<String>,
+    /// Seconds between writes of buffered usage to the database
+    pub usage_flush_interval_secs: u64,
+    /// Buffered responses that trigger a write before the next interval
+    pub usage_flush_max_buffer: usize,
 
     // Security Settings
-    #[allow(dead_code)]
     pub secret_key: String,
-    #[allow(dead_code)]
     pub api_key_prefix: String,
+    /// Accept API keys sent without `api_key_prefix` (deprecated, see
+    /// `api::key_prefix`; will default to false)
+    pub allow_unprefixed_tokens: bool,
     pub token_public_key: String,
+    /// Verification keys, newest first (`TOKEN_PUBLIC_KEYS`, else `TOKEN_PUBLIC_KEY`)
+    pub token_public_keys: Vec<String>,
     #[allow(dead_code)]
     pub token_private_key: String,
-    pub jwt_secret: String,
+    /// `iss`/`aud` written into and required on tokens (empty = none)
+    pub token_issuer: String,
+    pub token_audience: String,
+    /// Tokens without `iss`/`aud` are still accepted before this date
+    pub token_legacy_until: Option<chrono::NaiveDate>,
+    /// Longest validity accepted on admin tokens, in days
+    pub admin_token_max_days: i64,
+    /// Session JWT secrets: sessions are signed with the first and verified
+    /// against each in order (`JWT_SECRETS`, else `JWT_SECRET`)
+    pub jwt_secrets: Vec<String>,
+    /// Take the client IP from X-Forwarded-For (only behind a trusted proxy)
+    pub trust_proxy_headers: bool,
+    /// Accept opaque (non-CWT) API keys looked up in the database
+    pub opaque_api_keys: bool,
+    /// Serve the public `POST /v1/demo/embed` (kill switch)
+    pub demo_enabled: bool,
+    /// Demo requests allowed per client IP per minute (per instance)
+    pub demo_per_ip_per_minute: u32,
+    /// Demo requests allowed per day across all instances (0 = unlimited)
+    pub demo_daily_cap: i64,
+    /// Frames a `/v1/embed/ws` session may send per second (0 = unlimited)
+    pub ws_max_frames_per_second: u32,
+    /// Close `/v1/embed/ws` sessions that send nothing for this long
+    pub ws_idle_timeout_secs: u64,
+    /// How long links of shared embeddings (`POST /v1/share`) stay valid
+    pub share_link_ttl_secs: u64,
 
-    // Rate Limiting
-    #[allow(dead_code)]
+    // Feature flags (`FEATURE_<FLAG>=on|off|allowlist`, see `flags`)
+    pub feature_flags: HashMap<Flag, FlagMode>,
+
+    // Rate Limiting (read through `TierType::limits()`)
     pub free_tier_limit: i32,
-    #[allow(dead_code)]
     pub pro_tier_limit: i32,
-    #[allow(dead_code)]
     pub scale_tier_limit: i32,
+    /// Requests per minute per organization (0 = unlimited)
+    pub free_tier_rpm: i32,
+    pub pro_tier_rpm: i32,
+    pub scale_tier_rpm: i32,
+    /// Concurrent embed requests per API key (0 = unlimited)
+    pub free_tier_concurrency: i32,
+    pub pro_tier_concurrency: i32,
+    pub scale_tier_concurrency: i32,
+    /// Organizations a user may own, by the best tier they own (0 = unlimited)
+    pub free_tier_max_organizations: i32,
+    pub pro_tier_max_organizations: i32,
+    pub scale_tier_max_organizations: i32,
+    /// Active API keys per organization (0 = unlimited)
+    pub free_tier_max_api_keys: i32,
+    pub pro_tier_max_api_keys: i32,
+    pub scale_tier_max_api_keys: i32,
+    /// Organizations a user, and API keys an organization, may create per
+    /// hour (0 = unlimited)
+    pub free_tier_creations_per_hour: i32,
+    pub pro_tier_creations_per_hour: i32,
+    pub scale_tier_creations_per_hour: i32,
+    /// Dry-run embed requests per minute per API key
+    pub dry_run_rpm: u32,
+    /// Maximum input text length in characters
+    pub max_text_chars: usize,
+
+    // Pricing (cents per million tokens, used for monthly statements)
+    pub free_price_per_million_tokens: i32,
+    pub pro_price_per_million_tokens: i32,
+    pub scale_price_per_million_tokens: i32,
+
+    // Email
+    /// `log` (write emails to the log) or `smtp`
+    pub email_provider: String,
+    /// Sender address, e.g. `Smally <noreply@example.com>`
+    pub email_from: String,
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: String,
+    pub smtp_password: String,
+    /// STARTTLS (off only for local test servers)
+    pub smtp_tls: bool,
 
     // Performance Settings
-    #[allow(dead_code)]
+    /// Most texts of one `/v1/embed/batch` request
     pub max_batch_size: usize,
 }
 
@@ -63,41 +193,203 @@ impl Settings {
 
             host: get_env("HOST", "0.0.0.0"),
             port: get_env_int("PORT", 8000) as u16,
+            public_base_url: get_env(
+                "PUBLIC_BASE_URL",
+                &format!("http://localhost:{}", get_env_int("PORT", 8000)),
+            ),
+            deployment_region: get_env("DEPLOYMENT_REGION", crate::residency::ANY_REGION)
+                .trim()
+                .to_lowercase(),
+            region_base_urls: get_env("REGION_BASE_URLS", "")
+                .split(',')
+                .filter_map(|entry| entry.split_once('='))
+                .map(|(region, url)| {
+                    let url = url.trim().trim_end_matches('/');
+                    (region.trim().to_lowercase(), url.to_string())
+                })
+                .collect(),
             workers: get_env_int("WORKERS", 4) as usize,
 
             model_name: get_env("MODEL_NAME", "sentence-transformers/all-MiniLM-L6-v2"),
+            model_license: Some(get_env("MODEL_LICENSE", "Apache-2.0"))
+                .filter(|license| !license.is_empty()),
+            model_source_url: Some(get_env("MODEL_SOURCE_URL", "")).filter(|url| !url.is_empty()),
+            model_revision: Some(get_env("MODEL_REVISION", ""))
+                .map(|revision| revision.trim().to_string())
+                .filter(|revision| !revision.is_empty()),
             model_path: get_env("MODEL_PATH", "./models/all-MiniLM-L6-v2-onnx"),
             max_tokens: get_env_int("MAX_TOKENS", 128) as usize,
             embedding_dim: get_env_int("EMBEDDING_DIM", 384) as usize,
+            onnx_output_name: Some(get_env("ONNX_OUTPUT_NAME", "")).filter(|name| !name.is_empty()),
+            onnx_output_mode: get_env("ONNX_OUTPUT_MODE", "auto"),
+            onnx_intra_threads: get_env_int("ONNX_INTRA_THREADS", 0) as usize,
+            onnx_inter_threads: get_env_int("ONNX_INTER_THREADS", 0) as usize,
+            deterministic_inference: get_env_bool("DETERMINISTIC_INFERENCE", false),
+            model_languages: get_env("MODEL_LANGUAGES", "en")
+                .split(',')
+                .map(|lang| lang.trim().to_lowercase())
+                .filter(|lang| !lang.is_empty())
+                .collect(),
+            embedding_transform_path: Some(get_env("EMBEDDING_TRANSFORM_PATH", ""))
+                .filter(|path| !path.is_empty()),
+            max_inference_queue: get_env_int("MAX_INFERENCE_QUEUE", 64).max(0) as usize,
+            candidate_models_dir: get_env("CANDIDATE_MODELS_DIR", "./models"),
 
             l1_cache_size: get_env_int("L1_CACHE_SIZE", 10000) as usize,
+            l1_admission_policy: get_env("L1_ADMISSION_POLICY", "always"),
             l2_cache_ttl: get_env_int("L2_CACHE_TTL", 86400) as u64,
+            l2_write_queue_size: get_env_int("L2_WRITE_QUEUE_SIZE", 10000).max(1) as usize,
             redis_url: get_env("REDIS_URL", "redis://localhost:6379"),
+            redis_sentinels: get_env("REDIS_SENTINELS", "")
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from)
+                .collect(),
+            redis_sentinel_master: get_env("REDIS_SENTINEL_MASTER", "mymaster"),
             redis_db: get_env_int("REDIS_DB", 0),
 
             database_url: get_env(
                 "DATABASE_URL",
                 "postgres://localhost:5433/smally?sslmode=disable",
             ),
+            migrations_mode: get_env("MIGRATIONS_MODE", "auto"),
+            migrations_allow_long_running: get_env_bool("MIGRATIONS_ALLOW_LONG_RUNNING", false),
+            usage_outbox_path: Some(get_env("USAGE_OUTBOX_PATH", ""))
+                .filter(|path| !path.is_empty()),
+            usage_flush_interval_secs: get_env_int("USAGE_FLUSH_INTERVAL", 5).max(1) as u64,
+            usage_flush_max_buffer: get_env_int("USAGE_FLUSH_MAX_BUFFER", 10_000).max(1) as usize,
 
             secret_key: get_env(
                 "SECRET_KEY",
                 "change-this-to-a-secure-random-key-in-production",
             ),
             api_key_prefix: get_env("API_KEY_PREFIX", "sk_"),
+            allow_unprefixed_tokens: get_env_bool("ALLOW_UNPREFIXED_TOKENS", true),
             token_public_key: get_env("TOKEN_PUBLIC_KEY", ""),
+            token_public_keys: {
+                let keys: Vec<String> = get_env("TOKEN_PUBLIC_KEYS", "")
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect();
+                if keys.is_empty() {
+                    let key = get_env("TOKEN_PUBLIC_KEY", "");
+                    (!key.is_empty()).then_some(key).into_iter().collect()
+                } else {
+                    keys
+                }
+            },
             token_private_key: get_env("TOKEN_PRIVATE_KEY", ""),
-            jwt_secret: get_env(
-                "JWT_SECRET",
-                "change-this-to-a-secure-random-key-in-production-jwt",
-            ),
+            token_issuer: get_env("TOKEN_ISSUER", ""),
+            token_audience: get_env("TOKEN_AUDIENCE", ""),
+            token_legacy_until: provenance::var("TOKEN_LEGACY_UNTIL").and_then(|v| v.parse().ok()),
+            admin_token_max_days: get_env_int("ADMIN_TOKEN_MAX_DAYS", 90).max(1) as i64,
+            jwt_secrets: {
+                let secrets: Vec<String> = get_env("JWT_SECRETS", "")
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect();
+                if secrets.is_empty() {
+                    vec![get_env("JWT_SECRET", INSECURE_JWT_SECRET)]
+                } else {
+                    secrets
+                }
+            },
+            trust_proxy_headers: get_env_bool("TRUST_PROXY_HEADERS", false),
+            opaque_api_keys: get_env_bool("OPAQUE_API_KEYS", false),
+            demo_enabled: get_env_bool("DEMO_ENABLED", true),
+            demo_per_ip_per_minute: get_env_int("DEMO_PER_IP_PER_MINUTE", 5).max(0) as u32,
+            demo_daily_cap: get_env_int("DEMO_DAILY_CAP", 10_000).max(0) as i64,
+            ws_max_frames_per_second: get_env_int("WS_MAX_FRAMES_PER_SECOND", 20).max(0) as u32,
+            ws_idle_timeout_secs: get_env_int("WS_IDLE_TIMEOUT_SECS", 60).max(1) as u64,
+            share_link_ttl_secs: get_env_int("SHARE_LINK_TTL_SECS", 7 * 24 * 3600).max(1) as u64,
+
+            feature_flags: Flag::ALL
+                .into_iter()
+                .map(|flag| {
+                    let mode = provenance::var(&flag.env_var())
+                        .and_then(|v| FlagMode::parse(&v))
+                        .unwrap_or_else(|| flag.default_mode());
+                    (flag, mode)
+                })
+                .collect(),
 
             free_tier_limit: get_env_int("FREE_TIER_LIMIT", 20000),
             pro_tier_limit: get_env_int("PRO_TIER_LIMIT", 100000),
             scale_tier_limit: get_env_int("SCALE_TIER_LIMIT", 2000000),
+            free_tier_rpm: get_env_int("FREE_TIER_RPM", 60),
+            pro_tier_rpm: get_env_int("PRO_TIER_RPM", 600),
+            scale_tier_rpm: get_env_int("SCALE_TIER_RPM", 3000),
+            free_tier_concurrency: get_env_int("FREE_TIER_CONCURRENCY", 2),
+            pro_tier_concurrency: get_env_int("PRO_TIER_CONCURRENCY", 8),
+            scale_tier_concurrency: get_env_int("SCALE_TIER_CONCURRENCY", 32),
+            free_tier_max_organizations: get_env_int("FREE_TIER_MAX_ORGANIZATIONS", 20),
+            pro_tier_max_organizations: get_env_int("PRO_TIER_MAX_ORGANIZATIONS", 50),
+            scale_tier_max_organizations: get_env_int("SCALE_TIER_MAX_ORGANIZATIONS", 100),
+            free_tier_max_api_keys: get_env_int("FREE_TIER_MAX_API_KEYS", 100),
+            pro_tier_max_api_keys: get_env_int("PRO_TIER_MAX_API_KEYS", 500),
+            scale_tier_max_api_keys: get_env_int("SCALE_TIER_MAX_API_KEYS", 2000),
+            free_tier_creations_per_hour: get_env_int("FREE_TIER_CREATIONS_PER_HOUR", 30),
+            pro_tier_creations_per_hour: get_env_int("PRO_TIER_CREATIONS_PER_HOUR", 120),
+            scale_tier_creations_per_hour: get_env_int("SCALE_TIER_CREATIONS_PER_HOUR", 600),
+            dry_run_rpm: get_env_int("DRY_RUN_RPM", 10).max(0) as u32,
+            max_text_chars: get_env_int("MAX_TEXT_CHARS", 2000) as usize,
+
+            free_price_per_million_tokens: get_env_int("FREE_PRICE_PER_MILLION_TOKENS", 0),
+            pro_price_per_million_tokens: get_env_int("PRO_PRICE_PER_MILLION_TOKENS", 0),
+            scale_price_per_million_tokens: get_env_int("SCALE_PRICE_PER_MILLION_TOKENS", 0),
+
+            email_provider: get_env("EMAIL_PROVIDER", "log"),
+            email_from: get_env("EMAIL_FROM", "Smally <noreply@localhost>"),
+            smtp_host: get_env("SMTP_HOST", "localhost"),
+            smtp_port: get_env_int("SMTP_PORT", 587) as u16,
+            smtp_username: get_env("SMTP_USERNAME", ""),
+            smtp_password: get_env("SMTP_PASSWORD", ""),
+            smtp_tls: get_env_bool("SMTP_TLS", true),
+
+            max_batch_size: get_env_int("MAX_BATCH_SIZE", 32) as usize,
+        }
+    }
 
-            max_batch_size: get_env_int("MAX_BATCH_SIZE", 1) as usize,
+    /// Refuse to start with no session secret, or (outside debug mode)
+    /// with the placeholder one
+    pub fn check_session_secrets(&self) -> anyhow::Result<()> {
+        if self.jwt_secrets.is_empty() {
+            anyhow::bail!("No session secret configured (set JWT_SECRETS)");
         }
+        if !self.debug
+            && self
+                .jwt_secrets
+                .iter()
+                .any(|secret| secret == INSECURE_JWT_SECRET)
+        {
+            anyhow::bail!("JWT_SECRETS contains the insecure default secret");
+        }
+        Ok(())
+    }
+
+    /// Everything startup refuses to run with (`smally check` runs this too)
+    pub fn validate(&self) -> anyhow::Result<()> {
+        #[cfg(feature = "control-plane")]
+        self.check_session_secrets()?;
+        if crate::database::migrations::MigrationsMode::parse(&self.migrations_mode).is_none() {
+            anyhow::bail!(
+                "MIGRATIONS_MODE must be auto, check or skip (got '{}')",
+                self.migrations_mode
+            );
+        }
+        if crate::cache::admission::AdmissionPolicy::parse(&self.l1_admission_policy).is_none() {
+            anyhow::bail!(
+                "L1_ADMISSION_POLICY must be always or tinylfu (got '{}')",
+                self.l1_admission_policy
+            );
+        }
+        crate::residency::check_settings(self)?;
+        Ok(())
     }
 
     pub fn address(&self) -> String {
@@ -111,6 +403,9 @@ impl Default for Settings {
     }
 }
 
+/// Placeholder session secret used when none is configured (debug only)
+const INSECURE_JWT_SECRET: &str = "change-this-to-a-secure-random-key-in-production-jwt";
+
 pub static SETTINGS: Lazy<Settings> = Lazy::new(Settings::new);
 
 pub fn get_settings() -> &'static Settings {
@@ -118,19 +413,17 @@ pub fn get_settings() -> &'static Settings {
 }
 
 fn get_env(key: &str, default: &str) -> String {
-    env::var(key).unwrap_or_else(|_| default.to_string())
+    provenance::var(key).unwrap_or_else(|| default.to_string())
 }
 
 fn get_env_int(key: &str, default: i32) -> i32 {
-    env::var(key)
-        .ok()
+    provenance::var(key)
         .and_then(|v| v.parse().ok())
         .unwrap_or(default)
 }
 
 fn get_env_bool(key: &str, default: bool) -> bool {
-    env::var(key)
-        .ok()
+    provenance::var(key)
         .and_then(|v| v.parse().ok())
         .unwrap_or(default)
 }
@@ -0,0 +1,160 @@
+//! Where each setting came from, and a printable copy of the settings
+//!
+//! Every variable the loader reads is recorded. A variable set in the
+//! process environment comes from `env`; one filled in from `.env` by
+//! `load_dotenv` (which, like dotenvy, never overrides the environment)
+//! from `file`; anything unset falls back to its `default`.
+
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::path::PathBuf;
+
+use super::Settings;
+use crate::redact::{self, MASK};
+
+/// Settings that are never shown
+const SECRET_FIELDS: [&str; 4] = [
+    "secret_key",
+    "token_private_key",
+    "jwt_secrets",
+    "smtp_password",
+];
+
+/// Settings holding URLs whose password is never shown
+const URL_FIELDS: [&str; 3] = ["database_url", "redis_url", "redis_sentinels"];
+
+/// Variables read by the loader
+static READ: Lazy<Mutex<BTreeSet<String>>> = Lazy::new(Default::default);
+
+/// Variables set from `.env`
+static FROM_FILE: Lazy<Mutex<BTreeSet<String>>> = Lazy::new(Default::default);
+
+/// Where a setting's value came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Env,
+    File,
+    Default,
+}
+
+/// Load `.env` (if any) into the environment, remembering which variables
+/// it set. Variables already in the environment win.
+pub fn load_dotenv() -> dotenvy::Result<PathBuf> {
+    let already_set: BTreeSet<String> = env::vars_os()
+        .filter_map(|(key, _)| key.into_string().ok())
+        .collect();
+    let path = dotenvy::dotenv()?;
+    let mut from_file = FROM_FILE.lock();
+    for item in dotenvy::from_path_iter(&path)? {
+        let (key, _) = item?;
+        if !already_set.contains(&key) {
+            from_file.insert(key);
+        }
+    }
+    Ok(path)
+}
+
+/// Read an environment variable on behalf of the loader
+pub(super) fn var(key: &str) -> Option<String> {
+    READ.lock().insert(key.to_string());
+    env::var(key).ok()
+}
+
+fn source_of(key: &str) -> Source {
+    if env::var_os(key).is_none() {
+        Source::Default
+    } else if FROM_FILE.lock().contains(key) {
+        Source::File
+    } else {
+        Source::Env
+    }
+}
+
+/// Source of every variable the loader has read
+pub fn sources() -> BTreeMap<String, Source> {
+    READ.lock()
+        .iter()
+        .map(|key| (key.clone(), source_of(key)))
+        .collect()
+}
+
+impl Settings {
+    /// The settings with secrets masked, safe to log or return
+    pub fn masked(&self) -> Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        let Some(fields) = value.as_object_mut() else {
+            return value;
+        };
+        for field in SECRET_FIELDS {
+            if let Some(secret) = fields.get_mut(field) {
+                mask_each(secret, &|s| {
+                    if s.is_empty() {
+                        String::new()
+                    } else {
+                        MASK.to_string()
+                    }
+                });
+            }
+        }
+        for field in URL_FIELDS {
+            if let Some(url) = fields.get_mut(field) {
+                mask_each(url, &redact::url_password);
+            }
+        }
+        value
+    }
+}
+
+/// Apply `mask` to a string or to each string of an array
+fn mask_each(value: &mut Value, mask: &dyn Fn(&str) -> String) {
+    match value {
+        Value::String(s) => *s = mask(s),
+        Value::Array(items) => items.iter_mut().for_each(|item| mask_each(item, mask)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_masked_settings() {
+        let settings = Settings {
+            secret_key: "s3cret".to_string(),
+            jwt_secrets: vec!["one".to_string(), "two".to_string()],
+            smtp_password: String::new(),
+            database_url: "postgres://u:p@db/smally".to_string(),
+            ..Settings::new()
+        };
+        let masked = settings.masked();
+
+        assert_eq!(masked["secret_key"], MASK);
+        assert_eq!(masked["jwt_secrets"], serde_json::json!([MASK, MASK]));
+        // Unset stays visibly unset
+        assert_eq!(masked["smtp_password"], "");
+        assert_eq!(masked["database_url"], "postgres://u:********@db/smally");
+        assert_eq!(masked["port"], settings.port);
+        assert!(!masked.to_string().contains("s3cret"));
+    }
+
+    #[test]
+    fn test_sources() {
+        let (set, unset) = ("SMALLY_PROVENANCE_TEST_SET", "SMALLY_PROVENANCE_TEST_UNSET");
+        env::set_var(set, "1");
+        assert_eq!(var(set).as_deref(), Some("1"));
+        assert_eq!(var(unset), None);
+
+        let sources = sources();
+        assert_eq!(sources[set], Source::Env);
+        assert_eq!(sources[unset], Source::Default);
+        FROM_FILE.lock().insert(set.to_string());
+        assert_eq!(source_of(set), Source::File);
+        env::remove_var(set);
+    }
+}
@@ -0,0 +1,404 @@
+//! `api_request_log`: one row per API request, inserted as `pending` when
+//! the request arrives and completed once the response is known
+//!
+//! Postgres-only: with the `sqlite` feature requests aren't logged.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+use crate::database::{self, DbPool};
+
+/// Per-request audit insert. Kept as one constant so every call hits the
+/// same entry in sqlx's per-connection prepared statement cache.
+#[cfg(not(feature = "sqlite"))]
+const INSERT_PENDING: &str = "INSERT INTO api_request_log
+     (request_id, organization_id, api_key_id, product, endpoint, input_text, input_metadata,
+      client_ip, user_agent, request_timestamp, status)
+     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW(), 'pending')";
+
+#[cfg(not(feature = "sqlite"))]
+const INSERT_DRY_RUN: &str = "INSERT INTO api_request_log
+     (request_id, organization_id, api_key_id, product, endpoint, input_text, input_metadata,
+      client_ip, user_agent, request_timestamp, tokens, response_timestamp, status)
+     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, NOW(), $10, NOW(), 'dry_run')";
+
+/// Batch completion. Like the usage event insert, columns are bound as
+/// arrays so batches of any size share one prepared statement.
+#[cfg(not(feature = "sqlite"))]
+const COMPLETE_BATCH: &str = "UPDATE api_request_log l
+     SET tokens = u.tokens,
+         response_metadata = u.response_metadata,
+         response_timestamp = u.response_timestamp,
+         status = 'success',
+         updated_at = NOW()
+     FROM UNNEST($1::uuid[], $2::int4[], $3::jsonb[], $4::timestamptz[])
+          AS u(request_id, tokens, response_metadata, response_timestamp)
+     WHERE l.request_id = u.request_id";
+
+/// A request as it arrives
+#[derive(Clone, Debug)]
+pub struct NewRequest {
+    pub request_id: Uuid,
+    pub organization_id: Uuid,
+    pub api_key_id: Uuid,
+    pub product: String,
+    pub endpoint: String,
+    pub input_text: String,
+    pub input_metadata: Option<serde_json::Value>,
+    /// Proxy-aware client IP (None when the organization opted out)
+    pub client_ip: Option<IpAddr>,
+    /// User-Agent header, truncated to `USER_AGENT_MAX_CHARS`
+    pub user_agent: Option<String>,
+}
+
+/// Longest User-Agent kept (the column is VARCHAR(512))
+pub const USER_AGENT_MAX_CHARS: usize = 512;
+
+/// A logged request as shown to the organization and to admins (the input
+/// text is left out)
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct RequestLogEntry {
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub request_id: Uuid,
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub api_key_id: Uuid,
+    pub endpoint: String,
+    pub status: Option<String>,
+    pub tokens: Option<i32>,
+    pub client_ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_timestamp: DateTime<Utc>,
+}
+
+/// The response to a pending request
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResponseUpdate {
+    pub request_id: Uuid,
+    pub tokens: i32,
+    pub response_metadata: serde_json::Value,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Typed access to `api_request_log`
+#[derive(Clone, Copy)]
+pub struct ApiRequestLogRepo<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> ApiRequestLogRepo<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// An organization's most recent requests, optionally of one key
+    pub async fn recent(
+        &self,
+        org_id: Uuid,
+        api_key_id: Option<Uuid>,
+        limit: i64,
+    ) -> sqlx::Result<Vec<RequestLogEntry>> {
+        sqlx::query_as::<_, RequestLogEntry>(
+            "SELECT request_id, api_key_id, endpoint, status, tokens, client_ip, user_agent,
+                    request_timestamp
+             FROM api_request_log
+             WHERE organization_id = $1 AND ($2 IS NULL OR api_key_id = $2)
+             ORDER BY request_timestamp DESC
+             LIMIT $3",
+        )
+        .bind(org_id)
+        .bind(api_key_id)
+        .bind(limit)
+        .fetch_all(self.pool)
+        .await
+    }
+
+    /// Requests of a key since `since`, and how many distinct client IPs
+    /// they came from
+    pub async fn key_origins_since(
+        &self,
+        api_key_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> sqlx::Result<(i64, i64)> {
+        sqlx::query_as::<_, (i64, i64)>(
+            "SELECT COUNT(*), COUNT(DISTINCT client_ip) FROM api_request_log
+             WHERE api_key_id = $1 AND request_timestamp >= $2",
+        )
+        .bind(api_key_id)
+        .bind(database::timestamp(since))
+        .fetch_one(self.pool)
+        .await
+    }
+
+    /// Forget the client IPs of an organization's requests
+    pub async fn clear_client_ips(&self, org_id: Uuid) -> sqlx::Result<u64> {
+        let result = sqlx::query(
+            "UPDATE api_request_log SET client_ip = NULL
+             WHERE organization_id = $1 AND client_ip IS NOT NULL",
+        )
+        .bind(org_id)
+        .execute(self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl ApiRequestLogRepo<'_> {
+    pub async fn insert_pending(&self, _request: &NewRequest) -> sqlx::Result<()> {
+        super::warn_postgres_only("api_request_log");
+        Ok(())
+    }
+
+    pub async fn insert_dry_run(&self, _request: &NewRequest, _tokens: i32) -> sqlx::Result<()> {
+        super::warn_postgres_only("api_request_log");
+        Ok(())
+    }
+
+    pub async fn complete_batch(&self, _updates: &[ResponseUpdate]) -> sqlx::Result<u64> {
+        Ok(0)
+    }
+
+    pub async fn complete_batch_or_each(
+        &self,
+        _updates: &[ResponseUpdate],
+    ) -> sqlx::Result<(u64, Vec<(Uuid, String)>)> {
+        Ok((0, Vec::new()))
+    }
+
+    pub async fn logged_inputs(
+        &self,
+        _org_id: Uuid,
+        _before: DateTime<Utc>,
+        _limit: i64,
+    ) -> sqlx::Result<Vec<(Uuid, String, String)>> {
+        Ok(Vec::new())
+    }
+
+    pub async fn delete(&self, _request_ids: &[Uuid]) -> sqlx::Result<u64> {
+        Ok(0)
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+impl ApiRequestLogRepo<'_> {
+    /// Log a request that has just arrived (status `pending`)
+    pub async fn insert_pending(&self, request: &NewRequest) -> sqlx::Result<()> {
+        sqlx::query(INSERT_PENDING)
+            .persistent(true)
+            .bind(request.request_id)
+            .bind(request.organization_id)
+            .bind(request.api_key_id)
+            .bind(&request.product)
+            .bind(&request.endpoint)
+            .bind(&request.input_text)
+            .bind(&request.input_metadata)
+            .bind(request.client_ip.map(|ip| ip.to_string()))
+            .bind(&request.user_agent)
+            .execute(self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Log a dry run, complete with its token count (status `dry_run`)
+    pub async fn insert_dry_run(&self, request: &NewRequest, tokens: i32) -> sqlx::Result<()> {
+        sqlx::query(INSERT_DRY_RUN)
+            .bind(request.request_id)
+            .bind(request.organization_id)
+            .bind(request.api_key_id)
+            .bind(&request.product)
+            .bind(&request.endpoint)
+            .bind(&request.input_text)
+            .bind(&request.input_metadata)
+            .bind(request.client_ip.map(|ip| ip.to_string()))
+            .bind(&request.user_agent)
+            .bind(tokens)
+            .execute(self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Mark requests as answered (status `success`); returns the number of
+    /// rows updated
+    pub async fn complete_batch(&self, updates: &[ResponseUpdate]) -> sqlx::Result<u64> {
+        if updates.is_empty() {
+            return Ok(0);
+        }
+
+        let mut request_ids = Vec::with_capacity(updates.len());
+        let mut tokens = Vec::with_capacity(updates.len());
+        let mut metadata = Vec::with_capacity(updates.len());
+        let mut timestamps = Vec::with_capacity(updates.len());
+        for update in updates {
+            request_ids.push(update.request_id);
+            tokens.push(update.tokens);
+            metadata.push(update.response_metadata.clone());
+            timestamps.push(update.timestamp);
+        }
+
+        let result = sqlx::query(COMPLETE_BATCH)
+            .persistent(true)
+            .bind(request_ids)
+            .bind(tokens)
+            .bind(metadata)
+            .bind(timestamps)
+            .execute(self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// `complete_batch`, but when the database rejects a row's data the
+    /// rows are written one at a time, so one bad row doesn't hold back
+    /// the rest. Returns the number of rows updated and the rejected
+    /// requests with the reason.
+    pub async fn complete_batch_or_each(
+        &self,
+        updates: &[ResponseUpdate],
+    ) -> sqlx::Result<(u64, Vec<(Uuid, String)>)> {
+        match self.complete_batch(updates).await {
+            Err(e) if is_rejected_row(&e) => {
+                tracing::warn!("Batch response update failed, retrying row by row: {}", e)
+            }
+            result => return result.map(|updated| (updated, Vec::new())),
+        }
+
+        let mut updated = 0;
+        let mut rejected = Vec::new();
+        for update in updates {
+            match self.complete_batch(std::slice::from_ref(update)).await {
+                Ok(rows) => updated += rows,
+                Err(e) if is_rejected_row(&e) => rejected.push((update.request_id, e.to_string())),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok((updated, rejected))
+    }
+
+    /// Id, endpoint and input text of an organization's oldest requests
+    /// logged before `before`
+    pub async fn logged_inputs(
+        &self,
+        org_id: Uuid,
+        before: DateTime<Utc>,
+        limit: i64,
+    ) -> sqlx::Result<Vec<(Uuid, String, String)>> {
+        sqlx::query_as::<_, (Uuid, String, String)>(
+            "SELECT request_id, endpoint, input_text FROM api_request_log
+             WHERE organization_id = $1 AND request_timestamp < $2
+             ORDER BY request_timestamp
+             LIMIT $3",
+        )
+        .bind(org_id)
+        .bind(database::timestamp(before))
+        .bind(limit)
+        .fetch_all(self.pool)
+        .await
+    }
+
+    /// Delete logged requests; returns the number of rows deleted
+    pub async fn delete(&self, request_ids: &[Uuid]) -> sqlx::Result<u64> {
+        let result = sqlx::query("DELETE FROM api_request_log WHERE request_id = ANY($1)")
+            .bind(request_ids)
+            .execute(self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
+
+/// Error caused by a row's values (SQLSTATE class 22, data exception, or
+/// 23, integrity constraint violation) rather than by the connection or
+/// the server: retrying the same row won't help
+#[cfg(not(feature = "sqlite"))]
+fn is_rejected_row(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Database(e) => e
+            .code()
+            .is_some_and(|code| code.starts_with("22") || code.starts_with("23")),
+        _ => false,
+    }
+}
+
+#[cfg(all(test, feature = "control-plane", not(feature = "sqlite")))]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::test_utils::factory;
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_pending_then_completed() {
+        setup().await;
+        cleanup_db().await;
+        let repo = ApiRequestLogRepo::new(database::get_db());
+        let owner = factory::user("request-log@example.com").await;
+        let key = factory::api_key(&owner).await;
+
+        let requests: Vec<_> = (0..3)
+            .map(|i| NewRequest {
+                request_id: Uuid::now_v7(),
+                organization_id: key.org_id,
+                api_key_id: key.key_id,
+                product: "embeddings".to_string(),
+                endpoint: "/v1/embed".to_string(),
+                input_text: format!("text {}", i),
+                input_metadata: None,
+                client_ip: Some([203, 0, 113, 7].into()),
+                user_agent: Some("smally-test/1.0".to_string()),
+            })
+            .collect();
+        for request in &requests {
+            repo.insert_pending(request).await.unwrap();
+        }
+
+        // The last one is left pending; unknown ids are ignored
+        let now = chrono::Utc::now();
+        let mut updates: Vec<_> = requests[..2]
+            .iter()
+            .enumerate()
+            .map(|(i, request)| ResponseUpdate {
+                request_id: request.request_id,
+                tokens: i as i32 + 5,
+                response_metadata: serde_json::json!({"cached": i == 1}),
+                timestamp: now,
+            })
+            .collect();
+        updates.push(ResponseUpdate {
+            request_id: Uuid::now_v7(),
+            ..updates[0].clone()
+        });
+        assert_eq!(repo.complete_batch(&updates).await.unwrap(), 2);
+        assert_eq!(repo.complete_batch(&[]).await.unwrap(), 0);
+
+        let rows = sqlx::query_as::<_, (Uuid, String, Option<i32>, Option<serde_json::Value>)>(
+            "SELECT request_id, status, tokens, response_metadata FROM api_request_log
+             WHERE organization_id = $1 ORDER BY request_id",
+        )
+        .bind(key.org_id)
+        .fetch_all(database::get_db())
+        .await
+        .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    requests[0].request_id,
+                    "success".to_string(),
+                    Some(5),
+                    Some(serde_json::json!({"cached": false}))
+                ),
+                (
+                    requests[1].request_id,
+                    "success".to_string(),
+                    Some(6),
+                    Some(serde_json::json!({"cached": true}))
+                ),
+                (requests[2].request_id, "pending".to_string(), None, None),
+            ]
+        );
+
+        cleanup_db().await;
+    }
+}
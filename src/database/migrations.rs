@@ -0,0 +1,270 @@
+//! Migrations at startup (`MIGRATIONS_MODE`)
+//!
+//! - `auto` (default): startup applies the pending migrations
+//! - `check`: startup applies nothing, and the `database` component stays
+//!   unready while the database is behind this build (the process keeps
+//!   running, so it turns ready as soon as someone migrates)
+//! - `skip`: startup neither applies nor checks anything; an external
+//!   `smally migrate` job is trusted to have run
+//!
+//! A migration file with a `-- long-running` line (a backfill, an index
+//! built under a lock) would stall every instance of a rolling deploy at
+//! once if startup ran it. `auto` refuses to: it logs the migration and
+//! applies nothing, so readiness stays down until `smally migrate` has run
+//! it, unless `MIGRATIONS_ALLOW_LONG_RUNNING=true`.
+
+use anyhow::{anyhow, Context, Result};
+use sqlx::migrate::Migrate;
+use sqlx::pool::PoolOptions;
+use tracing::{error, info, warn};
+
+use super::{connect_options, migrator, Db, DbPool};
+use crate::config;
+
+/// Annotation (a line of its own) of migrations startup won't run
+pub const LONG_RUNNING: &str = "-- long-running";
+
+/// What startup does about migrations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationsMode {
+    Auto,
+    Check,
+    Skip,
+}
+
+impl MigrationsMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "auto" => Some(MigrationsMode::Auto),
+            "check" => Some(MigrationsMode::Check),
+            "skip" => Some(MigrationsMode::Skip),
+            _ => None,
+        }
+    }
+
+    /// `MIGRATIONS_MODE` (`Settings::validate` rejects unknown modes)
+    pub fn from_settings() -> Self {
+        Self::parse(&config::get_settings().migrations_mode).unwrap_or(MigrationsMode::Auto)
+    }
+}
+
+/// Whether a migration is annotated `-- long-running`
+fn is_long_running(sql: &str) -> bool {
+    sql.lines().any(|line| line.trim() == LONG_RUNNING)
+}
+
+/// Pending migrations (version, SQL) that are long-running
+fn long_running_pending(embedded: &[(i64, &str)], applied: &[i64]) -> Vec<i64> {
+    embedded
+        .iter()
+        .filter(|(version, sql)| !applied.contains(version) && is_long_running(sql))
+        .map(|(version, _)| *version)
+        .collect()
+}
+
+/// How the applied migrations differ from the embedded ones (version and
+/// checksum each), None if they're the same
+pub fn drift(embedded: &[(i64, &[u8])], applied: &[(i64, &[u8])]) -> Option<String> {
+    let mut problems = vec![];
+
+    let pending: Vec<String> = embedded
+        .iter()
+        .filter(|(version, _)| !applied.iter().any(|(applied, _)| applied == version))
+        .map(|(version, _)| version.to_string())
+        .collect();
+    if !pending.is_empty() {
+        problems.push(format!("pending migrations: {}", pending.join(", ")));
+    }
+
+    let modified: Vec<String> = embedded
+        .iter()
+        .filter(|(version, checksum)| {
+            applied.iter().any(|(applied, applied_checksum)| {
+                applied == version && applied_checksum != checksum
+            })
+        })
+        .map(|(version, _)| version.to_string())
+        .collect();
+    if !modified.is_empty() {
+        problems.push(format!(
+            "migrations changed after they were applied: {}",
+            modified.join(", ")
+        ));
+    }
+
+    let unknown: Vec<String> = applied
+        .iter()
+        .filter(|(version, _)| !embedded.iter().any(|(embedded, _)| embedded == version))
+        .map(|(version, _)| version.to_string())
+        .collect();
+    if !unknown.is_empty() {
+        problems.push(format!(
+            "applied migrations this build doesn't have: {}",
+            unknown.join(", ")
+        ));
+    }
+
+    (!problems.is_empty()).then(|| problems.join("; "))
+}
+
+/// Fail unless the database is at exactly this build's migrations
+pub async fn check_current(pool: &DbPool) -> Result<()> {
+    let mut conn = pool.acquire().await?;
+    let applied = conn
+        .list_applied_migrations()
+        .await
+        .context("reading applied migrations")?;
+
+    let migrator = migrator();
+    let embedded: Vec<(i64, &[u8])> = migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| (migration.version, &*migration.checksum))
+        .collect();
+    let applied: Vec<(i64, &[u8])> = applied
+        .iter()
+        .map(|migration| (migration.version, &*migration.checksum))
+        .collect();
+
+    match drift(&embedded, &applied) {
+        Some(drift) => Err(anyhow!(drift)),
+        None => Ok(()),
+    }
+}
+
+/// Apply or check the migrations as `mode` says (see the module docs)
+pub async fn on_startup(
+    pool: &DbPool,
+    mode: MigrationsMode,
+    allow_long_running: bool,
+) -> Result<()> {
+    match mode {
+        MigrationsMode::Skip => {
+            info!("MIGRATIONS_MODE=skip: not applying or checking migrations");
+        }
+        MigrationsMode::Check => match check_current(pool).await {
+            Ok(()) => info!("Database migrations are current"),
+            Err(e) => warn!(
+                "Database isn't at this build's migrations ({:#}); not ready until it's migrated",
+                e
+            ),
+        },
+        MigrationsMode::Auto => {
+            let migrator = migrator();
+            let mut conn = pool.acquire().await?;
+            conn.ensure_migrations_table().await?;
+            let applied: Vec<i64> = conn
+                .list_applied_migrations()
+                .await?
+                .iter()
+                .map(|migration| migration.version)
+                .collect();
+            drop(conn);
+
+            let embedded: Vec<(i64, &str)> = migrator
+                .iter()
+                .filter(|migration| !migration.migration_type.is_down_migration())
+                .map(|migration| (migration.version, &*migration.sql))
+                .collect();
+            let blocked = long_running_pending(&embedded, &applied);
+            if !blocked.is_empty() && !allow_long_running {
+                error!(
+                    migrations = ?blocked,
+                    "Not running migrations at startup: some are long-running; \
+                     run `smally migrate` (or set MIGRATIONS_ALLOW_LONG_RUNNING=true)"
+                );
+                return Ok(());
+            }
+
+            info!("Running database migrations...");
+            migrator.run(pool).await?;
+            info!("Database migrations completed");
+        }
+    }
+    Ok(())
+}
+
+/// Apply every pending migration, long-running ones included (`smally
+/// migrate`). Returns the versions applied.
+pub async fn migrate() -> Result<Vec<i64>> {
+    let settings = config::get_settings();
+    let pool = PoolOptions::<Db>::new()
+        .max_connections(1)
+        .connect_with(connect_options(&settings.database_url)?)
+        .await?;
+
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let before: Vec<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .iter()
+        .map(|migration| migration.version)
+        .collect();
+    drop(conn);
+
+    migrator().run(&pool).await?;
+    Ok(migrator()
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .filter(|version| !before.contains(version))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_parse() {
+        assert_eq!(MigrationsMode::parse("auto"), Some(MigrationsMode::Auto));
+        assert_eq!(
+            MigrationsMode::parse(" Check "),
+            Some(MigrationsMode::Check)
+        );
+        assert_eq!(MigrationsMode::parse("skip"), Some(MigrationsMode::Skip));
+        assert_eq!(MigrationsMode::parse("always"), None);
+    }
+
+    #[test]
+    fn test_drift() {
+        let embedded: Vec<(i64, &[u8])> = vec![(1, b"a"), (2, b"b"), (3, b"c")];
+
+        assert_eq!(drift(&embedded, &embedded), None);
+        // Behind this build, with one migration edited after it ran
+        assert_eq!(
+            drift(&embedded, &[(1, b"a"), (2, b"x")]).as_deref(),
+            Some("pending migrations: 3; migrations changed after they were applied: 2")
+        );
+        // Ahead of this build (an older release during a rolling deploy)
+        assert_eq!(
+            drift(&embedded[..2], &embedded).as_deref(),
+            Some("applied migrations this build doesn't have: 3")
+        );
+    }
+
+    #[test]
+    fn test_long_running_pending() {
+        let embedded = [
+            (1, "CREATE TABLE a (id INT);"),
+            (2, "-- long-running\nCREATE INDEX idx_a ON a(id);"),
+            (
+                3,
+                "-- not long-running, despite the mention\nALTER TABLE a ADD b INT;",
+            ),
+        ];
+
+        assert_eq!(long_running_pending(&embedded, &[]), vec![2]);
+        assert_eq!(long_running_pending(&embedded, &[1]), vec![2]);
+        assert!(long_running_pending(&embedded, &[1, 2]).is_empty());
+    }
+
+    /// The test database is migrated to this build
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_check_current() {
+        let state = crate::test_utils::helpers::setup().await;
+        check_current(&state.db).await.unwrap();
+    }
+}
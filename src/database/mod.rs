@@ -1,54 +1,327 @@
+//! Database pool and repositories
+//!
+//! Postgres by default. With the `sqlite` feature the pool is SQLite
+//! (`DATABASE_URL=sqlite://smally.db`) for local development and CI: the
+//! control plane (users, organizations, keys) runs unchanged with the
+//! schema in `migrations/sqlite`, while the data-plane tables
+//! (`api_request_log`, `usage_events`) stay Postgres-only and their writes
+//! are skipped with a warning.
+
+pub mod api_request_log;
+pub mod migrations;
+pub mod usage_events;
+
+pub use api_request_log::ApiRequestLogRepo;
+pub use usage_events::UsageEventsRepo;
+
 use anyhow::Result;
-use once_cell::sync::OnceCell;
-use sqlx::postgres::{PgPool, PgPoolOptions};
+use chrono::{DateTime, Utc};
+use sqlx::migrate::Migrator;
+use sqlx::pool::PoolOptions;
 use tracing::info;
 
 use crate::config;
 
-static DB_POOL: OnceCell<PgPool> = OnceCell::new();
+/// The database backend this build talks to
+#[cfg(not(feature = "sqlite"))]
+pub type Db = sqlx::Postgres;
+#[cfg(feature = "sqlite")]
+pub type Db = sqlx::Sqlite;
 
-pub async fn init_db() -> Result<()> {
-    // If already initialized, return early
-    if DB_POOL.get().is_some() {
-        return Ok(());
-    }
+pub type DbPool = sqlx::Pool<Db>;
+
+/// A list column read with `query_as`/`query_scalar`: a Postgres array, or
+/// a JSON array on SQLite (see `into_vec`). `FromRow` structs mark list
+/// fields `#[cfg_attr(feature = "sqlite", sqlx(json))]` instead.
+#[cfg(not(feature = "sqlite"))]
+pub type List<T> = Vec<T>;
+#[cfg(feature = "sqlite")]
+pub type List<T> = sqlx::types::Json<Vec<T>>;
+
+/// Bind value for a list column (a `Vec` or slice; map an `Option` through
+/// it so None stays NULL)
+#[cfg(not(feature = "sqlite"))]
+pub fn list<T>(values: T) -> T {
+    values
+}
+#[cfg(feature = "sqlite")]
+pub fn list<T>(values: T) -> sqlx::types::Json<T> {
+    sqlx::types::Json(values)
+}
+
+/// The values of a `List` column
+#[cfg(not(feature = "sqlite"))]
+pub fn into_vec<T>(list: List<T>) -> Vec<T> {
+    list
+}
+#[cfg(feature = "sqlite")]
+pub fn into_vec<T>(list: List<T>) -> Vec<T> {
+    list.0
+}
+
+/// Bind value for a timestamp column: a `timestamptz` on Postgres, or on
+/// SQLite UTC text in the format of its `CURRENT_TIMESTAMP` defaults, so
+/// bound and default values compare in order (map an `Option` through it)
+#[cfg(not(feature = "sqlite"))]
+pub fn timestamp(at: DateTime<Utc>) -> DateTime<Utc> {
+    at
+}
+#[cfg(feature = "sqlite")]
+pub fn timestamp(at: DateTime<Utc>) -> chrono::NaiveDateTime {
+    at.naive_utc()
+}
 
+/// Migrations embedded in this build, for the configured backend
+pub fn migrator() -> Migrator {
+    #[cfg(not(feature = "sqlite"))]
+    let migrator = sqlx::migrate!("./migrations");
+    #[cfg(feature = "sqlite")]
+    let migrator = sqlx::migrate!("./migrations/sqlite");
+    migrator
+}
+
+/// Open a pool for `DATABASE_URL` and migrate the database as
+/// `MIGRATIONS_MODE` says (outside tests)
+pub async fn connect() -> Result<DbPool> {
     let settings = config::get_settings();
 
     // In test mode, use smaller pool with shorter timeouts to fail fast
     #[cfg(test)]
-    let pool = PgPoolOptions::new()
+    let options = PoolOptions::<Db>::new()
         .max_connections(5)
         .min_connections(1)
-        .acquire_timeout(std::time::Duration::from_secs(2))
-        .connect(&settings.database_url)
-        .await?;
+        .acquire_timeout(std::time::Duration::from_secs(2));
 
     // In production, use larger pool with default timeout
     #[cfg(not(test))]
-    let pool = PgPoolOptions::new()
+    let options = PoolOptions::<Db>::new()
         .max_connections(10)
-        .min_connections(2)
-        .connect(&settings.database_url)
+        .min_connections(2);
+
+    #[cfg(feature = "fault-injection")]
+    let options = with_faults(options);
+
+    let pool = options
+        .connect_with(connect_options(&settings.database_url)?)
         .await?;
 
-    // Run migrations only in non-test mode
-    #[cfg(not(test))]
-    {
-        info!("Running database migrations...");
-        sqlx::migrate!("./migrations").run(&pool).await?;
-        info!("Database migrations completed");
-    }
+    // Run migrations only in non-test mode (the Postgres test database is
+    // migrated beforehand; a SQLite one is created by the tests)
+    #[cfg(any(not(test), feature = "sqlite"))]
+    migrations::on_startup(
+        &pool,
+        migrations::MigrationsMode::from_settings(),
+        settings.migrations_allow_long_running,
+    )
+    .await?;
 
     // Test the connection
     sqlx::query("SELECT 1").execute(&pool).await?;
 
-    DB_POOL.set(pool).ok(); // Ignore error if already set
-
     info!("Database connection pool initialized");
-    Ok(())
+    Ok(pool)
+}
+
+/// Apply injected Postgres faults (see `fault`) to pool acquires and new
+/// connections
+#[cfg(feature = "fault-injection")]
+fn with_faults(options: PoolOptions<Db>) -> PoolOptions<Db> {
+    use crate::fault::{self, Target};
+
+    options
+        .before_acquire(|_conn, _meta| {
+            Box::pin(async {
+                fault::apply(Target::Postgres)
+                    .await
+                    .map(|()| true)
+                    .map_err(|fault| sqlx::Error::Io(fault.into()))
+            })
+        })
+        .after_connect(|_conn, _meta| {
+            Box::pin(async {
+                fault::apply(Target::Postgres)
+                    .await
+                    .map_err(|fault| sqlx::Error::Io(fault.into()))
+            })
+        })
 }
 
-pub fn get_db() -> &'static PgPool {
-    DB_POOL.get().expect("Database pool not initialized")
+/// The pool of the process-wide `AppState` (prefer `AppState::db` where
+/// the state is at hand)
+pub fn get_db() -> &'static DbPool {
+    &crate::state::global().db
+}
+
+/// Connect options for `url`, which has to be for the backend of this build
+pub fn connect_options(
+    url: &str,
+) -> Result<<<Db as sqlx::Database>::Connection as sqlx::Connection>::Options> {
+    #[cfg(not(feature = "sqlite"))]
+    {
+        if url.starts_with("sqlite:") {
+            anyhow::bail!("DATABASE_URL is a SQLite URL; build with --features sqlite to use it");
+        }
+        Ok(url.parse()?)
+    }
+    #[cfg(feature = "sqlite")]
+    {
+        if !url.starts_with("sqlite:") {
+            anyhow::bail!(
+                "this build uses SQLite (--features sqlite); DATABASE_URL must be sqlite://..."
+            );
+        }
+        Ok(url
+            .parse::<sqlx::sqlite::SqliteConnectOptions>()?
+            .create_if_missing(true)
+            .foreign_keys(true))
+    }
+}
+
+/// Warn (once per table) that a data-plane table is skipped on SQLite
+#[cfg(feature = "sqlite")]
+pub(crate) fn warn_postgres_only(table: &'static str) {
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    static WARNED: Mutex<Option<HashSet<&'static str>>> = Mutex::new(None);
+    let mut warned = WARNED.lock().unwrap_or_else(|e| e.into_inner());
+    if warned.get_or_insert_with(HashSet::new).insert(table) {
+        tracing::warn!(
+            "{} is Postgres-only; skipping it on SQLite (usage and request logs aren't recorded)",
+            table
+        );
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::billing::{alerts, statements};
+    use crate::flags::{self, Flag};
+    use crate::models::{CreateUserRequest, OrganizationRole};
+    use crate::services::{organizations, users};
+    use chrono::NaiveDate;
+
+    /// A fresh database file in the temp directory
+    async fn temp_pool() -> (DbPool, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("smally-{}.db", uuid::Uuid::now_v7()));
+        let pool = PoolOptions::<Db>::new()
+            .max_connections(2)
+            .connect_with(connect_options(&format!("sqlite://{}", path.display())).unwrap())
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations/sqlite")
+            .run(&pool)
+            .await
+            .unwrap();
+        (pool, path)
+    }
+
+    #[test]
+    fn test_url_must_match_backend() {
+        assert!(connect_options("sqlite://smally.db").is_ok());
+        assert!(connect_options("postgres://localhost/smally").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_control_plane_on_sqlite() {
+        let (pool, path) = temp_pool().await;
+
+        let registered = users::register(
+            &pool,
+            &CreateUserRequest {
+                email: "sqlite@example.com".to_string(),
+                password: "correct horse battery".to_string(),
+                name: Some("SQLite".to_string()),
+            },
+        )
+        .await
+        .unwrap();
+        let org_id = registered.org_id;
+        assert_eq!(registered.user.last_selected_org_id, Some(org_id));
+
+        let orgs = organizations::list_for_user(&pool, registered.user.id)
+            .await
+            .unwrap();
+        assert_eq!(orgs.len(), 1);
+        assert_eq!(orgs[0].role, OrganizationRole::Owner);
+        assert_eq!(orgs[0].allowed_models, None);
+
+        // List columns round-trip as JSON
+        let models = vec!["all-MiniLM-L6-v2".to_string()];
+        sqlx::query("UPDATE organizations SET allowed_models = $1 WHERE id = $2")
+            .bind(list(&models))
+            .bind(org_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let member = organizations::membership(&pool, org_id, registered.user.id)
+            .await
+            .unwrap();
+        assert_eq!(member.allowed_models, Some(models));
+
+        // Anchor 1 keeps the calendar month's cycle, so there is no quota
+        // counter to carry over
+        let member = organizations::set_billing_anchor(&pool, org_id, registered.user.id, Some(1))
+            .await
+            .unwrap();
+        assert_eq!(member.billing_anchor_day, Some(1));
+        let member = organizations::set_ip_storage(&pool, org_id, registered.user.id, false)
+            .await
+            .unwrap();
+        assert!(!member.store_client_ip);
+
+        assert!(flags::set_org_flags(&pool, org_id, &[Flag::BatchApi])
+            .await
+            .unwrap());
+        assert_eq!(
+            flags::overrides(&pool).await.unwrap(),
+            vec![(org_id, vec!["batch_api".to_string()])]
+        );
+
+        assert_eq!(
+            alerts::load_settings(&pool, org_id).await.unwrap(),
+            alerts::AlertSettings::default()
+        );
+        let settings = alerts::AlertSettings {
+            enabled: true,
+            thresholds: vec![50, 90],
+            recipients: vec!["billing@example.com".to_string()],
+        };
+        alerts::save_settings(&pool, org_id, &settings)
+            .await
+            .unwrap();
+        assert_eq!(
+            alerts::load_settings(&pool, org_id).await.unwrap(),
+            settings
+        );
+
+        // Statements: no usage is recorded, versions still stack up
+        let month = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();
+        statements::regenerate(&pool, org_id, month, "first")
+            .await
+            .unwrap();
+        let second = statements::regenerate(&pool, org_id, month, "second")
+            .await
+            .unwrap();
+        assert_eq!(second.total_requests, 0);
+        let latest = statements::list_latest(&pool, org_id).await.unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].version, 2);
+        assert_eq!(
+            statements::find(&pool, org_id, month, Some(1))
+                .await
+                .unwrap()
+                .unwrap()
+                .version,
+            1
+        );
+
+        let usage = UsageEventsRepo::new(&pool);
+        let since = month.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        assert_eq!(usage.totals_since(org_id, since).await.unwrap(), (0, 0));
+
+        pool.close().await;
+        std::fs::remove_file(path).ok();
+    }
 }
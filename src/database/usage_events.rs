@@ -0,0 +1,307 @@
+//! `usage_events`: billed usage, written in batches by the usage buffer and
+//! read back for metrics and exports
+//!
+//! Writes are Postgres-only: with the `sqlite` feature no usage is recorded
+//! and the reads come back empty.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::database::{self, DbPool};
+
+/// Usage event batch insert. Columns are bound as arrays and unnested, so
+/// batches of any size share one prepared statement (a VALUES list would
+/// prepare a new statement per batch size). Events already recorded for a
+/// request (replayed from the outbox) are skipped.
+#[cfg(not(feature = "sqlite"))]
+const INSERT_BATCH: &str = "INSERT INTO usage_events
+     (organization_id, api_key_id, product, event_type, tokens, requests, timestamp, request_id)
+     SELECT * FROM UNNEST($1::uuid[], $2::uuid[], $3::varchar[], $4::varchar[], $5::int4[], $6::int4[], $7::timestamptz[], $8::uuid[])
+     ON CONFLICT (request_id) WHERE request_id IS NOT NULL DO NOTHING";
+
+/// A usage event to record
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewUsageEvent {
+    pub organization_id: Uuid,
+    pub api_key_id: Uuid,
+    pub product: String,
+    pub event_type: String,
+    pub tokens: i32,
+    pub requests: i32,
+    pub timestamp: DateTime<Utc>,
+    pub request_id: Uuid,
+}
+
+/// Requests of one active API key
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct KeyRequests {
+    pub key_id: Uuid,
+    pub name: String,
+    pub requests: i64,
+}
+
+/// Usage of one product on one day
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct DailyUsage {
+    pub day: NaiveDate,
+    pub product: String,
+    pub requests: i64,
+    pub tokens: i64,
+}
+
+/// Usage of all products on one day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::FromRow)]
+pub struct DailyTotals {
+    pub day: NaiveDate,
+    pub requests: i64,
+    pub tokens: i64,
+}
+
+/// Usage of one API key on one day
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct DailyKeyUsage {
+    pub day: NaiveDate,
+    /// None for usage of keys that have since been deleted
+    pub key_id: Option<Uuid>,
+    pub name: Option<String>,
+    pub requests: i64,
+    pub tokens: i64,
+}
+
+/// Typed access to `usage_events`
+#[derive(Clone, Copy)]
+pub struct UsageEventsRepo<'a> {
+    pool: &'a DbPool,
+}
+
+impl<'a> UsageEventsRepo<'a> {
+    pub fn new(pool: &'a DbPool) -> Self {
+        Self { pool }
+    }
+
+    /// Insert events, skipping request ids already recorded; returns the
+    /// number of rows inserted
+    #[cfg(feature = "sqlite")]
+    pub async fn insert_batch(&self, events: &[NewUsageEvent]) -> sqlx::Result<u64> {
+        if !events.is_empty() {
+            super::warn_postgres_only("usage_events");
+        }
+        Ok(0)
+    }
+
+    /// Insert events, skipping request ids already recorded; returns the
+    /// number of rows inserted
+    #[cfg(not(feature = "sqlite"))]
+    pub async fn insert_batch(&self, events: &[NewUsageEvent]) -> sqlx::Result<u64> {
+        if events.is_empty() {
+            return Ok(0);
+        }
+
+        let count = events.len();
+        let mut organization_ids = Vec::with_capacity(count);
+        let mut api_key_ids = Vec::with_capacity(count);
+        let mut products = Vec::with_capacity(count);
+        let mut event_types = Vec::with_capacity(count);
+        let mut tokens = Vec::with_capacity(count);
+        let mut requests = Vec::with_capacity(count);
+        let mut timestamps = Vec::with_capacity(count);
+        let mut request_ids = Vec::with_capacity(count);
+        for event in events {
+            organization_ids.push(event.organization_id);
+            api_key_ids.push(event.api_key_id);
+            products.push(event.product.as_str());
+            event_types.push(event.event_type.as_str());
+            tokens.push(event.tokens);
+            requests.push(event.requests);
+            timestamps.push(event.timestamp);
+            request_ids.push(event.request_id);
+        }
+
+        let result = sqlx::query(INSERT_BATCH)
+            .persistent(true)
+            .bind(organization_ids)
+            .bind(api_key_ids)
+            .bind(products)
+            .bind(event_types)
+            .bind(tokens)
+            .bind(requests)
+            .bind(timestamps)
+            .bind(request_ids)
+            .execute(self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Requests and tokens of an organization since `since`
+    pub async fn totals_since(
+        &self,
+        org_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> sqlx::Result<(i64, i64)> {
+        sqlx::query_as::<_, (i64, i64)>(
+            "SELECT CAST(COALESCE(SUM(requests), 0) AS BIGINT), CAST(COALESCE(SUM(tokens), 0) AS BIGINT)
+             FROM usage_events
+             WHERE organization_id = $1 AND timestamp >= $2",
+        )
+        .bind(org_id)
+        .bind(database::timestamp(since))
+        .fetch_one(self.pool)
+        .await
+    }
+
+    /// Requests since `since` of each active API key of an organization
+    /// (keys without usage included)
+    pub async fn requests_per_active_key_since(
+        &self,
+        org_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> sqlx::Result<Vec<KeyRequests>> {
+        sqlx::query_as::<_, KeyRequests>(
+            "SELECT k.key_id, k.name, CAST(COALESCE(SUM(ue.requests), 0) AS BIGINT) AS requests
+             FROM api_keys k
+             LEFT JOIN usage_events ue
+               ON ue.api_key_id = k.key_id AND ue.organization_id = k.organization_id AND ue.timestamp >= $2
+             WHERE k.organization_id = $1 AND k.is_active = true
+             GROUP BY k.key_id, k.name
+             ORDER BY k.key_id",
+        )
+        .bind(org_id)
+        .bind(database::timestamp(since))
+        .fetch_all(self.pool)
+        .await
+    }
+
+    /// Requests and tokens of an organization per day since `since`
+    /// (all products)
+    pub async fn daily_totals_since(
+        &self,
+        org_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> sqlx::Result<Vec<DailyTotals>> {
+        sqlx::query_as::<_, DailyTotals>(
+            "SELECT DATE(timestamp) AS day,
+                    CAST(COALESCE(SUM(requests), 0) AS BIGINT) AS requests,
+                    CAST(COALESCE(SUM(tokens), 0) AS BIGINT) AS tokens
+             FROM usage_events
+             WHERE organization_id = $1 AND timestamp >= $2
+             GROUP BY DATE(timestamp)
+             ORDER BY day",
+        )
+        .bind(org_id)
+        .bind(database::timestamp(since))
+        .fetch_all(self.pool)
+        .await
+    }
+
+    /// Requests and tokens of an organization per day and API key, from
+    /// `start` (included) to `end` (excluded)
+    pub async fn daily_by_key_between(
+        &self,
+        org_id: Uuid,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> sqlx::Result<Vec<DailyKeyUsage>> {
+        sqlx::query_as::<_, DailyKeyUsage>(
+            "SELECT DATE(ue.timestamp) AS day, ue.api_key_id AS key_id, k.name,
+                    CAST(COALESCE(SUM(ue.requests), 0) AS BIGINT) AS requests,
+                    CAST(COALESCE(SUM(ue.tokens), 0) AS BIGINT) AS tokens
+             FROM usage_events ue
+             LEFT JOIN api_keys k ON k.key_id = ue.api_key_id
+             WHERE ue.organization_id = $1 AND ue.timestamp >= $2 AND ue.timestamp < $3
+             GROUP BY DATE(ue.timestamp), ue.api_key_id, k.name
+             ORDER BY day, tokens DESC, requests DESC",
+        )
+        .bind(org_id)
+        .bind(database::timestamp(start))
+        .bind(database::timestamp(end))
+        .fetch_all(self.pool)
+        .await
+    }
+
+    /// All of an organization's usage, per day and product
+    pub async fn daily_by_product(&self, org_id: Uuid) -> sqlx::Result<Vec<DailyUsage>> {
+        sqlx::query_as::<_, DailyUsage>(
+            "SELECT DATE(timestamp) AS day, product,
+                    CAST(COALESCE(SUM(requests), 0) AS BIGINT) AS requests,
+                    CAST(COALESCE(SUM(tokens), 0) AS BIGINT) AS tokens
+             FROM usage_events
+             WHERE organization_id = $1
+             GROUP BY DATE(timestamp), product
+             ORDER BY day, product",
+        )
+        .bind(org_id)
+        .fetch_all(self.pool)
+        .await
+    }
+}
+
+#[cfg(all(test, feature = "control-plane", not(feature = "sqlite")))]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::test_utils::factory;
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_insert_batch_and_totals() {
+        setup().await;
+        cleanup_db().await;
+        let repo = UsageEventsRepo::new(database::get_db());
+        let owner = factory::user("usage-events@example.com").await;
+        let key = factory::api_key(&owner).await;
+
+        let day = NaiveDate::from_ymd_opt(2025, 3, 14).unwrap();
+        let event = |tokens, hour| NewUsageEvent {
+            organization_id: key.org_id,
+            api_key_id: key.key_id,
+            product: "embeddings".to_string(),
+            event_type: "inference".to_string(),
+            tokens,
+            requests: 1,
+            timestamp: day.and_hms_opt(hour, 0, 0).unwrap().and_utc(),
+            request_id: Uuid::now_v7(),
+        };
+        let events = vec![event(3, 1), event(4, 2)];
+        assert_eq!(repo.insert_batch(&events).await.unwrap(), 2);
+
+        // A replayed batch only adds what is new
+        let replay = vec![events[1].clone(), event(5, 3)];
+        assert_eq!(repo.insert_batch(&replay).await.unwrap(), 1);
+        assert_eq!(repo.insert_batch(&[]).await.unwrap(), 0);
+
+        let since = day.and_hms_opt(2, 0, 0).unwrap().and_utc();
+        assert_eq!(repo.totals_since(key.org_id, since).await.unwrap(), (2, 9));
+        assert_eq!(
+            repo.requests_per_active_key_since(key.org_id, since)
+                .await
+                .unwrap(),
+            vec![KeyRequests {
+                key_id: key.key_id,
+                name: "Test API Key".to_string(),
+                requests: 2,
+            }]
+        );
+        assert_eq!(
+            repo.daily_by_product(key.org_id).await.unwrap(),
+            vec![DailyUsage {
+                day,
+                product: "embeddings".to_string(),
+                requests: 3,
+                tokens: 12,
+            }]
+        );
+        assert_eq!(
+            repo.daily_totals_since(key.org_id, since).await.unwrap(),
+            vec![DailyTotals {
+                day,
+                requests: 2,
+                tokens: 9,
+            }]
+        );
+
+        cleanup_db().await;
+    }
+}
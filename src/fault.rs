@@ -0,0 +1,400 @@
+//! Fault injection for dependency failure testing (`fault-injection`
+//! feature, debug builds only)
+//!
+//! Admin endpoints inject latency and/or errors into one dependency at a
+//! time, for a limited duration and with a probability per call:
+//!
+//! - `redis`: every command of the shared connection (`redis_util`)
+//! - `postgres`: pool acquires and new connections (`database::connect`)
+//! - `inference`: model runs, through the `InferenceSession` seam
+//!
+//! `PUT /v1/admin/faults/:target` installs a fault, `DELETE` removes it
+//! (or all of them) and `GET /v1/admin/faults` lists the active ones. The
+//! fault endpoints themselves are exempt, so a Redis fault can't lock the
+//! admin out of clearing it.
+//!
+//! None of this exists without the cargo feature, and the feature refuses to
+//! compile in release builds.
+
+#[cfg(not(debug_assertions))]
+compile_error!("the `fault-injection` feature is only for debug builds");
+
+use axum::{
+    extract::{Path, Request},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::api::error::ApiError;
+use crate::auth::AdminTokenClaims;
+
+/// Longest a fault may stay installed
+pub const MAX_FAULT_SECS: u64 = 3600;
+
+/// A dependency faults can be injected into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    Redis,
+    Postgres,
+    Inference,
+}
+
+impl Target {
+    pub const ALL: [Target; 3] = [Target::Redis, Target::Postgres, Target::Inference];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Target::Redis => "redis",
+            Target::Postgres => "postgres",
+            Target::Inference => "inference",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Target::ALL.into_iter().find(|t| t.as_str() == name)
+    }
+}
+
+/// Request body for `PUT /v1/admin/faults/:target`
+#[derive(Debug, Clone, Deserialize)]
+pub struct FaultSpec {
+    /// Delay added to each affected call
+    #[serde(default)]
+    pub latency_ms: u64,
+    /// Fail affected calls (after the delay)
+    #[serde(default)]
+    pub error: bool,
+    /// Share of calls affected (0 to 1)
+    #[serde(default = "default_probability")]
+    pub probability: f64,
+    /// How long the fault stays installed
+    pub duration_secs: u64,
+}
+
+fn default_probability() -> f64 {
+    1.0
+}
+
+impl FaultSpec {
+    fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.probability) {
+            return Err("probability must be between 0 and 1".to_string());
+        }
+        if self.duration_secs == 0 || self.duration_secs > MAX_FAULT_SECS {
+            return Err(format!(
+                "duration_secs must be between 1 and {}",
+                MAX_FAULT_SECS
+            ));
+        }
+        if self.latency_ms == 0 && !self.error {
+            return Err("a fault needs latency_ms, error or both".to_string());
+        }
+        Ok(())
+    }
+}
+
+struct Fault {
+    spec: FaultSpec,
+    until: Instant,
+}
+
+/// An installed fault, as listed by `GET /v1/admin/faults`
+#[derive(Debug, Serialize)]
+pub struct FaultStatus {
+    pub target: Target,
+    pub latency_ms: u64,
+    pub error: bool,
+    pub probability: f64,
+    pub remaining_secs: u64,
+}
+
+static FAULTS: Lazy<RwLock<HashMap<Target, Fault>>> = Lazy::new(Default::default);
+
+tokio::task_local! {
+    /// Set while serving the fault endpoints
+    static EXEMPT: ();
+}
+
+/// Install a fault on `target`, replacing any previous one
+pub fn inject(target: Target, spec: FaultSpec) -> Result<(), String> {
+    spec.validate()?;
+    let until = Instant::now() + Duration::from_secs(spec.duration_secs);
+    FAULTS.write().insert(target, Fault { spec, until });
+    Ok(())
+}
+
+/// Remove the fault on `target`; false if there was none
+pub fn clear(target: Target) -> bool {
+    FAULTS.write().remove(&target).is_some()
+}
+
+pub fn clear_all() {
+    FAULTS.write().clear();
+}
+
+/// Faults still in effect
+pub fn active() -> Vec<FaultStatus> {
+    let now = Instant::now();
+    let mut faults: Vec<FaultStatus> = FAULTS
+        .read()
+        .iter()
+        .filter(|(_, fault)| fault.until > now)
+        .map(|(target, fault)| FaultStatus {
+            target: *target,
+            latency_ms: fault.spec.latency_ms,
+            error: fault.spec.error,
+            probability: fault.spec.probability,
+            remaining_secs: (fault.until - now).as_secs(),
+        })
+        .collect();
+    faults.sort_by_key(|f| f.target.as_str());
+    faults
+}
+
+/// What an affected call has to suffer, if the dice say it's affected
+fn roll(target: Target) -> Option<(Duration, bool)> {
+    if EXEMPT.try_with(|_| ()).is_ok() {
+        return None;
+    }
+
+    let now = Instant::now();
+    let (latency_ms, error, probability) = {
+        let faults = FAULTS.read();
+        let fault = faults.get(&target).filter(|fault| fault.until > now)?;
+        (
+            fault.spec.latency_ms,
+            fault.spec.error,
+            fault.spec.probability,
+        )
+    };
+
+    rand::thread_rng()
+        .gen_bool(probability)
+        .then(|| (Duration::from_millis(latency_ms), error))
+}
+
+/// Error returned by an injected failure
+#[derive(Debug)]
+pub struct InjectedFault(pub Target);
+
+impl std::fmt::Display for InjectedFault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "injected {} fault", self.0.as_str())
+    }
+}
+
+impl std::error::Error for InjectedFault {}
+
+impl From<InjectedFault> for std::io::Error {
+    fn from(fault: InjectedFault) -> Self {
+        std::io::Error::new(std::io::ErrorKind::ConnectionRefused, fault)
+    }
+}
+
+/// Apply the fault on `target` (if any) to an async call
+pub async fn apply(target: Target) -> Result<(), InjectedFault> {
+    match roll(target) {
+        None => Ok(()),
+        Some((latency, error)) => {
+            if !latency.is_zero() {
+                tokio::time::sleep(latency).await;
+            }
+            if error {
+                Err(InjectedFault(target))
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Apply the fault on `target` (if any) to a blocking call
+pub fn apply_blocking(target: Target) -> Result<(), InjectedFault> {
+    match roll(target) {
+        None => Ok(()),
+        Some((latency, error)) => {
+            if !latency.is_zero() {
+                std::thread::sleep(latency);
+            }
+            if error {
+                Err(InjectedFault(target))
+            } else {
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Model session with the `inference` fault applied before each run
+#[cfg(feature = "inference")]
+pub struct FaultySession(pub Box<dyn crate::inference::session::InferenceSession>);
+
+#[cfg(feature = "inference")]
+impl crate::inference::session::InferenceSession for FaultySession {
+    fn run_batch(
+        &mut self,
+        encodings: &[crate::inference::tokenizer::Encoding],
+    ) -> anyhow::Result<crate::inference::session::SessionOutput> {
+        apply_blocking(Target::Inference)?;
+        self.0.run_batch(encodings)
+    }
+}
+
+/// Serve the wrapped routes without faults
+pub async fn exempt_middleware(request: Request, next: Next) -> Response {
+    EXEMPT.scope((), next.run(request)).await
+}
+
+fn parse_target(name: &str) -> Result<Target, ApiError> {
+    Target::parse(name).ok_or_else(|| {
+        let known: Vec<&str> = Target::ALL.iter().map(|t| t.as_str()).collect();
+        ApiError::BadRequest(format!(
+            "Unknown fault target '{}' (known: {})",
+            name,
+            known.join(", ")
+        ))
+    })
+}
+
+/// Active faults (admin token required)
+pub async fn admin_list_faults_handler(_admin_token: AdminTokenClaims) -> Response {
+    (StatusCode::OK, Json(active())).into_response()
+}
+
+/// Install a fault (admin token required)
+pub async fn admin_inject_fault_handler(
+    admin_token: AdminTokenClaims,
+    Path(target): Path<String>,
+    Json(spec): Json<FaultSpec>,
+) -> Result<Response, ApiError> {
+    let target = parse_target(&target)?;
+    inject(target, spec.clone()).map_err(ApiError::BadRequest)?;
+
+    tracing::warn!(
+        target: "audit",
+        action = "fault.inject",
+        fault_target = target.as_str(),
+        latency_ms = spec.latency_ms,
+        error = spec.error,
+        probability = spec.probability,
+        duration_secs = spec.duration_secs,
+        injected_by = %admin_token.token_id(),
+        "Fault injected"
+    );
+
+    Ok((StatusCode::OK, Json(active())).into_response())
+}
+
+/// Remove the fault on one target (admin token required)
+pub async fn admin_clear_fault_handler(
+    admin_token: AdminTokenClaims,
+    Path(target): Path<String>,
+) -> Result<Response, ApiError> {
+    let target = parse_target(&target)?;
+    if !clear(target) {
+        return Err(ApiError::NotFound(format!(
+            "No fault on {}",
+            target.as_str()
+        )));
+    }
+
+    tracing::warn!(
+        target: "audit",
+        action = "fault.clear",
+        fault_target = target.as_str(),
+        cleared_by = %admin_token.token_id(),
+        "Fault cleared"
+    );
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Remove every fault (admin token required)
+pub async fn admin_clear_all_faults_handler(admin_token: AdminTokenClaims) -> Response {
+    clear_all();
+    tracing::warn!(
+        target: "audit",
+        action = "fault.clear",
+        fault_target = "all",
+        cleared_by = %admin_token.token_id(),
+        "Faults cleared"
+    );
+    StatusCode::NO_CONTENT.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+
+    fn spec(latency_ms: u64, error: bool, probability: f64) -> FaultSpec {
+        FaultSpec {
+            latency_ms,
+            error,
+            probability,
+            duration_secs: 30,
+        }
+    }
+
+    #[test]
+    fn test_spec_validation() {
+        assert!(spec(100, false, 1.0).validate().is_ok());
+        assert!(spec(0, true, 0.5).validate().is_ok());
+        assert!(spec(0, false, 1.0).validate().is_err());
+        assert!(spec(100, false, 1.5).validate().is_err());
+        assert!(FaultSpec {
+            duration_secs: MAX_FAULT_SECS + 1,
+            ..spec(100, false, 1.0)
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_apply_latency_and_error() {
+        clear_all();
+        assert!(apply(Target::Redis).await.is_ok());
+
+        inject(Target::Redis, spec(50, true, 1.0)).unwrap();
+        let started = Instant::now();
+        let err = apply(Target::Redis).await.unwrap_err();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(err.to_string(), "injected redis fault");
+
+        // Other targets are untouched, and the fault endpoints are exempt
+        assert!(apply_blocking(Target::Postgres).is_ok());
+        assert!(EXEMPT.scope((), apply(Target::Redis)).await.is_ok());
+
+        assert_eq!(active().len(), 1);
+        assert!(clear(Target::Redis));
+        assert!(!clear(Target::Redis));
+        assert!(apply(Target::Redis).await.is_ok());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_probability_and_expiry() {
+        clear_all();
+        inject(Target::Inference, spec(0, true, 0.0)).unwrap();
+        for _ in 0..100 {
+            assert!(apply_blocking(Target::Inference).is_ok());
+        }
+
+        FAULTS.write().get_mut(&Target::Inference).unwrap().until = Instant::now();
+        inject(Target::Postgres, spec(0, true, 1.0)).unwrap();
+        FAULTS.write().get_mut(&Target::Postgres).unwrap().until = Instant::now();
+        assert!(apply(Target::Postgres).await.is_ok());
+        assert!(active().is_empty());
+        clear_all();
+    }
+}
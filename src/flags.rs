@@ -0,0 +1,299 @@
+//! Feature flags for dark launches
+//!
+//! Each flag has a deployment-wide mode, set with `FEATURE_<FLAG>`
+//! (e.g. `FEATURE_BATCH_API=allowlist`):
+//!
+//! - `on`: enabled for every organization
+//! - `off`: disabled; gated endpoints answer 404 `feature_not_enabled`
+//! - `allowlist`: enabled for organizations that list the flag in their
+//!   `feature_flags` setting (`PUT /v1/admin/organizations/:org_id/flags`);
+//!   others get 403 `feature_not_enabled`
+//!
+//! Handlers call `check(flag, org_id)` before exposing the new behavior.
+//! Organization allowlists are read on every gated request, so they're
+//! cached in process like the model settings (see `inference::catalog`).
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::database::DbPool;
+use crate::{config, database};
+
+/// How long an organization's flags are trusted before hitting the
+/// database again
+const FLAGS_CACHE_TTL_SECS: u64 = 30;
+
+/// A feature that can be dark-launched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Flag {
+    /// Batch embedding endpoint
+    BatchApi,
+    /// `GET /v1/embed/ws`
+    WebsocketEmbed,
+}
+
+impl Flag {
+    pub const ALL: [Flag; 2] = [Flag::BatchApi, Flag::WebsocketEmbed];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Flag::BatchApi => "batch_api",
+            Flag::WebsocketEmbed => "websocket_embed",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|f| f.as_str() == name)
+    }
+
+    /// Setting holding the flag's mode
+    pub fn env_var(self) -> String {
+        format!("FEATURE_{}", self.as_str().to_uppercase())
+    }
+
+    /// Mode when the setting is absent or invalid
+    pub fn default_mode(self) -> FlagMode {
+        match self {
+            Flag::BatchApi => FlagMode::Off,
+            Flag::WebsocketEmbed => FlagMode::On,
+        }
+    }
+
+    /// Deployment-wide mode
+    pub fn mode(self) -> FlagMode {
+        config::get_settings()
+            .feature_flags
+            .get(&self)
+            .copied()
+            .unwrap_or_else(|| self.default_mode())
+    }
+}
+
+impl fmt::Display for Flag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Deployment-wide state of a flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlagMode {
+    On,
+    Off,
+    Allowlist,
+}
+
+impl FlagMode {
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.trim().to_ascii_lowercase().as_str() {
+            "on" => Some(FlagMode::On),
+            "off" => Some(FlagMode::Off),
+            "allowlist" => Some(FlagMode::Allowlist),
+            _ => None,
+        }
+    }
+}
+
+/// A gated feature the caller's organization can't use
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotEnabled {
+    pub flag: Flag,
+    pub mode: FlagMode,
+}
+
+impl NotEnabled {
+    /// 404 while the feature is off everywhere, 403 while it's limited to
+    /// other organizations
+    pub fn status(&self) -> axum::http::StatusCode {
+        match self.mode {
+            FlagMode::Allowlist => axum::http::StatusCode::FORBIDDEN,
+            FlagMode::On | FlagMode::Off => axum::http::StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+impl fmt::Display for NotEnabled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            FlagMode::Allowlist => write!(
+                f,
+                "Feature '{}' is not enabled for this organization",
+                self.flag
+            ),
+            FlagMode::On | FlagMode::Off => {
+                write!(f, "Feature '{}' is not enabled", self.flag)
+            }
+        }
+    }
+}
+
+/// Whether `flag` is enabled for an organization whose allowlist is
+/// `org_flags`
+fn decide(flag: Flag, mode: FlagMode, org_flags: &[String]) -> Result<(), NotEnabled> {
+    let enabled = match mode {
+        FlagMode::On => true,
+        FlagMode::Off => false,
+        FlagMode::Allowlist => org_flags.iter().any(|f| f == flag.as_str()),
+    };
+    if enabled {
+        Ok(())
+    } else {
+        Err(NotEnabled { flag, mode })
+    }
+}
+
+/// Whether `flag` is enabled for the organization
+pub async fn is_enabled(flag: Flag, org_id: Uuid) -> bool {
+    check(flag, org_id).await.is_ok()
+}
+
+/// Ok if `flag` is enabled for the organization
+pub async fn check(flag: Flag, org_id: Uuid) -> Result<(), NotEnabled> {
+    let mode = flag.mode();
+    let org_flags = match mode {
+        FlagMode::Allowlist => org_flags(org_id).await,
+        FlagMode::On | FlagMode::Off => Vec::new(),
+    };
+    decide(flag, mode, &org_flags)
+}
+
+/// org_id -> (flags, checked_at)
+static FLAGS_CACHE: Lazy<DashMap<Uuid, (Vec<String>, Instant)>> = Lazy::new(DashMap::new);
+
+/// Flags the organization is allowlisted for. Lookup failures fall back to
+/// the last known flags, else to none: a dark-launched feature stays dark
+/// while the database is unreachable.
+pub async fn org_flags(org_id: Uuid) -> Vec<String> {
+    if let Some(entry) = FLAGS_CACHE.get(&org_id) {
+        let (flags, checked_at) = &*entry;
+        if checked_at.elapsed().as_secs() < FLAGS_CACHE_TTL_SECS {
+            return flags.clone();
+        }
+    }
+
+    let row = sqlx::query_scalar::<_, database::List<String>>(
+        "SELECT feature_flags FROM organizations WHERE id = $1",
+    )
+    .bind(org_id)
+    .fetch_optional(database::get_db())
+    .await;
+
+    match row {
+        Ok(flags) => {
+            let flags = flags.map(database::into_vec).unwrap_or_default();
+            FLAGS_CACHE.insert(org_id, (flags.clone(), Instant::now()));
+            flags
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load feature flags of {}: {}", org_id, e);
+            FLAGS_CACHE
+                .get(&org_id)
+                .map(|entry| entry.0.clone())
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// Replace an organization's allowlisted flags. False if there's no such
+/// organization.
+pub async fn set_org_flags(pool: &DbPool, org_id: Uuid, flags: &[Flag]) -> sqlx::Result<bool> {
+    let mut names: Vec<&str> = flags.iter().map(|f| f.as_str()).collect();
+    names.sort();
+    names.dedup();
+
+    let result = sqlx::query("UPDATE organizations SET feature_flags = $2 WHERE id = $1")
+        .bind(org_id)
+        .bind(database::list(&names))
+        .execute(pool)
+        .await?;
+
+    FLAGS_CACHE.remove(&org_id);
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(not(feature = "sqlite"))]
+const OVERRIDES: &str = "SELECT id, feature_flags FROM organizations
+     WHERE cardinality(feature_flags) > 0
+     ORDER BY id";
+#[cfg(feature = "sqlite")]
+const OVERRIDES: &str = "SELECT id, feature_flags FROM organizations
+     WHERE json_array_length(feature_flags) > 0
+     ORDER BY id";
+
+/// Organizations allowlisted for any flag, with their flags
+pub async fn overrides(pool: &DbPool) -> sqlx::Result<Vec<(Uuid, Vec<String>)>> {
+    let rows = sqlx::query_as::<_, (Uuid, database::List<String>)>(OVERRIDES)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(id, flags)| (id, database::into_vec(flags)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::factory;
+    #[cfg(feature = "control-plane")]
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    #[cfg(feature = "control-plane")]
+    use serial_test::serial;
+
+    #[test]
+    fn test_names_and_modes() {
+        assert_eq!(Flag::BatchApi.env_var(), "FEATURE_BATCH_API");
+        assert_eq!(Flag::parse("websocket_embed"), Some(Flag::WebsocketEmbed));
+        assert_eq!(Flag::parse("teleport"), None);
+        assert_eq!(FlagMode::parse(" Allowlist"), Some(FlagMode::Allowlist));
+        assert_eq!(FlagMode::parse("maybe"), None);
+    }
+
+    #[test]
+    fn test_decide() {
+        let listed = vec!["batch_api".to_string()];
+
+        assert!(decide(Flag::BatchApi, FlagMode::On, &[]).is_ok());
+        assert!(decide(Flag::BatchApi, FlagMode::Allowlist, &listed).is_ok());
+
+        let off = decide(Flag::BatchApi, FlagMode::Off, &listed).unwrap_err();
+        assert_eq!(off.status(), axum::http::StatusCode::NOT_FOUND);
+        assert_eq!(off.to_string(), "Feature 'batch_api' is not enabled");
+
+        let unlisted = decide(Flag::WebsocketEmbed, FlagMode::Allowlist, &listed).unwrap_err();
+        assert_eq!(unlisted.status(), axum::http::StatusCode::FORBIDDEN);
+        assert!(unlisted.to_string().contains("for this organization"));
+    }
+
+    #[cfg(feature = "control-plane")]
+    #[tokio::test]
+    #[serial]
+    async fn test_org_allowlist() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let org_id = factory::user("flags@example.com").await.org_id;
+
+        assert!(org_flags(org_id).await.is_empty());
+        assert!(
+            set_org_flags(pool, org_id, &[Flag::BatchApi, Flag::BatchApi])
+                .await
+                .unwrap()
+        );
+        assert_eq!(org_flags(org_id).await, vec!["batch_api".to_string()]);
+        assert_eq!(
+            overrides(pool).await.unwrap(),
+            vec![(org_id, vec!["batch_api".to_string()])]
+        );
+
+        assert!(!set_org_flags(pool, Uuid::now_v7(), &[]).await.unwrap());
+        cleanup_db().await;
+    }
+}
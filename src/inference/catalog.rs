@@ -0,0 +1,350 @@
+//! Models this server offers, and which of them a caller may use
+//!
+//! Models are identified by the last path segment of their name (e.g.
+//! `all-MiniLM-L6-v2`). Embed responses report the model with its revision
+//! when one is configured (`all-MiniLM-L6-v2@2024-01`), so stored vectors
+//! can be traced to the model files that produced them. Access is
+//! narrowed in two steps: an organization may limit its keys to
+//! `allowed_models`, and a key may be restricted further when it's created
+//! (its `models` claim). Requests that don't name a model get the
+//! organization's `default_model`, else the server's.
+//!
+//! The organization settings are read on every embed request, so they're
+//! cached in process like the cache policy (see `cache::policy`).
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use std::fmt;
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::{config, database};
+
+/// How long an organization's model settings are trusted before hitting
+/// the database again
+const SETTINGS_CACHE_TTL_SECS: u64 = 30;
+
+/// A model served by this instance
+#[derive(Debug, Clone)]
+pub struct ModelInfo {
+    /// Model identifier, as accepted in `model` and returned by `/v1/embed`
+    pub id: String,
+    /// Length of the embedding vectors
+    pub dimensions: usize,
+    /// Model's own token ceiling
+    pub max_tokens: usize,
+    /// Upstream license (SPDX identifier)
+    pub license: Option<String>,
+    /// Where the model comes from
+    pub source_url: Option<String>,
+    /// Revision of the served model files
+    pub revision: Option<String>,
+}
+
+impl ModelInfo {
+    /// Identifier with the revision, as reported in embed responses
+    pub fn versioned_id(&self) -> String {
+        with_revision(&self.id, self.revision.as_deref())
+    }
+}
+
+/// Identifier of a model name (`org/name` -> `name`)
+pub fn model_id(name: &str) -> &str {
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+/// `name@revision`, or `name` without a revision
+pub fn with_revision(name: &str, revision: Option<&str>) -> String {
+    match revision {
+        Some(revision) => format!("{}@{}", name, revision),
+        None => name.to_string(),
+    }
+}
+
+/// Models served by this instance; the first one is the server default
+pub fn available() -> Vec<ModelInfo> {
+    let settings = config::get_settings();
+    vec![ModelInfo {
+        id: model_id(&settings.model_name).to_string(),
+        dimensions: settings.embedding_dim,
+        max_tokens: settings.max_tokens,
+        license: settings.model_license.clone(),
+        source_url: Some(
+            settings
+                .model_source_url
+                .clone()
+                .unwrap_or_else(|| format!("https://huggingface.co/{}", settings.model_name)),
+        ),
+        revision: settings.model_revision.clone(),
+    }]
+}
+
+/// Identifiers of the served models
+pub fn available_ids() -> Vec<String> {
+    available().into_iter().map(|m| m.id).collect()
+}
+
+/// An organization's model settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrgModels {
+    /// Models the organization's keys may use (None = all)
+    pub allowed: Option<Vec<String>>,
+    /// Model for requests that don't name one (None = the server default)
+    pub default: Option<String>,
+}
+
+/// Why a request can't use a model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelError {
+    /// No such model on this server
+    Unknown {
+        model: String,
+        available: Vec<String>,
+    },
+    /// The organization or key doesn't allow it
+    NotAllowed {
+        model: String,
+        permitted: Vec<String>,
+    },
+    /// The organization's and key's restrictions leave no served model
+    NonePermitted,
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::Unknown { model, available } => write!(
+                f,
+                "Unknown model '{}' (available: {})",
+                model,
+                available.join(", ")
+            ),
+            ModelError::NotAllowed { model, permitted } => write!(
+                f,
+                "Model '{}' is not allowed for this API key (permitted: {})",
+                model,
+                permitted.join(", ")
+            ),
+            ModelError::NonePermitted => {
+                write!(
+                    f,
+                    "No model served by this instance is allowed for this API key"
+                )
+            }
+        }
+    }
+}
+
+/// Of `available`, the models a key restricted to `key_models` in an
+/// organization with `org` settings may use (in `available` order)
+pub fn permitted(
+    available: &[String],
+    org: &OrgModels,
+    key_models: Option<&[String]>,
+) -> Vec<String> {
+    available
+        .iter()
+        .filter(|id| {
+            org.allowed
+                .as_ref()
+                .is_none_or(|allowed| allowed.contains(id))
+        })
+        .filter(|id| key_models.is_none_or(|models| models.contains(id)))
+        .cloned()
+        .collect()
+}
+
+/// The model a request should use: the requested one if permitted, else
+/// (when none is named) the organization's default, the server default or
+/// the first permitted model, in that order
+pub fn resolve(
+    requested: Option<&str>,
+    available: &[String],
+    org: &OrgModels,
+    key_models: Option<&[String]>,
+) -> Result<String, ModelError> {
+    let permitted = permitted(available, org, key_models);
+
+    let Some(requested) = requested else {
+        return org
+            .default
+            .iter()
+            .chain(available.first())
+            .find(|id| permitted.contains(id))
+            .or(permitted.first())
+            .cloned()
+            .ok_or(ModelError::NonePermitted);
+    };
+
+    if !available.iter().any(|id| id == requested) {
+        return Err(ModelError::Unknown {
+            model: requested.to_string(),
+            available: available.to_vec(),
+        });
+    }
+    if !permitted.iter().any(|id| id == requested) {
+        return Err(ModelError::NotAllowed {
+            model: requested.to_string(),
+            permitted,
+        });
+    }
+    Ok(requested.to_string())
+}
+
+/// org_id -> (settings, checked_at)
+static SETTINGS_CACHE: Lazy<DashMap<Uuid, (OrgModels, Instant)>> = Lazy::new(DashMap::new);
+
+/// Model settings of the organization. Lookup failures fall back to the
+/// last known settings, else to no restriction: model restrictions are for
+/// cost control, and an outage shouldn't reject every request.
+pub async fn org_models(org_id: Uuid) -> OrgModels {
+    if let Some(entry) = SETTINGS_CACHE.get(&org_id) {
+        let (settings, checked_at) = &*entry;
+        if checked_at.elapsed().as_secs() < SETTINGS_CACHE_TTL_SECS {
+            return settings.clone();
+        }
+    }
+
+    let row = sqlx::query_as::<_, (Option<database::List<String>>, Option<String>)>(
+        "SELECT allowed_models, default_model FROM organizations WHERE id = $1",
+    )
+    .bind(org_id)
+    .fetch_optional(database::get_db())
+    .await;
+
+    match row {
+        Ok(row) => {
+            let settings = row
+                .map(|(allowed, default)| OrgModels {
+                    allowed: allowed.map(database::into_vec),
+                    default,
+                })
+                .unwrap_or_default();
+            SETTINGS_CACHE.insert(org_id, (settings.clone(), Instant::now()));
+            settings
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load model settings of {}: {}", org_id, e);
+            SETTINGS_CACHE
+                .get(&org_id)
+                .map(|entry| entry.0.clone())
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// Forget cached settings (call after changing them)
+pub fn invalidate(org_id: Uuid) {
+    SETTINGS_CACHE.remove(&org_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_model_id_strips_owner() {
+        assert_eq!(
+            model_id("sentence-transformers/all-MiniLM-L6-v2"),
+            "all-MiniLM-L6-v2"
+        );
+        assert_eq!(model_id("local-model"), "local-model");
+    }
+
+    #[test]
+    fn test_versioned_id() {
+        let mut info = ModelInfo {
+            id: "all-MiniLM-L6-v2".to_string(),
+            dimensions: 384,
+            max_tokens: 128,
+            license: Some("Apache-2.0".to_string()),
+            source_url: None,
+            revision: None,
+        };
+        assert_eq!(info.versioned_id(), "all-MiniLM-L6-v2");
+        info.revision = Some("2024-01".to_string());
+        assert_eq!(info.versioned_id(), "all-MiniLM-L6-v2@2024-01");
+    }
+
+    #[test]
+    fn test_unrestricted_defaults_to_server_default() {
+        let available = ids(&["small", "large"]);
+        let org = OrgModels::default();
+        assert_eq!(resolve(None, &available, &org, None).unwrap(), "small");
+        assert_eq!(
+            resolve(Some("large"), &available, &org, None).unwrap(),
+            "large"
+        );
+        assert_eq!(
+            resolve(Some("huge"), &available, &org, None),
+            Err(ModelError::Unknown {
+                model: "huge".to_string(),
+                available: available.clone(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_org_default_and_allowed_models() {
+        let available = ids(&["small", "large", "multilingual"]);
+        let org = OrgModels {
+            allowed: Some(ids(&["large", "multilingual"])),
+            default: Some("multilingual".to_string()),
+        };
+        assert_eq!(
+            resolve(None, &available, &org, None).unwrap(),
+            "multilingual"
+        );
+
+        let err = resolve(Some("small"), &available, &org, None).unwrap_err();
+        assert_eq!(
+            err,
+            ModelError::NotAllowed {
+                model: "small".to_string(),
+                permitted: ids(&["large", "multilingual"]),
+            }
+        );
+        assert!(err.to_string().contains("permitted: large, multilingual"));
+
+        // Without an org default, the first permitted model
+        let org = OrgModels {
+            default: None,
+            ..org
+        };
+        assert_eq!(resolve(None, &available, &org, None).unwrap(), "large");
+    }
+
+    #[test]
+    fn test_key_restriction_narrows_org() {
+        let available = ids(&["small", "large"]);
+        let org = OrgModels {
+            allowed: None,
+            default: Some("small".to_string()),
+        };
+        let key = ids(&["large"]);
+
+        assert_eq!(permitted(&available, &org, Some(&key)), ids(&["large"]));
+        // The org default isn't permitted for this key, so it gets its own
+        assert_eq!(
+            resolve(None, &available, &org, Some(&key)).unwrap(),
+            "large"
+        );
+        assert!(matches!(
+            resolve(Some("small"), &available, &org, Some(&key)),
+            Err(ModelError::NotAllowed { .. })
+        ));
+
+        let org = OrgModels {
+            allowed: Some(ids(&["small"])),
+            default: None,
+        };
+        assert_eq!(
+            resolve(None, &available, &org, Some(&key)),
+            Err(ModelError::NonePermitted)
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! CPU detection and per-architecture ONNX Runtime thread defaults
+//!
+//! Detected once at startup and logged, so a slow instance can be told
+//! apart from one missing AVX2 or running on a small ARM core count.
+
+use std::fmt;
+
+/// What the server is running on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CpuInfo {
+    pub arch: &'static str,
+    /// SIMD extensions relevant to inference that this CPU supports
+    pub features: Vec<&'static str>,
+    /// Logical cores available to the process
+    pub cores: usize,
+}
+
+impl CpuInfo {
+    pub fn detect() -> Self {
+        CpuInfo {
+            arch: std::env::consts::ARCH,
+            features: detect_features(),
+            cores: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+impl fmt::Display for CpuInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let features = if self.features.is_empty() {
+            "none".to_string()
+        } else {
+            self.features.join(",")
+        };
+        write!(
+            f,
+            "{}, {} cores, features: {}",
+            self.arch, self.cores, features
+        )
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn detect_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if is_x86_feature_detected!("sse4.2") {
+        features.push("sse4.2");
+    }
+    if is_x86_feature_detected!("avx") {
+        features.push("avx");
+    }
+    if is_x86_feature_detected!("avx2") {
+        features.push("avx2");
+    }
+    if is_x86_feature_detected!("fma") {
+        features.push("fma");
+    }
+    if is_x86_feature_detected!("avx512f") {
+        features.push("avx512f");
+    }
+    if is_x86_feature_detected!("avx512vnni") {
+        features.push("avx512vnni");
+    }
+    features
+}
+
+#[cfg(target_arch = "aarch64")]
+fn detect_features() -> Vec<&'static str> {
+    use std::arch::is_aarch64_feature_detected;
+
+    let mut features = Vec::new();
+    if is_aarch64_feature_detected!("neon") {
+        features.push("neon");
+    }
+    if is_aarch64_feature_detected!("fp16") {
+        features.push("fp16");
+    }
+    if is_aarch64_feature_detected!("dotprod") {
+        features.push("dotprod");
+    }
+    if is_aarch64_feature_detected!("i8mm") {
+        features.push("i8mm");
+    }
+    if is_aarch64_feature_detected!("bf16") {
+        features.push("bf16");
+    }
+    if is_aarch64_feature_detected!("sve") {
+        features.push("sve");
+    }
+    features
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn detect_features() -> Vec<&'static str> {
+    Vec::new()
+}
+
+/// ONNX Runtime thread pool sizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadConfig {
+    /// Threads used inside one operator (matmuls)
+    pub intra: usize,
+    /// Threads running independent graph branches
+    pub inter: usize,
+}
+
+impl ThreadConfig {
+    /// One thread per pool: operators reduce in a fixed order, so output is
+    /// bitwise reproducible (used by `DETERMINISTIC_INFERENCE`)
+    pub const DETERMINISTIC: ThreadConfig = ThreadConfig { intra: 1, inter: 1 };
+
+    /// Defaults for `cpu`, with non-zero overrides taking precedence
+    pub fn resolve(cpu: &CpuInfo, intra_override: usize, inter_override: usize) -> Self {
+        let defaults = Self::defaults(cpu.arch, cpu.cores);
+        ThreadConfig {
+            intra: if intra_override > 0 {
+                intra_override
+            } else {
+                defaults.intra
+            },
+            inter: if inter_override > 0 {
+                inter_override
+            } else {
+                defaults.inter
+            },
+        }
+    }
+
+    /// x86 cores are usually hyperthreaded and shared with the async
+    /// runtime, so intra-op threads stop at 4. ARM server cores (Graviton,
+    /// Ampere) are physical and scale further, and the sequential BERT graph
+    /// gains nothing from a second inter-op thread there.
+    pub fn defaults(arch: &str, cores: usize) -> Self {
+        let cores = cores.max(1);
+        match arch {
+            "aarch64" => ThreadConfig {
+                intra: cores.min(8),
+                inter: 1,
+            },
+            _ => ThreadConfig {
+                intra: cores.min(4),
+                inter: 2,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ThreadConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} intra-op, {} inter-op", self.intra, self.inter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thread_defaults_per_arch() {
+        assert_eq!(
+            ThreadConfig::defaults("x86_64", 16),
+            ThreadConfig { intra: 4, inter: 2 }
+        );
+        assert_eq!(
+            ThreadConfig::defaults("x86_64", 2),
+            ThreadConfig { intra: 2, inter: 2 }
+        );
+        assert_eq!(
+            ThreadConfig::defaults("aarch64", 64),
+            ThreadConfig { intra: 8, inter: 1 }
+        );
+        assert_eq!(
+            ThreadConfig::defaults("aarch64", 0),
+            ThreadConfig { intra: 1, inter: 1 }
+        );
+    }
+
+    #[test]
+    fn test_overrides_take_precedence() {
+        let cpu = CpuInfo {
+            arch: "aarch64",
+            features: vec!["neon"],
+            cores: 4,
+        };
+        assert_eq!(
+            ThreadConfig::resolve(&cpu, 0, 0),
+            ThreadConfig { intra: 4, inter: 1 }
+        );
+        assert_eq!(
+            ThreadConfig::resolve(&cpu, 2, 3),
+            ThreadConfig { intra: 2, inter: 3 }
+        );
+        assert_eq!(cpu.to_string(), "aarch64, 4 cores, features: neon");
+    }
+}
@@ -0,0 +1,239 @@
+//! Inference queue and load shedding
+//!
+//! Cache misses take a place in the queue before waiting for the model, and
+//! give it back once inference is done. When `MAX_INFERENCE_QUEUE` requests
+//! are already queued or running, new ones are shed with a 503 instead of
+//! piling up behind the model lock.
+//!
+//! A bare 503 makes every shed client retry at once, so the response says
+//! how long to wait: the queue depth times the average inference latency
+//! (an EWMA of recent inferences), jittered per response so retries spread
+//! out. Shed request ids are remembered for a while; when a client retries
+//! with `X-Smally-Retry-Of: <request_id>` and succeeds, the actual wait is
+//! compared with the suggestion in `smally_shed_wait_accuracy_ratio`.
+
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use rand::Rng;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::{config, monitoring};
+
+/// Weight of the newest inference in the latency average
+const LATENCY_EWMA_ALPHA: f64 = 0.2;
+
+/// Latency assumed before the first inference
+const INITIAL_LATENCY_MS: f64 = 10.0;
+
+/// Suggested waits are scaled by a random factor in 1 ± this
+const WAIT_JITTER: f64 = 0.1;
+
+/// Shed requests whose retry is still awaited (older ones are forgotten)
+const MAX_TRACKED_SHEDS: usize = 10_000;
+const SHED_TRACKING_WINDOW: Duration = Duration::from_secs(600);
+
+/// Queue of inferences waiting for or holding the model
+pub struct Dispatcher {
+    /// Queue depth at which requests are shed (0 = never shed)
+    max_queue: usize,
+    depth: AtomicUsize,
+    /// EWMA of inference latency in milliseconds (f64 bits)
+    avg_latency_ms: AtomicU64,
+    /// Shed request id -> (shed at, suggested wait in ms)
+    sheds: DashMap<Uuid, (Instant, u64)>,
+}
+
+/// Place in the queue (given back on drop)
+pub struct QueueSlot<'a> {
+    dispatcher: &'a Dispatcher,
+}
+
+/// The queue is full
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overloaded {
+    /// Inferences queued or running when the request was shed
+    pub queue_depth: usize,
+    /// Time for the queue to drain, before jitter
+    pub estimated_wait_ms: f64,
+}
+
+impl Dispatcher {
+    pub fn new(max_queue: usize) -> Self {
+        Self {
+            max_queue,
+            depth: AtomicUsize::new(0),
+            avg_latency_ms: AtomicU64::new(INITIAL_LATENCY_MS.to_bits()),
+            sheds: DashMap::new(),
+        }
+    }
+
+    /// Take a place in the queue, or the estimated wait if it is full
+    pub fn try_enter(&self) -> Result<QueueSlot<'_>, Overloaded> {
+        let depth = self.depth.fetch_add(1, Ordering::SeqCst);
+        if self.max_queue > 0 && depth >= self.max_queue {
+            self.depth.fetch_sub(1, Ordering::SeqCst);
+            return Err(Overloaded {
+                queue_depth: depth,
+                estimated_wait_ms: depth as f64 * self.average_latency_ms(),
+            });
+        }
+        Ok(QueueSlot { dispatcher: self })
+    }
+
+    /// Inferences queued or running
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::SeqCst)
+    }
+
+    /// Fold an inference's latency into the average
+    pub fn record_latency(&self, latency_ms: f64) {
+        if !latency_ms.is_finite() || latency_ms < 0.0 {
+            return;
+        }
+        let _ = self
+            .avg_latency_ms
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |bits| {
+                let avg = f64::from_bits(bits);
+                let next = avg + LATENCY_EWMA_ALPHA * (latency_ms - avg);
+                Some(next.to_bits())
+            });
+    }
+
+    /// Average inference latency in milliseconds
+    pub fn average_latency_ms(&self) -> f64 {
+        f64::from_bits(self.avg_latency_ms.load(Ordering::SeqCst))
+    }
+
+    /// Remember a shed request and the wait it was told, to measure the
+    /// estimate when its retry succeeds
+    pub fn track_shed(&self, request_id: Uuid, suggested_wait_ms: u64, now: Instant) {
+        if self.sheds.len() >= MAX_TRACKED_SHEDS {
+            self.sheds
+                .retain(|_, (shed_at, _)| now.duration_since(*shed_at) < SHED_TRACKING_WINDOW);
+            if self.sheds.len() >= MAX_TRACKED_SHEDS {
+                return;
+            }
+        }
+        self.sheds.insert(request_id, (now, suggested_wait_ms));
+    }
+
+    /// A retry of `request_id` succeeded: actual wait / suggested wait, if
+    /// the shed is still tracked
+    pub fn retry_succeeded(&self, request_id: Uuid, now: Instant) -> Option<f64> {
+        let (_, (shed_at, suggested_ms)) = self.sheds.remove(&request_id)?;
+        let waited = now.duration_since(shed_at);
+        if waited >= SHED_TRACKING_WINDOW {
+            return None;
+        }
+        let ratio = waited.as_secs_f64() * 1000.0 / suggested_ms.max(1) as f64;
+        monitoring::SHED_WAIT_ACCURACY.observe(ratio);
+        Some(ratio)
+    }
+}
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.dispatcher.depth.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Wait to suggest to one shed request, in whole milliseconds (at least 1)
+pub fn jittered_wait_ms(estimated_wait_ms: f64, rng: &mut impl Rng) -> u64 {
+    let factor = rng.gen_range(1.0 - WAIT_JITTER..=1.0 + WAIT_JITTER);
+    (estimated_wait_ms * factor).round().max(1.0) as u64
+}
+
+/// `Retry-After` seconds for a suggested wait (rounded up, at least 1)
+pub fn retry_after_secs(wait_ms: u64) -> u64 {
+    wait_ms.div_ceil(1000).max(1)
+}
+
+static DISPATCHER: Lazy<Dispatcher> =
+    Lazy::new(|| Dispatcher::new(config::get_settings().max_inference_queue));
+
+/// Process-wide queue in front of the model
+pub fn dispatcher() -> &'static Dispatcher {
+    &DISPATCHER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_sheds_when_full() {
+        let dispatcher = Dispatcher::new(2);
+        let first = dispatcher.try_enter().unwrap();
+        let _second = dispatcher.try_enter().unwrap();
+
+        let overloaded = dispatcher.try_enter().err().unwrap();
+        assert_eq!(overloaded.queue_depth, 2);
+        assert_eq!(overloaded.estimated_wait_ms, 2.0 * INITIAL_LATENCY_MS);
+        assert_eq!(dispatcher.depth(), 2);
+
+        drop(first);
+        assert!(dispatcher.try_enter().is_ok());
+
+        let unbounded = Dispatcher::new(0);
+        let slots: Vec<_> = (0..100).map(|_| unbounded.try_enter().unwrap()).collect();
+        assert_eq!(unbounded.depth(), slots.len());
+    }
+
+    #[test]
+    fn test_latency_ewma() {
+        let dispatcher = Dispatcher::new(1);
+        dispatcher.record_latency(20.0);
+        assert!((dispatcher.average_latency_ms() - 12.0).abs() < 1e-9);
+
+        // Converges on a steady latency; bogus samples are ignored
+        for _ in 0..100 {
+            dispatcher.record_latency(50.0);
+        }
+        dispatcher.record_latency(f64::NAN);
+        assert!((dispatcher.average_latency_ms() - 50.0).abs() < 1e-6);
+
+        let _slot = dispatcher.try_enter().unwrap();
+        let overloaded = dispatcher.try_enter().err().unwrap();
+        assert!((overloaded.estimated_wait_ms - 50.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_jitter_bounds() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let waits: Vec<u64> = (0..1000)
+            .map(|_| jittered_wait_ms(1000.0, &mut rng))
+            .collect();
+        assert!(waits.iter().all(|w| (900..=1100).contains(w)));
+        // Spread out, not one value
+        assert!(waits.iter().min() < waits.iter().max());
+
+        assert_eq!(jittered_wait_ms(0.0, &mut rng), 1);
+        assert_eq!(retry_after_secs(1), 1);
+        assert_eq!(retry_after_secs(1000), 1);
+        assert_eq!(retry_after_secs(1001), 2);
+    }
+
+    #[test]
+    fn test_retry_accuracy() {
+        let dispatcher = Dispatcher::new(1);
+        let (id, start) = (Uuid::now_v7(), Instant::now());
+        dispatcher.track_shed(id, 500, start);
+
+        let ratio = dispatcher
+            .retry_succeeded(id, start + Duration::from_millis(750))
+            .unwrap();
+        assert!((ratio - 1.5).abs() < 1e-9);
+
+        // Counted once; unknown and stale ids are ignored
+        assert_eq!(dispatcher.retry_succeeded(id, start), None);
+        assert_eq!(dispatcher.retry_succeeded(Uuid::now_v7(), start), None);
+        dispatcher.track_shed(id, 500, start);
+        assert_eq!(
+            dispatcher.retry_succeeded(id, start + SHED_TRACKING_WINDOW),
+            None
+        );
+    }
+}
@@ -0,0 +1,378 @@
+//! ONNX graph introspection
+//!
+//! Exports of the same architecture don't agree on names: the hidden states
+//! may be called `last_hidden_state`, `token_embeddings` or `output_0`, and
+//! some models have no `token_type_ids` input. Some exports also bake the
+//! pooling into the graph and emit a `[batch, dim]` sentence embedding. The
+//! session's inputs and outputs are checked once at load so a mismatch fails
+//! at startup rather than on the first request.
+
+use anyhow::{anyhow, bail, Result};
+use ort::session::Session;
+use ort::tensor::TensorElementType;
+use ort::value::ValueType;
+
+pub const INPUT_IDS: &str = "input_ids";
+pub const ATTENTION_MASK: &str = "attention_mask";
+pub const TOKEN_TYPE_IDS: &str = "token_type_ids";
+
+/// Name, shape and element type of a graph input or output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TensorInfo {
+    pub name: String,
+    /// Dimensions, -1 where dynamic (None for non-tensor values)
+    pub shape: Option<Vec<i64>>,
+    pub is_float: bool,
+}
+
+impl TensorInfo {
+    fn from_value_type(name: &str, value_type: &ValueType) -> Self {
+        let (shape, is_float) = match value_type {
+            ValueType::Tensor { ty, shape, .. } => {
+                (Some(shape.to_vec()), *ty == TensorElementType::Float32)
+            }
+            _ => (None, false),
+        };
+        TensorInfo {
+            name: name.to_string(),
+            shape,
+            is_float,
+        }
+    }
+
+    fn rank(&self) -> Option<usize> {
+        self.shape.as_ref().map(Vec::len)
+    }
+
+    /// Output mode this tensor can serve, if any
+    fn output_mode(&self) -> Option<OutputMode> {
+        match (self.is_float, self.rank()) {
+            (true, Some(3)) => Some(OutputMode::Token),
+            (true, Some(2)) => Some(OutputMode::Pooled),
+            _ => None,
+        }
+    }
+
+    /// Last dimension, if static
+    fn dim(&self) -> Option<usize> {
+        self.shape
+            .as_ref()
+            .and_then(|shape| shape.last())
+            .and_then(|&dim| usize::try_from(dim).ok())
+    }
+}
+
+/// How the model output turns into a sentence embedding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// `[batch, seq, dim]` token embeddings, mean-pooled over the attention mask
+    Token,
+    /// `[batch, dim]` sentence embedding, pooled inside the graph
+    Pooled,
+}
+
+impl OutputMode {
+    /// Parse ONNX_OUTPUT_MODE (`auto` or empty = detect from the output shape)
+    pub fn from_setting(value: &str) -> Result<Option<Self>> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "auto" => Ok(None),
+            "token" => Ok(Some(OutputMode::Token)),
+            "pooled" => Ok(Some(OutputMode::Pooled)),
+            other => bail!(
+                "invalid ONNX_OUTPUT_MODE '{}' (expected auto, token or pooled)",
+                other
+            ),
+        }
+    }
+
+    fn rank(self) -> usize {
+        match self {
+            OutputMode::Token => 3,
+            OutputMode::Pooled => 2,
+        }
+    }
+}
+
+/// Resolved inputs/outputs of a loaded model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelIo {
+    /// Output holding the embeddings
+    pub output_name: String,
+    pub output_mode: OutputMode,
+    /// Whether the model takes a `token_type_ids` input
+    pub token_type_ids: bool,
+}
+
+/// Inputs and outputs exposed by a session
+pub fn describe(session: &Session) -> (Vec<TensorInfo>, Vec<TensorInfo>) {
+    let inputs = session
+        .inputs
+        .iter()
+        .map(|input| TensorInfo::from_value_type(&input.name, &input.input_type))
+        .collect();
+    let outputs = session
+        .outputs
+        .iter()
+        .map(|output| TensorInfo::from_value_type(&output.name, &output.output_type))
+        .collect();
+    (inputs, outputs)
+}
+
+/// Check the required inputs and pick the output to read.
+///
+/// `configured` (ONNX_OUTPUT_NAME) wins; otherwise the sole 3-D float output
+/// is used, falling back to the sole 2-D float output. `mode`
+/// (ONNX_OUTPUT_MODE) forces token or pooled handling instead of going by
+/// rank. A static last dimension must match `embedding_dim`.
+pub fn resolve(
+    inputs: &[TensorInfo],
+    outputs: &[TensorInfo],
+    configured: Option<&str>,
+    mode: Option<OutputMode>,
+    embedding_dim: usize,
+) -> Result<ModelIo> {
+    for required in [INPUT_IDS, ATTENTION_MASK] {
+        if !inputs.iter().any(|input| input.name == required) {
+            bail!(
+                "model has no '{}' input (model inputs: {})",
+                required,
+                names(inputs)
+            );
+        }
+    }
+    let token_type_ids = inputs.iter().any(|input| input.name == TOKEN_TYPE_IDS);
+
+    let output = match configured {
+        Some(name) => outputs
+            .iter()
+            .find(|output| output.name == name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "ONNX_OUTPUT_NAME '{}' is not a model output (model outputs: {})",
+                    name,
+                    describe_all(outputs)
+                )
+            })?,
+        None => {
+            let wanted = match mode {
+                Some(mode) => vec![mode],
+                None => vec![OutputMode::Token, OutputMode::Pooled],
+            };
+            let mut selected = None;
+            for mode in wanted {
+                let candidates: Vec<&TensorInfo> = outputs
+                    .iter()
+                    .filter(|output| output.output_mode() == Some(mode))
+                    .collect();
+                match candidates.as_slice() {
+                    [] => continue,
+                    [output] => {
+                        selected = Some(*output);
+                        break;
+                    }
+                    _ => bail!(
+                        "model has several {}-D float outputs, set ONNX_OUTPUT_NAME to one of: {}",
+                        mode.rank(),
+                        candidates
+                            .iter()
+                            .map(|output| output.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                }
+            }
+            selected.ok_or_else(|| {
+                anyhow!(
+                    "model has no usable float output (model outputs: {})",
+                    describe_all(outputs)
+                )
+            })?
+        }
+    };
+
+    let output_mode = match (output.output_mode(), mode) {
+        (Some(detected), Some(forced)) if detected != forced => bail!(
+            "ONNX_OUTPUT_MODE is {:?} but output '{}' is {}-D",
+            forced,
+            output.name,
+            output.rank().unwrap_or(0)
+        ),
+        (Some(detected), _) => detected,
+        (None, _) => bail!(
+            "output '{}' is not a 2-D or 3-D float tensor (model outputs: {})",
+            output.name,
+            describe_all(outputs)
+        ),
+    };
+
+    if let Some(dim) = output.dim() {
+        if dim != embedding_dim {
+            bail!(
+                "output '{}' has dimension {} but EMBEDDING_DIM is {}",
+                output.name,
+                dim,
+                embedding_dim
+            );
+        }
+    }
+
+    Ok(ModelIo {
+        output_name: output.name.clone(),
+        output_mode,
+        token_type_ids,
+    })
+}
+
+fn names(tensors: &[TensorInfo]) -> String {
+    tensors
+        .iter()
+        .map(|tensor| tensor.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn describe_all(tensors: &[TensorInfo]) -> String {
+    tensors
+        .iter()
+        .map(|tensor| match tensor.rank() {
+            Some(rank) => format!(
+                "{} ({}-D {})",
+                tensor.name,
+                rank,
+                if tensor.is_float {
+                    "float"
+                } else {
+                    "non-float"
+                }
+            ),
+            None => format!("{} (not a tensor)", tensor.name),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tensor(name: &str, shape: &[i64], is_float: bool) -> TensorInfo {
+        TensorInfo {
+            name: name.to_string(),
+            shape: Some(shape.to_vec()),
+            is_float,
+        }
+    }
+
+    fn bert_inputs() -> Vec<TensorInfo> {
+        vec![
+            tensor(INPUT_IDS, &[-1, -1], false),
+            tensor(ATTENTION_MASK, &[-1, -1], false),
+            tensor(TOKEN_TYPE_IDS, &[-1, -1], false),
+        ]
+    }
+
+    #[test]
+    fn test_auto_selects_sole_hidden_state_output() {
+        let outputs = vec![
+            tensor("token_embeddings", &[-1, -1, 384], true),
+            tensor("sentence_embedding", &[-1, 384], true),
+        ];
+
+        let io = resolve(&bert_inputs(), &outputs, None, None, 384).unwrap();
+        assert_eq!(io.output_name, "token_embeddings");
+        assert_eq!(io.output_mode, OutputMode::Token);
+        assert!(io.token_type_ids);
+    }
+
+    #[test]
+    fn test_configured_output_name() {
+        let outputs = vec![
+            tensor("last_hidden_state", &[-1, -1, 384], true),
+            tensor("output_0", &[-1, -1, 384], true),
+        ];
+
+        // Ambiguous without configuration
+        let err = resolve(&bert_inputs(), &outputs, None, None, 384).unwrap_err();
+        assert!(err.to_string().contains("ONNX_OUTPUT_NAME"));
+
+        let io = resolve(&bert_inputs(), &outputs, Some("output_0"), None, 384).unwrap();
+        assert_eq!(io.output_name, "output_0");
+
+        let err = resolve(&bert_inputs(), &outputs, Some("missing"), None, 384).unwrap_err();
+        assert!(err.to_string().contains("last_hidden_state (3-D float)"));
+    }
+
+    #[test]
+    fn test_missing_input_lists_model_inputs() {
+        let inputs = vec![
+            tensor(INPUT_IDS, &[-1, -1], false),
+            tensor("mask", &[-1, -1], false),
+        ];
+        let outputs = vec![tensor("last_hidden_state", &[-1, -1, 384], true)];
+
+        let err = resolve(&inputs, &outputs, None, None, 384)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("'attention_mask'"));
+        assert!(err.contains("input_ids, mask"));
+    }
+
+    #[test]
+    fn test_token_type_ids_optional() {
+        let inputs = vec![
+            tensor(INPUT_IDS, &[-1, -1], false),
+            tensor(ATTENTION_MASK, &[-1, -1], false),
+        ];
+        let outputs = vec![tensor("last_hidden_state", &[-1, -1, 384], true)];
+
+        let io = resolve(&inputs, &outputs, None, None, 384).unwrap();
+        assert!(!io.token_type_ids);
+    }
+
+    #[test]
+    fn test_pooled_output_detected_and_forced() {
+        let pooled_only = vec![tensor("sentence_embedding", &[-1, 384], true)];
+        let io = resolve(&bert_inputs(), &pooled_only, None, None, 384).unwrap();
+        assert_eq!(io.output_mode, OutputMode::Pooled);
+
+        // Forcing pooled mode skips the token embeddings output
+        let both = vec![
+            tensor("token_embeddings", &[-1, -1, 384], true),
+            tensor("sentence_embedding", &[-1, 384], true),
+        ];
+        let io = resolve(&bert_inputs(), &both, None, Some(OutputMode::Pooled), 384).unwrap();
+        assert_eq!(io.output_name, "sentence_embedding");
+
+        let err = resolve(
+            &bert_inputs(),
+            &both,
+            Some("token_embeddings"),
+            Some(OutputMode::Pooled),
+            384,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("is 3-D"));
+    }
+
+    #[test]
+    fn test_static_dimension_must_match() {
+        let outputs = vec![tensor("sentence_embedding", &[-1, 768], true)];
+        let err = resolve(&bert_inputs(), &outputs, None, None, 384).unwrap_err();
+        assert!(err.to_string().contains("dimension 768"));
+
+        // Dynamic dimensions are checked at inference time instead
+        let outputs = vec![tensor("sentence_embedding", &[-1, -1], true)];
+        assert!(resolve(&bert_inputs(), &outputs, None, None, 384).is_ok());
+    }
+
+    #[test]
+    fn test_output_mode_setting() {
+        assert_eq!(OutputMode::from_setting("").unwrap(), None);
+        assert_eq!(OutputMode::from_setting("auto").unwrap(), None);
+        assert_eq!(
+            OutputMode::from_setting("Pooled").unwrap(),
+            Some(OutputMode::Pooled)
+        );
+        assert!(OutputMode::from_setting("cls").is_err());
+    }
+}
@@ -0,0 +1,247 @@
+//! Inner loops of pooling and normalization, with SIMD versions
+//!
+//! The best implementation for this CPU is picked once at runtime: AVX2+FMA
+//! on x86_64, NEON on aarch64, plain loops everywhere else. `scalar` keeps
+//! the portable versions callable directly (tests and benches compare
+//! against them). SIMD versions sum in a different order, so results may
+//! differ from the scalar ones in the last bits.
+
+use once_cell::sync::Lazy;
+
+/// Implementation selected for this CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kernel {
+    Scalar,
+    Avx2,
+    Neon,
+}
+
+impl Kernel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kernel::Scalar => "scalar",
+            Kernel::Avx2 => "avx2",
+            Kernel::Neon => "neon",
+        }
+    }
+
+    fn detect() -> Self {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+            return Kernel::Avx2;
+        }
+        #[cfg(target_arch = "aarch64")]
+        if std::arch::is_aarch64_feature_detected!("neon") {
+            return Kernel::Neon;
+        }
+        Kernel::Scalar
+    }
+}
+
+static ACTIVE: Lazy<Kernel> = Lazy::new(Kernel::detect);
+
+/// Implementation used by the functions below
+pub fn active() -> Kernel {
+    *ACTIVE
+}
+
+/// `acc += weight * row`
+pub fn add_scaled(acc: &mut [f32], row: &[f32], weight: f32) {
+    assert_eq!(acc.len(), row.len());
+    match active() {
+        // SAFETY: the CPU supports the features (checked by `Kernel::detect`)
+        // and the lengths match
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => unsafe { avx2::add_scaled(acc, row, weight) },
+        #[cfg(target_arch = "aarch64")]
+        Kernel::Neon => unsafe { neon::add_scaled(acc, row, weight) },
+        _ => scalar::add_scaled(acc, row, weight),
+    }
+}
+
+/// `v *= factor`
+pub fn scale(v: &mut [f32], factor: f32) {
+    match active() {
+        // SAFETY: the CPU supports the features (checked by `Kernel::detect`)
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => unsafe { avx2::scale(v, factor) },
+        #[cfg(target_arch = "aarch64")]
+        Kernel::Neon => unsafe { neon::scale(v, factor) },
+        _ => scalar::scale(v, factor),
+    }
+}
+
+/// Dot product
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    assert_eq!(a.len(), b.len());
+    match active() {
+        // SAFETY: the CPU supports the features (checked by `Kernel::detect`)
+        // and the lengths match
+        #[cfg(target_arch = "x86_64")]
+        Kernel::Avx2 => unsafe { avx2::dot(a, b) },
+        #[cfg(target_arch = "aarch64")]
+        Kernel::Neon => unsafe { neon::dot(a, b) },
+        _ => scalar::dot(a, b),
+    }
+}
+
+/// Sum of squares (squared L2 norm)
+pub fn sum_squares(v: &[f32]) -> f32 {
+    dot(v, v)
+}
+
+/// Portable versions
+pub mod scalar {
+    pub fn add_scaled(acc: &mut [f32], row: &[f32], weight: f32) {
+        for (a, &x) in acc.iter_mut().zip(row) {
+            *a += weight * x;
+        }
+    }
+
+    pub fn scale(v: &mut [f32], factor: f32) {
+        for x in v.iter_mut() {
+            *x *= factor;
+        }
+    }
+
+    pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::*;
+
+    const LANES: usize = 8;
+
+    #[target_feature(enable = "avx2,fma")]
+    pub unsafe fn add_scaled(acc: &mut [f32], row: &[f32], weight: f32) {
+        let n = acc.len() - acc.len() % LANES;
+        let w = _mm256_set1_ps(weight);
+        for i in (0..n).step_by(LANES) {
+            let a = _mm256_loadu_ps(acc.as_ptr().add(i));
+            let x = _mm256_loadu_ps(row.as_ptr().add(i));
+            _mm256_storeu_ps(acc.as_mut_ptr().add(i), _mm256_fmadd_ps(w, x, a));
+        }
+        super::scalar::add_scaled(&mut acc[n..], &row[n..], weight);
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    pub unsafe fn scale(v: &mut [f32], factor: f32) {
+        let n = v.len() - v.len() % LANES;
+        let f = _mm256_set1_ps(factor);
+        for i in (0..n).step_by(LANES) {
+            let x = _mm256_loadu_ps(v.as_ptr().add(i));
+            _mm256_storeu_ps(v.as_mut_ptr().add(i), _mm256_mul_ps(x, f));
+        }
+        super::scalar::scale(&mut v[n..], factor);
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    pub unsafe fn dot(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len() - a.len() % LANES;
+        let mut sum = _mm256_setzero_ps();
+        for i in (0..n).step_by(LANES) {
+            let x = _mm256_loadu_ps(a.as_ptr().add(i));
+            let y = _mm256_loadu_ps(b.as_ptr().add(i));
+            sum = _mm256_fmadd_ps(x, y, sum);
+        }
+
+        let mut lanes = [0f32; LANES];
+        _mm256_storeu_ps(lanes.as_mut_ptr(), sum);
+        lanes.iter().sum::<f32>() + super::scalar::dot(&a[n..], &b[n..])
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    const LANES: usize = 4;
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn add_scaled(acc: &mut [f32], row: &[f32], weight: f32) {
+        let n = acc.len() - acc.len() % LANES;
+        let w = vdupq_n_f32(weight);
+        for i in (0..n).step_by(LANES) {
+            let a = vld1q_f32(acc.as_ptr().add(i));
+            let x = vld1q_f32(row.as_ptr().add(i));
+            vst1q_f32(acc.as_mut_ptr().add(i), vfmaq_f32(a, x, w));
+        }
+        super::scalar::add_scaled(&mut acc[n..], &row[n..], weight);
+    }
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn scale(v: &mut [f32], factor: f32) {
+        let n = v.len() - v.len() % LANES;
+        for i in (0..n).step_by(LANES) {
+            let x = vld1q_f32(v.as_ptr().add(i));
+            vst1q_f32(v.as_mut_ptr().add(i), vmulq_n_f32(x, factor));
+        }
+        super::scalar::scale(&mut v[n..], factor);
+    }
+
+    #[target_feature(enable = "neon")]
+    pub unsafe fn dot(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len() - a.len() % LANES;
+        let mut sum = vdupq_n_f32(0.0);
+        for i in (0..n).step_by(LANES) {
+            let x = vld1q_f32(a.as_ptr().add(i));
+            let y = vld1q_f32(b.as_ptr().add(i));
+            sum = vfmaq_f32(sum, x, y);
+        }
+        vaddvq_f32(sum) + super::scalar::dot(&a[n..], &b[n..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= 1e-4 * a.abs().max(b.abs()).max(1.0)
+    }
+
+    /// Lengths around the SIMD widths, so the scalar tails are exercised
+    fn vectors(seed: u64) -> impl Iterator<Item = (Vec<f32>, Vec<f32>)> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..=40).chain([384, 768, 1023]).map(move |len| {
+            let mut v = || {
+                (0..len)
+                    .map(|_| rng.gen_range(-1.0..1.0))
+                    .collect::<Vec<f32>>()
+            };
+            (v(), v())
+        })
+    }
+
+    #[test]
+    fn test_dispatched_kernels_match_scalar() {
+        for (a, b) in vectors(1) {
+            assert!(close(dot(&a, &b), scalar::dot(&a, &b)), "len {}", a.len());
+            assert!(close(sum_squares(&a), scalar::dot(&a, &a)));
+
+            let (mut simd, mut plain) = (a.clone(), a.clone());
+            add_scaled(&mut simd, &b, 0.5);
+            scalar::add_scaled(&mut plain, &b, 0.5);
+            assert!(simd.iter().zip(&plain).all(|(x, y)| close(*x, *y)));
+
+            scale(&mut simd, -3.0);
+            scalar::scale(&mut plain, -3.0);
+            assert!(simd.iter().zip(&plain).all(|(x, y)| close(*x, *y)));
+        }
+    }
+
+    #[test]
+    fn test_active_kernel_matches_target() {
+        let kernel = active();
+        if cfg!(target_arch = "aarch64") {
+            assert_eq!(kernel, Kernel::Neon);
+        }
+        if !cfg!(any(target_arch = "x86_64", target_arch = "aarch64")) {
+            assert_eq!(kernel, Kernel::Scalar);
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Lightweight language identification for embed requests
+//!
+//! A single pass over the text: non-Latin scripts map straight to a language
+//! (Han, kana, Hangul, Cyrillic, ...), Latin text is scored against short
+//! stopword lists. This only needs to tell users that they sent, say, Chinese
+//! to an English-only model, so it favours speed (a few microseconds for the
+//! longest accepted input) over coverage. Built with the `language-detection`
+//! feature (on by default); without it nothing is detected.
+
+/// Detected language of a text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    /// ISO 639-1 code
+    pub language: &'static str,
+    /// 0.0 - 1.0
+    pub confidence: f32,
+}
+
+/// Below this confidence a detection never produces a warning
+pub const WARNING_MIN_CONFIDENCE: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Han,
+    Kana,
+    Hangul,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+}
+
+const SCRIPTS: [Script; 10] = [
+    Script::Latin,
+    Script::Han,
+    Script::Kana,
+    Script::Hangul,
+    Script::Cyrillic,
+    Script::Greek,
+    Script::Arabic,
+    Script::Hebrew,
+    Script::Devanagari,
+    Script::Thai,
+];
+
+fn script(c: char) -> Option<Script> {
+    match c {
+        'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' => Some(Script::Latin),
+        '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' => Some(Script::Han),
+        '\u{3040}'..='\u{30FF}' => Some(Script::Kana),
+        '\u{AC00}'..='\u{D7AF}' | '\u{1100}'..='\u{11FF}' => Some(Script::Hangul),
+        '\u{0400}'..='\u{04FF}' => Some(Script::Cyrillic),
+        '\u{0370}'..='\u{03FF}' => Some(Script::Greek),
+        '\u{0600}'..='\u{06FF}' => Some(Script::Arabic),
+        '\u{0590}'..='\u{05FF}' => Some(Script::Hebrew),
+        '\u{0900}'..='\u{097F}' => Some(Script::Devanagari),
+        '\u{0E00}'..='\u{0E7F}' => Some(Script::Thai),
+        _ => None,
+    }
+}
+
+/// Weight of one character: an ideogram or syllable block carries about
+/// as much text as a short Latin word
+fn weight(script: Script) -> usize {
+    match script {
+        Script::Han | Script::Kana | Script::Hangul => 3,
+        _ => 1,
+    }
+}
+
+/// Stopwords of the Latin-script languages told apart
+const STOPWORDS: [(&str, &[&str]); 7] = [
+    (
+        "en",
+        &[
+            "the", "and", "is", "are", "of", "to", "in", "that", "it", "for", "with", "was",
+            "this", "on", "be", "you", "have", "not", "what", "how",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ein", "eine", "zu", "mit", "sich", "auf",
+            "ich", "den", "von", "wie",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "est", "un", "une", "des", "du", "que", "pas", "pour", "dans",
+            "ce", "qui", "avec",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "y", "es", "un", "una", "que", "del", "por", "para", "con", "no",
+            "se", "como", "está",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "lo", "gli", "e", "è", "di", "che", "non", "un", "una", "per", "con", "sono",
+            "della", "come",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "as", "e", "é", "um", "uma", "que", "não", "do", "da", "para", "com", "em",
+            "como",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "is", "niet", "van", "dat", "op", "te", "zijn", "met",
+            "voor", "ik",
+        ],
+    ),
+];
+
+/// Detect the language of a text (None if unsure or the feature is off)
+pub fn detect(text: &str) -> Option<Detection> {
+    if !cfg!(feature = "language-detection") {
+        return None;
+    }
+
+    let mut counts = [0usize; SCRIPTS.len()];
+    for c in text.chars() {
+        if let Some(script) = script(c) {
+            let index = SCRIPTS.iter().position(|s| *s == script).unwrap_or(0);
+            counts[index] += weight(script);
+        }
+    }
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let (index, &dominant) = counts.iter().enumerate().max_by_key(|(_, count)| **count)?;
+    let share = dominant as f32 / total as f32;
+
+    let language = match SCRIPTS[index] {
+        Script::Latin => return detect_latin(text, share),
+        // Japanese mixes kanji with kana; Han text with any kana is Japanese
+        Script::Han if counts[2] > 0 => "ja",
+        Script::Kana => "ja",
+        Script::Han => "zh",
+        Script::Hangul => "ko",
+        Script::Cyrillic if text.chars().any(|c| "іїєґІЇЄҐ".contains(c)) => "uk",
+        Script::Cyrillic => "ru",
+        Script::Greek => "el",
+        Script::Arabic => "ar",
+        Script::Hebrew => "he",
+        Script::Devanagari => "hi",
+        Script::Thai => "th",
+    };
+    let share = if language == "ja" {
+        (counts[1] + counts[2]) as f32 / total as f32
+    } else {
+        share
+    };
+
+    Some(Detection {
+        language,
+        confidence: share,
+    })
+}
+
+/// Tell Latin-script languages apart by their stopwords
+fn detect_latin(text: &str, share: f32) -> Option<Detection> {
+    let mut hits = [0usize; STOPWORDS.len()];
+    for word in text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+    {
+        let word = word.to_lowercase();
+        for (i, (_, stopwords)) in STOPWORDS.iter().enumerate() {
+            if stopwords.contains(&word.as_str()) {
+                hits[i] += 1;
+            }
+        }
+    }
+
+    let total: usize = hits.iter().sum();
+    let (index, &best) = hits.iter().enumerate().max_by_key(|(_, count)| **count)?;
+    if best == 0 {
+        return None;
+    }
+
+    // Share of the stopword hits, discounted for very little evidence
+    // (one hit gives at most 0.5, three give 0.75)
+    let ratio = best as f32 / total as f32;
+    let evidence = 1.0 - 1.0 / (1.0 + best as f32);
+    Some(Detection {
+        language: STOPWORDS[index].0,
+        confidence: share * ratio * evidence,
+    })
+}
+
+/// Warning for a confidently detected language the model doesn't support
+/// (an empty `supported` list means the model is multilingual)
+pub fn unsupported_warning(detection: &Detection, supported: &[String]) -> Option<String> {
+    if supported.is_empty()
+        || detection.confidence < WARNING_MIN_CONFIDENCE
+        || supported.iter().any(|lang| lang == detection.language)
+    {
+        return None;
+    }
+    Some(format!(
+        "Detected language '{}' is not supported by the model (supported: {}); embeddings may be poor",
+        detection.language,
+        supported.join(", ")
+    ))
+}
+
+#[cfg(all(test, feature = "language-detection"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_english() {
+        let detection =
+            detect("The quick brown fox jumps over the lazy dog and it is not tired").unwrap();
+        assert_eq!(detection.language, "en");
+        assert!(detection.confidence > 0.6);
+
+        // Too little evidence to tell
+        assert!(detect("Hello world").is_none());
+        assert!(detect("12345 !!!").is_none());
+    }
+
+    #[test]
+    fn test_chinese() {
+        let detection = detect("今天天气很好，我们去公园散步吧。").unwrap();
+        assert_eq!(detection.language, "zh");
+        assert!(detection.confidence > 0.9);
+
+        assert_eq!(detect("今日はいい天気ですね").unwrap().language, "ja");
+    }
+
+    #[test]
+    fn test_mixed() {
+        let chinese = detect("明天下午三点开始").unwrap();
+        let mixed = detect("The meeting is 明天下午三点开始").unwrap();
+
+        // The dominant script wins, with lower confidence than clean input
+        assert_eq!(mixed.language, "zh");
+        assert!(mixed.confidence < chinese.confidence);
+        assert!(mixed.confidence < 0.8);
+    }
+
+    #[test]
+    fn test_unsupported_warning() {
+        let supported = vec!["en".to_string()];
+        let chinese = detect("今天天气很好，我们去公园散步吧。").unwrap();
+        let english = detect("This is what the model was trained on").unwrap();
+
+        assert!(unsupported_warning(&chinese, &supported)
+            .unwrap()
+            .contains("'zh'"));
+        assert!(unsupported_warning(&english, &supported).is_none());
+        assert!(unsupported_warning(&chinese, &[]).is_none());
+
+        let unsure = Detection {
+            language: "de",
+            confidence: 0.3,
+        };
+        assert!(unsupported_warning(&unsure, &supported).is_none());
+    }
+
+    #[test]
+    fn test_detection_is_fast() {
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(45);
+        let started = std::time::Instant::now();
+        for _ in 0..100 {
+            detect(&text);
+        }
+        // Average per 2000-character input, with a wide margin for debug builds
+        assert!(started.elapsed() / 100 < std::time::Duration::from_millis(1));
+    }
+}
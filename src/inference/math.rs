@@ -0,0 +1,260 @@
+//! Vector math shared by everything that compares embeddings
+//!
+//! Embeddings leave the model L2-normalized (see `sentence_embedding`), so
+//! cosine similarity is a dot product for them. `cosine` still divides by
+//! the norms, so scores stay in [-1, 1] for vectors from anywhere else.
+//! The inner loops run on the SIMD kernels selected for this CPU.
+//!
+//! `pca_2d` projects a handful of embeddings onto a plane for the
+//! playground's scatter plot. With few points and many dimensions it works
+//! on the points' Gram matrix (n×n) instead of the covariance matrix (d×d).
+
+use super::kernels;
+
+/// Norms below this are treated as zero (avoids dividing by ~0)
+const MIN_NORM: f32 = 1e-9;
+
+/// Power iterations per principal component
+const PCA_ITERATIONS: usize = 500;
+
+/// Variance below this (relative to the total) counts as no spread
+const PCA_MIN_VARIANCE: f64 = 1e-9;
+
+/// Euclidean norm
+pub fn l2_norm(v: &[f32]) -> f32 {
+    kernels::sum_squares(v).sqrt()
+}
+
+/// Scale `v` to unit length in place (a zero vector stays zero)
+pub fn l2_normalize(v: &mut [f32]) {
+    let norm = l2_norm(v).max(MIN_NORM);
+    kernels::scale(v, 1.0 / norm);
+}
+
+/// Cosine similarity in [-1, 1]; 0 if either vector is zero or the lengths
+/// differ
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let norms = l2_norm(a) * l2_norm(b);
+    if norms < MIN_NORM {
+        return 0.0;
+    }
+    (kernels::dot(a, b) / norms).clamp(-1.0, 1.0)
+}
+
+/// Coordinates of `points` on their first two principal components, in
+/// input order. Points are centered first; a component with no spread
+/// (fewer than three points, identical points, points on a line) is 0.
+/// Signs are fixed so that the coordinate largest in magnitude is positive.
+/// All points must have the same length.
+pub fn pca_2d(points: &[Vec<f32>]) -> Vec<[f32; 2]> {
+    let n = points.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let dim = points[0].len();
+    let mut mean = vec![0.0f32; dim];
+    for point in points {
+        for (m, x) in mean.iter_mut().zip(point) {
+            *m += x / n as f32;
+        }
+    }
+    let centered: Vec<Vec<f32>> = points
+        .iter()
+        .map(|point| point.iter().zip(&mean).map(|(x, m)| x - m).collect())
+        .collect();
+
+    // Eigenvectors u of the Gram matrix with eigenvalue λ give the
+    // coordinates on the matching principal component: u * sqrt(λ)
+    let mut gram: Vec<Vec<f64>> = centered
+        .iter()
+        .map(|a| centered.iter().map(|b| kernels::dot(a, b) as f64).collect())
+        .collect();
+    let total: f64 = (0..n).map(|i| gram[i][i]).sum();
+
+    let mut axes: Vec<Vec<f32>> = Vec::with_capacity(2);
+    while axes.len() < 2 {
+        let (value, vector) = top_eigenpair(&gram);
+        if value <= PCA_MIN_VARIANCE * total.max(f64::MIN_POSITIVE) {
+            break;
+        }
+        let sign = vector
+            .iter()
+            .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .map_or(1.0, |largest| largest.signum());
+        axes.push(
+            vector
+                .iter()
+                .map(|u| (sign * u * value.sqrt()) as f32)
+                .collect(),
+        );
+        // Deflate so the next iteration finds the next component
+        for (i, row) in gram.iter_mut().enumerate() {
+            for (j, g) in row.iter_mut().enumerate() {
+                *g -= value * vector[i] * vector[j];
+            }
+        }
+    }
+
+    let coordinate = |axis: usize, i: usize| axes.get(axis).map_or(0.0, |axis| axis[i]);
+    (0..n)
+        .map(|i| [coordinate(0, i), coordinate(1, i)])
+        .collect()
+}
+
+/// Largest eigenvalue of a symmetric positive semi-definite matrix and its
+/// unit eigenvector, by power iteration
+fn top_eigenpair(matrix: &[Vec<f64>]) -> (f64, Vec<f64>) {
+    let n = matrix.len();
+    // Uneven start, so it's unlikely to be orthogonal to the answer
+    let mut vector: Vec<f64> = (0..n).map(|i| 1.0 + i as f64 / n as f64).collect();
+    let mut value = 0.0;
+    for _ in 0..PCA_ITERATIONS {
+        let next: Vec<f64> = matrix
+            .iter()
+            .map(|row| row.iter().zip(&vector).map(|(m, v)| m * v).sum())
+            .collect();
+        let norm = next.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm == 0.0 {
+            return (0.0, vector);
+        }
+        vector = next.into_iter().map(|x| x / norm).collect();
+        let converged = (norm - value).abs() <= 1e-12 * norm;
+        value = norm;
+        if converged {
+            break;
+        }
+    }
+    (value, vector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// Random vectors of embedding-like sizes and magnitudes
+    fn vectors(seed: u64) -> impl Iterator<Item = (Vec<f32>, Vec<f32>)> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        (0..500).map(move |_| {
+            let dim = rng.gen_range(1..=768);
+            let scale = 10f32.powi(rng.gen_range(-3..=3));
+            let mut v = || {
+                (0..dim)
+                    .map(|_| rng.gen_range(-1.0..1.0) * scale)
+                    .collect::<Vec<f32>>()
+            };
+            (v(), v())
+        })
+    }
+
+    #[test]
+    fn test_cosine_is_symmetric_and_bounded() {
+        for (a, b) in vectors(1) {
+            let ab = cosine(&a, &b);
+            assert_eq!(ab, cosine(&b, &a));
+            assert!((-1.0..=1.0).contains(&ab), "{} out of bounds", ab);
+        }
+    }
+
+    #[test]
+    fn test_self_similarity_is_one() {
+        for (a, _) in vectors(2) {
+            assert!((cosine(&a, &a) - 1.0).abs() < 1e-5);
+            let negated: Vec<f32> = a.iter().map(|x| -x).collect();
+            assert!((cosine(&a, &negated) + 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_cosine_ignores_scale() {
+        for (a, b) in vectors(3) {
+            let mut unit_a = a.clone();
+            l2_normalize(&mut unit_a);
+            assert!((l2_norm(&unit_a) - 1.0).abs() < 1e-5);
+            assert!((cosine(&unit_a, &b) - cosine(&a, &b)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_degenerate_inputs() {
+        assert_eq!(cosine(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine(&[1.0], &[1.0, 0.0]), 0.0);
+
+        let mut zero = vec![0.0f32; 3];
+        l2_normalize(&mut zero);
+        assert_eq!(zero, vec![0.0; 3]);
+    }
+
+    #[test]
+    fn test_pca_recovers_a_plane() {
+        // Points on a 2D grid, rotated into 6 dimensions and shifted: their
+        // pairwise distances survive the projection
+        let plane = [[0.0, 0.0], [3.0, 0.0], [0.0, 1.0], [3.0, 1.0], [1.5, 0.5]];
+        let points: Vec<Vec<f32>> = plane
+            .iter()
+            .map(|[x, y]| {
+                let (a, b) = (x * 0.6 - y * 0.8, x * 0.8 + y * 0.6);
+                vec![a + 5.0, 1.0, b, 0.0, -2.0, 0.0]
+            })
+            .collect();
+        let projected = pca_2d(&points);
+        assert_eq!(projected.len(), plane.len());
+
+        let distance =
+            |p: [f32; 2], q: [f32; 2]| ((p[0] - q[0]).powi(2) + (p[1] - q[1]).powi(2)).sqrt();
+        for i in 0..plane.len() {
+            for j in 0..plane.len() {
+                let expected = distance(plane[i], plane[j]);
+                let actual = distance(projected[i], projected[j]);
+                assert!(
+                    (expected - actual).abs() < 1e-3,
+                    "{} vs {}",
+                    expected,
+                    actual
+                );
+            }
+        }
+
+        // Centered, and the first component carries the wider spread
+        let mean_x: f32 = projected.iter().map(|p| p[0]).sum::<f32>() / 5.0;
+        assert!(mean_x.abs() < 1e-4);
+        assert!((projected[1][0] - projected[0][0]).abs() > 2.9);
+    }
+
+    #[test]
+    fn test_pca_is_deterministic() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let points: Vec<Vec<f32>> = (0..12)
+            .map(|_| {
+                let mut v: Vec<f32> = (0..384).map(|_| rng.gen_range(-1.0..1.0)).collect();
+                l2_normalize(&mut v);
+                v
+            })
+            .collect();
+        let projected = pca_2d(&points);
+        assert_eq!(projected, pca_2d(&points));
+
+        // Components come in order of decreasing variance
+        let variance = |k: usize| projected.iter().map(|p| p[k] * p[k]).sum::<f32>();
+        assert!(variance(0) >= variance(1));
+        assert!(variance(1) > 0.0);
+    }
+
+    #[test]
+    fn test_pca_degenerate_inputs() {
+        assert!(pca_2d(&[]).is_empty());
+        assert_eq!(pca_2d(&[vec![1.0, 2.0]]), vec![[0.0, 0.0]]);
+        assert_eq!(
+            pca_2d(&[vec![1.0, 2.0], vec![1.0, 2.0], vec![1.0, 2.0]]),
+            vec![[0.0, 0.0]; 3]
+        );
+
+        // Two points: all spread on the first component
+        let two = pca_2d(&[vec![0.0, 0.0, 0.0], vec![0.0, 4.0, 0.0]]);
+        assert!((two[0][0] + 2.0).abs() < 1e-5 && (two[1][0] - 2.0).abs() < 1e-5);
+        assert_eq!((two[0][1], two[1][1]), (0.0, 0.0));
+    }
+}
@@ -1,168 +1,76 @@
+pub mod catalog;
+pub mod cpu;
+pub mod dispatcher;
+#[cfg(feature = "inference")]
+pub mod graph;
+pub mod kernels;
+pub mod language;
+pub mod math;
+#[cfg(feature = "inference")]
+mod model;
+#[cfg(feature = "inference")]
+pub mod session;
 pub mod tokenizer;
-
-use anyhow::Result;
-use ndarray::Array2;
-use once_cell::sync::OnceCell;
-use ort::{session::Session, value::Value};
-use parking_lot::RwLock;
-use serde::{Deserialize, Serialize};
-use std::path::Path;
-use std::sync::Arc;
-use std::time::Instant;
-
-use crate::config;
-use tokenizer::Tokenizer;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Metadata {
-    pub model: String,
-    pub tokens: usize,
-    pub inference_time_ms: f64,
-}
-
-pub struct EmbeddingModel {
-    session: Session,
-    tokenizer: Arc<Tokenizer>,
-    max_tokens: usize,
-    embedding_dim: usize,
-    model_name: String,
-}
-
-static MODEL: OnceCell<RwLock<EmbeddingModel>> = OnceCell::new();
-
-impl EmbeddingModel {
-    pub fn new() -> Result<Self> {
-        let settings = config::get_settings();
-
-        // Load tokenizer
-        let model_path = Path::new(&settings.model_path);
-        let tokenizer = Arc::new(Tokenizer::new(model_path)?);
-
-        // Load ONNX model
-        let model_file = model_path.join("model.onnx");
-
-        let session = Session::builder()?
-            .with_intra_threads(4)?
-            .with_inter_threads(2)?
-            .commit_from_file(&model_file)?;
-
-        Ok(EmbeddingModel {
-            session,
-            tokenizer,
-            max_tokens: settings.max_tokens,
-            embedding_dim: settings.embedding_dim,
-            model_name: settings.model_name.clone(),
-        })
+pub mod transform;
+
+#[cfg(feature = "inference")]
+pub use model::{
+    get_model, mark_available, mark_unavailable, unavailable, EmbeddingModel, Metadata,
+};
+
+/// Per-request token ceiling: the requested limit bounded by the model's max.
+/// Never below 2, which leaves room for [CLS] and [SEP].
+/// A non-positive request (e.g. a key without an override) means the model max.
+pub fn effective_max_tokens(requested: usize, model_max: usize) -> usize {
+    if requested == 0 {
+        return model_max;
     }
+    requested.clamp(2, model_max.max(2))
+}
 
-    pub fn count_tokens(&self, text: &str) -> usize {
-        let tokens = self.tokenizer.encode(text, true);
-        tokens.len()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tokenizer::Tokenizer;
+
+    /// Minimal tokenizer fixture: every word is its own vocab entry
+    fn test_tokenizer() -> Tokenizer {
+        let dir = std::env::temp_dir().join(format!("smally-tokenizer-{}", uuid::Uuid::now_v7()));
+        fs::create_dir_all(&dir).unwrap();
+        let vocab = ["[PAD]", "[UNK]", "[CLS]", "[SEP]", "word"].join("\n");
+        fs::write(dir.join("vocab.txt"), vocab).unwrap();
+        let tokenizer = Tokenizer::new(&dir).unwrap();
+        fs::remove_dir_all(&dir).ok();
+        tokenizer
     }
 
-    pub fn encode(&mut self, text: &str, _normalize: bool) -> Result<(Vec<f32>, Metadata)> {
-        let start_time = Instant::now();
-
-        // Get model name before any borrows
-        let model_name = self.get_model_name();
-        let embedding_dim = self.embedding_dim;
-
-        // Tokenize
-        let encoding = self.tokenizer.encode_with_attention(text, self.max_tokens);
-
-        // Prepare ONNX inputs
-        let batch_size = 1usize;
-        let seq_len = encoding.input_ids.len();
-
-        let input_ids = Array2::from_shape_vec((batch_size, seq_len), encoding.input_ids.clone())?;
-
-        let attention_mask =
-            Array2::from_shape_vec((batch_size, seq_len), encoding.attention_mask.clone())?;
-
-        let token_type_ids =
-            Array2::from_shape_vec((batch_size, seq_len), encoding.token_type_ids.clone())?;
-
-        // Convert arrays to Vec and create ORT Values
-        let (input_ids_vec, _) = input_ids.into_raw_vec_and_offset();
-        let (attention_mask_vec, _) = attention_mask.into_raw_vec_and_offset();
-        let (token_type_ids_vec, _) = token_type_ids.into_raw_vec_and_offset();
-
-        let input_ids_value = Value::from_array(([batch_size, seq_len], input_ids_vec))?;
-        let attention_mask_value = Value::from_array(([batch_size, seq_len], attention_mask_vec))?;
-        let token_type_ids_value = Value::from_array(([batch_size, seq_len], token_type_ids_vec))?;
-
-        // Run inference
-        let outputs = self.session.run(ort::inputs![
-            "input_ids" => input_ids_value,
-            "attention_mask" => attention_mask_value,
-            "token_type_ids" => token_type_ids_value,
-        ])?;
-
-        // Extract output - returns (shape, data)
-        let (_shape, output_data) = outputs["last_hidden_state"].try_extract_tensor::<f32>()?;
-
-        // Mean pooling and L2 normalization (as standalone functions to avoid self borrow)
-        let mut embedding = vec![0.0f32; embedding_dim];
-
-        for i in 0..seq_len {
-            let mask = encoding.attention_mask[i] as f32;
-            for (j, emb) in embedding.iter_mut().enumerate().take(embedding_dim) {
-                let idx = i * embedding_dim + j;
-                *emb += output_data[idx] * mask;
-            }
-        }
-
-        // Calculate sum of mask
-        let mask_sum: f32 = encoding.attention_mask.iter().map(|&x| x as f32).sum();
-        let mask_sum = mask_sum.max(1e-9);
-
-        // Divide by mask sum
-        for val in embedding.iter_mut() {
-            *val /= mask_sum;
-        }
-
-        // L2 normalization
-        let norm: f32 = embedding.iter().map(|&x| x * x).sum::<f32>().sqrt();
-        let norm = norm.max(1e-9);
-        for val in embedding.iter_mut() {
-            *val /= norm;
-        }
-
-        let inference_time_ms = start_time.elapsed().as_secs_f64() * 1000.0;
-
-        // Count actual tokens (excluding padding)
-        // attention_mask is 1 for real tokens, 0 for padding
-        let actual_tokens = encoding.attention_mask.iter().filter(|&&x| x == 1).count();
-
-        let metadata = Metadata {
-            model: model_name,
-            tokens: actual_tokens,  // Actual tokens, not padded length
-            inference_time_ms: (inference_time_ms * 100.0).round() / 100.0,
-        };
-
-        Ok((embedding, metadata))
+    fn real_tokens(encoding: &tokenizer::Encoding) -> usize {
+        encoding.attention_mask.iter().filter(|&&x| x == 1).count()
     }
 
-    fn get_model_name(&self) -> String {
-        self.model_name
-            .split('/')
-            .next_back()
-            .unwrap_or(&self.model_name)
-            .to_string()
+    #[test]
+    fn test_effective_max_tokens_bounded_by_model() {
+        assert_eq!(effective_max_tokens(32, 128), 32);
+        assert_eq!(effective_max_tokens(128, 128), 128);
+        assert_eq!(effective_max_tokens(8192, 128), 128);
+        assert_eq!(effective_max_tokens(1, 128), 2);
+        assert_eq!(effective_max_tokens(0, 128), 128);
     }
-}
 
-pub fn init_model() -> Result<()> {
-    // If already initialized, return early
-    if MODEL.get().is_some() {
-        return Ok(());
-    }
+    #[test]
+    fn test_per_key_max_tokens_truncates() {
+        let tokenizer = test_tokenizer();
+        let text = vec!["word"; 200].join(" ");
 
-    let model = EmbeddingModel::new()?;
-    MODEL.set(RwLock::new(model)).ok(); // Ignore error if already set
-    Ok(())
-}
+        // Key with max_tokens=32 is truncated at 32
+        let small = tokenizer.encode_with_attention(&text, effective_max_tokens(32, 128));
+        assert_eq!(small.input_ids.len(), 32);
+        assert_eq!(real_tokens(&small), 32);
 
-pub fn get_model() -> &'static RwLock<EmbeddingModel> {
-    MODEL.get().expect("Model not initialized")
+        // Another key on the same org with the default ceiling gets 128
+        let large = tokenizer.encode_with_attention(&text, effective_max_tokens(128, 128));
+        assert_eq!(large.input_ids.len(), 128);
+        assert_eq!(real_tokens(&large), 128);
+    }
 }
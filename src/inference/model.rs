@@ -0,0 +1,618 @@
+//! The ONNX embedding model (needs the `inference` feature)
+
+use anyhow::{bail, Result};
+use ort::execution_providers::ExecutionProviderDispatch;
+use ort::session::Session;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::info;
+
+use super::catalog;
+use super::cpu::{CpuInfo, ThreadConfig};
+use super::graph::{self, OutputMode};
+use super::session::{InferenceSession, OrtSession, SessionOutput};
+use super::tokenizer::{Encoding, Tokenizer};
+use super::transform::Transform;
+use super::{effective_max_tokens, kernels, math};
+use crate::config;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Metadata {
+    pub model: String,
+    pub tokens: usize,
+    pub inference_time_ms: f64,
+}
+
+pub struct EmbeddingModel {
+    session: Box<dyn InferenceSession>,
+    output_mode: OutputMode,
+    tokenizer: Arc<Tokenizer>,
+    max_tokens: usize,
+    embedding_dim: usize,
+    model_name: String,
+    model_revision: Option<String>,
+    /// Optional `W(x - mu)` post-processing (see `inference::transform`)
+    transform: Option<Arc<Transform>>,
+}
+
+/// Why the model can't serve, when loading it failed
+static UNAVAILABLE: RwLock<Option<String>> = RwLock::new(None);
+
+/// The session with injected inference faults applied (see `fault`)
+#[cfg(feature = "fault-injection")]
+fn with_faults(session: Box<dyn InferenceSession>) -> Box<dyn InferenceSession> {
+    Box::new(crate::fault::FaultySession(session))
+}
+
+#[cfg(not(feature = "fault-injection"))]
+fn with_faults(session: Box<dyn InferenceSession>) -> Box<dyn InferenceSession> {
+    session
+}
+
+impl EmbeddingModel {
+    pub fn new() -> Result<Self> {
+        Self::load(config::get_settings().deterministic_inference)
+    }
+
+    /// Load the model from MODEL_PATH. A `deterministic` session runs on one
+    /// thread with ONNX Runtime's deterministic compute and no extra
+    /// execution providers, so the same text always gives the same bits.
+    pub fn load(deterministic: bool) -> Result<Self> {
+        let settings = config::get_settings();
+        let model_path = Path::new(&settings.model_path);
+        let mut model = Self::load_from(model_path, &settings.model_name, deterministic)?;
+
+        model.model_revision = settings.model_revision.clone();
+        model.transform = load_transform(
+            settings.embedding_transform_path.as_deref(),
+            model_path,
+            settings.embedding_dim,
+        )?;
+        Ok(model)
+    }
+
+    /// Load the model files in `model_path` as `model_name`, without a
+    /// revision or post-processing transform (e.g. a candidate model, see
+    /// `api::compare`). The model must produce EMBEDDING_DIM-dimensional
+    /// embeddings.
+    pub fn load_from(model_path: &Path, model_name: &str, deterministic: bool) -> Result<Self> {
+        let settings = config::get_settings();
+
+        // Load tokenizer
+        let tokenizer = Arc::new(Tokenizer::new(model_path)?);
+
+        // Load ONNX model
+        let model_file = model_path.join("model.onnx");
+
+        let cpu = CpuInfo::detect();
+        let threads = if deterministic {
+            ThreadConfig::DETERMINISTIC
+        } else {
+            ThreadConfig::resolve(
+                &cpu,
+                settings.onnx_intra_threads,
+                settings.onnx_inter_threads,
+            )
+        };
+        info!(
+            "CPU: {}; ONNX threads: {}{}; pooling kernels: {}",
+            cpu,
+            threads,
+            if deterministic {
+                " (deterministic)"
+            } else {
+                ""
+            },
+            kernels::active().as_str()
+        );
+
+        let providers = if deterministic {
+            Vec::new()
+        } else {
+            execution_providers()
+        };
+        let session = Session::builder()?
+            .with_execution_providers(providers)?
+            .with_intra_threads(threads.intra)?
+            .with_inter_threads(threads.inter)?
+            .with_deterministic_compute(deterministic)?
+            .commit_from_file(&model_file)?;
+
+        let (inputs, outputs) = graph::describe(&session);
+        let io = graph::resolve(
+            &inputs,
+            &outputs,
+            settings.onnx_output_name.as_deref(),
+            OutputMode::from_setting(&settings.onnx_output_mode)?,
+            settings.embedding_dim,
+        )?;
+        info!(
+            "Model graph: output '{}' ({:?} mode), token_type_ids {}",
+            io.output_name,
+            io.output_mode,
+            if io.token_type_ids { "fed" } else { "not used" }
+        );
+
+        Ok(EmbeddingModel {
+            output_mode: io.output_mode,
+            session: with_faults(Box::new(OrtSession::new(session, io))),
+            tokenizer,
+            max_tokens: settings.max_tokens,
+            embedding_dim: settings.embedding_dim,
+            model_name: model_name.to_string(),
+            model_revision: None,
+            transform: None,
+        })
+    }
+
+    pub fn count_tokens(&self, text: &str) -> usize {
+        let tokens = self.tokenizer.encode(text, true);
+        tokens.len()
+    }
+
+    /// Shared handle to the tokenizer (usable without holding the model lock)
+    pub fn tokenizer(&self) -> Arc<Tokenizer> {
+        self.tokenizer.clone()
+    }
+
+    /// Post-processing transform, if one is configured for this model
+    pub fn transform(&self) -> Option<Arc<Transform>> {
+        self.transform.clone()
+    }
+
+    /// Model's own token ceiling (per-key limits are bounded by this)
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    pub fn encode(&mut self, text: &str, normalize: bool) -> Result<(Vec<f32>, Metadata)> {
+        self.encode_with_limit(text, normalize, self.max_tokens)
+    }
+
+    /// Encode with a per-request token limit (e.g. from the API key claims).
+    /// The limit is clamped to the model's max via `effective_max_tokens`.
+    pub fn encode_with_limit(
+        &mut self,
+        text: &str,
+        _normalize: bool,
+        max_tokens: usize,
+    ) -> Result<(Vec<f32>, Metadata)> {
+        let start_time = Instant::now();
+
+        // Get model name before any borrows
+        let model_name = self.get_model_name();
+        let embedding_dim = self.embedding_dim;
+        let max_tokens = effective_max_tokens(max_tokens, self.max_tokens);
+
+        // Tokenize
+        let encoding = self.tokenizer.encode_with_attention(text, max_tokens);
+
+        // Run inference
+        let output = self.session.run(&encoding)?;
+        let embedding = sentence_embedding(self.output_mode, &output, &encoding, embedding_dim)?;
+
+        let inference_time_ms = start_time.elapsed().as_secs_f64() * 1000.0;
+
+        // Count actual tokens (excluding padding)
+        // attention_mask is 1 for real tokens, 0 for padding
+        let actual_tokens = encoding.attention_mask.iter().filter(|&&x| x == 1).count();
+
+        let metadata = Metadata {
+            model: model_name,
+            tokens: actual_tokens, // Actual tokens, not padded length
+            inference_time_ms: (inference_time_ms * 100.0).round() / 100.0,
+        };
+
+        Ok((embedding, metadata))
+    }
+
+    pub fn encode_batch(
+        &mut self,
+        texts: &[&str],
+        normalize: bool,
+    ) -> Result<Vec<(Vec<f32>, Metadata)>> {
+        self.encode_batch_with_limit(texts, normalize, self.max_tokens)
+    }
+
+    /// Encode `texts` in one session run, padded to the longest of them, with
+    /// a per-request token limit as in `encode_with_limit`. Results are in
+    /// input order; each one's `inference_time_ms` is the whole run's.
+    pub fn encode_batch_with_limit(
+        &mut self,
+        texts: &[&str],
+        _normalize: bool,
+        max_tokens: usize,
+    ) -> Result<Vec<(Vec<f32>, Metadata)>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let start_time = Instant::now();
+
+        let model_name = self.get_model_name();
+        let max_tokens = effective_max_tokens(max_tokens, self.max_tokens);
+
+        let encodings = self.tokenizer.encode_batch(texts, max_tokens);
+        let output = self.session.run_batch(&encodings)?;
+        let embeddings =
+            sentence_embeddings(self.output_mode, &output, &encodings, self.embedding_dim)?;
+
+        let inference_time_ms = start_time.elapsed().as_secs_f64() * 1000.0;
+
+        Ok(embeddings
+            .into_iter()
+            .zip(&encodings)
+            .map(|(embedding, encoding)| {
+                let metadata = Metadata {
+                    model: model_name.clone(),
+                    tokens: encoding.attention_mask.iter().filter(|&&x| x == 1).count(),
+                    inference_time_ms: (inference_time_ms * 100.0).round() / 100.0,
+                };
+                (embedding, metadata)
+            })
+            .collect())
+    }
+
+    fn get_model_name(&self) -> String {
+        catalog::with_revision(
+            catalog::model_id(&self.model_name),
+            self.model_revision.as_deref(),
+        )
+    }
+}
+
+/// Load the post-processing transform: `configured` if set (must exist),
+/// else `transform.bin` next to the model if present. Its input dimension
+/// must match the model's embedding dimension.
+fn load_transform(
+    configured: Option<&str>,
+    model_path: &Path,
+    embedding_dim: usize,
+) -> Result<Option<Arc<Transform>>> {
+    let path = match configured {
+        Some(path) => Path::new(path).to_path_buf(),
+        None => {
+            let default = model_path.join("transform.bin");
+            if !default.exists() {
+                return Ok(None);
+            }
+            default
+        }
+    };
+
+    let transform = Transform::load(&path)?;
+    if transform.in_dim() != embedding_dim {
+        bail!(
+            "transform {} expects {}-dimensional input, but EMBEDDING_DIM is {}",
+            path.display(),
+            transform.in_dim(),
+            embedding_dim
+        );
+    }
+    info!(
+        "Post-processing transform {}: {} -> {} dimensions",
+        path.display(),
+        transform.in_dim(),
+        transform.out_dim()
+    );
+    Ok(Some(Arc::new(transform)))
+}
+
+/// Execution providers enabled at build time, in order of preference. ONNX
+/// Runtime falls back to its default CPU provider when one fails to
+/// register (e.g. the ACL provider on a non-ARM host).
+#[allow(clippy::vec_init_then_push)]
+fn execution_providers() -> Vec<ExecutionProviderDispatch> {
+    #[allow(unused_mut)]
+    let mut providers = Vec::new();
+    #[cfg(feature = "acl")]
+    providers.push(ort::execution_providers::ACLExecutionProvider::default().build());
+    #[cfg(feature = "onednn")]
+    providers.push(ort::execution_providers::OneDNNExecutionProvider::default().build());
+    providers
+}
+
+/// Turn the model output into an L2-normalized sentence embedding.
+///
+/// Token mode mean-pools `[1, seq, dim]` over the attention mask; pooled mode
+/// takes the `[1, dim]` output as is.
+fn sentence_embedding(
+    mode: OutputMode,
+    output: &SessionOutput,
+    encoding: &Encoding,
+    embedding_dim: usize,
+) -> Result<Vec<f32>> {
+    let mut embeddings =
+        sentence_embeddings(mode, output, std::slice::from_ref(encoding), embedding_dim)?;
+    Ok(embeddings.remove(0))
+}
+
+/// `sentence_embedding` for a batched output: `[batch, seq, dim]` or
+/// `[batch, dim]`, one embedding per encoding. Each row is pooled over its
+/// own attention mask, so padding to the batch's longest input doesn't
+/// change it.
+fn sentence_embeddings(
+    mode: OutputMode,
+    output: &SessionOutput,
+    encodings: &[Encoding],
+    embedding_dim: usize,
+) -> Result<Vec<Vec<f32>>> {
+    let batch_size = encodings.len() as i64;
+    let seq_len = encodings.first().map_or(0, |e| e.attention_mask.len());
+    let expected = match mode {
+        OutputMode::Token => vec![batch_size, seq_len as i64, embedding_dim as i64],
+        OutputMode::Pooled => vec![batch_size, embedding_dim as i64],
+    };
+    if output.shape != expected || output.data.len() != expected.iter().product::<i64>() as usize {
+        bail!(
+            "model output has shape {:?}, expected {:?} ({:?} mode, EMBEDDING_DIM {})",
+            output.shape,
+            expected,
+            mode,
+            embedding_dim
+        );
+    }
+
+    let row_len = output.data.len() / encodings.len().max(1);
+    Ok(output
+        .data
+        .chunks_exact(row_len.max(1))
+        .zip(encodings)
+        .map(|(row, encoding)| {
+            let mut embedding = match mode {
+                OutputMode::Token => mean_pool(row, &encoding.attention_mask, embedding_dim),
+                OutputMode::Pooled => row.to_vec(),
+            };
+            math::l2_normalize(&mut embedding);
+            embedding
+        })
+        .collect())
+}
+
+/// Mean of the token embeddings, weighted by the attention mask
+fn mean_pool(hidden_states: &[f32], attention_mask: &[i64], embedding_dim: usize) -> Vec<f32> {
+    let mut embedding = vec![0.0f32; embedding_dim];
+
+    // Padding rows have weight 0, so they're skipped rather than added
+    for (row, &mask) in hidden_states
+        .chunks_exact(embedding_dim)
+        .zip(attention_mask)
+        .filter(|(_, &mask)| mask != 0)
+    {
+        kernels::add_scaled(&mut embedding, row, mask as f32);
+    }
+
+    let mask_sum: f32 = attention_mask.iter().map(|&x| x as f32).sum();
+    kernels::scale(&mut embedding, 1.0 / mask_sum.max(1e-9));
+
+    embedding
+}
+
+/// The model of the process-wide `AppState` (prefer `AppState::model`
+/// where the state is at hand)
+pub fn get_model() -> &'static RwLock<EmbeddingModel> {
+    crate::state::global()
+        .model
+        .as_deref()
+        .expect("Model not initialized")
+}
+
+/// Record that the model can't serve (e.g. it failed to load). The control
+/// plane keeps running; embedding routes answer 503 `model_unavailable`.
+pub fn mark_unavailable(reason: String) {
+    *UNAVAILABLE.write() = Some(reason);
+}
+
+/// Clear `mark_unavailable` (e.g. after the model was replaced)
+pub fn mark_available() {
+    *UNAVAILABLE.write() = None;
+}
+
+/// Why the model can't serve right now (None = it can)
+pub fn unavailable() -> Option<String> {
+    if let Some(reason) = UNAVAILABLE.read().clone() {
+        return Some(reason);
+    }
+    crate::state::try_global()
+        .and_then(|state| state.model.as_ref())
+        .is_none()
+        .then(|| "model not loaded".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Minimal tokenizer fixture: every word is its own vocab entry
+    fn test_tokenizer() -> Tokenizer {
+        let dir = std::env::temp_dir().join(format!("smally-tokenizer-{}", uuid::Uuid::now_v7()));
+        fs::create_dir_all(&dir).unwrap();
+        let vocab = ["[PAD]", "[UNK]", "[CLS]", "[SEP]", "word"].join("\n");
+        fs::write(dir.join("vocab.txt"), vocab).unwrap();
+        let tokenizer = Tokenizer::new(&dir).unwrap();
+        fs::remove_dir_all(&dir).ok();
+        tokenizer
+    }
+
+    /// Session stub returning per-token rows `[t, 1, ...]` (token mode, t = position + 1)
+    /// or a fixed sentence embedding (pooled mode)
+    struct StubSession {
+        mode: OutputMode,
+        dim: usize,
+    }
+
+    impl InferenceSession for StubSession {
+        fn run_batch(&mut self, encodings: &[Encoding]) -> Result<SessionOutput> {
+            let batch_size = encodings.len();
+            let seq_len = encodings[0].input_ids.len();
+            Ok(match self.mode {
+                OutputMode::Token => SessionOutput {
+                    shape: vec![batch_size as i64, seq_len as i64, self.dim as i64],
+                    data: encodings
+                        .iter()
+                        .flat_map(|_| (0..seq_len).flat_map(|i| token_row(i, self.dim)))
+                        .collect(),
+                },
+                OutputMode::Pooled => SessionOutput {
+                    shape: vec![batch_size as i64, self.dim as i64],
+                    data: encodings
+                        .iter()
+                        .flat_map(|_| (0..self.dim).map(|j| if j == 0 { 3.0 } else { 4.0 }))
+                        .collect(),
+                },
+            })
+        }
+    }
+
+    fn token_row(position: usize, dim: usize) -> impl Iterator<Item = f32> {
+        (0..dim).map(move |j| if j == 0 { (position + 1) as f32 } else { 1.0 })
+    }
+
+    fn stub_model(mode: OutputMode, output_dim: usize) -> EmbeddingModel {
+        EmbeddingModel {
+            session: Box::new(StubSession {
+                mode,
+                dim: output_dim,
+            }),
+            output_mode: mode,
+            tokenizer: Arc::new(test_tokenizer()),
+            max_tokens: 8,
+            embedding_dim: 2,
+            model_name: "org/stub".to_string(),
+            model_revision: None,
+            transform: None,
+        }
+    }
+
+    #[test]
+    fn test_token_output_is_mean_pooled() {
+        let mut model = stub_model(OutputMode::Token, 2);
+        let (embedding, metadata) = model.encode("word", true).unwrap();
+
+        // [CLS] word [SEP] then padding; padding rows are masked out,
+        // so the mean is [2, 1]
+        assert_eq!(metadata.tokens, 3);
+        assert_eq!(metadata.model, "stub");
+        assert!((embedding[0] - 2.0 / 5f32.sqrt()).abs() < 1e-6);
+        assert!((embedding[1] - 1.0 / 5f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_batch_matches_single_encodes() {
+        let texts = [
+            "word word word",
+            "word",
+            "word word word word word word word word",
+        ];
+        for mode in [OutputMode::Token, OutputMode::Pooled] {
+            let mut model = stub_model(mode, 2);
+            let batch = model.encode_batch(&texts, true).unwrap();
+            assert_eq!(batch.len(), texts.len());
+
+            // Padding "word" to the longest input leaves its embedding alone
+            for ((embedding, metadata), text) in batch.iter().zip(texts) {
+                let (single, single_metadata) = model.encode(text, true).unwrap();
+                assert_eq!(metadata.tokens, single_metadata.tokens);
+                for (a, b) in embedding.iter().zip(&single) {
+                    assert!((a - b).abs() < 1e-6, "{:?} in {:?} mode", text, mode);
+                }
+            }
+        }
+        // Truncated to the model's 8 tokens, like `encode`
+        let mut model = stub_model(OutputMode::Token, 2);
+        assert_eq!(model.encode_batch(&texts, true).unwrap()[2].1.tokens, 8);
+        assert!(model.encode_batch(&[], true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_batch_output_shape_checked() {
+        let mut model = stub_model(OutputMode::Token, 3);
+        let err = model.encode_batch(&["word", "word"], true).unwrap_err();
+        assert!(err.to_string().contains("expected [2, 3, 2]"));
+    }
+
+    #[test]
+    fn test_pooled_output_skips_pooling() {
+        let mut model = stub_model(OutputMode::Pooled, 2);
+        let (embedding, _) = model.encode("word word word", true).unwrap();
+
+        assert_eq!(embedding.len(), 2);
+        assert!((embedding[0] - 0.6).abs() < 1e-6);
+        assert!((embedding[1] - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_output_dimension_mismatch_is_an_error() {
+        for mode in [OutputMode::Token, OutputMode::Pooled] {
+            let mut model = stub_model(mode, 3);
+            let err = model.encode("word", true).unwrap_err();
+            assert!(err.to_string().contains("EMBEDDING_DIM 2"));
+        }
+    }
+
+    #[test]
+    fn test_transform_loaded_from_model_dir_and_dimension_checked() {
+        let dir = std::env::temp_dir().join(format!("smally-transform-{}", uuid::Uuid::now_v7()));
+        fs::create_dir_all(&dir).unwrap();
+        assert!(load_transform(None, &dir, 2).unwrap().is_none());
+
+        let transform = Transform::new(vec![0.0; 2], vec![1.0, 0.0], 1).unwrap();
+        fs::write(dir.join("transform.bin"), transform.to_bytes()).unwrap();
+        let loaded = load_transform(None, &dir, 2).unwrap().unwrap();
+        assert_eq!(loaded.out_dim(), 1);
+
+        let err = load_transform(None, &dir, 384).unwrap_err();
+        assert!(err.to_string().contains("EMBEDDING_DIM is 384"));
+        assert!(load_transform(Some("/nonexistent/transform.bin"), &dir, 2).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    /// Fixed corpus for the cross-run stability tests
+    const STABILITY_CORPUS: [&str; 6] = [
+        "how to reset password",
+        "The quick brown fox jumps over the lazy dog.",
+        "Embeddings for the same text should not depend on the instance.",
+        "a",
+        "Numbers 12345 and symbols !@#$% are tokenized too",
+        "Longer inputs exercise more of the attention layers, so reduction order matters \
+         more: this sentence keeps going for a while to produce a few dozen tokens.",
+    ];
+
+    /// Embed the corpus with a freshly constructed session
+    fn embed_corpus(deterministic: bool) -> Vec<Vec<f32>> {
+        dotenvy::from_filename(".env").ok();
+        let mut model = EmbeddingModel::load(deterministic).unwrap();
+        STABILITY_CORPUS
+            .iter()
+            .map(|text| model.encode(text, true).unwrap().0)
+            .collect()
+    }
+
+    #[test]
+    #[ignore = "needs the ONNX model at MODEL_PATH"]
+    fn test_deterministic_sessions_agree_bitwise() {
+        let (first, second) = (embed_corpus(true), embed_corpus(true));
+        for ((a, b), text) in first.iter().zip(&second).zip(STABILITY_CORPUS) {
+            let bits = |v: &[f32]| v.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits(a), bits(b), "embeddings of {:?} differ", text);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the ONNX model at MODEL_PATH"]
+    fn test_default_sessions_agree_within_tolerance() {
+        let (first, second) = (embed_corpus(false), embed_corpus(false));
+        for ((a, b), text) in first.iter().zip(&second).zip(STABILITY_CORPUS) {
+            let similarity = math::cosine(a, b);
+            assert!(
+                similarity >= 0.999999,
+                "embeddings of {:?} have cosine {}",
+                text,
+                similarity
+            );
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! The ONNX session behind `EmbeddingModel`, as a trait so pooling can be
+//! tested without a model file
+
+use anyhow::{bail, Result};
+use ort::{session::Session, value::Value};
+
+use super::graph::{self, ModelIo};
+use super::tokenizer::Encoding;
+
+/// Raw model output: shape and row-major data
+pub struct SessionOutput {
+    pub shape: Vec<i64>,
+    pub data: Vec<f32>,
+}
+
+/// Runs the model graph on tokenized inputs
+pub trait InferenceSession: Send + Sync {
+    /// One input, as a batch of one
+    fn run(&mut self, encoding: &Encoding) -> Result<SessionOutput> {
+        self.run_batch(std::slice::from_ref(encoding))
+    }
+
+    /// Equal-length inputs in one `[batch, seq]` run; output rows follow
+    /// the input order
+    fn run_batch(&mut self, encodings: &[Encoding]) -> Result<SessionOutput>;
+}
+
+/// ONNX Runtime session with its resolved inputs/outputs
+pub struct OrtSession {
+    session: Session,
+    io: ModelIo,
+}
+
+impl OrtSession {
+    pub fn new(session: Session, io: ModelIo) -> Self {
+        Self { session, io }
+    }
+}
+
+impl InferenceSession for OrtSession {
+    fn run_batch(&mut self, encodings: &[Encoding]) -> Result<SessionOutput> {
+        let batch_size = encodings.len();
+        let seq_len = encodings.first().map_or(0, |e| e.input_ids.len());
+        if encodings.iter().any(|e| e.input_ids.len() != seq_len) {
+            bail!("batched inputs must be padded to the same length");
+        }
+        let stacked = |field: fn(&Encoding) -> &Vec<i64>| -> Vec<i64> {
+            encodings
+                .iter()
+                .flat_map(|e| field(e).iter().copied())
+                .collect()
+        };
+
+        let input_ids_value =
+            Value::from_array(([batch_size, seq_len], stacked(|e| &e.input_ids)))?;
+        let attention_mask_value =
+            Value::from_array(([batch_size, seq_len], stacked(|e| &e.attention_mask)))?;
+
+        let mut inputs = ort::inputs![
+            graph::INPUT_IDS => input_ids_value,
+            graph::ATTENTION_MASK => attention_mask_value,
+        ];
+        // Single-sequence inputs: token types are all zeros, fed only if the model takes them
+        if self.io.token_type_ids {
+            let token_type_ids_value =
+                Value::from_array(([batch_size, seq_len], stacked(|e| &e.token_type_ids)))?;
+            inputs.push((graph::TOKEN_TYPE_IDS.into(), token_type_ids_value.into()));
+        }
+
+        let outputs = self.session.run(inputs)?;
+        let (shape, data) = outputs[self.io.output_name.as_str()].try_extract_tensor::<f32>()?;
+
+        Ok(SessionOutput {
+            shape: shape.to_vec(),
+            data: data.to_vec(),
+        })
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
@@ -11,6 +11,24 @@ pub struct Encoding {
     pub token_type_ids: Vec<i64>,
 }
 
+/// A special token and the id it resolved to
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpecialToken {
+    pub token: &'static str,
+    pub id: i64,
+    /// False when the token is missing from vocab.txt and the BERT default id is used
+    pub in_vocab: bool,
+}
+
+/// Resolved special tokens
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SpecialTokens {
+    pub cls: SpecialToken,
+    pub sep: SpecialToken,
+    pub pad: SpecialToken,
+    pub unk: SpecialToken,
+}
+
 #[derive(Debug, Deserialize)]
 struct TokenizerConfig {
     #[serde(default = "default_lowercase")]
@@ -51,6 +69,47 @@ impl Tokenizer {
         })
     }
 
+    /// Whether input is lowercased before tokenization (from tokenizer_config.json)
+    pub fn do_lower_case(&self) -> bool {
+        self.do_lower_case
+    }
+
+    /// Read `do_lower_case` from a model directory without loading the vocab
+    pub fn load_do_lower_case(model_path: &Path) -> bool {
+        Self::load_config(&model_path.join("tokenizer_config.json")).do_lower_case
+    }
+
+    /// Number of entries in the vocabulary
+    pub fn vocab_size(&self) -> usize {
+        self.vocab.len()
+    }
+
+    /// Ids used for [CLS]/[SEP]/[PAD]/[UNK]
+    pub fn special_tokens(&self) -> SpecialTokens {
+        let resolve = |token: &'static str, id: i64| SpecialToken {
+            token,
+            id,
+            in_vocab: self.vocab.get(token) == Some(&id),
+        };
+        SpecialTokens {
+            cls: resolve("[CLS]", self.cls_token_id),
+            sep: resolve("[SEP]", self.sep_token_id),
+            pad: resolve("[PAD]", self.pad_token_id),
+            unk: resolve("[UNK]", self.unk_token_id),
+        }
+    }
+
+    /// WordPiece pieces of `text` with their ids (no special tokens)
+    pub fn pieces(&self, text: &str) -> Vec<(String, i64)> {
+        self.tokenize(text)
+            .into_iter()
+            .map(|piece| {
+                let id = *self.vocab.get(&piece).unwrap_or(&self.unk_token_id);
+                (piece, id)
+            })
+            .collect()
+    }
+
     pub fn encode(&self, text: &str, add_special_tokens: bool) -> Vec<i64> {
         let tokens = self.tokenize(text);
         let mut ids = Vec::with_capacity(tokens.len() + 2);
@@ -71,6 +130,24 @@ impl Tokenizer {
     }
 
     pub fn encode_with_attention(&self, text: &str, max_length: usize) -> Encoding {
+        self.padded(self.truncated(text, max_length), max_length)
+    }
+
+    /// Encode `texts` for one batched run: each truncated to `max_length`,
+    /// then padded to the longest in the batch rather than to `max_length`
+    pub fn encode_batch(&self, texts: &[&str], max_length: usize) -> Vec<Encoding> {
+        let ids: Vec<Vec<i64>> = texts
+            .iter()
+            .map(|text| self.truncated(text, max_length))
+            .collect();
+        let seq_len = ids.iter().map(Vec::len).max().unwrap_or(0);
+        ids.into_iter()
+            .map(|ids| self.padded(ids, seq_len))
+            .collect()
+    }
+
+    /// Ids of `text` with special tokens, cut to `max_length` (still ending in [SEP])
+    fn truncated(&self, text: &str, max_length: usize) -> Vec<i64> {
         let mut ids = self.encode(text, true);
 
         // Truncate if needed
@@ -78,18 +155,22 @@ impl Tokenizer {
             ids.truncate(max_length - 1);
             ids.push(self.sep_token_id);
         }
+        ids
+    }
 
+    /// `ids` padded to `length`, with the attention mask covering the real tokens
+    fn padded(&self, mut ids: Vec<i64>, length: usize) -> Encoding {
         // Create attention mask
         let mut attention_mask = vec![1i64; ids.len()];
 
-        // Pad to max length
-        while ids.len() < max_length {
+        // Pad to length
+        while ids.len() < length {
             ids.push(self.pad_token_id);
             attention_mask.push(0);
         }
 
         // Token type IDs (all 0s for single sequence)
-        let token_type_ids = vec![0i64; max_length];
+        let token_type_ids = vec![0i64; ids.len()];
 
         Encoding {
             input_ids: ids,
@@ -183,3 +264,95 @@ impl Tokenizer {
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokenizer(vocab: &[&str]) -> Tokenizer {
+        let dir = std::env::temp_dir().join(format!("smally-tokenizer-{}", uuid::Uuid::now_v7()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("vocab.txt"), vocab.join("\n")).unwrap();
+        let tokenizer = Tokenizer::new(&dir).unwrap();
+        fs::remove_dir_all(&dir).ok();
+        tokenizer
+    }
+
+    #[test]
+    fn test_special_tokens_resolved_from_vocab() {
+        let tokenizer = tokenizer(&["[PAD]", "[UNK]", "[CLS]", "[SEP]", "em", "##bed"]);
+
+        assert_eq!(tokenizer.vocab_size(), 6);
+        let special = tokenizer.special_tokens();
+        assert_eq!((special.cls.id, special.cls.in_vocab), (2, true));
+        assert_eq!((special.sep.id, special.sep.in_vocab), (3, true));
+        assert_eq!((special.pad.id, special.pad.in_vocab), (0, true));
+        assert_eq!((special.unk.id, special.unk.in_vocab), (1, true));
+    }
+
+    #[test]
+    fn test_missing_special_tokens_use_defaults() {
+        let tokenizer = tokenizer(&["hello"]);
+
+        let special = tokenizer.special_tokens();
+        assert_eq!((special.cls.id, special.cls.in_vocab), (101, false));
+        // Id 0 exists ("hello") but is not [PAD]
+        assert_eq!((special.pad.id, special.pad.in_vocab), (0, false));
+    }
+
+    #[test]
+    fn test_pieces_match_encode() {
+        let tokenizer = tokenizer(&["[PAD]", "[UNK]", "[CLS]", "[SEP]", "em", "##bed"]);
+
+        let pieces = tokenizer.pieces("Embed xyz");
+        assert_eq!(
+            pieces,
+            vec![
+                ("em".to_string(), 4),
+                ("##bed".to_string(), 5),
+                ("[UNK]".to_string(), 1),
+            ]
+        );
+
+        let ids: Vec<i64> = pieces.iter().map(|(_, id)| *id).collect();
+        assert_eq!(tokenizer.encode("Embed xyz", false), ids);
+    }
+
+    #[test]
+    fn test_truncation_only_past_the_limit() {
+        let tokenizer = tokenizer(&["[PAD]", "[UNK]", "[CLS]", "[SEP]", "em", "##bed"]);
+        let real =
+            |encoding: &Encoding| encoding.attention_mask.iter().filter(|&&x| x == 1).count();
+
+        // [CLS] em ##bed em ##bed [SEP]
+        let tokens = tokenizer.encode("embed embed", true).len();
+        assert_eq!(tokens, 6);
+
+        // Exactly at the limit: nothing dropped
+        let encoding = tokenizer.encode_with_attention("embed embed", tokens);
+        assert_eq!(real(&encoding), tokens);
+        assert_eq!(encoding.input_ids.last(), Some(&3));
+
+        // One over: truncated to the limit, still ending in [SEP]
+        let encoding = tokenizer.encode_with_attention("embed embed", tokens - 1);
+        assert_eq!(real(&encoding), tokens - 1);
+        assert_eq!(encoding.input_ids.last(), Some(&3));
+    }
+
+    #[test]
+    fn test_batch_padded_to_longest() {
+        let tokenizer = tokenizer(&["[PAD]", "[UNK]", "[CLS]", "[SEP]", "em", "##bed"]);
+
+        let batch = tokenizer.encode_batch(&["embed", "embed embed embed"], 6);
+        // [CLS] em ##bed [SEP] [PAD] [PAD], then truncated to 6
+        assert_eq!(batch[0].input_ids, vec![2, 4, 5, 3, 0, 0]);
+        assert_eq!(batch[0].attention_mask, vec![1, 1, 1, 1, 0, 0]);
+        assert_eq!(batch[1].input_ids, vec![2, 4, 5, 4, 5, 3]);
+        assert_eq!(batch[1].token_type_ids, vec![0; 6]);
+
+        // Short batches aren't padded up to the limit
+        let batch = tokenizer.encode_batch(&["embed"], 128);
+        assert_eq!(batch[0].input_ids.len(), 4);
+        assert!(tokenizer.encode_batch(&[], 128).is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+//! Optional post-processing of pooled embeddings: `W(x - mu)`
+//!
+//! Covers learned mean-centering (W = identity) and PCA whitening or
+//! dimensionality reduction (W = the projection, `out_dim x in_dim`). The
+//! transform is loaded per model and applied only to requests that set
+//! `post_process`; cached embeddings are always the raw pooled vectors.
+//!
+//! File format (little-endian):
+//!
+//! ```text
+//! b"SMTX"  u32 version (1)  u32 in_dim  u32 out_dim
+//! f32 mean[in_dim]
+//! f32 matrix[out_dim][in_dim]   (row-major)
+//! ```
+//!
+//! Written from numpy with:
+//!
+//! ```text
+//! open("transform.bin", "wb").write(
+//!     b"SMTX" + struct.pack("<III", 1, mu.size, W.shape[0])
+//!     + mu.astype("<f4").tobytes() + W.astype("<f4").tobytes())
+//! ```
+
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+use super::{kernels, math};
+
+const MAGIC: &[u8; 4] = b"SMTX";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 16;
+
+/// Mean vector and projection matrix
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    mean: Vec<f32>,
+    /// `out_dim` rows of `in_dim` values
+    matrix: Vec<f32>,
+    in_dim: usize,
+    out_dim: usize,
+}
+
+impl Transform {
+    /// Validates that `matrix` is `out_dim x mean.len()` and finite
+    pub fn new(mean: Vec<f32>, matrix: Vec<f32>, out_dim: usize) -> Result<Self> {
+        let in_dim = mean.len();
+        if in_dim == 0 || out_dim == 0 {
+            bail!(
+                "transform dimensions must be non-zero ({}x{})",
+                out_dim,
+                in_dim
+            );
+        }
+        if matrix.len() != out_dim * in_dim {
+            bail!(
+                "transform matrix has {} values, expected {}x{} = {}",
+                matrix.len(),
+                out_dim,
+                in_dim,
+                out_dim * in_dim
+            );
+        }
+        if !mean.iter().chain(&matrix).all(|x| x.is_finite()) {
+            bail!("transform contains NaN or infinite values");
+        }
+        Ok(Transform {
+            mean,
+            matrix,
+            in_dim,
+            out_dim,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("reading transform {}", path.display()))?;
+        Self::from_bytes(&bytes).with_context(|| format!("loading transform {}", path.display()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            bail!("not a transform file (missing SMTX header)");
+        }
+        let header = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        let (version, in_dim, out_dim) = (header(4), header(8) as usize, header(12) as usize);
+        if version != VERSION {
+            bail!("unsupported transform version {}", version);
+        }
+
+        let expected = in_dim
+            .checked_mul(out_dim)
+            .and_then(|n| n.checked_add(in_dim))
+            .and_then(|n| n.checked_mul(4))
+            .and_then(|n| n.checked_add(HEADER_LEN));
+        match expected {
+            Some(expected) if expected == bytes.len() => {}
+            Some(expected) => bail!(
+                "transform file is {} bytes, expected {} for {}x{}",
+                bytes.len(),
+                expected,
+                out_dim,
+                in_dim
+            ),
+            None => bail!("transform dimensions {}x{} are too large", out_dim, in_dim),
+        }
+
+        let mut floats = bytes[HEADER_LEN..]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()));
+        let mean = floats.by_ref().take(in_dim).collect();
+        let matrix = floats.collect();
+        Self::new(mean, matrix, out_dim)
+    }
+
+    /// Serialize in the file format above
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + (self.mean.len() + self.matrix.len()) * 4);
+        bytes.extend_from_slice(MAGIC);
+        for value in [VERSION, self.in_dim as u32, self.out_dim as u32] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in self.mean.iter().chain(&self.matrix) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Dimension of the vectors the transform accepts
+    pub fn in_dim(&self) -> usize {
+        self.in_dim
+    }
+
+    /// Dimension of the transformed vectors
+    pub fn out_dim(&self) -> usize {
+        self.out_dim
+    }
+
+    /// `W(x - mu)`, L2-normalized if `renormalize`
+    pub fn apply(&self, x: &[f32], renormalize: bool) -> Vec<f32> {
+        assert_eq!(x.len(), self.in_dim, "transform input dimension");
+        let centered: Vec<f32> = x.iter().zip(&self.mean).map(|(x, mu)| x - mu).collect();
+
+        let mut out: Vec<f32> = self
+            .matrix
+            .chunks_exact(self.in_dim)
+            .map(|row| kernels::dot(row, &centered))
+            .collect();
+        if renormalize {
+            math::l2_normalize(&mut out);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn random(rng: &mut StdRng, n: usize) -> Vec<f32> {
+        (0..n).map(|_| rng.gen_range(-1.0..1.0)).collect()
+    }
+
+    /// Straightforward f64 computation of `W(x - mu)`
+    fn reference(mean: &[f32], matrix: &[f32], out_dim: usize, x: &[f32]) -> Vec<f64> {
+        let in_dim = mean.len();
+        (0..out_dim)
+            .map(|i| {
+                (0..in_dim)
+                    .map(|j| matrix[i * in_dim + j] as f64 * (x[j] as f64 - mean[j] as f64))
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_apply_matches_reference() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for (in_dim, out_dim) in [(384, 384), (384, 128), (768, 256), (5, 3)] {
+            let mean = random(&mut rng, in_dim);
+            let matrix = random(&mut rng, in_dim * out_dim);
+            let transform = Transform::new(mean.clone(), matrix.clone(), out_dim).unwrap();
+            let x = random(&mut rng, in_dim);
+
+            let expected = reference(&mean, &matrix, out_dim, &x);
+            let raw = transform.apply(&x, false);
+            assert_eq!(raw.len(), out_dim);
+            for (got, want) in raw.iter().zip(&expected) {
+                assert!((*got as f64 - want).abs() < 1e-3, "{} vs {}", got, want);
+            }
+
+            let norm = expected.iter().map(|v| v * v).sum::<f64>().sqrt();
+            let unit = transform.apply(&x, true);
+            assert!((math::l2_norm(&unit) - 1.0).abs() < 1e-5);
+            for (got, want) in unit.iter().zip(&expected) {
+                assert!((*got as f64 - want / norm).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_identity_matrix_only_centers() {
+        let mean = vec![1.0, 2.0, 3.0];
+        let identity = vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        let transform = Transform::new(mean, identity, 3).unwrap();
+        assert_eq!(
+            transform.apply(&[1.5, 2.0, 2.0], false),
+            vec![0.5, 0.0, -1.0]
+        );
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let transform = Transform::new(random(&mut rng, 6), random(&mut rng, 12), 2).unwrap();
+        let bytes = transform.to_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN + (6 + 12) * 4);
+        assert_eq!(Transform::from_bytes(&bytes).unwrap(), transform);
+    }
+
+    #[test]
+    fn test_invalid_dimensions_rejected() {
+        assert!(Transform::new(vec![0.0; 3], vec![0.0; 5], 2).is_err());
+        assert!(Transform::new(vec![], vec![], 0).is_err());
+        assert!(Transform::new(vec![f32::NAN, 0.0], vec![0.0; 2], 1).is_err());
+
+        let bytes = Transform::new(vec![0.0; 3], vec![0.0; 6], 2)
+            .unwrap()
+            .to_bytes();
+        assert!(Transform::from_bytes(&bytes[..bytes.len() - 4]).is_err());
+        assert!(Transform::from_bytes(b"NOPE").is_err());
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 2;
+        assert!(Transform::from_bytes(&wrong_version).is_err());
+
+        // Dimensions whose product overflows are rejected, not allocated
+        let mut huge = bytes;
+        huge[8..16].copy_from_slice(&[0xff; 8]);
+        assert!(Transform::from_bytes(&huge).is_err());
+    }
+}
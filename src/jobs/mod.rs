@@ -0,0 +1,369 @@
+//! Periodic background jobs
+//!
+//! Jobs are registered by name with an interval, an optional random jitter
+//! added to each wait, a timeout per run and an async run function. The
+//! supervisor runs each job in its own task, logs failures, records
+//! `smally_job_runs_total{job,outcome}` and the last successful run, and
+//! stops all jobs on graceful shutdown (jobs marked `run_on_shutdown` get
+//! one final run first, e.g. to flush buffers). `GET /v1/admin/jobs` lists
+//! each job's last run and next scheduled time.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use parking_lot::{Mutex, RwLock};
+use rand::Rng;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tracing::{error, info, warn};
+
+use crate::monitoring;
+
+/// Timeout of a single run unless the job sets its own
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+type JobFuture = Pin<Box<dyn Future<Output = Result<()>> + Send>>;
+
+/// A named periodic job
+#[derive(Clone)]
+pub struct Job {
+    name: &'static str,
+    interval: Duration,
+    jitter: Duration,
+    timeout: Duration,
+    run_on_shutdown: bool,
+    run: Arc<dyn Fn() -> JobFuture + Send + Sync>,
+}
+
+impl Job {
+    /// Job running `run` every `interval` (first run one interval after
+    /// registration)
+    pub fn new<F, Fut>(name: &'static str, interval: Duration, run: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        Job {
+            name,
+            interval,
+            jitter: Duration::ZERO,
+            timeout: DEFAULT_TIMEOUT,
+            run_on_shutdown: false,
+            run: Arc::new(move || Box::pin(run())),
+        }
+    }
+
+    /// Add up to `jitter` to each wait, so instances don't run in lockstep
+    pub fn jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Give up on a run after `timeout` (it counts as a failure)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run once more on shutdown before stopping
+    pub fn run_on_shutdown(mut self) -> Self {
+        self.run_on_shutdown = true;
+        self
+    }
+
+    fn next_delay(&self) -> Duration {
+        if self.jitter.is_zero() {
+            return self.interval;
+        }
+        let jitter_ms = rand::thread_rng().gen_range(0..=self.jitter.as_millis() as u64);
+        self.interval + Duration::from_millis(jitter_ms)
+    }
+}
+
+/// Result of one run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failure,
+    Timeout,
+}
+
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+            Outcome::Timeout => "timeout",
+        }
+    }
+}
+
+/// What the supervisor knows about a job
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub name: &'static str,
+    pub interval_secs: f64,
+    pub running: bool,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<f64>,
+    pub last_outcome: Option<Outcome>,
+    pub last_error: Option<String>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    /// None once the job has stopped
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+/// Runs registered jobs and tracks their status
+pub struct Supervisor {
+    statuses: RwLock<BTreeMap<&'static str, JobStatus>>,
+    tasks: Mutex<Vec<JoinHandle<()>>>,
+    shutdown: watch::Sender<bool>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Supervisor {
+            statuses: RwLock::new(BTreeMap::new()),
+            tasks: Mutex::new(Vec::new()),
+            shutdown: watch::channel(false).0,
+        }
+    }
+
+    /// Start running `job` in the background
+    pub fn register(self: &Arc<Self>, job: Job) {
+        self.statuses.write().insert(
+            job.name,
+            JobStatus {
+                name: job.name,
+                interval_secs: job.interval.as_secs_f64(),
+                running: false,
+                last_run_at: None,
+                last_duration_ms: None,
+                last_outcome: None,
+                last_error: None,
+                last_success_at: None,
+                next_run_at: None,
+            },
+        );
+
+        let supervisor = self.clone();
+        let shutdown = self.shutdown.subscribe();
+        let task = tokio::spawn(async move { supervisor.run_loop(job, shutdown).await });
+        self.tasks.lock().push(task);
+    }
+
+    /// Status of every job, by name
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        self.statuses.read().values().cloned().collect()
+    }
+
+    /// Stop all jobs, waiting up to `grace` for running and final runs
+    pub async fn shutdown(&self, grace: Duration) {
+        self.shutdown.send_replace(true);
+        let tasks = std::mem::take(&mut *self.tasks.lock());
+        if time::timeout(grace, futures_join(tasks)).await.is_err() {
+            warn!("Background jobs did not stop within {:?}", grace);
+        }
+    }
+
+    async fn run_loop(&self, job: Job, mut shutdown: watch::Receiver<bool>) {
+        loop {
+            let delay = job.next_delay();
+            self.update(job.name, |status| {
+                status.next_run_at = chrono::Duration::from_std(delay)
+                    .ok()
+                    .map(|delay| Utc::now() + delay);
+            });
+
+            tokio::select! {
+                _ = time::sleep(delay) => {}
+                _ = shutdown.changed() => break,
+            }
+            self.run_once(&job).await;
+        }
+
+        self.update(job.name, |status| status.next_run_at = None);
+        if job.run_on_shutdown {
+            info!("Running job {} before shutdown", job.name);
+            self.run_once(&job).await;
+        }
+    }
+
+    async fn run_once(&self, job: &Job) {
+        let started_at = Utc::now();
+        let start = Instant::now();
+        self.update(job.name, |status| status.running = true);
+
+        let (outcome, failure) = match time::timeout(job.timeout, (job.run)()).await {
+            Ok(Ok(())) => (Outcome::Success, None),
+            Ok(Err(e)) => (Outcome::Failure, Some(e.to_string())),
+            Err(_) => (
+                Outcome::Timeout,
+                Some(format!("timed out after {:?}", job.timeout)),
+            ),
+        };
+        let duration = start.elapsed();
+
+        if let Some(failure) = &failure {
+            error!("Job {} failed: {}", job.name, failure);
+        }
+        monitoring::JOB_RUNS
+            .with_label_values(&[job.name, outcome.as_str()])
+            .inc();
+        if outcome == Outcome::Success {
+            monitoring::JOB_LAST_SUCCESS
+                .with_label_values(&[job.name])
+                .set(Utc::now().timestamp());
+        }
+
+        self.update(job.name, |status| {
+            status.running = false;
+            status.last_run_at = Some(started_at);
+            status.last_duration_ms = Some(duration.as_secs_f64() * 1000.0);
+            status.last_outcome = Some(outcome);
+            status.last_error = failure;
+            if outcome == Outcome::Success {
+                status.last_success_at = Some(Utc::now());
+            }
+        });
+    }
+
+    fn update(&self, name: &'static str, f: impl FnOnce(&mut JobStatus)) {
+        if let Some(status) = self.statuses.write().get_mut(name) {
+            f(status);
+        }
+    }
+}
+
+async fn futures_join(tasks: Vec<JoinHandle<()>>) {
+    for task in tasks {
+        let _ = task.await;
+    }
+}
+
+static SUPERVISOR: Lazy<Arc<Supervisor>> = Lazy::new(|| Arc::new(Supervisor::new()));
+
+/// Start running `job` under the global supervisor
+pub fn register(job: Job) {
+    SUPERVISOR.register(job);
+}
+
+/// Status of every registered job
+pub fn statuses() -> Vec<JobStatus> {
+    SUPERVISOR.statuses()
+}
+
+/// Stop all jobs (after final runs), waiting up to `grace`
+pub async fn shutdown(grace: Duration) {
+    SUPERVISOR.shutdown(grace).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn status(supervisor: &Supervisor, name: &str) -> JobStatus {
+        supervisor
+            .statuses()
+            .into_iter()
+            .find(|s| s.name == name)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_outcomes_are_recorded() {
+        let supervisor = Arc::new(Supervisor::new());
+        let interval = Duration::from_millis(10);
+
+        supervisor.register(Job::new("test_ok", interval, || async { Ok(()) }));
+        supervisor.register(Job::new("test_fail", interval, || async {
+            Err(anyhow::anyhow!("boom"))
+        }));
+        supervisor.register(
+            Job::new("test_slow", interval, || async {
+                time::sleep(Duration::from_secs(5)).await;
+                Ok(())
+            })
+            .timeout(Duration::from_millis(10)),
+        );
+
+        time::sleep(Duration::from_millis(100)).await;
+
+        let ok = status(&supervisor, "test_ok");
+        assert_eq!(ok.last_outcome, Some(Outcome::Success));
+        assert!(ok.last_success_at.is_some());
+        assert!(ok.next_run_at.is_some());
+
+        let fail = status(&supervisor, "test_fail");
+        assert_eq!(fail.last_outcome, Some(Outcome::Failure));
+        assert_eq!(fail.last_error.as_deref(), Some("boom"));
+        assert!(fail.last_success_at.is_none());
+
+        let slow = status(&supervisor, "test_slow");
+        assert_eq!(slow.last_outcome, Some(Outcome::Timeout));
+
+        assert!(
+            monitoring::JOB_RUNS
+                .with_label_values(&["test_fail", "failure"])
+                .get()
+                >= 1.0
+        );
+
+        supervisor.shutdown(Duration::from_secs(1)).await;
+        assert!(status(&supervisor, "test_ok").next_run_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_runs_final_flush() {
+        let supervisor = Arc::new(Supervisor::new());
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let counter = runs.clone();
+        supervisor.register(
+            Job::new("test_flush", Duration::from_secs(3600), move || {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            })
+            .run_on_shutdown(),
+        );
+        supervisor.register(Job::new("test_idle", Duration::from_secs(3600), || async {
+            Ok(())
+        }));
+
+        // Nothing due yet
+        time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        supervisor.shutdown(Duration::from_secs(1)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(status(&supervisor, "test_idle").last_run_at.is_none());
+    }
+
+    #[test]
+    fn test_jitter_bounds() {
+        let job = Job::new("test_jitter", Duration::from_secs(10), || async { Ok(()) })
+            .jitter(Duration::from_secs(2));
+        for _ in 0..100 {
+            let delay = job.next_delay();
+            assert!(delay >= Duration::from_secs(10) && delay <= Duration::from_secs(12));
+        }
+    }
+}
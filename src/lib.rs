@@ -1,15 +1,31 @@
 // Library exports for testing and benchmarking
 
+#[cfg(not(any(feature = "inference", feature = "control-plane")))]
+compile_error!("enable the `inference` feature, the `control-plane` feature, or both");
+
 pub mod api;
 pub mod auth;
 pub mod billing;
 pub mod cache;
 pub mod config;
 pub mod database;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
+pub mod flags;
 pub mod inference;
+pub mod jobs;
+pub mod maintenance;
 pub mod models;
 pub mod monitoring;
+pub mod notifications;
+pub mod redact;
+pub mod redis_util;
+pub mod residency;
+#[cfg(feature = "control-plane")]
+pub mod services;
+pub mod state;
 pub mod uuid_dashless;
+#[cfg(feature = "web")]
 pub mod web;
 
 #[cfg(test)]
@@ -1,15 +1,9 @@
-mod api;
-mod auth;
-mod billing;
-mod cache;
-mod config;
-mod database;
-mod inference;
-mod models;
-mod monitoring;
-mod uuid_dashless;
-mod web;
-
+#[cfg(feature = "fault-injection")]
+use ::api::fault;
+use ::api::state::{self, AppState};
+#[cfg(feature = "web")]
+use ::api::web;
+use ::api::{api, billing, config, database, jobs, maintenance, monitoring, notifications};
 use axum::{
     http::Method,
     routing::{get, post},
@@ -17,19 +11,21 @@ use axum::{
 };
 use prometheus::{Encoder, TextEncoder};
 use std::net::SocketAddr;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::signal;
 use tower_http::cors::{Any, CorsLayer};
-use tower_http::services::{ServeDir, ServeFile};
+use tower_http::services::ServeDir;
 use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
 use tracing::{info, Level};
-use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    monitoring::info::mark_started();
+
     // Load .env file if it exists
-    if let Err(e) = dotenvy::dotenv() {
+    if let Err(e) = config::provenance::load_dotenv() {
         println!("No .env file found, using environment variables: {}", e);
     }
 
@@ -65,36 +61,43 @@ async fn main() -> anyhow::Result<()> {
     info!("Starting Smally API...");
 
     let settings = config::get_settings();
+    settings.validate()?;
+    info!(
+        config = %serde_json::to_string(&api::admin::EffectiveConfig::current())?,
+        "Effective configuration"
+    );
+
+    // Database, Redis, cache, token validator, usage buffer (replaying its
+    // outbox) and the model. A model that fails to load only takes the
+    // embedding routes down (503 model_unavailable); the control plane
+    // keeps serving.
+    let state = state::bootstrap(state::Components::SERVER).await?;
 
-    // Initialize database
-    info!("Initializing database...");
-    database::init_db().await?;
-    info!("Database connection pool initialized");
+    // Load shared maintenance state (kept in sync across instances via Redis)
+    info!("Initializing maintenance state...");
+    maintenance::init_maintenance().await?;
 
-    // Load ONNX model
-    info!("Loading ONNX model...");
-    inference::init_model()?;
-    info!("Model loaded: {}", settings.model_name);
+    // Email sender and outbox delivery
+    notifications::email::init()?;
 
-    // Connect to Redis cache
-    info!("Connecting to Redis...");
-    cache::init_cache().await?;
-    info!("Redis connected");
+    // Generate monthly billing statements (at startup and on the 1st of each month)
+    billing::statements::start_statement_task(database::get_db());
 
-    // Initialize Redis connection for billing (rate limiting)
-    info!("Initializing Redis connection for billing...");
-    billing::init_redis().await?;
-    info!("Redis connection for billing initialized");
+    // Weekly sweep of stale rate-limit keys in Redis
+    billing::gc::start_gc_task();
 
-    // Initialize token validator
-    info!("Initializing token validator...");
-    auth::init_token_validator().await?;
-    info!("Token validator initialized");
+    // Daily clean-up of expired trials (owners are emailed)
+    billing::trial::start_expiry_task(database::get_db());
 
-    // Initialize usage buffer with background flush task
-    info!("Initializing usage buffer...");
-    billing::init_usage_buffer(database::get_db())?;
-    info!("Usage buffer initialized with 5-second flush interval");
+    // Hourly discard of reissued API keys nobody retrieved in time
+    #[cfg(feature = "control-plane")]
+    ::api::services::key_reissue::start_expiry_task(database::get_db());
+
+    // Probe Postgres, Redis, the cache and the tokenizer in the background
+    monitoring::probes::start_probe_task(state.clone());
+
+    // Export the cost of verifying an API key, to catch regressions across deploys
+    monitoring::measure_token_verification();
 
     // Setup CORS
     let cors = CorsLayer::new()
@@ -104,30 +107,176 @@ async fn main() -> anyhow::Result<()> {
             hyper::header::CONTENT_TYPE,
             hyper::header::AUTHORIZATION,
             hyper::header::ACCEPT,
+            hyper::header::HeaderName::from_static(api::versions::VERSION_HEADER),
         ])
         .allow_credentials(false);
 
     // Setup routes
     let app = Router::new()
-        // Web UI routes (root domain)
-        .route("/", get(web::home))
-        .route("/login", get(web::auth::login_page))
-        .route("/login", post(web::auth::login_submit))
-        .route("/register", get(web::auth::register_page))
-        .route("/register", post(web::auth::register_submit))
-        .route("/logout", post(web::auth::logout_submit))
-        .route("/organizations", get(web::organizations::list))
-        .route("/organizations", post(web::organizations::create))
-        .route("/switch-org/:org_id", get(web::organizations::switch_org))
-        .route("/organizations/:id", get(web::api_keys::show))
-        .route("/organizations/:id/keys", post(web::api_keys::create))
+        // API routes (will be moved to api. subdomain later)
+        .route("/v1/snippets", get(api::snippets_handler))
+        .route("/v1/limits", get(api::limits_handler))
+        .route("/v1/models", get(api::models_handler))
         .route(
-            "/organizations/:id/keys/:key_id/revoke",
-            post(web::api_keys::revoke),
+            "/v1/meta/versions",
+            get(api::versions::meta_versions_handler),
         )
-        // API routes (will be moved to api. subdomain later)
+        // Per-organization Prometheus metrics (API key with metrics:read)
+        .route(
+            "/v1/organizations/:org_id/metrics",
+            get(api::metrics::org_metrics_handler),
+        )
+        // Admin operations (admin token required)
+        .route(
+            "/v1/admin/maintenance",
+            post(maintenance::admin_maintenance_handler),
+        )
+        .route(
+            "/v1/admin/maintenance/redis-gc",
+            post(api::admin::admin_redis_gc_handler),
+        )
+        .route("/v1/admin/stats", get(api::admin::admin_stats_handler))
+        .route(
+            "/v1/admin/cache/stats",
+            get(api::admin::admin_cache_stats_handler),
+        )
+        .route(
+            "/v1/admin/cache/clear",
+            post(api::admin::admin_cache_clear_handler),
+        )
+        .route(
+            "/v1/admin/usage-alerts",
+            post(api::admin::admin_usage_alerts_handler),
+        )
+        .route("/v1/admin/jobs", get(api::admin::admin_jobs_handler))
+        .route("/v1/admin/flags", get(api::admin::admin_flags_handler))
+        .route("/v1/admin/config", get(api::admin::admin_config_handler))
+        .route(
+            "/v1/admin/organizations/:org_id/flags",
+            axum::routing::put(api::admin::admin_set_org_flags_handler),
+        )
+        .route(
+            "/v1/admin/organizations/:org_id/trial",
+            post(api::admin::admin_grant_trial_handler),
+        )
+        // Shared embedding results (signed link; revoked by the org's keys)
+        .route("/v1/shared/:token", get(api::share::get_shared_handler))
+        .route(
+            "/v1/share/:id",
+            axum::routing::delete(api::share::revoke_share_handler),
+        )
+        // Health and metrics
+        .route("/health", get(api::health_handler))
+        .route("/health/ready", get(api::readiness_handler))
+        .route(
+            "/health/ready/:component",
+            get(api::component_readiness_handler),
+        )
+        .route("/metrics", get(metrics_handler))
+        .route("/internal/info", get(api::admin::instance_info_handler))
+        .route("/api", get(api::root_handler))
+        // OpenAPI documentation
+        .merge(SwaggerUi::new("/swagger-ui").url("/openapi.json", api::openapi()))
+        // Static documentation
+        .nest_service(
+            "/docs",
+            ServeDir::new("./docs/build").append_index_html_on_directories(true),
+        );
+
+    // Routes of the enabled features
+    #[cfg(feature = "inference")]
+    let app = app.merge(inference_routes());
+    #[cfg(feature = "control-plane")]
+    let app = app.merge(control_plane_routes());
+    #[cfg(feature = "web")]
+    let app = app.merge(web_routes());
+    // Chaos testing endpoints (debug builds with `fault-injection` only)
+    #[cfg(feature = "fault-injection")]
+    let app = app.merge(fault_routes());
+    // Landing page demo form (needs the model in this process)
+    #[cfg(all(feature = "web", feature = "inference"))]
+    let app = app.merge(
+        Router::new()
+            .route("/demo", post(web::demo::submit))
+            .route("/demo/compare", post(web::demo::compare))
+            .route_layer(axum::middleware::from_fn(
+                monitoring::readiness::inference_gate,
+            )),
+    );
+
+    let app = app
+        .with_state(state)
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .layer(axum::middleware::from_fn(
+            api::key_prefix::key_prefix_middleware,
+        ))
+        .layer(axum::middleware::from_fn(
+            maintenance::maintenance_middleware,
+        ))
+        // Outside maintenance so its 503s follow the requested version
+        .layer(axum::middleware::from_fn(api::versions::version_middleware))
+        .layer(cors);
+
+    // Create server address
+    let addr: SocketAddr = settings.address().parse()?;
+
+    info!("Smally API started on http://{}", addr);
+
+    // Start server with graceful shutdown
+    let listener = TcpListener::bind(&addr).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await?;
+
+    // Stop background jobs (flushing buffered usage) before exiting
+    jobs::shutdown(Duration::from_secs(10)).await;
+
+    info!("Shutdown complete");
+
+    Ok(())
+}
+
+/// Embedding endpoints (`inference` feature)
+#[cfg(feature = "inference")]
+fn inference_routes() -> Router<AppState> {
+    Router::new()
         // Embedding API (CWT token authentication)
         .route("/v1/embed", post(api::create_embedding_handler))
+        // Several texts in one request (one rate limit hit, one usage event)
+        .route("/v1/embed/batch", post(api::batch::batch_embed_handler))
+        // Embedding over a WebSocket (API key checked once, at the upgrade)
+        .route("/v1/embed/ws", get(api::ws::ws_embed_handler))
+        // Cost and time of a workload (tokenizes, no inference)
+        .route("/v1/estimate", post(api::estimate::estimate_handler))
+        // Embed and share the result behind a signed link
+        .route("/v1/share", post(api::share::create_share_handler))
+        // Landing page "try it" (no auth, per-IP and daily limits)
+        .route("/v1/demo/embed", post(api::demo::demo_embed_handler))
+        .route(
+            "/v1/admin/model/tokenizer",
+            get(api::admin::admin_tokenizer_handler),
+        )
+        .route(
+            "/v1/admin/models/compare",
+            post(api::compare::admin_compare_models_handler),
+        )
+        // 503 model_unavailable while the model can't serve
+        .route_layer(axum::middleware::from_fn(
+            monitoring::readiness::inference_gate,
+        ))
+}
+
+/// User, organization and API key management (`control-plane` feature)
+#[cfg(feature = "control-plane")]
+fn control_plane_routes() -> Router<AppState> {
+    Router::new()
         // User authentication (admin token required)
         .route("/v1/auth/register", post(api::users::register_handler))
         .route("/v1/auth/login", post(api::users::login_handler))
@@ -150,6 +299,61 @@ async fn main() -> anyhow::Result<()> {
             "/v1/organizations/:org_id/members",
             post(api::organizations::invite_member_handler),
         )
+        .route(
+            "/v1/organizations/:org_id/members/:user_id/activity",
+            get(api::organizations::member_activity_handler),
+        )
+        .route(
+            "/v1/organizations/:org_id/cache-policy",
+            axum::routing::put(api::organizations::update_cache_policy_handler),
+        )
+        .route(
+            "/v1/organizations/:org_id/models",
+            axum::routing::put(api::organizations::update_model_settings_handler),
+        )
+        .route(
+            "/v1/organizations/:org_id/billing-anchor",
+            axum::routing::put(api::organizations::update_billing_anchor_handler),
+        )
+        .route(
+            "/v1/organizations/:org_id/ip-storage",
+            axum::routing::put(api::organizations::update_ip_storage_handler),
+        )
+        .route(
+            "/v1/organizations/:org_id/region",
+            axum::routing::put(api::organizations::update_region_handler),
+        )
+        .route(
+            "/v1/organizations/:org_id/requests",
+            get(api::requests::list_requests_handler),
+        )
+        // Organization data export (owner only; download via signed URL)
+        .route(
+            "/v1/organizations/:org_id/export",
+            post(api::exports::request_export_handler),
+        )
+        .route(
+            "/v1/organizations/:org_id/export/:job_id",
+            get(api::exports::get_export_handler),
+        )
+        .route(
+            "/v1/exports/:job_id/download",
+            get(api::exports::download_export_handler),
+        )
+        // Monthly billing statements (stored, never recomputed)
+        .route(
+            "/v1/organizations/:org_id/statements",
+            get(api::statements::list_statements_handler),
+        )
+        .route(
+            "/v1/organizations/:org_id/statements/:month",
+            get(api::statements::get_statement_handler),
+        )
+        // Usage per day (members)
+        .route(
+            "/v1/organizations/:org_id/usage",
+            get(api::usage::usage_handler),
+        )
         // API key management (JWT session required)
         .route(
             "/v1/organizations/:org_id/keys",
@@ -163,39 +367,128 @@ async fn main() -> anyhow::Result<()> {
             "/v1/organizations/:org_id/keys/:key_id",
             axum::routing::delete(api::api_keys::revoke_api_key_handler),
         )
-        // Health and metrics
-        .route("/health", get(api::health_handler))
-        .route("/metrics", get(metrics_handler))
-        .route("/api", get(api::root_handler))
-        // OpenAPI documentation
-        .merge(SwaggerUi::new("/swagger-ui").url("/openapi.json", api::ApiDoc::openapi()))
-        // Static documentation
-        .nest_service(
-            "/docs",
-            ServeDir::new("./docs/build")
-                .append_index_html_on_directories(true)
+        .route(
+            "/v1/organizations/:org_id/keys/:key_id/usage",
+            get(api::api_keys::key_usage_handler),
         )
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
-                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        .route(
+            "/v1/organizations/:org_id/keys/reissued",
+            get(api::api_keys::list_reissued_keys_handler),
         )
-        .layer(cors);
-
-    // Create server address
-    let addr: SocketAddr = settings.address().parse()?;
-
-    info!("Smally API started on http://{}", addr);
+        .route(
+            "/v1/organizations/:org_id/keys/reissued/:reissue_id",
+            post(api::api_keys::retrieve_reissued_key_handler),
+        )
+        // Service accounts (owner only; their tokens also work above)
+        .route(
+            "/v1/organizations/:org_id/service-accounts",
+            post(api::service_accounts::create_service_account_handler),
+        )
+        .route(
+            "/v1/organizations/:org_id/service-accounts",
+            get(api::service_accounts::list_service_accounts_handler),
+        )
+        .route(
+            "/v1/organizations/:org_id/service-accounts/:id",
+            axum::routing::delete(api::service_accounts::revoke_service_account_handler),
+        )
+        // Admin operations (admin token required)
+        .route(
+            "/v1/admin/signing-keys",
+            get(api::admin::admin_signing_keys_handler),
+        )
+        .route(
+            "/v1/admin/keys/reissue",
+            post(api::admin::admin_reissue_keys_handler)
+                .get(api::admin::admin_reissue_progress_handler),
+        )
+        .route(
+            "/v1/admin/keys/reissue/revoke-superseded",
+            post(api::admin::admin_revoke_superseded_handler),
+        )
+        .route("/v1/admin/tokens", get(api::admin::admin_tokens_handler))
+        .route(
+            "/v1/admin/tokens/:token_id",
+            axum::routing::delete(api::admin::admin_revoke_token_handler),
+        )
+        .route(
+            "/v1/admin/statements/regenerate",
+            post(api::statements::admin_regenerate_statement_handler),
+        )
+        .route(
+            "/v1/admin/organizations/:org_id/requests",
+            get(api::requests::admin_list_requests_handler),
+        )
+        .route(
+            "/v1/admin/organizations/:org_id/limits",
+            get(api::admin::admin_org_limits_handler).put(api::admin::admin_set_org_limits_handler),
+        )
+}
 
-    // Start server with graceful shutdown
-    let listener = TcpListener::bind(&addr).await?;
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+/// Fault injection (`fault-injection` feature), served without faults
+#[cfg(feature = "fault-injection")]
+fn fault_routes() -> Router<AppState> {
+    use axum::routing::put;
 
-    info!("Shutdown complete");
+    Router::new()
+        .route(
+            "/v1/admin/faults",
+            get(fault::admin_list_faults_handler).delete(fault::admin_clear_all_faults_handler),
+        )
+        .route(
+            "/v1/admin/faults/:target",
+            put(fault::admin_inject_fault_handler).delete(fault::admin_clear_fault_handler),
+        )
+        .layer(axum::middleware::from_fn(fault::exempt_middleware))
+}
 
-    Ok(())
+/// Web UI (`web` feature, root domain)
+#[cfg(feature = "web")]
+fn web_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(web::home))
+        .route("/login", get(web::auth::login_page))
+        .route("/login", post(web::auth::login_submit))
+        .route("/register", get(web::auth::register_page))
+        .route("/register", post(web::auth::register_submit))
+        .route("/logout", post(web::auth::logout_submit))
+        .route("/organizations", get(web::organizations::list))
+        .route("/organizations", post(web::organizations::create))
+        .route("/switch-org/:org_id", get(web::organizations::switch_org))
+        .route("/organizations/:id", get(web::api_keys::show))
+        .route("/organizations/:id/keys", post(web::api_keys::create))
+        .route(
+            "/organizations/:id/region",
+            post(web::organizations::save_region),
+        )
+        .route(
+            "/organizations/:id/keys/reissued/:reissue_id",
+            post(web::api_keys::retrieve_reissued),
+        )
+        .route(
+            "/organizations/:id/members",
+            get(web::organizations::members),
+        )
+        .route(
+            "/organizations/:id/quota-fragment",
+            get(web::quota::fragment),
+        )
+        .route("/organizations/:id/billing", get(web::statements::list))
+        .route(
+            "/organizations/:id/billing/alerts",
+            post(web::statements::save_alerts),
+        )
+        .route(
+            "/organizations/:id/billing/:month",
+            get(web::statements::show),
+        )
+        .route(
+            "/organizations/:id/keys/:key_id/revoke",
+            post(web::api_keys::revoke),
+        )
+        .route("/share/:token", get(web::share::show))
+        .route("/lang/:locale", get(web::i18n::switch_locale))
+        .layer(axum::middleware::from_fn(web::i18n::locale_middleware))
 }
 
 async fn metrics_handler() -> String {
@@ -0,0 +1,325 @@
+use anyhow::Result;
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::RwLock;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time;
+use tracing::{info, warn};
+
+use crate::api::{versions, ErrorResponse};
+use crate::redis_util;
+
+/// Redis key holding the maintenance state (shared by all instances)
+const REDIS_KEY: &str = "maintenance";
+
+/// How often each instance re-reads the shared state from Redis
+const REFRESH_INTERVAL_SECS: u64 = 2;
+
+/// Retry-After when maintenance has no end time
+const DEFAULT_RETRY_AFTER_SECS: i64 = 300;
+
+/// Active maintenance window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaintenanceState {
+    /// Message shown to API clients and in the dashboard banner
+    pub message: String,
+    /// When maintenance ends automatically (None = until disabled)
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl MaintenanceState {
+    /// Whether this window is still in effect at `now`
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_none_or(|until| now < until)
+    }
+
+    /// Seconds clients should wait before retrying
+    pub fn retry_after(&self, now: DateTime<Utc>) -> i64 {
+        match self.until {
+            Some(until) => (until - now).num_seconds().max(1),
+            None => DEFAULT_RETRY_AFTER_SECS,
+        }
+    }
+}
+
+/// Local copy of the shared state, refreshed in the background
+static STATE: Lazy<RwLock<Option<MaintenanceState>>> = Lazy::new(|| RwLock::new(None));
+
+/// Set once the state was loaded and the refresh task started
+static INITIALIZED: OnceCell<()> = OnceCell::new();
+
+/// Current maintenance state, if active (no I/O; safe to call from templates)
+pub fn current() -> Option<MaintenanceState> {
+    STATE
+        .read()
+        .as_ref()
+        .filter(|state| state.is_active(Utc::now()))
+        .cloned()
+}
+
+fn set_local(state: Option<MaintenanceState>) {
+    *STATE.write() = state;
+}
+
+/// Enable maintenance for all instances. The Redis key expires at `until`,
+/// so the state survives restarts and ends on its own.
+pub async fn enable(state: MaintenanceState) -> Result<()> {
+    let mut conn = redis_util::get_connection();
+    let payload = serde_json::to_string(&state)?;
+
+    match state.until {
+        Some(until) => {
+            let ttl = (until - Utc::now()).num_seconds();
+            if ttl <= 0 {
+                return Err(anyhow::anyhow!("Maintenance end time is in the past"));
+            }
+            conn.set_ex::<_, _, ()>(REDIS_KEY, payload, ttl as u64)
+                .await?;
+        }
+        None => conn.set::<_, _, ()>(REDIS_KEY, payload).await?,
+    }
+
+    set_local(Some(state));
+    Ok(())
+}
+
+/// Disable maintenance for all instances
+pub async fn disable() -> Result<()> {
+    let mut conn = redis_util::get_connection();
+    conn.del::<_, ()>(REDIS_KEY).await?;
+    set_local(None);
+    Ok(())
+}
+
+/// Read the shared state from Redis into the local copy
+async fn refresh() -> Result<()> {
+    let mut conn = redis_util::get_connection();
+    let payload: Option<String> = conn.get(REDIS_KEY).await?;
+    let state = payload.and_then(|p| serde_json::from_str(&p).ok());
+    set_local(state);
+    Ok(())
+}
+
+/// Load the current state from Redis and start refreshing
+pub async fn init_maintenance() -> Result<()> {
+    // If already initialized, return early
+    if INITIALIZED.get().is_some() {
+        return Ok(());
+    }
+
+    refresh().await?;
+    INITIALIZED.set(()).ok(); // Ignore error if already set
+
+    tokio::spawn(async move {
+        let mut interval = time::interval(Duration::from_secs(REFRESH_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = refresh().await {
+                warn!("Failed to refresh maintenance state: {}", e);
+            }
+        }
+    });
+
+    info!("Maintenance state initialized");
+    Ok(())
+}
+
+/// Routes that keep working during maintenance
+fn is_exempt(path: &str) -> bool {
+    !path.starts_with("/v1/") || path.starts_with("/v1/admin/")
+}
+
+/// Middleware: reject /v1/* API traffic with 503 while maintenance is active.
+/// Health, metrics, the web dashboard and /v1/admin/* stay up.
+pub async fn maintenance_middleware(request: Request, next: Next) -> Response {
+    if !is_exempt(request.uri().path()) {
+        if let Some(state) = current() {
+            return unavailable_response(&state, Utc::now());
+        }
+    }
+    next.run(request).await
+}
+
+fn unavailable_response(state: &MaintenanceState, now: DateTime<Utc>) -> Response {
+    let body = ErrorResponse {
+        reset_at: state.until.map(crate::api::format_reset_at),
+        ..ErrorResponse::new("maintenance", state.message.clone())
+    };
+
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::RETRY_AFTER, state.retry_after(now).to_string())],
+        Json(versions::error_body(versions::current(), &body)),
+    )
+        .into_response()
+}
+
+/// Request body for `POST /v1/admin/maintenance`
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Enable or disable maintenance mode (admin token required)
+pub async fn admin_maintenance_handler(
+    _admin_token: crate::auth::AdminTokenClaims,
+    Json(payload): Json<MaintenanceRequest>,
+) -> Result<Response, crate::api::error::ApiError> {
+    use crate::api::error::ApiError;
+
+    if !payload.enabled {
+        disable().await.map_err(|e| {
+            ApiError::InternalError(format!("Failed to disable maintenance: {}", e))
+        })?;
+        info!("Maintenance mode disabled");
+        return Ok((
+            StatusCode::OK,
+            Json(serde_json::json!({ "enabled": false })),
+        )
+            .into_response());
+    }
+
+    let state = MaintenanceState {
+        message: payload.message.unwrap_or_else(|| {
+            "The API is temporarily down for maintenance. Please retry shortly.".to_string()
+        }),
+        until: payload.until,
+    };
+
+    if state.until.is_some_and(|until| until <= Utc::now()) {
+        return Err(ApiError::BadRequest(
+            "until must be in the future".to_string(),
+        ));
+    }
+
+    enable(state.clone())
+        .await
+        .map_err(|e| ApiError::InternalError(format!("Failed to enable maintenance: {}", e)))?;
+    info!("Maintenance mode enabled until {:?}", state.until);
+
+    Ok((
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "enabled": true,
+            "message": state.message,
+            "until": state.until,
+        })),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use serial_test::serial;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/v1/embed", get(|| async { "ok" }))
+            .route("/v1/admin/maintenance", get(|| async { "ok" }))
+            .route("/health", get(|| async { "ok" }))
+            .route("/organizations", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn(maintenance_middleware))
+    }
+
+    async fn status(path: &str) -> StatusCode {
+        app()
+            .oneshot(Request::builder().uri(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_traffic_allowed_when_disabled() {
+        set_local(None);
+        assert_eq!(status("/v1/embed").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_enabled_rejects_api_traffic() {
+        set_local(Some(MaintenanceState {
+            message: "Upgrading database".to_string(),
+            until: Some(Utc::now() + chrono::Duration::minutes(10)),
+        }));
+
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .uri("/v1/embed")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let retry_after: i64 = response.headers()[header::RETRY_AFTER]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(retry_after > 590 && retry_after <= 600);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "maintenance");
+        assert_eq!(json["message"], "Upgrading database");
+
+        // Health, admin and dashboard stay up
+        assert_eq!(status("/health").await, StatusCode::OK);
+        assert_eq!(status("/v1/admin/maintenance").await, StatusCode::OK);
+        assert_eq!(status("/organizations").await, StatusCode::OK);
+
+        set_local(None);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_expired_window_allows_traffic() {
+        set_local(Some(MaintenanceState {
+            message: "Done".to_string(),
+            until: Some(Utc::now() - chrono::Duration::seconds(1)),
+        }));
+
+        assert!(current().is_none());
+        assert_eq!(status("/v1/embed").await, StatusCode::OK);
+
+        set_local(None);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let now = Utc::now();
+        let open_ended = MaintenanceState {
+            message: String::new(),
+            until: None,
+        };
+        assert!(open_ended.is_active(now));
+        assert_eq!(open_ended.retry_after(now), DEFAULT_RETRY_AFTER_SECS);
+
+        let timed = MaintenanceState {
+            message: String::new(),
+            until: Some(now + chrono::Duration::seconds(42)),
+        };
+        assert_eq!(timed.retry_after(now), 42);
+        assert!(!timed.is_active(now + chrono::Duration::seconds(42)));
+    }
+}
@@ -1,6 +1,11 @@
-use chrono::NaiveDateTime;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use validator::{ValidationError, ValidationErrors};
+
+pub mod tier;
+
+pub use tier::TierLimits;
 
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, sqlx::Type)]
 #[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
@@ -30,6 +35,25 @@ impl TierType {
             _ => Err(format!("Invalid tier value: {}", value)),
         }
     }
+
+    /// Tier from its stored name (`free`, `pro`, `scale`)
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "free" => Some(TierType::Free),
+            "pro" => Some(TierType::Pro),
+            "scale" => Some(TierType::Scale),
+            _ => None,
+        }
+    }
+
+    /// Name shown to customers (emails)
+    pub fn display_name(self) -> &'static str {
+        match self {
+            TierType::Free => "Free",
+            TierType::Pro => "Pro",
+            TierType::Scale => "Scale",
+        }
+    }
 }
 
 // Custom serialization to use numbers instead of strings (for CBOR tokens)
@@ -65,8 +89,32 @@ pub struct User {
     pub password_hash: Option<String>,
     pub is_active: bool,
     pub last_selected_org_id: Option<Uuid>,
-    pub created_at: NaiveDateTime,
-    pub updated_at: NaiveDateTime,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// How an organization's embeddings may be cached
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum CachePolicy {
+    /// Keyed by content only, so identical texts share entries across orgs
+    #[default]
+    Shared,
+    /// Keys salted with the organization id, never shared across orgs
+    Private,
+    /// Nothing written to Redis; in-process cache only (salted)
+    Disabled,
+}
+
+impl CachePolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CachePolicy::Shared => "shared",
+            CachePolicy::Private => "private",
+            CachePolicy::Disabled => "disabled",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, Serialize, Deserialize)]
@@ -87,8 +135,20 @@ pub struct Organization {
     pub owner_id: Uuid,
     pub tier: TierType,
     pub is_active: bool,
-    pub created_at: NaiveDateTime,
-    pub updated_at: NaiveDateTime,
+    pub cache_policy: CachePolicy,
+    /// Models the organization's keys may use (None = all)
+    #[cfg_attr(feature = "sqlite", sqlx(json(nullable)))]
+    pub allowed_models: Option<Vec<String>>,
+    /// Model for requests that don't name one (None = the server default)
+    pub default_model: Option<String>,
+    /// Day of the month the billing cycle resets on (None = calendar month)
+    pub billing_anchor_day: Option<i32>,
+    /// Whether client IPs are kept in the request log
+    pub store_client_ip: bool,
+    /// Region requests must be served in (`any` = everywhere)
+    pub region: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
 }
 
 #[allow(dead_code)]
@@ -98,7 +158,7 @@ pub struct OrganizationMember {
     pub organization_id: Uuid,
     pub user_id: Uuid,
     pub role: OrganizationRole,
-    pub created_at: NaiveDateTime,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -108,8 +168,25 @@ pub struct APIKey {
     pub key_id: Uuid,
     pub name: String,
     pub is_active: bool,
-    pub created_at: NaiveDateTime,
-    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub require_request_signing: bool,
+    /// API version pinned at creation (None = the default version)
+    pub api_version: Option<String>,
+    /// Requests with this key reject unknown body fields
+    pub strict_validation: bool,
+}
+
+/// Non-human member of an organization (see `auth::service_account`)
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ServiceAccount {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub name: String,
+    pub role: OrganizationRole,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
 }
 
 #[allow(dead_code)]
@@ -119,7 +196,64 @@ pub struct Usage {
     pub organization_id: Uuid,
     pub api_key_id: Option<Uuid>,
     pub embeddings_count: i32,
-    pub timestamp: NaiveDateTime,
+    pub timestamp: DateTime<Utc>,
+}
+
+// ============================================================================
+// Input validation
+// ============================================================================
+
+/// Maximum length (in characters) of organization and API key names
+pub const NAME_MAX_CHARS: usize = 100;
+
+fn validate_display_name(name: &str, message: &'static str) -> Result<(), ValidationError> {
+    let len = name.chars().count();
+    if name.trim().is_empty() || len > NAME_MAX_CHARS {
+        return Err(ValidationError::new("length").with_message(message.into()));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(ValidationError::new("control_characters")
+            .with_message("Name must not contain control characters".into()));
+    }
+    Ok(())
+}
+
+/// Organization name: 1-100 characters, no control characters
+pub fn validate_org_name(name: &str) -> Result<(), ValidationError> {
+    validate_display_name(
+        name,
+        "Organization name must be between 1 and 100 characters",
+    )
+}
+
+/// API key name: 1-100 characters, no control characters
+pub fn validate_key_name(name: &str) -> Result<(), ValidationError> {
+    validate_display_name(name, "Key name must be between 1 and 100 characters")
+}
+
+/// Service account name: 1-100 characters, no control characters
+pub fn validate_service_account_name(name: &str) -> Result<(), ValidationError> {
+    validate_display_name(
+        name,
+        "Service account name must be between 1 and 100 characters",
+    )
+}
+
+/// Flatten validation errors into per-field messages ("field: message"),
+/// sorted by field name so output is stable
+pub fn validation_messages(errors: &ValidationErrors) -> Vec<String> {
+    let mut messages: Vec<String> = errors
+        .field_errors()
+        .iter()
+        .flat_map(|(field, errors)| {
+            errors.iter().map(move |e| match &e.message {
+                Some(message) => format!("{}: {}", field, message),
+                None => format!("{}: invalid value", field),
+            })
+        })
+        .collect();
+    messages.sort();
+    messages
 }
 
 // ============================================================================
@@ -151,6 +285,9 @@ pub struct CreateUserRequest {
 pub struct LoginRequest {
     pub email: String,
     pub password: String,
+    /// Issue a long-lived session (30 days instead of 24 hours)
+    #[serde(default)]
+    pub remember_me: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -159,47 +296,167 @@ pub struct AuthResponse {
     pub token: String, // JWT for session management
 }
 
+/// Current user profile with the session's expiry
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProfileResponse {
+    #[serde(flatten)]
+    pub user: UserResponse,
+    pub session_expires_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserResponse {
+    #[serde(with = "crate::uuid_dashless::simple")]
     pub id: Uuid,
     pub email: String,
     pub name: Option<String>,
     pub is_active: bool,
-    pub created_at: NaiveDateTime,
+    pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, validator::Validate)]
 pub struct CreateOrganizationRequest {
+    #[validate(custom(function = "validate_org_name"))]
     pub name: String,
     pub tier: Option<TierType>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OrganizationResponse {
+    #[serde(with = "crate::uuid_dashless::simple")]
     pub id: Uuid,
     pub name: String,
     pub tier: TierType,
     pub role: OrganizationRole, // Current user's role
     pub is_active: bool,
-    pub created_at: NaiveDateTime,
+    pub cache_policy: CachePolicy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_models: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub billing_anchor_day: Option<i32>,
+    pub store_client_ip: bool,
+    pub region: String,
+    /// Running trial; `tier` applies again once it ends
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trial: Option<crate::billing::trial::Trial>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Deserialize)]
+pub struct UpdateCachePolicyRequest {
+    pub cache_policy: CachePolicy,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateIpStorageRequest {
+    /// Keep client IPs in the request log (existing ones are erased when
+    /// switched off)
+    pub store_client_ip: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRegionRequest {
+    /// `any`, or a region of the deployment (moving to a stricter one
+    /// purges earlier request logs and cache entries)
+    pub region: String,
+}
+
+/// Omitting the day (or null) goes back to calendar-month cycles
+#[derive(Debug, Deserialize)]
+pub struct UpdateBillingAnchorRequest {
+    /// Day of the month (1-31) the billing cycle resets on
+    pub billing_anchor_day: Option<i32>,
+}
+
+/// Replaces both settings; omitted fields are cleared
+#[derive(Debug, Deserialize)]
+pub struct UpdateModelSettingsRequest {
+    /// Models the organization's keys may use (None = all served models)
+    pub allowed_models: Option<Vec<String>>,
+    /// Model for requests that don't name one (None = the server default)
+    pub default_model: Option<String>,
+}
+
+#[derive(Debug, Deserialize, validator::Validate)]
 pub struct CreateAPIKeyRequest {
+    #[validate(custom(function = "validate_key_name"))]
     pub name: String,
     pub tier: Option<TierType>,
+    /// Per-key token ceiling (defaults to, and may not exceed, the tier ceiling)
+    pub max_tokens: Option<i32>,
+    /// Per-key concurrent request cap (defaults to, and may not exceed, the tier cap)
+    pub max_concurrency: Option<u32>,
+    /// What the key may access (`embed`, `metrics:read`; defaults to embed only)
+    pub scopes: Option<Vec<String>>,
+    /// Models the key may use (defaults to those the organization allows)
+    pub models: Option<Vec<String>>,
+    /// Require HMAC-signed requests for this key
+    #[serde(default)]
+    pub require_request_signing: bool,
+    /// Issue an opaque key (looked up server-side) instead of a CWT
+    #[serde(default)]
+    pub opaque: bool,
+    /// API version for requests without `X-Smally-Version` (defaults to the
+    /// latest)
+    pub api_version: Option<String>,
+    /// Reject request bodies with unknown fields, as with `X-Smally-Strict: true`
+    #[serde(default)]
+    pub strict_validation: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct APIKeyResponse {
+    #[serde(with = "crate::uuid_dashless::simple")]
     pub id: Uuid,
+    #[serde(with = "crate::uuid_dashless::simple")]
     pub key_id: Uuid,
     pub name: String,
     pub is_active: bool,
-    pub created_at: NaiveDateTime,
-    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub require_request_signing: bool,
+    /// API version of requests without `X-Smally-Version`
+    pub api_version: String,
+    pub strict_validation: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub token: Option<String>, // Only included when creating new key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signing_secret: Option<String>, // Only included when creating a signing key
+}
+
+#[derive(Debug, Deserialize, validator::Validate)]
+pub struct CreateServiceAccountRequest {
+    #[validate(custom(function = "validate_service_account_name"))]
+    pub name: String,
+    /// `admin` or `member` (service accounts can't be owners)
+    pub role: OrganizationRole,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServiceAccountResponse {
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub id: Uuid,
+    pub name: String,
+    pub role: OrganizationRole,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>, // Only included when creating the account
+}
+
+impl From<ServiceAccount> for ServiceAccountResponse {
+    fn from(account: ServiceAccount) -> Self {
+        ServiceAccountResponse {
+            id: account.id,
+            name: account.name,
+            role: account.role,
+            created_at: account.created_at,
+            revoked_at: account.revoked_at,
+            token: None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -207,3 +464,73 @@ pub struct InviteMemberRequest {
     pub email: String,
     pub role: OrganizationRole,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use validator::Validate;
+
+    fn org(name: &str) -> CreateOrganizationRequest {
+        CreateOrganizationRequest {
+            name: name.to_string(),
+            tier: None,
+        }
+    }
+
+    fn key(name: &str) -> CreateAPIKeyRequest {
+        CreateAPIKeyRequest {
+            name: name.to_string(),
+            tier: None,
+            max_tokens: None,
+            max_concurrency: None,
+            scopes: None,
+            models: None,
+            require_request_signing: false,
+            opaque: false,
+            api_version: None,
+            strict_validation: false,
+        }
+    }
+
+    #[test]
+    fn test_name_length_boundaries() {
+        assert!(org("").validate().is_err());
+        assert!(org("   ").validate().is_err());
+        assert!(org("a").validate().is_ok());
+        assert!(org(&"a".repeat(NAME_MAX_CHARS)).validate().is_ok());
+        assert!(org(&"a".repeat(NAME_MAX_CHARS + 1)).validate().is_err());
+
+        assert!(key("").validate().is_err());
+        assert!(key("k").validate().is_ok());
+        assert!(key(&"k".repeat(NAME_MAX_CHARS)).validate().is_ok());
+        assert!(key(&"k".repeat(NAME_MAX_CHARS + 1)).validate().is_err());
+    }
+
+    #[test]
+    fn test_name_length_counts_characters() {
+        // 100 multi-byte characters is still within the limit
+        assert!(org(&"é".repeat(NAME_MAX_CHARS)).validate().is_ok());
+    }
+
+    #[test]
+    fn test_name_rejects_control_characters() {
+        for name in ["Acme\nCorp", "Acme\tCorp", "Acme\u{7f}", "\u{1b}[31mred"] {
+            let errors = org(name).validate().unwrap_err();
+            assert_eq!(
+                validation_messages(&errors),
+                vec!["name: Name must not contain control characters"]
+            );
+            assert!(key(name).validate().is_err());
+        }
+        assert!(org("Acme Corp (EU) — ünïcode").validate().is_ok());
+    }
+
+    #[test]
+    fn test_validation_messages_per_field() {
+        let errors = key("").validate().unwrap_err();
+        assert_eq!(
+            validation_messages(&errors),
+            vec!["name: Key name must be between 1 and 100 characters"]
+        );
+    }
+}
@@ -0,0 +1,159 @@
+//! Per-tier limits and pricing, resolved from Settings in one place
+
+use super::TierType;
+use crate::config::{self, Settings};
+
+/// Limits and price of a tier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TierLimits {
+    /// Token ceiling per request (per-key ceilings may only be lower)
+    pub max_tokens: usize,
+    /// Requests per calendar month
+    pub monthly_quota: i32,
+    /// Requests per minute per organization (0 = unlimited)
+    pub rpm: u32,
+    /// Concurrent requests per API key (0 = unlimited)
+    pub max_concurrency: u32,
+    /// Maximum input text length in characters
+    pub max_chars: usize,
+    /// Organizations a user may own (0 = unlimited)
+    pub max_organizations: u32,
+    /// Active API keys per organization (0 = unlimited)
+    pub max_api_keys: u32,
+    /// Organizations per user, and API keys per organization, created per
+    /// hour (0 = unlimited)
+    pub creations_per_hour: u32,
+    /// Cents per million tokens
+    pub price_per_million_tokens: i64,
+}
+
+impl TierLimits {
+    pub fn from_settings(tier: TierType, settings: &Settings) -> Self {
+        let (monthly_quota, rpm, concurrency, price_per_million_tokens) = match tier {
+            TierType::Free => (
+                settings.free_tier_limit,
+                settings.free_tier_rpm,
+                settings.free_tier_concurrency,
+                settings.free_price_per_million_tokens,
+            ),
+            TierType::Pro => (
+                settings.pro_tier_limit,
+                settings.pro_tier_rpm,
+                settings.pro_tier_concurrency,
+                settings.pro_price_per_million_tokens,
+            ),
+            TierType::Scale => (
+                settings.scale_tier_limit,
+                settings.scale_tier_rpm,
+                settings.scale_tier_concurrency,
+                settings.scale_price_per_million_tokens,
+            ),
+        };
+        let (max_organizations, max_api_keys, creations_per_hour) = match tier {
+            TierType::Free => (
+                settings.free_tier_max_organizations,
+                settings.free_tier_max_api_keys,
+                settings.free_tier_creations_per_hour,
+            ),
+            TierType::Pro => (
+                settings.pro_tier_max_organizations,
+                settings.pro_tier_max_api_keys,
+                settings.pro_tier_creations_per_hour,
+            ),
+            TierType::Scale => (
+                settings.scale_tier_max_organizations,
+                settings.scale_tier_max_api_keys,
+                settings.scale_tier_creations_per_hour,
+            ),
+        };
+
+        TierLimits {
+            max_tokens: settings.max_tokens,
+            monthly_quota,
+            rpm: rpm.max(0) as u32,
+            max_concurrency: concurrency.max(0) as u32,
+            max_chars: settings.max_text_chars,
+            max_organizations: max_organizations.max(0) as u32,
+            max_api_keys: max_api_keys.max(0) as u32,
+            creations_per_hour: creations_per_hour.max(0) as u32,
+            price_per_million_tokens: price_per_million_tokens as i64,
+        }
+    }
+}
+
+impl TierType {
+    /// Limits of this tier under the current configuration
+    pub fn limits(self) -> TierLimits {
+        TierLimits::from_settings(self, config::get_settings())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_limits() {
+        let settings = Settings::new();
+
+        assert_eq!(
+            TierLimits::from_settings(TierType::Free, &settings),
+            TierLimits {
+                max_tokens: 128,
+                monthly_quota: 20_000,
+                rpm: 60,
+                max_concurrency: 2,
+                max_chars: 2000,
+                max_organizations: 20,
+                max_api_keys: 100,
+                creations_per_hour: 30,
+                price_per_million_tokens: 0,
+            }
+        );
+        assert_eq!(
+            TierLimits::from_settings(TierType::Pro, &settings),
+            TierLimits {
+                max_tokens: 128,
+                monthly_quota: 100_000,
+                rpm: 600,
+                max_concurrency: 8,
+                max_chars: 2000,
+                max_organizations: 50,
+                max_api_keys: 500,
+                creations_per_hour: 120,
+                price_per_million_tokens: 0,
+            }
+        );
+        assert_eq!(
+            TierLimits::from_settings(TierType::Scale, &settings),
+            TierLimits {
+                max_tokens: 128,
+                monthly_quota: 2_000_000,
+                rpm: 3000,
+                max_concurrency: 32,
+                max_chars: 2000,
+                max_organizations: 100,
+                max_api_keys: 2000,
+                creations_per_hour: 600,
+                price_per_million_tokens: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_limits_follow_settings() {
+        let settings = Settings {
+            pro_tier_limit: 5,
+            pro_tier_rpm: -1,
+            pro_price_per_million_tokens: 250,
+            max_text_chars: 100,
+            ..Settings::new()
+        };
+
+        let limits = TierLimits::from_settings(TierType::Pro, &settings);
+        assert_eq!(limits.monthly_quota, 5);
+        assert_eq!(limits.rpm, 0);
+        assert_eq!(limits.price_per_million_tokens, 250);
+        assert_eq!(limits.max_chars, 100);
+    }
+}
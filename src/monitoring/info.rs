@@ -0,0 +1,203 @@
+//! What an instance runs, for fleet inventory
+//!
+//! `GET /internal/info` (admin token required) answers "which instances are
+//! on which git hash, model revision and configuration" in one scrape: the
+//! build, the model and the hash of its file, the cargo features compiled
+//! in, feature flag modes, the key configuration in effect, uptime and the
+//! host or pod name. `InstanceInfo` deserializes too, so a fleet overview
+//! can collect it from each instance and compare.
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::api::BuildInfo;
+use crate::auth::keyring;
+use crate::config;
+use crate::flags::{Flag, FlagMode};
+
+/// Hex characters kept of the configuration hash
+const CONFIG_FINGERPRINT_LEN: usize = 16;
+
+/// Cargo features, each with whether it's compiled in
+const FEATURES: [(&str, bool); 8] = [
+    ("language-detection", cfg!(feature = "language-detection")),
+    ("inference", cfg!(feature = "inference")),
+    ("control-plane", cfg!(feature = "control-plane")),
+    ("web", cfg!(feature = "web")),
+    ("acl", cfg!(feature = "acl")),
+    ("onednn", cfg!(feature = "onednn")),
+    ("sqlite", cfg!(feature = "sqlite")),
+    ("fault-injection", cfg!(feature = "fault-injection")),
+];
+
+static STARTED: Lazy<(Instant, DateTime<Utc>)> = Lazy::new(|| (Instant::now(), Utc::now()));
+
+/// SHA-256 of the model file, hashed once (None if there is no file)
+static MODEL_FILE_SHA256: OnceCell<Option<String>> = OnceCell::new();
+
+/// Record the process start (call first thing in `main`)
+pub fn mark_started() {
+    Lazy::force(&STARTED);
+}
+
+/// An instance's build, model and configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstanceInfo {
+    /// `HOSTNAME`, if set
+    pub hostname: Option<String>,
+    /// `POD_NAME`, if set (Kubernetes downward API)
+    pub pod_name: Option<String>,
+    pub version: String,
+    pub build: BuildInfo,
+    pub model: ServedModel,
+    /// Cargo features compiled in
+    pub features: Vec<String>,
+    /// Deployment-wide mode of each feature flag
+    pub flags: BTreeMap<String, FlagMode>,
+    pub keys: KeyVersions,
+    /// Hash of the effective settings (secrets masked): instances with the
+    /// same fingerprint run the same configuration
+    pub config_fingerprint: String,
+    pub started_at: DateTime<Utc>,
+    pub uptime_secs: u64,
+}
+
+/// The served model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServedModel {
+    pub name: String,
+    pub revision: Option<String>,
+    /// SHA-256 of `model.onnx`, None if the instance has no model file
+    pub file_sha256: Option<String>,
+}
+
+/// Which keys sign and verify credentials
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyVersions {
+    /// Kid new API keys are signed with
+    pub token_signing_kid: Option<String>,
+    /// Kids tokens are verified against, newest first
+    pub token_verification_kids: Vec<String>,
+    /// Session JWT secrets configured; sessions are signed with the first
+    /// and older ones only verify
+    pub session_secrets: usize,
+}
+
+impl InstanceInfo {
+    /// Information about this instance
+    pub async fn current() -> Self {
+        let settings = config::get_settings();
+        let (started, started_at) = *STARTED;
+
+        let file_sha256 = match MODEL_FILE_SHA256.get() {
+            Some(hash) => hash.clone(),
+            None => {
+                let model_file = Path::new(&settings.model_path).join("model.onnx");
+                tokio::task::spawn_blocking(move || {
+                    MODEL_FILE_SHA256
+                        .get_or_init(|| file_sha256(&model_file))
+                        .clone()
+                })
+                .await
+                .unwrap_or(None)
+            }
+        };
+
+        InstanceInfo {
+            hostname: env_var("HOSTNAME"),
+            pod_name: env_var("POD_NAME"),
+            version: settings.version.clone(),
+            build: BuildInfo::current(),
+            model: ServedModel {
+                name: settings.model_name.clone(),
+                revision: settings.model_revision.clone(),
+                file_sha256,
+            },
+            features: enabled_features(),
+            flags: Flag::ALL
+                .into_iter()
+                .map(|flag| (flag.as_str().to_string(), flag.mode()))
+                .collect(),
+            keys: KeyVersions {
+                token_signing_kid: keyring::signing_kid(),
+                token_verification_kids: keyring::Keyring::configured()
+                    .map(|keys| keys.kids())
+                    .unwrap_or_default(),
+                session_secrets: settings.jwt_secrets.len(),
+            },
+            config_fingerprint: fingerprint(&settings.masked()),
+            started_at,
+            uptime_secs: started.elapsed().as_secs(),
+        }
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn enabled_features() -> Vec<String> {
+    FEATURES
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(feature, _)| feature.to_string())
+        .collect()
+}
+
+/// Hex SHA-256 of a file, None if it can't be read
+fn file_sha256(path: &Path) -> Option<String> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher).ok()?;
+    Some(hex::encode(hasher.finalize()))
+}
+
+/// Short hash of a settings document
+fn fingerprint(settings: &serde_json::Value) -> String {
+    let digest = Sha256::digest(settings.to_string().as_bytes());
+    hex::encode(digest)[..CONFIG_FINGERPRINT_LEN].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_follows_settings() {
+        let settings = serde_json::json!({ "model_name": "all-MiniLM-L6-v2", "port": 8000 });
+        let same = serde_json::json!({ "port": 8000, "model_name": "all-MiniLM-L6-v2" });
+        let other = serde_json::json!({ "model_name": "all-MiniLM-L6-v2", "port": 8001 });
+
+        assert_eq!(fingerprint(&settings).len(), CONFIG_FINGERPRINT_LEN);
+        assert_eq!(fingerprint(&settings), fingerprint(&same));
+        assert_ne!(fingerprint(&settings), fingerprint(&other));
+    }
+
+    #[test]
+    fn test_file_sha256() {
+        let path = std::env::temp_dir().join(format!("smally-info-{}", uuid::Uuid::now_v7()));
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(
+            file_sha256(&path).as_deref(),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        std::fs::remove_file(&path).ok();
+        assert_eq!(file_sha256(&path), None);
+    }
+
+    #[tokio::test]
+    async fn test_info_round_trips() {
+        let info = InstanceInfo::current().await;
+        assert!(info.features.iter().any(|f| f == "inference") == cfg!(feature = "inference"));
+        assert_eq!(info.flags.len(), Flag::ALL.len());
+
+        let json = serde_json::to_string(&info).unwrap();
+        let parsed: InstanceInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, info);
+    }
+}
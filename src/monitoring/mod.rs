@@ -1,5 +1,16 @@
 use once_cell::sync::Lazy;
-use prometheus::{register_counter_vec, register_histogram, CounterVec, Histogram};
+use prometheus::{
+    register_counter, register_counter_vec, register_gauge, register_histogram,
+    register_histogram_vec, register_int_gauge, register_int_gauge_vec, Counter, CounterVec, Gauge,
+    Histogram, HistogramVec, IntGauge, IntGaugeVec,
+};
+
+use crate::auth;
+
+pub mod info;
+pub mod probes;
+pub mod readiness;
+pub mod selfcheck;
 
 pub static REQUEST_COUNT: Lazy<CounterVec> = Lazy::new(|| {
     register_counter_vec!(
@@ -32,14 +43,42 @@ pub static CACHE_HITS: Lazy<CounterVec> = Lazy::new(|| {
     register_counter_vec!(
         "smally_cache_hits_total",
         "Total number of cache hits",
-        &["cache_level"]
+        &["cache_level", "policy"]
+    )
+    .unwrap()
+});
+
+pub static CACHE_MISSES: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        "smally_cache_misses_total",
+        "Total number of cache misses",
+        &["policy"]
+    )
+    .unwrap()
+});
+
+pub static CACHE_L1_ADMISSION_REJECTIONS: Lazy<Counter> = Lazy::new(|| {
+    register_counter!(
+        "smally_cache_l1_admission_rejections_total",
+        "Entries kept out of the L1 cache by the admission policy"
+    )
+    .unwrap()
+});
+
+pub static CACHE_L2_WRITES_DROPPED: Lazy<Counter> = Lazy::new(|| {
+    register_counter!(
+        "smally_cache_l2_writes_dropped_total",
+        "L2 cache writes dropped because the write queue was full"
     )
     .unwrap()
 });
 
-pub static CACHE_MISSES: Lazy<prometheus::Counter> = Lazy::new(|| {
-    prometheus::register_counter!("smally_cache_misses_total", "Total number of cache misses")
-        .unwrap()
+pub static CACHE_L2_WRITE_QUEUE_DEPTH: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "smally_cache_l2_write_queue_depth",
+        "L2 cache writes waiting for the writer task"
+    )
+    .unwrap()
 });
 
 pub static TOKEN_COUNT: Lazy<Histogram> = Lazy::new(|| {
@@ -68,3 +107,167 @@ pub static RATE_LIMIT_EXCEEDED: Lazy<CounterVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+
+pub static DEPENDENCY_PROBE_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "smally_dependency_probe_seconds",
+        "Latency of background dependency probes in seconds",
+        &["dependency"],
+        vec![0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0, 5.0]
+    )
+    .unwrap()
+});
+
+pub static DEPENDENCY_UP: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "smally_dependency_up",
+        "Whether the last probe of a dependency succeeded (1) or failed (0)",
+        &["dependency"]
+    )
+    .unwrap()
+});
+
+pub static EMAIL_SENDS: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        "smally_email_sends_total",
+        "Email send attempts by outcome (success, failure)",
+        &["outcome"]
+    )
+    .unwrap()
+});
+
+pub static JOB_RUNS: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        "smally_job_runs_total",
+        "Background job runs by outcome (success, failure, timeout)",
+        &["job", "outcome"]
+    )
+    .unwrap()
+});
+
+pub static JOB_LAST_SUCCESS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        "smally_job_last_success_timestamp_seconds",
+        "Unix time of the last successful run of a background job",
+        &["job"]
+    )
+    .unwrap()
+});
+
+pub static SESSION_SECRET_FALLBACKS: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        "smally_session_secret_fallbacks_total",
+        "Session tokens verified with a non-primary JWT secret",
+        &["secret_index"]
+    )
+    .unwrap()
+});
+
+pub static REDIS_GC_KEYS_SCANNED: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        "smally_redis_gc_keys_scanned_total",
+        "Redis keys examined by the garbage collector",
+        &["prefix"]
+    )
+    .unwrap()
+});
+
+pub static REDIS_GC_KEYS_REMOVED: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        "smally_redis_gc_keys_removed_total",
+        "Stale Redis keys removed by the garbage collector",
+        &["prefix"]
+    )
+    .unwrap()
+});
+
+pub static DEMO_REQUESTS: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        "smally_demo_requests_total",
+        "Public demo embed requests (not recorded as usage)",
+        &["outcome"]
+    )
+    .unwrap()
+});
+
+pub static WS_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!("smally_ws_connections", "Open /v1/embed/ws sessions").unwrap()
+});
+
+pub static WS_FRAMES: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        "smally_ws_frames_total",
+        "Frames received on /v1/embed/ws sessions by outcome (ok or the error type)",
+        &["outcome"]
+    )
+    .unwrap()
+});
+
+pub static SHED_WAIT_ACCURACY: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "smally_shed_wait_accuracy_ratio",
+        "Actual time from a shed response to the retry's success over the suggested wait",
+        vec![0.25, 0.5, 0.75, 0.9, 1.1, 1.25, 1.5, 2.0, 4.0, 8.0]
+    )
+    .unwrap()
+});
+
+pub static UNPREFIXED_TOKENS: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        "smally_unprefixed_tokens_total",
+        "Requests authenticated with an API key sent without API_KEY_PREFIX (deprecated), by organization",
+        &["org_id"]
+    )
+    .unwrap()
+});
+
+pub static SINGLEFLIGHT_COALESCED: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        "smally_singleflight_coalesced_total",
+        "Cache misses that waited for an identical in-flight inference instead of running their own",
+        &["policy"]
+    )
+    .unwrap()
+});
+
+pub static CREATION_LIMIT_REJECTIONS: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        "smally_creation_limit_rejections_total",
+        "Organization and API key creations refused by a creation limit",
+        &["resource", "limit"]
+    )
+    .unwrap()
+});
+
+pub static RESPONSE_UPDATES_REJECTED: Lazy<Counter> = Lazy::new(|| {
+    register_counter!(
+        "smally_response_updates_rejected_total",
+        "Buffered responses dropped from api_request_log because the database rejected their values"
+    )
+    .unwrap()
+});
+
+pub static TOKEN_VERIFY_MICROS: Lazy<Gauge> = Lazy::new(|| {
+    register_gauge!(
+        "smally_token_verify_us",
+        "Mean API key (CWT) verification time in microseconds, measured at startup"
+    )
+    .unwrap()
+});
+
+/// Verifications timed at startup for `TOKEN_VERIFY_MICROS`
+const TOKEN_VERIFY_ITERATIONS: u32 = 1000;
+
+/// Time token verification once, off the async workers, and export the mean
+pub fn measure_token_verification() {
+    tokio::task::spawn_blocking(
+        || match auth::measure_verification(TOKEN_VERIFY_ITERATIONS) {
+            Ok(mean) => {
+                let micros = mean.as_secs_f64() * 1_000_000.0;
+                TOKEN_VERIFY_MICROS.set(micros);
+                tracing::info!(micros, "Token verification timed");
+            }
+            Err(e) => tracing::warn!("Could not time token verification: {}", e),
+        },
+    );
+}
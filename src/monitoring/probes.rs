@@ -0,0 +1,289 @@
+//! Background dependency probes
+//!
+//! Every 15 seconds Postgres (`SELECT 1`), its migrations (at this
+//! build's, unless `MIGRATIONS_MODE=skip`), Redis (`PING`), the embedding
+//! cache (writing and reading back a key) and, with the `inference`
+//! feature, the tokenizer (a tiny encode, no inference) are probed. Each
+//! result feeds the `smally_dependency_probe_seconds` histogram and the
+//! `smally_dependency_up` gauge, and the most recent results are kept in
+//! memory for `/health/ready?verbose=true`. `smally check` runs the same
+//! checks once, unrecorded (see `selfcheck`).
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::collections::VecDeque;
+use std::future::Future;
+#[cfg(feature = "inference")]
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time;
+
+use super::{DEPENDENCY_PROBE_SECONDS, DEPENDENCY_UP};
+use crate::cache::EmbeddingCache;
+use crate::database::migrations::{self, MigrationsMode};
+use crate::database::DbPool;
+#[cfg(feature = "inference")]
+use crate::inference::{self, EmbeddingModel};
+use crate::redis_util::RedisConnection;
+use crate::state::AppState;
+
+/// How often the dependencies are probed
+const PROBE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// A probe taking longer than this counts as failed
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of probe results kept in memory (across all dependencies)
+const HISTORY_SIZE: usize = 120;
+
+pub const POSTGRES: &str = "postgres";
+pub const MIGRATIONS: &str = "migrations";
+pub const REDIS: &str = "redis";
+pub const CACHE: &str = "cache";
+pub const TOKENIZER: &str = "tokenizer";
+
+/// Probed dependencies, in reporting order
+#[cfg(feature = "inference")]
+pub const DEPENDENCIES: &[&str] = &[POSTGRES, MIGRATIONS, REDIS, CACHE, TOKENIZER];
+#[cfg(not(feature = "inference"))]
+pub const DEPENDENCIES: &[&str] = &[POSTGRES, MIGRATIONS, REDIS, CACHE];
+
+/// Outcome of a single probe
+#[derive(Debug, Clone)]
+pub struct ProbeResult {
+    pub dependency: &'static str,
+    pub ok: bool,
+    pub latency_ms: f64,
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Bounded in-memory history of probe results (oldest first)
+pub struct ProbeHistory {
+    capacity: usize,
+    results: VecDeque<ProbeResult>,
+}
+
+impl ProbeHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            results: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, result: ProbeResult) {
+        if self.results.len() == self.capacity {
+            self.results.pop_front();
+        }
+        self.results.push_back(result);
+    }
+
+    /// Most recent result of a dependency
+    pub fn latest(&self, dependency: &str) -> Option<&ProbeResult> {
+        self.results
+            .iter()
+            .rev()
+            .find(|result| result.dependency == dependency)
+    }
+
+    /// Failed probes still in the history, newest first
+    pub fn failures(&self) -> Vec<ProbeResult> {
+        self.results
+            .iter()
+            .rev()
+            .filter(|result| !result.ok)
+            .cloned()
+            .collect()
+    }
+}
+
+static HISTORY: Lazy<RwLock<ProbeHistory>> =
+    Lazy::new(|| RwLock::new(ProbeHistory::new(HISTORY_SIZE)));
+
+/// Latest result per dependency (None until the first probe ran)
+pub fn latest() -> Vec<(&'static str, Option<ProbeResult>)> {
+    let history = HISTORY.read();
+    DEPENDENCIES
+        .iter()
+        .map(|dependency| (*dependency, history.latest(dependency).cloned()))
+        .collect()
+}
+
+/// Recent failed probes, newest first
+pub fn recent_failures() -> Vec<ProbeResult> {
+    HISTORY.read().failures()
+}
+
+/// Run one check with a timeout, without recording it
+pub async fn measure<F>(dependency: &'static str, check: F) -> ProbeResult
+where
+    F: Future<Output = Result<()>>,
+{
+    let started = Instant::now();
+    let outcome = match time::timeout(PROBE_TIMEOUT, check).await {
+        Ok(outcome) => outcome,
+        Err(_) => Err(anyhow!("timed out after {:?}", PROBE_TIMEOUT)),
+    };
+
+    ProbeResult {
+        dependency,
+        ok: outcome.is_ok(),
+        latency_ms: started.elapsed().as_secs_f64() * 1000.0,
+        error: outcome.err().map(|e| e.to_string()),
+        checked_at: Utc::now(),
+    }
+}
+
+/// Run one probe with a timeout and record its outcome
+async fn probe<F>(dependency: &'static str, check: F) -> ProbeResult
+where
+    F: Future<Output = Result<()>>,
+{
+    let result = measure(dependency, check).await;
+
+    DEPENDENCY_PROBE_SECONDS
+        .with_label_values(&[dependency])
+        .observe(result.latency_ms / 1000.0);
+    DEPENDENCY_UP
+        .with_label_values(&[dependency])
+        .set(result.ok as i64);
+    if let Some(error) = &result.error {
+        tracing::warn!("Dependency probe failed: {}: {}", dependency, error);
+    }
+    HISTORY.write().push(result.clone());
+
+    result
+}
+
+pub async fn check_postgres(pool: &DbPool) -> Result<()> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(())
+}
+
+/// The database is at this build's migrations (always passes with
+/// `MIGRATIONS_MODE=skip`)
+pub async fn check_migrations(pool: &DbPool) -> Result<()> {
+    match MigrationsMode::from_settings() {
+        MigrationsMode::Skip => Ok(()),
+        _ => migrations::check_current(pool).await,
+    }
+}
+
+pub async fn check_redis(mut conn: RedisConnection) -> Result<()> {
+    let _: String = redis::cmd("PING").query_async(&mut conn).await?;
+    Ok(())
+}
+
+pub async fn check_cache(cache: &EmbeddingCache) -> Result<()> {
+    cache.check_writable().await
+}
+
+#[cfg(feature = "inference")]
+pub async fn check_tokenizer(model: Arc<RwLock<EmbeddingModel>>) -> Result<()> {
+    let tokens = tokio::task::spawn_blocking(move || model.read().count_tokens("ping")).await?;
+    if tokens == 0 {
+        return Err(anyhow!("tokenizer returned no tokens"));
+    }
+    Ok(())
+}
+
+/// Probe every dependency of `state` once
+pub async fn probe_all(state: &AppState) {
+    probe(POSTGRES, check_postgres(&state.db)).await;
+    probe(MIGRATIONS, check_migrations(&state.db)).await;
+    probe(REDIS, check_redis(state.redis.clone())).await;
+    probe(CACHE, check_cache(&state.cache)).await;
+    #[cfg(feature = "inference")]
+    probe(TOKENIZER, async {
+        if let Some(reason) = inference::unavailable() {
+            return Err(anyhow!(reason));
+        }
+        match &state.model {
+            Some(model) => check_tokenizer(model.clone()).await,
+            None => Err(anyhow!("model not loaded")),
+        }
+    })
+    .await;
+}
+
+/// Start the background probe task (first round runs immediately)
+pub fn start_probe_task(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = time::interval(PROBE_INTERVAL);
+        loop {
+            interval.tick().await;
+            probe_all(&state).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(dependency: &'static str, ok: bool) -> ProbeResult {
+        ProbeResult {
+            dependency,
+            ok,
+            latency_ms: 1.0,
+            error: (!ok).then(|| "connection refused".to_string()),
+            checked_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut history = ProbeHistory::new(3);
+        history.push(result(POSTGRES, false));
+        for _ in 0..3 {
+            history.push(result(REDIS, true));
+        }
+
+        // The oldest (failed) result was evicted
+        assert_eq!(history.results.len(), 3);
+        assert!(history.latest(POSTGRES).is_none());
+        assert!(history.failures().is_empty());
+    }
+
+    #[test]
+    fn test_latest_and_failures() {
+        let mut history = ProbeHistory::new(10);
+        history.push(result(POSTGRES, false));
+        history.push(result(REDIS, true));
+        history.push(result(POSTGRES, true));
+        history.push(result(REDIS, false));
+
+        assert!(history.latest(POSTGRES).unwrap().ok);
+        assert!(!history.latest(REDIS).unwrap().ok);
+        assert!(history.latest(TOKENIZER).is_none());
+
+        let failures = history.failures();
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].dependency, REDIS);
+        assert_eq!(failures[1].dependency, POSTGRES);
+    }
+
+    #[tokio::test]
+    async fn test_probe_records_metrics() {
+        let ok = probe("test-ok", async { Ok(()) }).await;
+        assert!(ok.ok);
+        assert_eq!(DEPENDENCY_UP.with_label_values(&["test-ok"]).get(), 1);
+
+        let failed = probe("test-failed", async { Err(anyhow!("boom")) }).await;
+        assert!(!failed.ok);
+        assert_eq!(failed.error.as_deref(), Some("boom"));
+        assert_eq!(DEPENDENCY_UP.with_label_values(&["test-failed"]).get(), 0);
+        assert_eq!(
+            DEPENDENCY_PROBE_SECONDS
+                .with_label_values(&["test-failed"])
+                .get_sample_count(),
+            1
+        );
+        assert!(recent_failures()
+            .iter()
+            .any(|result| result.dependency == "test-failed"));
+    }
+}
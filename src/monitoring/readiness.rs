@@ -0,0 +1,220 @@
+//! Component readiness
+//!
+//! The dependency probes are grouped into the components a route needs:
+//! `database` (Postgres and its
+//! migrations), `redis` (rate limits, maintenance state), `cache` (the
+//! embedding cache) and, with the `inference` feature, `inference` (the
+//! model, plus the tokenizer probe). `/health/ready` is ready when every
+//! component is; `/health/ready/:component` reports one of them, so the
+//! deployment serving embeddings can use `/health/ready/inference`.
+//!
+//! A model that failed to load only takes `inference` down: the management
+//! API and dashboard keep serving, while the embedding routes answer 503
+//! `model_unavailable` (`inference_gate`).
+
+use super::probes::{self, ProbeResult};
+
+pub const INFERENCE: &str = "inference";
+pub const DATABASE: &str = "database";
+pub const REDIS: &str = "redis";
+pub const CACHE: &str = "cache";
+
+/// Components, in reporting order
+#[cfg(feature = "inference")]
+pub const COMPONENTS: &[&str] = &[INFERENCE, DATABASE, REDIS, CACHE];
+#[cfg(not(feature = "inference"))]
+pub const COMPONENTS: &[&str] = &[DATABASE, REDIS, CACHE];
+
+/// State of one component
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentState {
+    pub component: &'static str,
+    pub ready: bool,
+    /// Why the component isn't ready
+    pub reason: Option<String>,
+}
+
+/// Probed dependencies a component is made of
+fn dependencies(component: &str) -> &'static [&'static str] {
+    match component {
+        INFERENCE => &[probes::TOKENIZER],
+        DATABASE => &[probes::POSTGRES, probes::MIGRATIONS],
+        REDIS => &[probes::REDIS],
+        CACHE => &[probes::CACHE],
+        _ => &[],
+    }
+}
+
+/// State of `component` from the latest probes, and `down` (why it's known
+/// to be broken, regardless of the probes)
+pub fn state(
+    component: &'static str,
+    latest: &[(&'static str, Option<ProbeResult>)],
+    down: Option<String>,
+) -> ComponentState {
+    let reason = down.or_else(|| {
+        dependencies(component).iter().find_map(|dependency| {
+            match latest.iter().find(|(name, _)| name == dependency) {
+                Some((_, Some(result))) if result.ok => None,
+                Some((_, Some(result))) => Some(format!(
+                    "{}: {}",
+                    dependency,
+                    result.error.as_deref().unwrap_or("probe failed")
+                )),
+                _ => Some(format!("{}: not probed yet", dependency)),
+            }
+        })
+    });
+    ComponentState {
+        component,
+        ready: reason.is_none(),
+        reason,
+    }
+}
+
+/// Why a component is down regardless of its probes
+fn known_down(component: &str) -> Option<String> {
+    match component {
+        #[cfg(feature = "inference")]
+        INFERENCE => crate::inference::unavailable(),
+        _ => None,
+    }
+}
+
+/// State of every component
+pub fn components() -> Vec<ComponentState> {
+    let latest = probes::latest();
+    COMPONENTS
+        .iter()
+        .map(|component| state(component, &latest, known_down(component)))
+        .collect()
+}
+
+/// State of one component (None if there is no such component)
+pub fn component(name: &str) -> Option<ComponentState> {
+    let component = COMPONENTS.iter().find(|component| **component == name)?;
+    Some(state(component, &probes::latest(), known_down(component)))
+}
+
+/// Middleware of the data-plane routes: 503 `model_unavailable` while the
+/// model can't serve. Only the model itself counts here, not the tokenizer
+/// probe: a probe timing out under load shouldn't turn traffic away.
+#[cfg(feature = "inference")]
+pub async fn inference_gate(
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    use axum::response::IntoResponse;
+
+    match crate::inference::unavailable() {
+        Some(reason) => crate::api::ApiError::ModelUnavailable(format!(
+            "Embeddings are temporarily unavailable: {}",
+            reason
+        ))
+        .into_response(),
+        None => next.run(request).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn result(dependency: &'static str, ok: bool) -> (&'static str, Option<ProbeResult>) {
+        (
+            dependency,
+            Some(ProbeResult {
+                dependency,
+                ok,
+                latency_ms: 1.0,
+                error: (!ok).then(|| "connection refused".to_string()),
+                checked_at: Utc::now(),
+            }),
+        )
+    }
+
+    #[test]
+    fn test_component_state() {
+        let latest = vec![
+            result(probes::POSTGRES, false),
+            result(probes::REDIS, true),
+            (probes::CACHE, None),
+            result(probes::TOKENIZER, true),
+        ];
+
+        assert!(state(REDIS, &latest, None).ready);
+        assert_eq!(
+            state(DATABASE, &latest, None).reason.as_deref(),
+            Some("postgres: connection refused")
+        );
+        assert_eq!(
+            state(CACHE, &latest, None).reason.as_deref(),
+            Some("cache: not probed yet")
+        );
+
+        // Reachable, but behind this build's migrations
+        let behind = vec![
+            result(probes::POSTGRES, true),
+            (
+                probes::MIGRATIONS,
+                Some(ProbeResult {
+                    error: Some("pending migrations: 20250222000000".to_string()),
+                    ..result(probes::MIGRATIONS, false).1.unwrap()
+                }),
+            ),
+        ];
+        assert_eq!(
+            state(DATABASE, &behind, None).reason.as_deref(),
+            Some("migrations: pending migrations: 20250222000000")
+        );
+
+        // A broken model is down whatever the tokenizer probe says
+        assert!(state(INFERENCE, &latest, None).ready);
+        let broken = state(INFERENCE, &latest, Some("corrupt model file".to_string()));
+        assert!(!broken.ready);
+        assert_eq!(broken.reason.as_deref(), Some("corrupt model file"));
+    }
+
+    #[cfg(feature = "inference")]
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_gate_rejects_data_plane_routes_while_the_model_is_down() {
+        use axum::{body::Body, http::Request, routing::get, Router};
+        use tower::ServiceExt;
+
+        crate::inference::mark_unavailable("corrupt model file".to_string());
+        let app = Router::new()
+            .route("/v1/embed", get(|| async { "ok" }))
+            .route_layer(axum::middleware::from_fn(inference_gate))
+            .merge(Router::new().route("/v1/organizations", get(|| async { "ok" })));
+
+        let response = app
+            .clone()
+            .oneshot(Request::get("/v1/embed").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 503);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error"], "model_unavailable");
+        assert!(json["message"]
+            .as_str()
+            .unwrap()
+            .contains("corrupt model file"));
+
+        let response = app
+            .oneshot(
+                Request::get("/v1/organizations")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+
+        crate::inference::mark_available();
+    }
+}
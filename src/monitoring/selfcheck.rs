@@ -0,0 +1,300 @@
+//! Startup self-check (`smally check`)
+//!
+//! Does what startup does, without binding a port: validates the
+//! configuration, connects to Postgres and compares the applied migrations
+//! with the ones embedded in this build (without running them; not with
+//! `MIGRATIONS_MODE=skip`), pings Redis, round-trips a key through the
+//! cache, loads the model and embeds one text, and signs and verifies a
+//! token with the configured keys.
+//!
+//! Dependencies are checked with the background probes and judged by
+//! `readiness::state`, so a component passes here exactly when it would
+//! report ready at runtime.
+
+use anyhow::{anyhow, Context, Result};
+#[cfg(feature = "inference")]
+use parking_lot::RwLock;
+use serde::Serialize;
+use sqlx::pool::PoolOptions;
+#[cfg(feature = "inference")]
+use std::sync::Arc;
+use std::time::Instant;
+use uuid::Uuid;
+
+use super::probes::{self, ProbeResult};
+use super::readiness;
+use crate::auth::binding::TokenBinding;
+use crate::auth::keyring::Keyring;
+use crate::auth::{sign_token_direct, verify_token_direct, TokenData};
+use crate::cache::EmbeddingCache;
+use crate::config::{self, Settings};
+use crate::database::{self, Db};
+#[cfg(feature = "inference")]
+use crate::inference::EmbeddingModel;
+use crate::models::TierType;
+use crate::redis_util::RedisConnection;
+
+pub const CONFIG: &str = "config";
+pub const TOKENS: &str = "tokens";
+
+/// Outcome of one check
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub ok: bool,
+    pub duration_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Every check, in the order they ran
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// Whether every check passed
+    pub ok: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    fn new(checks: Vec<Check>) -> Self {
+        Self {
+            ok: checks.iter().all(|check| check.ok),
+            checks,
+        }
+    }
+}
+
+/// Run every check against the configured environment
+pub async fn run() -> Report {
+    let settings = config::get_settings();
+
+    let started = Instant::now();
+    let config = check(CONFIG, started, settings.validate());
+
+    let database = check_database(settings).await;
+    let (redis, connection) = check_redis(settings).await;
+    let cache = check_cache(connection).await;
+    #[cfg(feature = "inference")]
+    let inference = check_inference().await;
+
+    let started = Instant::now();
+    let tokens = check(
+        TOKENS,
+        started,
+        Keyring::from_settings().and_then(|keys| {
+            token_round_trip(
+                &settings.token_private_key,
+                &keys,
+                &TokenBinding::from_settings(),
+            )
+        }),
+    );
+
+    Report::new(vec![
+        config,
+        database,
+        redis,
+        cache,
+        #[cfg(feature = "inference")]
+        inference,
+        tokens,
+    ])
+}
+
+fn check(name: &'static str, started: Instant, outcome: Result<()>) -> Check {
+    Check {
+        name,
+        ok: outcome.is_ok(),
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        error: outcome.err().map(|e| format!("{:#}", e)),
+    }
+}
+
+/// `component` as readiness would judge it from `results`, or down for
+/// `down`
+fn component(
+    component: &'static str,
+    started: Instant,
+    results: Vec<ProbeResult>,
+    down: Option<String>,
+) -> Check {
+    let latest: Vec<_> = results
+        .into_iter()
+        .map(|result| (result.dependency, Some(result)))
+        .collect();
+    let state = readiness::state(component, &latest, down);
+    Check {
+        name: component,
+        ok: state.ready,
+        duration_ms: started.elapsed().as_secs_f64() * 1000.0,
+        error: state.reason,
+    }
+}
+
+/// Connect without migrating, probe, then compare the migrations (unless
+/// `MIGRATIONS_MODE=skip`)
+async fn check_database(settings: &Settings) -> Check {
+    let started = Instant::now();
+    let mut pool = None;
+    let probe = probes::measure(probes::POSTGRES, async {
+        let connected = PoolOptions::<Db>::new()
+            .max_connections(1)
+            .connect_with(database::connect_options(&settings.database_url)?)
+            .await?;
+        probes::check_postgres(&connected).await?;
+        pool = Some(connected);
+        Ok(())
+    })
+    .await;
+
+    let mut results = vec![probe];
+    if let Some(pool) = &pool {
+        results.push(probes::measure(probes::MIGRATIONS, probes::check_migrations(pool)).await);
+    }
+    component(readiness::DATABASE, started, results, None)
+}
+
+async fn check_redis(settings: &Settings) -> (Check, Option<RedisConnection>) {
+    let started = Instant::now();
+    let mut connection = None;
+    let probe = probes::measure(probes::REDIS, async {
+        let conn = RedisConnection::connect(settings).await?;
+        probes::check_redis(conn.clone()).await?;
+        connection = Some(conn);
+        Ok(())
+    })
+    .await;
+
+    (
+        component(readiness::REDIS, started, vec![probe], None),
+        connection,
+    )
+}
+
+async fn check_cache(connection: Option<RedisConnection>) -> Check {
+    let started = Instant::now();
+    let Some(connection) = connection else {
+        return component(
+            readiness::CACHE,
+            started,
+            vec![],
+            Some("redis is unreachable".to_string()),
+        );
+    };
+
+    let cache = EmbeddingCache::new(connection);
+    let probe = probes::measure(probes::CACHE, probes::check_cache(&cache)).await;
+    component(readiness::CACHE, started, vec![probe], None)
+}
+
+/// Load the model, probe the tokenizer and embed one text
+#[cfg(feature = "inference")]
+async fn check_inference() -> Check {
+    let started = Instant::now();
+    let model = match tokio::task::spawn_blocking(EmbeddingModel::new).await {
+        Ok(Ok(model)) => Arc::new(RwLock::new(model)),
+        Ok(Err(e)) => {
+            let down = format!("failed to load: {:#}", e);
+            return component(readiness::INFERENCE, started, vec![], Some(down));
+        }
+        Err(e) => {
+            let down = format!("failed to load: {}", e);
+            return component(readiness::INFERENCE, started, vec![], Some(down));
+        }
+    };
+
+    let probe = probes::measure(probes::TOKENIZER, probes::check_tokenizer(model.clone())).await;
+    let warmup = tokio::task::spawn_blocking(move || {
+        model.write().encode("smally self-check", true).map(|_| ())
+    })
+    .await;
+    let down = match warmup {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(format!("warmup embedding failed: {:#}", e)),
+        Err(e) => Some(format!("warmup embedding failed: {}", e)),
+    };
+    component(readiness::INFERENCE, started, vec![probe], down)
+}
+
+/// Sign a token with `private_key_hex` and verify it the way requests are
+fn token_round_trip(private_key_hex: &str, keys: &Keyring, binding: &TokenBinding) -> Result<()> {
+    let private_key = hex::decode(private_key_hex)
+        .ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| anyhow!("TOKEN_PRIVATE_KEY isn't a 32-byte hex Ed25519 key"))?;
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&private_key);
+
+    let token_data = TokenData {
+        org_id: Uuid::now_v7(),
+        key_id: Uuid::now_v7(),
+        tier: TierType::Free,
+        max_tokens: 128,
+        monthly_quota: 1,
+        require_signing: false,
+        max_concurrency: None,
+        scopes: None,
+        models: None,
+        api_version: None,
+        strict_validation: false,
+        generation: 0,
+    };
+    let token = sign_token_direct(&token_data, &signing_key, binding)?;
+    let claims = verify_token_direct(&token, keys, binding)
+        .context("a token signed with TOKEN_PRIVATE_KEY doesn't verify with TOKEN_PUBLIC_KEYS")?;
+    if claims.key_id() != token_data.key_id {
+        return Err(anyhow!("the verified token has different claims"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_round_trip() {
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let other = ed25519_dalek::SigningKey::from_bytes(&[8; 32]);
+        let private_key_hex = hex::encode(signing_key.to_bytes());
+        let binding = TokenBinding::default();
+
+        let keys = Keyring::new(vec![other.verifying_key(), signing_key.verifying_key()]);
+        assert!(token_round_trip(&private_key_hex, &keys, &binding).is_ok());
+
+        // The signing key's public key was dropped from TOKEN_PUBLIC_KEYS
+        let keys = Keyring::new(vec![other.verifying_key()]);
+        assert!(token_round_trip(&private_key_hex, &keys, &binding).is_err());
+        assert!(token_round_trip("not hex", &keys, &binding).is_err());
+    }
+
+    #[test]
+    fn test_report_fails_on_any_failed_check() {
+        let started = Instant::now();
+        let passed = Report::new(vec![check(CONFIG, started, Ok(()))]);
+        assert!(passed.ok);
+
+        let failed = Report::new(vec![
+            check(CONFIG, started, Ok(())),
+            component(readiness::CACHE, started, vec![], Some("down".to_string())),
+        ]);
+        assert!(!failed.ok);
+        let json = serde_json::to_value(&failed).unwrap();
+        assert_eq!(json["checks"][0]["name"], "config");
+        assert!(json["checks"][0].get("error").is_none());
+        assert_eq!(json["checks"][1]["error"], "down");
+    }
+
+    /// Smoke test against the test environment: everything the suite needs
+    /// is up, so every check passes
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_self_check_passes() {
+        crate::test_utils::helpers::setup().await;
+        let report = run().await;
+        assert!(
+            report.ok,
+            "{}",
+            serde_json::to_string_pretty(&report).unwrap()
+        );
+    }
+}
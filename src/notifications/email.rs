@@ -0,0 +1,333 @@
+//! Outbound email
+//!
+//! Senders implement `EmailSender`. `EMAIL_PROVIDER` picks the delivering
+//! sender: `log` writes emails to the log, `smtp` delivers them through
+//! `SMTP_HOST`. Request handlers never send directly: they hand emails to
+//! the `outbox()`, which only queues them. The `email_delivery` job sends
+//! queued emails and retries failures with exponential backoff, so a slow
+//! or unreachable mail server never delays a request.
+
+use anyhow::{anyhow, Result};
+use axum::async_trait;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use super::templates::Email;
+use crate::config::Settings;
+use crate::jobs::{self, Job};
+use crate::{config, monitoring};
+
+/// Attempts per email before it's dropped (with the backoff below, about
+/// five minutes of retrying)
+const MAX_ATTEMPTS: u32 = 6;
+
+/// Wait before the first retry; doubled after each further failure
+const RETRY_BASE: Duration = Duration::from_secs(10);
+
+/// Emails kept in the outbox; beyond this new emails are dropped
+const MAX_QUEUED: usize = 10_000;
+
+/// How often the outbox is checked for emails to send
+const DELIVERY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Delivers a single email
+#[async_trait]
+pub trait EmailSender: Send + Sync {
+    async fn send(&self, to: &str, subject: &str, html_body: &str, text_body: &str) -> Result<()>;
+}
+
+/// Development sender: logs the email instead of delivering it
+pub struct LogSender;
+
+#[async_trait]
+impl EmailSender for LogSender {
+    async fn send(&self, to: &str, subject: &str, _html_body: &str, text_body: &str) -> Result<()> {
+        info!(
+            to = to,
+            subject = subject,
+            "Email (log only):\n{}",
+            text_body
+        );
+        Ok(())
+    }
+}
+
+/// Delivers email through an SMTP server
+pub struct SmtpSender {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpSender {
+    pub fn from_settings(settings: &Settings) -> Result<Self> {
+        let from = settings
+            .email_from
+            .parse()
+            .map_err(|e| anyhow!("Invalid EMAIL_FROM: {}", e))?;
+
+        let mut builder = if settings.smtp_tls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.smtp_host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.smtp_host)
+        }
+        .port(settings.smtp_port);
+        if !settings.smtp_username.is_empty() {
+            builder = builder.credentials(Credentials::new(
+                settings.smtp_username.clone(),
+                settings.smtp_password.clone(),
+            ));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl EmailSender for SmtpSender {
+    async fn send(&self, to: &str, subject: &str, html_body: &str, text_body: &str) -> Result<()> {
+        let to: Mailbox = to
+            .parse()
+            .map_err(|e| anyhow!("Invalid recipient {}: {}", to, e))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject)
+            .multipart(MultiPart::alternative_plain_html(
+                text_body.to_string(),
+                html_body.to_string(),
+            ))?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+/// An email waiting in the outbox
+struct Pending {
+    to: String,
+    email: Email,
+    /// Failed attempts so far
+    attempts: u32,
+    due: Instant,
+}
+
+/// Queue of emails to send; `send` only enqueues
+#[derive(Default)]
+pub struct Outbox {
+    pending: Mutex<VecDeque<Pending>>,
+}
+
+impl Outbox {
+    /// Emails waiting to be sent (including ones waiting for a retry)
+    pub fn len(&self) -> usize {
+        self.pending.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Send the emails due at `now` through `sender`, requeueing failures
+    /// for a retry. Errors if any send failed.
+    pub async fn deliver_due(&self, sender: &dyn EmailSender, now: Instant) -> Result<()> {
+        let due = {
+            let mut pending = self.pending.lock();
+            let (due, waiting): (VecDeque<Pending>, _) =
+                pending.drain(..).partition(|p| p.due <= now);
+            *pending = waiting;
+            due
+        };
+
+        let mut failed = 0;
+        for mut item in due {
+            let email = &item.email;
+            match sender
+                .send(&item.to, &email.subject, &email.html_body, &email.text_body)
+                .await
+            {
+                Ok(()) => {
+                    monitoring::EMAIL_SENDS
+                        .with_label_values(&["success"])
+                        .inc();
+                }
+                Err(e) => {
+                    monitoring::EMAIL_SENDS
+                        .with_label_values(&["failure"])
+                        .inc();
+                    failed += 1;
+                    item.attempts += 1;
+                    if item.attempts >= MAX_ATTEMPTS {
+                        error!(
+                            "Giving up on email \"{}\" to {} after {} attempts: {}",
+                            email.subject, item.to, item.attempts, e
+                        );
+                        continue;
+                    }
+                    warn!(
+                        "Failed to send email \"{}\" to {} (attempt {}): {}",
+                        email.subject, item.to, item.attempts, e
+                    );
+                    item.due = now + retry_delay(item.attempts);
+                    self.pending.lock().push_back(item);
+                }
+            }
+        }
+
+        if failed > 0 {
+            return Err(anyhow!("{} email(s) failed to send", failed));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EmailSender for Outbox {
+    async fn send(&self, to: &str, subject: &str, html_body: &str, text_body: &str) -> Result<()> {
+        let mut pending = self.pending.lock();
+        if pending.len() >= MAX_QUEUED {
+            return Err(anyhow!("Email outbox is full"));
+        }
+        pending.push_back(Pending {
+            to: to.to_string(),
+            email: Email {
+                subject: subject.to_string(),
+                html_body: html_body.to_string(),
+                text_body: text_body.to_string(),
+            },
+            attempts: 0,
+            due: Instant::now(),
+        });
+        Ok(())
+    }
+}
+
+/// Wait before the retry following the `attempts`-th failure
+fn retry_delay(attempts: u32) -> Duration {
+    RETRY_BASE * 2u32.pow(attempts.saturating_sub(1))
+}
+
+static SENDER: OnceCell<Box<dyn EmailSender>> = OnceCell::new();
+static OUTBOX: Lazy<Outbox> = Lazy::new(Outbox::default);
+
+/// Set up the sender chosen by `EMAIL_PROVIDER` and start delivering the
+/// outbox
+pub fn init() -> Result<()> {
+    let settings = config::get_settings();
+    let sender: Box<dyn EmailSender> = match settings.email_provider.as_str() {
+        "log" => Box::new(LogSender),
+        "smtp" => Box::new(SmtpSender::from_settings(settings)?),
+        other => return Err(anyhow!("Unknown EMAIL_PROVIDER: {}", other)),
+    };
+    SENDER
+        .set(sender)
+        .map_err(|_| anyhow!("Email sender already initialized"))?;
+
+    jobs::register(
+        Job::new("email_delivery", DELIVERY_INTERVAL, || async {
+            OUTBOX.deliver_due(get_sender(), Instant::now()).await
+        })
+        .timeout(Duration::from_secs(60))
+        .run_on_shutdown(),
+    );
+    info!("Email provider: {}", settings.email_provider);
+    Ok(())
+}
+
+/// The configured sender (sends immediately; log-only before `init`)
+pub fn get_sender() -> &'static dyn EmailSender {
+    SENDER.get().map_or(&LogSender, |sender| sender.as_ref())
+}
+
+/// Queue for emails sent while handling requests
+pub fn outbox() -> &'static Outbox {
+    &OUTBOX
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails the first `failures` sends, then records
+    #[derive(Default)]
+    struct FlakySender {
+        failures: Mutex<u32>,
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl EmailSender for FlakySender {
+        async fn send(&self, to: &str, _subject: &str, _html: &str, _text: &str) -> Result<()> {
+            let mut failures = self.failures.lock();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(anyhow!("connection refused"));
+            }
+            self.sent.lock().push(to.to_string());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_email_is_retried_after_backoff() {
+        let outbox = Outbox::default();
+        let sender = FlakySender {
+            failures: Mutex::new(2),
+            ..Default::default()
+        };
+        outbox
+            .send("a@example.com", "Hi", "<p>Hi</p>", "Hi")
+            .await
+            .unwrap();
+
+        let start = Instant::now();
+        assert!(outbox.deliver_due(&sender, start).await.is_err());
+        assert_eq!(outbox.len(), 1);
+
+        // Not due again until the backoff has passed
+        assert!(outbox.deliver_due(&sender, start).await.is_ok());
+        assert_eq!(outbox.len(), 1);
+
+        let retry = start + retry_delay(1);
+        assert!(outbox.deliver_due(&sender, retry).await.is_err());
+        let retry = retry + retry_delay(2);
+        assert!(outbox.deliver_due(&sender, retry).await.is_ok());
+
+        assert!(outbox.is_empty());
+        assert_eq!(*sender.sent.lock(), vec!["a@example.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_email_dropped_after_max_attempts() {
+        let outbox = Outbox::default();
+        let sender = FlakySender {
+            failures: Mutex::new(u32::MAX),
+            ..Default::default()
+        };
+        outbox
+            .send("a@example.com", "Hi", "<p>Hi</p>", "Hi")
+            .await
+            .unwrap();
+
+        let mut now = Instant::now();
+        for attempt in 1..=MAX_ATTEMPTS {
+            assert!(outbox.deliver_due(&sender, now).await.is_err());
+            now += retry_delay(attempt);
+        }
+        assert!(outbox.is_empty());
+    }
+
+    #[test]
+    fn test_retry_delay_doubles() {
+        assert_eq!(retry_delay(1), Duration::from_secs(10));
+        assert_eq!(retry_delay(2), Duration::from_secs(20));
+        assert_eq!(retry_delay(5), Duration::from_secs(160));
+    }
+}
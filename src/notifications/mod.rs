@@ -0,0 +1,4 @@
+//! Outbound notifications to people (as opposed to API responses)
+
+pub mod email;
+pub mod templates;
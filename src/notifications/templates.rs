@@ -0,0 +1,257 @@
+//! Transactional email templates
+//!
+//! Each template returns the subject plus an HTML and a plain-text body
+//! with the same content. The HTML is deliberately plain (one wrapper, no
+//! external assets) so it renders the same in every client.
+
+use chrono::NaiveDate;
+
+use crate::models::TierType;
+
+/// A rendered email, ready to send
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
+/// Usage alert: `org_name` crossed `threshold`% of its monthly quota
+pub fn usage_alert(
+    org_name: &str,
+    threshold: i32,
+    usage: i64,
+    quota: i64,
+    month: NaiveDate,
+) -> Email {
+    let month = month.format("%B %Y");
+    Email {
+        subject: format!("{} has used {}% of its monthly quota", org_name, threshold),
+        text_body: format!(
+            "{} has used {} of its {} requests for {} ({}% alert).\n\n\
+             Requests beyond the quota are rejected until the quota resets on the 1st.",
+            org_name, usage, quota, month, threshold
+        ),
+        html_body: layout(&format!(
+            "<p><strong>{}</strong> has used {} of its {} requests for {} ({}% alert).</p>\
+             <p>Requests beyond the quota are rejected until the quota resets on the 1st.</p>",
+            html_escape(org_name),
+            usage,
+            quota,
+            month,
+            threshold
+        )),
+    }
+}
+
+/// Forecast alert: `org_name` has used `usage` requests and is projected to
+/// reach `projected` of its `quota` by the end of the cycle
+pub fn usage_forecast_alert(
+    org_name: &str,
+    usage: i64,
+    projected: i64,
+    quota: i64,
+    exhaustion_date: Option<NaiveDate>,
+    month: NaiveDate,
+) -> Email {
+    let month = month.format("%B %Y");
+    let percent = if quota > 0 {
+        projected * 100 / quota
+    } else {
+        0
+    };
+    let runs_out = exhaustion_date
+        .map(|day| {
+            format!(
+                " At this rate the quota runs out on {}.",
+                day.format("%B %-d")
+            )
+        })
+        .unwrap_or_default();
+    let summary = format!(
+        "has used {} of its {} requests for {}, and is on track for about {} ({}% of the quota).{}",
+        usage, quota, month, projected, percent, runs_out
+    );
+    Email {
+        subject: format!("{} is on track to exceed its monthly quota", org_name),
+        text_body: format!(
+            "{} {}\n\n\
+             Requests beyond the quota are rejected until the quota resets. \
+             Upgrade to a paid tier to keep serving requests.",
+            org_name, summary
+        ),
+        html_body: layout(&format!(
+            "<p><strong>{}</strong> {}</p>\
+             <p>Requests beyond the quota are rejected until the quota resets. \
+             Upgrade to a paid tier to keep serving requests.</p>",
+            html_escape(org_name),
+            summary
+        )),
+    }
+}
+
+/// `org_name`'s trial of `trial_tier` ended; it's back on `tier`
+pub fn trial_ended(org_name: &str, trial_tier: TierType, tier: TierType) -> Email {
+    let (trial_tier, tier) = (trial_tier.display_name(), tier.display_name());
+    Email {
+        subject: format!("The {} trial of {} has ended", trial_tier, org_name),
+        text_body: format!(
+            "The {} trial of {} has ended, and the organization is back on the {} tier. \
+             Its API keys keep working with the {} tier's limits.\n\n\
+             Upgrade to keep the {} limits.",
+            trial_tier, org_name, tier, tier, trial_tier
+        ),
+        html_body: layout(&format!(
+            "<p>The {} trial of <strong>{}</strong> has ended, and the organization is back \
+             on the {} tier. Its API keys keep working with the {} tier's limits.</p>\
+             <p>Upgrade to keep the {} limits.</p>",
+            trial_tier,
+            html_escape(org_name),
+            tier,
+            tier,
+            trial_tier
+        )),
+    }
+}
+
+/// Invitation to join an organization
+pub fn invitation(org_name: &str, invited_by: &str, accept_url: &str) -> Email {
+    Email {
+        subject: format!("You've been invited to {} on Smally", org_name),
+        text_body: format!(
+            "{} invited you to join {} on Smally.\n\n\
+             Accept the invitation: {}\n\n\
+             If you weren't expecting this, you can ignore this email.",
+            invited_by, org_name, accept_url
+        ),
+        html_body: layout(&format!(
+            "<p>{} invited you to join <strong>{}</strong> on Smally.</p>\
+             <p>{}</p>\
+             <p>If you weren't expecting this, you can ignore this email.</p>",
+            html_escape(invited_by),
+            html_escape(org_name),
+            button("Accept invitation", accept_url)
+        )),
+    }
+}
+
+/// Password reset link, valid for `valid_minutes`
+pub fn password_reset(reset_url: &str, valid_minutes: i64) -> Email {
+    Email {
+        subject: "Reset your Smally password".to_string(),
+        text_body: format!(
+            "Someone asked to reset the password of your Smally account.\n\n\
+             Choose a new password: {}\n\n\
+             The link expires in {} minutes. If it wasn't you, ignore this email; \
+             your password stays the same.",
+            reset_url, valid_minutes
+        ),
+        html_body: layout(&format!(
+            "<p>Someone asked to reset the password of your Smally account.</p>\
+             <p>{}</p>\
+             <p>The link expires in {} minutes. If it wasn't you, ignore this email; \
+             your password stays the same.</p>",
+            button("Choose a new password", reset_url),
+            valid_minutes
+        )),
+    }
+}
+
+/// Email address verification link
+pub fn email_verification(verify_url: &str) -> Email {
+    Email {
+        subject: "Verify your email for Smally".to_string(),
+        text_body: format!(
+            "Confirm that this is your email address: {}\n\n\
+             If you didn't sign up for Smally, you can ignore this email.",
+            verify_url
+        ),
+        html_body: layout(&format!(
+            "<p>Confirm that this is your email address.</p>\
+             <p>{}</p>\
+             <p>If you didn't sign up for Smally, you can ignore this email.</p>",
+            button("Verify email", verify_url)
+        )),
+    }
+}
+
+fn layout(content: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><body style=\"font-family: sans-serif; line-height: 1.5; \
+         color: #222; max-width: 560px; margin: 0 auto; padding: 24px;\">{}\
+         <p style=\"color: #888; font-size: 12px;\">Smally</p></body></html>",
+        content
+    )
+}
+
+fn button(label: &str, url: &str) -> String {
+    format!(
+        "<a href=\"{}\" style=\"display: inline-block; padding: 8px 16px; background: #222; \
+         color: #fff; text-decoration: none; border-radius: 4px;\">{}</a>",
+        html_escape(url),
+        html_escape(label)
+    )
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_input_is_escaped_in_html_only() {
+        let email = invitation(
+            "<script>Acme</script>",
+            "Ann & Bob",
+            "https://smally.example/invite?a=1&b=2",
+        );
+        assert!(email
+            .html_body
+            .contains("&lt;script&gt;Acme&lt;/script&gt;"));
+        assert!(email.html_body.contains("Ann &amp; Bob"));
+        assert!(email
+            .html_body
+            .contains("href=\"https://smally.example/invite?a=1&amp;b=2\""));
+        assert!(!email.html_body.contains("<script>"));
+
+        assert!(email.text_body.contains("<script>Acme</script>"));
+        assert!(email
+            .text_body
+            .contains("https://smally.example/invite?a=1&b=2"));
+    }
+
+    #[test]
+    fn test_usage_alert_content() {
+        let month = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+        let email = usage_alert("Acme", 80, 16_000, 20_000, month);
+        assert_eq!(email.subject, "Acme has used 80% of its monthly quota");
+        for body in [&email.html_body, &email.text_body] {
+            assert!(body.contains("16000 of its 20000 requests for March 2025"));
+        }
+
+        let day = NaiveDate::from_ymd_opt(2025, 3, 26).unwrap();
+        let email = usage_forecast_alert("Acme", 8_000, 23_000, 20_000, Some(day), month);
+        assert_eq!(
+            email.subject,
+            "Acme is on track to exceed its monthly quota"
+        );
+        for body in [&email.html_body, &email.text_body] {
+            assert!(body.contains("on track for about 23000 (115% of the quota)"));
+            assert!(body.contains("runs out on March 26"));
+        }
+    }
+
+    #[test]
+    fn test_trial_ended_content() {
+        let email = trial_ended("Acme", TierType::Pro, TierType::Free);
+        assert_eq!(email.subject, "The Pro trial of Acme has ended");
+        assert!(email.text_body.contains("back on the Free tier"));
+        assert!(email.html_body.contains("<strong>Acme</strong>"));
+    }
+}
@@ -0,0 +1,292 @@
+//! Shared Redis connection
+//!
+//! One connection is opened at startup and shared by the cache, billing,
+//! auth and maintenance modules. With `REDIS_SENTINELS` set, the master is
+//! resolved through Sentinel (`REDIS_SENTINEL_MASTER`) and re-resolved
+//! after a failover; otherwise `REDIS_URL` is used directly.
+//!
+//! Failover handling: a command that fails because the server went away or
+//! was demoted to a replica (`READONLY`) triggers a new master lookup. A
+//! `READONLY` command was not executed, so it is retried once on the new
+//! master; after I/O errors the command may or may not have run, so the
+//! error is returned and only later commands use the new master.
+
+use anyhow::Result;
+use parking_lot::RwLock;
+use redis::aio::{ConnectionLike, ConnectionManager};
+use redis::sentinel::{Sentinel, SentinelNodeConnectionInfo};
+use redis::{Cmd, ErrorKind, IntoConnectionInfo, Pipeline, RedisError, RedisFuture, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::config::Settings;
+
+/// How the Redis master is found
+enum Topology {
+    /// A single server at REDIS_URL
+    Standalone,
+    /// Master of a Sentinel-managed group
+    Sentinel(Box<SentinelMaster>),
+}
+
+struct SentinelMaster {
+    sentinel: tokio::sync::Mutex<Sentinel>,
+    master_name: String,
+    node: SentinelNodeConnectionInfo,
+}
+
+struct Inner {
+    conn: RwLock<ConnectionManager>,
+    /// Bumped on every reconnect so concurrent failures reconnect only once
+    generation: AtomicU64,
+    topology: Topology,
+    db: i64,
+}
+
+/// Cloneable handle to the shared connection (implements `AsyncCommands`)
+#[derive(Clone)]
+pub struct RedisConnection {
+    inner: Arc<Inner>,
+}
+
+impl RedisConnection {
+    /// Connect according to the settings
+    pub async fn connect(settings: &Settings) -> Result<Self> {
+        let url_info = settings.redis_url.as_str().into_connection_info()?;
+        let db = url_info.redis.db;
+
+        let (conn, topology) = if settings.redis_sentinels.is_empty() {
+            let client = redis::Client::open(url_info)?;
+            (ConnectionManager::new(client).await?, Topology::Standalone)
+        } else {
+            // REDIS_URL still provides the database and credentials
+            let node = SentinelNodeConnectionInfo {
+                tls_mode: None,
+                redis_connection_info: Some(url_info.redis),
+            };
+            let mut sentinel = Sentinel::build(settings.redis_sentinels.clone())?;
+            let client = sentinel
+                .async_master_for(&settings.redis_sentinel_master, Some(&node))
+                .await?;
+            info!(
+                "Redis master '{}' resolved via Sentinel: {}",
+                settings.redis_sentinel_master,
+                client.get_connection_info().addr
+            );
+            (
+                ConnectionManager::new(client).await?,
+                Topology::Sentinel(Box::new(SentinelMaster {
+                    sentinel: tokio::sync::Mutex::new(sentinel),
+                    master_name: settings.redis_sentinel_master.clone(),
+                    node,
+                })),
+            )
+        };
+
+        Ok(RedisConnection {
+            inner: Arc::new(Inner {
+                conn: RwLock::new(conn),
+                generation: AtomicU64::new(0),
+                topology,
+                db,
+            }),
+        })
+    }
+
+    fn current(&self) -> (ConnectionManager, u64) {
+        let conn = self.inner.conn.read();
+        (conn.clone(), self.inner.generation.load(Ordering::Acquire))
+    }
+
+    /// Resolve the master again after `generation` failed. Returns the
+    /// connection to use next (possibly one another caller just made).
+    async fn reconnect(&self, generation: u64) -> Result<ConnectionManager, RedisError> {
+        let Topology::Sentinel(master) = &self.inner.topology else {
+            return Ok(self.current().0);
+        };
+
+        let SentinelMaster {
+            sentinel,
+            master_name,
+            node,
+        } = master.as_ref();
+        let mut sentinel = sentinel.lock().await;
+        let (conn, current) = self.current();
+        if current != generation {
+            return Ok(conn);
+        }
+
+        let client = sentinel.async_master_for(master_name, Some(node)).await?;
+        let conn = ConnectionManager::new(client.clone()).await?;
+        *self.inner.conn.write() = conn.clone();
+        self.inner.generation.fetch_add(1, Ordering::AcqRel);
+        warn!(
+            "Redis master '{}' re-resolved via Sentinel: {}",
+            master_name,
+            client.get_connection_info().addr
+        );
+        Ok(conn)
+    }
+}
+
+/// What to do after a failed command
+#[derive(Debug, PartialEq, Eq)]
+enum Recovery {
+    /// Not a failover symptom
+    None,
+    /// Re-resolve the master, then return the error
+    Reconnect,
+    /// Re-resolve the master and run the command again
+    Retry,
+}
+
+fn recovery_for(err: &RedisError) -> Recovery {
+    if err.kind() == ErrorKind::ReadOnly {
+        Recovery::Retry
+    } else if err.is_io_error() || err.is_connection_dropped() || err.is_connection_refusal() {
+        Recovery::Reconnect
+    } else {
+        Recovery::None
+    }
+}
+
+/// Injected Redis fault, as the I/O error a dead server would give
+#[cfg(feature = "fault-injection")]
+async fn injected_fault() -> Result<(), RedisError> {
+    crate::fault::apply(crate::fault::Target::Redis)
+        .await
+        .map_err(|fault| RedisError::from(std::io::Error::from(fault)))
+}
+
+impl ConnectionLike for RedisConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+        Box::pin(async move {
+            #[cfg(feature = "fault-injection")]
+            injected_fault().await?;
+            let (mut conn, generation) = self.current();
+            match conn.req_packed_command(cmd).await {
+                Err(err) if matches!(self.inner.topology, Topology::Sentinel(_)) => {
+                    match recovery_for(&err) {
+                        Recovery::None => Err(err),
+                        Recovery::Reconnect => {
+                            self.reconnect(generation).await?;
+                            Err(err)
+                        }
+                        Recovery::Retry => {
+                            self.reconnect(generation)
+                                .await?
+                                .req_packed_command(cmd)
+                                .await
+                        }
+                    }
+                }
+                result => result,
+            }
+        })
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<Value>> {
+        Box::pin(async move {
+            #[cfg(feature = "fault-injection")]
+            injected_fault().await?;
+            let (mut conn, generation) = self.current();
+            match conn.req_packed_commands(cmd, offset, count).await {
+                Err(err) if matches!(self.inner.topology, Topology::Sentinel(_)) => {
+                    match recovery_for(&err) {
+                        Recovery::None => Err(err),
+                        Recovery::Reconnect => {
+                            self.reconnect(generation).await?;
+                            Err(err)
+                        }
+                        Recovery::Retry => {
+                            self.reconnect(generation)
+                                .await?
+                                .req_packed_commands(cmd, offset, count)
+                                .await
+                        }
+                    }
+                }
+                result => result,
+            }
+        })
+    }
+
+    fn get_db(&self) -> i64 {
+        self.inner.db
+    }
+}
+
+/// Handle to the connection of the process-wide `AppState` (prefer
+/// `AppState::redis` where the state is at hand)
+pub fn get_connection() -> RedisConnection {
+    crate::state::global().redis.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use redis::AsyncCommands;
+    use std::io;
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_recovery_for_failover_symptoms() {
+        let readonly = RedisError::from((ErrorKind::ReadOnly, "READONLY"));
+        assert_eq!(recovery_for(&readonly), Recovery::Retry);
+
+        let dropped = RedisError::from(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert_eq!(recovery_for(&dropped), Recovery::Reconnect);
+
+        let refused = RedisError::from(io::Error::from(io::ErrorKind::ConnectionRefused));
+        assert_eq!(recovery_for(&refused), Recovery::Reconnect);
+
+        let wrong_type = RedisError::from((ErrorKind::TypeError, "WRONGTYPE"));
+        assert_eq!(recovery_for(&wrong_type), Recovery::None);
+    }
+
+    fn docker(args: &[&str]) {
+        let status = Command::new("docker").args(args).status().unwrap();
+        assert!(status.success(), "docker {:?} failed", args);
+    }
+
+    /// Kills and restores the Redis container (docker-compose `smally-redis`,
+    /// or REDIS_TEST_CONTAINER) and checks the shared connection recovers
+    /// without being recreated. Run with `cargo test -- --ignored redis_util`.
+    #[tokio::test]
+    #[ignore = "needs docker and a running Redis container"]
+    async fn test_reconnects_after_redis_restart() {
+        let container =
+            std::env::var("REDIS_TEST_CONTAINER").unwrap_or_else(|_| "smally-redis".to_string());
+        let mut conn = RedisConnection::connect(config::get_settings())
+            .await
+            .unwrap();
+        let _: () = conn.set("redis_util:test", 1).await.unwrap();
+
+        docker(&["kill", &container]);
+        assert!(conn.get::<_, Option<i64>>("redis_util:test").await.is_err());
+        docker(&["start", &container]);
+
+        let deadline = Instant::now() + Duration::from_secs(30);
+        loop {
+            match conn.set::<_, _, ()>("redis_util:test", 2).await {
+                Ok(()) => break,
+                Err(e) if Instant::now() < deadline => {
+                    tracing::debug!("Waiting for Redis: {}", e);
+                    tokio::time::sleep(Duration::from_millis(500)).await;
+                }
+                Err(e) => panic!("connection did not recover: {}", e),
+            }
+        }
+        let value: i64 = conn.get("redis_util:test").await.unwrap();
+        assert_eq!(value, 2);
+        let _: () = conn.del("redis_util:test").await.unwrap();
+    }
+}
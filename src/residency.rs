@@ -0,0 +1,359 @@
+//! Data residency: organizations pinned to a region
+//!
+//! Each instance serves one region (`DEPLOYMENT_REGION`, `any` when the
+//! deployment isn't regional) and knows the base URLs of the others
+//! (`REGION_BASE_URLS`). An organization's `region` is `any` (the default)
+//! or one of those regions. Embedding requests of an organization pinned to
+//! another region are refused with a 451 `wrong_region` naming the right
+//! base URL, before their text is logged or looked up in the cache.
+//!
+//! The region is read on every embed request, so it's cached in process for
+//! a short time like the cache policy. When the lookup fails the last known
+//! region is used; an organization never seen is refused.
+//!
+//! Pinning an organization to a stricter region (from `any`, or from one
+//! region to another) purges what this deployment kept of its earlier
+//! requests: their `api_request_log` rows and the cache entries of their
+//! texts, in L2 and in this instance's L1. Other instances' L1 entries and
+//! other deployments' caches expire on their own (`L2_CACHE_TTL`).
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::time::Instant;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::cache::{self, EmbeddingCache};
+use crate::config::{self, Settings};
+use crate::database::{self, ApiRequestLogRepo, DbPool};
+use crate::models::CachePolicy;
+
+/// Region of organizations served anywhere (and of non-regional instances)
+pub const ANY_REGION: &str = "any";
+
+/// Longest region name (the column is VARCHAR(32))
+const REGION_MAX_CHARS: usize = 32;
+
+/// How long a region lookup is trusted before hitting the database again
+const REGION_CACHE_TTL_SECS: u64 = 30;
+
+/// Logged requests purged per round trip
+const PURGE_BATCH: i64 = 500;
+
+/// org_id -> (region, checked_at)
+static REGION_CACHE: Lazy<DashMap<Uuid, (String, Instant)>> = Lazy::new(DashMap::new);
+
+/// Why an embedding request was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Refused {
+    /// The organization is pinned to another region
+    WrongRegion {
+        region: String,
+        /// Base URL of the region's deployment, if this one knows it
+        base_url: Option<String>,
+    },
+    /// The organization's region couldn't be read
+    Unknown,
+}
+
+/// What a purge removed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PurgeReport {
+    pub requests_deleted: u64,
+    pub cache_keys_removed: u64,
+}
+
+/// Lowercase letters, digits and dashes, at most 32 characters
+pub fn is_valid_name(region: &str) -> bool {
+    !region.is_empty()
+        && region.len() <= REGION_MAX_CHARS
+        && region
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Refuse to start with malformed region settings
+pub fn check_settings(settings: &Settings) -> Result<()> {
+    if !is_valid_name(&settings.deployment_region) {
+        anyhow::bail!(
+            "DEPLOYMENT_REGION must be lowercase letters, digits and dashes (got '{}')",
+            settings.deployment_region
+        );
+    }
+    for (region, url) in &settings.region_base_urls {
+        if region == ANY_REGION || !is_valid_name(region) {
+            anyhow::bail!("REGION_BASE_URLS has an invalid region name '{}'", region);
+        }
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            anyhow::bail!(
+                "REGION_BASE_URLS has no http(s) URL for region '{}'",
+                region
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Regions organizations can be pinned to on this deployment, sorted
+pub fn known_regions(settings: &Settings) -> Vec<String> {
+    let mut regions: Vec<String> = settings.region_base_urls.keys().cloned().collect();
+    if settings.deployment_region != ANY_REGION && !regions.contains(&settings.deployment_region) {
+        regions.push(settings.deployment_region.clone());
+        regions.sort();
+    }
+    regions
+}
+
+/// Base URL of a region's deployment (this instance's for its own region)
+pub fn base_url(settings: &Settings, region: &str) -> Option<String> {
+    if region == settings.deployment_region {
+        return Some(settings.public_base_url.clone());
+    }
+    settings.region_base_urls.get(region).cloned()
+}
+
+/// Whether an instance of `deployment_region` may serve an organization of
+/// `org_region`
+pub fn serves(deployment_region: &str, org_region: &str) -> bool {
+    org_region == ANY_REGION || org_region == deployment_region
+}
+
+/// Whether moving from `from` to `to` narrows where data may be kept
+pub fn is_stricter(from: &str, to: &str) -> bool {
+    to != ANY_REGION && to != from
+}
+
+/// Region of the organization (`None` if it can't be read and isn't known
+/// from an earlier lookup)
+pub async fn region_for(org_id: Uuid) -> Option<String> {
+    if let Some(entry) = REGION_CACHE.get(&org_id) {
+        let (region, checked_at) = &*entry;
+        if checked_at.elapsed().as_secs() < REGION_CACHE_TTL_SECS {
+            return Some(region.clone());
+        }
+    }
+
+    let region = sqlx::query_scalar::<_, String>("SELECT region FROM organizations WHERE id = $1")
+        .bind(org_id)
+        .fetch_optional(database::get_db())
+        .await;
+
+    match region {
+        Ok(region) => {
+            let region = region.unwrap_or_else(|| ANY_REGION.to_string());
+            REGION_CACHE.insert(org_id, (region.clone(), Instant::now()));
+            Some(region)
+        }
+        Err(e) => {
+            tracing::warn!("Failed to load region of {}: {}", org_id, e);
+            // Stale, but better than serving a pinned organization anywhere
+            REGION_CACHE.get(&org_id).map(|entry| entry.0.clone())
+        }
+    }
+}
+
+/// Refuse requests of organizations pinned to another region than this
+/// instance's
+pub async fn check(org_id: Uuid) -> Result<(), Refused> {
+    let settings = config::get_settings();
+    let region = region_for(org_id).await.ok_or(Refused::Unknown)?;
+    if serves(&settings.deployment_region, &region) {
+        return Ok(());
+    }
+    Err(Refused::WrongRegion {
+        base_url: base_url(settings, &region),
+        region,
+    })
+}
+
+/// Forget a cached region (call after changing it)
+pub fn invalidate(org_id: Uuid) {
+    REGION_CACHE.remove(&org_id);
+}
+
+/// Texts of a logged request (batches log their texts as a JSON array)
+fn logged_texts(endpoint: &str, input_text: String) -> Vec<String> {
+    if endpoint == "/v1/embed/batch" {
+        if let Ok(texts) = serde_json::from_str::<Vec<String>>(&input_text) {
+            return texts;
+        }
+    }
+    vec![input_text]
+}
+
+/// Delete the organization's requests logged before `before`, and the
+/// cache entries of their texts under any cache policy. Shared entries go
+/// too: other organizations' requests for the same text simply miss once.
+pub async fn purge(
+    pool: &DbPool,
+    cache: &EmbeddingCache,
+    org_id: Uuid,
+    before: DateTime<Utc>,
+) -> Result<PurgeReport> {
+    let repo = ApiRequestLogRepo::new(pool);
+    let mut report = PurgeReport::default();
+    loop {
+        let logged = repo.logged_inputs(org_id, before, PURGE_BATCH).await?;
+        if logged.is_empty() {
+            return Ok(report);
+        }
+
+        let mut keys = Vec::new();
+        for (_, endpoint, input_text) in &logged {
+            for text in logged_texts(endpoint, input_text.clone()) {
+                keys.push(cache.key(&text, org_id, CachePolicy::Shared));
+                keys.push(cache.key(&text, org_id, CachePolicy::Private));
+            }
+        }
+        keys.sort();
+        keys.dedup();
+        // Entries first: a failed purge leaves the log rows to retry from
+        report.cache_keys_removed += cache.remove(&keys).await?;
+
+        let request_ids: Vec<Uuid> = logged.iter().map(|(id, ..)| *id).collect();
+        report.requests_deleted += repo.delete(&request_ids).await?;
+    }
+}
+
+/// Purge in the background what was kept before the organization moved to
+/// a stricter region
+pub fn spawn_purge(org_id: Uuid) {
+    let before = Utc::now();
+    tokio::spawn(async move {
+        match purge(database::get_db(), cache::get_cache(), org_id, before).await {
+            Ok(report) => info!(
+                org_id = %org_id,
+                "Region change purge: deleted {} logged requests and {} cache entries",
+                report.requests_deleted,
+                report.cache_keys_removed
+            ),
+            Err(e) => error!(org_id = %org_id, "Region change purge failed: {}", e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serves_and_is_stricter() {
+        assert!(serves("eu", ANY_REGION));
+        assert!(serves("eu", "eu"));
+        assert!(!serves("eu", "us"));
+        // A non-regional instance serves only unpinned organizations
+        assert!(!serves(ANY_REGION, "eu"));
+
+        assert!(is_stricter(ANY_REGION, "eu"));
+        assert!(is_stricter("us", "eu"));
+        assert!(!is_stricter("eu", "eu"));
+        assert!(!is_stricter("eu", ANY_REGION));
+    }
+
+    #[test]
+    fn test_region_settings() {
+        let mut settings = Settings::new();
+        settings.deployment_region = "eu".to_string();
+        settings.public_base_url = "https://eu.example.com".to_string();
+        settings.region_base_urls =
+            [("us".to_string(), "https://us.example.com".to_string())].into();
+        assert!(check_settings(&settings).is_ok());
+        assert_eq!(known_regions(&settings), vec!["eu", "us"]);
+        assert_eq!(
+            base_url(&settings, "eu").as_deref(),
+            Some("https://eu.example.com")
+        );
+        assert_eq!(
+            base_url(&settings, "us").as_deref(),
+            Some("https://us.example.com")
+        );
+        assert_eq!(base_url(&settings, "ap"), None);
+
+        settings
+            .region_base_urls
+            .insert("EU West".to_string(), "https://x.example.com".to_string());
+        assert!(check_settings(&settings).is_err());
+        settings.region_base_urls.remove("EU West");
+        settings
+            .region_base_urls
+            .insert("ap".to_string(), "ap.example.com".to_string());
+        assert!(check_settings(&settings).is_err());
+    }
+
+    #[cfg(feature = "control-plane")]
+    #[cfg(not(feature = "sqlite"))]
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn test_purge_deletes_requests_logged_before_the_change() {
+        use crate::database::api_request_log::NewRequest;
+        #[cfg(feature = "control-plane")]
+        use crate::test_utils::factory;
+        use crate::test_utils::helpers::{cleanup_db, setup};
+
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let cache = cache::get_cache();
+        let repo = ApiRequestLogRepo::new(pool);
+        let owner = factory::user("residency@example.com").await;
+        let key = factory::api_key(&owner).await;
+
+        let request = |endpoint: &str, input_text: &str| NewRequest {
+            request_id: Uuid::now_v7(),
+            organization_id: key.org_id,
+            api_key_id: key.key_id,
+            product: "embeddings".to_string(),
+            endpoint: endpoint.to_string(),
+            input_text: input_text.to_string(),
+            input_metadata: None,
+            client_ip: None,
+            user_agent: None,
+        };
+        repo.insert_pending(&request("/v1/embed", "residency one"))
+            .await
+            .unwrap();
+        repo.insert_pending(&request("/v1/embed/batch", "[\"residency two\"]"))
+            .await
+            .unwrap();
+        let cached = crate::cache::CachedEmbedding {
+            embedding: vec![0.5; 4],
+            tokens: 3,
+            model: "test".to_string(),
+        };
+        let private_key = cache.key("residency two", key.org_id, CachePolicy::Private);
+        cache
+            .set("residency two", key.org_id, CachePolicy::Private, cached)
+            .await;
+
+        let report = purge(pool, cache, key.org_id, Utc::now()).await.unwrap();
+        assert_eq!(report.requests_deleted, 2);
+        assert!(repo
+            .logged_inputs(key.org_id, Utc::now(), 10)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(cache
+            .get("residency two", key.org_id, CachePolicy::Private)
+            .await
+            .is_none());
+        // Nothing left to remove a second time
+        assert_eq!(cache.remove(&[private_key]).await.unwrap(), 0);
+
+        cleanup_db().await;
+    }
+
+    #[test]
+    fn test_logged_texts() {
+        assert_eq!(
+            logged_texts("/v1/embed", "[\"a\"]".to_string()),
+            vec!["[\"a\"]"]
+        );
+        assert_eq!(
+            logged_texts("/v1/embed/batch", "[\"a\",\"b\"]".to_string()),
+            vec!["a", "b"]
+        );
+    }
+}
@@ -0,0 +1,390 @@
+//! Member activity: who on a team uses the dashboard and what they did
+//!
+//! Audit events of signed-in users in an organization (keys, settings,
+//! members, exports) and their dashboard logins are stored in
+//! `audit_events`, next to the `audit` log line. Service accounts' actions
+//! are only logged. Members may see their own activity; owners and admins
+//! may see anyone's.
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use super::{organizations, Actor, ServiceError};
+use crate::database::{self, DbPool};
+use crate::models::OrganizationRole;
+
+/// Sign-in to the dashboard, landing in the organization
+pub const DASHBOARD_LOGIN: &str = "dashboard.login";
+/// Switch to the organization in the dashboard
+pub const DASHBOARD_SWITCH: &str = "dashboard.switch";
+
+/// Events returned when `limit` is omitted
+const DEFAULT_LIMIT: i64 = 50;
+/// Most events returned at once
+const MAX_LIMIT: i64 = 500;
+
+/// A stored audit event
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct AuditEvent {
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub id: Uuid,
+    /// As in the `audit` log (`api_key.create`, `dashboard.login`, ...)
+    pub action: String,
+    /// What the action was on (a key id, a setting's new value), if any
+    pub target: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A key created by the member
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct CreatedKey {
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub id: Uuid,
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub key_id: Uuid,
+    pub name: String,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Filters and page of `member_activity`
+#[derive(Debug, Default, Deserialize)]
+pub struct ActivityQuery {
+    /// Events from this day on (UTC)
+    pub from: Option<NaiveDate>,
+    /// Events up to this day, included
+    pub to: Option<NaiveDate>,
+    /// Number of events, newest first (default 50, at most 500)
+    pub limit: Option<i64>,
+    /// Events to skip
+    pub offset: Option<i64>,
+}
+
+/// A member's activity in an organization
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberActivity {
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub user_id: Uuid,
+    pub role: OrganizationRole,
+    /// Latest dashboard login or switch to the organization
+    pub last_login_at: Option<DateTime<Utc>>,
+    /// Latest event of any kind
+    pub last_activity_at: Option<DateTime<Utc>>,
+    /// Keys the member created in the period, newest first
+    pub keys_created: Vec<CreatedKey>,
+    /// A page of the member's events in the period, newest first
+    pub events: Vec<AuditEvent>,
+    /// Events in the period
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// A member with their latest activity (None if the viewer may not see it)
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MemberSummary {
+    pub user_id: Uuid,
+    pub email: String,
+    pub name: Option<String>,
+    pub role: OrganizationRole,
+    pub last_activity_at: Option<DateTime<Utc>>,
+}
+
+/// Store an audit event of a user (service accounts' aren't stored). A
+/// failure is only logged: the action itself has happened.
+pub async fn record(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    action: &str,
+    target: Option<&str>,
+) {
+    let Actor::User(user_id) = actor.into() else {
+        return;
+    };
+    let result = sqlx::query(
+        "INSERT INTO audit_events (id, organization_id, user_id, action, target, created_at)
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(Uuid::now_v7())
+    .bind(org_id)
+    .bind(user_id)
+    .bind(action)
+    .bind(target)
+    .bind(database::timestamp(Utc::now()))
+    .execute(pool)
+    .await;
+    if let Err(e) = result {
+        tracing::warn!("Failed to store audit event {}: {}", action, e);
+    }
+}
+
+/// Record a dashboard login landing in `org_id`, if the user (still)
+/// belongs to it
+pub async fn record_login(pool: &DbPool, user_id: Uuid, org_id: Uuid) {
+    if organizations::membership(pool, org_id, user_id)
+        .await
+        .is_ok()
+    {
+        record(pool, org_id, user_id, DASHBOARD_LOGIN, None).await;
+    }
+}
+
+/// Start and end (exclusive) of a period, open-ended where None
+type Period = (Option<DateTime<Utc>>, Option<DateTime<Utc>>);
+
+/// Start and end (exclusive) of the days `from` to `to`
+fn period(from: Option<NaiveDate>, to: Option<NaiveDate>) -> Result<Period, ServiceError> {
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err(ServiceError::Invalid(
+                "from must not be after to".to_string(),
+            ));
+        }
+    }
+    let start = from.map(|day| day.and_time(chrono::NaiveTime::MIN).and_utc());
+    let end = to
+        .and_then(|day| day.checked_add_days(Days::new(1)))
+        .map(|day| day.and_time(chrono::NaiveTime::MIN).and_utc());
+    Ok((start, end))
+}
+
+/// Limit and offset of a page
+fn page(limit: Option<i64>, offset: Option<i64>) -> Result<(i64, i64), ServiceError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(ServiceError::Invalid(format!(
+            "limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+    let offset = offset.unwrap_or(0);
+    if offset < 0 {
+        return Err(ServiceError::Invalid(
+            "offset must be 0 or more".to_string(),
+        ));
+    }
+    Ok((limit, offset))
+}
+
+/// Activity of member `user_id`, for that member or an owner or admin
+pub async fn member_activity(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    user_id: Uuid,
+    query: &ActivityQuery,
+) -> Result<MemberActivity, ServiceError> {
+    let actor = actor.into();
+    let viewer = organizations::membership(pool, org_id, actor).await?;
+    if actor != Actor::User(user_id) && !viewer.can_manage() {
+        return Err(ServiceError::Forbidden(
+            "Only owners and admins can see other members' activity".to_string(),
+        ));
+    }
+    let (start, end) = period(query.from, query.to)?;
+    let (limit, offset) = page(query.limit, query.offset)?;
+
+    let role = sqlx::query_scalar::<_, OrganizationRole>(
+        "SELECT role FROM organization_members WHERE organization_id = $1 AND user_id = $2",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ServiceError::NotFound("Member not found".to_string()))?;
+
+    let events = sqlx::query_as::<_, AuditEvent>(
+        "SELECT id, action, target, created_at FROM audit_events
+         WHERE organization_id = $1 AND user_id = $2
+           AND ($3 IS NULL OR created_at >= $3) AND ($4 IS NULL OR created_at < $4)
+         ORDER BY created_at DESC, id DESC
+         LIMIT $5 OFFSET $6",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .bind(start.map(database::timestamp))
+    .bind(end.map(database::timestamp))
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
+
+    let total = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM audit_events
+         WHERE organization_id = $1 AND user_id = $2
+           AND ($3 IS NULL OR created_at >= $3) AND ($4 IS NULL OR created_at < $4)",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .bind(start.map(database::timestamp))
+    .bind(end.map(database::timestamp))
+    .fetch_one(pool)
+    .await?;
+
+    let keys_created = sqlx::query_as::<_, CreatedKey>(
+        "SELECT id, key_id, name, is_active, created_at FROM api_keys
+         WHERE organization_id = $1 AND created_by = $2
+           AND ($3 IS NULL OR created_at >= $3) AND ($4 IS NULL OR created_at < $4)
+         ORDER BY created_at DESC",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .bind(start.map(database::timestamp))
+    .bind(end.map(database::timestamp))
+    .fetch_all(pool)
+    .await?;
+
+    // Latest ever, whatever the period
+    let (last_login_at, last_activity_at) =
+        sqlx::query_as::<_, (Option<DateTime<Utc>>, Option<DateTime<Utc>>)>(
+            "SELECT MAX(CASE WHEN action IN ($3, $4) THEN created_at END), MAX(created_at)
+             FROM audit_events WHERE organization_id = $1 AND user_id = $2",
+        )
+        .bind(org_id)
+        .bind(user_id)
+        .bind(DASHBOARD_LOGIN)
+        .bind(DASHBOARD_SWITCH)
+        .fetch_one(pool)
+        .await?;
+
+    Ok(MemberActivity {
+        user_id,
+        role,
+        last_login_at,
+        last_activity_at,
+        keys_created,
+        events,
+        total,
+        limit,
+        offset,
+    })
+}
+
+/// Members of an organization with their latest activity: everyone's for
+/// owners and admins, only their own for members
+pub async fn members(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+) -> Result<Vec<MemberSummary>, ServiceError> {
+    let actor = actor.into();
+    let viewer = organizations::membership(pool, org_id, actor).await?;
+
+    let mut members = sqlx::query_as::<_, MemberSummary>(
+        "SELECT u.id AS user_id, u.email, u.name, om.role, CAST(NULL AS TIMESTAMPTZ) AS last_activity_at
+         FROM organization_members om
+         INNER JOIN users u ON u.id = om.user_id
+         WHERE om.organization_id = $1
+         ORDER BY om.created_at",
+    )
+    .bind(org_id)
+    .fetch_all(pool)
+    .await?;
+
+    let latest: HashMap<Uuid, DateTime<Utc>> = sqlx::query_as::<_, (Uuid, DateTime<Utc>)>(
+        "SELECT user_id, MAX(created_at) FROM audit_events
+         WHERE organization_id = $1 GROUP BY user_id",
+    )
+    .bind(org_id)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+    for member in &mut members {
+        if viewer.can_manage() || actor == Actor::User(member.user_id) {
+            member.last_activity_at = latest.get(&member.user_id).copied();
+        }
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::models::InviteMemberRequest;
+    use crate::test_utils::factory;
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use serial_test::serial;
+
+    #[test]
+    fn test_period_and_page() {
+        let day = |d| NaiveDate::from_ymd_opt(2025, 3, d).unwrap();
+        let (start, end) = period(Some(day(1)), Some(day(31))).unwrap();
+        assert_eq!(start, Some(day(1).and_hms_opt(0, 0, 0).unwrap().and_utc()));
+        // The last day is included
+        assert_eq!(
+            end,
+            Some(
+                NaiveDate::from_ymd_opt(2025, 4, 1)
+                    .unwrap()
+                    .and_hms_opt(0, 0, 0)
+                    .unwrap()
+                    .and_utc()
+            )
+        );
+        assert!(period(Some(day(2)), Some(day(1))).is_err());
+        assert_eq!(period(None, None).unwrap(), (None, None));
+
+        assert_eq!(page(None, None).unwrap(), (DEFAULT_LIMIT, 0));
+        assert!(page(Some(0), None).is_err());
+        assert!(page(Some(MAX_LIMIT + 1), None).is_err());
+        assert!(page(Some(10), Some(-1)).is_err());
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_members_see_only_their_own_activity() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("activity-owner@example.com").await;
+        let member = factory::user("activity-member@example.com").await;
+        organizations::invite(
+            pool,
+            owner.org_id,
+            owner.id,
+            &InviteMemberRequest {
+                email: "activity-member@example.com".to_string(),
+                role: OrganizationRole::Member,
+            },
+        )
+        .await
+        .unwrap();
+        let key = factory::api_key(&owner).await;
+        record_login(pool, member.id, owner.org_id).await;
+
+        let query = ActivityQuery::default();
+        let own = member_activity(pool, owner.org_id, owner.id, owner.id, &query)
+            .await
+            .unwrap();
+        assert_eq!(own.keys_created.len(), 1);
+        assert_eq!(own.keys_created[0].key_id, key.key_id);
+        assert!(own.events.iter().any(|e| e.action == "api_key.create"));
+
+        // The owner sees the member's login; the member can't see the owner
+        let theirs = member_activity(pool, owner.org_id, owner.id, member.id, &query)
+            .await
+            .unwrap();
+        assert!(theirs.last_login_at.is_some());
+        assert!(matches!(
+            member_activity(pool, owner.org_id, member.id, owner.id, &query).await,
+            Err(ServiceError::Forbidden(_))
+        ));
+        assert!(
+            member_activity(pool, owner.org_id, member.id, member.id, &query)
+                .await
+                .is_ok()
+        );
+
+        let listed = members(pool, owner.org_id, member.id).await.unwrap();
+        let owner_row = listed.iter().find(|m| m.user_id == owner.id).unwrap();
+        assert_eq!(owner_row.last_activity_at, None);
+        let member_row = listed.iter().find(|m| m.user_id == member.id).unwrap();
+        assert!(member_row.last_activity_at.is_some());
+        cleanup_db().await;
+    }
+}
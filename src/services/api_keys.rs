@@ -0,0 +1,565 @@
+//! API key issuance
+
+use chrono::{Duration, Utc};
+use redis::AsyncCommands;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{activity, limits, organizations, validation_error, Actor, ServiceError};
+use crate::api::versions::ApiVersion;
+use crate::auth::binding::TokenBinding;
+use crate::auth::keyring;
+use crate::auth::{opaque, sign_token_direct, signing, TokenClaims, TokenData, SCOPES};
+use crate::database::{self, ApiRequestLogRepo, DbPool};
+use crate::inference::catalog;
+use crate::models::{APIKey, CreateAPIKeyRequest};
+use crate::redis_util;
+use crate::{billing, config};
+
+/// A newly created key with its one-time secrets
+#[derive(Debug, Clone)]
+pub struct IssuedKey {
+    pub api_key: APIKey,
+    /// Prefixed token (CWT or opaque), only available at creation
+    pub token: String,
+    /// HMAC secret for keys that require signed requests
+    pub signing_secret: Option<String>,
+}
+
+/// Create an API key in an organization on behalf of one of its owners or admins
+pub async fn create(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    req: &CreateAPIKeyRequest,
+) -> Result<IssuedKey, ServiceError> {
+    req.validate().map_err(|e| validation_error(&e))?;
+
+    let actor = actor.into();
+    let member = organizations::require_manager(pool, org_id, actor, "create API keys").await?;
+    limits::check_api_key_creation(pool, org_id).await?;
+
+    // Get organization tier (use provided tier or organization's tier)
+    let tier = req.tier.unwrap_or(member.tier);
+
+    // The token carries the base tier even during a trial (requests are
+    // upgraded while it runs, see billing::trial), but the key's ceilings
+    // are the trial tier's
+    let limits = billing::trial::effective_tier(tier, member.trial(), Utc::now()).limits();
+    let max_tokens = match req.max_tokens {
+        Some(requested) if requested < 2 || requested as usize > limits.max_tokens => {
+            return Err(ServiceError::Invalid(format!(
+                "max_tokens must be between 2 and {} for this tier",
+                limits.max_tokens
+            )));
+        }
+        Some(requested) => requested,
+        None => limits.max_tokens as i32,
+    };
+
+    // Same for the concurrency cap (a tier cap of 0 means unlimited)
+    let max_concurrency = match req.max_concurrency {
+        Some(0) => {
+            return Err(ServiceError::Invalid(
+                "max_concurrency must be at least 1".to_string(),
+            ));
+        }
+        Some(requested) if limits.max_concurrency > 0 && requested > limits.max_concurrency => {
+            return Err(ServiceError::Invalid(format!(
+                "max_concurrency must be between 1 and {} for this tier",
+                limits.max_concurrency
+            )));
+        }
+        requested => requested,
+    };
+
+    let scopes = match req.scopes.clone() {
+        Some(scopes) if scopes.is_empty() => {
+            return Err(ServiceError::Invalid(
+                "scopes must not be empty".to_string(),
+            ));
+        }
+        Some(mut scopes) => {
+            if let Some(unknown) = scopes.iter().find(|s| !SCOPES.contains(&s.as_str())) {
+                return Err(ServiceError::Invalid(format!(
+                    "Unknown scope '{}' (expected one of: {})",
+                    unknown,
+                    SCOPES.join(", ")
+                )));
+            }
+            scopes.sort();
+            scopes.dedup();
+            Some(scopes)
+        }
+        None => None,
+    };
+
+    // Within what the organization allows
+    let models = match req.models.clone() {
+        Some(models) if models.is_empty() => {
+            return Err(ServiceError::Invalid(
+                "models must not be empty (omit it to allow the organization's models)".to_string(),
+            ));
+        }
+        Some(models) => {
+            let models = organizations::known_models(models, &catalog::available_ids())?;
+            if let Some(allowed) = &member.allowed_models {
+                if let Some(disallowed) = models.iter().find(|m| !allowed.contains(m)) {
+                    return Err(ServiceError::Invalid(format!(
+                        "Model '{}' is not allowed in this organization (allowed: {})",
+                        disallowed,
+                        allowed.join(", ")
+                    )));
+                }
+            }
+            Some(models)
+        }
+        None => None,
+    };
+
+    // Pinned for requests that don't send X-Smally-Version
+    let api_version = match &req.api_version {
+        Some(requested) => ApiVersion::parse(requested).ok_or_else(|| {
+            let supported: Vec<&str> = ApiVersion::ALL.iter().map(|v| v.as_str()).collect();
+            ServiceError::Invalid(format!(
+                "Unknown API version '{}' (supported: {})",
+                requested,
+                supported.join(", ")
+            ))
+        })?,
+        None => ApiVersion::LATEST,
+    };
+
+    let settings = config::get_settings();
+    if req.opaque && !settings.opaque_api_keys {
+        return Err(ServiceError::Invalid(
+            "Opaque API keys are not enabled on this server".to_string(),
+        ));
+    }
+
+    // Generate key_id (UUIDv7)
+    let key_id = Uuid::now_v7();
+
+    // Create token data
+    let token_data = TokenData {
+        org_id,
+        key_id,
+        tier,
+        max_tokens,
+        monthly_quota: tier.limits().monthly_quota,
+        require_signing: req.require_request_signing,
+        max_concurrency,
+        scopes,
+        models,
+        api_version: Some(api_version.as_str().to_string()),
+        strict_validation: req.strict_validation,
+        generation: 0,
+    };
+
+    // Opaque keys store their hash and token data; CWT keys carry the data
+    // and are signed up front so the record can name the signing key. CWT
+    // keys store their data too, so they can be reissued with the same
+    // restrictions (see `key_reissue`)
+    let encoded_token_data = encode_token_data(&token_data)?;
+    let opaque_key = req.opaque.then(opaque::generate_opaque_key);
+    let (token, signing_kid) = match opaque_key {
+        Some(key) => (key, None),
+        None => {
+            let (token, kid) = sign_cwt(&token_data)?;
+            (token, Some(kid))
+        }
+    };
+
+    // Create API key record in database
+    let api_key = sqlx::query_as::<_, APIKey>(
+        "INSERT INTO api_keys (organization_id, key_id, name, is_active, created_at, last_used_at, require_request_signing, key_hash, token_data, signing_kid, api_version, strict_validation, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+         RETURNING *",
+    )
+    .bind(org_id)
+    .bind(key_id)
+    .bind(&req.name)
+    .bind(true)
+    .bind(database::timestamp(Utc::now()))
+    .bind(None::<chrono::DateTime<chrono::Utc>>)
+    .bind(req.require_request_signing)
+    .bind(req.opaque.then(|| opaque::hash_opaque_key(&token)))
+    .bind(encoded_token_data)
+    .bind(signing_kid)
+    .bind(api_version.as_str())
+    .bind(req.strict_validation)
+    .bind(match actor {
+        Actor::User(user_id) => Some(user_id),
+        Actor::ServiceAccount { .. } => None,
+    })
+    .fetch_one(pool)
+    .await
+    .map_err(|e| ServiceError::Internal(format!("Failed to create API key: {}", e)))?;
+
+    tracing::info!(
+        target: "audit",
+        action = "api_key.create",
+        org_id = %org_id,
+        key_id = %key_id,
+        actor = %actor,
+        "API key created"
+    );
+    activity::record(
+        pool,
+        org_id,
+        actor,
+        "api_key.create",
+        Some(&key_id.to_string()),
+    )
+    .await;
+
+    // Add prefix to token
+    let prefixed_token = format!("{}{}", settings.api_key_prefix, token);
+
+    // Signing secret is shown once, like the token itself
+    let signing_secret = api_key
+        .require_request_signing
+        .then(|| signing::derive_signing_secret(&settings.secret_key, key_id));
+
+    Ok(IssuedKey {
+        api_key,
+        token: prefixed_token,
+        signing_secret,
+    })
+}
+
+/// Token data as stored in `api_keys.token_data`
+pub(crate) fn encode_token_data(token_data: &TokenData) -> Result<Vec<u8>, ServiceError> {
+    TokenClaims::from_token_data(token_data.clone())
+        .to_cbor_bytes()
+        .map_err(|e| ServiceError::Internal(format!("Failed to encode token data: {}", e)))
+}
+
+/// Sign token data as a CWT with `TOKEN_PRIVATE_KEY`, returning the token
+/// (without prefix) and the kid it was signed with
+pub(crate) fn sign_cwt(token_data: &TokenData) -> Result<(String, String), ServiceError> {
+    let private_key_bytes = hex::decode(&config::get_settings().token_private_key)
+        .map_err(|e| ServiceError::Internal(format!("Invalid private key: {}", e)))?;
+
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(
+        &private_key_bytes[..]
+            .try_into()
+            .map_err(|_| ServiceError::Internal("Invalid private key length".to_string()))?,
+    );
+
+    let token = sign_token_direct(token_data, &signing_key, &TokenBinding::from_settings())
+        .map_err(|e| ServiceError::Internal(format!("Failed to sign token: {}", e)))?;
+    Ok((token, keyring::kid_hex(&signing_key.verifying_key())))
+}
+
+/// An active API key and the id of the Ed25519 key that signed it
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SignedKey {
+    pub id: Uuid,
+    pub key_id: Uuid,
+    pub organization_id: Uuid,
+    /// None for opaque keys and CWTs issued before kids were recorded
+    pub signing_kid: Option<String>,
+}
+
+/// Active keys with their signing key id, optionally only those signed by
+/// `kid`, oldest first
+pub async fn active_by_signing_kid(
+    pool: &DbPool,
+    kid: Option<&str>,
+) -> Result<Vec<SignedKey>, ServiceError> {
+    let keys = sqlx::query_as::<_, SignedKey>(
+        "SELECT id, key_id, organization_id, signing_kid
+         FROM api_keys
+         WHERE is_active = true AND key_hash IS NULL AND (CAST($1 AS TEXT) IS NULL OR signing_kid = $1)
+         ORDER BY created_at ASC",
+    )
+    .bind(kid)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(keys)
+}
+
+/// Keys of an organization the actor belongs to, newest first
+pub async fn list(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+) -> Result<Vec<APIKey>, ServiceError> {
+    organizations::membership(pool, org_id, actor).await?;
+
+    let api_keys = sqlx::query_as::<_, APIKey>(
+        "SELECT * FROM api_keys WHERE organization_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(org_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(api_keys)
+}
+
+/// Recent traffic of one key
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct KeyUsage {
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub key_id: Uuid,
+    /// Requests in the last 24 hours
+    pub requests_24h: i64,
+    /// Distinct client IPs in the last 24 hours; a sudden jump is the
+    /// clearest sign of a leaked key (IPs of organizations that opted out
+    /// of IP storage aren't counted)
+    pub distinct_ips_24h: i64,
+}
+
+/// Requests and distinct client IPs of a key (`id` as in `revoke`) over
+/// the last 24 hours
+pub async fn usage(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    id: Uuid,
+) -> Result<KeyUsage, ServiceError> {
+    organizations::membership(pool, org_id, actor).await?;
+
+    let key_id = sqlx::query_scalar::<_, Uuid>(
+        "SELECT key_id FROM api_keys WHERE id = $1 AND organization_id = $2",
+    )
+    .bind(id)
+    .bind(org_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ServiceError::NotFound("API key not found".to_string()))?;
+
+    let since = Utc::now() - Duration::hours(24);
+    let (requests_24h, distinct_ips_24h) = ApiRequestLogRepo::new(pool)
+        .key_origins_since(key_id, since)
+        .await?;
+
+    Ok(KeyUsage {
+        key_id,
+        requests_24h,
+        distinct_ips_24h,
+    })
+}
+
+/// Put a key on the revocation list, then deactivate it
+///
+/// Redis is written first: validation reads it, so once the row says the
+/// key is inactive no instance accepts it past its cached status. A failed
+/// Redis write fails the revocation, leaving the key active, so it can be
+/// retried. This process's validator forgets the key at once; others within
+/// their fresh TTL.
+pub async fn revoke(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    id: Uuid,
+) -> Result<(), ServiceError> {
+    let actor = actor.into();
+    organizations::require_manager(pool, org_id, actor, "revoke API keys").await?;
+
+    let (key_id, key_hash) = sqlx::query_as::<_, (Uuid, Option<String>)>(
+        "SELECT key_id, key_hash FROM api_keys WHERE id = $1 AND organization_id = $2",
+    )
+    .bind(id)
+    .bind(org_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ServiceError::Invalid("API key not found".to_string()))?;
+
+    // Expires in 1 year - same as token expiration
+    let mut conn = redis_util::get_connection();
+    let _: () = conn
+        .set_ex(crate::auth::revoked_key(key_id), 1, 365 * 24 * 60 * 60)
+        .await
+        .map_err(|e| ServiceError::Internal(format!("Redis error: {}", e)))?;
+    if let Some(state) = crate::state::try_global() {
+        state.validator.mark_revoked(key_id);
+    }
+
+    sqlx::query("UPDATE api_keys SET is_active = false WHERE id = $1")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    if let Some(key_hash) = key_hash {
+        opaque::API_KEY_CACHE.invalidate(&key_hash);
+    }
+    super::key_reissue::discard_pending(pool, id).await?;
+
+    tracing::warn!(
+        target: "audit",
+        action = "api_key.revoke",
+        org_id = %org_id,
+        key_id = %key_id,
+        actor = %actor,
+        "API key revoked"
+    );
+    activity::record(
+        pool,
+        org_id,
+        actor,
+        "api_key.revoke",
+        Some(&key_id.to_string()),
+    )
+    .await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::models::{InviteMemberRequest, OrganizationRole};
+    use crate::test_utils::factory;
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_max_tokens_capped_by_tier() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let settings = config::get_settings();
+        let owner = factory::user("test@example.com").await;
+
+        // Lower than the tier ceiling: allowed and signed into the token
+        let issued = create(
+            pool,
+            owner.org_id,
+            owner.id,
+            &CreateAPIKeyRequest {
+                max_tokens: Some(32),
+                ..factory::key_request("Key")
+            },
+        )
+        .await
+        .unwrap();
+        let claims = crate::auth::get_validator()
+            .validate(&issued.token[settings.api_key_prefix.len()..])
+            .await
+            .unwrap();
+        assert_eq!(claims.max_tokens(), 32);
+
+        // Above the tier ceiling: rejected
+        let result = create(
+            pool,
+            owner.org_id,
+            owner.id,
+            &CreateAPIKeyRequest {
+                max_tokens: Some(settings.max_tokens as i32 + 1),
+                ..factory::key_request("Key")
+            },
+        )
+        .await;
+        assert!(matches!(result, Err(ServiceError::Invalid(_))));
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_api_version_is_pinned() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let settings = config::get_settings();
+        let owner = factory::user("test@example.com").await;
+
+        // Latest by default, stored and carried in the token
+        let issued = create(pool, owner.org_id, owner.id, &factory::key_request("Key"))
+            .await
+            .unwrap();
+        assert_eq!(
+            issued.api_key.api_version.as_deref(),
+            Some(ApiVersion::LATEST.as_str())
+        );
+        let claims = crate::auth::get_validator()
+            .validate(&issued.token[settings.api_key_prefix.len()..])
+            .await
+            .unwrap();
+        assert_eq!(claims.api_version(), Some(ApiVersion::LATEST.as_str()));
+
+        let issued = create(
+            pool,
+            owner.org_id,
+            owner.id,
+            &CreateAPIKeyRequest {
+                api_version: Some("2025-01-01".to_string()),
+                ..factory::key_request("Legacy")
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(issued.api_key.api_version.as_deref(), Some("2025-01-01"));
+
+        let result = create(
+            pool,
+            owner.org_id,
+            owner.id,
+            &CreateAPIKeyRequest {
+                api_version: Some("2019-01-01".to_string()),
+                ..factory::key_request("Key")
+            },
+        )
+        .await;
+        assert!(matches!(result, Err(ServiceError::Invalid(_))));
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_only_managers_create_and_revoke() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("owner@example.com").await;
+        let member = factory::user("member@example.com").await;
+        let outsider = factory::user("outsider@example.com").await;
+        organizations::invite(
+            pool,
+            owner.org_id,
+            owner.id,
+            &InviteMemberRequest {
+                email: member.email.clone(),
+                role: OrganizationRole::Member,
+            },
+        )
+        .await
+        .unwrap();
+
+        let req = factory::key_request("Key");
+        assert!(matches!(
+            create(pool, owner.org_id, member.id, &req).await,
+            Err(ServiceError::Forbidden(_))
+        ));
+        assert!(matches!(
+            create(pool, owner.org_id, outsider.id, &req).await,
+            Err(ServiceError::Forbidden(_))
+        ));
+
+        // Members can see keys but not revoke them
+        let key = factory::api_key(&owner).await;
+        assert_eq!(list(pool, owner.org_id, member.id).await.unwrap().len(), 1);
+        assert!(list(pool, owner.org_id, outsider.id).await.is_err());
+        assert!(matches!(
+            revoke(pool, owner.org_id, member.id, key.id).await,
+            Err(ServiceError::Forbidden(_))
+        ));
+
+        revoke(pool, owner.org_id, owner.id, key.id).await.unwrap();
+        assert!(!list(pool, owner.org_id, owner.id).await.unwrap()[0].is_active);
+
+        // Keys of other organizations are not found
+        let other = factory::api_key(&outsider).await;
+        assert!(matches!(
+            revoke(pool, owner.org_id, owner.id, other.id).await,
+            Err(ServiceError::Invalid(_))
+        ));
+
+        cleanup_db().await;
+    }
+}
@@ -0,0 +1,694 @@
+//! Reissuing API keys with current claims
+//!
+//! Claims added since a key was created (scopes, issuer/audience, per-key
+//! limits...) only reach it through a new token, and customers can't be
+//! made to rotate overnight. So an admin reissues keys
+//! (`POST /v1/admin/keys/reissue`): each selected key gets a token of its
+//! next generation (`g` claim) under the same key_id, signed with the
+//! current key and binding. The token waits, sealed (see `auth::sealed`),
+//! for an owner or admin to retrieve it from the dashboard or the API
+//! within `RETRIEVAL_DAYS`.
+//!
+//! Both tokens validate until the older generations are explicitly revoked
+//! (`POST /v1/admin/keys/reissue/revoke-superseded`), which only happens
+//! for retrieved tokens. Each reissue moves through `pending` ->
+//! `retrieved` -> `completed`, or to `expired` when nobody retrieved it in
+//! time, so progress can be reported per status.
+//!
+//! CWT keys store their token data since reissuing exists, so they keep
+//! their restrictions. Keys created before get the tier defaults for what
+//! was only signed into their token (scopes, models, max_tokens,
+//! max_concurrency). Opaque keys aren't reissued: their claims are stored
+//! server-side.
+
+use chrono::{DateTime, Duration, Utc};
+use redis::AsyncCommands;
+use serde::Serialize;
+use tracing::info;
+use uuid::Uuid;
+
+use super::api_keys::{encode_token_data, sign_cwt};
+use super::{activity, organizations, Actor, ServiceError};
+use crate::auth::{self, sealed, TokenData};
+use crate::config;
+use crate::database::{self, DbPool};
+use crate::jobs;
+use crate::models::TierType;
+use crate::redis_util;
+
+/// How long a reissued token can be retrieved
+pub const RETRIEVAL_DAYS: i64 = 14;
+
+/// How often unretrieved tokens past their deadline are discarded
+const EXPIRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Lifetime of the Redis marker revoking older generations (as for
+/// revoked keys)
+const REVOKED_BEFORE_TTL_SECS: u64 = 365 * 24 * 60 * 60;
+
+/// Where a reissued token is in the migration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type, Serialize)]
+#[sqlx(type_name = "VARCHAR", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ReissueStatus {
+    /// Waiting to be retrieved
+    Pending,
+    /// Retrieved; older generations still validate
+    Retrieved,
+    /// Not retrieved in time, the token was discarded
+    Expired,
+    /// Older generations revoked
+    Completed,
+}
+
+impl ReissueStatus {
+    pub const ALL: [ReissueStatus; 4] = [
+        ReissueStatus::Pending,
+        ReissueStatus::Retrieved,
+        ReissueStatus::Expired,
+        ReissueStatus::Completed,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReissueStatus::Pending => "pending",
+            ReissueStatus::Retrieved => "retrieved",
+            ReissueStatus::Expired => "expired",
+            ReissueStatus::Completed => "completed",
+        }
+    }
+}
+
+/// Which keys to reissue; unset fields don't filter
+#[derive(Debug, Clone, Default)]
+pub struct ReissueFilter {
+    pub org_id: Option<Uuid>,
+    /// Keys signed with this kid
+    pub signing_kid: Option<String>,
+    pub created_before: Option<DateTime<Utc>>,
+    /// Keys by `api_keys.id`
+    pub ids: Option<Vec<Uuid>>,
+}
+
+/// An active CWT key that can be reissued
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Candidate {
+    pub id: Uuid,
+    pub organization_id: Uuid,
+    pub key_id: Uuid,
+    pub name: String,
+    pub signing_kid: Option<String>,
+    pub token_generation: i32,
+    pub require_request_signing: bool,
+    pub api_version: Option<String>,
+    pub strict_validation: bool,
+    pub token_data: Option<Vec<u8>>,
+    /// The organization's base tier
+    pub tier: TierType,
+}
+
+/// A reissued token, without the token
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Reissue {
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub id: Uuid,
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub api_key_id: Uuid,
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub key_id: Uuid,
+    pub name: String,
+    #[serde(with = "crate::uuid_dashless::simple")]
+    pub organization_id: Uuid,
+    pub generation: i32,
+    pub signing_kid: String,
+    pub status: ReissueStatus,
+    pub retrieve_before: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub retrieved_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+const REISSUE_COLUMNS: &str =
+    "r.id, r.api_key_id, k.key_id, k.name, r.organization_id, r.generation,
+     r.signing_kid, r.status, r.retrieve_before, r.created_at, r.retrieved_at, r.completed_at";
+
+/// Claims of the next generation of a key: what it was issued with (as
+/// stored, or the tier defaults for keys issued before token data was
+/// stored), the organization's current tier and the key's settings
+pub fn current_claims(key: &Candidate) -> Result<TokenData, ServiceError> {
+    let limits = key.tier.limits();
+    let stored = match &key.token_data {
+        Some(bytes) => Some(
+            ciborium::from_reader::<TokenData, _>(&bytes[..]).map_err(|e| {
+                ServiceError::Internal(format!("Invalid token data of key {}: {}", key.key_id, e))
+            })?,
+        ),
+        None => None,
+    };
+    let max_tokens = stored.as_ref().map_or(limits.max_tokens as i32, |data| {
+        data.max_tokens.min(limits.max_tokens as i32)
+    });
+
+    Ok(TokenData {
+        org_id: key.organization_id,
+        key_id: key.key_id,
+        tier: key.tier,
+        max_tokens,
+        monthly_quota: limits.monthly_quota,
+        require_signing: key.require_request_signing,
+        max_concurrency: stored.as_ref().and_then(|data| data.max_concurrency),
+        scopes: stored.as_ref().and_then(|data| data.scopes.clone()),
+        models: stored.as_ref().and_then(|data| data.models.clone()),
+        api_version: key.api_version.clone(),
+        strict_validation: key.strict_validation,
+        generation: key.token_generation as u32 + 1,
+    })
+}
+
+/// Active CWT keys matching `filter` without a reissue waiting to be
+/// retrieved, oldest first
+pub async fn candidates(
+    pool: &DbPool,
+    filter: &ReissueFilter,
+) -> Result<Vec<Candidate>, ServiceError> {
+    let keys = sqlx::query_as::<_, Candidate>(
+        "SELECT k.id, k.organization_id, k.key_id, k.name, k.signing_kid, k.token_generation,
+                k.require_request_signing, k.api_version, k.strict_validation, k.token_data, o.tier
+         FROM api_keys k
+         JOIN organizations o ON o.id = k.organization_id
+         WHERE k.is_active = true AND k.key_hash IS NULL
+           AND ($1 IS NULL OR k.organization_id = $1)
+           AND (CAST($2 AS TEXT) IS NULL OR k.signing_kid = $2)
+           AND ($3 IS NULL OR k.created_at < $3)
+           AND NOT EXISTS (
+               SELECT 1 FROM api_key_reissues r
+               WHERE r.api_key_id = k.id AND r.status = 'pending'
+           )
+         ORDER BY k.created_at ASC",
+    )
+    .bind(filter.org_id)
+    .bind(filter.signing_kid.as_deref())
+    .bind(filter.created_before.map(database::timestamp))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(match &filter.ids {
+        Some(ids) => keys.into_iter().filter(|k| ids.contains(&k.id)).collect(),
+        None => keys,
+    })
+}
+
+/// Reissue the keys matching `filter`: sign their next generation and store
+/// it sealed until `now` + `RETRIEVAL_DAYS`
+pub async fn reissue(
+    pool: &DbPool,
+    filter: &ReissueFilter,
+    now: DateTime<Utc>,
+) -> Result<Vec<Reissue>, ServiceError> {
+    let prefix = &config::get_settings().api_key_prefix;
+    let retrieve_before = now + Duration::days(RETRIEVAL_DAYS);
+
+    let mut reissued = Vec::new();
+    for key in candidates(pool, filter).await? {
+        let claims = current_claims(&key)?;
+        let (token, signing_kid) = sign_cwt(&claims)?;
+        let reissue_id = Uuid::now_v7();
+        let sealed_token = sealed::seal(&format!("{}{}", prefix, token), reissue_id.as_bytes())
+            .map_err(|e| ServiceError::Internal(format!("Failed to seal token: {}", e)))?;
+
+        let mut tx = pool.begin().await?;
+        // Losing the race means another reissue of this key got there first
+        let bumped = sqlx::query(
+            "UPDATE api_keys SET token_generation = $1, token_data = $2
+             WHERE id = $3 AND token_generation = $4",
+        )
+        .bind(claims.generation as i32)
+        .bind(encode_token_data(&claims)?)
+        .bind(key.id)
+        .bind(key.token_generation)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            == 1;
+        if !bumped {
+            continue;
+        }
+        sqlx::query(
+            "INSERT INTO api_key_reissues (id, api_key_id, organization_id, generation, signing_kid, status, sealed_token, retrieve_before, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(reissue_id)
+        .bind(key.id)
+        .bind(key.organization_id)
+        .bind(claims.generation as i32)
+        .bind(&signing_kid)
+        .bind(ReissueStatus::Pending)
+        .bind(sealed_token)
+        .bind(database::timestamp(retrieve_before))
+        .bind(database::timestamp(now))
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        tracing::info!(
+            target: "audit",
+            action = "api_key.reissue",
+            org_id = %key.organization_id,
+            key_id = %key.key_id,
+            generation = claims.generation,
+            signing_kid = %signing_kid,
+            "API key reissued"
+        );
+
+        reissued.push(Reissue {
+            id: reissue_id,
+            api_key_id: key.id,
+            key_id: key.key_id,
+            name: key.name,
+            organization_id: key.organization_id,
+            generation: claims.generation as i32,
+            signing_kid,
+            status: ReissueStatus::Pending,
+            retrieve_before,
+            created_at: now,
+            retrieved_at: None,
+            completed_at: None,
+        });
+    }
+
+    Ok(reissued)
+}
+
+/// Reissues, optionally of one organization, newest first
+pub async fn list(pool: &DbPool, org_id: Option<Uuid>) -> Result<Vec<Reissue>, ServiceError> {
+    let reissues = sqlx::query_as::<_, Reissue>(&format!(
+        "SELECT {} FROM api_key_reissues r
+         JOIN api_keys k ON k.id = r.api_key_id
+         WHERE ($1 IS NULL OR r.organization_id = $1)
+         ORDER BY r.created_at DESC",
+        REISSUE_COLUMNS
+    ))
+    .bind(org_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(reissues)
+}
+
+/// Reissued tokens of an organization waiting to be retrieved by one of its
+/// owners or admins
+pub async fn pending(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    now: DateTime<Utc>,
+) -> Result<Vec<Reissue>, ServiceError> {
+    organizations::require_manager(pool, org_id, actor, "retrieve reissued API keys").await?;
+
+    let reissues = sqlx::query_as::<_, Reissue>(&format!(
+        "SELECT {} FROM api_key_reissues r
+         JOIN api_keys k ON k.id = r.api_key_id
+         WHERE r.organization_id = $1 AND r.status = 'pending' AND r.retrieve_before > $2
+         ORDER BY k.created_at ASC",
+        REISSUE_COLUMNS
+    ))
+    .bind(org_id)
+    .bind(database::timestamp(now))
+    .fetch_all(pool)
+    .await?;
+
+    Ok(reissues)
+}
+
+/// The reissued token, once: retrieving it discards the stored copy
+pub async fn retrieve(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    reissue_id: Uuid,
+    now: DateTime<Utc>,
+) -> Result<(Reissue, String), ServiceError> {
+    let actor = actor.into();
+    organizations::require_manager(pool, org_id, actor, "retrieve reissued API keys").await?;
+
+    let not_found = || {
+        ServiceError::NotFound(
+            "Reissued API key not found, already retrieved or expired".to_string(),
+        )
+    };
+    let (reissue, sealed_token) = sqlx::query_as::<_, (Uuid, Option<String>)>(
+        "SELECT id, sealed_token FROM api_key_reissues
+         WHERE id = $1 AND organization_id = $2 AND status = 'pending' AND retrieve_before > $3",
+    )
+    .bind(reissue_id)
+    .bind(org_id)
+    .bind(database::timestamp(now))
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(not_found)?;
+    let sealed_token = sealed_token.ok_or_else(not_found)?;
+
+    // Losing the race means a concurrent retrieval got the token
+    let claimed = sqlx::query(
+        "UPDATE api_key_reissues SET status = $1, sealed_token = NULL, retrieved_at = $2
+         WHERE id = $3 AND status = 'pending'",
+    )
+    .bind(ReissueStatus::Retrieved)
+    .bind(database::timestamp(now))
+    .bind(reissue)
+    .execute(pool)
+    .await?
+    .rows_affected()
+        == 1;
+    if !claimed {
+        return Err(not_found());
+    }
+
+    let token = sealed::open(&sealed_token, reissue.as_bytes())
+        .map_err(|e| ServiceError::Internal(format!("Failed to open reissued token: {}", e)))?;
+    let reissue = get(pool, reissue).await?;
+
+    tracing::info!(
+        target: "audit",
+        action = "api_key.reissue_retrieved",
+        org_id = %org_id,
+        key_id = %reissue.key_id,
+        generation = reissue.generation,
+        actor = %actor,
+        "Reissued API key retrieved"
+    );
+    activity::record(
+        pool,
+        org_id,
+        actor,
+        "api_key.reissue_retrieved",
+        Some(&reissue.key_id.to_string()),
+    )
+    .await;
+
+    Ok((reissue, token))
+}
+
+async fn get(pool: &DbPool, reissue_id: Uuid) -> Result<Reissue, ServiceError> {
+    sqlx::query_as::<_, Reissue>(&format!(
+        "SELECT {} FROM api_key_reissues r
+         JOIN api_keys k ON k.id = r.api_key_id
+         WHERE r.id = $1",
+        REISSUE_COLUMNS
+    ))
+    .bind(reissue_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ServiceError::NotFound("Reissued API key not found".to_string()))
+}
+
+/// Revoke the generations older than the retrieved reissues matching
+/// `filter` (`created_before` and `signing_kid` are ignored), completing
+/// them. Reissues nobody retrieved are left alone: their owners only have
+/// the old token.
+pub async fn revoke_superseded(
+    pool: &DbPool,
+    filter: &ReissueFilter,
+    now: DateTime<Utc>,
+) -> Result<Vec<Reissue>, ServiceError> {
+    let retrieved = sqlx::query_as::<_, Reissue>(&format!(
+        "SELECT {} FROM api_key_reissues r
+         JOIN api_keys k ON k.id = r.api_key_id
+         WHERE r.status = 'retrieved' AND k.is_active = true
+           AND ($1 IS NULL OR r.organization_id = $1)
+         ORDER BY r.created_at ASC",
+        REISSUE_COLUMNS
+    ))
+    .bind(filter.org_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut conn = redis_util::get_connection();
+    let mut completed = Vec::new();
+    for mut reissue in retrieved {
+        if let Some(ids) = &filter.ids {
+            if !ids.contains(&reissue.api_key_id) {
+                continue;
+            }
+        }
+
+        // Validation reads the marker, so it's written before the status
+        // says the old tokens are gone
+        let _: () = conn
+            .set_ex(
+                auth::revoked_before_key(reissue.key_id),
+                reissue.generation,
+                REVOKED_BEFORE_TTL_SECS,
+            )
+            .await
+            .map_err(|e| ServiceError::Internal(format!("Redis error: {}", e)))?;
+
+        let mut tx = pool.begin().await?;
+        sqlx::query(
+            "UPDATE api_key_reissues SET status = $1, completed_at = $2
+             WHERE id = $3 AND status = 'retrieved'",
+        )
+        .bind(ReissueStatus::Completed)
+        .bind(database::timestamp(now))
+        .bind(reissue.id)
+        .execute(&mut *tx)
+        .await?;
+        // The signing-keys report now sees the key's only valid signature
+        sqlx::query("UPDATE api_keys SET signing_kid = $1 WHERE id = $2")
+            .bind(&reissue.signing_kid)
+            .bind(reissue.api_key_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        tracing::warn!(
+            target: "audit",
+            action = "api_key.revoke_superseded",
+            org_id = %reissue.organization_id,
+            key_id = %reissue.key_id,
+            generation = reissue.generation,
+            "API key tokens older than the reissued one revoked"
+        );
+
+        reissue.status = ReissueStatus::Completed;
+        reissue.completed_at = Some(now);
+        completed.push(reissue);
+    }
+
+    Ok(completed)
+}
+
+/// Discard the reissued tokens of a key that wasn't retrieved (the key was
+/// revoked)
+pub async fn discard_pending(pool: &DbPool, api_key_id: Uuid) -> Result<(), ServiceError> {
+    sqlx::query(
+        "UPDATE api_key_reissues SET status = $1, sealed_token = NULL
+         WHERE api_key_id = $2 AND status = 'pending'",
+    )
+    .bind(ReissueStatus::Expired)
+    .bind(api_key_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Discard the tokens not retrieved by their deadline. Returns how many.
+pub async fn expire_unretrieved(pool: &DbPool, now: DateTime<Utc>) -> anyhow::Result<u64> {
+    let expired = sqlx::query(
+        "UPDATE api_key_reissues SET status = $1, sealed_token = NULL
+         WHERE status = 'pending' AND retrieve_before <= $2",
+    )
+    .bind(ReissueStatus::Expired)
+    .bind(database::timestamp(now))
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(expired)
+}
+
+/// Register the hourly job discarding unretrieved tokens
+pub fn start_expiry_task(pool: &'static DbPool) {
+    jobs::register(
+        jobs::Job::new("key_reissue_expiry", EXPIRY_INTERVAL, move || async move {
+            let expired = expire_unretrieved(pool, Utc::now()).await?;
+            if expired > 0 {
+                info!("Discarded {} unretrieved reissued API keys", expired);
+            }
+            Ok(())
+        })
+        .jitter(std::time::Duration::from_secs(5 * 60)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::binding::TokenBinding;
+    use crate::auth::keyring::Keyring;
+    use crate::auth::TokenValidator;
+    use crate::database;
+    use crate::test_utils::factory;
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use serial_test::serial;
+
+    fn candidate(token_data: Option<&TokenData>) -> Candidate {
+        Candidate {
+            id: Uuid::now_v7(),
+            organization_id: Uuid::now_v7(),
+            key_id: Uuid::now_v7(),
+            name: "Key".to_string(),
+            signing_kid: None,
+            token_generation: 1,
+            require_request_signing: true,
+            api_version: Some("2025-01-01".to_string()),
+            strict_validation: false,
+            token_data: token_data.map(|data| encode_token_data(data).unwrap()),
+            tier: TierType::Free,
+        }
+    }
+
+    #[test]
+    fn test_current_claims() {
+        let limits = TierType::Free.limits();
+
+        // Keys issued before token data was stored get the tier defaults
+        let claims = current_claims(&candidate(None)).unwrap();
+        assert_eq!(claims.generation, 2);
+        assert_eq!(claims.max_tokens, limits.max_tokens as i32);
+        assert_eq!(claims.scopes, None);
+        assert!(claims.require_signing);
+        assert_eq!(claims.api_version.as_deref(), Some("2025-01-01"));
+
+        // Stored restrictions are kept, capped by the current tier
+        let stored = TokenData {
+            org_id: Uuid::now_v7(),
+            key_id: Uuid::now_v7(),
+            tier: TierType::Scale,
+            max_tokens: limits.max_tokens as i32 + 100,
+            monthly_quota: i32::MAX,
+            require_signing: false,
+            max_concurrency: Some(2),
+            scopes: Some(vec!["embed".to_string()]),
+            models: Some(vec!["all-MiniLM-L6-v2".to_string()]),
+            api_version: None,
+            strict_validation: false,
+            generation: 1,
+        };
+        let key = candidate(Some(&stored));
+        let claims = current_claims(&key).unwrap();
+        assert_eq!(claims.key_id, key.key_id);
+        assert_eq!(claims.tier, TierType::Free);
+        assert_eq!(claims.max_tokens, limits.max_tokens as i32);
+        assert_eq!(claims.monthly_quota, limits.monthly_quota);
+        assert_eq!(claims.max_concurrency, Some(2));
+        assert_eq!(claims.scopes, stored.scopes);
+        assert_eq!(claims.models, stored.models);
+        assert_eq!(claims.generation, 2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_reissue_retrieve_and_revoke_superseded() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let prefix = &config::get_settings().api_key_prefix;
+        let now = Utc::now();
+        let owner = factory::user("test@example.com").await;
+        let key = factory::api_key(&owner).await;
+        let filter = ReissueFilter {
+            org_id: Some(owner.org_id),
+            ..Default::default()
+        };
+
+        let reissued = reissue(pool, &filter, now).await.unwrap();
+        assert_eq!(reissued.len(), 1);
+        assert_eq!(reissued[0].api_key_id, key.id);
+        assert_eq!(reissued[0].generation, 1);
+        // A pending reissue isn't reissued again
+        assert!(reissue(pool, &filter, now).await.unwrap().is_empty());
+
+        let pending_ids: Vec<Uuid> = pending(pool, owner.org_id, owner.id, now)
+            .await
+            .unwrap()
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(pending_ids, vec![reissued[0].id]);
+
+        // Nothing to revoke before the new token is retrieved
+        assert!(revoke_superseded(pool, &filter, now)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Retrieved once, then gone
+        let (retrieved, token) = retrieve(pool, owner.org_id, owner.id, reissued[0].id, now)
+            .await
+            .unwrap();
+        assert_eq!(retrieved.status, ReissueStatus::Retrieved);
+        assert!(matches!(
+            retrieve(pool, owner.org_id, owner.id, reissued[0].id, now).await,
+            Err(ServiceError::NotFound(_))
+        ));
+
+        // No revocation cache, so each validation sees the latest state
+        let validator = TokenValidator::new(
+            Keyring::from_settings().unwrap(),
+            redis_util::get_connection(),
+            0,
+            0,
+            TokenBinding::from_settings(),
+        )
+        .await
+        .unwrap();
+        let old_token = &key.token[prefix.len()..];
+        let new_token = &token[prefix.len()..];
+
+        // Both generations validate until the old one is revoked
+        assert!(validator.validate(old_token).await.is_ok());
+        let claims = validator.validate(new_token).await.unwrap();
+        assert_eq!(claims.key_id(), key.key_id);
+        assert_eq!(claims.generation(), 1);
+
+        let completed = revoke_superseded(pool, &filter, now).await.unwrap();
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].status, ReissueStatus::Completed);
+        let err = validator.validate(old_token).await.unwrap_err();
+        assert!(err.to_string().contains("superseded"));
+        assert!(validator.validate(new_token).await.is_ok());
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_unretrieved_reissues_expire() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let now = Utc::now();
+        let owner = factory::user("test@example.com").await;
+        factory::api_key(&owner).await;
+        let filter = ReissueFilter {
+            org_id: Some(owner.org_id),
+            ..Default::default()
+        };
+
+        let reissued = reissue(pool, &filter, now).await.unwrap();
+        let deadline = reissued[0].retrieve_before;
+        assert_eq!(expire_unretrieved(pool, now).await.unwrap(), 0);
+        assert_eq!(expire_unretrieved(pool, deadline).await.unwrap(), 1);
+
+        assert!(matches!(
+            retrieve(pool, owner.org_id, owner.id, reissued[0].id, now).await,
+            Err(ServiceError::NotFound(_))
+        ));
+        assert_eq!(
+            list(pool, Some(owner.org_id)).await.unwrap()[0].status,
+            ReissueStatus::Expired
+        );
+
+        cleanup_db().await;
+    }
+}
@@ -0,0 +1,507 @@
+//! Creation limits
+//!
+//! A user may own `max_organizations` active organizations; an organization
+//! may hold `max_api_keys` active API keys. Each may create at most
+//! `creations_per_hour` of them in any hour. Limits come from the tier (the
+//! trial tier while a trial runs) and can be raised for one organization by
+//! an admin in `organization_limit_overrides`. A user's organization limits
+//! are the most generous among the organizations they own (Free's when they
+//! own none).
+//!
+//! The checks run in the create services, so the API and the web UI enforce
+//! the same limits. A reached ceiling is a `LimitReached` (403) and the
+//! hourly rate a `RateLimited` (429), both naming the limit and the current
+//! count. Limits are soft: two creations racing past the check can both
+//! succeed.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::ServiceError;
+use crate::billing::trial::{self, Trial};
+use crate::database::{self, DbPool};
+use crate::models::TierType;
+use crate::monitoring::CREATION_LIMIT_REJECTIONS;
+
+/// Creation limits in effect (0 = unlimited)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CreationLimits {
+    pub max_organizations: u32,
+    pub max_api_keys: u32,
+    pub creations_per_hour: u32,
+}
+
+/// Limits an admin set for one organization (None = the tier's, 0 =
+/// unlimited). Unlike other request bodies this one is always strict:
+/// setting the overrides replaces all of them, so a misspelled field would
+/// silently clear a limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(deny_unknown_fields)]
+pub struct Overrides {
+    pub max_organizations: Option<i32>,
+    pub max_api_keys: Option<i32>,
+    pub creations_per_hour: Option<i32>,
+}
+
+impl Overrides {
+    fn is_empty(&self) -> bool {
+        *self == Overrides::default()
+    }
+}
+
+impl CreationLimits {
+    pub fn for_tier(tier: TierType) -> Self {
+        let limits = tier.limits();
+        CreationLimits {
+            max_organizations: limits.max_organizations,
+            max_api_keys: limits.max_api_keys,
+            creations_per_hour: limits.creations_per_hour,
+        }
+    }
+
+    /// These limits with an organization's overrides applied
+    pub fn with(self, overrides: &Overrides) -> Self {
+        let pick = |value: Option<i32>, tier: u32| value.map_or(tier, |v| v.max(0) as u32);
+        CreationLimits {
+            max_organizations: pick(overrides.max_organizations, self.max_organizations),
+            max_api_keys: pick(overrides.max_api_keys, self.max_api_keys),
+            creations_per_hour: pick(overrides.creations_per_hour, self.creations_per_hour),
+        }
+    }
+
+    /// The more generous of the two, limit by limit
+    fn most_generous(self, other: Self) -> Self {
+        let pick = |a: u32, b: u32| if a == 0 || b == 0 { 0 } else { a.max(b) };
+        CreationLimits {
+            max_organizations: pick(self.max_organizations, other.max_organizations),
+            max_api_keys: pick(self.max_api_keys, other.max_api_keys),
+            creations_per_hour: pick(self.creations_per_hour, other.creations_per_hour),
+        }
+    }
+}
+
+/// Whether `count` is already at `limit` (0 = unlimited)
+fn reached(count: i64, limit: u32) -> bool {
+    limit > 0 && count >= i64::from(limit)
+}
+
+#[derive(sqlx::FromRow)]
+struct LimitsRow {
+    tier: TierType,
+    trial_tier: Option<TierType>,
+    trial_expires_at: Option<DateTime<Utc>>,
+    max_organizations: Option<i32>,
+    max_api_keys: Option<i32>,
+    creations_per_hour: Option<i32>,
+}
+
+impl LimitsRow {
+    fn limits(&self, now: DateTime<Utc>) -> CreationLimits {
+        let trial = Trial::from_columns(self.trial_tier, self.trial_expires_at);
+        CreationLimits::for_tier(trial::effective_tier(self.tier, trial, now)).with(&Overrides {
+            max_organizations: self.max_organizations,
+            max_api_keys: self.max_api_keys,
+            creations_per_hour: self.creations_per_hour,
+        })
+    }
+}
+
+const LIMITS_QUERY: &str = "SELECT o.tier, o.trial_tier, o.trial_expires_at,
+            l.max_organizations, l.max_api_keys, l.creations_per_hour
+     FROM organizations o
+     LEFT JOIN organization_limit_overrides l ON l.organization_id = o.id";
+
+/// Limits of an organization
+pub async fn for_organization(pool: &DbPool, org_id: Uuid) -> Result<CreationLimits, ServiceError> {
+    let row = sqlx::query_as::<_, LimitsRow>(&format!("{} WHERE o.id = $1", LIMITS_QUERY))
+        .bind(org_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("Organization not found".to_string()))?;
+    Ok(row.limits(Utc::now()))
+}
+
+/// Limits of a user: the most generous among the organizations they own
+pub async fn for_owner(pool: &DbPool, user_id: Uuid) -> Result<CreationLimits, ServiceError> {
+    let rows = sqlx::query_as::<_, LimitsRow>(&format!(
+        "{} WHERE o.owner_id = $1 AND o.is_active = true",
+        LIMITS_QUERY
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let now = Utc::now();
+    Ok(rows
+        .iter()
+        .map(|row| row.limits(now))
+        .reduce(CreationLimits::most_generous)
+        .unwrap_or_else(|| CreationLimits::for_tier(TierType::Free)))
+}
+
+fn reject(resource: &str, limit: &str, error: ServiceError) -> ServiceError {
+    CREATION_LIMIT_REJECTIONS
+        .with_label_values(&[resource, limit])
+        .inc();
+    error
+}
+
+/// Refuse a new organization for `user_id` past their limits
+pub async fn check_organization_creation(pool: &DbPool, user_id: Uuid) -> Result<(), ServiceError> {
+    let limits = for_owner(pool, user_id).await?;
+
+    let (owned,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM organizations WHERE owner_id = $1 AND is_active = true",
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+    if reached(owned, limits.max_organizations) {
+        tracing::info!(user_id = %user_id, owned, "Organization limit reached");
+        return Err(reject(
+            "organization",
+            "max",
+            ServiceError::LimitReached(format!(
+                "Organization limit reached: you own {} of {} organizations allowed",
+                owned, limits.max_organizations
+            )),
+        ));
+    }
+
+    let (recent,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM organizations WHERE owner_id = $1 AND created_at > $2",
+    )
+    .bind(user_id)
+    .bind(database::timestamp(Utc::now() - Duration::hours(1)))
+    .fetch_one(pool)
+    .await?;
+    if reached(recent, limits.creations_per_hour) {
+        tracing::info!(user_id = %user_id, recent, "Organization creation rate reached");
+        return Err(reject(
+            "organization",
+            "per_hour",
+            ServiceError::RateLimited(format!(
+                "Too many organizations created: {} in the last hour of {} allowed, try again later",
+                recent, limits.creations_per_hour
+            )),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Refuse a new API key in `org_id` past its limits
+pub async fn check_api_key_creation(pool: &DbPool, org_id: Uuid) -> Result<(), ServiceError> {
+    let limits = for_organization(pool, org_id).await?;
+
+    let (active,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM api_keys WHERE organization_id = $1 AND is_active = true",
+    )
+    .bind(org_id)
+    .fetch_one(pool)
+    .await?;
+    if reached(active, limits.max_api_keys) {
+        tracing::info!(org_id = %org_id, active, "API key limit reached");
+        return Err(reject(
+            "api_key",
+            "max",
+            ServiceError::LimitReached(format!(
+                "API key limit reached: this organization has {} of {} active keys allowed",
+                active, limits.max_api_keys
+            )),
+        ));
+    }
+
+    let (recent,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM api_keys WHERE organization_id = $1 AND created_at > $2",
+    )
+    .bind(org_id)
+    .bind(database::timestamp(Utc::now() - Duration::hours(1)))
+    .fetch_one(pool)
+    .await?;
+    if reached(recent, limits.creations_per_hour) {
+        tracing::info!(org_id = %org_id, recent, "API key creation rate reached");
+        return Err(reject(
+            "api_key",
+            "per_hour",
+            ServiceError::RateLimited(format!(
+                "Too many API keys created: {} in the last hour of {} allowed, try again later",
+                recent, limits.creations_per_hour
+            )),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Overrides set for an organization (all None if there are none)
+pub async fn overrides(pool: &DbPool, org_id: Uuid) -> Result<Overrides, ServiceError> {
+    let overrides = sqlx::query_as::<_, Overrides>(
+        "SELECT max_organizations, max_api_keys, creations_per_hour
+         FROM organization_limit_overrides WHERE organization_id = $1",
+    )
+    .bind(org_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(overrides.unwrap_or_default())
+}
+
+/// Replace an organization's overrides (all None removes them)
+pub async fn set_overrides(
+    pool: &DbPool,
+    org_id: Uuid,
+    overrides: &Overrides,
+) -> Result<(), ServiceError> {
+    let values = [
+        overrides.max_organizations,
+        overrides.max_api_keys,
+        overrides.creations_per_hour,
+    ];
+    if values.iter().flatten().any(|value| *value < 0) {
+        return Err(ServiceError::Invalid(
+            "Limits must be 0 (unlimited) or more".to_string(),
+        ));
+    }
+
+    let (exists,): (bool,) =
+        sqlx::query_as("SELECT EXISTS(SELECT 1 FROM organizations WHERE id = $1)")
+            .bind(org_id)
+            .fetch_one(pool)
+            .await?;
+    if !exists {
+        return Err(ServiceError::NotFound("Organization not found".to_string()));
+    }
+
+    if overrides.is_empty() {
+        sqlx::query("DELETE FROM organization_limit_overrides WHERE organization_id = $1")
+            .bind(org_id)
+            .execute(pool)
+            .await?;
+        return Ok(());
+    }
+
+    sqlx::query(
+        "INSERT INTO organization_limit_overrides
+             (organization_id, max_organizations, max_api_keys, creations_per_hour, updated_at)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (organization_id) DO UPDATE SET
+             max_organizations = excluded.max_organizations,
+             max_api_keys = excluded.max_api_keys,
+             creations_per_hour = excluded.creations_per_hour,
+             updated_at = excluded.updated_at",
+    )
+    .bind(org_id)
+    .bind(overrides.max_organizations)
+    .bind(overrides.max_api_keys)
+    .bind(overrides.creations_per_hour)
+    .bind(database::timestamp(Utc::now()))
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::models::CreateOrganizationRequest;
+    use crate::services::{api_keys, organizations};
+    use crate::test_utils::factory;
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use serial_test::serial;
+
+    #[test]
+    fn test_overrides_apply_per_limit() {
+        let free = CreationLimits::for_tier(TierType::Free);
+        assert_eq!(free.with(&Overrides::default()), free);
+
+        let raised = free.with(&Overrides {
+            max_api_keys: Some(5000),
+            creations_per_hour: Some(0),
+            ..Overrides::default()
+        });
+        assert_eq!(raised.max_organizations, free.max_organizations);
+        assert_eq!(raised.max_api_keys, 5000);
+        assert_eq!(raised.creations_per_hour, 0);
+    }
+
+    #[test]
+    fn test_overrides_reject_unknown_fields() {
+        let parsed: Overrides = serde_json::from_str(r#"{"max_api_keys": 50}"#).unwrap();
+        assert_eq!(parsed.max_api_keys, Some(50));
+        assert!(serde_json::from_str::<Overrides>(r#"{"max_api_key": 50}"#).is_err());
+    }
+
+    #[test]
+    fn test_most_generous_treats_zero_as_unlimited() {
+        let a = CreationLimits {
+            max_organizations: 20,
+            max_api_keys: 0,
+            creations_per_hour: 30,
+        };
+        let b = CreationLimits {
+            max_organizations: 50,
+            max_api_keys: 500,
+            creations_per_hour: 10,
+        };
+        assert_eq!(
+            a.most_generous(b),
+            CreationLimits {
+                max_organizations: 50,
+                max_api_keys: 0,
+                creations_per_hour: 30,
+            }
+        );
+        assert!(!reached(1_000_000, 0));
+        assert!(reached(20, 20));
+        assert!(!reached(19, 20));
+    }
+
+    fn org_request(name: &str) -> CreateOrganizationRequest {
+        CreateOrganizationRequest {
+            name: name.to_string(),
+            tier: None,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_organization_ceiling() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("test@example.com").await;
+        set_overrides(
+            pool,
+            owner.org_id,
+            &Overrides {
+                max_organizations: Some(2),
+                creations_per_hour: Some(0),
+                ..Overrides::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        // The personal organization counts
+        organizations::create(pool, owner.id, &org_request("Second"))
+            .await
+            .unwrap();
+        let result = organizations::create(pool, owner.id, &org_request("Third")).await;
+        let Err(ServiceError::LimitReached(message)) = result else {
+            panic!("expected LimitReached, got {:?}", result);
+        };
+        assert!(message.contains("2 of 2"), "{}", message);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_organization_hourly_rate() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("test@example.com").await;
+        set_overrides(
+            pool,
+            owner.org_id,
+            &Overrides {
+                max_organizations: Some(0),
+                creations_per_hour: Some(2),
+                ..Overrides::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        organizations::create(pool, owner.id, &org_request("Second"))
+            .await
+            .unwrap();
+        let result = organizations::create(pool, owner.id, &org_request("Third")).await;
+        let Err(ServiceError::RateLimited(message)) = result else {
+            panic!("expected RateLimited, got {:?}", result);
+        };
+        assert!(message.contains("2 in the last hour"), "{}", message);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_api_key_ceiling_counts_active_keys() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("test@example.com").await;
+        set_overrides(
+            pool,
+            owner.org_id,
+            &Overrides {
+                max_api_keys: Some(1),
+                creations_per_hour: Some(0),
+                ..Overrides::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let issued = api_keys::create(pool, owner.org_id, owner.id, &factory::key_request("A"))
+            .await
+            .unwrap();
+        let result =
+            api_keys::create(pool, owner.org_id, owner.id, &factory::key_request("B")).await;
+        let Err(ServiceError::LimitReached(message)) = result else {
+            panic!("expected LimitReached, got {:?}", result);
+        };
+        assert!(message.contains("1 of 1"), "{}", message);
+
+        // Revoking frees the slot
+        api_keys::revoke(pool, owner.org_id, owner.id, issued.api_key.id)
+            .await
+            .unwrap();
+        api_keys::create(pool, owner.org_id, owner.id, &factory::key_request("B"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_api_key_hourly_rate() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("test@example.com").await;
+        set_overrides(
+            pool,
+            owner.org_id,
+            &Overrides {
+                creations_per_hour: Some(1),
+                ..Overrides::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        api_keys::create(pool, owner.org_id, owner.id, &factory::key_request("A"))
+            .await
+            .unwrap();
+        let result =
+            api_keys::create(pool, owner.org_id, owner.id, &factory::key_request("B")).await;
+        assert!(
+            matches!(result, Err(ServiceError::RateLimited(_))),
+            "{:?}",
+            result
+        );
+
+        // Lifting the override restores the tier's rate
+        set_overrides(pool, owner.org_id, &Overrides::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            overrides(pool, owner.org_id).await.unwrap(),
+            Overrides::default()
+        );
+        api_keys::create(pool, owner.org_id, owner.id, &factory::key_request("B"))
+            .await
+            .unwrap();
+    }
+}
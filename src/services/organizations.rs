@@ -0,0 +1,801 @@
+//! Organizations and memberships
+
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{activity, limits, validation_error, Actor, ServiceError};
+use crate::billing::cycle::{self, BillingCycle};
+use crate::billing::trial::Trial;
+use crate::billing::{self, ip_storage};
+use crate::config;
+use crate::database::{self, ApiRequestLogRepo, DbPool};
+use crate::inference::catalog;
+use crate::models::{
+    CachePolicy, CreateOrganizationRequest, InviteMemberRequest, Organization, OrganizationRole,
+    TierType,
+};
+use crate::residency;
+
+/// An organization as seen by one of its members
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Membership {
+    pub id: Uuid,
+    pub name: String,
+    pub tier: TierType,
+    pub is_active: bool,
+    pub cache_policy: CachePolicy,
+    #[cfg_attr(feature = "sqlite", sqlx(json(nullable)))]
+    pub allowed_models: Option<Vec<String>>,
+    pub default_model: Option<String>,
+    pub billing_anchor_day: Option<i32>,
+    pub store_client_ip: bool,
+    pub region: String,
+    pub trial_tier: Option<TierType>,
+    pub trial_expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub role: OrganizationRole,
+}
+
+impl Membership {
+    /// The organization's trial, expired or not
+    pub fn trial(&self) -> Option<Trial> {
+        Trial::from_columns(self.trial_tier, self.trial_expires_at)
+    }
+
+    /// The organization's trial, if it's still running at `now`
+    pub fn active_trial(&self, now: DateTime<Utc>) -> Option<Trial> {
+        self.trial().filter(|trial| trial.is_active(now))
+    }
+
+    /// Owners and admins manage keys and members
+    pub fn can_manage(&self) -> bool {
+        matches!(self.role, OrganizationRole::Owner | OrganizationRole::Admin)
+    }
+}
+
+/// Organizations the user belongs to, newest first
+pub async fn list_for_user(pool: &DbPool, user_id: Uuid) -> Result<Vec<Membership>, ServiceError> {
+    let orgs = sqlx::query_as::<_, Membership>(
+        "SELECT o.id, o.name, o.tier, o.is_active, o.cache_policy, o.allowed_models, o.default_model,
+                o.billing_anchor_day, o.store_client_ip, o.region, o.trial_tier, o.trial_expires_at,
+                o.created_at, om.role
+         FROM organizations o
+         INNER JOIN organization_members om ON o.id = om.organization_id
+         WHERE om.user_id = $1
+         ORDER BY o.created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(orgs)
+}
+
+/// The actor's membership in an organization. A service account is a
+/// member of its own organization only, with the role it was created with.
+pub async fn membership(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+) -> Result<Membership, ServiceError> {
+    let not_a_member =
+        || ServiceError::Forbidden("You are not a member of this organization".to_string());
+
+    match actor.into() {
+        Actor::User(user_id) => sqlx::query_as::<_, Membership>(
+            "SELECT o.id, o.name, o.tier, o.is_active, o.cache_policy, o.allowed_models, o.default_model,
+                    o.billing_anchor_day, o.store_client_ip, o.region, o.trial_tier, o.trial_expires_at,
+                    o.created_at, om.role
+             FROM organizations o
+             INNER JOIN organization_members om ON o.id = om.organization_id
+             WHERE o.id = $1 AND om.user_id = $2",
+        )
+        .bind(org_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(not_a_member),
+        Actor::ServiceAccount {
+            org_id: own_org_id,
+            role,
+            ..
+        } => {
+            if own_org_id != org_id {
+                return Err(not_a_member());
+            }
+            sqlx::query_as::<_, Membership>(
+                "SELECT o.id, o.name, o.tier, o.is_active, o.cache_policy, o.allowed_models, o.default_model,
+                        o.billing_anchor_day, o.store_client_ip, o.region, o.trial_tier, o.trial_expires_at,
+                        o.created_at, CAST($2 AS VARCHAR) AS role
+                 FROM organizations o
+                 WHERE o.id = $1",
+            )
+            .bind(org_id)
+            .bind(role)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(not_a_member)
+        }
+    }
+}
+
+/// The actor's membership, provided they're an owner or admin; `action`
+/// completes "Only owners and admins can ..."
+pub async fn require_manager(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    action: &str,
+) -> Result<Membership, ServiceError> {
+    let member = membership(pool, org_id, actor).await?;
+    if !member.can_manage() {
+        return Err(ServiceError::Forbidden(format!(
+            "Only owners and admins can {}",
+            action
+        )));
+    }
+    Ok(member)
+}
+
+/// Create an organization owned by the user
+pub async fn create(
+    pool: &DbPool,
+    user_id: Uuid,
+    req: &CreateOrganizationRequest,
+) -> Result<Organization, ServiceError> {
+    req.validate().map_err(|e| validation_error(&e))?;
+    limits::check_organization_creation(pool, user_id).await?;
+
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+
+    let org = sqlx::query_as::<_, Organization>(
+        "INSERT INTO organizations (id, name, owner_id, tier, is_active, created_at, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         RETURNING *",
+    )
+    .bind(Uuid::now_v7())
+    .bind(&req.name)
+    .bind(user_id)
+    .bind(req.tier.unwrap_or(TierType::Free))
+    .bind(true)
+    .bind(database::timestamp(now))
+    .bind(database::timestamp(now))
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| ServiceError::Internal(format!("Failed to create organization: {}", e)))?;
+
+    add_member(&mut tx, org.id, user_id, OrganizationRole::Owner).await?;
+
+    tx.commit().await?;
+
+    Ok(org)
+}
+
+/// Remember the organization as the user's active one
+pub async fn select(pool: &DbPool, user_id: Uuid, org_id: Uuid) -> Result<(), ServiceError> {
+    membership(pool, org_id, user_id).await?;
+
+    sqlx::query("UPDATE users SET last_selected_org_id = $1 WHERE id = $2")
+        .bind(org_id)
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .map_err(|e| ServiceError::Internal(format!("Failed to update user preferences: {}", e)))?;
+
+    Ok(())
+}
+
+/// Change how the organization's embeddings are cached (owners and admins)
+pub async fn set_cache_policy(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    policy: CachePolicy,
+) -> Result<Membership, ServiceError> {
+    let actor = actor.into();
+    let mut member = require_manager(pool, org_id, actor, "change the cache policy").await?;
+
+    sqlx::query("UPDATE organizations SET cache_policy = $1, updated_at = $2 WHERE id = $3")
+        .bind(policy)
+        .bind(database::timestamp(Utc::now()))
+        .bind(org_id)
+        .execute(pool)
+        .await
+        .map_err(|e| ServiceError::Internal(format!("Failed to update cache policy: {}", e)))?;
+
+    crate::cache::policy::invalidate(org_id);
+
+    tracing::warn!(
+        target: "audit",
+        action = "organization.cache_policy_update",
+        org_id = %org_id,
+        actor = %actor,
+        from = member.cache_policy.as_str(),
+        to = policy.as_str(),
+        "Cache policy changed"
+    );
+    activity::record(
+        pool,
+        org_id,
+        actor,
+        "organization.cache_policy_update",
+        Some(policy.as_str()),
+    )
+    .await;
+
+    member.cache_policy = policy;
+    Ok(member)
+}
+
+/// Restrict the models the organization's keys may use and pick the default
+/// for requests that don't name one (owners and admins). `None` lifts the
+/// restriction / falls back to the server default.
+pub async fn set_model_settings(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    allowed_models: Option<Vec<String>>,
+    default_model: Option<String>,
+) -> Result<Membership, ServiceError> {
+    let actor = actor.into();
+    let mut member = require_manager(pool, org_id, actor, "change the model settings").await?;
+
+    let available = catalog::available_ids();
+    let allowed_models = match allowed_models {
+        Some(models) if models.is_empty() => {
+            return Err(ServiceError::Invalid(
+                "allowed_models must not be empty (omit it to allow all models)".to_string(),
+            ));
+        }
+        Some(models) => Some(known_models(models, &available)?),
+        None => None,
+    };
+    if let Some(default) = &default_model {
+        known_models(vec![default.clone()], &available)?;
+        if let Some(allowed) = &allowed_models {
+            if !allowed.contains(default) {
+                return Err(ServiceError::Invalid(format!(
+                    "default_model '{}' must be one of allowed_models",
+                    default
+                )));
+            }
+        }
+    }
+
+    sqlx::query(
+        "UPDATE organizations SET allowed_models = $1, default_model = $2, updated_at = $3
+         WHERE id = $4",
+    )
+    .bind(allowed_models.as_ref().map(database::list))
+    .bind(&default_model)
+    .bind(database::timestamp(Utc::now()))
+    .bind(org_id)
+    .execute(pool)
+    .await
+    .map_err(|e| ServiceError::Internal(format!("Failed to update model settings: {}", e)))?;
+
+    catalog::invalidate(org_id);
+
+    tracing::warn!(
+        target: "audit",
+        action = "organization.model_settings_update",
+        org_id = %org_id,
+        actor = %actor,
+        allowed_models = ?allowed_models,
+        default_model = ?default_model,
+        "Model settings changed"
+    );
+    activity::record(
+        pool,
+        org_id,
+        actor,
+        "organization.model_settings_update",
+        None,
+    )
+    .await;
+
+    member.allowed_models = allowed_models;
+    member.default_model = default_model;
+    Ok(member)
+}
+
+/// Days between two changes of an organization's billing anchor
+pub const ANCHOR_CHANGE_COOLDOWN_DAYS: i64 = 30;
+
+/// Anchor the organization's billing cycle (and free tier quota) to a day
+/// of the month (owners and admins). `None` goes back to calendar months.
+/// The current cycle's usage carries over to the cycle the new anchor
+/// starts, and the anchor can change once per `ANCHOR_CHANGE_COOLDOWN_DAYS`.
+pub async fn set_billing_anchor(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    anchor_day: Option<i32>,
+) -> Result<Membership, ServiceError> {
+    let actor = actor.into();
+    let mut member = require_manager(pool, org_id, actor, "change the billing anchor").await?;
+
+    if let Some(day) = anchor_day {
+        if !u32::try_from(day).is_ok_and(|day| cycle::ANCHOR_DAYS.contains(&day)) {
+            return Err(ServiceError::Invalid(
+                "billing_anchor_day must be between 1 and 31".to_string(),
+            ));
+        }
+    }
+
+    let now = Utc::now();
+    let anchor = |day: Option<i32>| day.map(|day| day as u32);
+    let from = BillingCycle::containing(now, anchor(member.billing_anchor_day));
+    let to = BillingCycle::containing(now, anchor(anchor_day));
+    if from != to {
+        billing::carry_over_cycle(org_id, &from, &to)
+            .await
+            .map_err(|e| ServiceError::Internal(format!("Redis error: {}", e)))?;
+    }
+
+    let cooldown_start = now - Duration::days(ANCHOR_CHANGE_COOLDOWN_DAYS);
+    let updated = sqlx::query(
+        "UPDATE organizations
+         SET billing_anchor_day = $1, billing_anchor_changed_at = $2, updated_at = $2
+         WHERE id = $3
+           AND (billing_anchor_changed_at IS NULL OR billing_anchor_changed_at <= $4)",
+    )
+    .bind(anchor_day)
+    .bind(database::timestamp(now))
+    .bind(org_id)
+    .bind(database::timestamp(cooldown_start))
+    .execute(pool)
+    .await
+    .map_err(|e| ServiceError::Internal(format!("Failed to update billing anchor: {}", e)))?;
+    if updated.rows_affected() == 0 {
+        return Err(ServiceError::RateLimited(format!(
+            "The billing anchor can only be changed once every {} days",
+            ANCHOR_CHANGE_COOLDOWN_DAYS
+        )));
+    }
+
+    cycle::invalidate(org_id);
+
+    tracing::warn!(
+        target: "audit",
+        action = "organization.billing_anchor_update",
+        org_id = %org_id,
+        actor = %actor,
+        from = ?member.billing_anchor_day,
+        to = ?anchor_day,
+        "Billing anchor changed"
+    );
+    activity::record(
+        pool,
+        org_id,
+        actor,
+        "organization.billing_anchor_update",
+        None,
+    )
+    .await;
+
+    member.billing_anchor_day = anchor_day;
+    Ok(member)
+}
+
+/// Keep or stop keeping client IPs in the organization's request log
+/// (owners and admins). Switching it off also erases the stored IPs.
+pub async fn set_ip_storage(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    store_client_ip: bool,
+) -> Result<Membership, ServiceError> {
+    let actor = actor.into();
+    let mut member = require_manager(pool, org_id, actor, "change IP storage").await?;
+
+    sqlx::query("UPDATE organizations SET store_client_ip = $1, updated_at = $2 WHERE id = $3")
+        .bind(store_client_ip)
+        .bind(database::timestamp(Utc::now()))
+        .bind(org_id)
+        .execute(pool)
+        .await
+        .map_err(|e| ServiceError::Internal(format!("Failed to update IP storage: {}", e)))?;
+
+    ip_storage::invalidate(org_id);
+
+    let erased = if store_client_ip {
+        0
+    } else {
+        ApiRequestLogRepo::new(pool)
+            .clear_client_ips(org_id)
+            .await
+            .map_err(|e| ServiceError::Internal(format!("Failed to erase client IPs: {}", e)))?
+    };
+
+    tracing::warn!(
+        target: "audit",
+        action = "organization.ip_storage_update",
+        org_id = %org_id,
+        actor = %actor,
+        from = member.store_client_ip,
+        to = store_client_ip,
+        erased = erased,
+        "Client IP storage changed"
+    );
+    activity::record(pool, org_id, actor, "organization.ip_storage_update", None).await;
+
+    member.store_client_ip = store_client_ip;
+    Ok(member)
+}
+
+/// Pin the organization to a region, or `any` (owners and admins). Moving
+/// to a stricter region purges, in the background, the requests logged so
+/// far and the cache entries of their texts.
+pub async fn set_region(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    region: &str,
+) -> Result<Membership, ServiceError> {
+    let actor = actor.into();
+    let mut member = require_manager(pool, org_id, actor, "change the region").await?;
+
+    let known = residency::known_regions(config::get_settings());
+    if region != residency::ANY_REGION && !known.iter().any(|known| known == region) {
+        let mut options = vec![residency::ANY_REGION.to_string()];
+        options.extend(known);
+        return Err(ServiceError::Invalid(format!(
+            "region must be one of: {}",
+            options.join(", ")
+        )));
+    }
+
+    sqlx::query("UPDATE organizations SET region = $1, updated_at = $2 WHERE id = $3")
+        .bind(region)
+        .bind(database::timestamp(Utc::now()))
+        .bind(org_id)
+        .execute(pool)
+        .await
+        .map_err(|e| ServiceError::Internal(format!("Failed to update region: {}", e)))?;
+
+    residency::invalidate(org_id);
+
+    let purge = residency::is_stricter(&member.region, region);
+    if purge {
+        residency::spawn_purge(org_id);
+    }
+
+    tracing::warn!(
+        target: "audit",
+        action = "organization.region_update",
+        org_id = %org_id,
+        actor = %actor,
+        from = %member.region,
+        to = %region,
+        purge = purge,
+        "Region changed"
+    );
+    activity::record(pool, org_id, actor, "organization.region_update", None).await;
+
+    member.region = region.to_string();
+    Ok(member)
+}
+
+/// `models` sorted and deduplicated, if all are served by this instance
+pub fn known_models(
+    mut models: Vec<String>,
+    available: &[String],
+) -> Result<Vec<String>, ServiceError> {
+    if let Some(unknown) = models.iter().find(|m| !available.contains(m)) {
+        return Err(ServiceError::Invalid(format!(
+            "Unknown model '{}' (available: {})",
+            unknown,
+            available.join(", ")
+        )));
+    }
+    models.sort();
+    models.dedup();
+    Ok(models)
+}
+
+/// Add an existing user to the organization
+pub async fn invite(
+    pool: &DbPool,
+    org_id: Uuid,
+    actor: impl Into<Actor>,
+    req: &InviteMemberRequest,
+) -> Result<(), ServiceError> {
+    let actor = actor.into();
+    require_manager(pool, org_id, actor, "invite members").await?;
+
+    let invited_user = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM users WHERE email = $1 AND deleted_at IS NULL",
+    )
+    .bind(&req.email)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ServiceError::Invalid("User not found".to_string()))?;
+
+    if membership(pool, org_id, invited_user).await.is_ok() {
+        return Err(ServiceError::Conflict(
+            "User is already a member".to_string(),
+        ));
+    }
+
+    let mut tx = pool.begin().await?;
+    add_member(&mut tx, org_id, invited_user, req.role).await?;
+    tx.commit().await?;
+
+    crate::auth::session::invalidate_membership(invited_user, org_id);
+
+    tracing::warn!(
+        target: "audit",
+        action = "member.invite",
+        org_id = %org_id,
+        user_id = %invited_user,
+        role = ?req.role,
+        actor = %actor,
+        "Member invited"
+    );
+    activity::record(
+        pool,
+        org_id,
+        actor,
+        "member.invite",
+        Some(&invited_user.to_string()),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Insert a membership row inside a caller's transaction
+pub(crate) async fn add_member(
+    tx: &mut sqlx::Transaction<'_, crate::database::Db>,
+    org_id: Uuid,
+    user_id: Uuid,
+    role: OrganizationRole,
+) -> Result<(), ServiceError> {
+    sqlx::query(
+        "INSERT INTO organization_members (organization_id, user_id, role, created_at)
+         VALUES ($1, $2, $3, $4)",
+    )
+    .bind(org_id)
+    .bind(user_id)
+    .bind(role)
+    .bind(database::timestamp(Utc::now()))
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| ServiceError::Internal(format!("Failed to add organization member: {}", e)))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::test_utils::factory;
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use serial_test::serial;
+
+    fn invite_req(email: &str, role: OrganizationRole) -> InviteMemberRequest {
+        InviteMemberRequest {
+            email: email.to_string(),
+            role,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_create_makes_caller_owner() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+
+        let user = factory::user("test@example.com").await;
+        let org = create(
+            pool,
+            user.id,
+            &CreateOrganizationRequest {
+                name: "Team".to_string(),
+                tier: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        let member = membership(pool, org.id, user.id).await.unwrap();
+        assert_eq!(member.role, OrganizationRole::Owner);
+        assert_eq!(member.tier, TierType::Free);
+        assert_eq!(list_for_user(pool, user.id).await.unwrap().len(), 2);
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_invite_rules() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+
+        let owner = factory::user("owner@example.com").await;
+        let member = factory::user("member@example.com").await;
+        let outsider = factory::user("outsider@example.com").await;
+
+        invite(
+            pool,
+            owner.org_id,
+            owner.id,
+            &invite_req(&member.email, OrganizationRole::Member),
+        )
+        .await
+        .unwrap();
+
+        // Already a member
+        assert!(matches!(
+            invite(
+                pool,
+                owner.org_id,
+                owner.id,
+                &invite_req(&member.email, OrganizationRole::Admin),
+            )
+            .await,
+            Err(ServiceError::Conflict(_))
+        ));
+
+        // Plain members can't invite
+        assert!(matches!(
+            invite(
+                pool,
+                owner.org_id,
+                member.id,
+                &invite_req(&outsider.email, OrganizationRole::Member),
+            )
+            .await,
+            Err(ServiceError::Forbidden(_))
+        ));
+
+        // Unknown users can't be invited
+        assert!(matches!(
+            invite(
+                pool,
+                owner.org_id,
+                owner.id,
+                &invite_req("nobody@example.com", OrganizationRole::Member),
+            )
+            .await,
+            Err(ServiceError::Invalid(_))
+        ));
+
+        // Selecting requires membership
+        select(pool, member.id, owner.org_id).await.unwrap();
+        assert!(select(pool, outsider.id, owner.org_id).await.is_err());
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_cache_policy_requires_manager() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+
+        let owner = factory::user("owner@example.com").await;
+        let member = factory::user("member@example.com").await;
+        invite(
+            pool,
+            owner.org_id,
+            owner.id,
+            &invite_req(&member.email, OrganizationRole::Member),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            crate::cache::policy::policy_for(owner.org_id).await,
+            CachePolicy::Shared
+        );
+        assert!(matches!(
+            set_cache_policy(pool, owner.org_id, member.id, CachePolicy::Private).await,
+            Err(ServiceError::Forbidden(_))
+        ));
+
+        let updated = set_cache_policy(pool, owner.org_id, owner.id, CachePolicy::Private)
+            .await
+            .unwrap();
+        assert_eq!(updated.cache_policy, CachePolicy::Private);
+        // The cached lookup was invalidated
+        assert_eq!(
+            crate::cache::policy::policy_for(owner.org_id).await,
+            CachePolicy::Private
+        );
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_region_requires_manager_and_known_region() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+
+        let owner = factory::user("owner@example.com").await;
+        let member = factory::user("member@example.com").await;
+        invite(
+            pool,
+            owner.org_id,
+            owner.id,
+            &invite_req(&member.email, OrganizationRole::Member),
+        )
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            set_region(pool, owner.org_id, member.id, residency::ANY_REGION).await,
+            Err(ServiceError::Forbidden(_))
+        ));
+        // No regions are configured in tests
+        assert!(matches!(
+            set_region(pool, owner.org_id, owner.id, "eu").await,
+            Err(ServiceError::Invalid(_))
+        ));
+
+        let updated = set_region(pool, owner.org_id, owner.id, residency::ANY_REGION)
+            .await
+            .unwrap();
+        assert_eq!(updated.region, residency::ANY_REGION);
+        assert_eq!(
+            residency::region_for(owner.org_id).await.as_deref(),
+            Some(residency::ANY_REGION)
+        );
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_anchor_change_keeps_used_quota() {
+        use redis::AsyncCommands;
+
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        let owner = factory::user("anchor@example.com").await;
+
+        // 900 requests used in the current calendar month
+        let calendar = BillingCycle::containing(Utc::now(), None);
+        let key = format!("ratelimit:{}:{}", owner.org_id, calendar.key());
+        let mut conn = crate::redis_util::get_connection();
+        let _: () = conn.set_ex(&key, 900, 60).await.unwrap();
+
+        // Moving the anchor mid-cycle switches to another counter, which
+        // starts from the used quota rather than from 0
+        let updated = set_billing_anchor(pool, owner.org_id, owner.id, Some(15))
+            .await
+            .unwrap();
+        assert_eq!(updated.billing_anchor_day, Some(15));
+        let usage = crate::billing::quota_usage(owner.org_id, 1_000)
+            .await
+            .unwrap();
+        assert_ne!(usage.cycle.unwrap().key(), calendar.key());
+        assert_eq!(usage.used, 900);
+
+        // And it can't be moved again right away
+        assert!(matches!(
+            set_billing_anchor(pool, owner.org_id, owner.id, None).await,
+            Err(ServiceError::RateLimited(_))
+        ));
+        assert_eq!(cycle::org_anchor(owner.org_id).await, Some(15));
+
+        let _: () = conn
+            .del(&[
+                key,
+                format!("ratelimit:{}:{}", owner.org_id, usage.cycle.unwrap().key()),
+            ])
+            .await
+            .unwrap();
+        cleanup_db().await;
+    }
+}
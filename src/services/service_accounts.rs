@@ -0,0 +1,170 @@
+//! Service accounts: organization-scoped principals for automation
+
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{activity, organizations, validation_error, ServiceError};
+use crate::auth::binding::TokenBinding;
+use crate::auth::{service_account, SERVICE_ACCOUNT_TOKEN_PREFIX};
+use crate::config;
+use crate::database::DbPool;
+use crate::models::{CreateServiceAccountRequest, OrganizationRole, ServiceAccount};
+
+/// A newly created account with its one-time token
+#[derive(Debug, Clone)]
+pub struct IssuedServiceAccount {
+    pub account: ServiceAccount,
+    /// `sa_`-prefixed token, only available at creation
+    pub token: String,
+}
+
+/// The user's membership, provided they own the organization
+async fn require_owner(
+    pool: &DbPool,
+    org_id: Uuid,
+    user_id: Uuid,
+    action: &str,
+) -> Result<(), ServiceError> {
+    let member = organizations::membership(pool, org_id, user_id).await?;
+    if member.role != OrganizationRole::Owner {
+        return Err(ServiceError::Forbidden(format!(
+            "Only owners can {}",
+            action
+        )));
+    }
+    Ok(())
+}
+
+/// Create a service account in an organization on behalf of its owner
+pub async fn create(
+    pool: &DbPool,
+    org_id: Uuid,
+    user_id: Uuid,
+    req: &CreateServiceAccountRequest,
+) -> Result<IssuedServiceAccount, ServiceError> {
+    req.validate().map_err(|e| validation_error(&e))?;
+    require_owner(pool, org_id, user_id, "create service accounts").await?;
+
+    if req.role == OrganizationRole::Owner {
+        return Err(ServiceError::Invalid(
+            "Service accounts can be admins or members, not owners".to_string(),
+        ));
+    }
+
+    let settings = config::get_settings();
+    let private_key_bytes = hex::decode(&settings.token_private_key)
+        .map_err(|e| ServiceError::Internal(format!("Invalid private key: {}", e)))?;
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(
+        &private_key_bytes[..]
+            .try_into()
+            .map_err(|_| ServiceError::Internal("Invalid private key length".to_string()))?,
+    );
+
+    let id = Uuid::now_v7();
+    let token = service_account::sign_service_account_token(
+        id,
+        org_id,
+        &signing_key,
+        &TokenBinding::from_settings(),
+    )
+    .map_err(|e| ServiceError::Internal(format!("Failed to sign token: {}", e)))?;
+
+    let account = sqlx::query_as::<_, ServiceAccount>(
+        "INSERT INTO service_accounts (id, organization_id, name, role, created_by)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING *",
+    )
+    .bind(id)
+    .bind(org_id)
+    .bind(&req.name)
+    .bind(req.role)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| ServiceError::Internal(format!("Failed to create service account: {}", e)))?;
+
+    tracing::warn!(
+        target: "audit",
+        action = "service_account.create",
+        org_id = %org_id,
+        service_account_id = %id,
+        role = ?req.role,
+        user_id = %user_id,
+        "Service account created"
+    );
+    activity::record(
+        pool,
+        org_id,
+        user_id,
+        "service_account.create",
+        Some(&id.to_string()),
+    )
+    .await;
+
+    Ok(IssuedServiceAccount {
+        account,
+        token: format!("{}{}", SERVICE_ACCOUNT_TOKEN_PREFIX, token),
+    })
+}
+
+/// Service accounts of an organization the user belongs to, newest first
+/// (revoked ones included)
+pub async fn list(
+    pool: &DbPool,
+    org_id: Uuid,
+    user_id: Uuid,
+) -> Result<Vec<ServiceAccount>, ServiceError> {
+    organizations::membership(pool, org_id, user_id).await?;
+
+    let accounts = sqlx::query_as::<_, ServiceAccount>(
+        "SELECT * FROM service_accounts WHERE organization_id = $1 ORDER BY created_at DESC",
+    )
+    .bind(org_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(accounts)
+}
+
+/// Revoke a service account; its token stops working within the account
+/// cache TTL on other instances, immediately on this one
+pub async fn revoke(
+    pool: &DbPool,
+    org_id: Uuid,
+    user_id: Uuid,
+    id: Uuid,
+) -> Result<ServiceAccount, ServiceError> {
+    require_owner(pool, org_id, user_id, "revoke service accounts").await?;
+
+    let account = sqlx::query_as::<_, ServiceAccount>(
+        "UPDATE service_accounts SET revoked_at = COALESCE(revoked_at, CURRENT_TIMESTAMP)
+         WHERE id = $1 AND organization_id = $2
+         RETURNING *",
+    )
+    .bind(id)
+    .bind(org_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ServiceError::NotFound("Service account not found".to_string()))?;
+
+    service_account::invalidate(id);
+
+    tracing::warn!(
+        target: "audit",
+        action = "service_account.revoke",
+        org_id = %org_id,
+        service_account_id = %id,
+        user_id = %user_id,
+        "Service account revoked"
+    );
+    activity::record(
+        pool,
+        org_id,
+        user_id,
+        "service_account.revoke",
+        Some(&id.to_string()),
+    )
+    .await;
+
+    Ok(account)
+}
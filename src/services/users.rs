@@ -0,0 +1,294 @@
+//! User registration
+//!
+//! An email belongs to at most one live account. Accounts an admin
+//! deactivated (`is_active = false`) are live: their email can't be
+//! registered again, and whoever tries is told to contact support rather
+//! than that the email is taken. Deleted accounts are anonymized tombstones
+//! (`deleted_at` set) that no longer hold their email, so it can be
+//! registered again; the audit log records when that happens.
+
+use bcrypt::{hash, DEFAULT_COST};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{organizations, validation_error, ServiceError};
+use crate::database::{self, DbPool};
+use crate::models::{CreateUserRequest, OrganizationRole, TierType, User};
+
+/// Domains of throwaway mailboxes that can't register
+const DISPOSABLE_DOMAINS: [&str; 5] = [
+    "tempmail.com",
+    "throwaway.email",
+    "guerrillamail.com",
+    "10minutemail.com",
+    "mailinator.com",
+];
+
+/// State of an account holding (or having held) an email
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountState {
+    Active,
+    /// Disabled by an admin, data kept: support can re-enable it
+    Deactivated,
+    /// Anonymized tombstone of a deleted account
+    Deleted,
+}
+
+impl AccountState {
+    fn of(is_active: bool, deleted_at: Option<DateTime<Utc>>) -> Self {
+        match (deleted_at, is_active) {
+            (Some(_), _) => AccountState::Deleted,
+            (None, true) => AccountState::Active,
+            (None, false) => AccountState::Deactivated,
+        }
+    }
+}
+
+/// Whether an email held by accounts in these states can be registered
+fn check_email_available(states: &[AccountState]) -> Result<(), ServiceError> {
+    if states.contains(&AccountState::Active) {
+        return Err(ServiceError::Conflict(
+            "Email already registered".to_string(),
+        ));
+    }
+    if states.contains(&AccountState::Deactivated) {
+        return Err(ServiceError::Forbidden(
+            "This account has been deactivated. Contact support to restore it.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// A new user and their personal organization
+#[derive(Debug, Clone)]
+pub struct RegisteredUser {
+    pub user: User,
+    pub org_id: Uuid,
+}
+
+/// Register a user with a personal organization they own
+pub async fn register(
+    pool: &DbPool,
+    req: &CreateUserRequest,
+) -> Result<RegisteredUser, ServiceError> {
+    req.validate().map_err(|e| validation_error(&e))?;
+
+    let email_lower = req.email.to_lowercase();
+    if DISPOSABLE_DOMAINS
+        .iter()
+        .any(|domain| email_lower.ends_with(domain))
+    {
+        return Err(ServiceError::Invalid(
+            "Disposable email addresses are not allowed".to_string(),
+        ));
+    }
+
+    let previous: Vec<AccountState> = sqlx::query_as::<_, (bool, Option<DateTime<Utc>>)>(
+        "SELECT is_active, deleted_at FROM users WHERE email = $1",
+    )
+    .bind(&req.email)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|(is_active, deleted_at)| AccountState::of(is_active, deleted_at))
+    .collect();
+    check_email_available(&previous)?;
+
+    let password_hash = hash(&req.password, DEFAULT_COST)
+        .map_err(|e| ServiceError::Internal(format!("Password hashing failed: {}", e)))?;
+
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+
+    let user = sqlx::query_as::<_, User>(
+        "INSERT INTO users (email, name, password_hash, is_active, created_at, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING *",
+    )
+    .bind(&req.email)
+    .bind(&req.name)
+    .bind(&password_hash)
+    .bind(true)
+    .bind(database::timestamp(now))
+    .bind(database::timestamp(now))
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| ServiceError::Internal(format!("Failed to create user: {}", e)))?;
+
+    let org_id = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO organizations (id, name, owner_id, tier, is_active, created_at, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         RETURNING id",
+    )
+    .bind(Uuid::now_v7())
+    .bind(format!("{}'s Organization", req.email))
+    .bind(user.id)
+    .bind(TierType::Free)
+    .bind(true)
+    .bind(database::timestamp(now))
+    .bind(database::timestamp(now))
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| ServiceError::Internal(format!("Failed to create organization: {}", e)))?;
+
+    organizations::add_member(&mut tx, org_id, user.id, OrganizationRole::Owner).await?;
+
+    // The personal organization starts out as the selected one
+    let user = sqlx::query_as::<_, User>(
+        "UPDATE users SET last_selected_org_id = $1 WHERE id = $2 RETURNING *",
+    )
+    .bind(org_id)
+    .bind(user.id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    if !previous.is_empty() {
+        tracing::info!(
+            target: "audit",
+            action = "user.reregister",
+            user_id = %user.id,
+            email = %user.email,
+            deleted_accounts = previous.len(),
+            "Email of a deleted account registered again"
+        );
+    }
+
+    Ok(RegisteredUser { user, org_id })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use serial_test::serial;
+
+    fn request(email: &str) -> CreateUserRequest {
+        CreateUserRequest {
+            email: email.to_string(),
+            password: "password123".to_string(),
+            name: None,
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_register_creates_selected_personal_org() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+
+        let registered = register(pool, &request("test@example.com")).await.unwrap();
+        assert_eq!(
+            registered.user.last_selected_org_id,
+            Some(registered.org_id)
+        );
+
+        let org = organizations::membership(pool, registered.org_id, registered.user.id)
+            .await
+            .unwrap();
+        assert_eq!(org.name, "test@example.com's Organization");
+        assert_eq!(org.role, OrganizationRole::Owner);
+
+        assert!(matches!(
+            register(pool, &request("test@example.com")).await,
+            Err(ServiceError::Conflict(_))
+        ));
+
+        cleanup_db().await;
+    }
+
+    #[test]
+    fn test_email_availability_by_account_state() {
+        use AccountState::*;
+
+        let now = Utc::now();
+        assert_eq!(AccountState::of(true, None), Active);
+        assert_eq!(AccountState::of(false, None), Deactivated);
+        assert_eq!(AccountState::of(false, Some(now)), Deleted);
+
+        assert!(check_email_available(&[]).is_ok());
+        assert!(check_email_available(&[Deleted, Deleted]).is_ok());
+        assert!(matches!(
+            check_email_available(&[Deleted, Active]),
+            Err(ServiceError::Conflict(_))
+        ));
+        assert!(matches!(
+            check_email_available(&[Deleted, Deactivated]),
+            Err(ServiceError::Forbidden(msg)) if msg.contains("Contact support")
+        ));
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_register_by_previous_account_state() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+
+        // Deactivated: still holds the email
+        let deactivated = register(pool, &request("deactivated@example.com"))
+            .await
+            .unwrap();
+        sqlx::query("UPDATE users SET is_active = false WHERE id = $1")
+            .bind(deactivated.user.id)
+            .execute(pool)
+            .await
+            .unwrap();
+        assert!(matches!(
+            register(pool, &request("deactivated@example.com")).await,
+            Err(ServiceError::Forbidden(_))
+        ));
+
+        // Deleted: the email is free again, once
+        let deleted = register(pool, &request("deleted@example.com"))
+            .await
+            .unwrap();
+        sqlx::query(
+            "UPDATE users SET is_active = false, name = NULL, password_hash = NULL, deleted_at = $1
+             WHERE id = $2",
+        )
+        .bind(database::timestamp(Utc::now()))
+        .bind(deleted.user.id)
+        .execute(pool)
+        .await
+        .unwrap();
+        let again = register(pool, &request("deleted@example.com"))
+            .await
+            .unwrap();
+        assert_ne!(again.user.id, deleted.user.id);
+        assert!(matches!(
+            register(pool, &request("deleted@example.com")).await,
+            Err(ServiceError::Conflict(_))
+        ));
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_register_rejects_invalid_input() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+
+        for req in [
+            request("not-an-email"),
+            request("someone@mailinator.com"),
+            CreateUserRequest {
+                password: "short".to_string(),
+                ..request("test@example.com")
+            },
+        ] {
+            assert!(matches!(
+                register(pool, &req).await,
+                Err(ServiceError::Invalid(_))
+            ));
+        }
+
+        cleanup_db().await;
+    }
+}
@@ -0,0 +1,179 @@
+//! What a running instance is made of
+//!
+//! `AppState` owns the database pool, the Redis connection, the embedding
+//! cache, the token validator, the usage buffer and the model. `bootstrap`
+//! builds it once per process (main, the test harness, `smally-loadtest
+//! --local`); callers racing it wait for the first to finish instead of
+//! building their own. Routers get it through axum's `State`.
+//!
+//! `AppState::build` makes an instance that isn't the process-wide one, so
+//! a test can run two side by side. Code that still reaches for the
+//! process-wide one through `database::get_db`, `cache::get_cache`,
+//! `auth::get_validator`, `billing::get_usage_buffer`,
+//! `redis_util::get_connection` or `inference::get_model` sees only the
+//! state `bootstrap` built; those shims go away as handlers move to
+//! `State<AppState>`.
+
+use anyhow::Result;
+#[cfg(feature = "inference")]
+use parking_lot::RwLock;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use tracing::info;
+
+use crate::auth::TokenValidator;
+use crate::billing::UsageBuffer;
+use crate::cache::EmbeddingCache;
+use crate::config;
+use crate::database::{self, DbPool};
+#[cfg(feature = "inference")]
+use crate::inference::{self, EmbeddingModel};
+use crate::redis_util::RedisConnection;
+
+static STATE: OnceCell<AppState> = OnceCell::const_new();
+
+/// Which optional parts `AppState::build` starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Components {
+    /// Load the embedding model (`inference` builds only)
+    pub model: bool,
+    /// Replay the usage outbox and flush the usage buffer periodically
+    /// (`USAGE_FLUSH_INTERVAL`) and when it fills up. Without it usage is
+    /// buffered until someone calls `flush`.
+    pub usage_flush: bool,
+}
+
+impl Components {
+    /// Everything, for the server
+    pub const SERVER: Components = Components {
+        model: true,
+        usage_flush: true,
+    };
+
+    /// The model without the flush job: tests flush when they check usage
+    pub const TESTS: Components = Components {
+        model: true,
+        usage_flush: false,
+    };
+}
+
+/// Shared services of an instance (cheap to clone)
+#[derive(Clone)]
+pub struct AppState {
+    pub db: DbPool,
+    pub redis: RedisConnection,
+    pub cache: Arc<EmbeddingCache>,
+    pub validator: Arc<TokenValidator>,
+    pub usage: Arc<UsageBuffer>,
+    /// None when the model isn't loaded: embedding routes answer 503
+    /// `model_unavailable` and the control plane keeps serving
+    #[cfg(feature = "inference")]
+    pub model: Option<Arc<RwLock<EmbeddingModel>>>,
+}
+
+impl AppState {
+    /// Connect and load the `components`. The result is independent of
+    /// the process-wide state and of other instances built here.
+    pub async fn build(components: Components) -> Result<Self> {
+        let db = database::connect().await?;
+        let redis = RedisConnection::connect(config::get_settings()).await?;
+        let cache = Arc::new(EmbeddingCache::new(redis.clone()));
+        let validator = Arc::new(
+            TokenValidator::from_settings(redis.clone())
+                .await?
+                .with_key_rows(db.clone()),
+        );
+        info!("Token validator initialized");
+
+        let usage = Arc::new(UsageBuffer::from_settings(db.clone())?);
+        if components.usage_flush {
+            usage.replay_outbox().await?;
+            usage.clone().start_flush_task();
+            info!("Usage buffer initialized with 5-second flush interval");
+        }
+
+        Ok(AppState {
+            db,
+            redis,
+            cache,
+            validator,
+            usage,
+            #[cfg(feature = "inference")]
+            model: components.model.then(load_model).flatten(),
+        })
+    }
+}
+
+/// Load the model, or record why it can't serve
+#[cfg(feature = "inference")]
+fn load_model() -> Option<Arc<RwLock<EmbeddingModel>>> {
+    let settings = config::get_settings();
+    info!("Loading ONNX model...");
+    match EmbeddingModel::new() {
+        Ok(model) => {
+            info!("Model loaded: {}", settings.model_name);
+            Some(Arc::new(RwLock::new(model)))
+        }
+        Err(e) => {
+            tracing::error!("Failed to load model {}: {:#}", settings.model_name, e);
+            inference::mark_unavailable(format!("failed to load: {:#}", e));
+            None
+        }
+    }
+}
+
+/// Build the process-wide state, once: later and concurrent calls get the
+/// same state (the `components` of the first call apply). A failed build
+/// isn't kept, so the next call tries again.
+pub async fn bootstrap(components: Components) -> Result<AppState> {
+    STATE
+        .get_or_try_init(|| AppState::build(components))
+        .await
+        .cloned()
+}
+
+/// The process-wide state
+///
+/// # Panics
+///
+/// Before `bootstrap` has finished
+pub fn global() -> &'static AppState {
+    STATE
+        .get()
+        .expect("App state not initialized (call state::bootstrap first)")
+}
+
+/// The process-wide state, if `bootstrap` has finished
+pub fn try_global() -> Option<&'static AppState> {
+    STATE.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::helpers::setup;
+    use serial_test::serial;
+
+    #[tokio::test]
+    #[serial]
+    async fn test_bootstrap_is_shared_and_build_is_isolated() {
+        let shared = setup().await;
+        let again = bootstrap(Components::TESTS).await.unwrap();
+        assert!(Arc::ptr_eq(&shared.cache, &again.cache));
+        assert!(Arc::ptr_eq(&shared.usage, &global().usage));
+
+        // A second instance shares nothing with the first
+        let other = AppState::build(Components {
+            model: false,
+            usage_flush: false,
+        })
+        .await
+        .unwrap();
+        assert!(!Arc::ptr_eq(&shared.cache, &other.cache));
+        assert!(!Arc::ptr_eq(&shared.validator, &other.validator));
+        assert!(!Arc::ptr_eq(&shared.usage, &other.usage));
+        #[cfg(feature = "inference")]
+        assert!(other.model.is_none());
+        assert!(other.usage.flush().await.is_ok());
+    }
+}
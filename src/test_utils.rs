@@ -1,21 +1,14 @@
 #[cfg(test)]
 pub mod helpers {
-    use crate::{auth, billing, cache, config, database, inference};
+    use crate::state::{self, AppState, Components};
+    use crate::{config, database};
     use std::sync::Once;
 
     static INIT: Once = Once::new();
 
-    /// Initialize the test environment once (database, Redis, model)
-    /// This runs only once for all tests - subsequent calls are no-ops
-    pub async fn setup() {
-        use std::sync::atomic::{AtomicBool, Ordering};
-        static SETUP_COMPLETE: AtomicBool = AtomicBool::new(false);
-
-        // Only run setup once across all tests
-        if SETUP_COMPLETE.swap(true, Ordering::SeqCst) {
-            return; // Already initialized
-        }
-
+    /// Initialize the test environment once (database, Redis, model) and
+    /// return it. Tests calling this concurrently wait for the first.
+    pub async fn setup() -> AppState {
         INIT.call_once(|| {
             // Load test environment variables
             dotenvy::from_filename(".env").ok();
@@ -28,34 +21,12 @@ pub mod helpers {
                 .ok();
         });
 
-        // Initialize services only once
-        // All these functions now handle re-initialization gracefully
-
-        // Database pool (no migrations in test mode)
-        database::init_db()
-            .await
-            .expect("Failed to initialize database");
-
-        // Model
-        inference::init_model().expect("Failed to initialize model");
-
-        // Redis cache
-        cache::init_cache()
-            .await
-            .expect("Failed to initialize cache");
-
-        // Billing Redis
-        billing::init_redis()
-            .await
-            .expect("Failed to initialize billing redis");
-
-        // Token validator
-        auth::init_token_validator()
+        // Database pool (no migrations in test mode), Redis, cache, token
+        // validator and model. The usage buffer isn't flushed in the
+        // background; tests checking usage flush it themselves.
+        state::bootstrap(Components::TESTS)
             .await
-            .expect("Failed to initialize token validator");
-
-        // Note: Usage buffer is NOT initialized in tests to avoid connection pool issues
-        // Tests don't record usage metrics anyway
+            .expect("Failed to initialize the test environment")
     }
 
     /// Clean up the test database
@@ -63,7 +34,14 @@ pub mod helpers {
         let pool = database::get_db();
 
         // Clean tables in correct order (respecting foreign keys)
-        sqlx::query("DELETE FROM usage").execute(pool).await.ok();
+        sqlx::query("DELETE FROM usage_events")
+            .execute(pool)
+            .await
+            .ok();
+        sqlx::query("DELETE FROM api_request_log")
+            .execute(pool)
+            .await
+            .ok();
         sqlx::query("DELETE FROM api_keys").execute(pool).await.ok();
         sqlx::query("DELETE FROM organization_members")
             .execute(pool)
@@ -76,96 +54,15 @@ pub mod helpers {
         sqlx::query("DELETE FROM users").execute(pool).await.ok();
     }
 
-    /// Create a test user and return (user_id, session_token, org_id)
-    pub async fn create_test_user(email: &str, password: &str) -> (i64, String, i64) {
-        use crate::auth::session::create_session_token;
-        use crate::models::{TierType, User};
-        use bcrypt::{hash, DEFAULT_COST};
-        use chrono::Utc;
-
-        let pool = database::get_db();
-
-        let password_hash = hash(password, DEFAULT_COST).expect("Failed to hash password");
-
-        let user = sqlx::query_as::<_, User>(
-            "INSERT INTO users (email, name, password_hash, tier, is_active, created_at, updated_at)
-             VALUES ($1, $2, $3, $4, $5, $6, $7)
-             RETURNING *",
-        )
-        .bind(email)
-        .bind("Test User")
-        .bind(&password_hash)
-        .bind(TierType::Free)
-        .bind(true)
-        .bind(Utc::now().naive_utc())
-        .bind(Utc::now().naive_utc())
-        .fetch_one(pool)
-        .await
-        .expect("Failed to create user");
-
-        // Create personal organization
-        let org_name = format!("{}'s Organization", email);
-
-        let org_id = sqlx::query_scalar::<_, i64>(
-            "INSERT INTO organizations (name, owner_id, tier, is_active, created_at, updated_at)
-             VALUES ($1, $2, $3, $4, $5, $6)
-             RETURNING id",
-        )
-        .bind(&org_name)
-        .bind(user.id)
-        .bind(TierType::Free)
-        .bind(true)
-        .bind(Utc::now().naive_utc())
-        .bind(Utc::now().naive_utc())
-        .fetch_one(pool)
-        .await
-        .expect("Failed to create organization");
-
-        // Add user as owner
-        sqlx::query(
-            "INSERT INTO organization_members (organization_id, user_id, role, created_at)
-             VALUES ($1, $2, $3, $4)",
-        )
-        .bind(org_id)
-        .bind(user.id)
-        .bind("owner")
-        .bind(Utc::now().naive_utc())
-        .execute(pool)
-        .await
-        .expect("Failed to add organization member");
-
-        let token =
-            create_session_token(user.id, &user.email).expect("Failed to create session token");
-
-        (user.id, token, org_id)
-    }
-
-    /// Create a test CWT token for API access
-    pub async fn create_test_api_token(org_id: i64, tier: crate::models::TierType) -> String {
-        use crate::auth::{sign_token_direct, TokenData};
+    /// Create a test admin token for UI/admin access
+    pub fn create_test_admin_token() -> String {
+        use crate::auth::binding::TokenBinding;
+        use crate::auth::sign_admin_token;
         use chrono::Utc;
-        use uuid::Uuid;
 
         let settings = config::get_settings();
-        let pool = database::get_db();
-
-        let key_id = Uuid::now_v7();
-
-        // Create API key in database
-        sqlx::query(
-            "INSERT INTO api_keys (organization_id, key_id, name, is_active, created_at)
-             VALUES ($1, $2, $3, $4, $5)",
-        )
-        .bind(org_id)
-        .bind(key_id)
-        .bind("Test API Key")
-        .bind(true)
-        .bind(Utc::now().naive_utc())
-        .execute(pool)
-        .await
-        .expect("Failed to create API key");
 
-        // Generate CWT token
+        // Generate admin token
         let private_key_bytes =
             hex::decode(&settings.token_private_key).expect("Invalid private key");
 
@@ -175,48 +72,113 @@ pub mod helpers {
                 .expect("Invalid private key length"),
         );
 
-        let expiration = Utc::now() + chrono::Duration::days(365);
-        let (max_tokens, monthly_quota) = match tier {
-            crate::models::TierType::Free => (settings.max_tokens, settings.free_tier_limit),
-            crate::models::TierType::Pro => (settings.max_tokens, settings.pro_tier_limit),
-            crate::models::TierType::Scale => (settings.max_tokens, settings.scale_tier_limit),
-        };
-
-        let token_data = TokenData {
-            expiration: expiration.timestamp(),
-            user_id: 1, // For backward compatibility
-            key_id,
-            tier,
-            max_tokens: max_tokens as i32,
-            monthly_quota,
-        };
-
-        let token = sign_token_direct(&token_data, &signing_key).expect("Failed to sign token");
-
-        format!("{}{}", settings.api_key_prefix, token)
+        let expiration = (Utc::now() + chrono::Duration::days(1)).timestamp();
+        let token = sign_admin_token(
+            uuid::Uuid::now_v7(),
+            "ui",
+            expiration,
+            &signing_key,
+            &TokenBinding::from_settings(),
+        )
+        .expect("Failed to sign admin token");
+
+        format!("admin_{}", token)
     }
+}
 
-    /// Create a test admin token for UI/admin access
-    pub fn create_test_admin_token() -> String {
-        use crate::auth::sign_admin_token;
-        use chrono::Utc;
+/// Typed test fixtures built through the same service code the handlers use
+/// (the control plane's, so they need `control-plane`)
+#[cfg(all(test, feature = "control-plane"))]
+pub mod factory {
+    use crate::auth::session::{create_session_token, SESSION_DURATION_SHORT};
+    use crate::database;
+    use crate::models::{CreateAPIKeyRequest, CreateUserRequest};
+    use crate::services;
+    use uuid::Uuid;
+
+    /// Password every factory user is registered with
+    pub const PASSWORD: &str = "password123";
+
+    /// A registered user, signed in, owning a personal organization
+    #[derive(Debug, Clone)]
+    pub struct TestUser {
+        pub id: Uuid,
+        pub email: String,
+        pub password: String,
+        pub session_token: String,
+        pub org_id: Uuid,
+    }
 
-        let settings = config::get_settings();
+    /// An API key issued in a user's organization
+    #[derive(Debug, Clone)]
+    pub struct TestKey {
+        pub id: Uuid,
+        pub key_id: Uuid,
+        pub org_id: Uuid,
+        pub token: String,
+    }
 
-        // Generate admin token
-        let private_key_bytes =
-            hex::decode(&settings.token_private_key).expect("Invalid private key");
+    /// Register a user the way `/v1/auth/register` does
+    pub async fn user(email: &str) -> TestUser {
+        let registered = services::users::register(
+            database::get_db(),
+            &CreateUserRequest {
+                email: email.to_string(),
+                password: PASSWORD.to_string(),
+                name: Some("Test User".to_string()),
+            },
+        )
+        .await
+        .expect("Failed to register test user");
 
-        let signing_key = ed25519_dalek::SigningKey::from_bytes(
-            &private_key_bytes[..]
-                .try_into()
-                .expect("Invalid private key length"),
-        );
+        let session_token = create_session_token(
+            registered.user.id,
+            &registered.user.email,
+            SESSION_DURATION_SHORT,
+        )
+        .expect("Failed to create session token");
+
+        TestUser {
+            id: registered.user.id,
+            email: registered.user.email,
+            password: PASSWORD.to_string(),
+            session_token,
+            org_id: registered.org_id,
+        }
+    }
 
-        let expiration = (Utc::now() + chrono::Duration::days(365)).timestamp();
-        let token =
-            sign_admin_token("ui", expiration, &signing_key).expect("Failed to sign admin token");
+    /// Key request with server defaults for everything but the name
+    pub fn key_request(name: &str) -> CreateAPIKeyRequest {
+        CreateAPIKeyRequest {
+            name: name.to_string(),
+            tier: None,
+            max_tokens: None,
+            max_concurrency: None,
+            scopes: None,
+            models: None,
+            require_request_signing: false,
+            opaque: false,
+            api_version: None,
+            strict_validation: false,
+        }
+    }
 
-        format!("admin_{}", token)
+    /// Issue a default CWT key in the user's personal organization
+    pub async fn api_key(owner: &TestUser) -> TestKey {
+        api_key_with(owner, key_request("Test API Key")).await
+    }
+
+    /// Issue a key from an explicit request in the user's personal organization
+    pub async fn api_key_with(owner: &TestUser, req: CreateAPIKeyRequest) -> TestKey {
+        let issued = services::api_keys::create(database::get_db(), owner.org_id, owner.id, &req)
+            .await
+            .expect("Failed to create test API key");
+
+        TestKey {
+            id: issued.api_key.id,
+            key_id: issued.api_key.key_id,
+            org_id: issued.api_key.organization_id,
+            token: issued.token,
+        }
     }
 }
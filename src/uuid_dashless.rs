@@ -6,6 +6,10 @@
 //! Example:
 //! - With dashes: `550e8400-e29b-41d4-a716-446655440000`
 //! - Without dashes: `550e8400e29b41d4a716446655440000`
+//!
+//! The dashless form is canonical for both the web UI and the JSON API. Input
+//! is accepted in either form. Plain `Uuid` fields in API DTOs use
+//! `#[serde(with = "crate::uuid_dashless::simple")]`.
 
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
@@ -31,11 +35,10 @@ impl DashlessUuid {
         self.0.simple().to_string()
     }
 
-    /// Parse from dashless string
+    /// Parse from dashless string (hyphenated input is accepted too)
     pub fn from_dashless_string(s: &str) -> Result<Self, uuid::Error> {
-        // If the string has dashes, remove them
-        let cleaned = s.replace('-', "");
-        let uuid = Uuid::parse_str(&cleaned)?;
+        // Uuid::parse_str accepts both the simple and the hyphenated form
+        let uuid = Uuid::parse_str(s)?;
         Ok(Self(uuid))
     }
 }
@@ -89,6 +92,45 @@ impl<'de> Deserialize<'de> for DashlessUuid {
 
 // Axum automatically uses FromStr for path extraction, so no custom implementation needed
 
+/// Serde helpers for plain `Uuid` fields: serialize dashless, accept both forms
+pub mod simple {
+    use super::*;
+
+    pub fn serialize<S>(uuid: &Uuid, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        DashlessUuid(*uuid).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Uuid, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        DashlessUuid::deserialize(deserializer).map(DashlessUuid::into_inner)
+    }
+
+    /// Same as the parent module, for `Option<Uuid>` fields
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S>(uuid: &Option<Uuid>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            uuid.map(DashlessUuid).serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Uuid>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Option::<DashlessUuid>::deserialize(deserializer)
+                .map(|uuid| uuid.map(DashlessUuid::into_inner))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -98,13 +140,17 @@ mod tests {
         let uuid = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
         let dashless = DashlessUuid::new(uuid);
 
-        assert_eq!(dashless.to_dashless_string(), "550e8400e29b41d4a716446655440000");
+        assert_eq!(
+            dashless.to_dashless_string(),
+            "550e8400e29b41d4a716446655440000"
+        );
         assert_eq!(dashless.to_string(), "550e8400e29b41d4a716446655440000");
     }
 
     #[test]
     fn test_parse_dashless() {
-        let dashless = DashlessUuid::from_dashless_string("550e8400e29b41d4a716446655440000").unwrap();
+        let dashless =
+            DashlessUuid::from_dashless_string("550e8400e29b41d4a716446655440000").unwrap();
         let expected = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
 
         assert_eq!(dashless.into_inner(), expected);
@@ -113,7 +159,8 @@ mod tests {
     #[test]
     fn test_parse_with_dashes() {
         // Should also accept dashed format for backward compatibility
-        let dashless = DashlessUuid::from_dashless_string("550e8400-e29b-41d4-a716-446655440000").unwrap();
+        let dashless =
+            DashlessUuid::from_dashless_string("550e8400-e29b-41d4-a716-446655440000").unwrap();
         let expected = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
 
         assert_eq!(dashless.into_inner(), expected);
@@ -136,4 +183,52 @@ mod tests {
 
         assert_eq!(dashless.into_inner(), expected);
     }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Dto {
+        #[serde(with = "simple")]
+        id: Uuid,
+        #[serde(with = "simple::option")]
+        parent: Option<Uuid>,
+    }
+
+    #[test]
+    fn test_simple_helper_serializes_dashless() {
+        let id = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
+        let json = serde_json::to_string(&Dto {
+            id,
+            parent: Some(id),
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            r#"{"id":"550e8400e29b41d4a716446655440000","parent":"550e8400e29b41d4a716446655440000"}"#
+        );
+
+        let json = serde_json::to_string(&Dto { id, parent: None }).unwrap();
+        assert_eq!(
+            json,
+            r#"{"id":"550e8400e29b41d4a716446655440000","parent":null}"#
+        );
+    }
+
+    #[test]
+    fn test_simple_helper_accepts_both_forms() {
+        let expected = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
+        for input in [
+            r#"{"id":"550e8400e29b41d4a716446655440000","parent":null}"#,
+            r#"{"id":"550e8400-e29b-41d4-a716-446655440000","parent":null}"#,
+        ] {
+            let dto: Dto = serde_json::from_str(input).unwrap();
+            assert_eq!(dto.id, expected);
+        }
+    }
+
+    #[test]
+    fn test_rejects_invalid() {
+        assert!("not-a-uuid".parse::<DashlessUuid>().is_err());
+        assert!("550e8400e29b41d4a71644665544000"
+            .parse::<DashlessUuid>()
+            .is_err());
+    }
 }
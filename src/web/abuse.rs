@@ -0,0 +1,145 @@
+//! Abuse protection for the web login and registration forms.
+//!
+//! - Per-IP fixed-window throttling (in-process, per instance; see
+//!   `api::ip_limit`)
+//! - A pluggable registration challenge (CAPTCHA, proof-of-work, ...) that is
+//!   only demanded once an IP starts hammering /register. The default
+//!   implementation accepts everything.
+
+use axum::{
+    async_trait,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use maud::{html, Markup};
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::components::layout;
+pub use crate::api::ip_limit::{ClientIp, IpRateLimiter};
+
+/// Login attempts allowed per IP per window
+const LOGIN_LIMIT: u32 = 10;
+const LOGIN_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// Registration attempts allowed per IP per window
+const REGISTER_LIMIT: u32 = 5;
+const REGISTER_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Registration attempts per window after which the challenge is required
+const CHALLENGE_THRESHOLD: u32 = 3;
+
+pub static LOGIN_LIMITER: Lazy<IpRateLimiter> =
+    Lazy::new(|| IpRateLimiter::new(LOGIN_LIMIT, LOGIN_WINDOW));
+
+pub static REGISTER_LIMITER: Lazy<IpRateLimiter> =
+    Lazy::new(|| IpRateLimiter::new(REGISTER_LIMIT, REGISTER_WINDOW));
+
+/// Record an attempt for `ip`; returns the attempt number (0 when the IP is
+/// unknown) or a 429 page once the limit is exceeded
+#[allow(clippy::result_large_err)] // handlers return Result<_, Response>
+pub fn throttle(
+    limiter: &IpRateLimiter,
+    ip: Option<IpAddr>,
+    back_href: &str,
+) -> Result<u32, Response> {
+    let Some(ip) = ip else {
+        return Ok(0);
+    };
+
+    limiter.hit(ip, Instant::now()).map_err(|retry_after| {
+        tracing::warn!(target: "audit", %ip, path = back_href, "Web auth attempt throttled");
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            layout::base(
+                "Too Many Attempts",
+                html! {
+                    div class="min-h-screen flex items-center justify-center bg-gray-50" {
+                        div class="max-w-md w-full" {
+                            (layout::alert("Too many attempts. Please wait a few minutes and try again.", "error"))
+                            a href=(back_href) class="text-primary hover:text-blue-500" {
+                                "← Go back"
+                            }
+                        }
+                    }
+                },
+            ),
+        )
+            .into_response()
+    })
+}
+
+/// Whether registration attempt number `attempt` must pass the challenge
+pub fn challenge_required(attempt: u32) -> bool {
+    attempt > CHALLENGE_THRESHOLD
+}
+
+/// Extra verification for suspicious registrations (CAPTCHA, proof-of-work)
+#[async_trait]
+pub trait RegistrationChallenge: Send + Sync {
+    /// Form markup rendered inside the registration form
+    fn widget(&self) -> Markup {
+        html! {}
+    }
+
+    /// Verify the `challenge-response` form field
+    async fn verify(&self, response: Option<&str>, ip: Option<IpAddr>) -> bool;
+}
+
+/// Default challenge: always passes
+pub struct NoopChallenge;
+
+#[async_trait]
+impl RegistrationChallenge for NoopChallenge {
+    async fn verify(&self, _response: Option<&str>, _ip: Option<IpAddr>) -> bool {
+        true
+    }
+}
+
+static CHALLENGE: Lazy<RwLock<Arc<dyn RegistrationChallenge>>> =
+    Lazy::new(|| RwLock::new(Arc::new(NoopChallenge)));
+
+/// Install a registration challenge (e.g. a CAPTCHA provider)
+pub fn set_registration_challenge(challenge: Arc<dyn RegistrationChallenge>) {
+    *CHALLENGE.write() = challenge;
+}
+
+/// Currently installed registration challenge
+pub fn registration_challenge() -> Arc<dyn RegistrationChallenge> {
+    CHALLENGE.read().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(last: u8) -> IpAddr {
+        IpAddr::from([203, 0, 113, last])
+    }
+
+    #[test]
+    fn test_throttle_without_ip_is_noop() {
+        let limiter = IpRateLimiter::new(0, Duration::from_secs(60));
+        assert_eq!(throttle(&limiter, None, "/login").unwrap(), 0);
+
+        let response = throttle(&limiter, Some(ip(1)), "/login").unwrap_err();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(response.headers().contains_key(header::RETRY_AFTER));
+    }
+
+    #[test]
+    fn test_challenge_threshold() {
+        assert!(!challenge_required(0));
+        assert!(!challenge_required(CHALLENGE_THRESHOLD));
+        assert!(challenge_required(CHALLENGE_THRESHOLD + 1));
+    }
+
+    #[tokio::test]
+    async fn test_noop_challenge_passes() {
+        assert!(NoopChallenge.verify(None, None).await);
+    }
+}
@@ -5,40 +5,45 @@ use axum::{
 };
 use maud::{html, Markup};
 use serde::Deserialize;
+use validator::Validate;
 
+use crate::api::snippets;
 use crate::auth::session::SessionCookie;
-use crate::auth::{sign_token_direct, TokenData};
 use crate::config;
 use crate::database;
-use crate::models::{APIKey, OrganizationRole, TierType};
+use crate::models::{APIKey, CreateAPIKeyRequest, OrganizationRole, TierType};
+use crate::residency;
+use crate::services::key_reissue::Reissue;
+use crate::services::{self, ServiceError};
 use crate::uuid_dashless::DashlessUuid;
-use chrono::Utc;
 use uuid::Uuid;
 
 use super::components::layout;
-use super::organizations::OrganizationsQuery;
-
-/// Organization with user's role (for access check)
-#[derive(Debug, sqlx::FromRow)]
-struct OrganizationWithRole {
-    id: Uuid,
-    name: String,
-    tier: TierType,
-    is_active: bool,
-    role: OrganizationRole,
-}
+use super::i18n::{t, t_with};
+use super::organizations::{region_card, trial_badge, OrganizationsQuery};
 
 /// Form data for creating API key
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate)]
 pub struct CreateAPIKeyForm {
+    #[validate(custom(function = "crate::models::validate_key_name"))]
     pub name: String,
+    /// Restrict the key to one model (empty = any the organization allows)
+    #[serde(default)]
+    pub model: Option<String>,
 }
 
-/// Helper struct for org list
-#[derive(Debug, sqlx::FromRow)]
-struct OrgListItem {
-    id: Uuid,
-    name: String,
+/// Organization switcher entries other than `org_id`, oldest first
+async fn other_orgs(user_id: Uuid, org_id: Uuid) -> Result<Vec<(String, String)>, Response> {
+    let orgs = services::organizations::list_for_user(database::get_db(), user_id)
+        .await
+        .map_err(|e| super::service_error_response(e, "/organizations"))?;
+
+    Ok(orgs
+        .into_iter()
+        .rev()
+        .filter(|o| o.is_active && o.id != org_id)
+        .map(|o| (o.id.simple().to_string(), o.name))
+        .collect())
 }
 
 /// Show organization detail with API keys
@@ -51,89 +56,54 @@ pub async fn show(
     let user_id = session.user_id();
     let org_id = org_id.into_inner();
 
-    // Fetch all user's organizations for the dropdown
-    let all_orgs = sqlx::query_as::<_, OrgListItem>(
-        "SELECT o.id, o.name
-         FROM organizations o
-         INNER JOIN organization_members om ON o.id = om.organization_id
-         WHERE om.user_id = $1 AND o.is_active = true
-         ORDER BY o.created_at ASC",
-    )
-    .bind(user_id)
-    .fetch_all(pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Database error: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
-    })?;
-
     // Check user has access to this organization
-    let org = sqlx::query_as::<_, OrganizationWithRole>(
-        r#"
-        SELECT o.id, o.name, o.tier, o.is_active, om.role
-        FROM organizations o
-        INNER JOIN organization_members om ON o.id = om.organization_id
-        WHERE o.id = $1 AND om.user_id = $2
-        "#,
-    )
-    .bind(org_id)
-    .bind(user_id)
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Database error: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
-    })?
-    .ok_or_else(|| {
-        (
-            StatusCode::NOT_FOUND,
-            layout::base("Organization Not Found", html! {
-                div class="min-h-screen flex items-center justify-center bg-gray-50" {
-                    div class="max-w-md w-full" {
-                        (layout::alert("Organization not found or you don't have access", "error"))
-                        a href="/organizations" class="text-primary hover:text-blue-500" {
-                            "← Back to organizations"
+    let org = match services::organizations::membership(pool, org_id, user_id).await {
+        Ok(org) => org,
+        Err(ServiceError::Forbidden(_)) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                layout::base(
+                    t("organizations.not_found_title"),
+                    html! {
+                        div class="min-h-screen flex items-center justify-center bg-gray-50" {
+                            div class="max-w-md w-full" {
+                                (layout::alert(t("organizations.not_found"), "error"))
+                                (layout::back_link("/organizations", t("organizations.back")))
+                            }
                         }
-                    }
-                }
-            }),
-        )
-            .into_response()
-    })?;
+                    },
+                ),
+            )
+                .into_response());
+        }
+        Err(e) => return Err(super::service_error_response(e, "/organizations")),
+    };
 
-    // Fetch API keys for this organization
-    let api_keys = sqlx::query_as::<_, APIKey>(
-        "SELECT * FROM api_keys WHERE organization_id = $1 ORDER BY created_at DESC",
-    )
-    .bind(org_id)
-    .fetch_all(pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch API keys: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to fetch API keys",
-        )
-            .into_response()
-    })?;
+    let api_keys = services::api_keys::list(pool, org_id, user_id)
+        .await
+        .map_err(|e| super::service_error_response(e, "/organizations"))?;
+
+    // Reissued tokens only go to those who can manage keys
+    let reissued = if org.can_manage() {
+        services::key_reissue::pending(pool, org_id, user_id, chrono::Utc::now())
+            .await
+            .map_err(|e| super::service_error_response(e, "/organizations"))?
+    } else {
+        Vec::new()
+    };
 
     // Build organization dropdown data
     let current_org_id_simple = org_id.simple().to_string();
     let current_org_name = &org.name;
 
-    let other_orgs: Vec<(String, String)> = all_orgs
-        .iter()
-        .filter(|o| o.id != org_id)
-        .map(|o| (o.id.simple().to_string(), o.name.clone()))
-        .collect();
-
+    let other_orgs = other_orgs(user_id, org_id).await?;
     let other_orgs_refs: Vec<(&str, &str)> = other_orgs
         .iter()
         .map(|(id, name)| (id.as_str(), name.as_str()))
         .collect();
 
     Ok(layout::base(
-        &format!("{} - Organization", org.name),
+        &t_with("organizations.page_title", &[("org", &org.name)]),
         html! {
             (layout::navbar(
                 session.email(),
@@ -142,13 +112,13 @@ pub async fn show(
             ))
             (layout::container(html! {
                 // Breadcrumb
-                nav class="mb-6" {
+                nav class="mb-6" aria-label=(t("layout.breadcrumb")) {
                     ol class="flex items-center space-x-2 text-sm" {
                         li {
-                            a href="/organizations" class="text-gray-500 hover:text-gray-700" { "Organizations" }
+                            a href="/organizations" class="text-gray-500 hover:text-gray-700" { (t("organizations.title")) }
                         }
-                        li class="text-gray-400" { "/" }
-                        li class="text-gray-900 font-medium" { (org.name) }
+                        li class="text-gray-400" aria-hidden="true" { "/" }
+                        li class="text-gray-900 font-medium" aria-current="page" { (org.name) }
                     }
                 }
 
@@ -165,62 +135,79 @@ pub async fn show(
                                         TierType::Scale => "bg-purple-100 text-purple-800",
                                     };
                                     @let tier_label = match org.tier {
-                                        TierType::Free => "Free",
-                                        TierType::Pro => "Pro",
-                                        TierType::Scale => "Scale",
+                                        TierType::Free => t("tier.free"),
+                                        TierType::Pro => t("tier.pro"),
+                                        TierType::Scale => t("tier.scale"),
                                     };
                                     span class=(format!("inline-flex items-center px-2.5 py-0.5 rounded-full text-xs font-medium {}", tier_class)) {
                                         (tier_label)
                                     }
+                                    (trial_badge(&org))
                                     @let role_class = match org.role {
                                         OrganizationRole::Owner => "bg-yellow-100 text-yellow-800",
                                         OrganizationRole::Admin => "bg-green-100 text-green-800",
                                         OrganizationRole::Member => "bg-gray-100 text-gray-800",
                                     };
                                     @let role_label = match org.role {
-                                        OrganizationRole::Owner => "Owner",
-                                        OrganizationRole::Admin => "Admin",
-                                        OrganizationRole::Member => "Member",
+                                        OrganizationRole::Owner => t("role.owner"),
+                                        OrganizationRole::Admin => t("role.admin"),
+                                        OrganizationRole::Member => t("role.member"),
                                     };
                                     span class=(format!("inline-flex items-center px-2.5 py-0.5 rounded-full text-xs font-medium {}", role_class)) {
                                         (role_label)
                                     }
                                 }
                             }
+                            div class="flex items-center gap-4" {
+                                a href=(format!("/organizations/{}/members", current_org_id_simple)) class="text-sm font-medium text-primary hover:text-blue-500" {
+                                    (t("api_keys.members"))
+                                }
+                                a href=(format!("/organizations/{}/billing", current_org_id_simple)) class="text-sm font-medium text-primary hover:text-blue-500" {
+                                    (t("api_keys.billing_statements"))
+                                }
+                            }
                         }
                     }
 
+                    (reissued_banner(&reissued, org_id))
+
+                    (region_card(&org, &residency::known_regions(config::get_settings())))
+
                     // API Keys section
                     div {
                         div class="flex items-center justify-between mb-4" {
-                            h2 class="text-xl font-bold text-gray-900" { "API Keys" }
+                            h2 class="text-xl font-bold text-gray-900" { (t("api_keys.title")) }
                             button
-                                onclick="document.getElementById('create-key-modal').classList.remove('hidden')"
+                                type="button"
+                                data-modal-open="create-key-modal"
+                                aria-haspopup="dialog"
                                 class="inline-flex items-center px-4 py-2 border border-transparent shadow-sm text-sm font-medium rounded-md text-white bg-primary hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary" {
-                                svg class="mr-2 h-5 w-5" fill="none" stroke="currentColor" viewBox="0 0 24 24" {
+                                svg class="mr-2 h-5 w-5" fill="none" stroke="currentColor" viewBox="0 0 24 24" aria-hidden="true" {
                                     path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M12 4v16m8-8H4" {}
                                 }
-                                "New API Key"
+                                (t("api_keys.new"))
                             }
                         }
 
                         @if api_keys.is_empty() {
-                            (layout::card("No API Keys", html! {
+                            (layout::card(t("api_keys.empty_title"), html! {
                                 div class="text-center py-12" {
-                                    svg class="mx-auto h-12 w-12 text-gray-400" fill="none" stroke="currentColor" viewBox="0 0 24 24" {
+                                    svg class="mx-auto h-12 w-12 text-gray-400" fill="none" stroke="currentColor" viewBox="0 0 24 24" aria-hidden="true" {
                                         path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M15 7a2 2 0 012 2m4 0a6 6 0 01-7.743 5.743L11 17H9v2H7v2H4a1 1 0 01-1-1v-2.586a1 1 0 01.293-.707l5.964-5.964A6 6 0 1121 9z" {}
                                     }
                                     h3 class="mt-2 text-sm font-medium text-gray-900" {
-                                        "No API keys"
+                                        (t("api_keys.empty_heading"))
                                     }
                                     p class="mt-1 text-sm text-gray-500" {
-                                        "Create an API key to start using the API."
+                                        (t("api_keys.empty_hint"))
                                     }
                                     div class="mt-6" {
                                         button
-                                            onclick="document.getElementById('create-key-modal').classList.remove('hidden')"
+                                            type="button"
+                                            data-modal-open="create-key-modal"
+                                            aria-haspopup="dialog"
                                             class="inline-flex items-center px-4 py-2 border border-transparent shadow-sm text-sm font-medium rounded-md text-white bg-primary hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary" {
-                                            "Create API Key"
+                                            (t("api_keys.create"))
                                         }
                                     }
                                 }
@@ -238,6 +225,37 @@ pub async fn show(
     ))
 }
 
+/// Reissued tokens waiting to be retrieved, with a button each
+fn reissued_banner(reissued: &[Reissue], org_id: Uuid) -> Markup {
+    let Some(deadline) = reissued.iter().map(|r| r.retrieve_before).min() else {
+        return html! {};
+    };
+
+    html! {
+        div class="rounded-md p-4 border bg-yellow-50 border-yellow-200" role="status" {
+            h2 class="text-sm font-medium text-yellow-800" { (t("api_keys.reissued_title")) }
+            p class="mt-1 text-sm text-yellow-800" {
+                (t_with("api_keys.reissued_notice", &[("date", &deadline.format("%Y-%m-%d").to_string())]))
+            }
+            ul class="mt-3 space-y-2" {
+                @for reissue in reissued {
+                    li class="flex items-center justify-between text-sm" {
+                        span class="font-medium text-gray-900" { (reissue.name) }
+                        form action=(format!("/organizations/{}/keys/reissued/{}", org_id.simple(), reissue.id.simple())) method="POST" class="inline" {
+                            button
+                                type="submit"
+                                class="text-primary hover:text-blue-700 font-medium focus:outline-none focus:ring-2 focus:ring-primary rounded"
+                                aria-label=(t_with("api_keys.reissued_retrieve_named", &[("name", &reissue.name)])) {
+                                (t("api_keys.reissued_retrieve"))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// Render API keys table
 fn api_keys_table(api_keys: &[APIKey], org_id: uuid::Uuid) -> Markup {
     let settings = crate::config::get_settings();
@@ -246,12 +264,12 @@ fn api_keys_table(api_keys: &[APIKey], org_id: uuid::Uuid) -> Markup {
             table class="min-w-full divide-y divide-gray-200" {
                 thead class="bg-gray-50" {
                     tr {
-                        th scope="col" class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { "Name" }
-                        th scope="col" class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { "Key Prefix" }
-                        th scope="col" class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { "Status" }
-                        th scope="col" class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { "Last Used" }
-                        th scope="col" class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { "Created" }
-                        th scope="col" class="px-6 py-3 text-right text-xs font-medium text-gray-500 uppercase tracking-wider" { "Actions" }
+                        th scope="col" class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("api_keys.name")) }
+                        th scope="col" class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("api_keys.key_prefix")) }
+                        th scope="col" class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("api_keys.status")) }
+                        th scope="col" class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("api_keys.last_used")) }
+                        th scope="col" class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("api_keys.created")) }
+                        th scope="col" class="px-6 py-3 text-right text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("api_keys.actions")) }
                     }
                 }
                 tbody class="bg-white divide-y divide-gray-200" {
@@ -266,11 +284,11 @@ fn api_keys_table(api_keys: &[APIKey], org_id: uuid::Uuid) -> Markup {
                             td class="px-6 py-4 whitespace-nowrap" {
                                 @if key.is_active {
                                     span class="px-2 inline-flex text-xs leading-5 font-semibold rounded-full bg-green-100 text-green-800" {
-                                        "Active"
+                                        (t("api_keys.active"))
                                     }
                                 } @else {
                                     span class="px-2 inline-flex text-xs leading-5 font-semibold rounded-full bg-red-100 text-red-800" {
-                                        "Revoked"
+                                        (t("api_keys.revoked"))
                                     }
                                 }
                             }
@@ -278,7 +296,7 @@ fn api_keys_table(api_keys: &[APIKey], org_id: uuid::Uuid) -> Markup {
                                 @if let Some(last_used) = key.last_used_at {
                                     (last_used.format("%Y-%m-%d %H:%M").to_string())
                                 } @else {
-                                    span class="text-gray-400" { "Never" }
+                                    span class="text-gray-400" { (t("api_keys.never")) }
                                 }
                             }
                             td class="px-6 py-4 whitespace-nowrap text-sm text-gray-500" {
@@ -289,13 +307,14 @@ fn api_keys_table(api_keys: &[APIKey], org_id: uuid::Uuid) -> Markup {
                                     form action=(format!("/organizations/{}/keys/{}/revoke", org_id.simple(), key.id.simple())) method="POST" class="inline" {
                                         button
                                             type="submit"
-                                            class="text-red-600 hover:text-red-900"
-                                            onclick="return confirm('Are you sure you want to revoke this API key? This cannot be undone.')" {
-                                            "Revoke"
+                                            class="text-red-600 hover:text-red-900 focus:outline-none focus:ring-2 focus:ring-red-500 rounded"
+                                            aria-label=(t_with("api_keys.revoke_named", &[("name", &key.name)]))
+                                            onclick=(format!("return confirm({})", js_string(t("api_keys.revoke_confirm")))) {
+                                            (t("api_keys.revoke"))
                                         }
                                     }
                                 } @else {
-                                    span class="text-gray-400" { "Revoked" }
+                                    span class="text-gray-400" { (t("api_keys.revoked")) }
                                 }
                             }
                         }
@@ -318,12 +337,12 @@ fn create_api_key_modal(org_id: uuid::Uuid, auto_open: bool) -> Markup {
         div
             id="create-key-modal"
             class=(modal_class)
-            aria-labelledby="modal-title"
+            aria-labelledby="create-key-modal-title"
             role="dialog"
             aria-modal="true" {
             div class="flex items-end justify-center min-h-screen pt-4 px-4 pb-20 text-center sm:block sm:p-0" {
                 div
-                    onclick="document.getElementById('create-key-modal').classList.add('hidden')"
+                    data-modal-close
                     class="fixed inset-0 bg-gray-500 bg-opacity-75 transition-opacity"
                     aria-hidden="true" {}
 
@@ -332,25 +351,45 @@ fn create_api_key_modal(org_id: uuid::Uuid, auto_open: bool) -> Markup {
                 div class="inline-block align-bottom bg-white rounded-lg px-4 pt-5 pb-4 text-left overflow-hidden shadow-xl transform transition-all sm:my-8 sm:align-middle sm:max-w-lg sm:w-full sm:p-6" {
                     div {
                         div class="mt-3 text-center sm:mt-0 sm:text-left" {
-                            h3 class="text-lg leading-6 font-medium text-gray-900" id="modal-title" {
-                                "Create New API Key"
+                            h3 class="text-lg leading-6 font-medium text-gray-900" id="create-key-modal-title" {
+                                (t("api_keys.create_heading"))
                             }
                             div class="mt-4" {
                                 form action=(format!("/organizations/{}/keys", org_id.simple())) method="POST" {
                                     div class="space-y-4" {
                                         div {
                                             label for="name" class="block text-sm font-medium text-gray-700" {
-                                                "Key Name"
+                                                (t("api_keys.key_name"))
                                             }
                                             input
                                                 type="text"
                                                 name="name"
                                                 id="name"
                                                 required
+                                                maxlength=(crate::models::NAME_MAX_CHARS)
                                                 class="mt-1 block w-full border border-gray-300 rounded-md shadow-sm py-2 px-3 focus:outline-none focus:ring-primary focus:border-primary sm:text-sm"
-                                                placeholder="Production API Key";
-                                            p class="mt-1 text-xs text-gray-500" {
-                                                "A descriptive name to help you identify this key"
+                                                aria-describedby="name-help"
+                                                placeholder=(t("api_keys.key_name_placeholder"));
+                                            p id="name-help" class="mt-1 text-xs text-gray-500" {
+                                                (t("api_keys.key_name_help"))
+                                            }
+                                        }
+                                        div {
+                                            label for="model" class="block text-sm font-medium text-gray-700" {
+                                                (t("api_keys.model"))
+                                            }
+                                            select
+                                                name="model"
+                                                id="model"
+                                                aria-describedby="model-help"
+                                                class="mt-1 block w-full border border-gray-300 rounded-md shadow-sm py-2 px-3 focus:outline-none focus:ring-primary focus:border-primary sm:text-sm" {
+                                                option value="" selected { (t("api_keys.any_model")) }
+                                                @for id in crate::inference::catalog::available_ids() {
+                                                    option value=(id) { (id) }
+                                                }
+                                            }
+                                            p id="model-help" class="mt-1 text-xs text-gray-500" {
+                                                (t("api_keys.model_help"))
                                             }
                                         }
                                     }
@@ -358,13 +397,13 @@ fn create_api_key_modal(org_id: uuid::Uuid, auto_open: bool) -> Markup {
                                         button
                                             type="submit"
                                             class="w-full inline-flex justify-center rounded-md border border-transparent shadow-sm px-4 py-2 bg-primary text-base font-medium text-white hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary sm:col-start-2 sm:text-sm" {
-                                            "Create"
+                                            (t("common.create"))
                                         }
                                         button
                                             type="button"
-                                            onclick="document.getElementById('create-key-modal').classList.add('hidden')"
+                                            data-modal-close
                                             class="mt-3 w-full inline-flex justify-center rounded-md border border-gray-300 shadow-sm px-4 py-2 bg-white text-base font-medium text-gray-700 hover:bg-gray-50 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary sm:mt-0 sm:col-start-1 sm:text-sm" {
-                                            "Cancel"
+                                            (t("common.cancel"))
                                         }
                                     }
                                 }
@@ -387,124 +426,90 @@ pub async fn create(
     let user_id = session.user_id();
     let org_id = org_id.into_inner();
 
-    // Fetch all user's organizations for the dropdown
-    let all_orgs = sqlx::query_as::<_, OrgListItem>(
-        "SELECT o.id, o.name
-         FROM organizations o
-         INNER JOIN organization_members om ON o.id = om.organization_id
-         WHERE om.user_id = $1 AND o.is_active = true
-         ORDER BY o.created_at ASC",
+    form.validate().map_err(|e| {
+        super::form_errors_response(&e, &format!("/organizations/{}", org_id.simple()))
+    })?;
+
+    let issued = services::api_keys::create(
+        pool,
+        org_id,
+        user_id,
+        &CreateAPIKeyRequest {
+            name: form.name,
+            tier: None,
+            max_tokens: None,
+            max_concurrency: None,
+            scopes: None,
+            models: form.model.filter(|m| !m.is_empty()).map(|m| vec![m]),
+            require_request_signing: false,
+            opaque: false,
+            api_version: None,
+            strict_validation: false,
+        },
     )
-    .bind(user_id)
-    .fetch_all(pool)
     .await
     .map_err(|e| {
-        tracing::error!("Database error: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        super::service_error_response(e, &format!("/organizations/{}", org_id.simple()))
     })?;
 
-    // Check user has access to this organization and get org name
-    let org_info = sqlx::query_as::<_, OrgListItem>(
-        "SELECT o.id, o.name FROM organizations o
-         INNER JOIN organization_members om ON o.id = om.organization_id
-         WHERE o.id = $1 AND om.user_id = $2",
+    token_page(
+        &session,
+        org_id,
+        t("api_keys.created_title"),
+        t("api_keys.created_notice"),
+        &issued.token,
     )
-    .bind(org_id)
-    .bind(user_id)
-    .fetch_optional(pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Database error: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
-    })?
-    .ok_or_else(|| (StatusCode::FORBIDDEN, "Access denied").into_response())?;
-
-    // Get organization tier
-    let org_tier =
-        sqlx::query_scalar::<_, TierType>("SELECT tier FROM organizations WHERE id = $1")
-            .bind(org_id)
-            .fetch_one(pool)
-            .await
-            .map_err(|e| {
-                tracing::error!("Failed to fetch organization tier: {}", e);
-                (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "Failed to fetch organization tier",
-                )
-                    .into_response()
-            })?;
-
-    // Generate UUIDv7 for the API key
-    let key_id = Uuid::now_v7();
-
-    // Get tier limits
-    let (max_tokens, monthly_quota) = get_tier_limits(org_tier);
-
-    // Create token data
-    let token_data = TokenData {
-        org_id,
-        key_id,
-        tier: org_tier,
-        max_tokens: max_tokens as i32,
-        monthly_quota,
-    };
-
-    // Sign the token
-    let settings = crate::config::get_settings();
-    let private_key_bytes = hex::decode(&settings.token_private_key).map_err(|e| {
-        tracing::error!("Failed to decode private key: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to decode private key",
-        )
-            .into_response()
-    })?;
-    let signing_key = ed25519_dalek::SigningKey::from_bytes(
-        &private_key_bytes[..32].try_into().map_err(|e| {
-            tracing::error!("Invalid key length: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, "Invalid key length").into_response()
-        })?,
-    );
-
-    let token = sign_token_direct(&token_data, &signing_key).map_err(|e| {
-        tracing::error!("Failed to sign token: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Failed to sign token").into_response()
-    })?;
+}
 
-    let settings = crate::config::get_settings();
-    let full_token = format!("{}{}", settings.api_key_prefix, token);
+/// Retrieve a reissued token (shown once, like a new key's)
+pub async fn retrieve_reissued(
+    session: SessionCookie,
+    Path((org_id, reissue_id)): Path<(DashlessUuid, DashlessUuid)>,
+) -> Result<Response, Response> {
+    let org_id = org_id.into_inner();
 
-    // Save to database
-    sqlx::query(
-        "INSERT INTO api_keys (organization_id, key_id, name, is_active, created_at)
-         VALUES ($1, $2, $3, $4, $5)",
+    let (_, token) = services::key_reissue::retrieve(
+        database::get_db(),
+        org_id,
+        session.user_id(),
+        reissue_id.into_inner(),
+        chrono::Utc::now(),
     )
-    .bind(org_id)
-    .bind(key_id)
-    .bind(&form.name)
-    .bind(true)
-    .bind(Utc::now().naive_utc())
-    .execute(pool)
     .await
     .map_err(|e| {
-        tracing::error!("Failed to create API key: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to create API key",
-        )
-            .into_response()
+        super::service_error_response(e, &format!("/organizations/{}", org_id.simple()))
     })?;
 
+    token_page(
+        &session,
+        org_id,
+        t("api_keys.reissued_token_title"),
+        t("api_keys.reissued_token_notice"),
+        &token,
+    )
+    .await
+}
+
+/// Page showing a token the user won't see again
+async fn token_page(
+    session: &SessionCookie,
+    org_id: Uuid,
+    title: &str,
+    notice: &str,
+    full_token: &str,
+) -> Result<Response, Response> {
+    let pool = database::get_db();
+    let user_id = session.user_id();
+
     // Build organization dropdown data
     let current_org_id_simple = org_id.simple().to_string();
-    let current_org_name = &org_info.name;
-
-    let other_orgs: Vec<(String, String)> = all_orgs
-        .iter()
-        .filter(|o| o.id != org_id)
-        .map(|o| (o.id.simple().to_string(), o.name.clone()))
-        .collect();
+    let current_org_name = services::organizations::membership(pool, org_id, user_id)
+        .await
+        .map_err(|e| super::service_error_response(e, "/organizations"))?
+        .name;
 
+    let other_orgs = other_orgs(user_id, org_id).await?;
     let other_orgs_refs: Vec<(&str, &str)> = other_orgs
         .iter()
         .map(|(id, name)| (id.as_str(), name.as_str()))
@@ -513,34 +518,38 @@ pub async fn create(
     // Show the token to the user (only once!)
     Ok((
         StatusCode::OK,
-        layout::base("API Key Created", html! {
+        layout::base(title, html! {
             (layout::navbar(
                 session.email(),
-                Some((current_org_id_simple.as_str(), current_org_name)),
+                Some((current_org_id_simple.as_str(), &current_org_name)),
                 &other_orgs_refs
             ))
             (layout::container(html! {
                 div class="max-w-2xl mx-auto" {
-                    (layout::alert("API key created successfully! Copy it now - you won't be able to see it again.", "success"))
+                    (layout::alert(notice, "success"))
 
                     div class="mt-6 bg-white shadow rounded-lg p-6" {
-                        h3 class="text-lg font-medium text-gray-900 mb-4" { "Your API Key" }
+                        h3 class="text-lg font-medium text-gray-900 mb-4" { (t("api_keys.your_key")) }
                         div class="bg-gray-50 rounded-md p-4 mb-4" {
-                            code class="text-sm break-all" { (full_token) }
+                            code id="api-key-token" class="text-sm break-all" { (full_token) }
                         }
+                        // Copies the text of the code block (ui.js), so the key
+                        // never sits in an attribute. aria-live announces
+                        // "Copied!" to screen readers
                         button
-                            onclick=(format!("navigator.clipboard.writeText('{}'); this.textContent = 'Copied!'; setTimeout(() => this.textContent = 'Copy to Clipboard', 2000)", full_token))
+                            type="button"
+                            aria-live="polite"
+                            data-copy="api-key-token"
+                            data-copied=(t("api_keys.copied"))
                             class="inline-flex items-center px-4 py-2 border border-gray-300 shadow-sm text-sm font-medium rounded-md text-gray-700 bg-white hover:bg-gray-50 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary" {
-                            "Copy to Clipboard"
+                            (t("api_keys.copy"))
                         }
                     }
 
+                    (usage_snippets(full_token))
+
                     div class="mt-6" {
-                        a
-                            href=(format!("/organizations/{}", org_id.simple()))
-                            class="text-primary hover:text-blue-500" {
-                            "← Back to organization"
-                        }
+                        (layout::back_link(&format!("/organizations/{}", org_id.simple()), t("api_keys.back_to_organization")))
                     }
                 }
             }))
@@ -548,6 +557,34 @@ pub async fn create(
     ).into_response())
 }
 
+/// "Use your key" section with a basic example per language
+fn usage_snippets(api_key: &str) -> Markup {
+    let base_url = &config::get_settings().public_base_url;
+
+    html! {
+        div class="mt-6 bg-white shadow rounded-lg p-6" {
+            h3 class="text-lg font-medium text-gray-900 mb-4" { (t("api_keys.use_your_key")) }
+            @for (i, lang) in snippets::SnippetLang::ALL.iter().enumerate() {
+                details class="mb-3" open[i == 0] {
+                    summary class="cursor-pointer text-sm font-medium text-gray-700" { (lang.label()) }
+                    pre class="mt-2 bg-gray-900 text-gray-100 rounded-md p-4 text-xs overflow-x-auto" {
+                        code { (snippets::render(*lang, snippets::SnippetVariant::Basic, base_url, api_key)) }
+                    }
+                }
+            }
+            p class="text-sm text-gray-500" {
+                (t("api_keys.more_examples")) " "
+                code { "GET /v1/snippets?lang=python" }
+            }
+        }
+    }
+}
+
+/// `text` as a JavaScript string literal, for inline handlers
+fn js_string(text: &str) -> String {
+    serde_json::to_string(text).expect("strings serialize")
+}
+
 /// Handle API key revocation
 pub async fn revoke(
     session: SessionCookie,
@@ -558,47 +595,12 @@ pub async fn revoke(
     let org_id = org_id.into_inner();
     let key_id = key_id.into_inner();
 
-    // Check user has access to this organization
-    let _org = sqlx::query_scalar::<_, Uuid>(
-        "SELECT o.id FROM organizations o
-         INNER JOIN organization_members om ON o.id = om.organization_id
-         WHERE o.id = $1 AND om.user_id = $2",
-    )
-    .bind(org_id)
-    .bind(user_id)
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Database error: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
-    })?
-    .ok_or_else(|| (StatusCode::FORBIDDEN, "Access denied").into_response())?;
-
-    // Revoke the key
-    sqlx::query("UPDATE api_keys SET is_active = false WHERE id = $1 AND organization_id = $2")
-        .bind(key_id)
-        .bind(org_id)
-        .execute(pool)
+    services::api_keys::revoke(pool, org_id, user_id, key_id)
         .await
         .map_err(|e| {
-            tracing::error!("Failed to revoke API key: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to revoke API key",
-            )
-                .into_response()
+            super::service_error_response(e, &format!("/organizations/{}", org_id.simple()))
         })?;
 
     // Redirect back to organization page
     Ok(Redirect::to(&format!("/organizations/{}", org_id.simple())).into_response())
 }
-
-/// Get tier limits for token creation
-fn get_tier_limits(tier: TierType) -> (usize, i32) {
-    let settings = config::get_settings();
-    match tier {
-        TierType::Free => (settings.max_tokens, settings.free_tier_limit),
-        TierType::Pro => (settings.max_tokens, settings.pro_tier_limit),
-        TierType::Scale => (settings.max_tokens, settings.scale_tier_limit),
-    }
-}
@@ -6,17 +6,24 @@ use axum::{
 use maud::{html, Markup};
 use serde::Deserialize;
 
-use crate::auth::session::{clear_session_cookie, create_session_cookie, create_session_token};
+use crate::auth::session::{
+    clear_session_cookie, create_session_cookie, create_session_token_with_org, session_duration,
+    SESSION_DURATION_SHORT,
+};
 use crate::database;
-use crate::models::{TierType, User};
+use crate::models::{CreateUserRequest, User};
+use crate::redact::redact;
+use crate::services::{self, ServiceError};
 use bcrypt::{hash, verify, DEFAULT_COST};
-use chrono::Utc;
+use once_cell::sync::Lazy;
 
+use super::abuse::{self, throttle, ClientIp};
 use super::components::layout;
+use super::i18n::t;
 
 /// Validate redirect URL to prevent open redirect attacks
 /// Only allows relative URLs starting with /
-fn validate_redirect_url(url: &str) -> String {
+pub(super) fn validate_redirect_url(url: &str) -> String {
     if url.starts_with('/') && !url.starts_with("//") {
         url.to_string()
     } else {
@@ -36,6 +43,15 @@ pub struct LoginForm {
     pub email: String,
     pub password: String,
     pub next: Option<String>,
+    /// "Remember me" checkbox (sent as "on" only when checked)
+    #[serde(rename = "remember-me", default)]
+    pub remember_me: Option<String>,
+}
+
+impl LoginForm {
+    pub fn remember_me(&self) -> bool {
+        self.remember_me.is_some()
+    }
 }
 
 /// Register form data
@@ -44,37 +60,39 @@ pub struct RegisterForm {
     pub email: String,
     pub password: String,
     pub name: String,
+    /// Answer to the registration challenge, when one is installed
+    #[serde(rename = "challenge-response", default)]
+    pub challenge_response: Option<String>,
 }
 
 /// Show login page
 pub async fn login_page(Query(redirect): Query<RedirectQuery>) -> Markup {
     layout::base(
-        "Login",
+        t("auth.login_title"),
         html! {
             div class="min-h-screen flex items-center justify-center bg-gray-50 py-12 px-4 sm:px-6 lg:px-8" {
                 div class="max-w-md w-full space-y-8" {
                     div {
                         h2 class="mt-6 text-center text-3xl font-extrabold text-gray-900" {
-                            "Sign in to your account"
+                            (t("auth.sign_in_heading"))
                         }
                         p class="mt-2 text-center text-sm text-gray-600" {
-                            "Or "
+                            (t("auth.or")) " "
                             a href="/register" class="font-medium text-primary hover:text-blue-500" {
-                                "create a new account"
+                                (t("auth.create_new_account"))
                             }
                         }
                     }
 
                     // Login form
                     form class="mt-8 space-y-6" action="/login" method="POST" {
-                        input type="hidden" name="remember" value="true";
                         @if let Some(next) = redirect.next {
                             input type="hidden" name="next" value=(next);
                         }
 
                         div class="rounded-md shadow-sm -space-y-px" {
                             div {
-                                label for="email" class="sr-only" { "Email address" }
+                                label for="email" class="sr-only" { (t("auth.email")) }
                                 input
                                     id="email"
                                     name="email"
@@ -82,10 +100,10 @@ pub async fn login_page(Query(redirect): Query<RedirectQuery>) -> Markup {
                                     autocomplete="email"
                                     required
                                     class="appearance-none rounded-none relative block w-full px-3 py-2 border border-gray-300 placeholder-gray-500 text-gray-900 rounded-t-md focus:outline-none focus:ring-primary focus:border-primary focus:z-10 sm:text-sm"
-                                    placeholder="Email address";
+                                    placeholder=(t("auth.email"));
                             }
                             div {
-                                label for="password" class="sr-only" { "Password" }
+                                label for="password" class="sr-only" { (t("auth.password")) }
                                 input
                                     id="password"
                                     name="password"
@@ -93,7 +111,7 @@ pub async fn login_page(Query(redirect): Query<RedirectQuery>) -> Markup {
                                     autocomplete="current-password"
                                     required
                                     class="appearance-none rounded-none relative block w-full px-3 py-2 border border-gray-300 placeholder-gray-500 text-gray-900 rounded-b-md focus:outline-none focus:ring-primary focus:border-primary focus:z-10 sm:text-sm"
-                                    placeholder="Password";
+                                    placeholder=(t("auth.password"));
                             }
                         }
 
@@ -105,13 +123,13 @@ pub async fn login_page(Query(redirect): Query<RedirectQuery>) -> Markup {
                                     type="checkbox"
                                     class="h-4 w-4 text-primary focus:ring-primary border-gray-300 rounded";
                                 label for="remember-me" class="ml-2 block text-sm text-gray-900" {
-                                    "Remember me"
+                                    (t("auth.remember_me"))
                                 }
                             }
 
                             div class="text-sm" {
                                 a href="#" class="font-medium text-primary hover:text-blue-500" {
-                                    "Forgot your password?"
+                                    (t("auth.forgot_password"))
                                 }
                             }
                         }
@@ -120,7 +138,7 @@ pub async fn login_page(Query(redirect): Query<RedirectQuery>) -> Markup {
                             button
                                 type="submit"
                                 class="group relative w-full flex justify-center py-2 px-4 border border-transparent text-sm font-medium rounded-md text-white bg-primary hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary" {
-                                "Sign in"
+                                (t("auth.sign_in"))
                             }
                         }
                     }
@@ -132,19 +150,21 @@ pub async fn login_page(Query(redirect): Query<RedirectQuery>) -> Markup {
 
 /// Show register page
 pub async fn register_page() -> Markup {
+    let challenge = abuse::registration_challenge();
+
     layout::base(
-        "Register",
+        t("auth.register_title"),
         html! {
             div class="min-h-screen flex items-center justify-center bg-gray-50 py-12 px-4 sm:px-6 lg:px-8" {
                 div class="max-w-md w-full space-y-8" {
                     div {
                         h2 class="mt-6 text-center text-3xl font-extrabold text-gray-900" {
-                            "Create your account"
+                            (t("auth.register_heading"))
                         }
                         p class="mt-2 text-center text-sm text-gray-600" {
-                            "Already have an account? "
+                            (t("auth.have_account")) " "
                             a href="/login" class="font-medium text-primary hover:text-blue-500" {
-                                "Sign in"
+                                (t("auth.sign_in"))
                             }
                         }
                     }
@@ -153,17 +173,17 @@ pub async fn register_page() -> Markup {
                     form class="mt-8 space-y-6" action="/register" method="POST" {
                         div class="rounded-md shadow-sm space-y-4" {
                             div {
-                                label for="name" class="block text-sm font-medium text-gray-700" { "Full name" }
+                                label for="name" class="block text-sm font-medium text-gray-700" { (t("auth.full_name")) }
                                 input
                                     id="name"
                                     name="name"
                                     type="text"
                                     required
                                     class="mt-1 appearance-none relative block w-full px-3 py-2 border border-gray-300 placeholder-gray-500 text-gray-900 rounded-md focus:outline-none focus:ring-primary focus:border-primary sm:text-sm"
-                                    placeholder="John Doe";
+                                    placeholder=(t("auth.full_name_placeholder"));
                             }
                             div {
-                                label for="email" class="block text-sm font-medium text-gray-700" { "Email address" }
+                                label for="email" class="block text-sm font-medium text-gray-700" { (t("auth.email")) }
                                 input
                                     id="email"
                                     name="email"
@@ -171,10 +191,10 @@ pub async fn register_page() -> Markup {
                                     autocomplete="email"
                                     required
                                     class="mt-1 appearance-none relative block w-full px-3 py-2 border border-gray-300 placeholder-gray-500 text-gray-900 rounded-md focus:outline-none focus:ring-primary focus:border-primary sm:text-sm"
-                                    placeholder="you@example.com";
+                                    placeholder=(t("auth.email_placeholder"));
                             }
                             div {
-                                label for="password" class="block text-sm font-medium text-gray-700" { "Password" }
+                                label for="password" class="block text-sm font-medium text-gray-700" { (t("auth.password")) }
                                 input
                                     id="password"
                                     name="password"
@@ -182,15 +202,17 @@ pub async fn register_page() -> Markup {
                                     autocomplete="new-password"
                                     required
                                     class="mt-1 appearance-none relative block w-full px-3 py-2 border border-gray-300 placeholder-gray-500 text-gray-900 rounded-md focus:outline-none focus:ring-primary focus:border-primary sm:text-sm"
-                                    placeholder="At least 8 characters";
+                                    placeholder=(t("auth.password_placeholder"));
                             }
                         }
 
+                        (challenge.widget())
+
                         div {
                             button
                                 type="submit"
                                 class="group relative w-full flex justify-center py-2 px-4 border border-transparent text-sm font-medium rounded-md text-white bg-primary hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary" {
-                                "Create account"
+                                (t("auth.create_account"))
                             }
                         }
                     }
@@ -200,72 +222,57 @@ pub async fn register_page() -> Markup {
     )
 }
 
+/// Bcrypt hash verified when the email is unknown, so a miss costs the same
+/// time as a wrong password
+static DUMMY_PASSWORD_HASH: Lazy<String> =
+    Lazy::new(|| hash("dummy-password", DEFAULT_COST).expect("bcrypt hashing works"));
+
+/// Identical response for every login failure (unknown email, wrong
+/// password, disabled account); the specific reason only goes to the logs
+fn login_failed() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        layout::base(
+            t("auth.login_failed_title"),
+            html! {
+                div class="min-h-screen flex items-center justify-center bg-gray-50" {
+                    div class="max-w-md w-full" {
+                        (layout::alert(t("auth.login_failed"), "error"))
+                        (layout::back_link("/login", t("auth.back_to_login")))
+                    }
+                }
+            },
+        ),
+    )
+        .into_response()
+}
+
 /// Handle login form submission
-pub async fn login_submit(Form(form): Form<LoginForm>) -> Result<Response, Response> {
+pub async fn login_submit(
+    ClientIp(ip): ClientIp,
+    Form(form): Form<LoginForm>,
+) -> Result<Response, Response> {
+    throttle(&abuse::LOGIN_LIMITER, ip, "/login")?;
+
     let pool = database::get_db();
 
     // Find user by email
-    let user = sqlx::query_as!(
-        User,
+    let user = sqlx::query_as::<_, User>(
         "SELECT id, email, name, password_hash, is_active, last_selected_org_id, created_at, updated_at
-         FROM users WHERE email = $1",
-        &form.email
+         FROM users WHERE email = $1 AND deleted_at IS NULL",
     )
+    .bind(&form.email)
     .fetch_optional(pool)
     .await
     .map_err(|e| {
-        tracing::error!("Database error: {}", e);
+        tracing::error!("Database error: {}", redact(&e.to_string()));
         (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
-    })?
-    .ok_or_else(|| {
-        (
-            StatusCode::UNAUTHORIZED,
-            layout::base(
-                "Login Failed",
-                html! {
-                    div class="min-h-screen flex items-center justify-center bg-gray-50" {
-                        div class="max-w-md w-full" {
-                            (layout::alert("Invalid email or password", "error"))
-                            a href="/login" class="text-primary hover:text-blue-500" {
-                                "← Back to login"
-                            }
-                        }
-                    }
-                },
-            ),
-        )
-            .into_response()
     })?;
 
-    // Check if user is active
-    if !user.is_active {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            layout::base(
-                "Account Disabled",
-                html! {
-                    div class="min-h-screen flex items-center justify-center bg-gray-50" {
-                        div class="max-w-md w-full" {
-                            (layout::alert("Your account has been disabled", "error"))
-                            a href="/login" class="text-primary hover:text-blue-500" {
-                                "← Back to login"
-                            }
-                        }
-                    }
-                },
-            ),
-        )
-            .into_response());
-    }
-
-    // Verify password
-    let password_hash = user.password_hash.as_ref().ok_or_else(|| {
-        (
-            StatusCode::UNAUTHORIZED,
-            "Invalid email or password".to_string(),
-        )
-            .into_response()
-    })?;
+    let password_hash = user
+        .as_ref()
+        .and_then(|u| u.password_hash.as_deref())
+        .unwrap_or(DUMMY_PASSWORD_HASH.as_str());
 
     let valid = verify(&form.password, password_hash).map_err(|e| {
         tracing::error!("Password verification error: {}", e);
@@ -276,38 +283,39 @@ pub async fn login_submit(Form(form): Form<LoginForm>) -> Result<Response, Respo
             .into_response()
     })?;
 
-    if !valid {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            layout::base(
-                "Login Failed",
-                html! {
-                    div class="min-h-screen flex items-center justify-center bg-gray-50" {
-                        div class="max-w-md w-full" {
-                            (layout::alert("Invalid email or password", "error"))
-                            a href="/login" class="text-primary hover:text-blue-500" {
-                                "← Back to login"
-                            }
-                        }
-                    }
-                },
-            ),
-        )
-            .into_response());
-    }
+    let user = match user {
+        Some(user) if valid && user.password_hash.is_some() && user.is_active => user,
+        user => {
+            let reason = match &user {
+                None => "unknown email",
+                Some(u) if !u.is_active => "account disabled",
+                Some(u) if u.password_hash.is_none() => "no password set",
+                Some(_) => "wrong password",
+            };
+            tracing::warn!(target: "audit", ?ip, email = %form.email, reason, "Web login failed");
+            return Err(login_failed());
+        }
+    };
 
-    // Generate session token
-    let token = create_session_token(user.id, &user.email).map_err(|e| {
-        tracing::error!("Failed to create session token: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to create session",
-        )
-            .into_response()
-    })?;
+    // Generate session token ("remember me" extends its lifetime)
+    let duration = session_duration(form.remember_me());
+    let token =
+        create_session_token_with_org(user.id, &user.email, user.last_selected_org_id, duration)
+            .map_err(|e| {
+                tracing::error!("Failed to create session token: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to create session",
+                )
+                    .into_response()
+            })?;
 
     // Create session cookie
-    let cookie = create_session_cookie(&token);
+    let cookie = create_session_cookie(&token, duration);
+
+    if let Some(org_id) = user.last_selected_org_id {
+        services::activity::record_login(pool, user.id, org_id).await;
+    }
 
     println!("User {} logged in", user.email);
     println!(
@@ -337,132 +345,75 @@ pub async fn login_submit(Form(form): Form<LoginForm>) -> Result<Response, Respo
     Ok(response)
 }
 
-/// Handle register form submission
-pub async fn register_submit(Form(form): Form<RegisterForm>) -> Result<Response, Response> {
-    let pool = database::get_db();
-
-    // Check if user already exists
-    let existing = sqlx::query_as!(
-        User,
-        "SELECT id, email, name, password_hash, is_active, last_selected_org_id, created_at, updated_at
-         FROM users WHERE email = $1",
-        &form.email
-    )
-    .fetch_optional(pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Database error: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
-    })?;
-
-    if existing.is_some() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            layout::base(
-                "Registration Failed",
-                html! {
-                    div class="min-h-screen flex items-center justify-center bg-gray-50" {
-                        div class="max-w-md w-full" {
-                            (layout::alert("Email already registered", "error"))
-                            a href="/register" class="text-primary hover:text-blue-500" {
-                                "← Back to registration"
-                            }
-                        }
+/// Identical response for every registration failure (including an email
+/// that is already registered); the specific reason only goes to the logs
+fn registration_failed() -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        layout::base(
+            t("auth.registration_failed_title"),
+            html! {
+                div class="min-h-screen flex items-center justify-center bg-gray-50" {
+                    div class="max-w-md w-full" {
+                        (layout::alert(t("auth.registration_failed"), "error"))
+                        (layout::back_link("/register", t("auth.back_to_registration")))
                     }
-                },
-            ),
-        )
-            .into_response());
-    }
-
-    // Hash password
-    let password_hash = hash(&form.password, DEFAULT_COST).map_err(|e| {
-        tracing::error!("Password hashing failed: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Password hashing failed").into_response()
-    })?;
-
-    // Generate organization ID on server (using v7 for time-ordered UUIDs)
-    let org_id = uuid::Uuid::now_v7();
-    let now = Utc::now().naive_utc();
-
-    // Create user with last_selected_org_id set to personal organization
-    let user = sqlx::query_as!(
-        User,
-        "INSERT INTO users (email, name, password_hash, is_active, last_selected_org_id, created_at, updated_at)
-         VALUES ($1, $2, $3, $4, $5, $6, $7)
-         RETURNING id, email, name, password_hash, is_active, last_selected_org_id, created_at, updated_at",
-        &form.email,
-        &form.name,
-        &password_hash,
-        true,
-        org_id,
-        now,
-        now
-    )
-    .fetch_one(pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to create user: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create user").into_response()
-    })?;
-
-    // Create personal organization with generated ID
-    let org_name = format!("{}'s Organization", form.email);
-
-    sqlx::query(
-        "INSERT INTO organizations (id, name, owner_id, tier, is_active, created_at, updated_at)
-         VALUES ($1, $2, $3, $4, $5, $6, $7)",
+                }
+            },
+        ),
     )
-    .bind(org_id)
-    .bind(&org_name)
-    .bind(user.id)
-    .bind(TierType::Free)
-    .bind(true)
-    .bind(now)
-    .bind(now)
-    .execute(pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to create organization: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to create organization",
-        )
-            .into_response()
-    })?;
+        .into_response()
+}
 
-    // Add user as owner
-    sqlx::query(
-        "INSERT INTO organization_members (organization_id, user_id, role, created_at)
-         VALUES ($1, $2, $3, $4)",
-    )
-    .bind(org_id)
-    .bind(user.id)
-    .bind("owner")
-    .bind(Utc::now().naive_utc())
-    .execute(pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to add organization member: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to add organization member",
-        )
-            .into_response()
-    })?;
+/// Handle register form submission
+pub async fn register_submit(
+    ClientIp(ip): ClientIp,
+    Form(form): Form<RegisterForm>,
+) -> Result<Response, Response> {
+    let attempt = throttle(&abuse::REGISTER_LIMITER, ip, "/register")?;
+
+    if abuse::challenge_required(attempt)
+        && !abuse::registration_challenge()
+            .verify(form.challenge_response.as_deref(), ip)
+            .await
+    {
+        tracing::warn!(target: "audit", ?ip, email = %form.email, "Registration challenge failed");
+        return Err(registration_failed());
+    }
 
-    // Generate session token
-    let token = create_session_token(user.id, &user.email).map_err(|e| {
-        tracing::error!("Failed to create session token: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to create session",
-        )
-            .into_response()
-    })?;
+    let request = CreateUserRequest {
+        email: form.email,
+        password: form.password,
+        name: Some(form.name),
+    };
+    let registered = match services::users::register(database::get_db(), &request).await {
+        Ok(registered) => registered,
+        Err(ServiceError::Conflict(_)) => {
+            tracing::warn!(target: "audit", ?ip, email = %request.email, "Registration for existing email");
+            return Err(registration_failed());
+        }
+        Err(e @ ServiceError::Forbidden(_)) => {
+            tracing::warn!(target: "audit", ?ip, email = %request.email, "Registration for deactivated account");
+            return Err(super::service_error_response(e, "/register"));
+        }
+        Err(e) => return Err(super::service_error_response(e, "/register")),
+    };
+    let (user, org_id) = (registered.user, registered.org_id);
+
+    // Generate session token scoped to the personal organization
+    let token =
+        create_session_token_with_org(user.id, &user.email, Some(org_id), SESSION_DURATION_SHORT)
+            .map_err(|e| {
+            tracing::error!("Failed to create session token: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to create session",
+            )
+                .into_response()
+        })?;
 
     // Create session cookie
-    let cookie = create_session_cookie(&token);
+    let cookie = create_session_cookie(&token, SESSION_DURATION_SHORT);
 
     // Redirect to the personal organization page (not the list)
     let redirect_url = format!("/organizations/{}", org_id.simple());
@@ -485,3 +436,176 @@ pub async fn logout_submit() -> Response {
 
     response
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::factory;
+    use crate::test_utils::helpers::{cleanup_db, setup};
+    use axum::{body::Body, extract::ConnectInfo, http::Request, routing::post, Router};
+    use serial_test::serial;
+    use std::net::{IpAddr, SocketAddr};
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route("/login", post(login_submit))
+            .route("/register", post(register_submit))
+    }
+
+    fn form_request(uri: &str, body: &str, ip: IpAddr) -> Request<Body> {
+        Request::builder()
+            .method("POST")
+            .uri(uri)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .extension(ConnectInfo(SocketAddr::new(ip, 40000)))
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn status_and_body(request: Request<Body>) -> (StatusCode, Vec<u8>) {
+        let response = app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, body.to_vec())
+    }
+
+    struct RejectAll;
+
+    #[axum::async_trait]
+    impl abuse::RegistrationChallenge for RejectAll {
+        async fn verify(&self, _response: Option<&str>, _ip: Option<IpAddr>) -> bool {
+            false
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_register_failures_identical_for_existing_and_new_email() {
+        setup().await;
+        cleanup_db().await;
+        factory::user("taken@example.com").await;
+
+        // Existing email
+        let existing = status_and_body(form_request(
+            "/register",
+            "email=taken%40example.com&password=password123&name=Taken",
+            IpAddr::from([198, 51, 100, 1]),
+        ))
+        .await;
+
+        // New email from an IP that has to pass the (failing) challenge
+        let flagged = IpAddr::from([198, 51, 100, 2]);
+        for _ in 0..3 {
+            abuse::REGISTER_LIMITER
+                .hit(flagged, std::time::Instant::now())
+                .unwrap();
+        }
+        abuse::set_registration_challenge(Arc::new(RejectAll));
+        let new_email = status_and_body(form_request(
+            "/register",
+            "email=fresh%40example.com&password=password123&name=Fresh",
+            flagged,
+        ))
+        .await;
+        abuse::set_registration_challenge(Arc::new(abuse::NoopChallenge));
+
+        assert_eq!(existing.0, StatusCode::BAD_REQUEST);
+        assert_eq!(existing, new_email);
+        assert!(!String::from_utf8_lossy(&existing.1).contains("already registered"));
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_register_over_deactivated_and_deleted_accounts() {
+        setup().await;
+        cleanup_db().await;
+        let pool = database::get_db();
+        factory::user("deactivated@example.com").await;
+        factory::user("deleted@example.com").await;
+        sqlx::query("UPDATE users SET is_active = false WHERE email = 'deactivated@example.com'")
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            "UPDATE users SET is_active = false, password_hash = NULL, deleted_at = $1
+             WHERE email = 'deleted@example.com'",
+        )
+        .bind(database::timestamp(chrono::Utc::now()))
+        .execute(pool)
+        .await
+        .unwrap();
+
+        let ip = IpAddr::from([198, 51, 100, 4]);
+        let (status, body) = status_and_body(form_request(
+            "/register",
+            "email=deactivated%40example.com&password=password123&name=Again",
+            ip,
+        ))
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(String::from_utf8_lossy(&body).contains("Contact support"));
+
+        // The deleted account's email is free, and signs in to the new account
+        let (status, _) = status_and_body(form_request(
+            "/register",
+            "email=deleted%40example.com&password=newpassword1&name=Again",
+            IpAddr::from([198, 51, 100, 5]),
+        ))
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        let (status, _) = status_and_body(form_request(
+            "/login",
+            "email=deleted%40example.com&password=newpassword1",
+            ip,
+        ))
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+
+        cleanup_db().await;
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_login_failures_identical() {
+        setup().await;
+        cleanup_db().await;
+        factory::user("user@example.com").await;
+        factory::user("disabled@example.com").await;
+        sqlx::query("UPDATE users SET is_active = false WHERE email = 'disabled@example.com'")
+            .execute(database::get_db())
+            .await
+            .unwrap();
+
+        let ip = IpAddr::from([198, 51, 100, 3]);
+        let wrong_password = status_and_body(form_request(
+            "/login",
+            "email=user%40example.com&password=wrong",
+            ip,
+        ))
+        .await;
+        let unknown_email = status_and_body(form_request(
+            "/login",
+            "email=nobody%40example.com&password=password123",
+            ip,
+        ))
+        .await;
+        let disabled = status_and_body(form_request(
+            "/login",
+            "email=disabled%40example.com&password=password123",
+            ip,
+        ))
+        .await;
+
+        assert_eq!(wrong_password.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(wrong_password, unknown_email);
+        assert_eq!(wrong_password, disabled);
+
+        cleanup_db().await;
+    }
+}
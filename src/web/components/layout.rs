@@ -1,10 +1,23 @@
-use maud::{html, Markup, DOCTYPE};
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+
+use crate::web::i18n::{self, t, Locale};
+
+/// Keyboard and focus handling of the dropdown menus and modals.
+///
+/// Menus: a `data-menu="<menu id>"` button toggles the `role="menu"` element
+/// (also with ArrowDown/ArrowUp); inside it the arrow keys, Home and End move
+/// between items and Escape closes it, returning focus to the button.
+///
+/// Modals: `data-modal-open="<dialog id>"` opens a `role="dialog"`, focusing
+/// its first field; `data-modal-close` elements, Escape and the overlay close
+/// it and focus returns to the opener. Tab stays inside the open dialog.
+const UI_SCRIPT: &str = include_str!("ui.js");
 
 /// Base HTML layout with Tailwind CSS and HTMX
 pub fn base(title: &str, content: Markup) -> Markup {
     html! {
         (DOCTYPE)
-        html lang="en" {
+        html lang=(i18n::current().code()) {
             head {
                 meta charset="utf-8";
                 meta name="viewport" content="width=device-width, initial-scale=1";
@@ -31,9 +44,21 @@ pub fn base(title: &str, content: Markup) -> Markup {
                     }
                     "#
                 }
+
+                script { (PreEscaped(UI_SCRIPT)) }
             }
             body class="bg-gray-50 min-h-screen" {
+                @if let Some(maintenance) = crate::maintenance::current() {
+                    div class="bg-yellow-100 border-b border-yellow-300 text-yellow-900 text-sm text-center px-4 py-2" role="status" {
+                        strong { (t("layout.maintenance")) " " }
+                        (maintenance.message)
+                        @if let Some(until) = maintenance.until {
+                            " (" (i18n::t_with("layout.maintenance_until", &[("time", &until.format("%Y-%m-%d %H:%M UTC").to_string())])) ")"
+                        }
+                    }
+                }
                 (content)
+                (language_switcher())
             }
         }
     }
@@ -46,7 +71,7 @@ pub fn navbar(
     other_orgs: &[(&str, &str)],
 ) -> Markup {
     html! {
-        nav class="bg-white shadow-sm border-b border-gray-200" {
+        nav class="bg-white shadow-sm border-b border-gray-200" aria-label=(t("layout.main_navigation")) {
             div class="max-w-7xl mx-auto px-4 sm:px-6 lg:px-8" {
                 div class="flex justify-between h-16" {
                     div class="flex items-center" {
@@ -64,13 +89,15 @@ pub fn navbar(
                                 div class="relative inline-block text-left" {
                                     button
                                         type="button"
-                                        onclick="document.getElementById('org-dropdown').classList.toggle('hidden')"
+                                        data-menu="org-dropdown"
                                         class="inline-flex justify-center items-center w-full rounded-md border border-gray-300 shadow-sm px-4 py-2 bg-white text-sm font-medium text-gray-700 hover:bg-gray-50 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary"
                                         id="org-menu-button"
+                                        aria-label=(i18n::t_with("layout.switch_organization", &[("org", org_name)]))
+                                        aria-controls="org-dropdown"
                                         aria-expanded="false"
-                                        aria-haspopup="true" {
+                                        aria-haspopup="menu" {
                                         span { (org_name) }
-                                        svg class="-mr-1 ml-2 h-5 w-5" xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 20" fill="currentColor" {
+                                        svg class="-mr-1 ml-2 h-5 w-5" xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 20" fill="currentColor" aria-hidden="true" {
                                             path fill-rule="evenodd" d="M5.293 7.293a1 1 0 011.414 0L10 10.586l3.293-3.293a1 1 0 111.414 1.414l-4 4a1 1 0 01-1.414 0l-4-4a1 1 0 010-1.414z" clip-rule="evenodd" {}
                                         }
                                     }
@@ -80,37 +107,44 @@ pub fn navbar(
                                         id="org-dropdown"
                                         role="menu"
                                         aria-orientation="vertical"
-                                        aria-labelledby="org-menu-button"
-                                        tabindex="-1" {
+                                        aria-labelledby="org-menu-button" {
                                         div class="py-1" role="none" {
                                             // Current organization (selected)
                                             a
                                                 href=(format!("/switch-org/{}", org_id))
-                                                class="flex items-center px-4 py-2 text-sm text-gray-900 bg-gray-100 font-medium"
-                                                role="menuitem" {
-                                                svg class="mr-3 h-5 w-5 text-primary" xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 20" fill="currentColor" {
+                                                class="flex items-center px-4 py-2 text-sm text-gray-900 bg-gray-100 font-medium focus:bg-gray-200 focus:outline-none"
+                                                role="menuitem"
+                                                tabindex="-1"
+                                                aria-current="true" {
+                                                svg class="mr-3 h-5 w-5 text-primary" xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 20" fill="currentColor" aria-hidden="true" {
                                                     path fill-rule="evenodd" d="M16.707 5.293a1 1 0 010 1.414l-8 8a1 1 0 01-1.414 0l-4-4a1 1 0 011.414-1.414L8 12.586l7.293-7.293a1 1 0 011.414 0z" clip-rule="evenodd" {}
                                                 }
                                                 (org_name)
+                                                span class="sr-only" { " " (t("layout.current_organization")) }
                                             }
 
                                             @if !other_orgs.is_empty() {
-                                                div class="border-t border-gray-100" {}
+                                                div class="border-t border-gray-100" role="none" {}
 
                                                 @for (other_id, other_name) in other_orgs {
                                                     a
                                                         href=(format!("/switch-org/{}", other_id))
-                                                        class="block px-4 py-2 text-sm text-gray-700 hover:bg-gray-100"
-                                                        role="menuitem" {
+                                                        class="block px-4 py-2 text-sm text-gray-700 hover:bg-gray-100 focus:bg-gray-100 focus:outline-none"
+                                                        role="menuitem"
+                                                        tabindex="-1" {
                                                         span class="mr-8" {} // Spacer for alignment
                                                         (other_name)
                                                     }
                                                 }
                                             }
 
-                                            div class="border-t border-gray-100" {}
-                                            a href="/organizations" class="block px-4 py-2 text-sm text-gray-700 hover:bg-gray-100" role="menuitem" {
-                                                "Manage Organizations"
+                                            div class="border-t border-gray-100" role="none" {}
+                                            a
+                                                href="/organizations"
+                                                class="block px-4 py-2 text-sm text-gray-700 hover:bg-gray-100 focus:bg-gray-100 focus:outline-none"
+                                                role="menuitem"
+                                                tabindex="-1" {
+                                                (t("layout.manage_organizations"))
                                             }
                                         }
                                     }
@@ -119,33 +153,67 @@ pub fn navbar(
                         } @else {
                             div class="ml-6" {
                                 a href="/organizations" class="text-sm font-medium text-gray-700 hover:text-gray-900" {
-                                    "Select Organization"
+                                    (t("layout.select_organization"))
                                 }
                             }
                         }
                     }
 
-                    // User menu
+                    // Signed-in user (the avatar is decoration next to the email)
                     div class="flex items-center" {
-                        div class="ml-3 relative" {
-                            button
-                                type="button"
-                                class="flex text-sm rounded-full focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary"
-                                id="user-menu-button"
-                                aria-expanded="false"
-                                aria-haspopup="true" {
-                                span class="sr-only" { "Open user menu" }
-                                div class="h-8 w-8 rounded-full bg-primary flex items-center justify-center text-white font-medium" {
-                                    (user_email.chars().next().unwrap_or('U').to_uppercase())
-                                }
-                            }
+                        // Remaining quota of the active organization, refreshed with the server-side cache
+                        @if let Some((org_id, _)) = current_org {
+                            div
+                                class="hidden md:block mr-4"
+                                hx-get=(format!("/organizations/{}/quota-fragment", org_id))
+                                hx-trigger="load, every 30s"
+                                hx-swap="innerHTML" {}
+                        }
+                        div
+                            class="ml-3 h-8 w-8 rounded-full bg-primary flex items-center justify-center text-white text-sm font-medium"
+                            aria-hidden="true" {
+                            (user_email.chars().next().unwrap_or('U').to_uppercase())
+                        }
+                        span class="ml-3 text-sm text-gray-700" {
+                            span class="sr-only" { (t("layout.signed_in_as")) " " }
+                            (user_email)
                         }
-                        span class="ml-3 text-sm text-gray-700" { (user_email) }
                         form action="/logout" method="post" class="ml-4" {
                             button
                                 type="submit"
-                                class="text-sm text-gray-500 hover:text-gray-700" {
-                                "Logout"
+                                class="text-sm text-gray-500 hover:text-gray-700 focus:outline-none focus:ring-2 focus:ring-primary rounded" {
+                                (t("layout.logout"))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Links switching the UI language, each labelled in its own language
+fn language_switcher() -> Markup {
+    let current = i18n::current();
+
+    html! {
+        footer class="max-w-7xl mx-auto px-4 sm:px-6 lg:px-8 py-6 print:hidden" {
+            nav aria-label=(t("layout.language")) {
+                ul class="flex justify-center gap-4 text-xs text-gray-500" {
+                    @for locale in Locale::ALL {
+                        li {
+                            @if locale == current {
+                                span class="font-medium text-gray-900" lang=(locale.code()) aria-current="true" {
+                                    (locale.native_name())
+                                }
+                            } @else {
+                                a
+                                    href=(format!("/lang/{}", locale.code()))
+                                    class="hover:text-gray-700 underline"
+                                    lang=(locale.code())
+                                    hreflang=(locale.code()) {
+                                    (locale.native_name())
+                                }
                             }
                         }
                     }
@@ -181,7 +249,7 @@ pub fn card(title: &str, content: Markup) -> Markup {
 /// Smally logo - "S" on fire
 pub fn logo() -> Markup {
     html! {
-        svg width="40" height="40" viewBox="0 0 100 120" xmlns="http://www.w3.org/2000/svg" class="inline-block" {
+        svg width="40" height="40" viewBox="0 0 100 120" xmlns="http://www.w3.org/2000/svg" class="inline-block" aria-hidden="true" {
             defs {
                 // Gradient for fire effect
                 linearGradient id="fireGradient" x1="0%" y1="0%" x2="0%" y2="100%" {
@@ -218,6 +286,16 @@ pub fn logo() -> Markup {
     }
 }
 
+/// "← label" link back to another page
+pub fn back_link(href: &str, label: &str) -> Markup {
+    html! {
+        a href=(href) class="text-primary hover:text-blue-500" {
+            span aria-hidden="true" { "← " }
+            (label)
+        }
+    }
+}
+
 /// Button component
 pub fn button(text: &str, button_type: &str, extra_classes: &str) -> Markup {
     let base_classes = "inline-flex justify-center py-2 px-4 border border-transparent shadow-sm text-sm font-medium rounded-md focus:outline-none focus:ring-2 focus:ring-offset-2";
@@ -238,24 +316,38 @@ pub fn button(text: &str, button_type: &str, extra_classes: &str) -> Markup {
     }
 }
 
-/// Alert message component
+/// Alert message component. The type is also announced as text and shown
+/// as an icon, not only as the background color.
 pub fn alert(message: &str, alert_type: &str) -> Markup {
-    let (bg_class, text_class, _border_class) = match alert_type {
+    let (bg_class, text_class, border_class) = match alert_type {
         "success" => ("bg-green-50", "text-green-800", "border-green-200"),
         "error" => ("bg-red-50", "text-red-800", "border-red-200"),
         "warning" => ("bg-yellow-50", "text-yellow-800", "border-yellow-200"),
         "info" => ("bg-blue-50", "text-blue-800", "border-blue-200"),
         _ => ("bg-blue-50", "text-blue-800", "border-blue-200"),
     };
+    let (icon, label) = match alert_type {
+        "success" => ("✓", t("alert.success")),
+        "error" => ("✕", t("alert.error")),
+        "warning" => ("!", t("alert.warning")),
+        _ => ("i", t("alert.info")),
+    };
+    // Errors interrupt screen readers, the rest waits its turn
+    let role = if alert_type == "error" {
+        "alert"
+    } else {
+        "status"
+    };
 
     html! {
-        div class=(format!("rounded-md p-4 mb-4 border {}", bg_class)) {
+        div class=(format!("rounded-md p-4 mb-4 border {} {}", bg_class, border_class)) role=(role) {
             div class="flex" {
-                div class="flex-shrink-0" {
-                    // Icon placeholder
+                div class=(format!("flex-shrink-0 font-bold {}", text_class)) aria-hidden="true" {
+                    (icon)
                 }
                 div class=(format!("ml-3 {}", text_class)) {
                     p class="text-sm font-medium" {
+                        span class="sr-only" { (label) " " }
                         (message)
                     }
                 }
@@ -263,3 +355,19 @@ pub fn alert(message: &str, alert_type: &str) -> Markup {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alert_type_is_not_color_only() {
+        let html = alert("Invalid email or password", "error").into_string();
+        assert!(html.contains(r#"role="alert""#));
+        assert!(html.contains(r#"<span class="sr-only">Error: </span>"#));
+
+        let html = alert("Saved", "success").into_string();
+        assert!(html.contains(r#"role="status""#));
+        assert!(html.contains("Success: "));
+    }
+}
@@ -0,0 +1,178 @@
+(function () {
+    var FOCUSABLE = 'a[href], button:not([disabled]), input:not([disabled]):not([type=hidden]), select:not([disabled]), textarea:not([disabled]), [tabindex]:not([tabindex="-1"])';
+    var modalOpener = null;
+
+    function focusable(root) {
+        return Array.prototype.filter.call(root.querySelectorAll(FOCUSABLE), function (el) {
+            return el.offsetParent !== null;
+        });
+    }
+
+    // Modals
+
+    function openModal(modal, opener) {
+        if (!modal) return;
+        modalOpener = opener || document.activeElement;
+        modal.classList.remove('hidden');
+        var first = modal.querySelector('input:not([type=hidden]), select, textarea') || focusable(modal)[0];
+        if (first) first.focus();
+    }
+
+    function closeModal(modal) {
+        if (!modal) return;
+        modal.classList.add('hidden');
+        if (modalOpener && document.body.contains(modalOpener)) modalOpener.focus();
+        modalOpener = null;
+    }
+
+    function trapTab(modal, event) {
+        var items = focusable(modal);
+        if (items.length === 0) return;
+        var first = items[0];
+        var last = items[items.length - 1];
+        if (event.shiftKey && document.activeElement === first) {
+            last.focus();
+            event.preventDefault();
+        } else if (!event.shiftKey && document.activeElement === last) {
+            first.focus();
+            event.preventDefault();
+        } else if (!modal.contains(document.activeElement)) {
+            first.focus();
+            event.preventDefault();
+        }
+    }
+
+    // Menus
+
+    function menuItems(menu) {
+        return Array.prototype.slice.call(menu.querySelectorAll('[role=menuitem]'));
+    }
+
+    function openMenu(button, focusIndex) {
+        var menu = document.getElementById(button.getAttribute('data-menu'));
+        if (!menu) return;
+        menu.classList.remove('hidden');
+        button.setAttribute('aria-expanded', 'true');
+        var items = menuItems(menu);
+        if (focusIndex !== null && items.length > 0) {
+            items[(focusIndex + items.length) % items.length].focus();
+        }
+    }
+
+    function closeMenu(button, restoreFocus) {
+        var menu = document.getElementById(button.getAttribute('data-menu'));
+        if (menu) menu.classList.add('hidden');
+        button.setAttribute('aria-expanded', 'false');
+        if (restoreFocus) button.focus();
+    }
+
+    function openMenuButtons() {
+        return Array.prototype.slice.call(document.querySelectorAll('[data-menu][aria-expanded=true]'));
+    }
+
+    // Copy buttons: data-copy names the element whose text is copied
+
+    function copyText(button) {
+        var source = document.getElementById(button.getAttribute('data-copy'));
+        if (!source || !navigator.clipboard) return;
+        navigator.clipboard.writeText(source.textContent).then(function () {
+            var label = button.textContent;
+            button.textContent = button.getAttribute('data-copied');
+            setTimeout(function () { button.textContent = label; }, 2000);
+        });
+    }
+
+    document.addEventListener('click', function (event) {
+        var target = event.target;
+
+        var copier = target.closest('[data-copy]');
+        if (copier) {
+            copyText(copier);
+            return;
+        }
+
+        var opener = target.closest('[data-modal-open]');
+        if (opener) {
+            openModal(document.getElementById(opener.getAttribute('data-modal-open')), opener);
+            return;
+        }
+        var closer = target.closest('[data-modal-close]');
+        if (closer) {
+            closeModal(closer.closest('[role=dialog]'));
+            return;
+        }
+
+        var toggle = target.closest('[data-menu]');
+        openMenuButtons().forEach(function (button) {
+            var menu = document.getElementById(button.getAttribute('data-menu'));
+            if (button !== toggle && !(menu && menu.contains(target))) closeMenu(button, false);
+        });
+        if (toggle) {
+            if (toggle.getAttribute('aria-expanded') === 'true') {
+                closeMenu(toggle, false);
+            } else {
+                // detail is 0 when activated with Enter or Space
+                openMenu(toggle, event.detail === 0 ? 0 : null);
+            }
+        }
+    });
+
+    document.addEventListener('keydown', function (event) {
+        var modal = document.querySelector('[role=dialog]:not(.hidden)');
+        if (modal) {
+            if (event.key === 'Escape') {
+                closeModal(modal);
+                event.preventDefault();
+            } else if (event.key === 'Tab') {
+                trapTab(modal, event);
+            }
+            return;
+        }
+
+        var target = event.target;
+        if (!target.closest) return;
+
+        var toggle = target.closest('[data-menu]');
+        if (toggle && (event.key === 'ArrowDown' || event.key === 'ArrowUp')) {
+            openMenu(toggle, event.key === 'ArrowDown' ? 0 : -1);
+            event.preventDefault();
+            return;
+        }
+
+        var menu = target.closest('[role=menu]');
+        if (!menu) return;
+        var button = document.querySelector('[data-menu="' + menu.id + '"]');
+        var items = menuItems(menu);
+        var index = items.indexOf(target);
+
+        switch (event.key) {
+            case 'ArrowDown':
+                items[(index + 1) % items.length].focus();
+                break;
+            case 'ArrowUp':
+                items[(index - 1 + items.length) % items.length].focus();
+                break;
+            case 'Home':
+                items[0].focus();
+                break;
+            case 'End':
+                items[items.length - 1].focus();
+                break;
+            case 'Escape':
+                if (button) closeMenu(button, true);
+                break;
+            case 'Tab':
+                if (button) closeMenu(button, false);
+                return;
+            default:
+                return;
+        }
+        event.preventDefault();
+    });
+
+    // Modals rendered open (e.g. `?new=true`) get focus like opened ones
+    document.addEventListener('DOMContentLoaded', function () {
+        var modal = document.querySelector('[role=dialog]:not(.hidden)');
+        if (modal) openModal(modal, null);
+    });
+})();
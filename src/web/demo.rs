@@ -0,0 +1,441 @@
+//! "Try it" box on the landing page (needs the model in this process).
+//! The form posts to `/demo`, which embeds through `api::demo` (same limits
+//! and counters as `POST /v1/demo/embed`) and returns an HTML fragment.
+//!
+//! The compare form posts two texts to `/demo/compare`, which shows their
+//! cosine similarity. Every text embedded on the page joins a playground
+//! kept in this process under a `playground` cookie (the last
+//! `PLAYGROUND_POINTS` texts with their full vectors, which never leave
+//! the server), and each response redraws a 2D PCA projection of them as
+//! an SVG scatter plot (swapped out of band into `#demo-map`).
+
+use axum::extract::Form;
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use dashmap::DashMap;
+use maud::{html, Markup};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use super::abuse::ClientIp;
+use super::components::layout;
+use crate::api::demo::{self, DemoEmbedResponse, DEMO_MAX_CHARS};
+use crate::inference::math;
+
+/// Cookie naming the visitor's playground
+const PLAYGROUND_COOKIE: &str = "playground";
+
+/// Texts kept per playground (and plotted)
+const PLAYGROUND_POINTS: usize = 12;
+
+/// Playgrounds untouched for this long are dropped
+const PLAYGROUND_IDLE: Duration = Duration::from_secs(30 * 60);
+
+/// Playgrounds kept per instance; the least recently used goes first
+const MAX_PLAYGROUNDS: usize = 1000;
+
+/// Scatter plot size in SVG units
+const MAP_WIDTH: f32 = 320.0;
+const MAP_HEIGHT: f32 = 200.0;
+const MAP_PADDING: f32 = 16.0;
+
+/// Characters of a text shown as its label on the map
+const MAP_LABEL_CHARS: usize = 18;
+
+static PLAYGROUNDS: Lazy<Playgrounds> = Lazy::new(|| Playgrounds::new(MAX_PLAYGROUNDS));
+
+#[derive(Debug, Deserialize)]
+pub struct DemoForm {
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareForm {
+    pub a: String,
+    pub b: String,
+}
+
+/// A text embedded on the playground
+#[derive(Debug, Clone, PartialEq)]
+struct Point {
+    text: String,
+    vector: Vec<f32>,
+}
+
+struct Playground {
+    points: VecDeque<Point>,
+    last_used: Instant,
+}
+
+/// Recently embedded texts per visitor
+struct Playgrounds {
+    capacity: usize,
+    playgrounds: DashMap<Uuid, Playground>,
+}
+
+impl Playgrounds {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            playgrounds: DashMap::new(),
+        }
+    }
+
+    /// Add `points` to a playground (a text already there moves to the
+    /// end) and return what it holds now, oldest first
+    fn remember(&self, id: Uuid, points: Vec<Point>, now: Instant) -> Vec<Point> {
+        if !self.playgrounds.contains_key(&id) && self.playgrounds.len() >= self.capacity {
+            self.evict(now);
+        }
+
+        let mut playground = self.playgrounds.entry(id).or_insert_with(|| Playground {
+            points: VecDeque::new(),
+            last_used: now,
+        });
+        playground.last_used = now;
+        for point in points {
+            playground.points.retain(|p| p.text != point.text);
+            playground.points.push_back(point);
+            if playground.points.len() > PLAYGROUND_POINTS {
+                playground.points.pop_front();
+            }
+        }
+        playground.points.iter().cloned().collect()
+    }
+
+    /// Drop idle playgrounds, or the least recently used if none is idle
+    fn evict(&self, now: Instant) {
+        self.playgrounds
+            .retain(|_, playground| now.duration_since(playground.last_used) < PLAYGROUND_IDLE);
+        if self.playgrounds.len() >= self.capacity {
+            let oldest = self
+                .playgrounds
+                .iter()
+                .min_by_key(|entry| entry.last_used)
+                .map(|entry| *entry.key());
+            if let Some(oldest) = oldest {
+                self.playgrounds.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// The visitor's playground id, with the cookie set if it's new
+fn playground_id(jar: CookieJar) -> (Uuid, CookieJar) {
+    if let Some(id) = jar
+        .get(PLAYGROUND_COOKIE)
+        .and_then(|cookie| Uuid::parse_str(cookie.value()).ok())
+    {
+        return (id, jar);
+    }
+
+    let id = Uuid::from_bytes(rand::random());
+    let cookie = Cookie::build((PLAYGROUND_COOKIE, id.simple().to_string()))
+        .path("/")
+        .same_site(SameSite::Lax)
+        .http_only(true)
+        .build();
+    (id, jar.add(cookie))
+}
+
+/// Demo form and result area
+pub fn section() -> Markup {
+    html! {
+        div class="max-w-3xl mx-auto px-4 sm:px-6 lg:px-8 pb-16" {
+            div class="bg-white rounded-lg shadow-md p-6" {
+                h2 class="text-xl font-bold text-gray-900 mb-4" { "Try it" }
+                form hx-post="/demo" hx-target="#demo-result" hx-swap="innerHTML" class="space-y-4" {
+                    textarea
+                        name="text"
+                        rows="2"
+                        required
+                        maxlength=(DEMO_MAX_CHARS)
+                        placeholder="how to reset password"
+                        class="block w-full border border-gray-300 rounded-md shadow-sm py-2 px-3 sm:text-sm" {}
+                    (layout::button("Embed", "primary", ""))
+                }
+                div id="demo-result" class="mt-4" {}
+
+                h3 class="text-lg font-semibold text-gray-900 mt-8 mb-4" { "Compare" }
+                form hx-post="/demo/compare" hx-target="#demo-compare-result" hx-swap="innerHTML" class="space-y-4" {
+                    div class="grid grid-cols-1 gap-4 sm:grid-cols-2" {
+                        textarea
+                            name="a"
+                            rows="2"
+                            required
+                            maxlength=(DEMO_MAX_CHARS)
+                            placeholder="how to reset password"
+                            class="block w-full border border-gray-300 rounded-md shadow-sm py-2 px-3 sm:text-sm" {}
+                        textarea
+                            name="b"
+                            rows="2"
+                            required
+                            maxlength=(DEMO_MAX_CHARS)
+                            placeholder="I forgot my login"
+                            class="block w-full border border-gray-300 rounded-md shadow-sm py-2 px-3 sm:text-sm" {}
+                    }
+                    (layout::button("Compare", "primary", ""))
+                }
+                div id="demo-compare-result" class="mt-4" {}
+                div id="demo-map" class="mt-6" {}
+            }
+        }
+    }
+}
+
+/// Embed the form text and render the truncated vector (errors render as an
+/// alert with status 200, so HTMX swaps them in)
+pub async fn submit(
+    ClientIp(ip): ClientIp,
+    jar: CookieJar,
+    Form(form): Form<DemoForm>,
+) -> (CookieJar, Markup) {
+    let (id, jar) = playground_id(jar);
+    let markup = match demo::embed_full(ip, &form.text).await {
+        Ok((response, vector)) => {
+            let points = PLAYGROUNDS.remember(
+                id,
+                vec![Point {
+                    text: form.text,
+                    vector,
+                }],
+                Instant::now(),
+            );
+            html! {
+                (result(&response))
+                (map(&points, 1))
+            }
+        }
+        Err(e) => layout::alert(&e.to_string(), "error"),
+    };
+    (jar, markup)
+}
+
+/// Embed both texts and render their cosine similarity
+pub async fn compare(
+    ClientIp(ip): ClientIp,
+    jar: CookieJar,
+    Form(form): Form<CompareForm>,
+) -> (CookieJar, Markup) {
+    let (id, jar) = playground_id(jar);
+    let embedded = match demo::embed_full(ip, &form.a).await {
+        Ok((_, a)) => demo::embed_full(ip, &form.b).await.map(|(_, b)| (a, b)),
+        Err(e) => Err(e),
+    };
+    let markup = match embedded {
+        Ok((a, b)) => {
+            let similarity = math::cosine(&a, &b);
+            let points = PLAYGROUNDS.remember(
+                id,
+                vec![
+                    Point {
+                        text: form.a,
+                        vector: a,
+                    },
+                    Point {
+                        text: form.b,
+                        vector: b,
+                    },
+                ],
+                Instant::now(),
+            );
+            html! {
+                (comparison(similarity))
+                (map(&points, 2))
+            }
+        }
+        Err(e) => layout::alert(&e.to_string(), "error"),
+    };
+    (jar, markup)
+}
+
+fn result(response: &DemoEmbedResponse) -> Markup {
+    let values: Vec<String> = response
+        .embedding
+        .iter()
+        .map(|value| format!("{:.4}", value))
+        .collect();
+
+    html! {
+        pre class="bg-gray-50 rounded-md p-4 text-sm text-gray-800 overflow-x-auto" {
+            "[" (values.join(", ")) ", …]"
+        }
+        p class="mt-2 text-sm text-gray-500" {
+            "First " (response.embedding.len()) " of " (response.dimensions)
+            " dimensions · " (response.tokens) " tokens. "
+            a href="/register" class="text-primary hover:text-blue-500" { "Get an API key" }
+            " for the full vector."
+        }
+    }
+}
+
+/// Similarity of the compared texts, with a rough reading of it
+fn comparison(similarity: f32) -> Markup {
+    let verdict = if similarity >= 0.7 {
+        "Very similar"
+    } else if similarity >= 0.4 {
+        "Somewhat similar"
+    } else {
+        "Not similar"
+    };
+
+    html! {
+        div class="bg-gray-50 rounded-md p-4 text-center" {
+            p class="text-4xl font-bold text-primary" { (format!("{:.3}", similarity)) }
+            p class="mt-1 text-sm text-gray-600" { "Cosine similarity · " (verdict) }
+        }
+    }
+}
+
+/// Scatter plot of the playground's texts on their first two principal
+/// components, the `latest` ones highlighted (swapped into `#demo-map`)
+fn map(points: &[Point], latest: usize) -> Markup {
+    let vectors: Vec<Vec<f32>> = points.iter().map(|p| p.vector.clone()).collect();
+    let projected = math::pca_2d(&vectors);
+
+    // One scale for both axes, so distances on the map stay comparable
+    let (min_x, max_x, min_y, max_y) = projected.iter().fold(
+        (f32::MAX, f32::MIN, f32::MAX, f32::MIN),
+        |(min_x, max_x, min_y, max_y), [x, y]| {
+            (min_x.min(*x), max_x.max(*x), min_y.min(*y), max_y.max(*y))
+        },
+    );
+    let scale = ((MAP_WIDTH - 2.0 * MAP_PADDING) / (max_x - min_x))
+        .min((MAP_HEIGHT - 2.0 * MAP_PADDING) / (max_y - min_y))
+        .min(1e6);
+    let (center_x, center_y) = ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+    let position = |[x, y]: [f32; 2]| {
+        (
+            MAP_WIDTH / 2.0 + (x - center_x) * scale,
+            // SVG y grows downwards
+            MAP_HEIGHT / 2.0 - (y - center_y) * scale,
+        )
+    };
+    let first_latest = points.len().saturating_sub(latest);
+
+    html! {
+        div id="demo-map" class="mt-6" hx-swap-oob="true" {
+            @if points.len() < 2 {
+                p class="text-sm text-gray-500" {
+                    "Embed a few more texts to see them on a map."
+                }
+            } @else {
+                h3 class="text-sm font-semibold text-gray-700 mb-2" {
+                    "Your last " (points.len()) " texts, closer when more similar"
+                }
+                svg
+                    viewBox=(format!("0 0 {} {}", MAP_WIDTH, MAP_HEIGHT))
+                    role="img"
+                    aria-label="2D projection of the embedded texts"
+                    class="w-full bg-gray-50 rounded-md" {
+                    @for (i, (point, coordinates)) in points.iter().zip(&projected).enumerate() {
+                        @let (x, y) = position(*coordinates);
+                        @let fill = if i >= first_latest { "#2563eb" } else { "#9ca3af" };
+                        g {
+                            title { (point.text) }
+                            circle
+                                cx=(format!("{:.1}", x))
+                                cy=(format!("{:.1}", y))
+                                r="4"
+                                fill=(fill) {}
+                            text
+                                x=(format!("{:.1}", x + 6.0))
+                                y=(format!("{:.1}", y + 3.0))
+                                font-size="9"
+                                fill="#374151" {
+                                (label(&point.text))
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn label(text: &str) -> String {
+    if text.chars().count() > MAP_LABEL_CHARS {
+        let short: String = text.chars().take(MAP_LABEL_CHARS - 1).collect();
+        format!("{}…", short.trim_end())
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(text: &str, vector: Vec<f32>) -> Point {
+        Point {
+            text: text.to_string(),
+            vector,
+        }
+    }
+
+    #[test]
+    fn test_result_shows_truncated_vector() {
+        let markup = result(&DemoEmbedResponse {
+            embedding: vec![0.5, -0.25],
+            dimensions: 384,
+            tokens: 6,
+        })
+        .into_string();
+        assert!(markup.contains("[0.5000, -0.2500, …]"));
+        assert!(markup.contains("of 384"));
+    }
+
+    #[test]
+    fn test_playground_keeps_recent_texts() {
+        let playgrounds = Playgrounds::new(2);
+        let (first, second, third) = (Uuid::now_v7(), Uuid::now_v7(), Uuid::now_v7());
+        let now = Instant::now();
+
+        for i in 0..PLAYGROUND_POINTS + 2 {
+            playgrounds.remember(first, vec![point(&i.to_string(), vec![i as f32])], now);
+        }
+        // A repeated text moves to the end instead of appearing twice
+        let points = playgrounds.remember(first, vec![point("2", vec![2.0])], now);
+        assert_eq!(points.len(), PLAYGROUND_POINTS);
+        assert_eq!(points[0].text, "3");
+        assert_eq!(points.last().unwrap().text, "2");
+
+        // At capacity, the least recently used playground makes room
+        playgrounds.remember(
+            second,
+            vec![point("a", vec![1.0])],
+            now + Duration::from_secs(1),
+        );
+        playgrounds.remember(
+            third,
+            vec![point("b", vec![1.0])],
+            now + Duration::from_secs(2),
+        );
+        assert!(!playgrounds.playgrounds.contains_key(&first));
+        assert!(playgrounds.playgrounds.contains_key(&second));
+    }
+
+    #[test]
+    fn test_map_plots_every_point() {
+        let points = vec![
+            point("how to reset password", vec![1.0, 0.0, 0.0]),
+            point("I forgot my login", vec![0.9, 0.1, 0.0]),
+            point("pizza recipe <3", vec![0.0, 0.0, 1.0]),
+        ];
+        let markup = map(&points, 2).into_string();
+        assert_eq!(markup.matches("<circle").count(), 3);
+        assert_eq!(markup.matches("#2563eb").count(), 2);
+        assert!(markup.contains("pizza recipe &lt;3"));
+        assert!(markup.contains("hx-swap-oob"));
+
+        // Every point lands inside the plot
+        for coordinate in markup.split("cx=\"").skip(1) {
+            let x: f32 = coordinate.split('"').next().unwrap().parse().unwrap();
+            assert!((0.0..=MAP_WIDTH).contains(&x));
+        }
+
+        assert!(!map(&points[..1], 1).into_string().contains("<svg"));
+    }
+}
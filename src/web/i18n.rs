@@ -0,0 +1,437 @@
+//! Translations of the web UI
+//!
+//! Templates look strings up by key (`t("auth.sign_in")`). Each locale is a
+//! TOML file in `src/web/locales/` (`[section]` tables of `key = "text"`),
+//! compiled into the binary; a key missing from a locale falls back to
+//! English, then to the key itself.
+//!
+//! `locale_middleware` picks the request's locale from the `lang` cookie
+//! (set by `GET /lang/:locale`), else from `Accept-Language`, else English.
+
+use axum::{
+    extract::{Path, Query, Request},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Cookie holding the chosen locale
+pub const LOCALE_COOKIE: &str = "lang";
+
+/// Locales the web UI is translated to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::De];
+
+    /// Language code (`html lang`, cookie value, `/lang/:locale`)
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::De => "de",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Locale::ALL
+            .into_iter()
+            .find(|locale| locale.code().eq_ignore_ascii_case(code))
+    }
+
+    /// Name of the language in that language, for the language switcher
+    pub fn native_name(self) -> &'static str {
+        match self {
+            Locale::En => "English",
+            Locale::De => "Deutsch",
+        }
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            Locale::En => include_str!("locales/en.toml"),
+            Locale::De => include_str!("locales/de.toml"),
+        }
+    }
+}
+
+/// Parsed locale files: locale -> dotted key -> text
+static CATALOGS: Lazy<HashMap<Locale, HashMap<String, String>>> = Lazy::new(|| {
+    Locale::ALL
+        .into_iter()
+        .map(|locale| {
+            let catalog = parse(locale.source())
+                .unwrap_or_else(|e| panic!("locales/{}.toml: {}", locale.code(), e));
+            (locale, catalog)
+        })
+        .collect()
+});
+
+tokio::task_local! {
+    /// Locale of the request being rendered
+    static LOCALE: Locale;
+}
+
+/// Locale of the current request (English outside `locale_middleware`)
+pub fn current() -> Locale {
+    LOCALE.try_with(|locale| *locale).unwrap_or_default()
+}
+
+/// Text of `key` in the current locale
+pub fn t(key: &'static str) -> &'static str {
+    lookup(current(), key)
+}
+
+/// Text of `key` with its `{name}` placeholders filled in
+pub fn t_with(key: &'static str, args: &[(&str, &str)]) -> String {
+    args.iter().fold(t(key).to_string(), |text, (name, value)| {
+        text.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+fn lookup(locale: Locale, key: &'static str) -> &'static str {
+    let catalogs = Lazy::force(&CATALOGS);
+    [locale, Locale::En]
+        .iter()
+        .find_map(|locale| catalogs[locale].get(key))
+        .map(String::as_str)
+        .unwrap_or(key)
+}
+
+/// Locale from the `lang` cookie, else the best `Accept-Language` match
+pub fn negotiate(headers: &HeaderMap) -> Locale {
+    let jar = CookieJar::from_headers(headers);
+    if let Some(locale) = jar
+        .get(LOCALE_COOKIE)
+        .and_then(|cookie| Locale::from_code(cookie.value()))
+    {
+        return locale;
+    }
+
+    headers
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(from_accept_language)
+        .unwrap_or_default()
+}
+
+/// Highest weighted supported language of an `Accept-Language` header
+/// (`de-CH, fr;q=0.9, en;q=0.8`); region subtags are ignored
+fn from_accept_language(value: &str) -> Option<Locale> {
+    let mut ranges: Vec<(Locale, f32)> = value
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';');
+            let tag = parts.next()?.trim();
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())?;
+            let language = tag.split('-').next()?;
+            Some((Locale::from_code(language)?, quality))
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+
+    // Stable: equal weights keep the header's order
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranges.first().map(|(locale, _)| *locale)
+}
+
+/// Render the request in its negotiated locale
+pub async fn locale_middleware(request: Request, next: Next) -> Response {
+    let locale = negotiate(request.headers());
+    LOCALE.scope(locale, next.run(request)).await
+}
+
+/// Where to go after switching the language
+#[derive(Debug, Deserialize)]
+pub struct SwitchQuery {
+    pub next: Option<String>,
+}
+
+/// Switch the UI language: remember it in a cookie and go back to `next`
+/// (or the referring page)
+pub async fn switch_locale(
+    Path(code): Path<String>,
+    Query(query): Query<SwitchQuery>,
+    headers: HeaderMap,
+) -> Response {
+    let Some(locale) = Locale::from_code(&code) else {
+        return (StatusCode::NOT_FOUND, super::not_found().await).into_response();
+    };
+
+    let back = query
+        .next
+        .or_else(|| {
+            let referer = headers.get(header::REFERER)?.to_str().ok()?;
+            Some(referer_path(referer).to_string())
+        })
+        .map_or_else(
+            || "/".to_string(),
+            |next| super::auth::validate_redirect_url(&next),
+        );
+
+    let cookie = Cookie::build((LOCALE_COOKIE, locale.code()))
+        .path("/")
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::days(365))
+        .build();
+
+    let mut response = Redirect::to(&back).into_response();
+    response
+        .headers_mut()
+        .insert(header::SET_COOKIE, cookie.to_string().parse().unwrap());
+    response
+}
+
+/// Path (and query) of an absolute URL
+fn referer_path(url: &str) -> &str {
+    let after_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    after_scheme
+        .find('/')
+        .map_or("/", |start| &after_scheme[start..])
+}
+
+/// Parse the TOML subset the locale files use: `[section]` headers, `key =
+/// "text"` lines with `\"`, `\\`, `\n` and `\t` escapes, and `#` comments
+fn parse(source: &str) -> Result<HashMap<String, String>, String> {
+    let mut entries = HashMap::new();
+    let mut section = String::new();
+
+    for (number, line) in source.lines().enumerate() {
+        let line = line.trim();
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            section = name
+                .strip_suffix(']')
+                .filter(|name| is_bare_key(name))
+                .ok_or_else(|| error("invalid section header"))?
+                .to_string();
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected `key = \"text\"`"))?;
+        let key = key.trim();
+        if !is_bare_key(key) {
+            return Err(error("invalid key"));
+        }
+        let text = parse_string(value.trim()).ok_or_else(|| error("invalid string"))?;
+
+        let key = if section.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", section, key)
+        };
+        if entries.insert(key, text).is_some() {
+            return Err(error("duplicate key"));
+        }
+    }
+    Ok(entries)
+}
+
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// A basic string (`"..."`), optionally followed by a comment
+fn parse_string(value: &str) -> Option<String> {
+    let mut chars = value.strip_prefix('"')?.chars();
+    let mut text = String::new();
+
+    loop {
+        match chars.next()? {
+            '"' => break,
+            '\\' => text.push(match chars.next()? {
+                '"' => '"',
+                '\\' => '\\',
+                'n' => '\n',
+                't' => '\t',
+                _ => return None,
+            }),
+            c => text.push(c),
+        }
+    }
+
+    let rest = chars.as_str().trim();
+    (rest.is_empty() || rest.starts_with('#')).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::path::Path;
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), value.parse().unwrap()))
+            .collect()
+    }
+
+    /// Keys passed to `t` / `t_with` in the web templates
+    fn used_keys(dir: &Path, keys: &mut BTreeSet<String>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                used_keys(&path, keys);
+                continue;
+            }
+            if path.extension().is_none_or(|ext| ext != "rs") || path.ends_with("i18n.rs") {
+                continue;
+            }
+
+            let source = std::fs::read_to_string(&path).unwrap();
+            for call in ["t(\"", "t_with(\""] {
+                for (start, _) in source.match_indices(call) {
+                    let preceded_by_ident = source[..start]
+                        .chars()
+                        .next_back()
+                        .is_some_and(|c| c.is_alphanumeric() || c == '_');
+                    if preceded_by_ident {
+                        continue;
+                    }
+                    let rest = &source[start + call.len()..];
+                    let key = &rest[..rest.find('"').unwrap()];
+                    keys.insert(key.to_string());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_used_key_exists_in_every_locale() {
+        let mut keys = BTreeSet::new();
+        used_keys(
+            &Path::new(env!("CARGO_MANIFEST_DIR")).join("src/web"),
+            &mut keys,
+        );
+        // The scan itself works
+        assert!(keys.contains("layout.logout"), "{:?}", keys);
+
+        for locale in Locale::ALL {
+            let catalog = &CATALOGS[&locale];
+            let missing: Vec<_> = keys.iter().filter(|k| !catalog.contains_key(*k)).collect();
+            assert!(
+                missing.is_empty(),
+                "{}: missing {:?}",
+                locale.code(),
+                missing
+            );
+        }
+    }
+
+    #[test]
+    fn test_locales_have_the_same_keys() {
+        let english: BTreeSet<_> = CATALOGS[&Locale::En].keys().collect();
+        for locale in Locale::ALL {
+            let keys: BTreeSet<_> = CATALOGS[&locale].keys().collect();
+            assert_eq!(
+                english.difference(&keys).collect::<Vec<_>>(),
+                Vec::<&&String>::new(),
+                "missing from {}",
+                locale.code()
+            );
+            assert_eq!(
+                keys.difference(&english).collect::<Vec<_>>(),
+                Vec::<&&String>::new(),
+                "only in {}",
+                locale.code()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let catalog = parse(
+            "# comment\ntop = \"Top\"\n\n[auth]\nsign_in = \"Sign \\\"in\\\"\" # trailing\nlines = \"a\\nb\"\n",
+        )
+        .unwrap();
+        assert_eq!(catalog["top"], "Top");
+        assert_eq!(catalog["auth.sign_in"], "Sign \"in\"");
+        assert_eq!(catalog["auth.lines"], "a\nb");
+
+        assert!(parse("key = unquoted").is_err());
+        assert!(parse("key = \"open").is_err());
+        assert!(parse("[a]\nk = \"1\"\nk = \"2\"").is_err());
+        assert!(parse("[bad section\n").is_err());
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(&HeaderMap::new()), Locale::En);
+        assert_eq!(
+            negotiate(&headers(&[(header::ACCEPT_LANGUAGE, "de-CH, en;q=0.8")])),
+            Locale::De
+        );
+        assert_eq!(
+            negotiate(&headers(&[(
+                header::ACCEPT_LANGUAGE,
+                "fr, en;q=0.5, de;q=0.9"
+            )])),
+            Locale::De
+        );
+        assert_eq!(
+            negotiate(&headers(&[(header::ACCEPT_LANGUAGE, "fr, de;q=0")])),
+            Locale::En
+        );
+        // The cookie wins over the header; unknown cookie values are ignored
+        assert_eq!(
+            negotiate(&headers(&[
+                (header::ACCEPT_LANGUAGE, "de"),
+                (header::COOKIE, "session=x; lang=en"),
+            ])),
+            Locale::En
+        );
+        assert_eq!(
+            negotiate(&headers(&[
+                (header::ACCEPT_LANGUAGE, "de"),
+                (header::COOKIE, "lang=xx"),
+            ])),
+            Locale::De
+        );
+    }
+
+    #[tokio::test]
+    async fn test_t_in_locale_scope() {
+        assert_eq!(t("layout.logout"), "Logout");
+        LOCALE
+            .scope(Locale::De, async {
+                assert_eq!(current(), Locale::De);
+                assert_eq!(t("layout.logout"), "Abmelden");
+                assert_eq!(
+                    t_with("organizations.page_title", &[("org", "Acme")]),
+                    "Acme - Organisation"
+                );
+            })
+            .await;
+        assert_eq!(t("no.such.key"), "no.such.key");
+    }
+
+    #[test]
+    fn test_referer_path() {
+        assert_eq!(
+            referer_path("https://smally.dev/organizations?new=true"),
+            "/organizations?new=true"
+        );
+        assert_eq!(referer_path("https://smally.dev"), "/");
+        assert_eq!(referer_path("/billing"), "/billing");
+    }
+}
@@ -1,14 +1,28 @@
+pub mod abuse;
 pub mod api_keys;
 pub mod auth;
 pub mod components;
+#[cfg(feature = "inference")]
+pub mod demo;
+pub mod i18n;
 pub mod organizations;
+pub mod quota;
+pub mod share;
+pub mod statements;
 
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
 use maud::{html, Markup};
 
+use crate::services::ServiceError;
+use i18n::t;
+
 /// Home page - landing page with login button
 pub async fn home() -> Markup {
     components::layout::base(
-        "Smally - Fast Text Embeddings API",
+        t("home.page_title"),
         html! {
             div class="min-h-screen bg-gradient-to-br from-blue-50 to-indigo-100" {
                 // Navigation
@@ -23,12 +37,12 @@ pub async fn home() -> Markup {
                                 a
                                     href="/login"
                                     class="text-gray-700 hover:text-primary font-medium" {
-                                    "Sign in"
+                                    (t("home.sign_in"))
                                 }
                                 a
                                     href="/register"
                                     class="inline-flex items-center px-4 py-2 border border-transparent text-sm font-medium rounded-md shadow-sm text-white bg-primary hover:bg-blue-700" {
-                                    "Get Started"
+                                    (t("home.get_started"))
                                 }
                             }
                         }
@@ -38,41 +52,33 @@ pub async fn home() -> Markup {
                 // Hero section
                 div class="max-w-7xl mx-auto px-4 sm:px-6 lg:px-8 pt-20 pb-16 text-center" {
                     h1 class="text-5xl font-extrabold text-gray-900 sm:text-6xl md:text-7xl mb-8" {
-                        "Fast Text Embeddings API"
+                        (t("home.headline"))
                     }
                     p class="text-xl text-gray-600 max-w-3xl mx-auto mb-12" {
-                        "Production-ready embedding service powered by state-of-the-art BERT models. "
-                        "Generate high-quality vector representations for semantic search, RAG, and more."
+                        (t("home.tagline"))
                     }
                     div class="flex justify-center gap-4" {
                         a
                             href="/register"
                             class="inline-flex items-center px-8 py-3 border border-transparent text-base font-medium rounded-md shadow-sm text-white bg-primary hover:bg-blue-700" {
-                            "Start Free"
+                            (t("home.start_free"))
                         }
                         a
                             href="/docs"
                             class="inline-flex items-center px-8 py-3 border border-gray-300 text-base font-medium rounded-md text-gray-700 bg-white hover:bg-gray-50" {
-                            "Documentation"
+                            (t("home.documentation"))
                         }
                     }
                 }
 
+                (try_it())
+
                 // Features
                 div class="max-w-7xl mx-auto px-4 sm:px-6 lg:px-8 pb-20" {
                     div class="grid grid-cols-1 gap-8 sm:grid-cols-2 lg:grid-cols-3" {
-                        (feature_card(
-                            "⚡ Fast",
-                            "Optimized ONNX runtime with intelligent caching for blazing-fast embeddings"
-                        ))
-                        (feature_card(
-                            "🔒 Secure",
-                            "API key authentication with rate limiting and usage tracking"
-                        ))
-                        (feature_card(
-                            "📊 Scalable",
-                            "Built with Rust for high performance and reliable production deployments"
-                        ))
+                        (feature_card("⚡", t("home.fast"), t("home.fast_description")))
+                        (feature_card("🔒", t("home.secure"), t("home.secure_description")))
+                        (feature_card("📊", t("home.scalable"), t("home.scalable_description")))
                     }
                 }
             }
@@ -80,10 +86,22 @@ pub async fn home() -> Markup {
     )
 }
 
-fn feature_card(title: &str, description: &str) -> Markup {
+/// Demo box, when this process serves the model
+#[cfg(feature = "inference")]
+fn try_it() -> Markup {
+    demo::section()
+}
+
+#[cfg(not(feature = "inference"))]
+fn try_it() -> Markup {
+    html! {}
+}
+
+fn feature_card(icon: &str, title: &str, description: &str) -> Markup {
     html! {
         div class="bg-white rounded-lg shadow-md p-6" {
             h3 class="text-xl font-bold text-gray-900 mb-2" {
+                span aria-hidden="true" { (icon) " " }
                 (title)
             }
             p class="text-gray-600" {
@@ -96,17 +114,76 @@ fn feature_card(title: &str, description: &str) -> Markup {
 /// 404 Not Found page
 pub async fn not_found() -> Markup {
     components::layout::base(
-        "404 Not Found",
+        t("errors.not_found_title"),
         html! {
             div class="min-h-screen flex items-center justify-center bg-gray-50" {
                 div class="text-center" {
                     h1 class="text-6xl font-bold text-gray-900 mb-4" { "404" }
-                    p class="text-xl text-gray-600 mb-8" { "Page not found" }
+                    p class="text-xl text-gray-600 mb-8" { (t("errors.page_not_found")) }
                     a href="/" class="text-blue-600 hover:text-blue-800 underline" {
-                        "Go back home"
+                        (t("errors.go_home"))
                     }
                 }
             }
         },
     )
 }
+
+/// Page listing form validation errors (one alert per field message)
+pub(crate) fn form_errors_response(
+    errors: &validator::ValidationErrors,
+    back_href: &str,
+) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        components::layout::base(
+            t("errors.invalid_input"),
+            html! {
+                div class="min-h-screen flex items-center justify-center bg-gray-50" {
+                    div class="max-w-md w-full" {
+                        @for message in crate::models::validation_messages(errors) {
+                            (components::layout::alert(&message, "error"))
+                        }
+                        (components::layout::back_link(back_href, t("common.go_back")))
+                    }
+                }
+            },
+        ),
+    )
+        .into_response()
+}
+
+/// Page for a failed service call, with a link back to `back_href`
+pub(crate) fn service_error_response(error: ServiceError, back_href: &str) -> Response {
+    let (status, message) = match error {
+        ServiceError::Invalid(msg) | ServiceError::Conflict(msg) => (StatusCode::BAD_REQUEST, msg),
+        ServiceError::Forbidden(msg) | ServiceError::LimitReached(msg) => {
+            (StatusCode::FORBIDDEN, msg)
+        }
+        ServiceError::RateLimited(msg) => (StatusCode::TOO_MANY_REQUESTS, msg),
+        ServiceError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+        ServiceError::Internal(msg) => {
+            tracing::error!("{}", crate::redact::redact(&msg));
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                t("errors.internal").to_string(),
+            )
+        }
+    };
+
+    (
+        status,
+        components::layout::base(
+            t("errors.error_title"),
+            html! {
+                div class="min-h-screen flex items-center justify-center bg-gray-50" {
+                    div class="max-w-md w-full" {
+                        (components::layout::alert(&message, "error"))
+                        (components::layout::back_link(back_href, t("common.go_back")))
+                    }
+                }
+            },
+        ),
+    )
+        .into_response()
+}
@@ -5,16 +5,19 @@ use axum::{
 };
 use maud::{html, Markup};
 use serde::Deserialize;
+use validator::Validate;
 
 use crate::auth::session::{create_session_cookie, create_session_token_with_org, SessionCookie};
 use crate::database;
-use crate::models::{OrganizationRole, TierType};
+use crate::models::{CreateOrganizationRequest, OrganizationRole, TierType};
+use crate::residency;
+use crate::services::{self, organizations::Membership, ServiceError};
 use crate::uuid_dashless::DashlessUuid;
 use axum::extract::Path;
 use axum::http::header;
-use chrono::Utc;
 
 use super::components::layout;
+use super::i18n::{t, t_with};
 
 /// Query parameters for organizations list
 #[derive(Debug, Deserialize)]
@@ -22,19 +25,10 @@ pub struct OrganizationsQuery {
     pub new: Option<bool>,
 }
 
-/// Organization with user's role
-#[derive(Debug, sqlx::FromRow)]
-struct OrganizationWithRole {
-    id: uuid::Uuid,
-    name: String,
-    tier: TierType,
-    is_active: bool,
-    role: OrganizationRole,
-}
-
 /// Form data for creating organization
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Validate)]
 pub struct CreateOrganizationForm {
+    #[validate(custom(function = "crate::models::validate_org_name"))]
     pub name: String,
 }
 
@@ -46,74 +40,78 @@ pub async fn list(
     let pool = database::get_db();
     let user_id = session.user_id();
 
-    // Fetch organizations where user is a member
-    let organizations = sqlx::query_as::<_, OrganizationWithRole>(
-        r#"
-        SELECT o.id, o.name, o.tier, o.is_active, om.role
-        FROM organizations o
-        INNER JOIN organization_members om ON o.id = om.organization_id
-        WHERE om.user_id = $1
-        ORDER BY o.created_at DESC
-        "#,
-    )
-    .bind(user_id)
-    .fetch_all(pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to fetch organizations: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to fetch organizations",
-        )
-            .into_response()
-    })?;
+    let organizations = services::organizations::list_for_user(pool, user_id)
+        .await
+        .map_err(|e| super::service_error_response(e, "/"))?;
+
+    // Organization switcher: the session's active organization, then the rest
+    let current_org_id = session.current_org_id();
+    let switcher: Vec<(String, &str)> = organizations
+        .iter()
+        .map(|o| (o.id.simple().to_string(), o.name.as_str()))
+        .collect();
+    let current_org = organizations
+        .iter()
+        .zip(&switcher)
+        .find(|(o, _)| Some(o.id) == current_org_id)
+        .map(|(_, (id, name))| (id.as_str(), *name));
+    let other_orgs: Vec<(&str, &str)> = organizations
+        .iter()
+        .zip(&switcher)
+        .filter(|(o, _)| Some(o.id) != current_org_id)
+        .map(|(_, (id, name))| (id.as_str(), *name))
+        .collect();
 
     Ok(layout::base(
-        "Organizations",
+        t("organizations.title"),
         html! {
-            (layout::navbar(session.email(), None, &[]))
+            (layout::navbar(session.email(), current_org, &other_orgs))
             (layout::container(html! {
                 div class="space-y-6" {
                     // Header
                     div class="md:flex md:items-center md:justify-between" {
                         div class="flex-1 min-w-0" {
                             h1 class="text-3xl font-bold text-gray-900" {
-                                "Organizations"
+                                (t("organizations.title"))
                             }
                             p class="mt-2 text-sm text-gray-500" {
-                                "Manage your organizations and teams"
+                                (t("organizations.subtitle"))
                             }
                         }
                         div class="mt-4 flex md:mt-0 md:ml-4" {
                             button
-                                onclick="document.getElementById('create-org-modal').classList.remove('hidden')"
+                                type="button"
+                                data-modal-open="create-org-modal"
+                                aria-haspopup="dialog"
                                 class="inline-flex items-center px-4 py-2 border border-transparent shadow-sm text-sm font-medium rounded-md text-white bg-primary hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary" {
-                                svg class="mr-2 h-5 w-5" fill="none" stroke="currentColor" viewBox="0 0 24 24" {
+                                svg class="mr-2 h-5 w-5" fill="none" stroke="currentColor" viewBox="0 0 24 24" aria-hidden="true" {
                                     path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M12 4v16m8-8H4" {}
                                 }
-                                "New Organization"
+                                (t("organizations.new"))
                             }
                         }
                     }
 
                     // Organizations grid
                     @if organizations.is_empty() {
-                        (layout::card("No Organizations", html! {
+                        (layout::card(t("organizations.empty_title"), html! {
                             div class="text-center py-12" {
-                                svg class="mx-auto h-12 w-12 text-gray-400" fill="none" stroke="currentColor" viewBox="0 0 24 24" {
+                                svg class="mx-auto h-12 w-12 text-gray-400" fill="none" stroke="currentColor" viewBox="0 0 24 24" aria-hidden="true" {
                                     path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M19 21V5a2 2 0 00-2-2H7a2 2 0 00-2 2v16m14 0h2m-2 0h-5m-9 0H3m2 0h5M9 7h1m-1 4h1m4-4h1m-1 4h1m-5 10v-5a1 1 0 011-1h2a1 1 0 011 1v5m-4 0h4" {}
                                 }
                                 h3 class="mt-2 text-sm font-medium text-gray-900" {
-                                    "No organizations"
+                                    (t("organizations.empty_heading"))
                                 }
                                 p class="mt-1 text-sm text-gray-500" {
-                                    "Get started by creating a new organization."
+                                    (t("organizations.empty_hint"))
                                 }
                                 div class="mt-6" {
                                     button
-                                        onclick="document.getElementById('create-org-modal').classList.remove('hidden')"
+                                        type="button"
+                                        data-modal-open="create-org-modal"
+                                        aria-haspopup="dialog"
                                         class="inline-flex items-center px-4 py-2 border border-transparent shadow-sm text-sm font-medium rounded-md text-white bg-primary hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary" {
-                                        "Create Organization"
+                                        (t("organizations.create"))
                                     }
                                 }
                             }
@@ -134,18 +132,45 @@ pub async fn list(
     ))
 }
 
+/// Badge of a running trial with the days left (nothing without one)
+pub(super) fn trial_badge(org: &Membership) -> Markup {
+    let now = chrono::Utc::now();
+    let Some(trial) = org.active_trial(now) else {
+        return html! {};
+    };
+    let tier = match trial.tier {
+        TierType::Free => t("tier.free"),
+        TierType::Pro => t("tier.pro"),
+        TierType::Scale => t("tier.scale"),
+    };
+    let label = match trial.days_left(now) {
+        1 => t_with("tier.trial_last_day", &[("tier", tier)]),
+        days => t_with(
+            "tier.trial_days_left",
+            &[("tier", tier), ("days", &days.to_string())],
+        ),
+    };
+
+    html! {
+        span class="inline-flex items-center px-2.5 py-0.5 rounded-full text-xs font-medium bg-orange-100 text-orange-800"
+            title=(t_with("tier.trial_ends", &[("date", &trial.expires_at.format("%Y-%m-%d %H:%M UTC").to_string())])) {
+            (label)
+        }
+    }
+}
+
 /// Render an organization card
-fn organization_card(org: &OrganizationWithRole) -> Markup {
+fn organization_card(org: &Membership) -> Markup {
     let tier_badge = match org.tier {
-        TierType::Free => ("bg-gray-100 text-gray-800", "Free"),
-        TierType::Pro => ("bg-blue-100 text-blue-800", "Pro"),
-        TierType::Scale => ("bg-purple-100 text-purple-800", "Scale"),
+        TierType::Free => ("bg-gray-100 text-gray-800", t("tier.free")),
+        TierType::Pro => ("bg-blue-100 text-blue-800", t("tier.pro")),
+        TierType::Scale => ("bg-purple-100 text-purple-800", t("tier.scale")),
     };
 
     let role_badge = match org.role {
-        OrganizationRole::Owner => ("bg-yellow-100 text-yellow-800", "Owner"),
-        OrganizationRole::Admin => ("bg-green-100 text-green-800", "Admin"),
-        OrganizationRole::Member => ("bg-gray-100 text-gray-800", "Member"),
+        OrganizationRole::Owner => ("bg-yellow-100 text-yellow-800", t("role.owner")),
+        OrganizationRole::Admin => ("bg-green-100 text-green-800", t("role.admin")),
+        OrganizationRole::Member => ("bg-gray-100 text-gray-800", t("role.member")),
     };
 
     html! {
@@ -157,11 +182,11 @@ fn organization_card(org: &OrganizationWithRole) -> Markup {
                     }
                     @if org.is_active {
                         span class="inline-flex items-center px-2.5 py-0.5 rounded-full text-xs font-medium bg-green-100 text-green-800" {
-                            "Active"
+                            (t("organizations.active"))
                         }
                     } @else {
                         span class="inline-flex items-center px-2.5 py-0.5 rounded-full text-xs font-medium bg-red-100 text-red-800" {
-                            "Inactive"
+                            (t("organizations.inactive"))
                         }
                     }
                 }
@@ -169,6 +194,7 @@ fn organization_card(org: &OrganizationWithRole) -> Markup {
                     span class=(format!("inline-flex items-center px-2.5 py-0.5 rounded-full text-xs font-medium {}", tier_badge.0)) {
                         (tier_badge.1)
                     }
+                    (trial_badge(org))
                     span class=(format!("inline-flex items-center px-2.5 py-0.5 rounded-full text-xs font-medium {}", role_badge.0)) {
                         (role_badge.1)
                     }
@@ -176,8 +202,10 @@ fn organization_card(org: &OrganizationWithRole) -> Markup {
                 div class="mt-6" {
                     a
                         href=(format!("/organizations/{}", org.id.simple()))
-                        class="text-primary hover:text-blue-500 text-sm font-medium" {
-                        "View details →"
+                        class="text-primary hover:text-blue-500 text-sm font-medium"
+                        aria-label=(t_with("organizations.view_details_of", &[("org", &org.name)])) {
+                        (t("organizations.view_details")) " "
+                        span aria-hidden="true" { "→" }
                     }
                 }
             }
@@ -185,6 +213,87 @@ fn organization_card(org: &OrganizationWithRole) -> Markup {
     }
 }
 
+/// Members of an organization with their last activity (everyone's for
+/// owners and admins, only their own for members)
+pub async fn members(
+    session: SessionCookie,
+    Path(org_id): Path<DashlessUuid>,
+) -> Result<Markup, Response> {
+    let pool = database::get_db();
+    let org_id = org_id.into_inner();
+    let back = format!("/organizations/{}", org_id.simple());
+
+    let org = services::organizations::membership(pool, org_id, session.user_id())
+        .await
+        .map_err(|e| super::service_error_response(e, "/organizations"))?;
+    let members = services::activity::members(pool, org_id, session.user_id())
+        .await
+        .map_err(|e| super::service_error_response(e, &back))?;
+
+    let org_id_simple = org_id.simple().to_string();
+
+    Ok(layout::base(
+        &t_with("members.page_title", &[("org", &org.name)]),
+        html! {
+            (layout::navbar(session.email(), Some((org_id_simple.as_str(), org.name.as_str())), &[]))
+            (layout::container(html! {
+                nav class="mb-6" aria-label=(t("layout.breadcrumb")) {
+                    ol class="flex items-center space-x-2 text-sm" {
+                        li {
+                            a href="/organizations" class="text-gray-500 hover:text-gray-700" { (t("organizations.title")) }
+                        }
+                        li class="text-gray-400" aria-hidden="true" { "/" }
+                        li {
+                            a href=(back) class="text-gray-500 hover:text-gray-700" { (org.name) }
+                        }
+                        li class="text-gray-400" aria-hidden="true" { "/" }
+                        li class="text-gray-900 font-medium" aria-current="page" { (t("members.title")) }
+                    }
+                }
+
+                h1 class="text-2xl font-bold text-gray-900 mb-4" { (t("members.title")) }
+
+                div class="bg-white shadow overflow-hidden sm:rounded-lg" {
+                    table class="min-w-full divide-y divide-gray-200" {
+                        thead class="bg-gray-50" {
+                            tr {
+                                th class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("members.member")) }
+                                th class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("members.role")) }
+                                th class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("members.last_activity")) }
+                            }
+                        }
+                        tbody class="bg-white divide-y divide-gray-200" {
+                            @for member in &members {
+                                @let visible = org.can_manage() || member.user_id == session.user_id();
+                                tr {
+                                    td class="px-6 py-4 text-sm" {
+                                        div class="font-medium text-gray-900" { (member.name.as_deref().unwrap_or(&member.email)) }
+                                        div class="text-gray-500" { (member.email) }
+                                    }
+                                    td class="px-6 py-4 text-sm text-gray-500" {
+                                        (match member.role {
+                                            OrganizationRole::Owner => t("role.owner"),
+                                            OrganizationRole::Admin => t("role.admin"),
+                                            OrganizationRole::Member => t("role.member"),
+                                        })
+                                    }
+                                    td class="px-6 py-4 text-sm text-gray-500" {
+                                        @match (member.last_activity_at, visible) {
+                                            (Some(at), _) => (at.format("%Y-%m-%d %H:%M UTC")),
+                                            (None, true) => (t("members.no_activity")),
+                                            (None, false) => span title=(t("members.hidden_hint")) { (t("members.hidden")) },
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }))
+        },
+    ))
+}
+
 /// Create organization modal
 fn create_organization_modal(auto_open: bool) -> Markup {
     let modal_class = if auto_open {
@@ -197,13 +306,13 @@ fn create_organization_modal(auto_open: bool) -> Markup {
         div
             id="create-org-modal"
             class=(modal_class)
-            aria-labelledby="modal-title"
+            aria-labelledby="create-org-modal-title"
             role="dialog"
             aria-modal="true" {
             div class="flex items-end justify-center min-h-screen pt-4 px-4 pb-20 text-center sm:block sm:p-0" {
                 // Background overlay
                 div
-                    onclick="document.getElementById('create-org-modal').classList.add('hidden')"
+                    data-modal-close
                     class="fixed inset-0 bg-gray-500 bg-opacity-75 transition-opacity"
                     aria-hidden="true" {}
 
@@ -213,36 +322,37 @@ fn create_organization_modal(auto_open: bool) -> Markup {
                 div class="inline-block align-bottom bg-white rounded-lg px-4 pt-5 pb-4 text-left overflow-hidden shadow-xl transform transition-all sm:my-8 sm:align-middle sm:max-w-lg sm:w-full sm:p-6" {
                     div {
                         div class="mt-3 text-center sm:mt-0 sm:text-left" {
-                            h3 class="text-lg leading-6 font-medium text-gray-900" id="modal-title" {
-                                "Create New Organization"
+                            h3 class="text-lg leading-6 font-medium text-gray-900" id="create-org-modal-title" {
+                                (t("organizations.create_heading"))
                             }
                             div class="mt-4" {
                                 form action="/organizations" method="POST" {
                                     div class="space-y-4" {
                                         div {
                                             label for="name" class="block text-sm font-medium text-gray-700" {
-                                                "Organization Name"
+                                                (t("organizations.name"))
                                             }
                                             input
                                                 type="text"
                                                 name="name"
                                                 id="name"
                                                 required
+                                                maxlength=(crate::models::NAME_MAX_CHARS)
                                                 class="mt-1 block w-full border border-gray-300 rounded-md shadow-sm py-2 px-3 focus:outline-none focus:ring-primary focus:border-primary sm:text-sm"
-                                                placeholder="Acme Inc.";
+                                                placeholder=(t("organizations.name_placeholder"));
                                         }
                                     }
                                     div class="mt-5 sm:mt-6 sm:grid sm:grid-cols-2 sm:gap-3 sm:grid-flow-row-dense" {
                                         button
                                             type="submit"
                                             class="w-full inline-flex justify-center rounded-md border border-transparent shadow-sm px-4 py-2 bg-primary text-base font-medium text-white hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary sm:col-start-2 sm:text-sm" {
-                                            "Create"
+                                            (t("common.create"))
                                         }
                                         button
                                             type="button"
-                                            onclick="document.getElementById('create-org-modal').classList.add('hidden')"
+                                            data-modal-close
                                             class="mt-3 w-full inline-flex justify-center rounded-md border border-gray-300 shadow-sm px-4 py-2 bg-white text-base font-medium text-gray-700 hover:bg-gray-50 focus:outline-none focus:ring-2 focus:ring-offset-2 focus:ring-primary sm:mt-0 sm:col-start-1 sm:text-sm" {
-                                            "Cancel"
+                                            (t("common.cancel"))
                                         }
                                     }
                                 }
@@ -263,71 +373,47 @@ pub async fn create(
     let pool = database::get_db();
     let user_id = session.user_id();
 
-    // Generate organization ID on server (using v7 for time-ordered UUIDs)
-    let org_id = uuid::Uuid::now_v7();
-    let now = Utc::now().naive_utc();
+    form.validate()
+        .map_err(|e| super::form_errors_response(&e, "/organizations?new=true"))?;
 
-    // Create organization with generated ID
-    sqlx::query(
-        "INSERT INTO organizations (id, name, owner_id, tier, is_active, created_at, updated_at)
-         VALUES ($1, $2, $3, $4, $5, $6, $7)",
-    )
-    .bind(org_id)
-    .bind(&form.name)
-    .bind(user_id)
-    .bind(TierType::Free)
-    .bind(true)
-    .bind(now)
-    .bind(now)
-    .execute(pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Failed to create organization: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to create organization",
-        )
-            .into_response()
-    })?;
-
-    // Add user as owner
-    sqlx::query(
-        "INSERT INTO organization_members (organization_id, user_id, role, created_at)
-         VALUES ($1, $2, $3, $4)",
+    let org = services::organizations::create(
+        pool,
+        user_id,
+        &CreateOrganizationRequest {
+            name: form.name,
+            tier: None,
+        },
     )
-    .bind(org_id)
-    .bind(user_id)
-    .bind(OrganizationRole::Owner)
-    .bind(now)
-    .execute(pool)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to add organization member: {}", e);
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "Failed to add organization member",
-        )
-            .into_response()
-    })?;
+    .map_err(|e| super::service_error_response(e, "/organizations?new=true"))?;
+    let org_id = org.id;
 
     // Update user's last selected organization to the new one
-    sqlx::query("UPDATE users SET last_selected_org_id = $1 WHERE id = $2")
-        .bind(org_id)
-        .bind(user_id)
-        .execute(pool)
+    services::organizations::select(pool, user_id, org_id)
         .await
-        .map_err(|e| {
-            tracing::error!("Failed to update last_selected_org_id: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to update user preferences",
-            )
-                .into_response()
-        })?;
+        .map_err(|e| super::service_error_response(e, "/organizations"))?;
+
+    // Make the new organization the session's active organization
+    let token =
+        create_session_token_with_org(user_id, session.email(), Some(org_id), session.remaining())
+            .map_err(|e| {
+                tracing::error!("Failed to create session token: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to update session",
+                )
+                    .into_response()
+            })?;
+    let cookie = create_session_cookie(&token, session.remaining());
 
     // Redirect to the newly created organization page
     let redirect_url = format!("/organizations/{}", org_id.simple());
-    Ok(Redirect::to(&redirect_url).into_response())
+    let mut response = Redirect::to(&redirect_url).into_response();
+    response
+        .headers_mut()
+        .insert(header::SET_COOKIE, cookie.to_string().parse().unwrap());
+
+    Ok(response)
 }
 
 /// Switch organization context
@@ -339,68 +425,51 @@ pub async fn switch_org(
     let user_id = session.user_id();
     let org_id = org_id.into_inner();
 
-    // Verify user is a member of this organization
-    let is_member = sqlx::query_scalar::<_, bool>(
-        "SELECT EXISTS(SELECT 1 FROM organization_members WHERE organization_id = $1 AND user_id = $2)",
-    )
-    .bind(org_id)
-    .bind(user_id)
-    .fetch_one(pool)
-    .await
-    .map_err(|e| {
-        tracing::error!("Database error: {}", e);
-        (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
-    })?;
-
-    if !is_member {
-        return Err((
-            StatusCode::FORBIDDEN,
-            layout::base(
-                "Access Denied",
-                html! {
-                    div class="min-h-screen flex items-center justify-center bg-gray-50" {
-                        div class="max-w-md w-full" {
-                            (layout::alert("You don't have access to this organization", "error"))
-                            a href="/organizations" class="text-primary hover:text-blue-500" {
-                                "← Back to organizations"
+    // Verify membership and update user's last selected organization
+    match services::organizations::select(pool, user_id, org_id).await {
+        Ok(()) => {}
+        Err(ServiceError::Forbidden(_)) => {
+            return Err((
+                StatusCode::FORBIDDEN,
+                layout::base(
+                    t("organizations.access_denied_title"),
+                    html! {
+                        div class="min-h-screen flex items-center justify-center bg-gray-50" {
+                            div class="max-w-md w-full" {
+                                (layout::alert(t("organizations.access_denied"), "error"))
+                                (layout::back_link("/organizations", t("organizations.back")))
                             }
                         }
-                    }
-                },
-            ),
-        )
-            .into_response());
-    }
-
-    // Update user's last selected organization
-    sqlx::query("UPDATE users SET last_selected_org_id = $1 WHERE id = $2")
-        .bind(org_id)
-        .bind(user_id)
-        .execute(pool)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to update last_selected_org_id: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to update user preferences",
+                    },
+                ),
             )
-                .into_response()
-        })?;
+                .into_response());
+        }
+        Err(e) => return Err(super::service_error_response(e, "/organizations")),
+    }
+    services::activity::record(
+        pool,
+        org_id,
+        user_id,
+        services::activity::DASHBOARD_SWITCH,
+        None,
+    )
+    .await;
 
     // Create new session token with organization context
-    let token = create_session_token_with_org(user_id, session.email(), Some(org_id)).map_err(
-        |e| {
-            tracing::error!("Failed to create session token: {}", e);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Failed to update session",
-            )
-                .into_response()
-        },
-    )?;
+    let token =
+        create_session_token_with_org(user_id, session.email(), Some(org_id), session.remaining())
+            .map_err(|e| {
+                tracing::error!("Failed to create session token: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to update session",
+                )
+                    .into_response()
+            })?;
 
     // Create session cookie
-    let cookie = create_session_cookie(&token);
+    let cookie = create_session_cookie(&token, session.remaining());
 
     // Redirect to organizations with new session
     let mut response = Redirect::to("/organizations").into_response();
@@ -410,3 +479,117 @@ pub async fn switch_org(
 
     Ok(response)
 }
+
+/// Form data for pinning the organization to a region
+#[derive(Debug, Deserialize)]
+pub struct RegionForm {
+    pub region: String,
+}
+
+/// Data residency card of the organization page. Hidden on deployments
+/// without regions, unless the organization is pinned to one anyway.
+pub fn region_card(org: &Membership, regions: &[String]) -> Markup {
+    if regions.is_empty() && org.region == residency::ANY_REGION {
+        return html! {};
+    }
+    let org_id_simple = org.id.simple().to_string();
+    let region_label = |region: &str| {
+        if region == residency::ANY_REGION {
+            t("organizations.region_any").to_string()
+        } else {
+            region.to_string()
+        }
+    };
+
+    layout::card(
+        t("organizations.region"),
+        html! {
+            p class="text-sm text-gray-500 mb-4" { (t("organizations.region_hint")) }
+            @if org.can_manage() {
+                form method="POST" action=(format!("/organizations/{}/region", org_id_simple)) class="space-y-4" {
+                    div {
+                        label for="region" class="block text-sm font-medium text-gray-700" { (t("organizations.region")) }
+                        select id="region" name="region" aria-describedby="region-warning"
+                            class="mt-1 block w-full border border-gray-300 rounded-md shadow-sm py-2 px-3 sm:text-sm" {
+                            option value=(residency::ANY_REGION) selected[org.region == residency::ANY_REGION] {
+                                (region_label(residency::ANY_REGION))
+                            }
+                            @for region in regions {
+                                option value=(region) selected[&org.region == region] { (region) }
+                            }
+                        }
+                    }
+                    div id="region-warning" {
+                        (layout::alert(t("organizations.region_warning"), "warning"))
+                    }
+                    (layout::button(t("organizations.region_save"), "primary", ""))
+                }
+            } @else {
+                p class="text-sm text-gray-900" { (region_label(&org.region)) }
+            }
+        },
+    )
+}
+
+/// Pin the organization to a region (owners and admins only)
+pub async fn save_region(
+    session: SessionCookie,
+    Path(org_id): Path<DashlessUuid>,
+    Form(form): Form<RegionForm>,
+) -> Result<Response, Response> {
+    let org_id = org_id.into_inner();
+    let org_href = format!("/organizations/{}", org_id.simple());
+
+    services::organizations::set_region(
+        database::get_db(),
+        org_id,
+        session.user_id(),
+        &form.region,
+    )
+    .await
+    .map_err(|e| super::service_error_response(e, &org_href))?;
+
+    Ok(Redirect::to(&org_href).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn membership(region: &str, role: OrganizationRole) -> Membership {
+        Membership {
+            id: uuid::Uuid::now_v7(),
+            name: "Acme".to_string(),
+            tier: TierType::Free,
+            is_active: true,
+            cache_policy: Default::default(),
+            allowed_models: None,
+            default_model: None,
+            billing_anchor_day: None,
+            store_client_ip: true,
+            region: region.to_string(),
+            trial_tier: None,
+            trial_expires_at: None,
+            created_at: chrono::Utc::now(),
+            role,
+        }
+    }
+
+    #[test]
+    fn test_region_card() {
+        let regions = vec!["eu".to_string(), "us".to_string()];
+
+        let html = region_card(&membership("eu", OrganizationRole::Owner), &regions).into_string();
+        assert!(html.contains(r#"<option value="eu" selected>"#), "{}", html);
+        assert!(html.contains(r#"<option value="us">"#));
+        assert!(html.contains(r#"id="region-warning""#));
+
+        // Members see the region, not the form
+        let html = region_card(&membership("eu", OrganizationRole::Member), &regions).into_string();
+        assert!(!html.contains("<form"));
+
+        // Nothing to choose on a deployment without regions
+        let html = region_card(&membership("any", OrganizationRole::Owner), &[]).into_string();
+        assert_eq!(html, "");
+    }
+}
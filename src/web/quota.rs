@@ -0,0 +1,146 @@
+//! Remaining-quota widget of the navbar, loaded and refreshed by HTMX
+
+use axum::extract::Path;
+use dashmap::DashMap;
+use maud::{html, Markup};
+use once_cell::sync::Lazy;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::auth::session::SessionCookie;
+use crate::billing::{self, trial, WindowUsage};
+use crate::database;
+use crate::models::TierType;
+use crate::redact::redact;
+use crate::services;
+use crate::uuid_dashless::DashlessUuid;
+
+use super::i18n::{t, t_with};
+
+/// How long an organization's usage is reused; the navbar polls as often
+const QUOTA_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Quota usage per organization (`None`: no hard limit) and when it was read
+static QUOTA_CACHE: Lazy<DashMap<Uuid, (Option<WindowUsage>, Instant)>> = Lazy::new(DashMap::new);
+
+/// Requests used of the monthly quota in the current billing cycle, for the
+/// navbar. Empty for tiers without a hard limit, for non-members and when
+/// the usage can't be read.
+pub async fn fragment(session: Option<SessionCookie>, Path(org_id): Path<DashlessUuid>) -> Markup {
+    let org_id = org_id.into_inner();
+    let Some(session) = session else {
+        return html! {};
+    };
+    let Ok(org) =
+        services::organizations::membership(database::get_db(), org_id, session.user_id()).await
+    else {
+        return html! {};
+    };
+
+    if let Some(entry) = QUOTA_CACHE.get(&org_id) {
+        let (usage, read_at) = &*entry;
+        if read_at.elapsed() < QUOTA_CACHE_TTL {
+            return widget(usage.as_ref());
+        }
+    }
+
+    // Same tier and counter as the rate limiter: only the free tier has a
+    // hard monthly quota
+    let usage = match trial::effective_tier(org.tier, org.trial(), chrono::Utc::now()) {
+        TierType::Free => {
+            let quota = TierType::Free.limits().monthly_quota as i64;
+            match billing::quota_usage(org_id, quota).await {
+                Ok(usage) => Some(usage),
+                Err(e) => {
+                    tracing::warn!("Failed to read quota usage: {}", redact(&e.to_string()));
+                    return html! {};
+                }
+            }
+        }
+        TierType::Pro | TierType::Scale => None,
+    };
+
+    let markup = widget(usage.as_ref());
+    QUOTA_CACHE.insert(org_id, (usage, Instant::now()));
+    markup
+}
+
+/// Progress bar color: green, yellow from 75% and red from 90% used
+fn bar_color(percent: i64) -> &'static str {
+    match percent {
+        p if p >= 90 => "bg-red-500",
+        p if p >= 75 => "bg-yellow-500",
+        _ => "bg-green-500",
+    }
+}
+
+fn widget(usage: Option<&WindowUsage>) -> Markup {
+    let Some(usage) = usage else {
+        return html! {};
+    };
+    let percent = if usage.limit > 0 {
+        (usage.used * 100 / usage.limit).min(100)
+    } else {
+        100
+    };
+    let label = t_with(
+        "quota.used",
+        &[
+            ("used", &usage.used.to_string()),
+            ("limit", &usage.limit.to_string()),
+        ],
+    );
+
+    html! {
+        div class="w-40" title=(t_with("quota.resets", &[("date", &usage.reset_at.format("%Y-%m-%d %H:%M UTC").to_string())])) {
+            div class="flex justify-between text-xs text-gray-500 mb-1" {
+                span { (t("quota.label")) }
+                span { (label) }
+            }
+            div
+                class="h-2 w-full rounded-full bg-gray-200 overflow-hidden"
+                role="progressbar"
+                aria-label=(t("quota.label"))
+                aria-valuemin="0"
+                aria-valuemax=(usage.limit)
+                aria-valuenow=(usage.used.min(usage.limit))
+                aria-valuetext=(label) {
+                div class=(format!("h-2 {}", bar_color(percent))) style=(format!("width: {}%", percent)) {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::billing::LimitWindow;
+
+    fn usage(used: i64, limit: i64) -> WindowUsage {
+        WindowUsage {
+            window: LimitWindow::Month,
+            limit,
+            used,
+            remaining: (limit - used).max(0),
+            reset_at: "2025-04-01T00:00:00Z".parse().unwrap(),
+            cycle: None,
+        }
+    }
+
+    #[test]
+    fn test_widget() {
+        let html = widget(Some(&usage(800, 1000))).into_string();
+        assert!(html.contains("800 / 1000"), "{}", html);
+        assert!(html.contains("width: 80%"));
+        assert!(html.contains("bg-yellow-500"));
+        assert!(html.contains(r#"role="progressbar""#));
+
+        // Over the quota: the bar stays full
+        let html = widget(Some(&usage(1200, 1000))).into_string();
+        assert!(html.contains("width: 100%"));
+        assert!(html.contains("bg-red-500"));
+
+        // No hard limit: nothing shown
+        assert_eq!(widget(None).into_string(), "");
+    }
+}
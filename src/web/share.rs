@@ -0,0 +1,196 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use maud::{html, Markup};
+
+use crate::api::error::ApiError;
+use crate::api::share::{self, SharedEmbedding};
+use crate::api::Embedding;
+use crate::redact::redact;
+
+use super::components::layout;
+use super::i18n::{t, t_with};
+
+/// Dimensions shown before the full vector is expanded
+const PREVIEW_DIMENSIONS: usize = 8;
+
+/// Read-only page of a shared embedding (`GET /share/:token`, no login)
+pub async fn show(Path(token): Path<String>) -> Response {
+    match share::open(&token, "page").await {
+        Ok(shared) => (
+            StatusCode::OK,
+            share::noindex_headers(),
+            page(&shared, &token),
+        )
+            .into_response(),
+        Err(ApiError::NotFound(_)) => (
+            StatusCode::NOT_FOUND,
+            share::noindex_headers(),
+            unavailable(),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!(
+                "Failed to open shared embedding: {}",
+                redact(&format!("{:?}", e))
+            );
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
+
+fn page(shared: &SharedEmbedding, token: &str) -> Markup {
+    let embedding = &shared.embedding;
+    let full = serde_json::to_string(embedding).unwrap_or_default();
+    let head = Embedding::new(
+        embedding
+            .values
+            .iter()
+            .take(PREVIEW_DIMENSIONS)
+            .copied()
+            .collect(),
+        embedding.precision,
+    );
+    let mut preview = serde_json::to_string(&head).unwrap_or_default();
+    if embedding.values.len() > PREVIEW_DIMENSIONS {
+        preview.insert_str(preview.len() - 1, ",…");
+    }
+    let options = &shared.options;
+
+    layout::base(
+        t("share.page_title"),
+        html! {
+            (header())
+            (layout::container(html! {
+                h1 class="text-2xl font-bold text-gray-900 mb-2" { (t("share.title")) }
+                p class="text-sm text-gray-500 mb-6" {
+                    (t_with("share.shared_on", &[
+                        ("created", &shared.created_at.format("%Y-%m-%d %H:%M UTC").to_string()),
+                        ("expires", &shared.expires_at.format("%Y-%m-%d %H:%M UTC").to_string()),
+                    ]))
+                }
+
+                (layout::card(t("share.input"), html! {
+                    @match &shared.text {
+                        Some(text) => blockquote class="text-gray-900 whitespace-pre-wrap break-words" { (text) },
+                        None => p class="text-sm text-gray-500 italic" { (t("share.text_not_shared")) },
+                    }
+                }))
+
+                div class="mt-6" {
+                    (layout::card(t("share.result"), html! {
+                        dl class="grid grid-cols-2 gap-y-2 text-sm mb-4" {
+                            dt class="text-gray-500" { (t("share.model")) }
+                            dd class="text-gray-900 font-mono" { (shared.model) }
+                            dt class="text-gray-500" { (t("share.dimensions")) }
+                            dd class="text-gray-900" { (shared.dimensions) }
+                            dt class="text-gray-500" { (t("share.tokens")) }
+                            dd class="text-gray-900" { (shared.tokens) }
+                            dt class="text-gray-500" { (t("share.options")) }
+                            dd class="text-gray-900 font-mono" {
+                                "normalize=" (options.normalize)
+                                ", post_process=" (options.post_process)
+                                ", truncate=" (options.truncate)
+                                @if let Some(precision) = options.precision {
+                                    ", precision=" (precision)
+                                }
+                            }
+                        }
+                        p class="text-sm text-gray-500 mb-1" {
+                            (t_with("share.first_dimensions", &[("count", &head.values.len().to_string())]))
+                        }
+                        pre class="bg-gray-50 rounded p-3 text-xs overflow-x-auto" { (preview) }
+                        details class="mt-4" {
+                            summary class="cursor-pointer text-sm text-primary" { (t("share.full_vector")) }
+                            pre class="bg-gray-50 rounded p-3 mt-2 text-xs break-all whitespace-pre-wrap max-h-96 overflow-y-auto" { (full) }
+                        }
+                        p class="mt-4 text-sm" {
+                            a href=(format!("/v1/shared/{}", token)) class="text-primary hover:text-blue-500" {
+                                (t("share.download_json"))
+                            }
+                        }
+                    }))
+                }
+
+                div class="mt-8 bg-white shadow sm:rounded-lg p-6 text-center" {
+                    h2 class="text-lg font-medium text-gray-900 mb-2" { (t("share.try_it_title")) }
+                    p class="text-sm text-gray-500 mb-4" {
+                        (t_with("share.try_it_description", &[("model", &shared.model)]))
+                    }
+                    a
+                        href="/register"
+                        class="inline-flex items-center px-6 py-2 border border-transparent text-sm font-medium rounded-md shadow-sm text-white bg-primary hover:bg-blue-700" {
+                        (t("share.try_it"))
+                    }
+                }
+            }))
+        },
+    )
+}
+
+/// Page of an invalid, expired or revoked link
+fn unavailable() -> Markup {
+    layout::base(
+        t("share.page_title"),
+        html! {
+            (header())
+            div class="min-h-screen flex items-center justify-center bg-gray-50" {
+                div class="max-w-md w-full" {
+                    (layout::alert(t("share.unavailable"), "error"))
+                    (layout::back_link("/", t("errors.go_home")))
+                }
+            }
+        },
+    )
+}
+
+/// Logo header of the public pages
+fn header() -> Markup {
+    html! {
+        nav class="bg-white shadow-sm mb-8" {
+            div class="max-w-7xl mx-auto px-4 sm:px-6 lg:px-8" {
+                div class="flex items-center h-16" {
+                    a href="/" class="flex items-center gap-2" {
+                        (layout::logo())
+                        span class="text-2xl font-bold text-primary" { "Smally" }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::share::ShareOptions;
+
+    fn shared(text: Option<&str>) -> SharedEmbedding {
+        let now = chrono::Utc::now();
+        SharedEmbedding {
+            id: "0192".to_string(),
+            text: text.map(str::to_string),
+            embedding: Embedding::new((0..10).map(|i| i as f32 / 8.0).collect(), Some(2)),
+            dimensions: 10,
+            model: "all-MiniLM-L6-v2@2024-01".to_string(),
+            tokens: 3,
+            options: ShareOptions::default(),
+            created_at: now,
+            expires_at: now,
+        }
+    }
+
+    #[test]
+    fn test_page_shows_result_and_hides_unshared_text() {
+        let html = page(&shared(Some("<b>hello</b>")), "tok.sig").into_string();
+        assert!(html.contains("all-MiniLM-L6-v2@2024-01"));
+        assert!(html.contains("&lt;b&gt;hello&lt;/b&gt;"));
+        assert!(html.contains("[0.0,0.13,0.25,0.38,0.5,0.63,0.75,0.88,…]"));
+        assert!(html.contains("/v1/shared/tok.sig"));
+
+        let html = page(&shared(None), "tok.sig").into_string();
+        assert!(html.contains(t("share.text_not_shared")));
+    }
+}
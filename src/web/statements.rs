@@ -0,0 +1,491 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use maud::{html, Markup};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::auth::session::SessionCookie;
+use crate::billing::alerts::{self, AlertSettings};
+use crate::billing::statements::{self, Statement};
+use crate::database;
+use crate::models::{OrganizationRole, TierType};
+use crate::redact::redact;
+use crate::services::{self, ServiceError};
+use crate::uuid_dashless::DashlessUuid;
+
+use super::components::layout;
+use super::i18n::{t, t_with};
+
+/// Usage alerts form on the billing page
+#[derive(Debug, Deserialize)]
+pub struct UsageAlertsForm {
+    /// Checkbox: present when checked
+    pub enabled: Option<String>,
+    /// Comma-separated percentages
+    pub thresholds: String,
+    /// Comma- or newline-separated emails (empty = owners)
+    pub recipients: String,
+}
+
+/// Billing page: list of monthly statements and usage alert settings
+pub async fn list(
+    session: SessionCookie,
+    Path(org_id): Path<DashlessUuid>,
+) -> Result<Markup, Response> {
+    let org_id = org_id.into_inner();
+    let (org_name, role) = member_org(org_id, session.user_id()).await?;
+
+    let statements = statements::list_latest(database::get_db(), org_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to fetch statements: {}", redact(&e.to_string()));
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        })?;
+    let alert_settings = alerts::load_settings(database::get_db(), org_id)
+        .await
+        .map_err(|e| {
+            tracing::error!(
+                "Failed to fetch usage alert settings: {}",
+                redact(&e.to_string())
+            );
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        })?;
+    let can_edit_alerts = matches!(role, OrganizationRole::Owner | OrganizationRole::Admin);
+
+    let org_id_simple = org_id.simple().to_string();
+
+    Ok(layout::base(
+        &t_with("billing.page_title", &[("org", &org_name)]),
+        html! {
+            (layout::navbar(session.email(), Some((org_id_simple.as_str(), org_name.as_str())), &[]))
+            (layout::container(html! {
+                nav class="mb-6" aria-label=(t("layout.breadcrumb")) {
+                    ol class="flex items-center space-x-2 text-sm" {
+                        li {
+                            a href="/organizations" class="text-gray-500 hover:text-gray-700" { (t("organizations.title")) }
+                        }
+                        li class="text-gray-400" aria-hidden="true" { "/" }
+                        li {
+                            a href=(format!("/organizations/{}", org_id_simple)) class="text-gray-500 hover:text-gray-700" { (org_name) }
+                        }
+                        li class="text-gray-400" aria-hidden="true" { "/" }
+                        li class="text-gray-900 font-medium" aria-current="page" { (t("billing.title")) }
+                    }
+                }
+
+                h1 class="text-2xl font-bold text-gray-900 mb-4" { (t("billing.monthly_statements")) }
+
+                @if statements.is_empty() {
+                    (layout::card(t("billing.no_statements"), html! {
+                        p class="text-sm text-gray-500" {
+                            (t("billing.no_statements_hint"))
+                        }
+                    }))
+                } @else {
+                    div class="bg-white shadow overflow-hidden sm:rounded-lg" {
+                        table class="min-w-full divide-y divide-gray-200" {
+                            thead class="bg-gray-50" {
+                                tr {
+                                    th class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("billing.month")) }
+                                    th class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("billing.requests")) }
+                                    th class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("billing.tokens")) }
+                                    th class="px-6 py-3 text-left text-xs font-medium text-gray-500 uppercase tracking-wider" { (t("billing.cost")) }
+                                    th class="px-6 py-3" { span class="sr-only" { (t("api_keys.actions")) } }
+                                }
+                            }
+                            tbody class="bg-white divide-y divide-gray-200" {
+                                @for statement in &statements {
+                                    @let month = statements::format_month(statement.month);
+                                    tr {
+                                        td class="px-6 py-4 text-sm font-medium text-gray-900" {
+                                            (month)
+                                            @if statement.version > 1 {
+                                                span class="ml-2 text-xs text-gray-500" { (t_with("billing.version_short", &[("version", &statement.version.to_string())])) }
+                                            }
+                                        }
+                                        td class="px-6 py-4 text-sm text-gray-500" { (statement.total_requests) }
+                                        td class="px-6 py-4 text-sm text-gray-500" { (statement.total_tokens) }
+                                        td class="px-6 py-4 text-sm text-gray-500" { (format_cents(statement.cost_cents)) }
+                                        td class="px-6 py-4 text-right text-sm" {
+                                            a
+                                                href=(format!("/organizations/{}/billing/{}", org_id_simple, month))
+                                                class="text-primary hover:text-blue-500"
+                                                aria-label=(t_with("billing.view_statement", &[("month", &month)])) {
+                                                (t("billing.view"))
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                div class="mt-8" {
+                    (alerts_card(&org_id_simple, &alert_settings, can_edit_alerts))
+                }
+            }))
+        },
+    ))
+}
+
+fn alerts_card(org_id_simple: &str, settings: &AlertSettings, can_edit: bool) -> Markup {
+    let thresholds = settings
+        .thresholds
+        .iter()
+        .map(|t| t.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let recipients = settings.recipients.join("\n");
+
+    layout::card(
+        t("billing.usage_alerts"),
+        html! {
+            p class="text-sm text-gray-500 mb-4" {
+                (t("billing.usage_alerts_hint"))
+            }
+            @if can_edit {
+                form method="POST" action=(format!("/organizations/{}/billing/alerts", org_id_simple)) class="space-y-4" {
+                    div class="flex items-center" {
+                        input type="checkbox" id="enabled" name="enabled" value="on" checked[settings.enabled]
+                            class="h-4 w-4 text-primary border-gray-300 rounded";
+                        label for="enabled" class="ml-2 text-sm text-gray-700" { (t("billing.send_alerts")) }
+                    }
+                    div {
+                        label for="thresholds" class="block text-sm font-medium text-gray-700" { (t("billing.thresholds_percent")) }
+                        input type="text" id="thresholds" name="thresholds" value=(thresholds) placeholder="80, 100"
+                            class="mt-1 block w-full border border-gray-300 rounded-md shadow-sm py-2 px-3 sm:text-sm";
+                    }
+                    div {
+                        label for="recipients" class="block text-sm font-medium text-gray-700" { (t("billing.recipients")) }
+                        textarea id="recipients" name="recipients" rows="3" placeholder=(t("billing.recipients_placeholder"))
+                            class="mt-1 block w-full border border-gray-300 rounded-md shadow-sm py-2 px-3 sm:text-sm" { (recipients) }
+                    }
+                    (layout::button(t("billing.save_alerts"), "primary", ""))
+                }
+            } @else {
+                dl class="grid grid-cols-2 gap-y-2 text-sm" {
+                    dt class="text-gray-500" { (t("billing.status")) }
+                    dd class="text-gray-900" { @if settings.enabled { (t("billing.on")) } @else { (t("billing.off")) } }
+                    dt class="text-gray-500" { (t("billing.thresholds")) }
+                    dd class="text-gray-900" { (thresholds) "%" }
+                    dt class="text-gray-500" { (t("billing.recipients")) }
+                    dd class="text-gray-900" {
+                        @if settings.recipients.is_empty() { (t("billing.owners")) } @else { (settings.recipients.join(", ")) }
+                    }
+                }
+            }
+        },
+    )
+}
+
+/// Save the usage alert settings (owners and admins only)
+pub async fn save_alerts(
+    session: SessionCookie,
+    Path(org_id): Path<DashlessUuid>,
+    Form(form): Form<UsageAlertsForm>,
+) -> Result<Response, Response> {
+    let org_id = org_id.into_inner();
+    let billing_href = format!("/organizations/{}/billing", org_id.simple());
+    let (_org_name, role) = member_org(org_id, session.user_id()).await?;
+
+    if !matches!(role, OrganizationRole::Owner | OrganizationRole::Admin) {
+        return Err((StatusCode::FORBIDDEN, "Access denied").into_response());
+    }
+
+    let settings = alerts::parse_thresholds(&form.thresholds)
+        .and_then(|thresholds| {
+            Ok(AlertSettings {
+                enabled: form.enabled.is_some(),
+                thresholds,
+                recipients: alerts::parse_recipients(&form.recipients)?,
+            })
+        })
+        .map_err(|message| {
+            error_page(
+                StatusCode::BAD_REQUEST,
+                t("errors.invalid_input"),
+                &message,
+                &billing_href,
+            )
+        })?;
+
+    alerts::save_settings(database::get_db(), org_id, &settings)
+        .await
+        .map_err(|e| {
+            tracing::error!(
+                "Failed to save usage alert settings: {}",
+                redact(&e.to_string())
+            );
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        })?;
+
+    tracing::warn!(
+        target: "audit",
+        action = "usage_alert.settings_update",
+        org_id = %org_id,
+        user_id = %session.user_id(),
+        enabled = settings.enabled,
+        thresholds = ?settings.thresholds,
+        recipients = settings.recipients.len(),
+        "Usage alert settings updated"
+    );
+    services::activity::record(
+        database::get_db(),
+        org_id,
+        session.user_id(),
+        "usage_alert.settings_update",
+        None,
+    )
+    .await;
+
+    Ok(Redirect::to(&billing_href).into_response())
+}
+
+/// Print-friendly statement for one month
+pub async fn show(
+    session: SessionCookie,
+    Path((org_id, month)): Path<(DashlessUuid, String)>,
+) -> Result<Markup, Response> {
+    let org_id = org_id.into_inner();
+    let (org_name, _role) = member_org(org_id, session.user_id()).await?;
+
+    let statement = match statements::parse_month(&month) {
+        Some(month) => statements::find(database::get_db(), org_id, month, None)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to fetch statement: {}", redact(&e.to_string()));
+                (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+            })?,
+        None => None,
+    }
+    .ok_or_else(|| {
+        not_found(
+            t("billing.statement_not_found"),
+            &format!("/organizations/{}/billing", org_id.simple()),
+        )
+    })?;
+
+    Ok(layout::base(
+        &t_with(
+            "billing.statement_title",
+            &[("org", &org_name), ("month", &month)],
+        ),
+        statement_document(&org_name, &statement),
+    ))
+}
+
+fn statement_document(org_name: &str, statement: &Statement) -> Markup {
+    let billing_href = format!(
+        "/organizations/{}/billing",
+        statement.organization_id.simple()
+    );
+
+    html! {
+        div class="max-w-3xl mx-auto px-4 py-8 print:p-0 print:max-w-none" {
+            div class="flex items-center justify-between mb-6 print:hidden" {
+                (layout::back_link(&billing_href, t("billing.all_statements")))
+                button type="button" onclick="window.print()" class="px-4 py-2 text-sm font-medium rounded-md text-white bg-primary hover:bg-blue-700" {
+                    (t("billing.print"))
+                }
+            }
+
+            div class="bg-white shadow rounded-lg p-8 print:shadow-none print:rounded-none" {
+                div class="flex justify-between items-start border-b border-gray-200 pb-6 mb-6" {
+                    div {
+                        div class="flex items-center gap-2" {
+                            (layout::logo())
+                            span class="text-xl font-bold text-gray-900" { "Smally" }
+                        }
+                        p class="mt-4 text-sm text-gray-500" { (t("billing.statement_for")) }
+                        p class="text-lg font-medium text-gray-900" { (org_name) }
+                    }
+                    div class="text-right text-sm text-gray-500" {
+                        p class="text-2xl font-bold text-gray-900" { (statement.month.format("%B %Y")) }
+                        p { (t_with("billing.version", &[("version", &statement.version.to_string())])) }
+                        p { (t_with("billing.generated", &[("time", &statement.generated_at.format("%Y-%m-%d %H:%M UTC").to_string())])) }
+                        p class="font-mono text-xs mt-1" { (statement.id.simple()) }
+                    }
+                }
+
+                dl class="grid grid-cols-2 gap-x-8 gap-y-3 text-sm mb-8" {
+                    dt class="text-gray-500" { (t("billing.tier")) }
+                    dd class="text-gray-900 text-right" { (tier_label(statement.tier)) }
+                    dt class="text-gray-500" { (t("billing.monthly_quota")) }
+                    dd class="text-gray-900 text-right" { (t_with("billing.quota_requests", &[("count", &statement.monthly_quota.to_string())])) }
+                    dt class="text-gray-500" { (t("billing.max_tokens")) }
+                    dd class="text-gray-900 text-right" { (statement.max_tokens) }
+                    dt class="text-gray-500" { (t("billing.price_per_million")) }
+                    dd class="text-gray-900 text-right" { (format_cents(statement.price_per_million_tokens)) }
+                }
+
+                table class="min-w-full text-sm mb-8" {
+                    thead {
+                        tr class="border-b border-gray-200 text-left text-xs uppercase tracking-wider text-gray-500" {
+                            th class="py-2" { (t("billing.api_key")) }
+                            th class="py-2 text-right" { (t("billing.requests")) }
+                            th class="py-2 text-right" { (t("billing.tokens")) }
+                        }
+                    }
+                    tbody {
+                        @for key in statement.key_breakdown.iter() {
+                            tr class="border-b border-gray-100" {
+                                td class="py-2 text-gray-900" {
+                                    @match (&key.name, key.key_id) {
+                                        (Some(name), _) => (name),
+                                        (None, Some(key_id)) => span class="font-mono" { (key_id.simple()) },
+                                        (None, None) => span class="text-gray-500" { (t("billing.deleted_key")) },
+                                    }
+                                }
+                                td class="py-2 text-right text-gray-900" { (key.requests) }
+                                td class="py-2 text-right text-gray-900" { (key.tokens) }
+                            }
+                        }
+                        tr class="font-medium" {
+                            td class="py-2 text-gray-900" { (t("billing.total")) }
+                            td class="py-2 text-right text-gray-900" { (statement.total_requests) }
+                            td class="py-2 text-right text-gray-900" { (statement.total_tokens) }
+                        }
+                    }
+                }
+
+                div class="flex justify-end border-t border-gray-200 pt-4" {
+                    div class="text-right" {
+                        p class="text-sm text-gray-500" { (t("billing.amount_due")) }
+                        p class="text-2xl font-bold text-gray-900" { (format_cents(statement.cost_cents)) }
+                    }
+                }
+
+                @if let Some(reason) = &statement.reason {
+                    p class="mt-6 text-xs text-gray-500" { (t_with("billing.revised", &[("reason", reason)])) }
+                }
+            }
+        }
+    }
+}
+
+/// Organization name and the user's role, if the user is a member
+async fn member_org(org_id: Uuid, user_id: Uuid) -> Result<(String, OrganizationRole), Response> {
+    match services::organizations::membership(database::get_db(), org_id, user_id).await {
+        Ok(member) => Ok((member.name, member.role)),
+        Err(ServiceError::Forbidden(_)) => {
+            Err(not_found(t("organizations.not_found"), "/organizations"))
+        }
+        Err(e) => Err(super::service_error_response(e, "/organizations")),
+    }
+}
+
+fn not_found(message: &str, back_href: &str) -> Response {
+    error_page(
+        StatusCode::NOT_FOUND,
+        t("errors.not_found_title"),
+        message,
+        back_href,
+    )
+}
+
+fn error_page(status: StatusCode, title: &str, message: &str, back_href: &str) -> Response {
+    (
+        status,
+        layout::base(
+            title,
+            html! {
+                div class="min-h-screen flex items-center justify-center bg-gray-50" {
+                    div class="max-w-md w-full" {
+                        (layout::alert(message, "error"))
+                        (layout::back_link(back_href, t("common.go_back")))
+                    }
+                }
+            },
+        ),
+    )
+        .into_response()
+}
+
+fn tier_label(tier: TierType) -> &'static str {
+    match tier {
+        TierType::Free => t("tier.free"),
+        TierType::Pro => t("tier.pro"),
+        TierType::Scale => t("tier.scale"),
+    }
+}
+
+/// Format cents as dollars (e.g. `$12.34`)
+fn format_cents(cents: i64) -> String {
+    format!("${}.{:02}", cents / 100, cents % 100)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::billing::statements::KeyUsage;
+    use chrono::NaiveDate;
+    use sqlx::types::Json;
+
+    #[test]
+    fn test_format_cents() {
+        assert_eq!(format_cents(0), "$0.00");
+        assert_eq!(format_cents(5), "$0.05");
+        assert_eq!(format_cents(123456), "$1234.56");
+    }
+
+    #[test]
+    fn test_statement_document_renders_stored_numbers() {
+        let statement = Statement {
+            id: Uuid::now_v7(),
+            organization_id: Uuid::now_v7(),
+            month: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+            version: 2,
+            tier: TierType::Pro,
+            monthly_quota: 100000,
+            max_tokens: 128,
+            price_per_million_tokens: 500,
+            total_requests: 42,
+            total_tokens: 4200,
+            cost_cents: 3,
+            key_breakdown: Json(vec![KeyUsage {
+                key_id: None,
+                name: Some("Production".to_string()),
+                requests: 42,
+                tokens: 4200,
+            }]),
+            generated_at: NaiveDate::from_ymd_opt(2025, 2, 1)
+                .unwrap()
+                .and_hms_opt(0, 5, 0)
+                .unwrap()
+                .and_utc(),
+            generated_by: "admin".to_string(),
+            reason: Some("token recount".to_string()),
+        };
+
+        let html = statement_document("Acme", &statement).into_string();
+        assert!(html.contains("January 2025"));
+        assert!(html.contains("Version 2"));
+        assert!(html.contains("Production"));
+        assert!(html.contains("4200"));
+        assert!(html.contains("$0.03"));
+        assert!(html.contains("Revised: token recount"));
+        assert!(html.contains("print:hidden"));
+    }
+
+    #[test]
+    fn test_alerts_card_is_read_only_for_members() {
+        let settings = AlertSettings {
+            recipients: vec!["ops@example.com".to_string()],
+            ..AlertSettings::default()
+        };
+
+        let html = alerts_card("abc", &settings, true).into_string();
+        assert!(html.contains("/organizations/abc/billing/alerts"));
+        assert!(html.contains("80, 100"));
+        assert!(html.contains("ops@example.com"));
+
+        let html = alerts_card("abc", &settings, false).into_string();
+        assert!(!html.contains("<form"));
+        assert!(html.contains("80, 100%"));
+    }
+}